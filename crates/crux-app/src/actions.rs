@@ -1,6 +1,10 @@
 //! GPUI action definitions for keybinding dispatch.
+//!
+//! Keys are bound to these actions by the configured keymap; see
+//! [`crate::keys`].
 
-use gpui::actions;
+use crux_config::keymap::Command;
+use gpui::{actions, Action};
 
 actions!(
     crux,
@@ -30,3 +34,32 @@ actions!(
         NextPrompt,
     ]
 );
+
+/// The action a configured key binding dispatches for `command`.
+pub(crate) fn command_action(command: Command) -> Box<dyn Action> {
+    match command {
+        Command::NewTab => Box::new(NewTab),
+        Command::CloseTab => Box::new(CloseTab),
+        Command::ForceCloseTab => Box::new(ForceCloseTab),
+        Command::NextTab => Box::new(NextTab),
+        Command::PrevTab => Box::new(PrevTab),
+        Command::SelectTab(1) => Box::new(SelectTab1),
+        Command::SelectTab(2) => Box::new(SelectTab2),
+        Command::SelectTab(3) => Box::new(SelectTab3),
+        Command::SelectTab(4) => Box::new(SelectTab4),
+        Command::SelectTab(5) => Box::new(SelectTab5),
+        Command::SelectTab(6) => Box::new(SelectTab6),
+        Command::SelectTab(7) => Box::new(SelectTab7),
+        Command::SelectTab(8) => Box::new(SelectTab8),
+        Command::SelectTab(_) => Box::new(SelectTab9),
+        Command::SplitRight => Box::new(SplitRight),
+        Command::SplitDown => Box::new(SplitDown),
+        Command::WindowSplitRight => Box::new(WindowSplitRight),
+        Command::WindowSplitDown => Box::new(WindowSplitDown),
+        Command::FocusNextPane => Box::new(FocusNextPane),
+        Command::FocusPrevPane => Box::new(FocusPrevPane),
+        Command::ZoomPane => Box::new(ZoomPane),
+        Command::PrevPrompt => Box::new(PrevPrompt),
+        Command::NextPrompt => Box::new(NextPrompt),
+    }
+}
@@ -1,82 +1,54 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use gpui::*;
 use gpui_component::dock::{
-    DockArea, DockAreaState, DockItem, DockPlacement, PanelView, TabPanel, ToggleZoom,
+    DockArea, DockItem, DockPlacement, PanelView, StackPanel, TabPanel, ToggleZoom,
 };
 use gpui_component::Placement;
 
+use crux_config::keymap::KeyResolver;
 use crux_config::CruxConfig;
-use crux_protocol::{PaneEvent, PaneId};
+use crux_protocol::{PaneEvent, PaneId, TabId, WindowId};
 
 use crate::actions::*;
 use crate::dock::terminal_panel::CruxTerminalPanel;
+use crate::dock::tmux_panel::TmuxWindowPanel;
+use crate::workspace::Workspace;
 
 /// Maximum recursion depth for DockItem tree traversal.
 pub(crate) const MAX_DOCK_DEPTH: usize = 100;
 
-/// Top-level application view managing the DockArea with terminal panels.
+/// Root view of one window, managing the DockArea with terminal panels.
+///
+/// State shared between windows lives in the [`Workspace`] global.
 pub struct CruxApp {
+    /// IPC ID of the window this view is the root of.
+    pub(crate) window_id: WindowId,
     pub(crate) dock_area: Entity<DockArea>,
-    /// Kept for socket cleanup on drop.
-    _socket_path: Option<std::path::PathBuf>,
-    /// IPC server cancellation token for graceful shutdown.
-    ipc_cancel: Option<crux_ipc::CancellationToken>,
     pub(crate) pane_registry: HashMap<PaneId, Entity<CruxTerminalPanel>>,
-    next_pane_id: AtomicU64,
-    /// Buffer of pane lifecycle events for future consumers (IPC notifications, etc.).
-    pane_events: VecDeque<PaneEvent>,
+    /// In-flight `crux:pane/run-command` per pane. The PTY-side capture holds
+    /// the strong reference, so the entry goes stale once it finishes.
+    pub(crate) running_commands: HashMap<PaneId, std::sync::Weak<()>>,
     /// Tracks which pane was split from which parent pane.
     pub(crate) pane_parents: HashMap<PaneId, PaneId>,
-    /// Background MCP server process.
-    mcp_process: Option<std::process::Child>,
     /// Application configuration loaded from config file.
     pub(crate) config: CruxConfig,
     /// Cached active pane ID for O(1) lookup. Updated when focus changes.
     pub(crate) active_pane: Option<PaneId>,
+    /// This window's key binding state: pending chord and key mode.
+    pub(crate) keys: KeyResolver,
 }
 
 impl CruxApp {
-    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        // Load configuration early.
-        let config = CruxConfig::load().unwrap_or_else(|e| {
-            log::warn!("Failed to load config: {}, using defaults", e);
-            CruxConfig::default()
-        });
-
-        // Set CRUX_SOCKET env var BEFORE starting IPC server to ensure it's set
-        // before any async tasks are spawned.
-        let socket_path_for_env = crux_ipc::socket_path();
-        // SAFETY: Called during app initialization before any background threads are spawned.
-        // No concurrent readers of this environment variable exist at this point.
-        unsafe { std::env::set_var("CRUX_SOCKET", &socket_path_for_env) };
-
-        // Start IPC server.
-        let (socket_path, ipc_rx, ipc_cancel) = match crux_ipc::start_ipc() {
-            Ok((path, rx, cancel_token)) => {
-                log::info!("IPC server started at {}", path.display());
-                (Some(path), Some(rx), Some(cancel_token))
-            }
-            Err(e) => {
-                log::error!("Failed to start IPC server: {}", e);
-                (None, None, None)
-            }
-        };
-
-        // Spawn MCP server if IPC server started successfully.
-        let mcp_process = if let Some(socket_path) = &socket_path {
-            Self::spawn_mcp_server(socket_path)
-        } else {
-            None
-        };
+    pub fn new(window_id: WindowId, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let config = cx.global::<Workspace>().config().clone();
 
         let dock_area = cx.new(|cx| DockArea::new("crux-dock", Some(1), window, cx));
 
         // Create the initial terminal panel and register it.
         let mut pane_registry = HashMap::new();
-        let pane_id = PaneId(0);
+        let pane_id = Workspace::allocate_pane_id(cx);
         let weak_dock = dock_area.downgrade();
 
         // Ensure CRUX_SOCKET is passed via env HashMap.
@@ -98,6 +70,7 @@ impl CruxApp {
                 cx,
             )
         });
+        Self::watch_tmux(pane_id, &initial_tab, cx);
         pane_registry.insert(pane_id, initial_tab.clone());
 
         let dock_item = DockItem::tab(initial_tab, &weak_dock, window, cx);
@@ -105,85 +78,26 @@ impl CruxApp {
             area.set_center(dock_item, window, cx);
         });
 
-        // Move IPC command processing to an async task instead of polling in render().
-        if let Some(mut ipc_cmd_rx) = ipc_rx {
-            cx.spawn_in(window, async move |this: WeakEntity<Self>, cx| {
-                while let Some(cmd) = ipc_cmd_rx.recv().await {
-                    let result = cx.update(|window, app| {
-                        if let Some(entity) = this.upgrade() {
-                            entity.update(app, |self_, ctx| {
-                                self_.handle_ipc_command(cmd, window, ctx);
-                                ctx.notify();
-                            });
-                        }
-                    });
-                    if result.is_err() {
-                        break; // Window or entity dropped
-                    }
-                }
-            })
-            .detach();
-        }
+        // Commands without an explicit window go to the one focused last.
+        cx.observe_window_activation(window, move |_, window, cx| {
+            if window.is_window_active() {
+                cx.global_mut::<Workspace>().set_focused_window(window_id);
+            }
+        })
+        .detach();
 
         Self {
+            window_id,
             dock_area,
-            _socket_path: socket_path,
-            ipc_cancel,
             pane_registry,
-            next_pane_id: AtomicU64::new(1),
-            pane_events: VecDeque::new(),
+            running_commands: HashMap::new(),
             pane_parents: HashMap::new(),
-            mcp_process,
+            keys: KeyResolver::new(crate::keys::keymap(&config)),
             config,
             active_pane: Some(pane_id),
         }
     }
 
-    /// Attempt to spawn the crux-mcp binary next to the current executable.
-    fn spawn_mcp_server(socket_path: &std::path::Path) -> Option<std::process::Child> {
-        // Find the crux-mcp binary next to the current executable.
-        let exe_path = match std::env::current_exe() {
-            Ok(path) => path,
-            Err(e) => {
-                log::warn!("Failed to get current executable path: {}", e);
-                return None;
-            }
-        };
-
-        let mcp_binary = exe_path.parent()?.join("crux-mcp");
-        if !mcp_binary.exists() {
-            log::info!(
-                "MCP server binary not found at {}, skipping auto-launch",
-                mcp_binary.display()
-            );
-            return None;
-        }
-
-        // Spawn crux-mcp with --http mode (stdio doesn't work for child processes).
-        match std::process::Command::new(&mcp_binary)
-            .arg("--http")
-            .arg("--socket")
-            .arg(socket_path)
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::inherit())
-            .spawn()
-        {
-            Ok(child) => {
-                log::info!(
-                    "MCP server spawned at {} (PID {})",
-                    mcp_binary.display(),
-                    child.id()
-                );
-                Some(child)
-            }
-            Err(e) => {
-                log::warn!("Failed to spawn MCP server: {}", e);
-                None
-            }
-        }
-    }
-
     // -- Helpers --------------------------------------------------------
 
     /// Get a reference to the application configuration.
@@ -214,8 +128,14 @@ impl CruxApp {
                 .or_insert(socket_path);
         }
 
-        cx.new(|cx| {
-            CruxTerminalPanel::new(
+        let launch = crux_protocol::LayoutPane {
+            cwd: cwd.map(String::from),
+            command: command.map(<[String]>::to_vec),
+            env: env.cloned(),
+            ..Default::default()
+        };
+        let panel = cx.new(|cx| {
+            let mut panel = CruxTerminalPanel::new(
                 pane_id,
                 cwd,
                 command,
@@ -225,8 +145,12 @@ impl CruxApp {
                 self.config.terminal.clone(),
                 window,
                 cx,
-            )
-        })
+            );
+            panel.set_launch(launch);
+            panel
+        });
+        Self::watch_tmux(pane_id, &panel, cx);
+        panel
     }
 
     /// Collect all TabPanel entities from the DockItem tree in depth-first order.
@@ -278,27 +202,120 @@ impl CruxApp {
         tab_panels.into_iter().next()
     }
 
+    /// Collect the window's TabPanels by walking the live panel tree.
+    ///
+    /// Unlike [`Self::collect_tab_panels`], this sees tab panels created by
+    /// splits after the DockItem tree was last set.
+    pub(crate) fn tab_panels(&self, cx: &App) -> Vec<Entity<TabPanel>> {
+        let root = self.dock_area.read(cx).items().view();
+        let mut result = Vec::new();
+        Self::tab_panels_recursive(root.view(), &mut result, cx, 0);
+        result
+    }
+
+    fn tab_panels_recursive(
+        view: AnyView,
+        out: &mut Vec<Entity<TabPanel>>,
+        cx: &App,
+        depth: usize,
+    ) {
+        if depth > MAX_DOCK_DEPTH {
+            log::warn!(
+                "tab_panels_recursive: max depth {} exceeded, stopping recursion",
+                MAX_DOCK_DEPTH
+            );
+            return;
+        }
+        match view.downcast::<StackPanel>() {
+            Ok(stack) => {
+                for child in stack.read(cx).panels() {
+                    Self::tab_panels_recursive(child.view(), out, cx, depth + 1);
+                }
+            }
+            Err(view) => {
+                if let Ok(tab_panel) = view.downcast::<TabPanel>() {
+                    out.push(tab_panel);
+                }
+            }
+        }
+    }
+
+    /// IPC ID of a tab panel.
+    pub(crate) fn tab_id(tab_panel: &Entity<TabPanel>) -> TabId {
+        TabId(tab_panel.entity_id().as_u64())
+    }
+
+    /// Panes shown in a tab panel, in tab order.
+    pub(crate) fn tab_pane_ids(tab_panel: &Entity<TabPanel>, cx: &App) -> Vec<PaneId> {
+        tab_panel
+            .read(cx)
+            .panels()
+            .iter()
+            .flat_map(|panel| Self::panel_pane_ids(panel, cx))
+            .collect()
+    }
+
+    /// Panes shown by one tab: a terminal, or every pane of a tmux window.
+    pub(crate) fn panel_pane_ids(panel: &Arc<dyn PanelView>, cx: &App) -> Vec<PaneId> {
+        let view = panel.view();
+        match view.downcast::<CruxTerminalPanel>() {
+            Ok(terminal) => vec![terminal.read(cx).pane_id()],
+            Err(view) => view
+                .downcast::<TmuxWindowPanel>()
+                .map(|tmux| tmux.read(cx).pane_ids(cx))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Find a tab panel of this window by its IPC ID.
+    pub(crate) fn tab_panel_by_id(&self, tab_id: TabId, cx: &App) -> Option<Entity<TabPanel>> {
+        self.tab_panels(cx)
+            .into_iter()
+            .find(|tp| Self::tab_id(tp) == tab_id)
+    }
+
+    /// IPC ID of the tab panel showing `pane_id`.
+    pub(crate) fn tab_id_for_pane(&self, pane_id: PaneId, cx: &App) -> Option<TabId> {
+        self.tab_panels(cx)
+            .iter()
+            .find(|tp| Self::tab_pane_ids(tp, cx).contains(&pane_id))
+            .map(Self::tab_id)
+    }
+
     // -- Action handlers ------------------------------------------------
 
     fn action_new_tab(&mut self, _: &NewTab, window: &mut Window, cx: &mut Context<Self>) {
-        let pane_id = self.allocate_pane_id();
-        if let Some(tab_panel) = self.focused_tab_panel(window, cx) {
-            let panel = self.create_terminal_panel(pane_id, None, None, None, window, cx);
-            self.pane_registry.insert(pane_id, panel.clone());
-            let panel_view: Arc<dyn PanelView> = Arc::new(panel);
+        let tab_panel = self.focused_tab_panel(window, cx);
+        self.open_tab(tab_panel, None, None, None, window, cx);
+    }
+
+    /// Open a new terminal pane as a tab of `tab_panel`, or in the dock
+    /// center when there is none. Returns the new pane's ID.
+    pub(crate) fn open_tab(
+        &mut self,
+        tab_panel: Option<Entity<TabPanel>>,
+        cwd: Option<&str>,
+        command: Option<&[String]>,
+        env: Option<&HashMap<String, String>>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> PaneId {
+        let pane_id = Workspace::allocate_pane_id(cx);
+        let panel = self.create_terminal_panel(pane_id, cwd, command, env, window, cx);
+        self.pane_registry.insert(pane_id, panel.clone());
+        let panel_view: Arc<dyn PanelView> = Arc::new(panel);
+        if let Some(tab_panel) = tab_panel {
             tab_panel.update(cx, |tp, cx| {
                 tp.add_panel(panel_view, window, cx);
             });
         } else {
-            let panel = self.create_terminal_panel(pane_id, None, None, None, window, cx);
-            self.pane_registry.insert(pane_id, panel.clone());
-            let panel_view: Arc<dyn PanelView> = Arc::new(panel);
             self.dock_area.update(cx, |area, cx| {
                 area.add_panel(panel_view, DockPlacement::Center, None, window, cx);
             });
         }
         self.active_pane = Some(pane_id);
-        self.emit_pane_event(PaneEvent::Created { pane_id });
+        Workspace::emit_pane_event(PaneEvent::Created { pane_id }, cx);
+        pane_id
     }
 
     fn action_close_tab(&mut self, _: &CloseTab, window: &mut Window, cx: &mut Context<Self>) {
@@ -318,6 +335,11 @@ impl CruxApp {
         let Some(tab_panel) = self.focused_tab_panel(window, cx) else {
             return;
         };
+        let active = tab_panel.read(cx).active_panel(cx);
+        if active.is_some_and(|panel| panel.view().downcast::<TmuxWindowPanel>().is_ok()) {
+            log::warn!("A tmux window is closed from tmux, e.g. with `kill-window`");
+            return;
+        }
 
         let closing_pane_id = self.active_pane_id(window, cx);
 
@@ -348,7 +370,7 @@ impl CruxApp {
                 self.active_pane = None;
             }
             self.pane_parents.remove(&pane_id);
-            self.emit_pane_event(PaneEvent::Closed { pane_id });
+            Workspace::emit_pane_event(PaneEvent::Closed { pane_id }, cx);
         }
     }
 
@@ -428,7 +450,7 @@ impl CruxApp {
     }
 
     fn window_split(&mut self, placement: Placement, window: &mut Window, cx: &mut Context<Self>) {
-        let pane_id = self.allocate_pane_id();
+        let pane_id = Workspace::allocate_pane_id(cx);
         let panel = self.create_terminal_panel(pane_id, None, None, None, window, cx);
         self.pane_registry.insert(pane_id, panel.clone());
 
@@ -446,7 +468,7 @@ impl CruxApp {
         });
 
         self.active_pane = Some(pane_id);
-        self.emit_pane_event(PaneEvent::Created { pane_id });
+        Workspace::emit_pane_event(PaneEvent::Created { pane_id }, cx);
     }
 
     fn split_pane(&mut self, placement: Placement, window: &mut Window, cx: &mut Context<Self>) {
@@ -457,7 +479,7 @@ impl CruxApp {
         // Record the parent (the currently active pane being split from).
         let parent_pane_id = self.active_pane_id(window, cx);
 
-        let pane_id = self.allocate_pane_id();
+        let pane_id = Workspace::allocate_pane_id(cx);
         let panel = self.create_terminal_panel(pane_id, None, None, None, window, cx);
         self.pane_registry.insert(pane_id, panel.clone());
         let panel_view: Arc<dyn PanelView> = Arc::new(panel);
@@ -470,7 +492,7 @@ impl CruxApp {
             self.pane_parents.insert(pane_id, parent_id);
         }
         self.active_pane = Some(pane_id);
-        self.emit_pane_event(PaneEvent::Created { pane_id });
+        Workspace::emit_pane_event(PaneEvent::Created { pane_id }, cx);
     }
 
     fn action_zoom_pane(&mut self, _: &ZoomPane, window: &mut Window, cx: &mut Context<Self>) {
@@ -552,36 +574,12 @@ impl CruxApp {
         // Update cached active pane and emit focused event.
         if let Some(pane_id) = self.active_pane_id(window, cx) {
             self.active_pane = Some(pane_id);
-            self.emit_pane_event(PaneEvent::Focused { pane_id });
+            Workspace::emit_pane_event(PaneEvent::Focused { pane_id }, cx);
         }
     }
 
     // -- IPC integration ---------------------------------------------------
 
-    /// Allocate the next pane ID (atomic, safe for future non-&mut-self use).
-    pub(crate) fn allocate_pane_id(&self) -> PaneId {
-        let id = self.next_pane_id.fetch_add(1, Ordering::Relaxed);
-        PaneId(id)
-    }
-
-    /// Push a pane lifecycle event into the buffer.
-    pub(crate) fn emit_pane_event(&mut self, event: PaneEvent) {
-        const MAX_PANE_EVENTS: usize = 10_000;
-        if self.pane_events.len() >= MAX_PANE_EVENTS {
-            log::warn!(
-                "pane event buffer full ({}), dropping oldest event",
-                MAX_PANE_EVENTS
-            );
-            self.pane_events.pop_front();
-        }
-        self.pane_events.push_back(event);
-    }
-
-    /// Drain all buffered pane events for consumption.
-    pub(crate) fn drain_pane_events(&mut self) -> Vec<PaneEvent> {
-        self.pane_events.drain(..).collect()
-    }
-
     /// Get the parent pane that a given pane was split from.
     // TODO: Used by future pane tree navigation (Phase 2 split pane features)
     #[allow(dead_code)]
@@ -609,33 +607,23 @@ impl CruxApp {
 
     // -- Session save/load -------------------------------------------------
 
-    /// Default session file path: `~/.config/crux/session.json`.
-    fn default_session_path() -> std::path::PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        std::path::PathBuf::from(home)
-            .join(".config")
-            .join("crux")
-            .join("session.json")
-    }
-
-    /// Save the current DockArea layout to a JSON file.
+    /// Save every pane's layout, launch settings, size and scrollback to a
+    /// versioned session file.
     pub(crate) fn handle_session_save(
         &self,
-        path: Option<String>,
+        params: crux_protocol::SessionSaveParams,
         cx: &App,
     ) -> anyhow::Result<crux_protocol::SessionSaveResult> {
-        let file_path = match path {
+        let file_path = match params.path {
             Some(p) => std::path::Path::new(&p).to_path_buf(),
-            None => Self::default_session_path(),
+            None => crux_config::session::default_path(),
         };
-
-        let state = self.dock_area.read(cx).dump(cx);
-        let json = serde_json::to_string_pretty(&state)?;
-
-        if let Some(parent) = file_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        std::fs::write(&file_path, json)?;
+        let history = params
+            .scrollback_lines
+            .unwrap_or(crux_protocol::session::DEFAULT_SCROLLBACK_LINES);
+        let session = self.session_snapshot(history, params.compress.unwrap_or(true), cx)?;
+        let json = serde_json::to_string_pretty(&session)?;
+        crux_config::session::write(&file_path, &json, 0)?;
 
         log::info!("Session saved to {}", file_path.display());
         Ok(crux_protocol::SessionSaveResult {
@@ -643,7 +631,47 @@ impl CruxApp {
         })
     }
 
-    /// Load a DockArea layout from a JSON file and reconstruct panels.
+    /// This window as a session: the layout with each pane's launch settings,
+    /// plus its size and up to `history` lines of scrollback.
+    pub(crate) fn session_snapshot(
+        &self,
+        history: u32,
+        compress: bool,
+        cx: &App,
+    ) -> anyhow::Result<crux_protocol::Session> {
+        let mut layout = self.layout_tree(cx)?;
+        let mut panes = Vec::new();
+        for spec in layout.panes_mut() {
+            let Some((pane_id, panel)) = spec
+                .pane_id
+                .and_then(|id| self.pane_registry.get(&id).map(|panel| (id, panel)))
+            else {
+                continue;
+            };
+            let panel = panel.read(cx);
+            // Titles set by the program come back on their own.
+            spec.title = panel.fixed_title().map(|t| t.to_string());
+
+            let view = panel.terminal_view().read(cx);
+            let size = view.terminal_size();
+            panes.push(crux_protocol::SessionPane {
+                pane_id,
+                size: Some(crux_protocol::PaneSize {
+                    rows: size.rows as u32,
+                    cols: size.cols as u32,
+                }),
+                scrollback: (history > 0)
+                    .then(|| view.scrollback_snapshot(history as usize, compress)),
+            });
+        }
+        Ok(crux_protocol::Session::new(layout, panes))
+    }
+
+    /// Replace this window's panes with those of a session file.
+    ///
+    /// Files written before the versioned format are migrated; files that do
+    /// not parse, or come from a newer version, are rejected before any pane
+    /// is touched.
     pub(crate) fn handle_session_load(
         &mut self,
         path: Option<String>,
@@ -652,29 +680,12 @@ impl CruxApp {
     ) -> anyhow::Result<crux_protocol::SessionLoadResult> {
         let file_path = match path {
             Some(p) => std::path::Path::new(&p).to_path_buf(),
-            None => Self::default_session_path(),
+            None => crux_config::session::default_path(),
         };
 
         let json = std::fs::read_to_string(&file_path)?;
-        let state: DockAreaState = serde_json::from_str(&json)?;
-
-        self.dock_area
-            .update(cx, |area, cx| area.load(state, window, cx))?;
-
-        // Re-populate pane_registry by walking the new DockItem tree.
-        self.pane_registry.clear();
-        self.pane_parents.clear();
-        let items = self.dock_area.read(cx).items().clone();
-        self.collect_panes_from_dock_item(&items, cx);
-
-        // Set next_pane_id to max+1.
-        let max_id = self.pane_registry.keys().map(|id| id.0).max().unwrap_or(0);
-        self.next_pane_id.store(max_id + 1, Ordering::Relaxed);
-
-        // Reset active_pane cache after session load.
-        self.active_pane = None;
-
-        let pane_count = self.pane_registry.len() as u32;
+        let session = crux_protocol::Session::from_json(&json)?;
+        let pane_count = self.restore_session(&session, window, cx)?;
         log::info!(
             "Session loaded from {} ({} panes)",
             file_path.display(),
@@ -683,42 +694,31 @@ impl CruxApp {
         Ok(crux_protocol::SessionLoadResult { pane_count })
     }
 
-    /// Walk the DockItem tree and collect all CruxTerminalPanel entities
-    /// into the pane_registry.
-    fn collect_panes_from_dock_item(&mut self, item: &DockItem, cx: &App) {
-        self.collect_panes_from_dock_item_recursive(item, cx, 0);
-    }
-
-    fn collect_panes_from_dock_item_recursive(&mut self, item: &DockItem, cx: &App, depth: usize) {
-        if depth > MAX_DOCK_DEPTH {
-            log::warn!(
-                "collect_panes_from_dock_item: max depth {} exceeded, stopping recursion",
-                MAX_DOCK_DEPTH
-            );
-            return;
-        }
-        match item {
-            DockItem::Tabs { items, .. } => {
-                for panel_view in items {
-                    if let Ok(terminal_panel) = panel_view.view().downcast::<CruxTerminalPanel>() {
-                        let pane_id = terminal_panel.read(cx).pane_id();
-                        self.pane_registry.insert(pane_id, terminal_panel);
-                    }
-                }
-            }
-            DockItem::Split { items, .. } => {
-                for child in items {
-                    self.collect_panes_from_dock_item_recursive(child, cx, depth + 1);
-                }
-            }
-            DockItem::Panel { view, .. } => {
-                if let Ok(terminal_panel) = view.view().downcast::<CruxTerminalPanel>() {
-                    let pane_id = terminal_panel.read(cx).pane_id();
-                    self.pane_registry.insert(pane_id, terminal_panel);
+    /// Replace this window's panes with those of `session`, relaunching each
+    /// with its cwd, command and env and replaying its scrollback. Returns
+    /// the number of panes.
+    pub(crate) fn restore_session(
+        &mut self,
+        session: &crux_protocol::Session,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<u32> {
+        let saved: Vec<Option<PaneId>> = session.layout.panes().iter().map(|p| p.pane_id).collect();
+
+        let layout = self.apply_layout(session.layout.clone(), true, window, cx)?;
+        for (old, new) in saved.into_iter().zip(layout.panes()) {
+            let scrollback = old
+                .and_then(|id| session.pane(id))
+                .and_then(|p| p.scrollback.as_ref());
+            let panel = new.pane_id.and_then(|id| self.pane_registry.get(&id));
+            if let (Some(scrollback), Some(panel)) = (scrollback, panel) {
+                let view = panel.read(cx).terminal_view().read(cx);
+                if let Err(e) = view.restore_scrollback(scrollback) {
+                    log::warn!("skipping saved scrollback of pane {:?}: {}", old, e);
                 }
             }
-            _ => {}
         }
+        Ok(self.pane_registry.len() as u32)
     }
 }
 
@@ -727,6 +727,7 @@ impl Render for CruxApp {
         div()
             .id("crux-app")
             .size_full()
+            .capture_key_down(cx.listener(Self::handle_key_binding))
             .on_action(cx.listener(Self::action_new_tab))
             .on_action(cx.listener(Self::action_close_tab))
             .on_action(cx.listener(Self::action_force_close_tab))
@@ -771,45 +772,3 @@ impl Render for CruxApp {
             .child(self.dock_area.clone())
     }
 }
-
-impl Drop for CruxApp {
-    fn drop(&mut self) {
-        // Cancel IPC server gracefully
-        if let Some(cancel_token) = self.ipc_cancel.take() {
-            log::info!("Cancelling IPC server...");
-            cancel_token.cancel();
-        }
-
-        if let Some(mut child) = self.mcp_process.take() {
-            let pid = child.id();
-            log::info!("Terminating MCP server (PID {pid})...");
-            // Step 1: Send SIGTERM for graceful shutdown
-            // Safe cast: PIDs on macOS/Linux are always within i32 range.
-            let pid_i32 = i32::try_from(pid).expect("PID exceeds i32::MAX");
-            unsafe {
-                libc::kill(pid_i32, libc::SIGTERM);
-            }
-            // Step 2: Wait up to 2 seconds
-            for _ in 0..40 {
-                match child.try_wait() {
-                    Ok(Some(status)) => {
-                        log::info!("MCP server exited gracefully: {status}");
-                        return;
-                    }
-                    Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
-                    Err(e) => {
-                        log::warn!("Failed to check MCP server status: {e}");
-                        break;
-                    }
-                }
-            }
-            // Step 3: Force kill
-            log::warn!("MCP server did not exit gracefully, sending SIGKILL");
-            let _ = child.kill();
-            match child.wait() {
-                Ok(status) => log::info!("MCP server force-killed: {status}"),
-                Err(e) => log::warn!("Failed to wait for MCP server: {e}"),
-            }
-        }
-    }
-}
@@ -1,11 +1,25 @@
 //! CLI command definitions using clap derive macros.
 
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
 /// Crux terminal emulator
 #[derive(Parser)]
 #[command(name = "crux-app", version, about)]
 pub struct CliArgs {
+    /// Start with a fresh window even if `session.restore_on_launch` is set
+    #[arg(long)]
+    pub no_restore: bool,
+
+    /// Open a window attached to this mux session, creating it if needed
+    #[arg(long, value_name = "SESSION")]
+    pub attach: Option<String>,
+
+    /// Override a config value, e.g. `font.size=16` (repeatable)
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub config_override: Vec<String>,
+
     #[command(subcommand)]
     pub command: Option<CliCommand>,
 }
@@ -17,6 +31,37 @@ pub enum CliCommand {
         #[command(subcommand)]
         action: CliAction,
     },
+
+    /// Check, inspect and document the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Report errors in a config file and the files it imports
+    Check {
+        /// Config file (default: the user config file)
+        path: Option<PathBuf>,
+    },
+
+    /// Print the default configuration with every setting documented
+    Default,
+
+    /// Print the effective value of a key or section, e.g. `font.size`
+    Get {
+        /// Dotted key or section name
+        key: String,
+
+        /// Also show which layer set each value
+        #[arg(long)]
+        explain: bool,
+    },
+
+    /// Print the JSON Schema of the config file for editor completion
+    Schema,
 }
 
 #[derive(Subcommand)]
@@ -102,7 +147,7 @@ pub enum CliAction {
         force: bool,
     },
 
-    /// Create a new window (single-window mode: returns existing window)
+    /// Open a new window
     WindowCreate {
         /// Window title
         #[arg(long)]
@@ -123,4 +168,90 @@ pub enum CliAction {
         #[arg(long, default_value = "table")]
         format: String,
     },
+
+    /// Close a window
+    WindowClose {
+        /// Window ID to close
+        #[arg(long)]
+        window_id: u64,
+
+        /// Close even if a pane has a running process
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Focus a window
+    WindowFocus {
+        /// Window ID to focus
+        #[arg(long)]
+        window_id: u64,
+    },
+
+    /// Open a new tab
+    TabCreate {
+        /// Window to open the tab in (default: focused window)
+        #[arg(long)]
+        window_id: Option<u64>,
+
+        /// Tab panel to add the tab to (default: focused tab panel)
+        #[arg(long)]
+        tab_id: Option<u64>,
+
+        /// Working directory for the new pane
+        #[arg(long)]
+        cwd: Option<String>,
+
+        /// Command to run in the new pane
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+
+    /// List tabs
+    TabList {
+        /// Only list tabs of this window
+        #[arg(long)]
+        window_id: Option<u64>,
+
+        /// Output format: "table" (default) or "json"
+        #[arg(long, default_value = "table")]
+        format: String,
+    },
+
+    /// Move a pane into another tab
+    TabMovePane {
+        /// Pane ID to move
+        #[arg(long)]
+        pane_id: u64,
+
+        /// Destination tab ID
+        #[arg(long)]
+        tab_id: u64,
+    },
+
+    /// List the mux daemon's detached sessions
+    MuxList {
+        /// Output format: "table" (default) or "json"
+        #[arg(long, default_value = "table")]
+        format: String,
+    },
+
+    /// Attach this terminal to a mux session (detach with Ctrl-\)
+    MuxAttach {
+        /// Session name (default: a new session with the next free number)
+        name: Option<String>,
+
+        /// Create the session if it does not exist, starting the daemon if needed
+        #[arg(long)]
+        create: bool,
+
+        /// Command to run instead of the shell when creating the session
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+
+    /// Kill a mux session and its processes
+    MuxKill {
+        /// Session name
+        name: String,
+    },
 }
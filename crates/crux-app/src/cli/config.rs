@@ -0,0 +1,54 @@
+//! `crux-app config *`: configuration tooling that runs without a window.
+
+use crux_config::check::{self, Severity};
+use crux_config::layered::ConfigLoader;
+use crux_config::{schema, CruxConfig};
+
+use super::commands::ConfigAction;
+
+/// Run a config subcommand. `overrides` are the `--config-override` values,
+/// which `get` takes into account.
+pub fn run(action: ConfigAction, overrides: &[String]) -> anyhow::Result<()> {
+    match action {
+        ConfigAction::Check { path } => {
+            let path = path.unwrap_or_else(CruxConfig::config_path);
+            let diagnostics = check::check_file(&path);
+            for diagnostic in &diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            if errors > 0 {
+                anyhow::bail!("{} has {} error(s)", path.display(), errors);
+            }
+            println!("{}: ok", path.display());
+        }
+        ConfigAction::Default => print!("{}", schema::annotated_default()),
+        ConfigAction::Get { key, explain } => {
+            let layered = ConfigLoader::new().overrides(overrides.to_vec()).load()?;
+            let entries = layered.get(&key);
+            if entries.is_empty() {
+                anyhow::bail!("unknown config key: {key}");
+            }
+            for entry in entries {
+                if entry.key == key && !explain {
+                    // A single value prints bare, for use in scripts.
+                    match entry.value.as_str() {
+                        Some(s) => println!("{s}"),
+                        None => println!("{}", entry.value),
+                    }
+                } else if explain {
+                    println!("{} = {}  # {}", entry.key, entry.value, entry.source);
+                } else {
+                    println!("{} = {}", entry.key, entry.value);
+                }
+            }
+        }
+        ConfigAction::Schema => {
+            println!("{}", serde_json::to_string_pretty(&schema::json_schema())?);
+        }
+    }
+    Ok(())
+}
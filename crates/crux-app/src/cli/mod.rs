@@ -1,5 +1,7 @@
 pub mod client;
 pub mod commands;
+pub mod config;
+pub mod mux;
 pub mod output;
 
 pub use commands::CliArgs;
@@ -0,0 +1,312 @@
+//! `crux-app cli mux-*`: detached sessions hosted by the mux daemon.
+//!
+//! `mux-attach` turns the current terminal into a view of a daemon session:
+//! the session screen is redrawn, then PTY output is streamed to stdout and
+//! keystrokes are forwarded with `crux:pane/send-text` until the session
+//! ends or the user detaches with Ctrl-\.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use crux_ipc::{IpcClient, IpcTransport};
+use crux_protocol::{method, MuxSessionInfo, OutputNotifyParams, PaneId};
+
+/// Key that detaches from the session (Ctrl-\).
+const DETACH_KEY: u8 = 0x1c;
+
+/// How long to wait for a freshly spawned daemon to open its socket.
+const DAEMON_STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the terminal size is checked for changes.
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Connect to the mux daemon's control socket, starting the daemon first
+/// if `spawn` is set and none is running.
+pub fn connect(spawn: bool) -> anyhow::Result<IpcClient> {
+    let socket = crux_ipc::mux_socket_path();
+    if let Ok(client) = IpcClient::connect_to(socket.clone()) {
+        return Ok(client);
+    }
+    if !spawn {
+        anyhow::bail!("no mux daemon running at {}", socket.display());
+    }
+
+    spawn_daemon(&socket)?;
+    let deadline = Instant::now() + DAEMON_STARTUP_TIMEOUT;
+    loop {
+        match IpcClient::connect_to(socket.clone()) {
+            Ok(client) => return Ok(client),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(e.context("mux daemon did not start"))
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// Start `crux-headless --mux` in its own session so it outlives this
+/// process and the terminal it runs in.
+fn spawn_daemon(socket: &std::path::Path) -> anyhow::Result<()> {
+    // Prefer the binary installed next to ours.
+    let daemon = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("crux-headless")))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("crux-headless"));
+
+    let mut command = Command::new(&daemon);
+    command
+        .arg("--mux")
+        .arg("--socket")
+        .arg(socket)
+        // Panes must not inherit this window's socket or pane ID.
+        .env_remove("CRUX_SOCKET")
+        .env_remove("CRUX_PANE")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe and touches no parent state.
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(&mut command, || {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    command
+        .spawn()
+        .with_context(|| format!("failed to start {}", daemon.display()))?;
+    Ok(())
+}
+
+/// Print the daemon's sessions.
+pub fn list(format: &str) -> anyhow::Result<()> {
+    let sessions = match connect(false) {
+        Ok(client) => {
+            let result = client.call(method::MUX_LIST, serde_json::json!({}))?;
+            serde_json::from_value::<crux_protocol::MuxListResult>(result)?.sessions
+        }
+        // No daemon simply means no sessions.
+        Err(_) => Vec::new(),
+    };
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+    } else {
+        for s in &sessions {
+            println!(
+                "{} | {} panes | {}x{} | {}",
+                s.name, s.pane_count, s.cols, s.rows, s.socket_path
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Kill a session and every process in it.
+pub fn kill(name: &str) -> anyhow::Result<()> {
+    let client = connect(false)?;
+    client.call(method::MUX_KILL, serde_json::json!({ "name": name }))?;
+    Ok(())
+}
+
+/// Attach this terminal to a session until it ends or the user detaches.
+pub fn attach(name: Option<String>, create: bool, command: Vec<String>) -> anyhow::Result<()> {
+    let control = connect(create)?;
+    let size = terminal_size();
+    let params = crux_protocol::MuxAttachParams {
+        name,
+        create,
+        cwd: std::env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned()),
+        command: (!command.is_empty()).then_some(command),
+        rows: size.map(|(rows, _)| rows),
+        cols: size.map(|(_, cols)| cols),
+    };
+    let info: MuxSessionInfo =
+        serde_json::from_value(control.call(method::MUX_ATTACH, serde_json::to_value(&params)?)?)?;
+    let socket = PathBuf::from(&info.socket_path);
+
+    let output = IpcClient::connect_to(socket.clone())?;
+    output.call(method::PANE_SUBSCRIBE_OUTPUT, serde_json::json!({}))?;
+
+    let _raw = RawMode::enable()?;
+    let mut stdout = std::io::stdout().lock();
+    redraw(&output, None, &mut stdout)?;
+
+    let detached = Arc::new(AtomicBool::new(false));
+    spawn_input_forwarder(socket, detached.clone());
+
+    let mut control = Some(control);
+    let mut last_size = size;
+    let mut next_resize_check = Instant::now() + RESIZE_POLL_INTERVAL;
+    let ended = loop {
+        if detached.load(Ordering::Relaxed) {
+            break false;
+        }
+        if Instant::now() >= next_resize_check {
+            next_resize_check = Instant::now() + RESIZE_POLL_INTERVAL;
+            let size = terminal_size();
+            if let (Some((rows, cols)), true) = (size, size != last_size) {
+                last_size = size;
+                let params = serde_json::json!({ "name": info.name, "rows": rows, "cols": cols });
+                // The control connection may have timed out while idle.
+                let resized = control
+                    .as_ref()
+                    .map(|c| c.call(method::MUX_RESIZE, params.clone()));
+                if !matches!(resized, Some(Ok(_))) {
+                    control = connect(false).ok();
+                    if let Some(c) = &control {
+                        let _ = c.call(method::MUX_RESIZE, params);
+                    }
+                }
+            }
+        }
+
+        let Some(notification) = output.next_notification(RESIZE_POLL_INTERVAL)? else {
+            continue;
+        };
+        if notification.method != method::PANE_OUTPUT_NOTIFY {
+            continue;
+        }
+        let Some(params) = notification.params else {
+            continue;
+        };
+        let params: OutputNotifyParams = serde_json::from_value(params)?;
+        stdout.write_all(params.data.as_bytes())?;
+        stdout.flush()?;
+        if params.closed {
+            break true;
+        }
+    };
+
+    drop(_raw);
+    if ended {
+        eprintln!("\r\n[session {} ended]", info.name);
+    } else {
+        eprintln!("\r\n[detached from session {}]", info.name);
+    }
+    Ok(())
+}
+
+/// Clear the screen and draw the current screen and cursor of `pane_id`,
+/// or of the session's active pane.
+pub(crate) fn redraw(
+    client: &IpcClient,
+    pane_id: Option<PaneId>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let text: crux_protocol::GetTextResult = serde_json::from_value(client.call(
+        method::PANE_GET_TEXT,
+        serde_json::json!({ "pane_id": pane_id, "include_escapes": true }),
+    )?)?;
+    let snapshot: crux_protocol::GetSnapshotResult = serde_json::from_value(client.call(
+        method::PANE_GET_SNAPSHOT,
+        serde_json::json!({ "pane_id": pane_id }),
+    )?)?;
+
+    write!(
+        out,
+        "\x1b[0m\x1b[H\x1b[2J{}",
+        text.lines.join("\x1b[0m\r\n")
+    )?;
+    write!(
+        out,
+        "\x1b[0m\x1b[{};{}H",
+        snapshot.cursor_row.max(0) + 1,
+        snapshot.cursor_col + 1
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Forward stdin to the session from a background thread. Sets `detached`
+/// on the detach key or when stdin closes.
+fn spawn_input_forwarder(socket: PathBuf, detached: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        let mut client = IpcClient::connect_to(socket.clone()).ok();
+        let mut stdin = std::io::stdin().lock();
+        let mut buf = [0u8; 4096];
+        // A multi-byte character split across reads is sent once complete.
+        let mut utf8_tail = Vec::new();
+        loop {
+            let n = match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let input = &buf[..n];
+            let (input, detach) = match input.iter().position(|&b| b == DETACH_KEY) {
+                Some(pos) => (&input[..pos], true),
+                None => (input, false),
+            };
+            let text = crux_ipc::output::decode_utf8(&mut utf8_tail, input);
+            if !text.is_empty() {
+                let params = serde_json::json!({
+                    "text": text,
+                    "bracketed_paste": false,
+                });
+                // Idle connections are closed by the server; reconnect once.
+                let sent = client
+                    .as_ref()
+                    .map(|c| c.call(method::PANE_SEND_TEXT, params.clone()));
+                if !matches!(sent, Some(Ok(_))) {
+                    client = IpcClient::connect_to(socket.clone()).ok();
+                    if let Some(c) = &client {
+                        let _ = c.call(method::PANE_SEND_TEXT, params);
+                    }
+                }
+            }
+            if detach {
+                break;
+            }
+        }
+        detached.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Size of the controlling terminal as `(rows, cols)`.
+fn terminal_size() -> Option<(u32, u32)> {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass.
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0;
+    (ok && ws.ws_row > 0 && ws.ws_col > 0).then(|| (u32::from(ws.ws_row), u32::from(ws.ws_col)))
+}
+
+/// Puts stdin in raw mode and restores the previous mode on drop.
+struct RawMode {
+    saved: Option<libc::termios>,
+}
+
+impl RawMode {
+    fn enable() -> anyhow::Result<Self> {
+        // SAFETY: tcgetattr/tcsetattr only read and write the struct we own.
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                // Not a terminal (e.g. piped input): nothing to restore.
+                return Ok(Self { saved: None });
+            }
+            let mut raw = saved;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            Ok(Self { saved: Some(saved) })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            // SAFETY: restores the attributes read in `enable`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
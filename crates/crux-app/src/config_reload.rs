@@ -0,0 +1,136 @@
+//! Config hot-reload.
+//!
+//! The [`ConfigWatcher`] reloads every config layer when the user config
+//! file, a file it imports or the theme file changes. Each successful reload is diffed against the running
+//! configuration and only the affected parts of open panes are updated;
+//! key bindings take effect at once, while shell, window and session
+//! settings apply to what is opened afterwards.
+//! A reload that fails keeps the running configuration. Both outcomes are
+//! broadcast as events and reported by `crux:config/get-status`.
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+use gpui::*;
+
+use crux_config::diff::ConfigDiff;
+use crux_config::layered::ConfigLoader;
+use crux_config::watcher::{ConfigEvent, ConfigWatcher};
+use crux_config::CruxConfig;
+use crux_protocol::{ConfigStatusResult, PaneEvent};
+
+use crate::app::CruxApp;
+use crate::workspace::Workspace;
+
+/// How often the watcher's channel is drained.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+impl Workspace {
+    /// Start reloading the configuration resolved by `loader` whenever one
+    /// of the files it reads changes.
+    pub(crate) fn watch_config(loader: ConfigLoader, cx: &mut App) {
+        let path = cx.global::<Self>().live_config.path().to_path_buf();
+        let (watcher, events) = match ConfigWatcher::with_loader(path, loader) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log::warn!("config hot-reload disabled: {}", e);
+                return;
+            }
+        };
+        cx.global_mut::<Self>().config_watcher = Some(watcher);
+        cx.spawn(async move |cx: &mut AsyncApp| loop {
+            cx.background_executor().timer(POLL_INTERVAL).await;
+            match cx.update(|cx| Self::drain_config_events(&events, cx)) {
+                Ok(true) => {}
+                _ => break, // Watcher stopped or application shutting down
+            }
+        })
+        .detach();
+    }
+
+    /// Apply queued reloads. Returns `false` once the watcher is gone.
+    fn drain_config_events(events: &Receiver<ConfigEvent>, cx: &mut App) -> bool {
+        loop {
+            match events.try_recv() {
+                Ok(event) => Self::apply_config_event(event, cx),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn apply_config_event(event: ConfigEvent, cx: &mut App) {
+        let result = cx.global_mut::<Self>().live_config.apply(event);
+        let diff = match result {
+            Ok(diff) => diff,
+            Err(message) => {
+                log::warn!(
+                    "config reload failed, keeping the current config: {}",
+                    message
+                );
+                Self::emit_pane_event(PaneEvent::ConfigError { message }, cx);
+                return;
+            }
+        };
+        if diff.is_empty() {
+            return;
+        }
+        log::info!("config reloaded: {}", diff.keys.join(", "));
+
+        let workspace = cx.global_mut::<Self>();
+        let config = workspace.live_config.config().clone();
+        if diff.window() {
+            workspace.default_size = size(px(config.window.width), px(config.window.height));
+        }
+        if diff.session() {
+            workspace.session = config.session.clone();
+        }
+        for (_, app) in Self::windows(cx) {
+            app.update(cx, |app, cx| app.apply_config(&config, &diff, cx));
+        }
+        Self::emit_pane_event(PaneEvent::ConfigReloaded { changed: diff.keys }, cx);
+    }
+
+    /// Answer `crux:config/get-status`.
+    pub(crate) fn config_status(&self) -> ConfigStatusResult {
+        ConfigStatusResult {
+            path: Some(self.live_config.path().display().to_string()),
+            watching: self.config_watcher.is_some(),
+            reloads: self.live_config.reloads(),
+            last_changed: self.live_config.last_changed().to_vec(),
+            error: self.live_config.last_error().map(String::from),
+        }
+    }
+}
+
+impl CruxApp {
+    /// Bring this window's panes up to date with a reloaded configuration.
+    fn apply_config(&mut self, config: &CruxConfig, diff: &ConfigDiff, cx: &mut Context<Self>) {
+        self.config = config.clone();
+        if diff.keybindings() {
+            self.keys.set_keymap(crate::keys::keymap(config));
+        }
+        if !(diff.font() || diff.colors() || diff.scrollback() || diff.downloads()) {
+            return;
+        }
+        for panel in self.pane_registry.values() {
+            let view = panel.read(cx).terminal_view().clone();
+            view.update(cx, |view, cx| {
+                if diff.font() {
+                    view.update_font_config(config.font.clone());
+                }
+                if diff.colors() {
+                    view.update_color_config(config.colors.clone());
+                }
+                if diff.scrollback() {
+                    view.set_scrollback_lines(config.terminal.scrollback_lines);
+                }
+                if diff.downloads() {
+                    view.set_downloads(&config.terminal);
+                }
+                cx.notify();
+            });
+        }
+        cx.notify();
+    }
+}
@@ -1 +1,2 @@
 pub mod terminal_panel;
+pub mod tmux_panel;
@@ -5,8 +5,8 @@ use gpui::*;
 use gpui_component::dock::{register_panel, Panel, PanelEvent, PanelInfo, PanelState};
 
 use crux_config::{ColorConfig, FontConfig, TerminalConfig};
-use crux_protocol::PaneId;
-use crux_terminal_view::CruxTerminalView;
+use crux_protocol::{LayoutPane, PaneId};
+use crux_terminal_view::{protocol, CruxTerminal, CruxTerminalView};
 
 /// Register `CruxTerminalPanel` in the global PanelRegistry so that
 /// `DockArea::load` can reconstruct terminal panels from saved state.
@@ -60,6 +60,10 @@ pub struct CruxTerminalPanel {
     pane_id: PaneId,
     focus_handle: FocusHandle,
     terminal_view: Entity<CruxTerminalView>,
+    /// Fixed tab title from a layout; takes precedence over the OSC title.
+    title: Option<SharedString>,
+    /// What the pane was started with, for session files.
+    launch: LayoutPane,
 }
 
 impl CruxTerminalPanel {
@@ -75,8 +79,6 @@ impl CruxTerminalPanel {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Self {
-        let focus_handle = cx.focus_handle();
-
         // Build environment variables for the child process.
         // Merge user-provided env with CRUX_PANE and TERM_PROGRAM.
         let mut child_env = env.cloned().unwrap_or_default();
@@ -94,6 +96,30 @@ impl CruxTerminalPanel {
                 cx,
             )
         });
+        Self::from_view(pane_id, terminal_view, window, cx)
+    }
+
+    /// Wrap a terminal that is already running, such as a remote tmux pane.
+    pub fn from_terminal(
+        pane_id: PaneId,
+        terminal: CruxTerminal,
+        font_config: FontConfig,
+        color_config: ColorConfig,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let terminal_view =
+            cx.new(|cx| CruxTerminalView::from_terminal(terminal, font_config, color_config, cx));
+        Self::from_view(pane_id, terminal_view, window, cx)
+    }
+
+    fn from_view(
+        pane_id: PaneId,
+        terminal_view: Entity<CruxTerminalView>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let focus_handle = cx.focus_handle();
 
         // Focus the inner terminal view so key events reach the PTY.
         let inner_focus = terminal_view.read(cx).focus_handle(cx);
@@ -103,6 +129,8 @@ impl CruxTerminalPanel {
             pane_id,
             focus_handle,
             terminal_view,
+            title: None,
+            launch: LayoutPane::default(),
         }
     }
 
@@ -111,6 +139,27 @@ impl CruxTerminalPanel {
         self.pane_id
     }
 
+    /// Pin the tab title, or go back to the terminal's own title with `None`.
+    pub fn set_title(&mut self, title: Option<SharedString>, cx: &mut Context<Self>) {
+        self.title = title;
+        cx.notify();
+    }
+
+    /// The title pinned with [`Self::set_title`], if any.
+    pub fn fixed_title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Record the cwd, command and env this pane was started with.
+    pub fn set_launch(&mut self, launch: LayoutPane) {
+        self.launch = launch;
+    }
+
+    /// The cwd, command and env this pane was started with.
+    pub fn launch(&self) -> &LayoutPane {
+        &self.launch
+    }
+
     /// Returns a reference to the inner terminal view entity.
     pub fn terminal_view(&self) -> &Entity<CruxTerminalView> {
         &self.terminal_view
@@ -146,47 +195,110 @@ impl CruxTerminalPanel {
         self.terminal_view.read(cx).selection_to_string()
     }
 
-    /// Get the terminal text content and cursor position.
-    pub fn get_text(&self, cx: &App) -> (Vec<String>, u32, u32) {
+    /// Read text from the screen or scrollback, as requested over IPC.
+    pub fn get_text(
+        &self,
+        params: &crux_protocol::GetTextParams,
+        cx: &App,
+    ) -> crux_protocol::GetTextResult {
+        let text = self.terminal_view.read(cx).read_text(
+            params.start_line,
+            params.end_line,
+            params.include_escapes,
+        );
+        protocol::text_result(text)
+    }
+
+    /// Dump a region of the grid cell by cell, with colors resolved to RGB.
+    pub fn dump_grid(
+        &self,
+        params: &crux_protocol::DumpGridParams,
+        cx: &App,
+    ) -> anyhow::Result<crux_protocol::DumpGridResult> {
+        let dump = self.terminal_view.read(cx).dump_grid(
+            params.start_line..params.end_line,
+            params.start_col.map(|c| c as usize)..params.end_col.map(|c| c as usize),
+        );
+        protocol::grid_result(self.pane_id, dump)
+    }
+
+    /// Modes, cursor style, charsets and keyboard flags set by the application.
+    pub fn get_modes(&self, cx: &App) -> crux_protocol::GetModesResult {
+        protocol::modes_result(self.pane_id, self.terminal_view.read(cx).modes())
+    }
+
+    /// Images stored in the terminal and where they are placed.
+    pub fn list_images(&self, cx: &App) -> crux_protocol::ListImagesResult {
+        protocol::images_result(self.pane_id, self.terminal_view.read(cx).graphics())
+    }
+
+    /// An image from the terminal, encoded as PNG.
+    pub fn get_image(
+        &self,
+        params: &crux_protocol::GetImageParams,
+        cx: &App,
+    ) -> anyhow::Result<crux_protocol::GetImageResult> {
+        protocol::image_result(self.terminal_view.read(cx).graphics(), params)
+    }
+
+    /// Show the image `file` at the cursor, bypassing the PTY.
+    pub fn render_image(
+        &mut self,
+        params: &crux_protocol::RenderImageParams,
+        file: Vec<u8>,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<crux_protocol::RenderImageResult> {
+        let pane_id = self.pane_id;
+        self.terminal_view.update(cx, |view, cx| {
+            let image_id = view.show_image(file, params.cols, params.rows)?;
+            cx.notify();
+            protocol::render_image_result(pane_id, view.graphics(), image_id)
+        })
+    }
+
+    /// Check that the shell is idle at an OSC 133-marked prompt.
+    pub fn ready_for_command(&self, cx: &App) -> anyhow::Result<()> {
         let view = self.terminal_view.read(cx);
-        // Single snapshot call to avoid double FairMutex acquisition.
-        let content = view.terminal_content_snapshot();
+        if !view.has_shell_integration() {
+            anyhow::bail!(
+                "pane {} has no OSC 133 shell integration; command boundaries cannot be detected",
+                self.pane_id
+            );
+        }
+        if view.is_command_running() {
+            anyhow::bail!("pane {} is busy running a command", self.pane_id);
+        }
+        Ok(())
+    }
 
-        // Extract text lines from the existing snapshot instead of calling get_text_lines(),
-        // which would acquire the FairMutex a second time.
-        let lines = view.get_text_lines_from_content(&content);
+    /// Shell working directory reported via OSC 7, if any.
+    pub fn cwd(&self, cx: &App) -> Option<String> {
+        self.terminal_view.read(cx).cwd().map(|s| s.to_string())
+    }
 
-        (
-            lines,
-            content.cursor.point.line.0 as u32,
-            content.cursor.point.column.0 as u32,
-        )
+    /// Completed commands matching `filter`, newest first.
+    pub fn history(
+        &self,
+        filter: &crux_terminal_view::HistoryFilter,
+        cx: &App,
+    ) -> Vec<crux_terminal_view::CommandRecord> {
+        self.terminal_view.read(cx).history().query(filter)
+    }
+
+    /// Register a raw PTY output observer on this panel's terminal.
+    pub fn add_output_sink(&self, sink: crux_terminal_view::OutputSink, cx: &App) {
+        self.terminal_view.read(cx).output_tap().add(sink);
     }
 
     /// Get a full snapshot of the terminal state (text + metadata).
     pub fn get_snapshot(&self, cx: &App) -> crux_protocol::GetSnapshotResult {
         let view = self.terminal_view.read(cx);
-        // Single snapshot call to avoid double FairMutex acquisition.
         let content = view.terminal_content_snapshot();
-        let size = view.terminal_size();
-
-        // Extract text lines from the existing snapshot instead of calling get_text_lines(),
-        // which would acquire the FairMutex a second time.
-        let lines = view.get_text_lines_from_content(&content);
-
-        let cursor_shape = format!("{:?}", content.cursor.shape);
-        crux_protocol::GetSnapshotResult {
-            lines,
-            rows: size.rows as u32,
-            cols: size.cols as u32,
-            cursor_row: content.cursor.point.line.0,
-            cursor_col: content.cursor.point.column.0 as u32,
-            cursor_shape,
-            display_offset: content.display_offset as u32,
-            has_selection: content.selection.is_some(),
-            title: view.title().map(|s| s.to_string()),
-            cwd: view.cwd().map(|s| s.to_string()),
-        }
+        protocol::snapshot_result(
+            &content,
+            view.title().map(|s| s.to_string()),
+            view.cwd().map(|s| s.to_string()),
+        )
     }
 
     /// Scroll to the previous prompt in the terminal scrollback.
@@ -211,8 +323,11 @@ impl CruxTerminalPanel {
 
     /// Compute the display title from the terminal state.
     ///
-    /// Priority: OSC title > CWD basename > "Terminal"
+    /// Priority: fixed title > OSC title > CWD basename > "Terminal"
     pub fn display_title(&self, cx: &App) -> SharedString {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
         let view = self.terminal_view.read(cx);
 
         // 1. Use OSC title if set by the shell/program.
@@ -256,7 +371,7 @@ impl Panel for CruxTerminalPanel {
         let info_json = serde_json::json!({
             "pane_id": self.pane_id.0,
             "cwd": view.cwd(),
-            "title": view.title(),
+            "title": self.title.as_deref().or(view.title()),
         });
         let mut state = PanelState::new(self);
         state.info = PanelInfo::panel(info_json);
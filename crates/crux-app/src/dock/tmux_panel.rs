@@ -0,0 +1,137 @@
+use gpui::*;
+use gpui_component::dock::{Panel, PanelEvent, PanelInfo, PanelState};
+
+use crux_protocol::PaneId;
+
+use crate::dock::terminal_panel::CruxTerminalPanel;
+
+/// Panes of a tmux window, split the way tmux lays them out.
+pub enum TmuxPaneTree {
+    Pane(Entity<CruxTerminalPanel>),
+    /// Children with their share of the parent along `axis`.
+    Split {
+        axis: Axis,
+        children: Vec<(f32, TmuxPaneTree)>,
+    },
+}
+
+impl TmuxPaneTree {
+    /// Terminal panels in depth-first order.
+    pub fn panels(&self) -> Vec<&Entity<CruxTerminalPanel>> {
+        match self {
+            Self::Pane(panel) => vec![panel],
+            Self::Split { children, .. } => children
+                .iter()
+                .flat_map(|(_, child)| child.panels())
+                .collect(),
+        }
+    }
+
+    fn render(&self) -> AnyElement {
+        match self {
+            Self::Pane(panel) => div().size_full().child(panel.clone()).into_any_element(),
+            Self::Split { axis, children } => {
+                let axis = *axis;
+                let cells = children.iter().map(move |(fraction, child)| {
+                    let cell = div().overflow_hidden();
+                    let cell = match axis {
+                        Axis::Horizontal => cell.h_full().w(relative(*fraction)),
+                        Axis::Vertical => cell.w_full().h(relative(*fraction)),
+                    };
+                    cell.child(child.render())
+                });
+                let row = div().flex().size_full();
+                let row = match axis {
+                    Axis::Horizontal => row.flex_row(),
+                    Axis::Vertical => row.flex_col(),
+                };
+                row.children(cells).into_any_element()
+            }
+        }
+    }
+}
+
+/// A tmux window shown as one tab.
+///
+/// tmux owns the split layout and the window's lifetime, so the panes are
+/// not dock panels of their own and the tab cannot be closed from Crux.
+pub struct TmuxWindowPanel {
+    focus_handle: FocusHandle,
+    /// tmux window name, shown as the tab title.
+    name: SharedString,
+    panes: TmuxPaneTree,
+}
+
+impl TmuxWindowPanel {
+    pub fn new(name: SharedString, panes: TmuxPaneTree, cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            name,
+            panes,
+        }
+    }
+
+    pub fn set_name(&mut self, name: SharedString, cx: &mut Context<Self>) {
+        self.name = name;
+        cx.notify();
+    }
+
+    /// Show `panes` instead of the current ones, which are returned.
+    pub fn replace_panes(&mut self, panes: TmuxPaneTree, cx: &mut Context<Self>) -> TmuxPaneTree {
+        cx.notify();
+        std::mem::replace(&mut self.panes, panes)
+    }
+
+    /// IDs of the panes shown, in depth-first order.
+    pub fn pane_ids(&self, cx: &App) -> Vec<PaneId> {
+        self.panes
+            .panels()
+            .into_iter()
+            .map(|panel| panel.read(cx).pane_id())
+            .collect()
+    }
+}
+
+impl Panel for TmuxWindowPanel {
+    fn panel_name(&self) -> &'static str {
+        "TmuxWindowPanel"
+    }
+
+    fn title(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        self.name.clone()
+    }
+
+    fn closable(&self, _cx: &App) -> bool {
+        false
+    }
+
+    fn dump(&self, _cx: &App) -> PanelState {
+        // Saved as a plain shell pane: the tmux panes live on in tmux and
+        // come back when control mode is started again.
+        let mut state = PanelState::new(self);
+        state.info = PanelInfo::panel(serde_json::json!({}));
+        state
+    }
+
+    fn inner_padding(&self, _cx: &App) -> bool {
+        false
+    }
+}
+
+impl EventEmitter<PanelEvent> for TmuxWindowPanel {}
+
+impl Focusable for TmuxWindowPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for TmuxWindowPanel {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .id("tmux-window-panel")
+            .track_focus(&self.focus_handle)
+            .size_full()
+            .child(self.panes.render())
+    }
+}
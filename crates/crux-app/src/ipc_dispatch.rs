@@ -4,11 +4,321 @@ use gpui::*;
 use gpui_component::dock::{DockItem, PanelView, StackPanel, TabPanel};
 use gpui_component::Placement;
 
-use crux_ipc::IpcCommand;
-use crux_protocol::PaneId;
+use crux_ipc::{CommandStarted, IpcCommand};
+use crux_protocol::{
+    LayoutApplyParams, LayoutApplyResult, LayoutGetParams, LayoutGetResult, OutputFormat, PaneId,
+    RunCommandResult, TabId, TabMovePaneParams, WindowCloseParams, WindowCreateParams, WindowId,
+};
+use crux_terminal_view::{protocol, CommandCapture, OutputSink};
 
 use crate::app::CruxApp;
 use crate::dock::terminal_panel::CruxTerminalPanel;
+use crate::workspace::{WindowEntry, Workspace};
+
+/// Handle an IPC command for the whole application.
+///
+/// Window, tab, and event commands are answered here; everything else is
+/// forwarded to the window that owns the target pane or tab, or to the
+/// focused window when the command names neither.
+pub(crate) fn dispatch_ipc_command(cmd: IpcCommand, cx: &mut App) {
+    match cmd {
+        IpcCommand::Handshake { params: _, reply } => {
+            let result = crux_protocol::HandshakeResult {
+                server_name: "crux".into(),
+                server_version: env!("CARGO_PKG_VERSION").into(),
+                protocol_version: "1.0".into(),
+                supported_capabilities: vec!["pane".into(), "window".into(), "tab".into()],
+            };
+            let _ = reply.send(Ok(result));
+        }
+
+        IpcCommand::ListPanes { reply } => {
+            let mut panes = Vec::new();
+            for (entry, app) in Workspace::windows(cx) {
+                let _ = entry.handle.update(cx, |_, window, cx| {
+                    panes.extend(app.read(cx).pane_infos(window, cx));
+                });
+            }
+            let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes }));
+        }
+
+        IpcCommand::WindowCreate { params, reply } => {
+            let result = Workspace::open_window(&params, cx).map(|(window_id, tab_id, pane_id)| {
+                crux_protocol::WindowCreateResult {
+                    window_id,
+                    tab_id,
+                    pane_id,
+                }
+            });
+            let _ = reply.send(result);
+        }
+
+        IpcCommand::WindowList { reply } => {
+            let focused = cx.global::<Workspace>().focused_window_id();
+            let mut windows = Vec::new();
+            for (entry, app) in Workspace::windows(cx) {
+                let _ = entry.handle.update(cx, |_, window, cx| {
+                    let app = app.read(cx);
+                    windows.push(crux_protocol::WindowInfo {
+                        window_id: entry.id,
+                        title: window.window_title(),
+                        pane_count: app.pane_registry.len() as u32,
+                        tab_count: app.tab_panels(cx).len() as u32,
+                        is_focused: focused == Some(entry.id),
+                    });
+                });
+            }
+            let _ = reply.send(Ok(crux_protocol::WindowListResult { windows }));
+        }
+
+        IpcCommand::WindowClose { params, reply } => {
+            let _ = reply.send(close_window(params, cx));
+        }
+
+        IpcCommand::WindowFocus { params, reply } => {
+            let result = Workspace::window(params.window_id, cx)
+                .ok_or_else(|| anyhow::anyhow!("window {} not found", params.window_id))
+                .and_then(|(entry, _)| {
+                    entry
+                        .handle
+                        .update(cx, |_, window, _| window.activate_window())?;
+                    cx.global_mut::<Workspace>().set_focused_window(entry.id);
+                    Ok(())
+                });
+            let _ = reply.send(result);
+        }
+
+        IpcCommand::TabList { params, reply } => {
+            let windows = match params.window_id {
+                Some(window_id) => match Workspace::window(window_id, cx) {
+                    Some(window) => vec![window],
+                    None => {
+                        let _ = reply.send(Err(anyhow::anyhow!("window {} not found", window_id)));
+                        return;
+                    }
+                },
+                None => Workspace::windows(cx),
+            };
+            let tabs = windows
+                .iter()
+                .flat_map(|(_, app)| app.read(cx).tab_infos(cx))
+                .collect();
+            let _ = reply.send(Ok(crux_protocol::TabListResult { tabs }));
+        }
+
+        IpcCommand::TabMovePane { params, reply } => {
+            let _ = reply.send(move_pane(params, cx));
+        }
+
+        IpcCommand::LayoutApply { params, reply } => {
+            let _ = reply.send(apply_layout(params, cx));
+        }
+
+        IpcCommand::LayoutGet { params, reply } => {
+            let _ = reply.send(layout_get(params, cx));
+        }
+
+        cmd @ (IpcCommand::MuxList { .. }
+        | IpcCommand::MuxAttach { .. }
+        | IpcCommand::MuxResize { .. }
+        | IpcCommand::MuxKill { .. }) => {
+            cmd.reject(anyhow::anyhow!(
+                "this is a Crux window, not the mux daemon (see `crux-app cli mux-list`)"
+            ));
+        }
+
+        IpcCommand::ConfigGetStatus { reply } => {
+            let _ = reply.send(Ok(cx.global::<Workspace>().config_status()));
+        }
+
+        IpcCommand::EventsPoll { reply } => {
+            let events = cx.global_mut::<Workspace>().drain_pane_events();
+            let _ = reply.send(Ok(crux_protocol::EventsPollResult { events }));
+        }
+
+        IpcCommand::EventsSubscribe {
+            subscription,
+            reply,
+        } => {
+            let events = subscription.events().to_vec();
+            cx.global_mut::<Workspace>()
+                .event_subscribers
+                .push(subscription);
+            let _ = reply.send(Ok(crux_protocol::EventsSubscribeResult { events }));
+        }
+
+        IpcCommand::TabCreate { params, reply } => {
+            let target = match (params.tab_id, params.window_id) {
+                (Some(tab_id), _) => Workspace::windows(cx)
+                    .into_iter()
+                    .find(|(_, app)| app.read(cx).tab_panel_by_id(tab_id, cx).is_some())
+                    .ok_or_else(|| anyhow::anyhow!("tab {} not found", tab_id)),
+                (None, Some(window_id)) => Workspace::window(window_id, cx)
+                    .ok_or_else(|| anyhow::anyhow!("window {} not found", window_id)),
+                (None, None) => {
+                    Workspace::focused_window(cx).ok_or_else(|| anyhow::anyhow!("no window"))
+                }
+            };
+            match target {
+                Ok(target) => {
+                    let cmd = IpcCommand::TabCreate { params, reply };
+                    forward_to_window(target, cmd, cx);
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(e));
+                }
+            }
+        }
+
+        cmd => {
+            // Pane IDs are unique across windows; an unknown pane falls
+            // through to the focused window, which reports it as not found.
+            let target = cmd
+                .target_pane()
+                .and_then(|pane_id| Workspace::window_for_pane(pane_id, cx))
+                .or_else(|| Workspace::focused_window(cx));
+            match target {
+                Some(target) => forward_to_window(target, cmd, cx),
+                None => log::warn!("dropping IPC command: no window is open"),
+            }
+        }
+    }
+}
+
+/// Run a command against one window's [`CruxApp`].
+fn forward_to_window((entry, app): (WindowEntry, Entity<CruxApp>), cmd: IpcCommand, cx: &mut App) {
+    let result = entry.handle.update(cx, |_, window, cx| {
+        app.update(cx, |app, cx| {
+            app.handle_ipc_command(cmd, window, cx);
+            cx.notify();
+        });
+    });
+    if let Err(e) = result {
+        log::warn!(
+            "window {} went away before handling a command: {}",
+            entry.id,
+            e
+        );
+    }
+}
+
+/// Close a window, refusing while any of its panes runs a process unless forced.
+fn close_window(params: WindowCloseParams, cx: &mut App) -> anyhow::Result<()> {
+    let (entry, app) = Workspace::window(params.window_id, cx)
+        .ok_or_else(|| anyhow::anyhow!("window {} not found", params.window_id))?;
+
+    let panels: Vec<_> = app
+        .read(cx)
+        .pane_registry
+        .iter()
+        .map(|(id, panel)| (*id, panel.clone()))
+        .collect();
+    if !params.force {
+        for (pane_id, panel) in &panels {
+            if panel.update(cx, |p, cx| p.is_process_running(cx)) {
+                return Err(anyhow::anyhow!(
+                    "window {} has a running process in pane {}, use force: true to close",
+                    params.window_id,
+                    pane_id
+                ));
+            }
+        }
+    }
+
+    entry
+        .handle
+        .update(cx, |_, window, _| window.remove_window())?;
+    cx.global_mut::<Workspace>().remove_window(entry.id);
+    for (pane_id, _) in panels {
+        Workspace::emit_pane_event(crux_protocol::PaneEvent::Closed { pane_id }, cx);
+    }
+    Ok(())
+}
+
+/// The window a layout command targets: the given one or the focused one.
+fn layout_window(
+    window_id: Option<WindowId>,
+    cx: &mut App,
+) -> anyhow::Result<(WindowEntry, Entity<CruxApp>)> {
+    match window_id {
+        Some(id) => {
+            Workspace::window(id, cx).ok_or_else(|| anyhow::anyhow!("window {} not found", id))
+        }
+        None => Workspace::focused_window(cx).ok_or_else(|| anyhow::anyhow!("no window")),
+    }
+}
+
+/// Build a declarative layout in an existing or new window.
+fn apply_layout(params: LayoutApplyParams, cx: &mut App) -> anyhow::Result<LayoutApplyResult> {
+    // Resolve before opening a window so a bad template leaves nothing behind.
+    let tree = crux_config::layouts::resolve(params.layout, params.template.as_deref())?;
+    let (target, force) = if params.new_window {
+        let create = WindowCreateParams {
+            title: params.template.clone(),
+            width: None,
+            height: None,
+        };
+        let (window_id, _, _) = Workspace::open_window(&create, cx)?;
+        // The window's initial shell is always replaced.
+        (layout_window(Some(window_id), cx)?, true)
+    } else {
+        (layout_window(params.window_id, cx)?, params.force)
+    };
+
+    let (entry, app) = target;
+    let layout = entry.handle.update(cx, |_, window, cx| {
+        app.update(cx, |app, cx| {
+            let layout = app.apply_layout(tree, force, window, cx);
+            cx.notify();
+            layout
+        })
+    })??;
+    Ok(LayoutApplyResult {
+        window_id: entry.id,
+        layout,
+    })
+}
+
+fn layout_get(params: LayoutGetParams, cx: &mut App) -> anyhow::Result<LayoutGetResult> {
+    let (entry, app) = layout_window(params.window_id, cx)?;
+    let layout = app.read(cx).layout_tree(cx)?;
+    Ok(LayoutGetResult {
+        window_id: entry.id,
+        layout,
+    })
+}
+
+/// Move a pane into another tab, possibly in another window.
+fn move_pane(params: TabMovePaneParams, cx: &mut App) -> anyhow::Result<()> {
+    let TabMovePaneParams { pane_id, tab_id } = params;
+    let (source, source_app) = Workspace::window_for_pane(pane_id, cx)
+        .ok_or_else(|| anyhow::anyhow!("pane {} not found", pane_id))?;
+    let (target, target_app) = Workspace::windows(cx)
+        .into_iter()
+        .find(|(_, app)| app.read(cx).tab_panel_by_id(tab_id, cx).is_some())
+        .ok_or_else(|| anyhow::anyhow!("tab {} not found", tab_id))?;
+
+    // Detaching the only pane of a tab removes the tab, so a move within
+    // the same tab must not touch it.
+    if source_app.read(cx).tab_id_for_pane(pane_id, cx) == Some(tab_id) {
+        return Ok(());
+    }
+
+    let detached = source.handle.update(cx, |_, window, cx| {
+        source_app.update(cx, |app, cx| app.detach_pane(pane_id, window, cx))
+    })??;
+    target.handle.update(cx, |_, window, cx| {
+        target_app.update(cx, |app, cx| app.attach_pane(detached, tab_id, window, cx))
+    })??;
+    Ok(())
+}
+
+/// A pane taken out of one window on its way into another tab.
+pub(crate) struct DetachedPane {
+    pane_id: PaneId,
+    panel: Entity<CruxTerminalPanel>,
+    running_command: Option<std::sync::Weak<()>>,
+}
 
 impl CruxApp {
     /// Resolve a pane ID from an optional parameter, falling back to the active pane.
@@ -31,23 +341,13 @@ impl CruxApp {
         cx: &mut Context<Self>,
     ) {
         match cmd {
-            IpcCommand::Handshake { params: _, reply } => {
-                let result = crux_protocol::HandshakeResult {
-                    server_name: "crux".into(),
-                    server_version: env!("CARGO_PKG_VERSION").into(),
-                    protocol_version: "1.0".into(),
-                    supported_capabilities: vec!["pane".into()],
-                };
-                let _ = reply.send(Ok(result));
-            }
-
             IpcCommand::SplitPane { params, reply } => {
                 // Determine the parent pane (target or currently active).
                 let parent_pane_id = params
                     .target_pane_id
                     .or_else(|| self.active_pane_id(window, cx));
 
-                let pane_id = self.allocate_pane_id();
+                let pane_id = Workspace::allocate_pane_id(cx);
                 let panel = self.create_terminal_panel(
                     pane_id,
                     params.cwd.as_deref(),
@@ -72,10 +372,12 @@ impl CruxApp {
                     crux_protocol::SplitDirection::Bottom => Placement::Bottom,
                 };
 
-                if let Some(tp) = target_tp {
+                let split_size = parent_pane_id
+                    .and_then(|id| self.split_size(id, params.direction, params.size?, cx));
+                if let Some(tp) = &target_tp {
                     let panel_view: Arc<dyn PanelView> = Arc::new(panel.clone());
                     tp.update(cx, |tp, cx| {
-                        tp.add_panel_at(panel_view, placement, None, window, cx);
+                        tp.add_panel_at(panel_view, placement, split_size, window, cx);
                     });
                 }
 
@@ -83,20 +385,35 @@ impl CruxApp {
                     self.pane_parents.insert(pane_id, parent_id);
                 }
                 self.active_pane = Some(pane_id);
-                self.emit_pane_event(crux_protocol::PaneEvent::Created { pane_id });
+                Workspace::emit_pane_event(crux_protocol::PaneEvent::Created { pane_id }, cx);
 
                 let size = panel.read(cx).terminal_view_size(cx);
-                let result = crux_protocol::SplitPaneResult {
-                    pane_id,
-                    window_id: crux_protocol::WindowId(0),
-                    tab_id: crux_protocol::TabId(0),
-                    size: crux_protocol::PaneSize {
-                        rows: size.0,
-                        cols: size.1,
-                    },
-                    tty: None,
-                };
-                let _ = reply.send(Ok(result));
+                let window_id = self.window_id;
+                let fallback_tab = target_tp.as_ref().map(Self::tab_id);
+                // The split lands on the next turn of the event loop; reply
+                // once the new pane's tab panel exists.
+                cx.spawn_in(window, async move |this, cx| {
+                    let tab_id = this
+                        .read_with(cx, |this, cx| this.tab_id_for_pane(pane_id, cx))
+                        .ok()
+                        .flatten()
+                        .or(fallback_tab);
+                    let result = match tab_id {
+                        Some(tab_id) => Ok(crux_protocol::SplitPaneResult {
+                            pane_id,
+                            window_id,
+                            tab_id,
+                            size: crux_protocol::PaneSize {
+                                rows: size.0,
+                                cols: size.1,
+                            },
+                            tty: None,
+                        }),
+                        None => Err(anyhow::anyhow!("no tab panel to split")),
+                    };
+                    let _ = reply.send(result);
+                })
+                .detach();
             }
 
             IpcCommand::SendText { params, reply } => {
@@ -117,13 +434,55 @@ impl CruxApp {
 
             IpcCommand::GetText { params, reply } => {
                 if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
-                    let (lines, cursor_row, cursor_col) = panel.read(cx).get_text(cx);
-                    let result = crux_protocol::GetTextResult {
-                        lines,
-                        first_line: 0,
-                        cursor_row,
-                        cursor_col,
-                    };
+                    let result = panel.read(cx).get_text(&params, cx);
+                    let _ = reply.send(Ok(result));
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            IpcCommand::PaneHistory { params, reply } => {
+                if let Some((pane_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let commands = panel
+                        .read(cx)
+                        .history(&protocol::history_filter(&params), cx)
+                        .into_iter()
+                        .map(|record| protocol::history_entry(record, params.include_output))
+                        .collect();
+                    let _ = reply.send(Ok(crux_protocol::PaneHistoryResult { pane_id, commands }));
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            IpcCommand::DumpGrid { params, reply } => {
+                if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let _ = reply.send(panel.read(cx).dump_grid(&params, cx));
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            IpcCommand::GetModes { params, reply } => {
+                if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let result = panel.read(cx).get_modes(cx);
+                    let _ = reply.send(Ok(result));
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            IpcCommand::ListImages { params, reply } => {
+                if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let result = panel.read(cx).list_images(cx);
                     let _ = reply.send(Ok(result));
                 } else if let Some(id) = params.pane_id {
                     let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
@@ -132,6 +491,32 @@ impl CruxApp {
                 }
             }
 
+            IpcCommand::GetImage { params, reply } => {
+                if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let result = panel.read(cx).get_image(&params, cx);
+                    let _ = reply.send(result);
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            IpcCommand::RenderImage {
+                params,
+                file,
+                reply,
+            } => {
+                if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
+                    let result = panel.update(cx, |p, cx| p.render_image(&params, file, cx));
+                    let _ = reply.send(result);
+                } else if let Some(id) = params.pane_id {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
             IpcCommand::GetSelection { params, reply } => {
                 if let Some((_id, panel)) = self.resolve_pane(params.pane_id, window, cx) {
                     let text = panel.read(cx).get_selection(cx);
@@ -158,37 +543,6 @@ impl CruxApp {
                 }
             }
 
-            IpcCommand::ListPanes { reply } => {
-                let panes: Vec<crux_protocol::PaneInfo> = self
-                    .pane_registry
-                    .iter()
-                    .map(|(id, panel)| {
-                        let p = panel.read(cx);
-                        let view = p.terminal_view().read(cx);
-                        let size = view.terminal_size();
-                        let content = view.terminal_content_snapshot();
-                        crux_protocol::PaneInfo {
-                            pane_id: *id,
-                            window_id: crux_protocol::WindowId(0),
-                            tab_id: crux_protocol::TabId(0),
-                            size: crux_protocol::PaneSize {
-                                rows: size.rows as u32,
-                                cols: size.cols as u32,
-                            },
-                            title: view.title().unwrap_or("").to_string(),
-                            cwd: view.cwd().map(|s| s.to_string()),
-                            is_active: self.is_pane_active(*id, window, cx),
-                            is_zoomed: false,
-                            cursor_x: content.cursor.point.column.0 as u32,
-                            cursor_y: content.cursor.point.line.0.max(0) as u32,
-                            tty: None,
-                            pid: None,
-                        }
-                    })
-                    .collect();
-                let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes }));
-            }
-
             IpcCommand::ResizePane { params, reply } => {
                 let result = self.handle_resize_pane(
                     params.pane_id,
@@ -205,6 +559,12 @@ impl CruxApp {
                     let fh = panel.read(cx).focus_handle(cx);
                     fh.focus(window);
                     self.active_pane = Some(params.pane_id);
+                    Workspace::emit_pane_event(
+                        crux_protocol::PaneEvent::Focused {
+                            pane_id: params.pane_id,
+                        },
+                        cx,
+                    );
                     let _ = reply.send(Ok(()));
                 } else {
                     let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", params.pane_id)));
@@ -235,39 +595,20 @@ impl CruxApp {
                             tp.remove_panel(panel_view.clone(), window, cx);
                         });
                     }
-                    self.emit_pane_event(crux_protocol::PaneEvent::Closed {
-                        pane_id: params.pane_id,
-                    });
+                    Workspace::emit_pane_event(
+                        crux_protocol::PaneEvent::Closed {
+                            pane_id: params.pane_id,
+                        },
+                        cx,
+                    );
                     let _ = reply.send(Ok(()));
                 } else {
                     let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", params.pane_id)));
                 }
             }
 
-            IpcCommand::WindowCreate { params: _, reply } => {
-                // Single-window mode: return the existing window.
-                let result = crux_protocol::WindowCreateResult {
-                    window_id: crux_protocol::WindowId(0),
-                };
-                let _ = reply.send(Ok(result));
-            }
-
-            IpcCommand::WindowList { reply } => {
-                let pane_count = self.pane_registry.len() as u32;
-                let window_info = crux_protocol::WindowInfo {
-                    window_id: crux_protocol::WindowId(0),
-                    title: "Crux".to_string(),
-                    pane_count,
-                    is_focused: true,
-                };
-                let result = crux_protocol::WindowListResult {
-                    windows: vec![window_info],
-                };
-                let _ = reply.send(Ok(result));
-            }
-
             IpcCommand::SessionSave { params, reply } => {
-                let result = self.handle_session_save(params.path, cx);
+                let result = self.handle_session_save(params, cx);
                 let _ = reply.send(result);
             }
 
@@ -438,9 +779,121 @@ impl CruxApp {
                 }
             }
 
-            IpcCommand::EventsPoll { reply } => {
-                let events = self.drain_pane_events();
-                let _ = reply.send(Ok(crux_protocol::EventsPollResult { events }));
+            IpcCommand::RunCommand { params, reply } => {
+                let Some((pane_id, panel)) = self.resolve_pane(params.pane_id, window, cx) else {
+                    let err = match params.pane_id {
+                        Some(id) => anyhow::anyhow!("pane {} not found", id),
+                        None => anyhow::anyhow!("no active pane"),
+                    };
+                    let _ = reply.send(Err(err));
+                    return;
+                };
+                let in_flight = self
+                    .running_commands
+                    .get(&pane_id)
+                    .is_some_and(|token| token.strong_count() > 0);
+                if in_flight {
+                    let _ = reply.send(Err(anyhow::anyhow!(
+                        "pane {} already has a command in progress",
+                        pane_id
+                    )));
+                    return;
+                }
+                if let Err(e) = panel.read(cx).ready_for_command(cx) {
+                    let _ = reply.send(Err(e));
+                    return;
+                }
+
+                let token = Arc::new(());
+                self.running_commands
+                    .insert(pane_id, Arc::downgrade(&token));
+                let (done_tx, done) = tokio::sync::oneshot::channel();
+                let cwd = panel.read(cx).cwd(cx);
+                // Register the capture before typing so no output is missed.
+                panel.read(cx).add_output_sink(
+                    run_command_sink(pane_id, params.format, cwd, done_tx, token),
+                    cx,
+                );
+                let line = format!("{}\r", params.command);
+                panel.update(cx, |p, cx| p.write_to_pty(line.as_bytes(), false, cx));
+                let _ = reply.send(Ok(CommandStarted { pane_id, done }));
+            }
+
+            IpcCommand::TabCreate { params, reply } => {
+                let tab_panel = match params.tab_id {
+                    Some(tab_id) => match self.tab_panel_by_id(tab_id, cx) {
+                        Some(tab_panel) => Some(tab_panel),
+                        None => {
+                            let _ = reply.send(Err(anyhow::anyhow!("tab {} not found", tab_id)));
+                            return;
+                        }
+                    },
+                    None => self.focused_tab_panel(window, cx),
+                };
+                let pane_id = self.open_tab(
+                    tab_panel,
+                    params.cwd.as_deref(),
+                    params.command.as_deref(),
+                    params.env.as_ref(),
+                    window,
+                    cx,
+                );
+                let result = self
+                    .tab_id_for_pane(pane_id, cx)
+                    .map(|tab_id| crux_protocol::TabCreateResult {
+                        window_id: self.window_id,
+                        tab_id,
+                        pane_id,
+                    })
+                    .ok_or_else(|| anyhow::anyhow!("pane {} is not in a tab", pane_id));
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::SubscribeOutput {
+                subscription,
+                reply,
+            } => {
+                let requested = subscription.pane_id();
+                if let Some((pane_id, panel)) = self.resolve_pane(requested, window, cx) {
+                    let subscription_id = subscription.id();
+                    // The sink runs on the PTY reader thread and unregisters
+                    // itself once the IPC connection drops the stream.
+                    panel
+                        .read(cx)
+                        .add_output_sink(Box::new(move |bytes| subscription.offer(bytes)), cx);
+                    let _ = reply.send(Ok(crux_protocol::SubscribeOutputResult {
+                        subscription_id,
+                        pane_id,
+                    }));
+                } else if let Some(id) = requested {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", id)));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("no active pane")));
+                }
+            }
+
+            // Answered application-wide by `dispatch_ipc_command`.
+            IpcCommand::Handshake { .. }
+            | IpcCommand::ListPanes { .. }
+            | IpcCommand::WindowCreate { .. }
+            | IpcCommand::WindowList { .. }
+            | IpcCommand::WindowClose { .. }
+            | IpcCommand::WindowFocus { .. }
+            | IpcCommand::TabList { .. }
+            | IpcCommand::TabMovePane { .. }
+            | IpcCommand::LayoutApply { .. }
+            | IpcCommand::LayoutGet { .. }
+            | IpcCommand::MuxList { .. }
+            | IpcCommand::MuxAttach { .. }
+            | IpcCommand::MuxResize { .. }
+            | IpcCommand::MuxKill { .. }
+            | IpcCommand::ConfigGetStatus { .. }
+            | IpcCommand::EventsPoll { .. }
+            | IpcCommand::EventsSubscribe { .. } => {
+                log::warn!(
+                    "workspace-level IPC command routed to window {}",
+                    self.window_id
+                );
             }
         }
     }
@@ -467,6 +920,161 @@ impl CruxApp {
         self.pane_registry.keys().next().copied()
     }
 
+    /// Size of a new pane split off `target` in `direction`, along the axis
+    /// of the split: a share of the target's extent, or a number of cells.
+    fn split_size(
+        &self,
+        target: PaneId,
+        direction: crux_protocol::SplitDirection,
+        size: crux_protocol::SplitSize,
+        cx: &App,
+    ) -> Option<Pixels> {
+        let panel = self.pane_registry.get(&target)?;
+        let terminal = panel.read(cx).terminal_view().read(cx).terminal_size();
+        let (cells, cell) = match direction {
+            crux_protocol::SplitDirection::Left | crux_protocol::SplitDirection::Right => {
+                (terminal.cols, terminal.cell_width)
+            }
+            crux_protocol::SplitDirection::Top | crux_protocol::SplitDirection::Bottom => {
+                (terminal.rows, terminal.cell_height)
+            }
+        };
+        let extent = cells as f32 * cell;
+        let pixels = match size {
+            crux_protocol::SplitSize::Percent(p) => extent * f32::from(p.min(100)) / 100.0,
+            crux_protocol::SplitSize::Cells(n) => (n as f32 * cell).min(extent),
+        };
+        Some(px(pixels))
+    }
+
+    /// `PaneInfo` for every pane of this window. A pane that is in no tab
+    /// panel (e.g. mid-move) is left out rather than reported in a tab that
+    /// does not hold it.
+    fn pane_infos(&self, window: &Window, cx: &App) -> Vec<crux_protocol::PaneInfo> {
+        let tab_ids: std::collections::HashMap<PaneId, TabId> = self
+            .tab_panels(cx)
+            .iter()
+            .flat_map(|tp| {
+                let tab_id = Self::tab_id(tp);
+                Self::tab_pane_ids(tp, cx)
+                    .into_iter()
+                    .map(move |pane_id| (pane_id, tab_id))
+            })
+            .collect();
+        self.pane_registry
+            .iter()
+            .filter_map(|(id, panel)| {
+                let Some(&tab_id) = tab_ids.get(id) else {
+                    log::warn!("pane {} is in no tab; leaving it out of the pane list", id);
+                    return None;
+                };
+                let p = panel.read(cx);
+                let view = p.terminal_view().read(cx);
+                let size = view.terminal_size();
+                let content = view.terminal_content_snapshot();
+                Some(crux_protocol::PaneInfo {
+                    pane_id: *id,
+                    window_id: self.window_id,
+                    tab_id,
+                    size: crux_protocol::PaneSize {
+                        rows: size.rows as u32,
+                        cols: size.cols as u32,
+                    },
+                    title: view.title().unwrap_or("").to_string(),
+                    cwd: view.cwd().map(|s| s.to_string()),
+                    is_active: self.is_pane_active(*id, window, cx),
+                    is_zoomed: false,
+                    cursor_x: content.cursor.point.column.0 as u32,
+                    cursor_y: content.cursor.point.line.0.max(0) as u32,
+                    tty: None,
+                    pid: None,
+                })
+            })
+            .collect()
+    }
+
+    /// `TabInfo` for every tab panel of this window.
+    fn tab_infos(&self, cx: &App) -> Vec<crux_protocol::TabInfo> {
+        self.tab_panels(cx)
+            .iter()
+            .map(|tp| {
+                let pane_ids = Self::tab_pane_ids(tp, cx);
+                let active_ids = tp
+                    .read(cx)
+                    .active_panel(cx)
+                    .map(|panel| Self::panel_pane_ids(&panel, cx))
+                    .unwrap_or_default();
+                let active_pane_id = self
+                    .active_pane
+                    .filter(|id| active_ids.contains(id))
+                    .or(active_ids.first().copied());
+                crux_protocol::TabInfo {
+                    tab_id: Self::tab_id(tp),
+                    window_id: self.window_id,
+                    is_active: self.active_pane.is_some_and(|id| pane_ids.contains(&id)),
+                    pane_ids,
+                    active_pane_id,
+                }
+            })
+            .collect()
+    }
+
+    /// Take a pane out of its tab panel and this window's registry, keeping
+    /// its terminal alive for [`Self::attach_pane`].
+    fn detach_pane(
+        &mut self,
+        pane_id: PaneId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<DetachedPane> {
+        if self.tmux_tab_for_pane(pane_id, cx).is_some() {
+            anyhow::bail!(
+                "pane {} belongs to a tmux window and cannot be moved",
+                pane_id
+            );
+        }
+        let panel = self
+            .pane_registry
+            .remove(&pane_id)
+            .ok_or_else(|| anyhow::anyhow!("pane {} not found", pane_id))?;
+        let panel_view: Arc<dyn PanelView> = Arc::new(panel.clone());
+        for tp in self.tab_panels(cx) {
+            if Self::tab_pane_ids(&tp, cx).contains(&pane_id) {
+                tp.update(cx, |tp, cx| tp.remove_panel(panel_view.clone(), window, cx));
+            }
+        }
+        self.pane_parents.remove(&pane_id);
+        if self.active_pane == Some(pane_id) {
+            self.active_pane = None;
+        }
+        Ok(DetachedPane {
+            pane_id,
+            panel,
+            running_command: self.running_commands.remove(&pane_id),
+        })
+    }
+
+    /// Add a detached pane to one of this window's tab panels.
+    fn attach_pane(
+        &mut self,
+        pane: DetachedPane,
+        tab_id: TabId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<()> {
+        let tp = self
+            .tab_panel_by_id(tab_id, cx)
+            .ok_or_else(|| anyhow::anyhow!("tab {} not found", tab_id))?;
+        let panel_view: Arc<dyn PanelView> = Arc::new(pane.panel.clone());
+        tp.update(cx, |tp, cx| tp.add_panel(panel_view, window, cx));
+        self.pane_registry.insert(pane.pane_id, pane.panel);
+        if let Some(token) = pane.running_command {
+            self.running_commands.insert(pane.pane_id, token);
+        }
+        self.active_pane = Some(pane.pane_id);
+        Ok(())
+    }
+
     /// Check if a pane currently has focus.
     fn is_pane_active(&self, pane_id: PaneId, window: &Window, cx: &App) -> bool {
         if let Some(panel) = self.pane_registry.get(&pane_id) {
@@ -646,6 +1254,42 @@ impl CruxApp {
     }
 }
 
+/// Maximum bytes of output kept for a single `crux:pane/run-command`.
+const RUN_COMMAND_MAX_OUTPUT: usize = 8 * 1024 * 1024;
+
+/// Build the PTY output sink that captures one command's OSC 133 C→D output
+/// and delivers the result. Runs on the PTY reader thread.
+fn run_command_sink(
+    pane_id: PaneId,
+    format: OutputFormat,
+    cwd: Option<String>,
+    done: tokio::sync::oneshot::Sender<RunCommandResult>,
+    token: Arc<()>,
+) -> OutputSink {
+    let mut capture = CommandCapture::new(RUN_COMMAND_MAX_OUTPUT);
+    let mut done = Some(done);
+    Box::new(move |bytes| {
+        let _in_flight = &token;
+        // The IPC side gave up (timeout or disconnect); stop capturing.
+        if done.as_ref().is_none_or(|tx| tx.is_closed()) {
+            return false;
+        }
+        let Some(captured) = capture.feed(bytes) else {
+            return true;
+        };
+        if let Some(tx) = done.take() {
+            let _ = tx.send(protocol::run_command_result(
+                pane_id,
+                format,
+                cwd.clone(),
+                captured,
+                crux_ipc::output::decode_output,
+            ));
+        }
+        false
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crux_protocol::*;
@@ -0,0 +1,81 @@
+//! Configurable key bindings.
+//!
+//! Each window feeds its key presses through a [`KeyResolver`] in the capture
+//! phase, before the focused terminal sees them. Bound keys, keys that
+//! continue a chord and keys swallowed by a mode stop there; everything else
+//! reaches the terminal as usual.
+
+use std::time::Instant;
+
+use gpui::*;
+
+use crux_config::keymap::{KeyAction, KeyPress, Keymap, Resolution};
+use crux_config::CruxConfig;
+use crux_ipc::IpcTransport;
+
+use crate::actions::command_action;
+use crate::app::CruxApp;
+
+/// The keymap of `config`. Loading already rejected invalid keymaps, so the
+/// fallback to the built-in bindings only covers configs that skipped it.
+pub(crate) fn keymap(config: &CruxConfig) -> Keymap {
+    Keymap::new(config).unwrap_or_else(|e| {
+        log::warn!("using the built-in key bindings: {}", e);
+        Keymap::default()
+    })
+}
+
+fn key_press(keystroke: &Keystroke) -> KeyPress {
+    KeyPress {
+        ctrl: keystroke.modifiers.control,
+        alt: keystroke.modifiers.alt,
+        shift: keystroke.modifiers.shift,
+        cmd: keystroke.modifiers.platform,
+        ..KeyPress::new(&keystroke.key)
+    }
+}
+
+impl CruxApp {
+    pub(crate) fn handle_key_binding(
+        &mut self,
+        event: &KeyDownEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let key = key_press(&event.keystroke);
+        match self.keys.resolve(&key, Instant::now()) {
+            Resolution::Unhandled => return,
+            Resolution::Pending | Resolution::Ignored => {}
+            Resolution::Action(action) => self.run_key_action(action, window, cx),
+        }
+        cx.stop_propagation();
+    }
+
+    fn run_key_action(&mut self, action: KeyAction, window: &mut Window, cx: &mut Context<Self>) {
+        match action {
+            KeyAction::Command(command) => window.dispatch_action(command_action(command), cx),
+            KeyAction::SendText(text) => {
+                if let Some((_, panel)) = self.resolve_pane(None, window, cx) {
+                    panel.update(cx, |panel, cx| {
+                        panel.write_to_pty(text.as_bytes(), false, cx);
+                    });
+                }
+            }
+            KeyAction::Ipc { method, params } => call_ipc(method, params),
+            KeyAction::EnterMode(_) | KeyAction::ExitMode => {
+                log::debug!("key mode: {}", self.keys.mode());
+            }
+        }
+    }
+}
+
+/// Call `method` on this instance's IPC server. The server answers from the
+/// main thread, so the call runs on its own thread.
+fn call_ipc(method: String, params: serde_json::Value) {
+    std::thread::spawn(move || {
+        let result = crux_ipc::IpcClient::connect().and_then(|client| client.call(&method, params));
+        if let Err(e) = result {
+            log::warn!("key binding {} failed: {}", method, e);
+        }
+    });
+}
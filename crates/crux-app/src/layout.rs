@@ -0,0 +1,299 @@
+//! `crux:layout/apply` and `crux:layout/get` for one window.
+//!
+//! Translates between the declarative [`LayoutNode`] schema and the
+//! window's DockArea tree.
+
+use std::sync::Arc;
+
+use gpui::*;
+use gpui_component::dock::{DockArea, DockItem, PanelInfo, PanelState, PanelView};
+
+use crux_protocol::layout::normalize_ratios;
+use crux_protocol::{LayoutAxis, LayoutNode, LayoutPane, PaneEvent, PaneId};
+
+use crate::app::{CruxApp, MAX_DOCK_DEPTH};
+use crate::dock::terminal_panel::CruxTerminalPanel;
+use crate::workspace::Workspace;
+
+/// Creates the panel for a pane of a layout being applied.
+pub(crate) type PaneSpawner<'a> = dyn FnMut(
+        &mut CruxApp,
+        PaneId,
+        &LayoutPane,
+        &mut Window,
+        &mut Context<CruxApp>,
+    ) -> Entity<CruxTerminalPanel>
+    + 'a;
+
+impl CruxApp {
+    /// Replace every pane of this window with `tree`, which must already be
+    /// validated. Returns `tree` with the new pane IDs filled in.
+    ///
+    /// Nothing is changed if a pane has a running process and `force` is
+    /// not set.
+    pub(crate) fn apply_layout(
+        &mut self,
+        tree: LayoutNode,
+        force: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<LayoutNode> {
+        self.replace_layout(
+            tree,
+            force,
+            &mut |app, pane_id, pane, window, cx| {
+                app.create_terminal_panel(
+                    pane_id,
+                    pane.cwd.as_deref(),
+                    pane.command.as_deref(),
+                    pane.env.as_ref(),
+                    window,
+                    cx,
+                )
+            },
+            window,
+            cx,
+        )
+    }
+
+    /// [`Self::apply_layout`] with the panel of each pane created by `spawn`
+    /// instead of from the pane's launch settings.
+    pub(crate) fn replace_layout(
+        &mut self,
+        mut tree: LayoutNode,
+        force: bool,
+        spawn: &mut PaneSpawner<'_>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<LayoutNode> {
+        if !force {
+            for (pane_id, panel) in &self.pane_registry {
+                if panel.update(cx, |p, cx| p.is_process_running(cx)) {
+                    anyhow::bail!(
+                        "pane {} has a running process, use force: true to replace it",
+                        pane_id
+                    );
+                }
+            }
+        }
+
+        let weak_dock = self.dock_area.downgrade();
+        let mut created = Vec::new();
+        let item = self.build_dock_item(
+            &mut tree,
+            window.viewport_size(),
+            &weak_dock,
+            &mut created,
+            spawn,
+            window,
+            cx,
+        );
+        self.dock_area.update(cx, |area, cx| {
+            area.set_center(item, window, cx);
+        });
+
+        let old: Vec<PaneId> = self
+            .pane_registry
+            .keys()
+            .copied()
+            .filter(|id| !created.contains(id))
+            .collect();
+        for pane_id in old {
+            self.pane_registry.remove(&pane_id);
+            self.running_commands.remove(&pane_id);
+            Workspace::emit_pane_event(PaneEvent::Closed { pane_id }, cx);
+        }
+        self.pane_parents.clear();
+        for &pane_id in &created {
+            Workspace::emit_pane_event(PaneEvent::Created { pane_id }, cx);
+        }
+
+        self.active_pane = created.first().copied();
+        if let Some(panel) = self.active_pane.and_then(|id| self.pane_registry.get(&id)) {
+            let inner = panel.read(cx).terminal_view().read(cx).focus_handle(cx);
+            inner.focus(window);
+        }
+        Ok(tree)
+    }
+
+    /// Build the DockItem for `node` in a space of `space`, registering a new
+    /// terminal panel for each pane and recording its ID in `node`.
+    #[allow(clippy::too_many_arguments)]
+    fn build_dock_item(
+        &mut self,
+        node: &mut LayoutNode,
+        space: Size<Pixels>,
+        weak_dock: &WeakEntity<DockArea>,
+        created: &mut Vec<PaneId>,
+        spawn: &mut PaneSpawner<'_>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> DockItem {
+        match node {
+            LayoutNode::Pane(pane) => {
+                let panel = self.open_layout_pane(pane, created, spawn, window, cx);
+                DockItem::tab(panel, weak_dock, window, cx)
+            }
+            LayoutNode::Tabs { active, panes } => {
+                let panels: Vec<Arc<dyn PanelView>> = panes
+                    .iter_mut()
+                    .map(|pane| {
+                        let panel = self.open_layout_pane(pane, created, spawn, window, cx);
+                        Arc::new(panel) as Arc<dyn PanelView>
+                    })
+                    .collect();
+                let item = DockItem::tabs(panels, weak_dock, window, cx);
+                if let DockItem::Tabs { view, .. } = &item {
+                    let active = *active;
+                    view.update(cx, |tp, cx| tp.set_active_ix(active, window, cx));
+                }
+                item
+            }
+            LayoutNode::Split {
+                axis,
+                ratios,
+                children,
+            } => {
+                let fractions = normalize_ratios(ratios.as_deref(), children.len());
+                let (axis, extent) = match axis {
+                    LayoutAxis::Horizontal => (Axis::Horizontal, space.width),
+                    LayoutAxis::Vertical => (Axis::Vertical, space.height),
+                };
+                let mut items = Vec::with_capacity(children.len());
+                let mut sizes = Vec::with_capacity(children.len());
+                for (child, fraction) in children.iter_mut().zip(fractions) {
+                    let size = extent * fraction;
+                    let child_space = match axis {
+                        Axis::Horizontal => Size {
+                            width: size,
+                            ..space
+                        },
+                        Axis::Vertical => Size {
+                            height: size,
+                            ..space
+                        },
+                    };
+                    items.push(self.build_dock_item(
+                        child,
+                        child_space,
+                        weak_dock,
+                        created,
+                        spawn,
+                        window,
+                        cx,
+                    ));
+                    sizes.push(Some(size));
+                }
+                DockItem::split_with_sizes(axis, items, sizes, weak_dock, window, cx)
+            }
+        }
+    }
+
+    fn open_layout_pane(
+        &mut self,
+        pane: &mut LayoutPane,
+        created: &mut Vec<PaneId>,
+        spawn: &mut PaneSpawner<'_>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<CruxTerminalPanel> {
+        let pane_id = Workspace::allocate_pane_id(cx);
+        let panel = spawn(self, pane_id, pane, window, cx);
+        if let Some(title) = pane.title.clone() {
+            panel.update(cx, |p, cx| p.set_title(Some(title.into()), cx));
+        }
+        self.pane_registry.insert(pane_id, panel.clone());
+        created.push(pane_id);
+        pane.pane_id = Some(pane_id);
+        panel
+    }
+
+    /// This window's panes in the `crux:layout/get` schema, with each
+    /// pane's launch command and environment, so that the tree can be
+    /// passed back to `crux:layout/apply`.
+    pub(crate) fn layout_tree(&self, cx: &App) -> anyhow::Result<LayoutNode> {
+        let state = self.dock_area.read(cx).dump(cx).center;
+        let mut tree =
+            layout_from_state(&state, 0).ok_or_else(|| anyhow::anyhow!("window has no panes"))?;
+        for spec in tree.panes_mut() {
+            let Some(panel) = spec.pane_id.and_then(|id| self.pane_registry.get(&id)) else {
+                continue;
+            };
+            let launch = panel.read(cx).launch();
+            if spec.cwd.is_none() {
+                spec.cwd = launch.cwd.clone();
+            }
+            spec.command = launch.command.clone();
+            spec.env = launch.env.clone();
+        }
+        Ok(tree)
+    }
+}
+
+/// Convert a dumped DockArea panel tree. Empty stacks and tab panels are
+/// skipped; a stack left with one child collapses into it.
+fn layout_from_state(state: &PanelState, depth: usize) -> Option<LayoutNode> {
+    if depth > MAX_DOCK_DEPTH {
+        log::warn!(
+            "layout_from_state: max depth {} exceeded, stopping recursion",
+            MAX_DOCK_DEPTH
+        );
+        return None;
+    }
+    match &state.info {
+        PanelInfo::Stack { sizes, axis } => {
+            let mut children = Vec::new();
+            let mut weights = Vec::new();
+            for (i, child) in state.children.iter().enumerate() {
+                if let Some(node) = layout_from_state(child, depth + 1) {
+                    children.push(node);
+                    weights.push(sizes.get(i).map(|s| f32::from(*s)).unwrap_or(0.0));
+                }
+            }
+            if children.len() <= 1 {
+                return children.pop();
+            }
+            let ratios = normalize_ratios(Some(&weights), children.len());
+            Some(LayoutNode::Split {
+                axis: if *axis == 0 {
+                    LayoutAxis::Horizontal
+                } else {
+                    LayoutAxis::Vertical
+                },
+                ratios: Some(ratios),
+                children,
+            })
+        }
+        PanelInfo::Tabs { active_index } => {
+            let mut panes: Vec<LayoutPane> = state
+                .children
+                .iter()
+                .filter_map(|child| match &child.info {
+                    PanelInfo::Panel(json) => Some(layout_pane(json)),
+                    _ => None,
+                })
+                .collect();
+            match panes.len() {
+                0 => None,
+                1 => panes.pop().map(LayoutNode::Pane),
+                n => Some(LayoutNode::Tabs {
+                    active: (*active_index).min(n - 1),
+                    panes,
+                }),
+            }
+        }
+        PanelInfo::Panel(json) => Some(LayoutNode::Pane(layout_pane(json))),
+        PanelInfo::Tiles { .. } => None,
+    }
+}
+
+/// Pane description from the JSON written by `CruxTerminalPanel::dump`.
+fn layout_pane(json: &serde_json::Value) -> LayoutPane {
+    let string = |key: &str| json.get(key).and_then(|v| v.as_str()).map(String::from);
+    LayoutPane {
+        pane_id: json.get("pane_id").and_then(|v| v.as_u64()).map(PaneId),
+        cwd: string("cwd"),
+        title: string("title"),
+        ..LayoutPane::default()
+    }
+}
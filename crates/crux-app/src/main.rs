@@ -1,8 +1,14 @@
 mod actions;
 mod app;
 mod cli;
+mod config_reload;
 mod dock;
 mod ipc_dispatch;
+mod keys;
+mod layout;
+mod mux;
+mod tmux;
+mod workspace;
 
 use clap::Parser;
 use gpui::*;
@@ -12,9 +18,15 @@ fn main() {
 
     let args = cli::CliArgs::parse();
 
-    // If a CLI subcommand was given, run it and exit.
-    if let Some(cli::commands::CliCommand::Cli { action }) = args.command {
-        if let Err(e) = run_cli(action) {
+    // If a subcommand was given, run it and exit.
+    if let Some(command) = args.command {
+        let result = match command {
+            cli::commands::CliCommand::Cli { action } => run_cli(action),
+            cli::commands::CliCommand::Config { action } => {
+                cli::config::run(action, &args.config_override)
+            }
+        };
+        if let Err(e) = result {
             eprintln!("error: {e}");
             std::process::exit(1);
         }
@@ -24,11 +36,31 @@ fn main() {
     // Otherwise, start the GUI application.
     crux_terminal_view::ensure_terminfo_installed();
 
-    // Load config for window dimensions.
-    let config = crux_config::CruxConfig::load().unwrap_or_else(|e| {
-        eprintln!("warning: failed to load config: {}, using defaults", e);
-        crux_config::CruxConfig::default()
-    });
+    // Resolve the config layers once; every window uses the result.
+    let loader = crux_config::layered::ConfigLoader::new().overrides(args.config_override.clone());
+    let mut load_error = None;
+    let config = match loader.load() {
+        Ok(layered) => {
+            for entry in layered.overridden() {
+                log::debug!(
+                    "config {} = {} from {}",
+                    entry.key,
+                    entry.value,
+                    entry.source
+                );
+            }
+            layered.config
+        }
+        Err(e) => {
+            eprintln!("warning: failed to load config: {}, using defaults", e);
+            load_error = Some(e);
+            crux_config::CruxConfig::default()
+        }
+    };
+
+    // Attaching to a mux session replaces whatever would have been restored.
+    let attach = args.attach.clone();
+    let restore_session = config.session.restore_on_launch && !args.no_restore && attach.is_none();
 
     let application = Application::new().with_assets(gpui_component_assets::Assets);
     application.run(move |cx: &mut App| {
@@ -37,47 +69,25 @@ fn main() {
         // Register panel factory for session restore.
         dock::terminal_panel::register(cx);
 
-        // Register keybindings.
-        cx.bind_keys([
-            KeyBinding::new("cmd-t", actions::NewTab, None),
-            KeyBinding::new("cmd-w", actions::CloseTab, None),
-            KeyBinding::new("cmd-shift-w", actions::ForceCloseTab, None),
-            KeyBinding::new("ctrl-tab", actions::NextTab, None),
-            KeyBinding::new("ctrl-shift-tab", actions::PrevTab, None),
-            KeyBinding::new("cmd-d", actions::SplitRight, None),
-            KeyBinding::new("cmd-shift-d", actions::SplitDown, None),
-            KeyBinding::new("cmd-ctrl-d", actions::WindowSplitRight, None),
-            KeyBinding::new("cmd-ctrl-shift-d", actions::WindowSplitDown, None),
-            KeyBinding::new("cmd-shift-enter", actions::ZoomPane, None),
-            KeyBinding::new("cmd-1", actions::SelectTab1, None),
-            KeyBinding::new("cmd-2", actions::SelectTab2, None),
-            KeyBinding::new("cmd-3", actions::SelectTab3, None),
-            KeyBinding::new("cmd-4", actions::SelectTab4, None),
-            KeyBinding::new("cmd-5", actions::SelectTab5, None),
-            KeyBinding::new("cmd-6", actions::SelectTab6, None),
-            KeyBinding::new("cmd-7", actions::SelectTab7, None),
-            KeyBinding::new("cmd-8", actions::SelectTab8, None),
-            KeyBinding::new("cmd-9", actions::SelectTab9, None),
-            KeyBinding::new("cmd-]", actions::FocusNextPane, None),
-            KeyBinding::new("cmd-[", actions::FocusPrevPane, None),
-            KeyBinding::new("cmd-up", actions::PrevPrompt, None),
-            KeyBinding::new("cmd-down", actions::NextPrompt, None),
-        ]);
-
-        cx.open_window(
-            WindowOptions {
-                window_bounds: Some(WindowBounds::Windowed(Bounds {
-                    origin: point(px(0.0), px(0.0)),
-                    size: size(px(config.window.width), px(config.window.height)),
-                })),
-                ..Default::default()
-            },
-            |window, cx| {
-                let view = cx.new(|cx| app::CruxApp::new(window, cx));
-                cx.new(|cx| gpui_component::Root::new(view, window, cx))
-            },
-        )
-        .expect("Failed to open main window — is Metal/display available?");
+        workspace::Workspace::init(&config, cx);
+        if let Some(e) = load_error {
+            // Reported by `crux:config/get-status` until a reload succeeds.
+            let live_config = &mut cx.global_mut::<workspace::Workspace>().live_config;
+            let _ = live_config.apply(crux_config::watcher::ConfigEvent::Error(e));
+        }
+        workspace::Workspace::watch_config(loader, cx);
+        let params = crux_protocol::WindowCreateParams {
+            title: None,
+            width: None,
+            height: None,
+        };
+        let (window_id, _, _) = workspace::Workspace::open_window(&params, cx)
+            .expect("Failed to open main window — is Metal/display available?");
+        if let Some(name) = &attach {
+            mux::attach_session(window_id, name, cx);
+        } else if restore_session {
+            workspace::Workspace::restore_last_session(window_id, cx);
+        }
     });
 }
 
@@ -86,6 +96,18 @@ fn run_cli(action: cli::commands::CliAction) -> anyhow::Result<()> {
     use crux_ipc::IpcTransport;
     use crux_protocol::*;
 
+    // Mux sessions live in the daemon, not in a Crux window.
+    let action = match action {
+        CliAction::MuxList { format } => return cli::mux::list(&format),
+        CliAction::MuxAttach {
+            name,
+            create,
+            command,
+        } => return cli::mux::attach(name, create, command),
+        CliAction::MuxKill { name } => return cli::mux::kill(&name),
+        action => action,
+    };
+
     let client = cli::client::connect()?;
 
     match action {
@@ -214,12 +236,84 @@ fn run_cli(action: cli::commands::CliAction) -> anyhow::Result<()> {
             } else {
                 for w in &result.windows {
                     println!(
-                        "Window {} | {} panes | focused={}",
-                        w.window_id, w.pane_count, w.is_focused
+                        "Window {} | {} | {} tabs | {} panes | focused={}",
+                        w.window_id, w.title, w.tab_count, w.pane_count, w.is_focused
                     );
                 }
             }
         }
+
+        CliAction::WindowClose { window_id, force } => {
+            let params = WindowCloseParams {
+                window_id: WindowId(window_id),
+                force,
+            };
+            client.call(method::WINDOW_CLOSE, serde_json::to_value(&params)?)?;
+        }
+
+        CliAction::WindowFocus { window_id } => {
+            let params = WindowFocusParams {
+                window_id: WindowId(window_id),
+            };
+            client.call(method::WINDOW_FOCUS, serde_json::to_value(&params)?)?;
+        }
+
+        CliAction::TabCreate {
+            window_id,
+            tab_id,
+            cwd,
+            command,
+        } => {
+            let params = TabCreateParams {
+                window_id: window_id.map(WindowId),
+                tab_id: tab_id.map(TabId),
+                cwd,
+                command: if command.is_empty() {
+                    None
+                } else {
+                    Some(command)
+                },
+                env: None,
+            };
+            let result = client.call(method::TAB_CREATE, serde_json::to_value(&params)?)?;
+            let result: TabCreateResult = serde_json::from_value(result)?;
+            // Print new pane ID to stdout for scripting.
+            println!("{}", result.pane_id);
+        }
+
+        CliAction::TabList { window_id, format } => {
+            let params = TabListParams {
+                window_id: window_id.map(WindowId),
+            };
+            let result = client.call(method::TAB_LIST, serde_json::to_value(&params)?)?;
+            let result: TabListResult = serde_json::from_value(result)?;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&result.tabs)?);
+            } else {
+                for t in &result.tabs {
+                    let panes: Vec<String> = t.pane_ids.iter().map(|p| p.to_string()).collect();
+                    println!(
+                        "Tab {} | window {} | panes [{}] | active={}",
+                        t.tab_id,
+                        t.window_id,
+                        panes.join(", "),
+                        t.is_active
+                    );
+                }
+            }
+        }
+
+        CliAction::TabMovePane { pane_id, tab_id } => {
+            let params = TabMovePaneParams {
+                pane_id: PaneId(pane_id),
+                tab_id: TabId(tab_id),
+            };
+            client.call(method::TAB_MOVE_PANE, serde_json::to_value(&params)?)?;
+        }
+
+        CliAction::MuxList { .. } | CliAction::MuxAttach { .. } | CliAction::MuxKill { .. } => {
+            unreachable!("handled above")
+        }
     }
 
     Ok(())
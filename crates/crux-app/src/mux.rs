@@ -0,0 +1,236 @@
+//! Mux daemon sessions shown as native panes.
+//!
+//! Attaching asks the daemon for the session's socket and rebuilds the
+//! session's `crux:layout/get` tree in the window. Each pane is a remote
+//! terminal: its screen is drawn from `crux:pane/get-text`, new output
+//! arrives through `crux:pane/subscribe-output`, and keystrokes go back with
+//! `crux:pane/send-text`. Closing the window leaves the session running in
+//! the daemon.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Weak};
+use std::time::Duration;
+
+use gpui::*;
+
+use crux_ipc::{IpcClient, IpcTransport};
+use crux_protocol::{
+    method, LayoutGetResult, LayoutNode, MuxAttachParams, MuxSessionInfo, OutputNotifyParams,
+    PaneId, WindowId,
+};
+use crux_terminal_view::{CruxTerminal, RemoteFeed, TerminalSize};
+
+use crate::dock::terminal_panel::CruxTerminalPanel;
+use crate::workspace::Workspace;
+
+/// How long a pane's output thread waits before checking that its terminal
+/// still exists.
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A session attached to, with its layout as the daemon reported it.
+struct Attached {
+    info: MuxSessionInfo,
+    layout: LayoutNode,
+}
+
+/// Replace the panes of `window_id` with the panes of mux session `name`,
+/// creating the session if needed. The daemon is contacted off the UI
+/// thread; the window keeps its panes if that fails.
+pub(crate) fn attach_session(window_id: WindowId, name: &str, cx: &mut App) {
+    let name = name.to_string();
+    // The session takes the size of the pane it replaces.
+    let size = Workspace::window(window_id, cx).and_then(|(_, app)| {
+        let app = app.read(cx);
+        let panel = app.active_pane.and_then(|id| app.pane_registry.get(&id))?;
+        Some(panel.read(cx).terminal_view_size(cx))
+    });
+    cx.spawn(async move |cx: &mut AsyncApp| {
+        let task_name = name.clone();
+        let attached = cx
+            .background_executor()
+            .spawn(async move { attach(&task_name, size) })
+            .await;
+        let result = match attached {
+            Ok(attached) => cx
+                .update(|cx| show_session(window_id, attached, cx))
+                .and_then(|shown| shown),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::warn!("failed to attach mux session {:?}: {}", name, e);
+        }
+    })
+    .detach();
+}
+
+/// Attach to (or create) session `name`, resizing it to `size` as
+/// `(rows, cols)`, and read its layout.
+fn attach(name: &str, size: Option<(u32, u32)>) -> anyhow::Result<Attached> {
+    let control = crate::cli::mux::connect(true)?;
+    let params = MuxAttachParams {
+        name: Some(name.to_string()),
+        create: true,
+        rows: size.map(|(rows, _)| rows),
+        cols: size.map(|(_, cols)| cols),
+        ..MuxAttachParams::default()
+    };
+    let info: MuxSessionInfo =
+        serde_json::from_value(control.call(method::MUX_ATTACH, serde_json::to_value(&params)?)?)?;
+    let session = IpcClient::connect_to(PathBuf::from(&info.socket_path))?;
+    let layout: LayoutGetResult =
+        serde_json::from_value(session.call(method::LAYOUT_GET, serde_json::json!({}))?)?;
+    Ok(Attached {
+        info,
+        layout: layout.layout,
+    })
+}
+
+/// Rebuild the session's layout in `window_id` with a remote terminal per
+/// session pane.
+fn show_session(window_id: WindowId, attached: Attached, cx: &mut App) -> anyhow::Result<()> {
+    let (entry, app) = Workspace::window(window_id, cx)
+        .ok_or_else(|| anyhow::anyhow!("window {} is closed", window_id))?;
+    let Attached { info, layout } = attached;
+    let socket = PathBuf::from(&info.socket_path);
+    let size = TerminalSize {
+        rows: info.rows as usize,
+        cols: info.cols as usize,
+        ..TerminalSize::default()
+    };
+    entry.handle.update(cx, |_, window, cx| {
+        app.update(cx, |app, cx| {
+            let applied = app.replace_layout(
+                layout,
+                true,
+                &mut |app, pane_id, pane, window, cx| {
+                    let Some(remote) = pane.pane_id else {
+                        return app.create_terminal_panel(pane_id, None, None, None, window, cx);
+                    };
+                    let size = TerminalSize {
+                        scrollback_lines: app.config.terminal.scrollback_lines,
+                        ..size
+                    };
+                    let input = PaneInput::spawn(socket.clone(), remote);
+                    let alive = input.alive();
+                    let (terminal, feed) = CruxTerminal::new_remote(size, Box::new(input));
+                    spawn_output_stream(socket.clone(), remote, feed, alive);
+                    let font = app.config.font.clone();
+                    let colors = app.config.colors.clone();
+                    cx.new(|cx| {
+                        CruxTerminalPanel::from_terminal(
+                            pane_id, terminal, font, colors, window, cx,
+                        )
+                    })
+                },
+                window,
+                cx,
+            );
+            cx.notify();
+            applied
+        })
+    })??;
+    log::info!("attached to mux session {}", info.name);
+    Ok(())
+}
+
+/// Draw the current screen of session pane `remote` into `feed`, then
+/// stream its output until the pane ends or `alive` says the terminal has
+/// been closed.
+fn spawn_output_stream(socket: PathBuf, remote: PaneId, feed: RemoteFeed, alive: Weak<()>) {
+    std::thread::spawn(move || {
+        let result = (|| -> anyhow::Result<()> {
+            let client = IpcClient::connect_to(socket)?;
+            client.call(
+                method::PANE_SUBSCRIBE_OUTPUT,
+                serde_json::json!({ "pane_id": remote }),
+            )?;
+            let mut screen = Vec::new();
+            crate::cli::mux::redraw(&client, Some(remote), &mut screen)?;
+            feed.feed(screen);
+            while alive.strong_count() > 0 {
+                let Some(notification) = client.next_notification(OUTPUT_POLL_INTERVAL)? else {
+                    continue;
+                };
+                if notification.method != method::PANE_OUTPUT_NOTIFY {
+                    continue;
+                }
+                let Some(params) = notification.params else {
+                    continue;
+                };
+                let params: OutputNotifyParams = serde_json::from_value(params)?;
+                if !feed.feed(params.data.into_bytes()) || params.closed {
+                    break;
+                }
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            log::warn!("mux pane {} output stream ended: {}", remote, e);
+        }
+    });
+}
+
+/// Input of a remote pane, sent to the session with `crux:pane/send-text`
+/// from a background thread so that typing never waits on the socket.
+struct PaneInput {
+    tx: mpsc::Sender<Vec<u8>>,
+    /// Dropped with the terminal, which owns its input.
+    alive: Arc<()>,
+}
+
+impl PaneInput {
+    fn spawn(socket: PathBuf, remote: PaneId) -> Self {
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut client = None;
+            // A multi-byte character split across writes is sent once complete.
+            let mut utf8_tail = Vec::new();
+            for bytes in rx {
+                let text = crux_ipc::output::decode_utf8(&mut utf8_tail, &bytes);
+                if text.is_empty() {
+                    continue;
+                }
+                let params = serde_json::json!({
+                    "pane_id": remote,
+                    "text": text,
+                    "bracketed_paste": false,
+                });
+                // Idle connections are closed by the server; reconnect once.
+                let sent = client
+                    .as_ref()
+                    .map(|c: &IpcClient| c.call(method::PANE_SEND_TEXT, params.clone()));
+                if !matches!(sent, Some(Ok(_))) {
+                    client = IpcClient::connect_to(socket.clone()).ok();
+                    if let Some(c) = &client {
+                        if let Err(e) = c.call(method::PANE_SEND_TEXT, params) {
+                            log::warn!("failed to send input to mux pane {}: {}", remote, e);
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            tx,
+            alive: Arc::new(()),
+        }
+    }
+
+    /// Handle that expires once the terminal is closed.
+    fn alive(&self) -> Weak<()> {
+        Arc::downgrade(&self.alive)
+    }
+}
+
+impl Write for PaneInput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tx
+            .send(buf.to_vec())
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
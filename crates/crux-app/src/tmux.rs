@@ -0,0 +1,369 @@
+//! tmux control mode (`tmux -CC`) shown as native Crux tabs.
+//!
+//! A pane that starts tmux in control mode becomes the session's gateway:
+//! its view emits [`TmuxNotification`]s, and the [`TmuxController`] built
+//! on the first one sends tmux commands back through its PTY. Each tmux
+//! window opens as a tab next to the gateway pane: a [`TmuxWindowPanel`]
+//! whose splits mirror the tmux layout, with one remote terminal per tmux
+//! pane. Renaming or closing the tmux window renames or closes its tab.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use gpui::*;
+use gpui_component::dock::{DockPlacement, PanelView};
+
+use crux_protocol::layout::normalize_ratios;
+use crux_protocol::{PaneEvent, PaneId, WindowId};
+use crux_terminal_view::{
+    CruxTerminalView, TerminalSize, TmuxAction, TmuxController, TmuxLayout, TmuxLayoutCell,
+    TmuxNotification, TmuxPaneId, TmuxWindowId,
+};
+
+use crate::app::CruxApp;
+use crate::dock::terminal_panel::CruxTerminalPanel;
+use crate::dock::tmux_panel::{TmuxPaneTree, TmuxWindowPanel};
+use crate::workspace::Workspace;
+
+/// Running control-mode sessions, by gateway pane.
+#[derive(Default)]
+pub(crate) struct TmuxSessions {
+    sessions: HashMap<PaneId, TmuxSession>,
+}
+
+impl Global for TmuxSessions {}
+
+struct TmuxSession {
+    controller: TmuxController,
+    /// Tab showing each tmux window.
+    tabs: HashMap<TmuxWindowId, TmuxTab>,
+}
+
+/// The tab of a tmux window and the Crux window it was opened in.
+#[derive(Clone)]
+struct TmuxTab {
+    window_id: WindowId,
+    panel: Entity<TmuxWindowPanel>,
+}
+
+impl CruxApp {
+    /// Drive a tmux control-mode session if one starts in `panel`.
+    pub(crate) fn watch_tmux(
+        pane_id: PaneId,
+        panel: &Entity<CruxTerminalPanel>,
+        cx: &mut Context<Self>,
+    ) {
+        let view = panel.read(cx).terminal_view().clone();
+        cx.subscribe(
+            &view,
+            move |_, view, notification: &TmuxNotification, cx| {
+                let notification = notification.clone();
+                // Handling may open and close tabs, including this one.
+                cx.defer(move |cx| handle_notification(pane_id, &view, notification, cx));
+            },
+        )
+        .detach();
+    }
+
+    /// The tmux window tab showing `pane_id`, if it is a tmux pane.
+    pub(crate) fn tmux_tab_for_pane(
+        &self,
+        pane_id: PaneId,
+        cx: &App,
+    ) -> Option<Entity<TmuxWindowPanel>> {
+        self.tab_panels(cx).iter().find_map(|tp| {
+            tp.read(cx)
+                .panels()
+                .iter()
+                .filter_map(|panel| panel.view().downcast::<TmuxWindowPanel>().ok())
+                .find(|tmux| tmux.read(cx).pane_ids(cx).contains(&pane_id))
+        })
+    }
+
+    /// Open tmux window `layout` as a tab next to the gateway pane.
+    fn open_tmux_tab(
+        &mut self,
+        gateway: PaneId,
+        name: String,
+        layout: &TmuxLayout,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<TmuxWindowPanel> {
+        let panes = self.open_tmux_panes(gateway, layout, window, cx);
+        let first = panes.panels().first().map(|panel| panel.read(cx).pane_id());
+        let panel = cx.new(|cx| TmuxWindowPanel::new(name.into(), panes, cx));
+
+        let tab_panel = self
+            .tab_id_for_pane(gateway, cx)
+            .and_then(|tab_id| self.tab_panel_by_id(tab_id, cx));
+        let panel_view: Arc<dyn PanelView> = Arc::new(panel.clone());
+        if let Some(tab_panel) = tab_panel {
+            tab_panel.update(cx, |tp, cx| tp.add_panel(panel_view, window, cx));
+        } else {
+            self.dock_area.update(cx, |area, cx| {
+                area.add_panel(panel_view, DockPlacement::Center, None, window, cx);
+            });
+        }
+        if first.is_some() {
+            self.active_pane = first;
+        }
+        panel
+    }
+
+    /// Replace the panes of a tmux window tab with those of `layout`.
+    fn relayout_tmux_tab(
+        &mut self,
+        gateway: PaneId,
+        panel: &Entity<TmuxWindowPanel>,
+        layout: &TmuxLayout,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let panes = self.open_tmux_panes(gateway, layout, window, cx);
+        let first = panes.panels().first().map(|panel| panel.read(cx).pane_id());
+        let old = panel.update(cx, |panel, cx| panel.replace_panes(panes, cx));
+        let old: Vec<PaneId> = old
+            .panels()
+            .into_iter()
+            .map(|panel| panel.read(cx).pane_id())
+            .collect();
+        let was_active = self.active_pane.is_some_and(|id| old.contains(&id));
+        self.forget_tmux_panes(old, cx);
+        if was_active {
+            self.active_pane = first;
+        }
+    }
+
+    /// Remove a tmux window tab and its panes.
+    fn close_tmux_tab(
+        &mut self,
+        panel: &Entity<TmuxWindowPanel>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let pane_ids = panel.read(cx).pane_ids(cx);
+        let panel_view: Arc<dyn PanelView> = Arc::new(panel.clone());
+        for tp in self.tab_panels(cx) {
+            let shown = tp
+                .read(cx)
+                .panels()
+                .iter()
+                .any(|p| p.view() == panel_view.view());
+            if shown {
+                tp.update(cx, |tp, cx| tp.remove_panel(panel_view.clone(), window, cx));
+            }
+        }
+        self.forget_tmux_panes(pane_ids, cx);
+    }
+
+    fn forget_tmux_panes(&mut self, pane_ids: Vec<PaneId>, cx: &mut Context<Self>) {
+        for pane_id in pane_ids {
+            self.pane_registry.remove(&pane_id);
+            self.running_commands.remove(&pane_id);
+            if self.active_pane == Some(pane_id) {
+                self.active_pane = None;
+            }
+            Workspace::emit_pane_event(PaneEvent::Closed { pane_id }, cx);
+        }
+    }
+
+    /// Remote terminals for the panes of `layout`, split like tmux splits
+    /// them.
+    fn open_tmux_panes(
+        &mut self,
+        gateway: PaneId,
+        layout: &TmuxLayout,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> TmuxPaneTree {
+        let (axis, children) = match &layout.cell {
+            TmuxLayoutCell::Pane(pane) => {
+                let panel = self.open_tmux_pane(gateway, *pane, layout, window, cx);
+                return TmuxPaneTree::Pane(panel);
+            }
+            TmuxLayoutCell::LeftRight(children) => (Axis::Horizontal, children),
+            TmuxLayoutCell::TopBottom(children) => (Axis::Vertical, children),
+        };
+        if let [only] = children.as_slice() {
+            return self.open_tmux_panes(gateway, only, window, cx);
+        }
+        let weights: Vec<f32> = children
+            .iter()
+            .map(|child| match axis {
+                Axis::Horizontal => child.cols.max(1) as f32,
+                Axis::Vertical => child.rows.max(1) as f32,
+            })
+            .collect();
+        let fractions = normalize_ratios(Some(&weights), children.len());
+        let children = children
+            .iter()
+            .zip(fractions)
+            .map(|(child, fraction)| (fraction, self.open_tmux_panes(gateway, child, window, cx)))
+            .collect();
+        TmuxPaneTree::Split { axis, children }
+    }
+
+    fn open_tmux_pane(
+        &mut self,
+        gateway: PaneId,
+        pane: TmuxPaneId,
+        node: &TmuxLayout,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<CruxTerminalPanel> {
+        let pane_id = Workspace::allocate_pane_id(cx);
+        let size = TerminalSize {
+            rows: node.rows,
+            cols: node.cols,
+            scrollback_lines: self.config.terminal.scrollback_lines,
+            ..TerminalSize::default()
+        };
+        let terminal = cx
+            .global::<TmuxSessions>()
+            .sessions
+            .get(&gateway)
+            .map(|session| session.controller.open_pane(pane, size));
+        let panel = match terminal {
+            Some(terminal) => {
+                let font = self.config.font.clone();
+                let colors = self.config.colors.clone();
+                cx.new(|cx| {
+                    CruxTerminalPanel::from_terminal(pane_id, terminal, font, colors, window, cx)
+                })
+            }
+            // The session ended while the tab was being built.
+            None => self.create_terminal_panel(pane_id, None, None, None, window, cx),
+        };
+        self.pane_registry.insert(pane_id, panel.clone());
+        Workspace::emit_pane_event(PaneEvent::Created { pane_id }, cx);
+        panel
+    }
+}
+
+fn handle_notification(
+    gateway: PaneId,
+    view: &Entity<CruxTerminalView>,
+    notification: TmuxNotification,
+    cx: &mut App,
+) {
+    if notification == TmuxNotification::Enter {
+        log::info!("tmux control mode started in pane {}", gateway);
+        let controller = view.read(cx).tmux_controller();
+        let sessions = &mut cx.default_global::<TmuxSessions>().sessions;
+        if let Some(old) = sessions.insert(
+            gateway,
+            TmuxSession {
+                controller,
+                tabs: HashMap::new(),
+            },
+        ) {
+            // tmux was restarted without ending the previous session.
+            for tab in old.tabs.into_values() {
+                close_tab(tab, cx);
+            }
+        }
+        return;
+    }
+
+    let Some(session) = cx
+        .default_global::<TmuxSessions>()
+        .sessions
+        .get_mut(&gateway)
+    else {
+        return;
+    };
+    let actions = session.controller.handle(notification);
+    for action in actions {
+        apply_action(gateway, action, cx);
+    }
+}
+
+fn apply_action(gateway: PaneId, action: TmuxAction, cx: &mut App) {
+    match action {
+        TmuxAction::OpenWindow {
+            window,
+            name,
+            layout,
+        } => {
+            let Some((entry, _)) = Workspace::window_for_pane(gateway, cx) else {
+                log::warn!("tmux window {} has no gateway pane to open next to", window);
+                return;
+            };
+            let window_id = entry.id;
+            let panel = update_app(window_id, cx, |app, win, cx| {
+                app.open_tmux_tab(gateway, name, &layout, win, cx)
+            });
+            match (panel, session(gateway, cx)) {
+                (Some(panel), Some(session)) => {
+                    session.tabs.insert(window, TmuxTab { window_id, panel });
+                }
+                (Some(_), None) => {}
+                (None, _) => log::warn!("failed to open tmux window {}", window),
+            }
+        }
+        TmuxAction::Relayout { window, layout } => {
+            if let Some(tab) = tab(gateway, window, cx) {
+                update_app(tab.window_id, cx, |app, win, cx| {
+                    app.relayout_tmux_tab(gateway, &tab.panel, &layout, win, cx)
+                });
+            }
+        }
+        TmuxAction::RenameWindow { window, name } => {
+            if let Some(tab) = tab(gateway, window, cx) {
+                tab.panel
+                    .update(cx, |panel, cx| panel.set_name(name.into(), cx));
+            }
+        }
+        TmuxAction::CloseWindow(window) => {
+            let closed = session(gateway, cx).and_then(|session| session.tabs.remove(&window));
+            if let Some(tab) = closed {
+                close_tab(tab, cx);
+            }
+        }
+        TmuxAction::Exit => {
+            log::info!("tmux control mode ended in pane {}", gateway);
+            cx.default_global::<TmuxSessions>()
+                .sessions
+                .remove(&gateway);
+        }
+    }
+}
+
+fn session(gateway: PaneId, cx: &mut App) -> Option<&mut TmuxSession> {
+    cx.default_global::<TmuxSessions>()
+        .sessions
+        .get_mut(&gateway)
+}
+
+fn tab(gateway: PaneId, window: TmuxWindowId, cx: &mut App) -> Option<TmuxTab> {
+    session(gateway, cx)?.tabs.get(&window).cloned()
+}
+
+/// Run `f` on the root view of `window_id`. Returns `None` if the window
+/// has been closed.
+fn update_app<R>(
+    window_id: WindowId,
+    cx: &mut App,
+    f: impl FnOnce(&mut CruxApp, &mut Window, &mut Context<CruxApp>) -> R,
+) -> Option<R> {
+    let (entry, app) = Workspace::window(window_id, cx)?;
+    entry
+        .handle
+        .update(cx, |_, window, cx| {
+            app.update(cx, |app, cx| {
+                let result = f(app, window, cx);
+                cx.notify();
+                result
+            })
+        })
+        .ok()
+}
+
+fn close_tab(tab: TmuxTab, cx: &mut App) {
+    let closed = update_app(tab.window_id, cx, |app, window, cx| {
+        app.close_tmux_tab(&tab.panel, window, cx)
+    });
+    if closed.is_none() {
+        log::debug!("window {} of a tmux tab already closed", tab.window_id);
+    }
+}
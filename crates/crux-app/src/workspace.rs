@@ -0,0 +1,463 @@
+//! Process-wide state shared by all Crux windows.
+//!
+//! Each window hosts its own [`CruxApp`] (dock, pane registry, active pane).
+//! The [`Workspace`] global owns what must outlive any single window: the
+//! IPC server and MCP child, window and pane ID allocation, the pane
+//! event stream, and session autosave.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use gpui::*;
+
+use crux_config::watcher::{ConfigWatcher, LiveConfig};
+use crux_config::{CruxConfig, SessionConfig};
+use crux_protocol::{PaneEvent, PaneId, Session, TabId, WindowCreateParams, WindowId};
+
+use crate::app::CruxApp;
+
+/// Maximum buffered events for `crux:events/poll`.
+const MAX_PANE_EVENTS: usize = 10_000;
+
+/// A window opened by Crux.
+#[derive(Clone)]
+pub(crate) struct WindowEntry {
+    pub(crate) id: WindowId,
+    pub(crate) handle: AnyWindowHandle,
+    pub(crate) app: WeakEntity<CruxApp>,
+}
+
+pub(crate) struct Workspace {
+    /// Effective configuration, resolved at launch so every window sees the
+    /// same layers and command-line overrides, and replaced on hot-reload.
+    pub(crate) live_config: LiveConfig,
+    /// Reloads `live_config` when the user config file changes.
+    pub(crate) config_watcher: Option<ConfigWatcher>,
+    /// Open windows, in creation order.
+    windows: Vec<WindowEntry>,
+    next_window_id: u64,
+    next_pane_id: u64,
+    /// Window that receives commands without an explicit target.
+    focused_window: Option<WindowId>,
+    /// Window size used when `crux:window/create` does not specify one.
+    pub(crate) default_size: Size<Pixels>,
+    /// Autosave and restore settings.
+    pub(crate) session: SessionConfig,
+    /// Buffer of pane lifecycle events drained by `crux:events/poll`.
+    pane_events: VecDeque<PaneEvent>,
+    /// Per-connection `crux:events/subscribe` queues fed by `emit_pane_event`.
+    pub(crate) event_subscribers: Vec<crux_ipc::EventSubscription>,
+    /// Kept for socket cleanup on drop.
+    _socket_path: Option<std::path::PathBuf>,
+    /// IPC server cancellation token for graceful shutdown.
+    ipc_cancel: Option<crux_ipc::CancellationToken>,
+    /// Background MCP server process.
+    mcp_process: Option<std::process::Child>,
+}
+
+impl Global for Workspace {}
+
+impl Workspace {
+    /// Start the IPC server and MCP child and install the global.
+    ///
+    /// Must run before the first window is opened.
+    pub(crate) fn init(config: &CruxConfig, cx: &mut App) {
+        // Set CRUX_SOCKET env var BEFORE starting IPC server to ensure it's set
+        // before any async tasks are spawned.
+        let socket_path_for_env = crux_ipc::socket_path();
+        // SAFETY: Called during app initialization before any background threads are spawned.
+        // No concurrent readers of this environment variable exist at this point.
+        unsafe { std::env::set_var("CRUX_SOCKET", &socket_path_for_env) };
+
+        // Start IPC server.
+        let (socket_path, ipc_rx, ipc_cancel) = match crux_ipc::start_ipc() {
+            Ok((path, rx, cancel_token)) => {
+                log::info!("IPC server started at {}", path.display());
+                (Some(path), Some(rx), Some(cancel_token))
+            }
+            Err(e) => {
+                log::error!("Failed to start IPC server: {}", e);
+                (None, None, None)
+            }
+        };
+
+        // Spawn MCP server if IPC server started successfully.
+        let mcp_process = if let Some(socket_path) = &socket_path {
+            spawn_mcp_server(socket_path)
+        } else {
+            None
+        };
+
+        cx.set_global(Self {
+            live_config: LiveConfig::new(config.clone(), CruxConfig::config_path()),
+            config_watcher: None,
+            windows: Vec::new(),
+            next_window_id: 0,
+            next_pane_id: 0,
+            focused_window: None,
+            default_size: size(px(config.window.width), px(config.window.height)),
+            session: config.session.clone(),
+            pane_events: VecDeque::new(),
+            event_subscribers: Vec::new(),
+            _socket_path: socket_path,
+            ipc_cancel,
+            mcp_process,
+        });
+
+        // IPC commands are routed to the owning window by `dispatch_ipc_command`.
+        if let Some(mut ipc_cmd_rx) = ipc_rx {
+            cx.spawn(async move |cx: &mut AsyncApp| {
+                while let Some(cmd) = ipc_cmd_rx.recv().await {
+                    let result = cx.update(|cx| crate::ipc_dispatch::dispatch_ipc_command(cmd, cx));
+                    if result.is_err() {
+                        break; // Application shutting down
+                    }
+                }
+            })
+            .detach();
+        }
+
+        if config.session.autosave {
+            let interval = Duration::from_secs(config.session.autosave_interval_secs);
+            cx.spawn(async move |cx: &mut AsyncApp| loop {
+                cx.background_executor().timer(interval).await;
+                if cx.update(Self::autosave).is_err() {
+                    break; // Application shutting down
+                }
+            })
+            .detach();
+        }
+
+        cx.on_app_quit(|cx| {
+            Self::autosave(cx);
+            cx.global_mut::<Self>().shutdown();
+            async {}
+        })
+        .detach();
+    }
+
+    /// Open a new window with a single terminal pane.
+    ///
+    /// Returns the new window's ID with the tab and pane it starts with.
+    pub(crate) fn open_window(
+        params: &WindowCreateParams,
+        cx: &mut App,
+    ) -> anyhow::Result<(WindowId, TabId, PaneId)> {
+        let workspace = cx.global_mut::<Self>();
+        let window_id = WindowId(workspace.next_window_id);
+        workspace.next_window_id += 1;
+        let default_size = workspace.default_size;
+
+        let window_size = size(
+            params
+                .width
+                .map(|w| px(w as f32))
+                .unwrap_or(default_size.width),
+            params
+                .height
+                .map(|h| px(h as f32))
+                .unwrap_or(default_size.height),
+        );
+        let title: SharedString = params.title.clone().unwrap_or_else(|| "Crux".into()).into();
+
+        let mut app = None;
+        let handle = cx.open_window(
+            WindowOptions {
+                window_bounds: Some(WindowBounds::Windowed(Bounds {
+                    origin: point(px(0.0), px(0.0)),
+                    size: window_size,
+                })),
+                titlebar: Some(TitlebarOptions {
+                    title: Some(title),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            |window, cx| {
+                let view = cx.new(|cx| CruxApp::new(window_id, window, cx));
+                app = Some(view.clone());
+                cx.new(|cx| gpui_component::Root::new(view, window, cx))
+            },
+        )?;
+        let app = app.ok_or_else(|| anyhow::anyhow!("window {} has no root view", window_id))?;
+
+        let workspace = cx.global_mut::<Self>();
+        workspace.windows.push(WindowEntry {
+            id: window_id,
+            handle: handle.into(),
+            app: app.downgrade(),
+        });
+        workspace.focused_window = Some(window_id);
+
+        let app = app.read(cx);
+        let pane_id = app
+            .active_pane
+            .ok_or_else(|| anyhow::anyhow!("window {} has no pane", window_id))?;
+        let tab_id = app
+            .tab_id_for_pane(pane_id, cx)
+            .ok_or_else(|| anyhow::anyhow!("pane {} is not in a tab", pane_id))?;
+        Ok((window_id, tab_id, pane_id))
+    }
+
+    /// Live windows in creation order. Windows the user has closed are
+    /// dropped from the list here.
+    pub(crate) fn windows(cx: &mut App) -> Vec<(WindowEntry, Entity<CruxApp>)> {
+        let workspace = cx.global_mut::<Self>();
+        let mut live = Vec::new();
+        workspace.windows.retain(|entry| match entry.app.upgrade() {
+            Some(app) => {
+                live.push((entry.clone(), app));
+                true
+            }
+            None => false,
+        });
+        live
+    }
+
+    /// Look up a window by ID.
+    pub(crate) fn window(
+        window_id: WindowId,
+        cx: &mut App,
+    ) -> Option<(WindowEntry, Entity<CruxApp>)> {
+        Self::windows(cx)
+            .into_iter()
+            .find(|(entry, _)| entry.id == window_id)
+    }
+
+    /// The window that owns `pane_id`.
+    pub(crate) fn window_for_pane(
+        pane_id: PaneId,
+        cx: &mut App,
+    ) -> Option<(WindowEntry, Entity<CruxApp>)> {
+        Self::windows(cx)
+            .into_iter()
+            .find(|(_, app)| app.read(cx).pane_registry.contains_key(&pane_id))
+    }
+
+    /// The window commands without an explicit target go to: the last
+    /// focused window, falling back to the most recently opened one.
+    pub(crate) fn focused_window(cx: &mut App) -> Option<(WindowEntry, Entity<CruxApp>)> {
+        let windows = Self::windows(cx);
+        let focused = cx.global::<Self>().focused_window;
+        let ix = windows
+            .iter()
+            .position(|(entry, _)| Some(entry.id) == focused)
+            .unwrap_or(windows.len().saturating_sub(1));
+        windows.into_iter().nth(ix)
+    }
+
+    pub(crate) fn config(&self) -> &CruxConfig {
+        self.live_config.config()
+    }
+
+    pub(crate) fn focused_window_id(&self) -> Option<WindowId> {
+        self.focused_window
+    }
+
+    pub(crate) fn set_focused_window(&mut self, window_id: WindowId) {
+        self.focused_window = Some(window_id);
+    }
+
+    /// Forget a window that is about to be removed.
+    pub(crate) fn remove_window(&mut self, window_id: WindowId) {
+        self.windows.retain(|entry| entry.id != window_id);
+        if self.focused_window == Some(window_id) {
+            self.focused_window = self.windows.last().map(|entry| entry.id);
+        }
+    }
+
+    /// Allocate the next pane ID. Pane IDs are unique across windows.
+    pub(crate) fn allocate_pane_id(cx: &mut App) -> PaneId {
+        let workspace = cx.global_mut::<Self>();
+        let id = PaneId(workspace.next_pane_id);
+        workspace.next_pane_id += 1;
+        id
+    }
+
+    /// Push a pane lifecycle event to subscribers and into the poll buffer.
+    pub(crate) fn emit_pane_event(event: PaneEvent, cx: &mut App) {
+        let workspace = cx.global_mut::<Self>();
+        // Subscriptions whose connection has closed are dropped here.
+        workspace.event_subscribers.retain(|sub| sub.offer(&event));
+
+        if workspace.pane_events.len() >= MAX_PANE_EVENTS {
+            log::warn!(
+                "pane event buffer full ({}), dropping oldest event",
+                MAX_PANE_EVENTS
+            );
+            workspace.pane_events.pop_front();
+        }
+        workspace.pane_events.push_back(event);
+    }
+
+    /// Drain all buffered pane events for consumption.
+    pub(crate) fn drain_pane_events(&mut self) -> Vec<PaneEvent> {
+        self.pane_events.drain(..).collect()
+    }
+
+    /// Write every open window to the default session file, rotating the
+    /// previous file into the backups. Does nothing if autosave is off.
+    pub(crate) fn autosave(cx: &mut App) {
+        let config = cx.global::<Self>().session.clone();
+        if !config.autosave {
+            return;
+        }
+        let windows = Self::windows(cx);
+        let result = windows
+            .iter()
+            .map(|(_, app)| {
+                app.read(cx)
+                    .session_snapshot(config.scrollback_lines, true, cx)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .and_then(|sessions| {
+                let Some(session) = Session::from_windows(sessions) else {
+                    return Ok(());
+                };
+                let json = serde_json::to_string(&session)?;
+                let path = crux_config::session::default_path();
+                Ok(crux_config::session::write(&path, &json, config.backups)?)
+            });
+        match result {
+            Ok(()) => log::debug!("autosaved {} windows", windows.len()),
+            Err(e) => log::warn!("session autosave failed: {}", e),
+        }
+    }
+
+    /// Load the newest readable autosave: its first window replaces the
+    /// panes of `window_id` and every further window opens a new one. Falls
+    /// back to the backups when the session file is missing or damaged; with
+    /// nothing to restore the window is left as is.
+    pub(crate) fn restore_last_session(window_id: WindowId, cx: &mut App) {
+        let backups = cx.global::<Self>().session.backups;
+        let path = crux_config::session::default_path();
+        let Some((found, session)) = crux_config::session::load_latest(&path, backups) else {
+            return;
+        };
+        let mut pane_count = 0;
+        for (i, session) in session.into_windows().into_iter().enumerate() {
+            let target = if i == 0 {
+                Ok(window_id)
+            } else {
+                let params = WindowCreateParams {
+                    title: None,
+                    width: None,
+                    height: None,
+                };
+                Self::open_window(&params, cx).map(|(id, _, _)| id)
+            };
+            match target.and_then(|id| Self::restore_window(id, &session, cx)) {
+                Ok(count) => pane_count += count,
+                Err(e) => log::warn!("failed to restore session {}: {}", found.display(), e),
+            }
+        }
+        cx.global_mut::<Self>().focused_window = Some(window_id);
+        log::info!("Restored {} panes from {}", pane_count, found.display());
+    }
+
+    /// Replace the panes of `window_id` with those of a single-window
+    /// `session`. Returns the number of panes restored.
+    fn restore_window(window_id: WindowId, session: &Session, cx: &mut App) -> anyhow::Result<u32> {
+        let (entry, app) = Self::window(window_id, cx)
+            .ok_or_else(|| anyhow::anyhow!("window {} is closed", window_id))?;
+        entry.handle.update(cx, |_, window, cx| {
+            app.update(cx, |app, cx| {
+                let restored = app.restore_session(session, window, cx);
+                cx.notify();
+                restored
+            })
+        })?
+    }
+
+    /// Stop the IPC server and the MCP child.
+    fn shutdown(&mut self) {
+        // Cancel IPC server gracefully
+        if let Some(cancel_token) = self.ipc_cancel.take() {
+            log::info!("Cancelling IPC server...");
+            cancel_token.cancel();
+        }
+
+        if let Some(mut child) = self.mcp_process.take() {
+            let pid = child.id();
+            log::info!("Terminating MCP server (PID {pid})...");
+            // Step 1: Send SIGTERM for graceful shutdown
+            // Safe cast: PIDs on macOS/Linux are always within i32 range.
+            let pid_i32 = i32::try_from(pid).expect("PID exceeds i32::MAX");
+            unsafe {
+                libc::kill(pid_i32, libc::SIGTERM);
+            }
+            // Step 2: Wait up to 2 seconds
+            for _ in 0..40 {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        log::info!("MCP server exited gracefully: {status}");
+                        return;
+                    }
+                    Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
+                    Err(e) => {
+                        log::warn!("Failed to check MCP server status: {e}");
+                        break;
+                    }
+                }
+            }
+            // Step 3: Force kill
+            log::warn!("MCP server did not exit gracefully, sending SIGKILL");
+            let _ = child.kill();
+            match child.wait() {
+                Ok(status) => log::info!("MCP server force-killed: {status}"),
+                Err(e) => log::warn!("Failed to wait for MCP server: {e}"),
+            }
+        }
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Attempt to spawn the crux-mcp binary next to the current executable.
+fn spawn_mcp_server(socket_path: &std::path::Path) -> Option<std::process::Child> {
+    // Find the crux-mcp binary next to the current executable.
+    let exe_path = match std::env::current_exe() {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!("Failed to get current executable path: {}", e);
+            return None;
+        }
+    };
+
+    let mcp_binary = exe_path.parent()?.join("crux-mcp");
+    if !mcp_binary.exists() {
+        log::info!(
+            "MCP server binary not found at {}, skipping auto-launch",
+            mcp_binary.display()
+        );
+        return None;
+    }
+
+    // Spawn crux-mcp with --http mode (stdio doesn't work for child processes).
+    match std::process::Command::new(&mcp_binary)
+        .arg("--http")
+        .arg("--socket")
+        .arg(socket_path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()
+    {
+        Ok(child) => {
+            log::info!(
+                "MCP server spawned at {} (PID {})",
+                mcp_binary.display(),
+                child.id()
+            );
+            Some(child)
+        }
+        Err(e) => {
+            log::warn!("Failed to spawn MCP server: {}", e);
+            None
+        }
+    }
+}
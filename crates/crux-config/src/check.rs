@@ -0,0 +1,215 @@
+//! Config file diagnostics.
+//!
+//! [`check_file`] reports every problem it can find in a config file and the
+//! files it imports, with the position of TOML errors, instead of stopping
+//! at the first one like loading does.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::layered::ConfigLoader;
+use crate::theme::{self, THEMES_DIR};
+use crate::CruxConfig;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The configuration would fail to load.
+    Error,
+    /// The configuration loads, but probably not as intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// One problem found in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The file the problem is in, if it is attributable to one.
+    pub file: Option<PathBuf>,
+    /// 1-based line and column, for syntax and type errors.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(file: Option<&Path>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            file: file.map(Path::to_path_buf),
+            position: None,
+            message: message.into(),
+        }
+    }
+}
+
+/// Formats as `file:line:column: severity: message`.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some((line, column)) = self.position {
+                write!(f, "{line}:{column}:")?;
+            }
+            f.write_str(" ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Check the config file at `path`, its imports and the configuration they
+/// resolve to. Returns no diagnostics if the file is fine.
+pub fn check_file(path: &Path) -> Vec<Diagnostic> {
+    if !path.is_file() {
+        return vec![Diagnostic::error(Some(path), "no such file")];
+    }
+
+    let mut diagnostics = Vec::new();
+    check_syntax(path, &mut Vec::new(), &mut diagnostics);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return diagnostics;
+    }
+
+    // Every file parses; what remains are value ranges, themes and import
+    // cycles, which only the full load sees.
+    let loader = ConfigLoader::empty()
+        .user_path(Some(path.to_path_buf()))
+        .themes_dir(path.parent().map(|dir| dir.join(THEMES_DIR)));
+    if let Err(e) = loader.load() {
+        diagnostics.push(Diagnostic::error(None, e.to_string()));
+    }
+    diagnostics
+}
+
+/// Parse `path` and, recursively, its imports. `chain` holds the files
+/// being checked; the load reports cycles.
+fn check_syntax(path: &Path, chain: &mut Vec<PathBuf>, out: &mut Vec<Diagnostic>) {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if chain.contains(&canonical) {
+        return;
+    }
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            out.push(Diagnostic::error(Some(path), e.to_string()));
+            return;
+        }
+    };
+    let config: CruxConfig = match toml::from_str(&contents) {
+        Ok(config) => config,
+        Err(e) => {
+            out.push(Diagnostic {
+                severity: Severity::Error,
+                file: Some(path.to_path_buf()),
+                position: e.span().map(|span| position(&contents, span.start)),
+                message: e.message().trim_end().to_string(),
+            });
+            return;
+        }
+    };
+
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for import in &config.import {
+        let import_path = dir.join(theme::expand_home(import));
+        if import_path.is_file() {
+            check_syntax(&import_path, chain, out);
+        } else {
+            out.push(Diagnostic {
+                severity: Severity::Warning,
+                file: Some(path.to_path_buf()),
+                position: None,
+                message: format!("import {import:?} not found, it will be skipped"),
+            });
+        }
+    }
+    chain.pop();
+}
+
+/// 1-based line and column of byte `offset` in `contents`.
+fn position(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("crux-config-check-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_valid_file() {
+        let dir = temp_dir("valid");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "[font]\nsize = 16.0\n").unwrap();
+        assert_eq!(check_file(&path), vec![]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let dir = temp_dir("positions");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "import = [\"colors.toml\"]\n").unwrap();
+        std::fs::write(
+            dir.join("colors.toml"),
+            "[colors]\nbackground = 0x000000\n\n[font]\n  sise = 12.0\n",
+        )
+        .unwrap();
+
+        let diagnostics = check_file(&path);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.file.as_deref(), Some(&*dir.join("colors.toml")));
+        assert_eq!(diagnostic.position, Some((5, 3)));
+        assert!(diagnostic.message.contains("sise"), "{diagnostic}");
+        assert!(diagnostic.to_string().starts_with(&format!(
+            "{}:5:3: error: ",
+            dir.join("colors.toml").display()
+        )));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_errors_and_missing_imports() {
+        let dir = temp_dir("load");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "import = [\"local.toml\"]\n[font]\nsize = 100.0\n").unwrap();
+
+        let diagnostics = check_file(&path);
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("local.toml"));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert!(diagnostics[1].message.contains("font.size"));
+
+        assert_eq!(
+            check_file(&dir.join("missing.toml"))[0].message,
+            "no such file"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(position("a = 1\nbb = 2", 0), (1, 1));
+        assert_eq!(position("a = 1\nbb = 2", 8), (2, 3));
+    }
+}
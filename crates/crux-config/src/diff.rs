@@ -0,0 +1,188 @@
+//! Differences between two configurations.
+//!
+//! A reload computes a [`ConfigDiff`] against the running configuration so
+//! only the affected subsystems are updated: a color change repaints panes
+//! without touching their fonts, and a new shell only affects panes opened
+//! afterwards.
+
+use std::collections::BTreeMap;
+
+use toml::{Table, Value};
+
+use crate::layered::collect_entries;
+use crate::CruxConfig;
+
+/// The settings that differ between two configurations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Dotted keys whose value was added, removed or changed, in key order.
+    /// Arrays such as `keybindings` are single keys.
+    pub keys: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Compare `old` with `new`.
+    pub fn between(old: &CruxConfig, new: &CruxConfig) -> Self {
+        let old = leaves(old);
+        let mut new = leaves(new);
+        let mut keys: Vec<String> = old
+            .into_iter()
+            .filter_map(|(key, value)| match new.remove(&key) {
+                Some(new_value) if new_value == value => None,
+                _ => Some(key),
+            })
+            .collect();
+        keys.extend(new.into_keys());
+        keys.sort();
+        Self { keys }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Whether any key under `section` (e.g. `"font"`) changed.
+    pub fn section_changed(&self, section: &str) -> bool {
+        self.keys.iter().any(|key| {
+            key == section
+                || key
+                    .strip_prefix(section)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    /// Font family or size: cell metrics must be remeasured.
+    pub fn font(&self) -> bool {
+        self.section_changed("font")
+    }
+
+    /// Any color: panes must be repainted.
+    pub fn colors(&self) -> bool {
+        self.section_changed("colors")
+    }
+
+    /// The scrollback limit of existing terminals.
+    pub fn scrollback(&self) -> bool {
+        self.section_changed("terminal.scrollback_lines")
+    }
+
+    /// Whether, and how large, files sent by programs are saved.
+    pub fn downloads(&self) -> bool {
+        self.section_changed("terminal.allow_downloads")
+            || self.section_changed("terminal.max_download_bytes")
+    }
+
+    /// Bindings, the leader key or the chord timeout.
+    pub fn keybindings(&self) -> bool {
+        self.section_changed("keybindings") || self.section_changed("keys")
+    }
+
+    /// Shell, arguments or environment: only affects panes opened later.
+    pub fn terminal_env(&self) -> bool {
+        ["terminal.shell", "terminal.shell_args", "terminal.env"]
+            .iter()
+            .any(|section| self.section_changed(section))
+    }
+
+    /// Window size, opacity and the like: only affects windows opened later.
+    pub fn window(&self) -> bool {
+        self.section_changed("window")
+    }
+
+    pub fn session(&self) -> bool {
+        self.section_changed("session")
+    }
+}
+
+fn leaves(config: &CruxConfig) -> BTreeMap<String, Value> {
+    let table = match Value::try_from(config) {
+        Ok(Value::Table(table)) => table,
+        _ => Table::new(),
+    };
+    let mut entries = Vec::new();
+    collect_entries(&table, "", &mut entries);
+    entries
+        .into_iter()
+        .map(|entry| (entry.key, entry.value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyBinding;
+
+    #[test]
+    fn test_identical_configs() {
+        let diff = ConfigDiff::between(&CruxConfig::default(), &CruxConfig::default());
+        assert!(diff.is_empty());
+        assert!(!diff.font());
+    }
+
+    #[test]
+    fn test_changed_sections() {
+        let old = CruxConfig::default();
+        let mut new = CruxConfig::default();
+        new.font.size = 16.0;
+        new.colors.normal[1] = 0xff0000;
+        new.colors.selection_background = Some(0x333333);
+        new.terminal.shell = Some("/bin/fish".to_string());
+        new.terminal
+            .env
+            .insert("EDITOR".to_string(), "vim".to_string());
+        new.keybindings.push(KeyBinding {
+            key: "t".to_string(),
+            mods: vec!["cmd".to_string()],
+            action: "new_tab".to_string(),
+            ..KeyBinding::default()
+        });
+
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(
+            diff.keys,
+            [
+                "colors.normal",
+                "colors.selection_background",
+                "font.size",
+                "keybindings",
+                "terminal.env.EDITOR",
+                "terminal.shell",
+            ]
+        );
+        assert!(diff.font());
+        assert!(diff.colors());
+        assert!(diff.keybindings());
+        assert!(diff.terminal_env());
+        assert!(!diff.scrollback());
+        assert!(!diff.downloads());
+        assert!(!diff.window());
+        assert!(!diff.session());
+
+        // Removals count too.
+        let back = ConfigDiff::between(&new, &old);
+        assert_eq!(back.keys, diff.keys);
+    }
+
+    #[test]
+    fn test_section_prefix_is_exact() {
+        let diff = ConfigDiff {
+            keys: vec!["terminal.shell_args".to_string()],
+        };
+        assert!(diff.section_changed("terminal"));
+        assert!(diff.section_changed("terminal.shell_args"));
+        assert!(!diff.section_changed("terminal.shell"));
+        assert!(!diff.section_changed("term"));
+    }
+
+    #[test]
+    fn test_downloads_are_off_by_default() {
+        let old = CruxConfig::default();
+        assert!(!old.terminal.allow_downloads);
+
+        let mut new = old.clone();
+        new.terminal.allow_downloads = true;
+        let diff = ConfigDiff::between(&old, &new);
+        assert!(diff.downloads());
+        assert!(!diff.terminal_env());
+    }
+}
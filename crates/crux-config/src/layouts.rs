@@ -0,0 +1,200 @@
+//! User layout templates for `crux:layout/apply`.
+//!
+//! Templates live next to the config file, one per file:
+//! `layouts/<name>.toml`. A template holds a `[layout]` table in the
+//! `crux_protocol::LayoutNode` schema and an optional `description`:
+//!
+//! ```toml
+//! description = "Editor with a shell below"
+//!
+//! [layout]
+//! type = "split"
+//! axis = "vertical"
+//! ratios = [3, 1]
+//!
+//! [[layout.children]]
+//! type = "pane"
+//! command = ["nvim"]
+//!
+//! [[layout.children]]
+//! type = "pane"
+//! ```
+//!
+//! A template shadows the built-in preset of the same name.
+
+use std::path::{Path, PathBuf};
+
+use crux_protocol::layout::{LayoutNode, LAYOUT_PRESETS};
+use serde::Deserialize;
+
+use crate::{ConfigError, CruxConfig};
+
+/// A layout template file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutTemplate {
+    /// Shown when templates are listed.
+    #[serde(default)]
+    pub description: Option<String>,
+    pub layout: LayoutNode,
+}
+
+/// Directory user templates are loaded from.
+pub fn layouts_dir() -> PathBuf {
+    CruxConfig::config_path()
+        .parent()
+        .map(|dir| dir.join("layouts"))
+        .unwrap_or_else(|| PathBuf::from("layouts"))
+}
+
+/// Resolve the layout for `crux:layout/apply` from an inline tree or a
+/// template name, and validate it.
+pub fn resolve(
+    layout: Option<LayoutNode>,
+    template: Option<&str>,
+) -> Result<LayoutNode, ConfigError> {
+    resolve_in(&layouts_dir(), layout, template)
+}
+
+/// [`resolve`] with templates loaded from `dir`.
+pub fn resolve_in(
+    dir: &Path,
+    layout: Option<LayoutNode>,
+    template: Option<&str>,
+) -> Result<LayoutNode, ConfigError> {
+    let layout = match (layout, template) {
+        (Some(layout), None) => layout,
+        (None, Some(name)) => find_in(dir, name)?,
+        _ => {
+            return Err(ConfigError::ValidationError(
+                "exactly one of layout and template is required".into(),
+            ))
+        }
+    };
+    layout.validate().map_err(ConfigError::ValidationError)?;
+    Ok(layout)
+}
+
+/// Look up a template by name in `dir`, falling back to the built-in presets.
+pub fn find_in(dir: &Path, name: &str) -> Result<LayoutNode, ConfigError> {
+    // Names map straight to file names; keep them inside `dir`.
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(ConfigError::ValidationError(format!(
+            "invalid layout template name {name:?}"
+        )));
+    }
+    let path = dir.join(format!("{name}.toml"));
+    if path.exists() {
+        return Ok(load_template(&path)?.layout);
+    }
+    LayoutNode::preset(name).ok_or_else(|| {
+        ConfigError::ValidationError(format!(
+            "unknown layout template {name:?} (presets: {})",
+            LAYOUT_PRESETS.join(", ")
+        ))
+    })
+}
+
+/// Parse one template file.
+pub fn load_template(path: &Path) -> Result<LayoutTemplate, ConfigError> {
+    let contents = std::fs::read_to_string(path)?;
+    let template: LayoutTemplate = toml::from_str(&contents)?;
+    Ok(template)
+}
+
+/// Names of the templates in `dir` followed by the presets they do not
+/// shadow, sorted within each group.
+pub fn template_names_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect();
+    names.sort();
+    for preset in LAYOUT_PRESETS {
+        if !names.iter().any(|n| n == preset) {
+            names.push(preset.to_string());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crux_protocol::LayoutAxis;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crux-layouts-{tag}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_template_shadows_preset() {
+        let dir = temp_dir("shadow");
+        std::fs::write(
+            dir.join("grid.toml"),
+            r#"
+description = "Editor over a shell"
+
+[layout]
+type = "split"
+axis = "vertical"
+ratios = [3, 1]
+
+[[layout.children]]
+type = "pane"
+command = ["nvim"]
+
+[[layout.children]]
+type = "pane"
+"#,
+        )
+        .unwrap();
+
+        let layout = find_in(&dir, "grid").unwrap();
+        match &layout {
+            LayoutNode::Split { axis, ratios, .. } => {
+                assert_eq!(*axis, LayoutAxis::Vertical);
+                assert_eq!(ratios.as_deref(), Some(&[3.0, 1.0][..]));
+            }
+            other => panic!("unexpected layout {other:?}"),
+        }
+        assert_eq!(layout.panes()[0].command, Some(vec!["nvim".to_string()]));
+
+        // Unshadowed presets still resolve.
+        assert_eq!(find_in(&dir, "two-rows").unwrap().panes().len(), 2);
+        let names = template_names_in(&dir);
+        assert_eq!(names[0], "grid");
+        assert_eq!(names.iter().filter(|n| *n == "grid").count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_resolve_rejects_bad_input() {
+        let dir = temp_dir("bad");
+        assert!(find_in(&dir, "../config").is_err());
+        assert!(find_in(&dir, "missing").is_err());
+        assert!(resolve_in(&dir, None, None).is_err());
+        assert!(resolve_in(&dir, Some(LayoutNode::pane()), Some("grid")).is_err());
+
+        std::fs::write(
+            dir.join("empty.toml"),
+            "[layout]\ntype = \"tabs\"\npanes = []\n",
+        )
+        .unwrap();
+        let err = resolve_in(&dir, None, Some("empty")).unwrap_err();
+        assert!(err.to_string().contains("no panes"), "{err}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
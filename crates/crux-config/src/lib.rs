@@ -5,13 +5,30 @@
 //!
 //! # Config file locations
 //!
-//! Priority order:
+//! The user config file is looked up in this order:
 //! 1. `$CRUX_CONFIG` environment variable
 //! 2. macOS: `~/Library/Application Support/crux/config.toml`
 //! 3. XDG: `~/.config/crux/config.toml`
-
+//!
+//! [`CruxConfig::load`] layers it over a system config and under a
+//! per-project `.crux.toml`, `CRUX_*` environment variables and command-line
+//! overrides; see [`layered`]. Config files can pull in other files with
+//! `import = ["colors.toml"]`, and `colors.theme` selects a color theme;
+//! see [`theme`].
+//!
+//! `keybindings` are resolved against the built-in bindings by [`keymap`].
+
+pub mod check;
+pub mod diff;
+pub mod keymap;
+pub mod layered;
+pub mod layouts;
+pub mod schema;
+pub mod session;
+pub mod theme;
 pub mod watcher;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -30,30 +47,52 @@ pub enum ConfigError {
 
     #[error("File watcher error: {0}")]
     WatchError(String),
+
+    #[error("Invalid theme {name}: {message}")]
+    ThemeError { name: String, message: String },
+
+    #[error("Invalid {layer} configuration: {message}")]
+    LayerError {
+        layer: layered::ConfigLayer,
+        message: String,
+    },
 }
 
 /// Main configuration structure.
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub struct CruxConfig {
+    /// Size and appearance of new windows.
     pub window: WindowConfig,
+    /// Terminal font.
     pub font: FontConfig,
+    /// Color theme and palette.
     pub colors: ColorConfig,
+    /// Shell and scrollback.
     pub terminal: TerminalConfig,
+    /// Session autosave and restore.
+    pub session: SessionConfig,
+    /// Leader key and chord settings.
+    pub keys: KeysConfig,
+    /// Custom key bindings, on top of the built-in ones.
     #[serde(default)]
     pub keybindings: Vec<KeyBinding>,
+    /// Config files merged beneath this one, in order. Relative paths are
+    /// resolved against the importing file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub import: Vec<String>,
 }
 
 impl CruxConfig {
-    /// Load configuration from the default location.
+    /// Load the layered configuration from the default locations, the
+    /// current directory and the process environment.
     ///
     /// Returns default configuration if no config file exists.
     pub fn load() -> Result<Self, ConfigError> {
-        let path = Self::config_path();
-        Self::load_from(&path)
+        Ok(layered::ConfigLoader::new().load()?.config)
     }
 
-    /// Load configuration from a specific path.
+    /// Load configuration from a specific path, with its imports and theme.
     ///
     /// Returns default configuration if the file doesn't exist.
     pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
@@ -62,11 +101,10 @@ impl CruxConfig {
             return Ok(Self::default());
         }
 
-        log::info!("Loading config from {}", path.display());
-        let contents = std::fs::read_to_string(path)?;
-        let config: CruxConfig = toml::from_str(&contents)?;
-        config.validate()?;
-        Ok(config)
+        let loader = layered::ConfigLoader::empty()
+            .user_path(Some(path.to_path_buf()))
+            .themes_dir(path.parent().map(|dir| dir.join(theme::THEMES_DIR)));
+        Ok(loader.load()?.config)
     }
 
     /// Get the config file path based on environment and platform.
@@ -130,12 +168,43 @@ impl CruxConfig {
             )));
         }
 
+        // Validate session autosave
+        if self.session.autosave_interval_secs < 5 {
+            return Err(ConfigError::ValidationError(format!(
+                "session.autosave_interval_secs must be >= 5, got {}",
+                self.session.autosave_interval_secs
+            )));
+        }
+
+        if self.session.backups > 20 {
+            return Err(ConfigError::ValidationError(format!(
+                "session.backups must be <= 20, got {}",
+                self.session.backups
+            )));
+        }
+
+        // Validate palette overrides
+        if let Some(entry) = self.colors.indexed.iter().find(|entry| entry.index < 16) {
+            return Err(ConfigError::ValidationError(format!(
+                "colors.indexed entries must have index >= 16 (use colors.normal and colors.bright), got {}",
+                entry.index
+            )));
+        }
+
+        // Validate key bindings
+        if self.keys.chord_timeout_ms == 0 {
+            return Err(ConfigError::ValidationError(
+                "keys.chord_timeout_ms must be > 0".to_string(),
+            ));
+        }
+        keymap::Keymap::new(self)?;
+
         Ok(())
     }
 }
 
 /// Window appearance configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub struct WindowConfig {
     /// Window width in pixels.
@@ -160,7 +229,7 @@ impl Default for WindowConfig {
 }
 
 /// Font configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub struct FontConfig {
     /// Primary font family name.
@@ -190,10 +259,11 @@ impl Default for FontConfig {
 }
 
 /// Color scheme configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub struct ColorConfig {
-    /// Named theme (reserved for future use).
+    /// Theme name or theme file. Colors set explicitly in the config take
+    /// precedence over the theme's; see [`theme`].
     pub theme: Option<String>,
     /// Background color (RGB hex, e.g., 0x1e1e2e).
     pub background: u32,
@@ -201,10 +271,21 @@ pub struct ColorConfig {
     pub foreground: u32,
     /// Cursor color (RGB hex).
     pub cursor: u32,
+    /// Text color under a block cursor. Defaults to the background color.
+    pub cursor_text: Option<u32>,
+    /// Selection highlight. Defaults to a translucent foreground.
+    pub selection_background: Option<u32>,
+    /// Selected text color. Defaults to the text's own color.
+    pub selection_foreground: Option<u32>,
     /// Normal ANSI colors (0-7).
     pub normal: [u32; 8],
     /// Bright ANSI colors (8-15).
     pub bright: [u32; 8],
+    /// Faint (SGR 2) ANSI colors. Derived from `normal` when unset.
+    pub dim: Option<[u32; 8]>,
+    /// Overrides for palette entries 16-255, which otherwise follow the
+    /// xterm color cube and gray ramp.
+    pub indexed: Vec<IndexedColor>,
 }
 
 impl Default for ColorConfig {
@@ -215,6 +296,9 @@ impl Default for ColorConfig {
             background: 0x1e1e2e,
             foreground: 0xcdd6f4,
             cursor: 0xf5e0dc,
+            cursor_text: None,
+            selection_background: None,
+            selection_foreground: None,
             normal: [
                 0x1e1e2e, // black
                 0xf38ba8, // red
@@ -235,12 +319,67 @@ impl Default for ColorConfig {
                 0x89dceb, // bright cyan
                 0xffffff, // bright white
             ],
+            dim: None,
+            indexed: Vec::new(),
         }
     }
 }
 
+impl ColorConfig {
+    /// The full 256-color palette: `normal` and `bright`, then the xterm
+    /// 6x6x6 color cube and 24-step gray ramp, with `indexed` applied on top.
+    pub fn palette(&self) -> [u32; 256] {
+        let mut palette = [0; 256];
+        palette[..8].copy_from_slice(&self.normal);
+        palette[8..16].copy_from_slice(&self.bright);
+        for (i, color) in palette.iter_mut().enumerate().skip(16) {
+            *color = xterm_color(i as u8);
+        }
+        for entry in &self.indexed {
+            palette[entry.index as usize] = entry.color;
+        }
+        palette
+    }
+
+    /// `dim`, or `normal` at two-thirds brightness.
+    pub fn dim_colors(&self) -> [u32; 8] {
+        self.dim.unwrap_or_else(|| self.normal.map(dim_color))
+    }
+
+    /// The foreground at two-thirds brightness.
+    pub fn dim_foreground(&self) -> u32 {
+        dim_color(self.foreground)
+    }
+}
+
+/// One palette entry override.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct IndexedColor {
+    /// Palette index (16-255).
+    pub index: u8,
+    /// Color (RGB hex).
+    pub color: u32,
+}
+
+/// Default color of palette entry `index` (16-255).
+fn xterm_color(index: u8) -> u32 {
+    if index >= 232 {
+        let level = 8 + 10 * (index as u32 - 232);
+        return level << 16 | level << 8 | level;
+    }
+    let index = index as u32 - 16;
+    let level = |n: u32| if n == 0 { 0 } else { 55 + 40 * n };
+    level(index / 36) << 16 | level(index / 6 % 6) << 8 | level(index % 6)
+}
+
+fn dim_color(color: u32) -> u32 {
+    let scale = |shift: u32| ((color >> shift & 0xff) * 2 / 3) << shift;
+    scale(16) | scale(8) | scale(0)
+}
+
 /// Terminal behavior configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub struct TerminalConfig {
     /// Scrollback history size in lines.
@@ -258,6 +397,13 @@ pub struct TerminalConfig {
     /// Additional environment variables to pass to the shell.
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Save files that programs send with iTerm2's `inline=0` to
+    /// ~/Downloads. Off by default: any program writing to the terminal,
+    /// including one on a remote host, could otherwise drop files there.
+    /// Project configs cannot turn it on.
+    pub allow_downloads: bool,
+    /// Largest file saved when `allow_downloads` is on, in bytes.
+    pub max_download_bytes: usize,
 }
 
 impl Default for TerminalConfig {
@@ -267,21 +413,98 @@ impl Default for TerminalConfig {
             shell: None,
             shell_args: vec!["-l".to_string()],
             env: HashMap::new(),
+            allow_downloads: false,
+            max_download_bytes: 16 * 1024 * 1024,
         }
     }
 }
 
-/// Keybinding configuration.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Session autosave and restore configuration.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub struct SessionConfig {
+    /// Restore the last autosaved session when Crux starts. Off by default,
+    /// so a new launch does not bring back old panes and their scrollback
+    /// unasked; `crux:session/load` restores a session on demand.
+    pub restore_on_launch: bool,
+    /// Save the session periodically and on quit, to
+    /// `~/.config/crux/session.json`. The file holds each pane's working
+    /// directory and up to `scrollback_lines` of its scrollback, so it is
+    /// created readable by the user only (mode 0600).
+    pub autosave: bool,
+    /// Seconds between periodic autosaves.
+    pub autosave_interval_secs: u64,
+    /// Previous autosaves kept as `session.json.1`, `session.json.2`, ...
+    pub backups: usize,
+    /// Scrollback lines saved per pane (0 = none).
+    pub scrollback_lines: u32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            restore_on_launch: false,
+            autosave: true,
+            autosave_interval_secs: 60,
+            backups: 3,
+            scrollback_lines: crux_protocol::session::DEFAULT_SCROLLBACK_LINES,
+        }
+    }
+}
+
+/// Leader key and chord configuration.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub struct KeysConfig {
+    /// Key that bindings with the "leader" modifier follow (e.g., "ctrl-a").
+    pub leader: Option<String>,
+    /// How long the leader key or a chord waits for the next key, in
+    /// milliseconds.
+    pub chord_timeout_ms: u64,
+    /// Drop the built-in key bindings, keeping only `keybindings`.
+    pub disable_defaults: bool,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            leader: None,
+            chord_timeout_ms: 1000,
+            disable_defaults: false,
+        }
+    }
+}
+
+/// Keybinding configuration. See [`keymap`] for the available actions.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct KeyBinding {
-    /// Key combination (e.g., "cmd-t", "ctrl-shift-c").
+    /// Key combination (e.g., "cmd-t", "ctrl-shift-c"), or a chord of
+    /// several separated by spaces (e.g., "ctrl-k ctrl-c").
     pub key: String,
-    /// Modifiers (e.g., ["cmd"], ["ctrl", "shift"]).
+    /// Modifiers for the last key (e.g., ["cmd"], ["ctrl", "shift"]).
+    /// "leader" puts the leader key in front.
     #[serde(default)]
     pub mods: Vec<String>,
-    /// Action to perform (e.g., "new_tab", "close_tab").
+    /// Action to perform (e.g., "new_tab", "split_right", "send_text",
+    /// "ipc", "enter_mode", "exit_mode"), or "none" to unbind the key.
     pub action: String,
+    /// Mode the binding is active in. Unset for the default mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Text written to the pane by "send_text".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// IPC method called by "ipc" (e.g., "crux:pane/split").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Parameters of the "ipc" method.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub params: Option<toml::Value>,
+    /// Mode entered by "enter_mode".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
 }
 
 #[cfg(test)]
@@ -327,6 +550,17 @@ mod tests {
         // Invalid opacity
         config.window.opacity = 1.5;
         assert!(config.validate().is_err());
+
+        config.window.opacity = 1.0;
+        assert!(config.validate().is_ok());
+
+        // Invalid autosave settings
+        config.session.autosave_interval_secs = 1;
+        assert!(config.validate().is_err());
+
+        config.session.autosave_interval_secs = 60;
+        config.session.backups = 100;
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -342,12 +576,19 @@ size = 16.0
 
 [terminal]
 scrollback_lines = 50000
+
+[session]
+restore_on_launch = true
+backups = 5
 "#;
 
         let config: CruxConfig = toml::from_str(toml).unwrap();
         assert_eq!(config.window.width, 1024.0);
         assert_eq!(config.font.family, "JetBrains Mono");
         assert_eq!(config.terminal.scrollback_lines, 50_000);
+        assert!(config.session.restore_on_launch);
+        assert_eq!(config.session.backups, 5);
+        assert!(config.session.autosave);
     }
 
     #[test]
@@ -374,6 +615,32 @@ unknown_field = "oops"
         assert_eq!(config.colors.bright[0], 0x585b70); // bright black
     }
 
+    #[test]
+    fn test_palette() {
+        let mut colors = ColorConfig::default();
+        colors.indexed.push(IndexedColor {
+            index: 200,
+            color: 0x123456,
+        });
+        let palette = colors.palette();
+        assert_eq!(palette[1], 0xf38ba8);
+        assert_eq!(palette[9], 0xeba0ac);
+        assert_eq!(palette[16], 0x000000);
+        assert_eq!(palette[21], 0x0000ff);
+        assert_eq!(palette[196], 0xff0000);
+        assert_eq!(palette[200], 0x123456);
+        assert_eq!(palette[232], 0x080808);
+        assert_eq!(palette[255], 0xeeeeee);
+        assert_eq!(colors.dim_colors()[7], 0x888ea2);
+
+        let mut config = CruxConfig::default();
+        config
+            .colors
+            .indexed
+            .push(IndexedColor { index: 3, color: 0 });
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_missing_config_file_uses_defaults() {
         let path = PathBuf::from("/nonexistent/config.toml");
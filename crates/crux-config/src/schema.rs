@@ -0,0 +1,173 @@
+//! JSON Schema and annotated default configuration.
+//!
+//! Both are generated from [`CruxConfig`] and its doc comments, so they stay
+//! in step with the settings the parser accepts. Editors that understand
+//! JSON Schema (e.g. Taplo, or VS Code's Even Better TOML) can use
+//! [`json_schema`] for completion and validation of `config.toml`.
+
+use serde_json::Value as Json;
+use toml::{Table, Value};
+
+use crate::CruxConfig;
+
+/// JSON Schema (draft 2020-12) of the configuration file.
+pub fn json_schema() -> Json {
+    schemars::schema_for!(CruxConfig).to_value()
+}
+
+/// The default configuration as TOML, with every setting preceded by its
+/// description. Settings without a default are included commented out.
+pub fn annotated_default() -> String {
+    let schema = json_schema();
+    let defaults = match Value::try_from(CruxConfig::default()) {
+        Ok(Value::Table(table)) => table,
+        _ => Table::new(),
+    };
+    let top_level = properties(&schema, &schema);
+
+    let mut out = String::from(
+        "# Crux configuration\n\
+         #\n\
+         # Every setting is shown with its default value. Commented-out\n\
+         # settings have no default. Keep only what you want to change.\n",
+    );
+
+    // Top-level keys must come before the first [section] header.
+    let (sections, keys): (Vec<_>, Vec<_>) = top_level
+        .iter()
+        .partition(|(_, property)| resolve(&schema, property).get("properties").is_some());
+    for (key, property) in keys {
+        out.push('\n');
+        push_setting(&mut out, "", key, property, defaults.get(key));
+    }
+    for (section, property) in sections {
+        out.push('\n');
+        push_description(&mut out, property);
+        out.push_str(&format!("[{section}]\n"));
+        let values = match defaults.get(section) {
+            Some(Value::Table(table)) => table.clone(),
+            _ => Table::new(),
+        };
+        for (key, field) in properties(&schema, property) {
+            push_setting(&mut out, section, key, field, values.get(key));
+        }
+    }
+    out
+}
+
+/// The `properties` of `schema`, following a `$ref` into `$defs`.
+fn properties<'a>(root: &'a Json, schema: &'a Json) -> Vec<(&'a str, &'a Json)> {
+    match resolve(root, schema).get("properties") {
+        Some(Json::Object(properties)) => properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn resolve<'a>(root: &'a Json, schema: &'a Json) -> &'a Json {
+    let target = schema
+        .get("$ref")
+        .and_then(Json::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        .and_then(|name| root.get("$defs")?.get(name));
+    target.unwrap_or(schema)
+}
+
+fn push_setting(out: &mut String, section: &str, key: &str, schema: &Json, value: Option<&Value>) {
+    push_description(out, schema);
+    match value {
+        Some(value) => out.push_str(&format!("{key} = {}\n", render(section, value))),
+        None if is_array(schema) => out.push_str(&format!("# {key} = []\n")),
+        None => out.push_str(&format!("# {key} =\n")),
+    }
+}
+
+fn push_description(out: &mut String, schema: &Json) {
+    let Some(description) = schema.get("description").and_then(Json::as_str) else {
+        return;
+    };
+    // Drop rustdoc link brackets: "[`theme`]" reads as "`theme`".
+    let description = description.replace("[`", "`").replace("`]", "`");
+    for line in description.lines() {
+        if line.is_empty() {
+            out.push_str("#\n");
+        } else {
+            out.push_str(&format!("# {line}\n"));
+        }
+    }
+}
+
+fn is_array(schema: &Json) -> bool {
+    match schema.get("type") {
+        Some(Json::String(ty)) => ty == "array",
+        Some(Json::Array(types)) => types.iter().any(|ty| ty == "array"),
+        _ => false,
+    }
+}
+
+/// Colors are written in hex, as users write them.
+fn render(section: &str, value: &Value) -> String {
+    match value {
+        Value::Integer(color) if section == "colors" => format!("0x{color:06x}"),
+        Value::Array(items) if section == "colors" => {
+            let items: Vec<String> = items.iter().map(|item| render(section, item)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Map;
+
+    /// Every key of the schema's objects that lacks a description, as
+    /// `Type.field`.
+    fn undocumented(schema: &Json) -> Vec<String> {
+        let mut objects: Vec<(String, &Map<String, Json>)> = Vec::new();
+        if let Some(Json::Object(properties)) = schema.get("properties") {
+            objects.push(("CruxConfig".to_string(), properties));
+        }
+        if let Some(Json::Object(defs)) = schema.get("$defs") {
+            for (name, def) in defs {
+                if let Some(Json::Object(properties)) = def.get("properties") {
+                    objects.push((name.clone(), properties));
+                }
+            }
+        }
+        objects
+            .into_iter()
+            .flat_map(|(name, properties)| {
+                properties
+                    .iter()
+                    .filter(|(_, property)| property.get("description").is_none())
+                    .map(move |(key, _)| format!("{name}.{key}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_setting_is_documented() {
+        assert_eq!(undocumented(&json_schema()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_annotated_default_parses_to_the_default() {
+        let text = annotated_default();
+        let parsed: CruxConfig = toml::from_str(&text).unwrap();
+        assert_eq!(
+            Value::try_from(&parsed).unwrap(),
+            Value::try_from(CruxConfig::default()).unwrap()
+        );
+
+        assert!(text.contains("# Font size in points.\nsize = 14.0\n"));
+        assert!(text.contains("background = 0x1e1e2e\n"));
+        assert!(text.contains("# shell =\n"));
+        assert!(text.contains("# import = []\n"));
+        // Top-level keys precede every section.
+        assert!(text.find("keybindings = []").unwrap() < text.find("[colors]").unwrap());
+    }
+}
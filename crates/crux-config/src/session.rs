@@ -0,0 +1,200 @@
+//! Session files on disk: the default location, crash-safe writes and
+//! rotating backups.
+//!
+//! Autosave keeps the last [`SessionConfig::backups`](crate::SessionConfig)
+//! files next to the session as `session.json.1` (newest) through
+//! `session.json.N`. Every write goes to a temporary file in the same
+//! directory that is then renamed over the target, so a crash mid-write
+//! leaves the previous file intact. Sessions include scrollback, so the
+//! files are only readable by their owner.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crux_protocol::Session;
+
+use crate::ConfigError;
+
+/// Distinguishes the temporary files of saves running at the same time.
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+/// Held while backups are rotated and the new file is moved into place, so
+/// concurrent saves do not rename each other's files away.
+static ROTATION: Mutex<()> = Mutex::new(());
+
+/// Default session file: `~/.config/crux/session.json` on every platform.
+pub fn default_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".config/crux/session.json")
+}
+
+/// Path of backup number `n` (1 = newest) of `path`.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Atomically replace `path` with `contents`, first shifting the current
+/// file into the newest of `backups` rotating backups.
+pub fn write(path: &Path, contents: &str, backups: usize) -> Result<(), ConfigError> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().ok_or_else(|| {
+        ConfigError::ValidationError(format!("not a file path: {}", path.display()))
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        let _rotation = ROTATION.lock().unwrap_or_else(|e| e.into_inner());
+
+        if backups > 0 && path.exists() {
+            for n in (1..backups).rev() {
+                let from = backup_path(path, n);
+                if from.exists() {
+                    std::fs::rename(&from, backup_path(path, n + 1))?;
+                }
+            }
+            std::fs::rename(path, backup_path(path, 1))?;
+        }
+        std::fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    Ok(result?)
+}
+
+/// Load the newest readable session: `path` itself, then its backups from
+/// newest to oldest. Files that are missing, unreadable or fail to parse are
+/// skipped with a warning.
+pub fn load_latest(path: &Path, backups: usize) -> Option<(PathBuf, Session)> {
+    std::iter::once(path.to_path_buf())
+        .chain((1..=backups).map(|n| backup_path(path, n)))
+        .find_map(|candidate| {
+            let json = std::fs::read_to_string(&candidate).ok()?;
+            match Session::from_json(&json) {
+                Ok(session) => Some((candidate, session)),
+                Err(e) => {
+                    log::warn!("ignoring session file {}: {}", candidate.display(), e);
+                    None
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crux_protocol::LayoutNode;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crux-session-{tag}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn session_json(cwd: &str) -> String {
+        let mut layout = LayoutNode::pane();
+        layout.panes_mut()[0].cwd = Some(cwd.into());
+        serde_json::to_string(&Session::new(layout, Vec::new())).unwrap()
+    }
+
+    #[test]
+    fn test_write_rotates_backups() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("session.json");
+        for i in 0..4 {
+            write(&path, &format!("{i}"), 2).unwrap();
+        }
+
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "3");
+        assert_eq!(read(backup_path(&path, 1)), "2");
+        assert_eq!(read(backup_path(&path, 2)), "1");
+        assert!(!backup_path(&path, 3).exists());
+        // No temporary files are left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("private");
+        let path = dir.join("session.json");
+        write(&path, "{}", 1).unwrap();
+        write(&path, "{}", 1).unwrap();
+        for file in [path.clone(), backup_path(&path, 1)] {
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file.display());
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        let dir = temp_dir("concurrent");
+        let path = dir.join("session.json");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || write(&path, &format!("{i}"), 2))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        let saved: usize = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert!(saved < 8);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_latest_falls_back_to_backup() {
+        let dir = temp_dir("latest");
+        let path = dir.join("session.json");
+        assert!(load_latest(&path, 2).is_none());
+
+        write(&path, &session_json("/good"), 2).unwrap();
+        write(&path, "{ truncated", 2).unwrap();
+        let (found, session) = load_latest(&path, 2).unwrap();
+        assert_eq!(found, backup_path(&path, 1));
+        assert_eq!(session.layout.panes()[0].cwd.as_deref(), Some("/good"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
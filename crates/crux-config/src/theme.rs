@@ -0,0 +1,654 @@
+//! Color themes.
+//!
+//! `colors.theme` names a theme or points at a theme file:
+//!
+//! ```toml
+//! [colors]
+//! theme = "dracula"
+//! ```
+//!
+//! A name is looked up in the `themes` directory next to the user config
+//! file (`<name>.toml`, `<name>.itermcolors`, `<name>.yaml`, `<name>.yml`),
+//! then among the bundled themes. Anything that looks like a path is read
+//! directly. Colors set in the config itself win over the theme's.
+//!
+//! Theme files can be Crux TOML (a `[colors]` table), Alacritty TOML,
+//! iTerm2 `.itermcolors` property lists or Base16 YAML schemes.
+
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::{ColorConfig, ConfigError, CruxConfig};
+
+/// Name of the themes directory next to the user config file.
+pub const THEMES_DIR: &str = "themes";
+
+/// Themes shipped with Crux, in Crux TOML.
+const BUNDLED: &[(&str, &str)] = &[
+    (
+        "catppuccin-latte",
+        include_str!("../themes/catppuccin-latte.toml"),
+    ),
+    (
+        "catppuccin-mocha",
+        include_str!("../themes/catppuccin-mocha.toml"),
+    ),
+    ("dracula", include_str!("../themes/dracula.toml")),
+    ("gruvbox-dark", include_str!("../themes/gruvbox-dark.toml")),
+    ("nord", include_str!("../themes/nord.toml")),
+    (
+        "solarized-dark",
+        include_str!("../themes/solarized-dark.toml"),
+    ),
+    (
+        "solarized-light",
+        include_str!("../themes/solarized-light.toml"),
+    ),
+];
+
+/// Extensions tried, in order, when looking a theme up by name.
+const EXTENSIONS: &[&str] = &["toml", "itermcolors", "yaml", "yml"];
+
+/// Names of the bundled themes.
+pub fn bundled() -> impl Iterator<Item = &'static str> {
+    BUNDLED.iter().map(|(name, _)| *name)
+}
+
+/// The user themes directory.
+pub fn themes_dir() -> PathBuf {
+    let config_path = CruxConfig::config_path();
+    config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(THEMES_DIR)
+}
+
+/// A theme file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeFormat {
+    /// A Crux config file containing only a `[colors]` table.
+    Crux,
+    /// Alacritty's `[colors.primary]`, `[colors.normal]`, ... tables.
+    Alacritty,
+    /// An iTerm2 `.itermcolors` property list.
+    ITerm2,
+    /// A Base16 scheme with `base00` to `base0F`.
+    Base16,
+}
+
+impl ThemeFormat {
+    /// Guess the format from the file extension, and for TOML from its
+    /// contents.
+    pub fn detect(path: &Path, contents: &str) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("itermcolors") | Some("plist") => ThemeFormat::ITerm2,
+            Some("yaml") | Some("yml") => ThemeFormat::Base16,
+            _ => {
+                let alacritty = contents
+                    .parse::<Table>()
+                    .ok()
+                    .and_then(|table| table.get("colors")?.get("primary").cloned())
+                    .is_some();
+                if alacritty {
+                    ThemeFormat::Alacritty
+                } else {
+                    ThemeFormat::Crux
+                }
+            }
+        }
+    }
+}
+
+/// A parsed theme.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// File the theme was read from; `None` for bundled themes.
+    pub path: Option<PathBuf>,
+    /// The `colors` keys the theme sets.
+    values: Table,
+}
+
+impl Theme {
+    /// Resolve `spec` as described in the [module docs](self). `themes_dir`
+    /// is searched for names; relative paths are resolved against it too.
+    pub fn find(spec: &str, themes_dir: Option<&Path>) -> Result<Self, ConfigError> {
+        let looks_like_path = spec.contains('/')
+            || spec.starts_with('~')
+            || Path::new(spec)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext) || ext == "plist");
+        if looks_like_path {
+            let path = expand_home(spec);
+            let path = match themes_dir {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path,
+            };
+            return Self::from_file(&path);
+        }
+
+        if let Some(dir) = themes_dir {
+            for ext in EXTENSIONS {
+                let path = dir.join(format!("{spec}.{ext}"));
+                if path.is_file() {
+                    return Self::from_file(&path);
+                }
+            }
+        }
+
+        match BUNDLED.iter().find(|(name, _)| *name == spec) {
+            Some((name, contents)) => Self::parse(name, contents, ThemeFormat::Crux),
+            None => Err(theme_error(spec, "no such theme")),
+        }
+    }
+
+    /// Read a theme file, detecting its format.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| theme_error(&name, format!("{}: {}", path.display(), e)))?;
+        let mut theme = Self::parse(&name, &contents, ThemeFormat::detect(path, &contents))?;
+        theme.path = Some(path.to_path_buf());
+        Ok(theme)
+    }
+
+    /// Parse theme `contents` in `format`.
+    pub fn parse(name: &str, contents: &str, format: ThemeFormat) -> Result<Self, ConfigError> {
+        let values = match format {
+            ThemeFormat::Crux => parse_crux(contents),
+            ThemeFormat::Alacritty => parse_alacritty(contents),
+            ThemeFormat::ITerm2 => parse_iterm2(contents),
+            ThemeFormat::Base16 => parse_base16(contents),
+        }
+        .map_err(|message| theme_error(name, message))?;
+
+        let mut config = Table::new();
+        config.insert("colors".to_string(), Value::Table(values.clone()));
+        if let Err(e) = Value::Table(config).try_into::<CruxConfig>() {
+            return Err(theme_error(name, e.to_string().trim_end()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path: None,
+            values,
+        })
+    }
+
+    /// The `colors` keys the theme sets, as config values.
+    pub fn values(&self) -> &Table {
+        &self.values
+    }
+
+    /// The default colors with the theme applied.
+    pub fn colors(&self) -> ColorConfig {
+        let mut colors = match Value::try_from(ColorConfig::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+        colors.extend(self.values.clone());
+        // Checked in `parse`.
+        Value::Table(colors).try_into().unwrap_or_default()
+    }
+
+    /// Where the theme came from, for display.
+    pub fn origin(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => format!("bundled theme {}", self.name),
+        }
+    }
+}
+
+fn theme_error(name: &str, message: impl Into<String>) -> ConfigError {
+    ConfigError::ThemeError {
+        name: name.to_string(),
+        message: message.into(),
+    }
+}
+
+/// `~/...` relative to `$HOME`; anything else unchanged.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => match std::env::var("HOME") {
+            Ok(home) => PathBuf::from(home).join(rest),
+            Err(_) => PathBuf::from(path),
+        },
+        None => PathBuf::from(path),
+    }
+}
+
+fn parse_crux(contents: &str) -> Result<Table, String> {
+    let mut table = contents.parse::<Table>().map_err(|e| e.to_string())?;
+    let colors = match table.remove("colors") {
+        Some(Value::Table(colors)) => colors,
+        Some(_) => return Err("colors must be a table".to_string()),
+        None => Table::new(),
+    };
+    if let Some(key) = table.keys().next() {
+        return Err(format!("theme files may only set colors, found {key:?}"));
+    }
+    if colors.contains_key("theme") {
+        return Err("a theme cannot set colors.theme".to_string());
+    }
+    Ok(colors)
+}
+
+/// `"#rrggbb"` or `"0xrrggbb"`.
+fn parse_hex(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let hex = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+fn color_value(color: u32) -> Value {
+    Value::Integer(color as i64)
+}
+
+fn palette_value(colors: [u32; 8]) -> Value {
+    Value::Array(colors.iter().map(|&color| color_value(color)).collect())
+}
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+fn parse_alacritty(contents: &str) -> Result<Table, String> {
+    let table = contents.parse::<Table>().map_err(|e| e.to_string())?;
+    let colors = table
+        .get("colors")
+        .and_then(Value::as_table)
+        .ok_or("missing [colors] table")?;
+    // Alacritty also accepts keywords like "CellBackground"; only
+    // literal colors carry over.
+    let color = |section: &str, key: &str| -> Option<u32> {
+        parse_hex(colors.get(section)?.get(key)?.as_str()?)
+    };
+    let palette = |section: &str| -> Option<[u32; 8]> {
+        let mut palette = [0; 8];
+        for (slot, name) in palette.iter_mut().zip(ANSI_NAMES) {
+            *slot = color(section, name)?;
+        }
+        Some(palette)
+    };
+
+    let mut values = Table::new();
+    let mut set = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            values.insert(key.to_string(), value);
+        }
+    };
+    set(
+        "background",
+        color("primary", "background").map(color_value),
+    );
+    set(
+        "foreground",
+        color("primary", "foreground").map(color_value),
+    );
+    set("cursor", color("cursor", "cursor").map(color_value));
+    set("cursor_text", color("cursor", "text").map(color_value));
+    set(
+        "selection_background",
+        color("selection", "background").map(color_value),
+    );
+    set(
+        "selection_foreground",
+        color("selection", "text").map(color_value),
+    );
+    set("normal", palette("normal").map(palette_value));
+    set("bright", palette("bright").map(palette_value));
+    set("dim", palette("dim").map(palette_value));
+
+    let indexed: Vec<Value> = colors
+        .get("indexed_colors")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let index = entry.get("index")?.as_integer()?;
+            let color = parse_hex(entry.get("color")?.as_str()?)?;
+            let mut table = Table::new();
+            table.insert("index".to_string(), Value::Integer(index));
+            table.insert("color".to_string(), color_value(color));
+            Some(Value::Table(table))
+        })
+        .collect();
+    if !indexed.is_empty() {
+        values.insert("indexed".to_string(), Value::Array(indexed));
+    }
+    Ok(values)
+}
+
+fn parse_iterm2(contents: &str) -> Result<Table, String> {
+    // Exported files carry the Apple plist DOCTYPE.
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let doc =
+        roxmltree::Document::parse_with_options(contents, options).map_err(|e| e.to_string())?;
+    let dict = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("dict"))
+        .ok_or("missing top-level <dict>")?;
+
+    // The top-level dict alternates <key>name</key> and <dict>color</dict>.
+    let mut colors = Vec::new();
+    let mut elements = dict.children().filter(|node| node.is_element());
+    while let Some(key) = elements.next() {
+        let Some(value) = elements.next() else {
+            break;
+        };
+        if key.has_tag_name("key") && value.has_tag_name("dict") {
+            if let (Some(name), Some(color)) = (key.text(), iterm2_color(value)) {
+                colors.push((name.to_string(), color));
+            }
+        }
+    }
+    let color = |name: &str| -> Option<u32> {
+        colors
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, color)| *color)
+    };
+    let palette = |offset: usize| -> Option<[u32; 8]> {
+        let mut palette = [0; 8];
+        for (i, slot) in palette.iter_mut().enumerate() {
+            *slot = color(&format!("Ansi {} Color", offset + i))?;
+        }
+        Some(palette)
+    };
+
+    let mut values = Table::new();
+    for (key, name) in [
+        ("background", "Background Color"),
+        ("foreground", "Foreground Color"),
+        ("cursor", "Cursor Color"),
+        ("cursor_text", "Cursor Text Color"),
+        ("selection_background", "Selection Color"),
+        ("selection_foreground", "Selected Text Color"),
+    ] {
+        if let Some(color) = color(name) {
+            values.insert(key.to_string(), color_value(color));
+        }
+    }
+    if let Some(normal) = palette(0) {
+        values.insert("normal".to_string(), palette_value(normal));
+    }
+    if let Some(bright) = palette(8) {
+        values.insert("bright".to_string(), palette_value(bright));
+    }
+    if values.is_empty() {
+        return Err("no colors found".to_string());
+    }
+    Ok(values)
+}
+
+/// A color dict with `Red Component` etc. as reals in 0.0-1.0.
+fn iterm2_color(dict: roxmltree::Node) -> Option<u32> {
+    let mut rgb = [None; 3];
+    let mut elements = dict.children().filter(|node| node.is_element());
+    while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+        let slot = match key.text()? {
+            "Red Component" => 0,
+            "Green Component" => 1,
+            "Blue Component" => 2,
+            _ => continue,
+        };
+        let component: f64 = value.text()?.trim().parse().ok()?;
+        rgb[slot] = Some((component.clamp(0.0, 1.0) * 255.0).round() as u32);
+    }
+    let [r, g, b] = rgb;
+    Some(r? << 16 | g? << 8 | b?)
+}
+
+/// Base16 schemes, flat (`base00: "1d1f21"`) or nested under `palette:`.
+/// Only the `baseXX` keys matter, so this reads them line by line instead
+/// of pulling in a YAML parser.
+fn parse_base16(contents: &str) -> Result<Table, String> {
+    let mut base = [None; 16];
+    for line in contents.lines() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let Some(index) = key
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .strip_prefix("base0")
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+        else {
+            continue;
+        };
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or(""),
+            _ => value.split_whitespace().next().unwrap_or(""),
+        };
+        let color = parse_hex(value).ok_or_else(|| format!("invalid color for {key}"))?;
+        base[index as usize] = Some(color);
+    }
+    let mut colors = [0; 16];
+    for (i, (slot, color)) in colors.iter_mut().zip(base).enumerate() {
+        *slot = color.ok_or_else(|| format!("missing base{i:02X}"))?;
+    }
+    let base = |i: usize| colors[i];
+
+    let mut values = Table::new();
+    values.insert("background".to_string(), color_value(base(0x00)));
+    values.insert("foreground".to_string(), color_value(base(0x05)));
+    values.insert("cursor".to_string(), color_value(base(0x05)));
+    values.insert("cursor_text".to_string(), color_value(base(0x00)));
+    values.insert("selection_background".to_string(), color_value(base(0x02)));
+    let accents = [0x08, 0x0B, 0x0A, 0x0D, 0x0E, 0x0C];
+    let palette = |black: usize, white: usize| {
+        let mut palette = [base(black); 8];
+        for (slot, accent) in palette[1..7].iter_mut().zip(accents) {
+            *slot = base(accent);
+        }
+        palette[7] = base(white);
+        palette_value(palette)
+    };
+    values.insert("normal".to_string(), palette(0x00, 0x05));
+    values.insert("bright".to_string(), palette(0x03, 0x07));
+    // The base16-shell layout of the extra shades.
+    let indexed = [
+        (16, 0x09),
+        (17, 0x0F),
+        (18, 0x01),
+        (19, 0x02),
+        (20, 0x04),
+        (21, 0x06),
+    ]
+    .into_iter()
+    .map(|(index, shade)| {
+        let mut table = Table::new();
+        table.insert("index".to_string(), Value::Integer(index));
+        table.insert("color".to_string(), color_value(base(shade)));
+        Value::Table(table)
+    })
+    .collect();
+    values.insert("indexed".to_string(), Value::Array(indexed));
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_themes_parse() {
+        for name in bundled() {
+            let theme = Theme::find(name, None).unwrap();
+            assert_eq!(theme.path, None);
+            assert!(theme.values().contains_key("background"), "{name}");
+        }
+        let mocha = Theme::find("catppuccin-mocha", None).unwrap().colors();
+        let defaults = ColorConfig::default();
+        assert_eq!(mocha.palette(), defaults.palette());
+        assert_eq!(mocha.cursor, defaults.cursor);
+
+        let err = Theme::find("no-such-theme", None).unwrap_err();
+        assert!(matches!(err, ConfigError::ThemeError { .. }), "{err}");
+    }
+
+    #[test]
+    fn test_themes_dir_shadows_bundled() {
+        let dir = std::env::temp_dir().join(format!("crux-themes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("dracula.toml"),
+            "[colors]\nbackground = 0x101010\n",
+        )
+        .unwrap();
+
+        let theme = Theme::find("dracula", Some(&dir)).unwrap();
+        assert_eq!(theme.path, Some(dir.join("dracula.toml")));
+        assert_eq!(theme.colors().background, 0x101010);
+        assert_eq!(theme.colors().foreground, ColorConfig::default().foreground);
+
+        std::fs::write(dir.join("bad.toml"), "[font]\nsize = 12.0\n").unwrap();
+        assert!(Theme::find("bad.toml", Some(&dir)).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_alacritty_theme() {
+        let contents = r##"
+[colors.primary]
+background = "#101112"
+foreground = "#e0e1e2"
+
+[colors.cursor]
+text = "CellBackground"
+cursor = "#ff0000"
+
+[colors.selection]
+text = "#000000"
+background = "#444444"
+
+[colors.normal]
+black = "#000000"
+red = "#aa0000"
+green = "#00aa00"
+yellow = "#aaaa00"
+blue = "#0000aa"
+magenta = "#aa00aa"
+cyan = "#00aaaa"
+white = "#aaaaaa"
+
+[[colors.indexed_colors]]
+index = 16
+color = "#ff8800"
+"##;
+        let path = Path::new("theme.toml");
+        assert_eq!(ThemeFormat::detect(path, contents), ThemeFormat::Alacritty);
+        let colors = Theme::parse("alacritty", contents, ThemeFormat::Alacritty)
+            .unwrap()
+            .colors();
+        assert_eq!(colors.background, 0x101112);
+        assert_eq!(colors.cursor, 0xff0000);
+        assert_eq!(colors.cursor_text, None);
+        assert_eq!(colors.selection_background, Some(0x444444));
+        assert_eq!(colors.selection_foreground, Some(0x000000));
+        assert_eq!(colors.normal[1], 0xaa0000);
+        // Missing sections keep the defaults.
+        assert_eq!(colors.bright, ColorConfig::default().bright);
+        assert_eq!(colors.palette()[16], 0xff8800);
+    }
+
+    #[test]
+    fn test_iterm2_theme() {
+        let component =
+            |name: &str, value: f64| format!("<key>{name} Component</key><real>{value}</real>");
+        let color = |key: &str, r: f64, g: f64, b: f64| {
+            format!(
+                "<key>{key}</key><dict><key>Color Space</key><string>sRGB</string>{}{}{}</dict>",
+                component("Red", r),
+                component("Green", g),
+                component("Blue", b)
+            )
+        };
+        let mut entries = String::new();
+        entries.push_str(&color("Background Color", 0.0, 0.0, 0.0));
+        entries.push_str(&color("Foreground Color", 1.0, 1.0, 1.0));
+        entries.push_str(&color("Selection Color", 0.2, 0.2, 0.2));
+        for i in 0..16 {
+            entries.push_str(&color(
+                &format!("Ansi {i} Color"),
+                i as f64 / 15.0,
+                0.0,
+                1.0,
+            ));
+        }
+        let contents = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0"><dict>{entries}</dict></plist>"#
+        );
+        assert_eq!(
+            ThemeFormat::detect(Path::new("x.itermcolors"), &contents),
+            ThemeFormat::ITerm2
+        );
+        let colors = Theme::parse("iterm", &contents, ThemeFormat::ITerm2)
+            .unwrap()
+            .colors();
+        assert_eq!(colors.background, 0x000000);
+        assert_eq!(colors.foreground, 0xffffff);
+        assert_eq!(colors.selection_background, Some(0x333333));
+        assert_eq!(colors.normal[0], 0x0000ff);
+        assert_eq!(colors.bright[7], 0xff00ff);
+        assert_eq!(colors.cursor, ColorConfig::default().cursor);
+    }
+
+    #[test]
+    fn test_base16_theme() {
+        let contents = r#"
+system: "base16"
+name: "Example"
+palette:
+  base00: "181818" # background
+  base01: "282828"
+  base02: "383838"
+  base03: "585858"
+  base04: "b8b8b8"
+  base05: "d8d8d8"
+  base06: "e8e8e8"
+  base07: "f8f8f8"
+  base08: "ab4642"
+  base09: "dc9656"
+  base0A: "f7ca88"
+  base0B: "a1b56c"
+  base0C: "86c1b9"
+  base0D: "7cafc2"
+  base0E: "ba8baf"
+  base0F: '#a16946'
+"#;
+        let colors = Theme::parse("base16", contents, ThemeFormat::Base16)
+            .unwrap()
+            .colors();
+        assert_eq!(colors.background, 0x181818);
+        assert_eq!(colors.foreground, 0xd8d8d8);
+        assert_eq!(colors.selection_background, Some(0x383838));
+        assert_eq!(colors.normal[1], 0xab4642);
+        assert_eq!(colors.normal[2], 0xa1b56c);
+        assert_eq!(colors.bright[0], 0x585858);
+        assert_eq!(colors.bright[7], 0xf8f8f8);
+        assert_eq!(colors.palette()[17], 0xa16946);
+
+        let err = Theme::parse("partial", "base00: \"000000\"\n", ThemeFormat::Base16);
+        assert!(err.unwrap_err().to_string().contains("base01"));
+    }
+}
@@ -1,17 +1,22 @@
 //! File-system watcher for hot-reloading configuration changes.
 //!
-//! Watches the config file's parent directory (to handle atomic saves where
-//! editors write a temp file then rename it) and debounces events with a
-//! 500ms window. Consumers receive [`ConfigEvent`] values over a
-//! [`std::sync::mpsc::Receiver`].
+//! Watches the parent directories of the config file and of every file it
+//! pulls in (to handle atomic saves where editors write a temp file then
+//! rename it) and debounces events with a 500ms window. Consumers receive
+//! [`ConfigEvent`] values over a [`std::sync::mpsc::Receiver`] and feed them
+//! to a [`LiveConfig`], which keeps the running configuration when a reload
+//! fails.
 
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 
 use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
 
-use crate::{ConfigError, CruxConfig};
+use crate::diff::ConfigDiff;
+use crate::layered::{ConfigLoader, LayeredConfig};
+use crate::{theme, ConfigError, CruxConfig};
 
 /// Events emitted by the [`ConfigWatcher`].
 #[derive(Debug)]
@@ -24,8 +29,8 @@ pub enum ConfigEvent {
 
 /// Watches a configuration file for changes and sends reload events.
 ///
-/// The watcher monitors the parent directory of the config file so that
-/// atomic save operations (write-to-temp + rename) are detected correctly.
+/// The watcher monitors the parent directories of the config file, its
+/// imports and the theme file so that atomic save operations (write-to-temp + rename) are detected correctly.
 /// Events are debounced with a 500ms window to avoid redundant reloads.
 ///
 /// # Drop behavior
@@ -34,7 +39,7 @@ pub enum ConfigEvent {
 /// The receiver channel will then yield `Err(RecvError)` on the next read.
 pub struct ConfigWatcher {
     /// Kept alive to maintain the watch; dropped on `ConfigWatcher` drop.
-    _watcher: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
+    _watcher: Arc<Mutex<Option<Debouncer>>>,
 }
 
 impl ConfigWatcher {
@@ -44,6 +49,20 @@ impl ConfigWatcher {
     /// If the config file does not exist yet, the watcher still monitors
     /// the parent directory and will fire when the file is created.
     pub fn new(config_path: PathBuf) -> Result<(Self, mpsc::Receiver<ConfigEvent>), ConfigError> {
+        let loader = ConfigLoader::empty()
+            .user_path(Some(config_path.clone()))
+            .themes_dir(config_path.parent().map(|dir| dir.join(theme::THEMES_DIR)));
+        Self::with_loader(config_path, loader)
+    }
+
+    /// Watch `config_path` and every other file `loader` reads (imports,
+    /// other layers and the theme file), reloading every layer of `loader`
+    /// when one of them changes. `loader` should read `config_path` as one
+    /// of its layers. The set of watched files follows each reload.
+    pub fn with_loader(
+        config_path: PathBuf,
+        loader: ConfigLoader,
+    ) -> Result<(Self, mpsc::Receiver<ConfigEvent>), ConfigError> {
         let (event_tx, event_rx) = mpsc::channel();
 
         // Determine the directory to watch.
@@ -57,39 +76,53 @@ impl ConfigWatcher {
             std::fs::create_dir_all(&watch_dir).map_err(ConfigError::IoError)?;
         }
 
-        // Canonicalize watch_dir so event paths match our canonical config path.
-        let watch_dir = watch_dir.canonicalize().unwrap_or(watch_dir);
-
         // Canonicalize the config path to resolve symlinks (e.g. /var → /private/var on macOS).
         // Fall back to the original path if canonicalization fails (file may not exist yet).
         let canonical_config = config_path
             .canonicalize()
             .unwrap_or_else(|_| config_path.clone());
-        let config_path_for_closure = canonical_config.clone();
+        let mut watched = WatchedFiles::new(canonical_config.clone());
+        if let Ok(layered) = loader.load() {
+            watched.set_loaded(&layered);
+        }
+
+        // The handler re-registers directory watches after each reload, so
+        // it needs the debouncer. It holds a weak reference to avoid keeping
+        // the watcher alive after `ConfigWatcher` is dropped.
+        let debouncer_slot: Arc<Mutex<Option<Debouncer>>> = Arc::new(Mutex::new(None));
+        let weak_slot = Arc::downgrade(&debouncer_slot);
+        let mut handler_watched = watched.clone();
 
         let mut debouncer = new_debouncer(
             Duration::from_millis(500),
             move |result: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
                 match result {
                     Ok(events) => {
-                        // Check if any event relates to our config file.
+                        // Check if any event relates to a file we read.
                         // Compare using canonicalized paths to handle symlinks.
                         let config_changed = events.iter().any(|e| {
-                            e.kind == DebouncedEventKind::Any
-                                && (e.path == config_path_for_closure
-                                    || e.path.canonicalize().ok().as_ref()
-                                        == Some(&config_path_for_closure))
+                            e.kind == DebouncedEventKind::Any && handler_watched.contains(&e.path)
                         });
-
-                        if config_changed {
-                            let event =
-                                match CruxConfig::load_from(config_path_for_closure.as_path()) {
-                                    Ok(config) => ConfigEvent::Reloaded(Box::new(config)),
-                                    Err(e) => ConfigEvent::Error(e),
-                                };
-                            // Ignore send error — receiver may have been dropped.
-                            let _ = event_tx.send(event);
+                        if !config_changed {
+                            return;
                         }
+
+                        let event = match loader.load() {
+                            Ok(layered) => {
+                                let old_dirs = handler_watched.dirs();
+                                handler_watched.set_loaded(&layered);
+                                if let Some(slot) = weak_slot.upgrade() {
+                                    let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+                                    if let Some(debouncer) = slot.as_mut() {
+                                        rewatch(debouncer, &old_dirs, &handler_watched.dirs());
+                                    }
+                                }
+                                ConfigEvent::Reloaded(Box::new(layered.config))
+                            }
+                            Err(e) => ConfigEvent::Error(e),
+                        };
+                        // Ignore send error — receiver may have been dropped.
+                        let _ = event_tx.send(event);
                     }
                     Err(e) => {
                         let _ = event_tx
@@ -100,26 +133,165 @@ impl ConfigWatcher {
         )
         .map_err(|e| ConfigError::WatchError(e.to_string()))?;
 
-        debouncer
-            .watcher()
-            .watch(&watch_dir, notify::RecursiveMode::NonRecursive)
-            .map_err(|e| ConfigError::WatchError(e.to_string()))?;
+        for dir in watched.dirs() {
+            debouncer
+                .watcher()
+                .watch(&dir, notify::RecursiveMode::NonRecursive)
+                .map_err(|e| ConfigError::WatchError(e.to_string()))?;
+        }
+        *debouncer_slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(debouncer);
 
         log::info!(
-            "Config watcher started for {} (canonical: {})",
+            "Config watcher started for {} (canonical: {}) and {} other file(s)",
             config_path.display(),
-            canonical_config.display()
+            canonical_config.display(),
+            watched.files.len() - 1
         );
 
         Ok((
             Self {
-                _watcher: debouncer,
+                _watcher: debouncer_slot,
             },
             event_rx,
         ))
     }
 }
 
+type Debouncer = notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>;
+
+/// Canonical paths of the files a reload depends on.
+#[derive(Debug, Clone)]
+struct WatchedFiles {
+    /// The config file given to the watcher, watched even while missing.
+    config: PathBuf,
+    /// `config` and every file the latest successful load read.
+    files: BTreeSet<PathBuf>,
+}
+
+impl WatchedFiles {
+    fn new(config: PathBuf) -> Self {
+        let files = BTreeSet::from([config.clone()]);
+        Self { config, files }
+    }
+
+    /// Replace the watched files with those `layered` was read from.
+    fn set_loaded(&mut self, layered: &LayeredConfig) {
+        self.files = std::iter::once(self.config.clone())
+            .chain(
+                layered
+                    .files()
+                    .iter()
+                    .map(|(_, path)| path.canonicalize().unwrap_or_else(|_| path.clone())),
+            )
+            .collect();
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || path
+                .canonicalize()
+                .is_ok_and(|canonical| self.files.contains(&canonical))
+    }
+
+    /// Directories to watch: the parents of the files, so that atomic
+    /// saves (write to a temporary file, then rename) are seen.
+    fn dirs(&self) -> BTreeSet<PathBuf> {
+        self.files
+            .iter()
+            .map(|file| {
+                file.parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."))
+                    .to_path_buf()
+            })
+            .collect()
+    }
+}
+
+/// Move directory watches from `old` to `new`.
+fn rewatch(debouncer: &mut Debouncer, old: &BTreeSet<PathBuf>, new: &BTreeSet<PathBuf>) {
+    let watcher = debouncer.watcher();
+    for dir in old.difference(new) {
+        if let Err(e) = watcher.unwatch(dir) {
+            log::debug!("failed to stop watching {}: {}", dir.display(), e);
+        }
+    }
+    for dir in new.difference(old) {
+        if let Err(e) = watcher.watch(dir, notify::RecursiveMode::NonRecursive) {
+            log::warn!("failed to watch {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// The running configuration and the outcome of the latest reload.
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    config: CruxConfig,
+    path: PathBuf,
+    reloads: u64,
+    last_changed: Vec<String>,
+    last_error: Option<String>,
+}
+
+impl LiveConfig {
+    /// Track `config`, loaded from `path`.
+    pub fn new(config: CruxConfig, path: PathBuf) -> Self {
+        Self {
+            config,
+            path,
+            reloads: 0,
+            last_changed: Vec::new(),
+            last_error: None,
+        }
+    }
+
+    pub fn config(&self) -> &CruxConfig {
+        &self.config
+    }
+
+    /// The watched config file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Successful reloads so far.
+    pub fn reloads(&self) -> u64 {
+        self.reloads
+    }
+
+    /// Keys changed by the latest successful reload.
+    pub fn last_changed(&self) -> &[String] {
+        &self.last_changed
+    }
+
+    /// Why the latest reload failed, if it did. Cleared by the next
+    /// successful reload.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Apply a watcher event. A reload replaces the configuration and
+    /// returns what changed; an error keeps the current configuration and
+    /// returns the message.
+    pub fn apply(&mut self, event: ConfigEvent) -> Result<ConfigDiff, String> {
+        match event {
+            ConfigEvent::Reloaded(config) => {
+                let diff = ConfigDiff::between(&self.config, &config);
+                self.config = *config;
+                self.reloads += 1;
+                self.last_changed = diff.keys.clone();
+                self.last_error = None;
+                Ok(diff)
+            }
+            ConfigEvent::Error(e) => {
+                let message = e.to_string();
+                self.last_error = Some(message.clone());
+                Err(message)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,6 +349,73 @@ mod tests {
         let _ = std::fs::remove_dir_all(&tmp_dir);
     }
 
+    #[test]
+    fn test_config_watcher_follows_imports() {
+        let tmp_dir =
+            std::env::temp_dir().join(format!("crux-watcher-import-test-{}", std::process::id()));
+        let parts = tmp_dir.join("parts");
+        let other = tmp_dir.join("other");
+        std::fs::create_dir_all(&parts).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        let config_path = tmp_dir.join("config.toml");
+        std::fs::write(&config_path, "import = [\"parts/font.toml\"]\n").unwrap();
+        std::fs::write(parts.join("font.toml"), "[font]\nsize = 13.0\n").unwrap();
+
+        let (_watcher, rx) = ConfigWatcher::new(config_path.clone()).unwrap();
+        // One save can be reported in more than one debounced batch, so
+        // earlier reloads are skipped.
+        let expect_size = |size: f32| loop {
+            match rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(ConfigEvent::Reloaded(config)) if config.font.size == size => break,
+                Ok(ConfigEvent::Reloaded(_)) => continue,
+                Ok(ConfigEvent::Error(e)) => panic!("Expected Reloaded, got Error: {}", e),
+                Err(e) => panic!("Timed out waiting for config event: {}", e),
+            }
+        };
+
+        // An imported file in another directory triggers a reload.
+        std::thread::sleep(Duration::from_millis(1000));
+        std::fs::write(parts.join("font.toml"), "[font]\nsize = 15.0\n").unwrap();
+        expect_size(15.0);
+
+        // Newly imported files are watched after the reload.
+        std::fs::write(other.join("font.toml"), "[font]\nsize = 17.0\n").unwrap();
+        std::fs::write(&config_path, "import = [\"other/font.toml\"]\n").unwrap();
+        expect_size(17.0);
+        std::thread::sleep(Duration::from_millis(1000));
+        std::fs::write(other.join("font.toml"), "[font]\nsize = 19.0\n").unwrap();
+        expect_size(19.0);
+
+        // Cleanup.
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_live_config_keeps_config_on_error() {
+        let mut live = LiveConfig::new(CruxConfig::default(), PathBuf::from("config.toml"));
+
+        let mut config = CruxConfig::default();
+        config.font.size = 18.0;
+        let diff = live.apply(ConfigEvent::Reloaded(Box::new(config))).unwrap();
+        assert_eq!(diff.keys, ["font.size"]);
+        assert_eq!(live.reloads(), 1);
+
+        let err = live
+            .apply(ConfigEvent::Error(ConfigError::ValidationError(
+                "font.size must be between 6.0 and 72.0, got 100".to_string(),
+            )))
+            .unwrap_err();
+        assert!(err.contains("font.size"));
+        assert_eq!(live.last_error(), Some(err.as_str()));
+        assert_eq!(live.config().font.size, 18.0);
+        assert_eq!(live.last_changed(), ["font.size"]);
+
+        let diff = live.apply(ConfigEvent::Reloaded(Box::default())).unwrap();
+        assert!(diff.font());
+        assert_eq!(live.last_error(), None);
+        assert_eq!(live.reloads(), 2);
+    }
+
     #[test]
     fn test_config_watcher_nonexistent_dir_created() {
         let tmp_dir = std::env::temp_dir().join(format!(
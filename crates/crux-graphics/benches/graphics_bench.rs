@@ -98,7 +98,7 @@ fn bench_placement_query(c: &mut Criterion) {
         for p in 0..2u32 {
             let mut placement = ImagePlacement::new(ImageId(i));
             placement.placement_id = p;
-            placement.row = (i as i32) * 2 + p as i32;
+            placement.row = i64::from(i) * 2 + i64::from(p);
             placement.z_index = if p == 0 { -1 } else { 1 };
             mgr.place_image(placement).unwrap();
         }
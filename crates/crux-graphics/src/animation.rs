@@ -0,0 +1,330 @@
+//! Animated images: frame timing and composition.
+//!
+//! An animation is a list of frames the size of the image, each shown for
+//! its gap before the next one. Playback has no clock of its own: the
+//! timeline is anchored the first time a frame is asked for after playback
+//! (re)started, and advanced by the timestamps the renderer asks with.
+
+use std::time::{Duration, Instant};
+
+use crate::types::ImageData;
+
+/// Gap of a frame the client gave none for, in milliseconds.
+pub const DEFAULT_GAP_MS: u32 = 40;
+
+/// Whether an image's frames are being played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimationState {
+    /// Show the current frame only.
+    #[default]
+    Stopped,
+    /// Play, then wait at the last frame for more frames to arrive.
+    Loading,
+    /// Play, looping as often as the loop count allows.
+    Running,
+}
+
+/// How a frame's pixels are combined with the frame beneath.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Composition {
+    /// Blend using the alpha channel.
+    #[default]
+    AlphaBlend,
+    /// Replace the pixels, alpha included.
+    Overwrite,
+}
+
+/// The frame an image shows at a point in time.
+#[derive(Debug, Clone)]
+pub struct CurrentFrame {
+    /// The frame's pixels.
+    pub data: std::sync::Arc<ImageData>,
+    /// Index of the frame, 0 being the image itself.
+    pub index: usize,
+    /// When another frame is due, if the animation is playing.
+    pub next_change: Option<Instant>,
+}
+
+/// Playback position of an image's frames.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Timeline {
+    pub(crate) state: AnimationState,
+    /// Passes through the frames before playback stops at the last one.
+    /// `None` loops forever.
+    loops: Option<u32>,
+    /// Passes left of `loops`, counting the one in progress.
+    passes_left: Option<u32>,
+    /// The frame being shown.
+    current: usize,
+    /// When `current` started to show; `None` until the next query.
+    epoch: Option<Instant>,
+    /// Loading playback reached the last frame and waits for more.
+    waiting: bool,
+}
+
+impl Timeline {
+    /// Change the playback state. The current frame starts over, and
+    /// playback gets all its loops again.
+    pub(crate) fn set_state(&mut self, state: AnimationState) {
+        self.state = state;
+        self.passes_left = self.loops;
+        self.epoch = None;
+        self.waiting = false;
+    }
+
+    pub(crate) fn set_loops(&mut self, loops: Option<u32>) {
+        self.loops = loops;
+        self.passes_left = loops;
+    }
+
+    /// Jump to frame `index`, which starts showing at the next query.
+    pub(crate) fn set_current(&mut self, index: usize) {
+        self.current = index;
+        self.epoch = None;
+        self.waiting = false;
+    }
+
+    /// Frames were appended: playback waiting for them moves on.
+    pub(crate) fn frames_added(&mut self) {
+        if self.waiting {
+            self.set_current(self.current + 1);
+        }
+    }
+
+    /// The frame to show at `now` for frames with `gaps` (in milliseconds),
+    /// and when the next one is due.
+    pub(crate) fn frame_at(&mut self, gaps: &[u32], now: Instant) -> (usize, Option<Instant>) {
+        let last = gaps.len().saturating_sub(1);
+        self.current = self.current.min(last);
+        if self.state == AnimationState::Stopped || self.waiting || last == 0 {
+            return (self.current, None);
+        }
+
+        let mut start = *self.epoch.get_or_insert(now);
+        let mut from = self.current;
+        let rest = span(&gaps[from..]);
+        if now >= start + rest {
+            // The pass is over. Whole passes are skipped, not walked, so a
+            // view that was hidden for hours costs no more than one pass.
+            start += rest;
+            let cycle = span(gaps);
+            let passes = match cycle.as_nanos() {
+                0 => u32::MAX,
+                cycle => u32::try_from((now - start).as_nanos() / cycle).unwrap_or(u32::MAX),
+            };
+            let left = self.passes_left.map(|n| n.saturating_sub(1));
+            if self.state == AnimationState::Loading {
+                self.current = last;
+                self.waiting = true;
+                return (last, None);
+            }
+            if cycle.is_zero() || left.is_some_and(|n| n <= passes) {
+                self.state = AnimationState::Stopped;
+                self.set_current(last);
+                return (last, None);
+            }
+            self.passes_left = left.map(|n| n - passes);
+            start += cycle.saturating_mul(passes);
+            from = 0;
+        }
+
+        let (index, start, end) = walk(gaps, from, start, now);
+        self.current = index;
+        self.epoch = Some(start);
+        (index, Some(end))
+    }
+}
+
+/// Frame showing at `now` when frame `from` started at `start`, with its
+/// start and end. Frames with a gap of zero are skipped.
+fn walk(gaps: &[u32], from: usize, mut start: Instant, now: Instant) -> (usize, Instant, Instant) {
+    let last = gaps.len() - 1;
+    for (index, &gap) in gaps.iter().enumerate().skip(from) {
+        let end = start + millis(gap);
+        if now < end || index == last {
+            return (index, start, end);
+        }
+        start = end;
+    }
+    (last, start, start)
+}
+
+fn span(gaps: &[u32]) -> Duration {
+    gaps.iter().map(|&gap| millis(gap)).sum()
+}
+
+fn millis(gap: u32) -> Duration {
+    Duration::from_millis(u64::from(gap))
+}
+
+/// Draw the `width` x `height` pixels of `source` at (`source_x`,
+/// `source_y`) onto `canvas` at (`x`, `y`). Both images are BGRA; the
+/// rectangle is clipped to both.
+pub(crate) fn compose(
+    canvas: &mut ImageData,
+    (x, y): (u32, u32),
+    source: &ImageData,
+    (source_x, source_y): (u32, u32),
+    (width, height): (u32, u32),
+    composition: Composition,
+) {
+    let width = width
+        .min(source.width.saturating_sub(source_x))
+        .min(canvas.width.saturating_sub(x)) as usize;
+    let height = height
+        .min(source.height.saturating_sub(source_y))
+        .min(canvas.height.saturating_sub(y));
+    for row in 0..height {
+        let from = ((source_y + row) as usize * source.width as usize + source_x as usize) * 4;
+        let to = ((y + row) as usize * canvas.width as usize + x as usize) * 4;
+        let (Some(from), Some(to)) = (
+            source.data.get(from..from + width * 4),
+            canvas.data.get_mut(to..to + width * 4),
+        ) else {
+            return;
+        };
+        match composition {
+            Composition::Overwrite => to.copy_from_slice(from),
+            Composition::AlphaBlend => {
+                for (dst, src) in to.chunks_exact_mut(4).zip(from.chunks_exact(4)) {
+                    blend(dst, src);
+                }
+            }
+        }
+    }
+}
+
+/// `src` over `dst`, both non-premultiplied BGRA.
+fn blend(dst: &mut [u8], src: &[u8]) {
+    let src_alpha = u32::from(src[3]);
+    if src_alpha == 255 {
+        dst.copy_from_slice(src);
+        return;
+    }
+    // Alphas scaled to 0..=255 * 255.
+    let dst_alpha = u32::from(dst[3]) * (255 - src_alpha);
+    let alpha = src_alpha * 255 + dst_alpha;
+    if alpha == 0 {
+        dst.copy_from_slice(&[0; 4]);
+        return;
+    }
+    for channel in 0..3 {
+        let value = u32::from(src[channel]) * src_alpha * 255 + u32::from(dst[channel]) * dst_alpha;
+        dst[channel] = ((value + alpha / 2) / alpha) as u8;
+    }
+    dst[3] = ((alpha + 127) / 255) as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PixelFormat;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn playing(state: AnimationState, loops: Option<u32>) -> Timeline {
+        let mut timeline = Timeline::default();
+        timeline.set_loops(loops);
+        timeline.set_state(state);
+        timeline
+    }
+
+    #[test]
+    fn test_frames_follow_gaps_and_loop() {
+        let t0 = Instant::now();
+        let gaps = [100, 0, 50];
+        let mut timeline = playing(AnimationState::Running, None);
+        assert_eq!(timeline.frame_at(&gaps, t0), (0, Some(t0 + ms(100))));
+        assert_eq!(timeline.frame_at(&gaps, t0 + ms(99)).0, 0);
+        // The gapless frame is never shown.
+        assert_eq!(
+            timeline.frame_at(&gaps, t0 + ms(100)),
+            (2, Some(t0 + ms(150)))
+        );
+        assert_eq!(
+            timeline.frame_at(&gaps, t0 + ms(160)),
+            (0, Some(t0 + ms(250)))
+        );
+        // Many passes later, still in step.
+        assert_eq!(
+            timeline.frame_at(&gaps, t0 + ms(150 * 1000 + 120)),
+            (2, Some(t0 + ms(150 * 1001)))
+        );
+    }
+
+    #[test]
+    fn test_loop_count_stops_at_last_frame() {
+        let t0 = Instant::now();
+        let gaps = [10, 10];
+        let mut timeline = playing(AnimationState::Running, Some(2));
+        assert_eq!(timeline.frame_at(&gaps, t0).0, 0);
+        assert_eq!(timeline.frame_at(&gaps, t0 + ms(25)).0, 0);
+        assert_eq!(timeline.frame_at(&gaps, t0 + ms(40)), (1, None));
+        assert_eq!(timeline.state, AnimationState::Stopped);
+        assert_eq!(timeline.frame_at(&gaps, t0 + ms(45)), (1, None));
+
+        // Playing again starts from the current frame with all loops.
+        timeline.set_state(AnimationState::Running);
+        let t1 = t0 + ms(100);
+        assert_eq!(timeline.frame_at(&gaps, t1).0, 1);
+        assert_eq!(timeline.frame_at(&gaps, t1 + ms(15)).0, 0);
+        assert_eq!(timeline.frame_at(&gaps, t1 + ms(25)).0, 1);
+        assert_eq!(timeline.frame_at(&gaps, t1 + ms(35)), (1, None));
+    }
+
+    #[test]
+    fn test_loading_waits_for_frames() {
+        let t0 = Instant::now();
+        let mut timeline = playing(AnimationState::Loading, None);
+        assert_eq!(timeline.frame_at(&[10, 10], t0).0, 0);
+        assert_eq!(timeline.frame_at(&[10, 10], t0 + ms(30)), (1, None));
+        timeline.frames_added();
+        let t1 = t0 + ms(50);
+        assert_eq!(timeline.frame_at(&[10, 10, 10], t1), (2, Some(t1 + ms(10))));
+    }
+
+    #[test]
+    fn test_stopped_shows_current_frame() {
+        let mut timeline = Timeline::default();
+        timeline.set_current(1);
+        assert_eq!(timeline.frame_at(&[10, 10, 10], Instant::now()), (1, None));
+        // A frame number past the end is clamped.
+        timeline.set_current(7);
+        assert_eq!(timeline.frame_at(&[10, 10], Instant::now()), (1, None));
+    }
+
+    #[test]
+    fn test_compose_blends_and_clips() {
+        let mut canvas = ImageData::new([0, 0, 255, 255].repeat(4), 2, 2, PixelFormat::Bgra);
+        let source = ImageData::new(
+            vec![255, 0, 0, 255, 255, 0, 0, 0, 255, 0, 0, 128, 0, 0, 0, 0],
+            2,
+            2,
+            PixelFormat::Bgra,
+        );
+        compose(
+            &mut canvas,
+            (1, 0),
+            &source,
+            (0, 1),
+            (2, 2),
+            Composition::AlphaBlend,
+        );
+        // Only the top-right pixel is covered, by a half-transparent blue.
+        assert_eq!(&canvas.data[4..8], &[128, 0, 127, 255]);
+        assert_eq!(&canvas.data[..4], &[0, 0, 255, 255]);
+        assert_eq!(&canvas.data[8..], &[0, 0, 255, 255].repeat(2));
+
+        compose(
+            &mut canvas,
+            (0, 0),
+            &source,
+            (1, 0),
+            (1, 1),
+            Composition::Overwrite,
+        );
+        assert_eq!(&canvas.data[..4], &[255, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,301 @@
+//! Turning transmitted bytes into BGRA pixels.
+//!
+//! Protocols send raw RGB/RGBA pixels or PNG files, optionally
+//! zlib-compressed. Everything is decoded into [`ImageData`] in BGRA, the
+//! format the [`ImageManager`](crate::ImageManager) stores, and encoded
+//! back into PNG for clients reading images out of the terminal.
+
+use std::io::{Cursor, Read};
+
+use flate2::read::ZlibDecoder;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, ImageDecoder};
+
+use crate::animation::DEFAULT_GAP_MS;
+use crate::error::GraphicsError;
+use crate::manager::MAX_IMAGE_BYTES;
+use crate::types::{ImageData, PixelFormat};
+
+/// Inflate zlib-compressed data (Kitty `o=z`).
+///
+/// Output beyond the per-image limit is an error rather than being
+/// allocated, so a small payload cannot expand without bound.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, GraphicsError> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data)
+        .take(MAX_IMAGE_BYTES as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| GraphicsError::DecodeError(format!("zlib: {e}")))?;
+    if out.len() > MAX_IMAGE_BYTES {
+        return Err(GraphicsError::ImageTooLarge {
+            size: out.len(),
+            max: MAX_IMAGE_BYTES,
+        });
+    }
+    Ok(out)
+}
+
+/// Decode a PNG file into BGRA pixels.
+pub fn decode_png(data: &[u8]) -> Result<ImageData, GraphicsError> {
+    decode_with(
+        image::ImageReader::with_format(Cursor::new(data), image::ImageFormat::Png),
+        "png",
+    )
+}
+
+/// Decode a PNG, JPEG or GIF file into BGRA pixels, telling them apart by
+/// their signature. Only the first frame of an animated GIF is kept; see
+/// [`decode_animation`] for all of them.
+pub fn decode_file(data: &[u8]) -> Result<ImageData, GraphicsError> {
+    let format = image::guess_format(data)
+        .map_err(|_| GraphicsError::UnsupportedFormat("unknown image file".into()))?;
+    let name = match format {
+        image::ImageFormat::Png => "png",
+        image::ImageFormat::Jpeg => "jpeg",
+        image::ImageFormat::Gif => "gif",
+        other => return Err(GraphicsError::UnsupportedFormat(format!("{other:?}"))),
+    };
+    decode_with(
+        image::ImageReader::with_format(Cursor::new(data), format),
+        name,
+    )
+}
+
+/// Decode a file like [`decode_file`], but keep every frame of an animated
+/// GIF, each with the milliseconds until the next one.
+///
+/// Frames past the per-image limit in total are dropped, so a long
+/// animation plays only its beginning rather than not at all.
+pub fn decode_animation(data: &[u8]) -> Result<Vec<(ImageData, u32)>, GraphicsError> {
+    if image::guess_format(data).ok() != Some(image::ImageFormat::Gif) {
+        return Ok(vec![(decode_file(data)?, DEFAULT_GAP_MS)]);
+    }
+    let error = |e: image::ImageError| GraphicsError::DecodeError(format!("gif: {e}"));
+    let mut decoder = GifDecoder::new(Cursor::new(data)).map_err(error)?;
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(MAX_IMAGE_BYTES as u64);
+    decoder.set_limits(limits).map_err(error)?;
+
+    let mut frames = Vec::new();
+    let mut total = 0;
+    for frame in decoder.into_frames() {
+        let frame = frame.map_err(error)?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let buffer = frame.into_buffer();
+        total += buffer.len();
+        if total > MAX_IMAGE_BYTES && !frames.is_empty() {
+            log::warn!("dropping GIF frames past {} bytes", MAX_IMAGE_BYTES);
+            break;
+        }
+        let (width, height) = buffer.dimensions();
+        let mut image = ImageData::new(buffer.into_raw(), width, height, PixelFormat::Rgba);
+        image.to_bgra();
+        frames.push((image, gif_delay(numerator / denominator.max(1))));
+    }
+    if frames.is_empty() {
+        return Err(GraphicsError::DecodeError("gif: no frames".into()));
+    }
+    Ok(frames)
+}
+
+/// Browsers show GIF frames with a delay under 20ms for 100ms, and GIFs
+/// are made with that in mind.
+fn gif_delay(milliseconds: u32) -> u32 {
+    if milliseconds < 20 {
+        100
+    } else {
+        milliseconds
+    }
+}
+
+fn decode_with(
+    mut reader: image::ImageReader<Cursor<&[u8]>>,
+    format: &str,
+) -> Result<ImageData, GraphicsError> {
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(MAX_IMAGE_BYTES as u64);
+    reader.limits(limits);
+    let rgba = reader
+        .decode()
+        .map_err(|e| GraphicsError::DecodeError(format!("{format}: {e}")))?
+        .into_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut image = ImageData::new(rgba.into_raw(), width, height, PixelFormat::Rgba);
+    image.to_bgra();
+    Ok(image)
+}
+
+/// Build a BGRA image from transmitted data in `format`.
+///
+/// `width` and `height` are required for raw pixels and ignored for PNG,
+/// which carries its own. Extra bytes after the last pixel are dropped.
+pub fn decode_image(
+    data: Vec<u8>,
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+) -> Result<ImageData, GraphicsError> {
+    let bytes_per_pixel = match format {
+        PixelFormat::Png => return decode_png(&data),
+        PixelFormat::Rgb => 3,
+        PixelFormat::Rgba | PixelFormat::Bgra => 4,
+    };
+    if width == 0 || height == 0 {
+        return Err(GraphicsError::InvalidDimensions { width, height });
+    }
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(bytes_per_pixel))
+        .filter(|&size| size <= MAX_IMAGE_BYTES)
+        .ok_or(GraphicsError::InvalidDimensions { width, height })?;
+    if data.len() < expected {
+        return Err(GraphicsError::InsufficientData {
+            expected,
+            actual: data.len(),
+        });
+    }
+    let mut data = data;
+    data.truncate(expected);
+    let mut image = ImageData::new(data, width, height, format);
+    image.to_bgra();
+    Ok(image)
+}
+
+/// Encode an image as a PNG file.
+pub fn encode_png(image: &ImageData) -> Result<Vec<u8>, GraphicsError> {
+    if image.format == PixelFormat::Png {
+        return Ok(image.data.clone());
+    }
+    let invalid = || GraphicsError::InvalidDimensions {
+        width: image.width,
+        height: image.height,
+    };
+    let mut pixels = image.clone();
+    pixels.to_bgra();
+    for pixel in pixels.data.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    let rgba =
+        image::RgbaImage::from_raw(image.width, image.height, pixels.data).ok_or_else(invalid)?;
+    let mut png = Vec::new();
+    rgba.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|_| invalid())?;
+    Ok(png)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// A 2x1 PNG: one opaque red pixel, one half-transparent blue pixel.
+    pub(crate) fn sample_png() -> Vec<u8> {
+        let image = image::RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_decode_png() {
+        let image = decode_png(&sample_png()).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.format, PixelFormat::Bgra);
+        assert_eq!(image.data, vec![0, 0, 255, 255, 255, 0, 0, 128]);
+
+        assert!(matches!(
+            decode_png(b"not a png"),
+            Err(GraphicsError::DecodeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_png_round_trip() {
+        let image = decode_png(&sample_png()).unwrap();
+        let decoded = decode_png(&encode_png(&image).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+        assert_eq!(decoded.data, image.data);
+
+        let rgb = ImageData::new(vec![1, 2, 3], 1, 1, PixelFormat::Rgb);
+        let decoded = decode_png(&encode_png(&rgb).unwrap()).unwrap();
+        assert_eq!(decoded.data, vec![3, 2, 1, 255]);
+        assert!(encode_png(&ImageData::new(vec![0; 4], 2, 2, PixelFormat::Bgra)).is_err());
+    }
+
+    #[test]
+    fn test_decode_file_formats() {
+        let image = decode_file(&sample_png()).unwrap();
+        assert_eq!(image.data, vec![0, 0, 255, 255, 255, 0, 0, 128]);
+
+        let rgb = image::RgbImage::from_pixel(8, 4, image::Rgb([0, 0, 255]));
+        for format in [image::ImageFormat::Jpeg, image::ImageFormat::Gif] {
+            let mut file = Vec::new();
+            rgb.write_to(&mut Cursor::new(&mut file), format).unwrap();
+            let image = decode_file(&file).unwrap();
+            assert_eq!((image.width, image.height), (8, 4), "{format:?}");
+            assert_eq!(image.format, PixelFormat::Bgra);
+            // Blue, give or take JPEG's compression.
+            assert!(image.data[0] > 200 && image.data[2] < 50, "{format:?}");
+        }
+
+        assert!(matches!(
+            decode_file(b"BM not supported"),
+            Err(GraphicsError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_animation() {
+        let frames = [[255, 0, 0, 255], [0, 255, 0, 255]].map(|pixel| {
+            image::Frame::from_parts(
+                image::RgbaImage::from_pixel(4, 2, image::Rgba(pixel)),
+                0,
+                0,
+                image::Delay::from_numer_denom_ms(70, 1),
+            )
+        });
+        let mut gif = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut gif)
+            .encode_frames(frames)
+            .unwrap();
+
+        let frames = decode_animation(&gif).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, 70);
+        assert_eq!((frames[1].0.width, frames[1].0.height), (4, 2));
+        assert_eq!(&frames[1].0.data[..4], &[0, 255, 0, 255]);
+
+        let still = decode_animation(&sample_png()).unwrap();
+        assert_eq!(still.len(), 1);
+    }
+
+    #[test]
+    fn test_inflate() {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&[7u8; 1000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(inflate(&compressed).unwrap(), vec![7u8; 1000]);
+        assert!(inflate(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_decode_raw_pixels() {
+        let image = decode_image(vec![1, 2, 3, 4, 5, 6, 9], PixelFormat::Rgb, 2, 1).unwrap();
+        assert_eq!(image.data, vec![3, 2, 1, 255, 6, 5, 4, 255]);
+
+        assert!(matches!(
+            decode_image(vec![0; 7], PixelFormat::Rgba, 2, 1),
+            Err(GraphicsError::InsufficientData {
+                expected: 8,
+                actual: 7
+            })
+        ));
+        assert!(matches!(
+            decode_image(vec![0; 4], PixelFormat::Rgba, 0, 1),
+            Err(GraphicsError::InvalidDimensions { .. })
+        ));
+    }
+}
@@ -9,6 +9,10 @@ pub enum GraphicsError {
     #[error("image not found: {0:?}")]
     ImageNotFound(ImageId),
 
+    /// The image has no frame with this index.
+    #[error("frame not found: image {image_id:?}, frame {frame}")]
+    FrameNotFound { image_id: ImageId, frame: usize },
+
     /// The placement ID was not found for the given image.
     #[error("placement not found: image {image_id:?}, placement {placement_id}")]
     PlacementNotFound {
@@ -47,4 +51,12 @@ pub enum GraphicsError {
     /// Chunked transfer is incomplete or corrupted.
     #[error("incomplete chunked transfer for image {0:?}")]
     IncompleteTransfer(ImageId),
+
+    /// Fewer pixel bytes arrived than the dimensions call for.
+    #[error("insufficient image data: {actual} bytes (expected {expected} bytes)")]
+    InsufficientData { expected: usize, actual: usize },
+
+    /// Compressed or encoded image data could not be decoded.
+    #[error("decode error: {0}")]
+    DecodeError(String),
 }
@@ -0,0 +1,1144 @@
+//! Running parsed graphics commands against an [`ImageManager`].
+//!
+//! [`ImageManager::execute_kitty`] covers the whole command life cycle:
+//! reassembling chunked transfers, loading the data from wherever it was
+//! sent, decoding it, placing it at the cursor and composing the reply the
+//! client expects. The terminal only has to supply the cursor position and
+//! cell size, write the reply back and move the cursor as told.
+//!
+//! Animation frames are built here too: Kitty's `a=f` and `a=c` draw onto
+//! copies of existing frames, and animated GIFs become one frame each.
+//!
+//! [`ImageManager::execute_iterm2`] does the same for iTerm2 files, which
+//! arrive whole and get no reply, but may be downloads instead of images;
+//! [`ImageManager::execute_sixel`] for Sixel images.
+//!
+//! Reading and decoding need no image store. A terminal that does not want
+//! to wait on them with its screen locked loads a command first, with
+//! [`LoadedKitty::load`], [`LoadedIterm2::load`] or
+//! [`sixel::decode_sixel`], and executes the result afterwards.
+
+use std::sync::Arc;
+
+use crate::animation::{self, AnimationState, Composition, DEFAULT_GAP_MS};
+use crate::decode;
+use crate::error::GraphicsError;
+use crate::manager::ImageManager;
+use crate::protocol::iterm2::{Dimension, Iterm2Command};
+use crate::protocol::kitty::{Compression, DeleteTarget, KittyAction, KittyCommand};
+use crate::protocol::sixel;
+use crate::transmission;
+use crate::types::{ImageData, ImageId, ImagePlacement, PixelFormat, TransmissionMode};
+
+/// Terminal state a command is executed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellContext {
+    /// Cursor column (0-indexed).
+    pub cursor_column: u32,
+    /// Cursor row on the screen (0-indexed).
+    pub cursor_row: i32,
+    /// Cell width in pixels.
+    pub cell_width: u32,
+    /// Cell height in pixels.
+    pub cell_height: u32,
+    /// Number of rows on the screen.
+    pub screen_rows: u32,
+    /// Number of columns on the screen.
+    pub screen_columns: u32,
+}
+
+/// What the terminal has to do after a command ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KittyOutcome {
+    /// Response to write back to the PTY, including the APC framing.
+    pub reply: Option<String>,
+    /// Cells to move the cursor by after an image was displayed:
+    /// `(columns right, rows down)`.
+    pub cursor_advance: Option<(u32, u32)>,
+}
+
+/// What the terminal has to do after an iTerm2 file transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Iterm2Outcome {
+    /// The image was placed at the cursor. The cursor moves by
+    /// `(columns right, rows down)`, if at all.
+    Displayed {
+        image_id: ImageId,
+        cursor_advance: Option<(u32, u32)>,
+    },
+    /// The file is not for display (`inline=0`); the terminal decides
+    /// where to save it.
+    Download { name: Option<String>, data: Vec<u8> },
+}
+
+/// A Kitty command with its data read from wherever it was sent and
+/// decompressed. The image of a transmit or query is decoded too; the data
+/// of an animation frame waits for the size of the image it belongs to.
+#[derive(Debug)]
+pub struct LoadedKitty {
+    cmd: KittyCommand,
+    data: Result<KittyData, GraphicsError>,
+}
+
+#[derive(Debug)]
+enum KittyData {
+    Image(ImageData),
+    Raw(Vec<u8>),
+}
+
+impl LoadedKitty {
+    /// Load the data of `cmd`, a whole command as returned by
+    /// [`ImageManager::collect_kitty_chunks`].
+    pub fn load(cmd: KittyCommand) -> Self {
+        let data = match cmd.action {
+            KittyAction::Transmit | KittyAction::TransmitAndDisplay | KittyAction::Query => {
+                kitty_read(&cmd)
+                    .and_then(|data| decode::decode_image(data, cmd.format, cmd.width, cmd.height))
+                    .map(KittyData::Image)
+            }
+            KittyAction::AnimationFrame if !cmd.payload.is_empty() => {
+                kitty_read(&cmd).map(KittyData::Raw)
+            }
+            _ => Ok(KittyData::Raw(Vec::new())),
+        };
+        Self { cmd, data }
+    }
+}
+
+impl KittyData {
+    /// The image, decoding raw data as `width` x `height` pixels unless it
+    /// is a PNG.
+    fn decode(
+        self,
+        cmd: &KittyCommand,
+        width: u32,
+        height: u32,
+    ) -> Result<ImageData, GraphicsError> {
+        match self {
+            Self::Image(image) => Ok(image),
+            Self::Raw(data) => decode::decode_image(data, cmd.format, width, height),
+        }
+    }
+}
+
+/// An iTerm2 file transfer with its image decoded, unless it is a download.
+#[derive(Debug)]
+pub struct LoadedIterm2 {
+    cmd: Iterm2Command,
+    frames: Result<Vec<(ImageData, u32)>, GraphicsError>,
+}
+
+impl LoadedIterm2 {
+    pub fn load(cmd: Iterm2Command) -> Self {
+        let frames = if cmd.inline {
+            decode::decode_animation(&cmd.data)
+        } else {
+            Ok(Vec::new())
+        };
+        Self { cmd, frames }
+    }
+}
+
+impl ImageManager {
+    /// Execute a Kitty graphics command with the cursor and cell size in
+    /// `context`.
+    pub fn execute_kitty(&mut self, cmd: KittyCommand, context: &CellContext) -> KittyOutcome {
+        match self.collect_kitty_chunks(cmd) {
+            Some(cmd) => self.execute_loaded_kitty(LoadedKitty::load(cmd), context),
+            None => KittyOutcome::default(),
+        }
+    }
+
+    /// Execute a Kitty graphics command loaded beforehand, with the cursor
+    /// and cell size in `context`.
+    pub fn execute_loaded_kitty(
+        &mut self,
+        loaded: LoadedKitty,
+        context: &CellContext,
+    ) -> KittyOutcome {
+        let LoadedKitty { cmd, data } = loaded;
+        let mut outcome = KittyOutcome::default();
+        let result = match cmd.action {
+            KittyAction::Transmit => self.kitty_transmit(&cmd, data).map(|_| ()),
+            KittyAction::TransmitAndDisplay => self
+                .kitty_transmit(&cmd, data)
+                .and_then(|id| self.kitty_place(id, &cmd, context))
+                .map(|advance| outcome.cursor_advance = advance),
+            KittyAction::Display => self
+                .kitty_place(ImageId(cmd.image_id), &cmd, context)
+                .map(|advance| outcome.cursor_advance = advance),
+            KittyAction::Query => data.map(|_| ()),
+            KittyAction::Delete => {
+                self.kitty_delete(&cmd, context);
+                return outcome;
+            }
+            KittyAction::AnimationFrame => self.kitty_frame(&cmd, data),
+            KittyAction::AnimationControl => self.kitty_animate(&cmd),
+            KittyAction::ComposeFrames => self.kitty_compose(&cmd),
+        };
+        outcome.reply = kitty_reply(&cmd, &result);
+        outcome
+    }
+
+    /// Buffer the chunks of a transfer. Returns the command to execute once
+    /// the last chunk arrived, with the payload of all chunks.
+    pub fn collect_kitty_chunks(&mut self, mut cmd: KittyCommand) -> Option<KittyCommand> {
+        let more_data = cmd.more_data;
+        let payload = std::mem::take(&mut cmd.payload);
+        // Continuation chunks only carry `m` and the payload; the first
+        // chunk's keys describe the whole transfer.
+        let image_id = match &self.kitty_transfer {
+            Some(transfer) => transfer.image_id,
+            None if !more_data => {
+                cmd.payload = payload;
+                return Some(cmd);
+            }
+            None => {
+                let image_id = cmd.image_id;
+                self.kitty_transfer = Some(cmd);
+                image_id
+            }
+        };
+        if let Err(e) = self.append_chunk(image_id, &payload) {
+            log::warn!("discarding Kitty transfer for image {}: {}", image_id, e);
+            self.kitty_transfer = None;
+            return None;
+        }
+        if more_data {
+            return None;
+        }
+        let mut transfer = self.kitty_transfer.take()?;
+        transfer.payload = self.complete_chunked_transfer(image_id).unwrap_or_default();
+        transfer.more_data = false;
+        Some(transfer)
+    }
+
+    /// Store the image of a transmit. Returns the ID it was stored under.
+    fn kitty_transmit(
+        &mut self,
+        cmd: &KittyCommand,
+        data: Result<KittyData, GraphicsError>,
+    ) -> Result<ImageId, GraphicsError> {
+        let image = data?.decode(cmd, cmd.width, cmd.height)?;
+        let id = if cmd.image_id == 0 {
+            self.next_image_id()
+        } else {
+            ImageId(cmd.image_id)
+        };
+        self.store_image(id, image)?;
+        Ok(id)
+    }
+
+    /// Place image `id` at the cursor. Returns how far the cursor moves.
+    fn kitty_place(
+        &mut self,
+        id: ImageId,
+        cmd: &KittyCommand,
+        context: &CellContext,
+    ) -> Result<Option<(u32, u32)>, GraphicsError> {
+        let (width, height) = {
+            let image = self.get_image(id)?;
+            (image.width, image.height)
+        };
+        // The displayed area is the source rectangle, clipped to the image.
+        let source_x = cmd.source_x.min(width);
+        let source_y = cmd.source_y.min(height);
+        let source_width = match cmd.source_width {
+            0 => width - source_x,
+            w => w.min(width - source_x),
+        };
+        let source_height = match cmd.source_height {
+            0 => height - source_y,
+            h => h.min(height - source_y),
+        };
+
+        let cell_width = context.cell_width.max(1);
+        let cell_height = context.cell_height.max(1);
+        let columns = match cmd.display_columns {
+            0 => (source_width + cmd.cell_x_offset.min(cell_width - 1)).div_ceil(cell_width),
+            c => c,
+        }
+        .max(1);
+        let rows = match cmd.display_rows {
+            0 => (source_height + cmd.cell_y_offset.min(cell_height - 1)).div_ceil(cell_height),
+            r => r,
+        }
+        .max(1);
+
+        let placement = ImagePlacement {
+            image_id: id,
+            placement_id: cmd.placement_id,
+            column: context.cursor_column,
+            row: self.top_line() + i64::from(context.cursor_row),
+            columns,
+            rows,
+            source_x,
+            source_y,
+            source_width,
+            source_height,
+            z_index: cmd.z_index,
+            x_offset: cmd.cell_x_offset.min(cell_width - 1),
+            y_offset: cmd.cell_y_offset.min(cell_height - 1),
+        };
+        if cmd.unicode_placeholder {
+            // Shown by placeholder text, which moves the cursor itself.
+            self.place_virtual(placement)?;
+            return Ok(None);
+        }
+        self.place_image(placement)?;
+
+        Ok((!cmd.hold_cursor).then_some((columns, rows - 1)))
+    }
+
+    /// Add a frame to an image's animation or edit one (`a=f`).
+    fn kitty_frame(
+        &mut self,
+        cmd: &KittyCommand,
+        data: Result<KittyData, GraphicsError>,
+    ) -> Result<(), GraphicsError> {
+        let id = ImageId(cmd.image_id);
+        let image = self
+            .image_data(id)
+            .ok_or(GraphicsError::ImageNotFound(id))?;
+        let edit = frame_index(cmd.display_rows);
+        let mut canvas = match edit.or(frame_index(cmd.display_columns)) {
+            Some(index) => (*self.existing_frame(id, index)?).clone(),
+            None => {
+                let [r, g, b, a] = cmd.cell_y_offset.to_be_bytes();
+                let pixels = image.width as usize * image.height as usize;
+                ImageData::new(
+                    [b, g, r, a].repeat(pixels),
+                    image.width,
+                    image.height,
+                    PixelFormat::Bgra,
+                )
+            }
+        };
+
+        // A frame without data is a copy of the frame it starts as.
+        if !cmd.payload.is_empty() {
+            let width = match cmd.width {
+                0 => image.width.saturating_sub(cmd.source_x),
+                w => w,
+            };
+            let height = match cmd.height {
+                0 => image.height.saturating_sub(cmd.source_y),
+                h => h,
+            };
+            let data = data?.decode(cmd, width, height)?;
+            let composition = match cmd.cell_x_offset {
+                1 => Composition::Overwrite,
+                _ => Composition::AlphaBlend,
+            };
+            animation::compose(
+                &mut canvas,
+                (cmd.source_x, cmd.source_y),
+                &data,
+                (0, 0),
+                (data.width, data.height),
+                composition,
+            );
+        }
+
+        let gap = frame_gap(cmd.z_index);
+        match edit {
+            Some(index) => {
+                self.replace_frame(id, index, canvas)?;
+                if let Some(gap) = gap {
+                    self.set_frame_gap(id, index, gap)?;
+                }
+            }
+            None => {
+                self.add_frame(id, canvas, gap.unwrap_or(DEFAULT_GAP_MS))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Change how an image's animation plays (`a=a`).
+    fn kitty_animate(&mut self, cmd: &KittyCommand) -> Result<(), GraphicsError> {
+        let id = ImageId(cmd.image_id);
+        if !self.has_image(id) {
+            return Err(GraphicsError::ImageNotFound(id));
+        }
+        if let (Some(index), Some(gap)) = (frame_index(cmd.display_rows), frame_gap(cmd.z_index)) {
+            self.set_frame_gap(id, index, gap)?;
+        }
+        match cmd.height {
+            0 => {}
+            1 => self.set_loop_count(id, None)?,
+            n => self.set_loop_count(id, Some(n - 1))?,
+        }
+        let state = match cmd.width {
+            0 => None,
+            1 => Some(AnimationState::Stopped),
+            2 => Some(AnimationState::Loading),
+            3 => Some(AnimationState::Running),
+            n => {
+                return Err(GraphicsError::ParseError(format!(
+                    "invalid animation state: {n}"
+                )))
+            }
+        };
+        if let Some(state) = state {
+            self.set_animation_state(id, state)?;
+        }
+        if let Some(index) = frame_index(cmd.display_columns) {
+            self.set_current_frame(id, index)?;
+        }
+        Ok(())
+    }
+
+    /// Draw part of one animation frame onto another (`a=c`).
+    fn kitty_compose(&mut self, cmd: &KittyCommand) -> Result<(), GraphicsError> {
+        let id = ImageId(cmd.image_id);
+        if !self.has_image(id) {
+            return Err(GraphicsError::ImageNotFound(id));
+        }
+        let to = frame_index(cmd.display_columns).unwrap_or(0);
+        let source = self.existing_frame(id, frame_index(cmd.display_rows).unwrap_or(0))?;
+        let mut canvas = (*self.existing_frame(id, to)?).clone();
+        let width = match cmd.source_width {
+            0 => source.width,
+            w => w,
+        };
+        let height = match cmd.source_height {
+            0 => source.height,
+            h => h,
+        };
+        let composition = if cmd.hold_cursor {
+            Composition::Overwrite
+        } else {
+            Composition::AlphaBlend
+        };
+        animation::compose(
+            &mut canvas,
+            (cmd.source_x, cmd.source_y),
+            &source,
+            (cmd.cell_x_offset, cmd.cell_y_offset),
+            (width, height),
+            composition,
+        );
+        self.replace_frame(id, to, canvas)
+    }
+
+    fn existing_frame(&self, id: ImageId, index: usize) -> Result<Arc<ImageData>, GraphicsError> {
+        self.frame(id, index).ok_or(GraphicsError::FrameNotFound {
+            image_id: id,
+            frame: index,
+        })
+    }
+
+    fn kitty_delete(&mut self, cmd: &KittyCommand, context: &CellContext) {
+        let target = cmd.delete_target.clone().unwrap_or(DeleteTarget::All);
+        // Rows in commands and the context are screen rows.
+        let top = self.top_line();
+        let screen_rows = i64::from(context.screen_rows);
+        let affected = match target {
+            DeleteTarget::All => {
+                self.delete_placements_where(|p| p.intersects_rows(top, top + screen_rows))
+            }
+            DeleteTarget::ById(id) => {
+                if cmd.delete_data {
+                    let _ = self.delete_image(id);
+                    return;
+                }
+                self.delete_virtual_placements(id, None);
+                self.delete_placements_where(|p| p.image_id == id)
+            }
+            DeleteTarget::ByPlacement {
+                image_id,
+                placement_id,
+            } => {
+                self.delete_virtual_placements(image_id, Some(placement_id));
+                let mut affected = self.delete_placements_where(|p| {
+                    p.image_id == image_id && p.placement_id == placement_id
+                });
+                affected.push(image_id);
+                affected
+            }
+            DeleteTarget::AtCursor => {
+                let row = top + i64::from(context.cursor_row);
+                self.delete_placements_where(|p| p.covers(context.cursor_column, row))
+            }
+            DeleteTarget::InRange { column, row } => {
+                self.delete_placements_where(|p| p.covers(column, top + i64::from(row)))
+            }
+            DeleteTarget::Column(column) => self.delete_placements_where(|p| {
+                p.intersects_columns(column, column.saturating_add(1))
+            }),
+            DeleteTarget::Row(row) => {
+                let row = top + i64::from(row);
+                self.delete_placements_where(|p| p.intersects_rows(row, row + 1))
+            }
+            DeleteTarget::ZIndex(z_index) => self.delete_placements_where(|p| p.z_index == z_index),
+        };
+        if cmd.delete_data {
+            self.delete_unplaced_images(&affected);
+        }
+    }
+
+    /// Execute an iTerm2 file transfer with the cursor, cell and screen
+    /// size in `context`.
+    pub fn execute_iterm2(
+        &mut self,
+        cmd: Iterm2Command,
+        context: &CellContext,
+    ) -> Result<Iterm2Outcome, GraphicsError> {
+        self.execute_loaded_iterm2(LoadedIterm2::load(cmd), context)
+    }
+
+    /// Execute an iTerm2 file transfer loaded beforehand.
+    pub fn execute_loaded_iterm2(
+        &mut self,
+        loaded: LoadedIterm2,
+        context: &CellContext,
+    ) -> Result<Iterm2Outcome, GraphicsError> {
+        let LoadedIterm2 { cmd, frames } = loaded;
+        if !cmd.inline {
+            return Ok(Iterm2Outcome::Download {
+                name: cmd.name,
+                data: cmd.data,
+            });
+        }
+
+        let mut frames = frames?.into_iter();
+        let Some((image, gap)) = frames.next() else {
+            return Err(GraphicsError::DecodeError("no image".into()));
+        };
+        let (columns, rows) = iterm2_cells(&cmd, image.width, image.height, context);
+        let id = self.place_anonymous(image, columns, rows, context)?;
+        if frames.len() > 0 {
+            // Animated GIFs play in a loop, as in a browser.
+            self.set_frame_gap(id, 0, gap)?;
+            for (frame, gap) in frames {
+                self.add_frame(id, frame, gap)?;
+            }
+            self.set_animation_state(id, AnimationState::Running)?;
+        }
+
+        Ok(Iterm2Outcome::Displayed {
+            image_id: id,
+            cursor_advance: (!cmd.hold_cursor).then_some((columns, rows - 1)),
+        })
+    }
+
+    /// Decode and place a Sixel image at the cursor. `params` and `data`
+    /// are the DCS parameters and the data after `q`.
+    ///
+    /// Returns how far the cursor moves, `(columns right, rows down)`: to
+    /// the last text row the image covers, in the column it started in.
+    pub fn execute_sixel(
+        &mut self,
+        params: &[u16],
+        data: &[u8],
+        context: &CellContext,
+    ) -> Result<(u32, u32), GraphicsError> {
+        let image = sixel::decode_sixel(params, data)?;
+        self.place_sixel(image, context)
+    }
+
+    /// Place a decoded Sixel image at the cursor, as
+    /// [`execute_sixel`](Self::execute_sixel) does.
+    pub fn place_sixel(
+        &mut self,
+        image: ImageData,
+        context: &CellContext,
+    ) -> Result<(u32, u32), GraphicsError> {
+        let columns = image.width.div_ceil(context.cell_width.max(1));
+        let rows = image.height.div_ceil(context.cell_height.max(1));
+        self.place_anonymous(image, columns, rows, context)?;
+        Ok((0, rows - 1))
+    }
+
+    /// Store `image` under a fresh ID and place it at the cursor over
+    /// `columns` x `rows` cells. Returns the ID.
+    fn place_anonymous(
+        &mut self,
+        image: ImageData,
+        columns: u32,
+        rows: u32,
+        context: &CellContext,
+    ) -> Result<ImageId, GraphicsError> {
+        // Free earlier images whose placement has been deleted.
+        let shown = std::mem::take(&mut self.anonymous_images);
+        self.delete_unplaced_images(&shown);
+        self.anonymous_images = shown.into_iter().filter(|&id| self.has_image(id)).collect();
+
+        let (width, height) = (image.width, image.height);
+        // Auto-assigned IDs may have been taken by Kitty clients.
+        let mut id = self.next_image_id();
+        while self.has_image(id) {
+            id = self.next_image_id();
+        }
+        self.store_image(id, image)?;
+        self.anonymous_images.push(id);
+        self.place_image(ImagePlacement {
+            image_id: id,
+            placement_id: 0,
+            column: context.cursor_column,
+            row: self.top_line() + i64::from(context.cursor_row),
+            columns,
+            rows,
+            source_x: 0,
+            source_y: 0,
+            source_width: width,
+            source_height: height,
+            z_index: 0,
+            x_offset: 0,
+            y_offset: 0,
+        })?;
+        Ok(id)
+    }
+}
+
+/// Cells an iTerm2 image of `width`x`height` pixels covers.
+///
+/// A dimension left at `auto` follows the other one proportionally; with
+/// neither given, the image keeps its size unless it is wider than the
+/// screen.
+fn iterm2_cells(cmd: &Iterm2Command, width: u32, height: u32, context: &CellContext) -> (u32, u32) {
+    let cell_width = f64::from(context.cell_width.max(1));
+    let cell_height = f64::from(context.cell_height.max(1));
+    let requested = |dimension: Dimension, cell: f64, cells_on_screen: u32| match dimension {
+        Dimension::Auto => None,
+        Dimension::Cells(n) => Some(f64::from(n) * cell),
+        Dimension::Pixels(n) => Some(f64::from(n)),
+        Dimension::Percent(p) => Some(f64::from(cells_on_screen) * cell * f64::from(p) / 100.0),
+    };
+    let (w, h) = (f64::from(width), f64::from(height));
+    let (w, h) = match (
+        requested(cmd.width, cell_width, context.screen_columns),
+        requested(cmd.height, cell_height, context.screen_rows),
+    ) {
+        (None, None) => {
+            let scale = (f64::from(context.screen_columns.max(1)) * cell_width / w).min(1.0);
+            (w * scale, h * scale)
+        }
+        (Some(target), None) => (target, h * target / w),
+        (None, Some(target)) => (w * target / h, target),
+        (Some(target_w), Some(target_h)) if cmd.preserve_aspect_ratio => {
+            let scale = (target_w / w).min(target_h / h);
+            (w * scale, h * scale)
+        }
+        (Some(target_w), Some(target_h)) => (target_w, target_h),
+    };
+    let cells =
+        |pixels: f64, cell: f64| (pixels / cell).ceil().clamp(1.0, f64::from(u16::MAX)) as u32;
+    (cells(w, cell_width), cells(h, cell_height))
+}
+
+/// A 1-based frame number as an index; `None` if unset.
+fn frame_index(number: u32) -> Option<usize> {
+    number.checked_sub(1).map(|index| index as usize)
+}
+
+/// A frame gap from `z`: unset if 0, none if negative.
+fn frame_gap(z: i32) -> Option<u32> {
+    match z {
+        0 => None,
+        z => Some(z.max(0) as u32),
+    }
+}
+
+/// Read and decompress the image data of `cmd`.
+fn kitty_read(cmd: &KittyCommand) -> Result<Vec<u8>, GraphicsError> {
+    let payload = cmd.decode_payload()?;
+    let data = match cmd.transmission {
+        TransmissionMode::Direct => payload,
+        mode => transmission::read_medium(
+            mode,
+            &payload,
+            cmd.data_size as usize,
+            cmd.data_offset as usize,
+        )?,
+    };
+    match cmd.compression {
+        Compression::None => Ok(data),
+        Compression::Zlib => decode::inflate(&data),
+    }
+}
+
+/// The response to `cmd`, honoring its `q` key. Clients that did not pick
+/// an image ID get no response.
+fn kitty_reply(cmd: &KittyCommand, result: &Result<(), GraphicsError>) -> Option<String> {
+    if cmd.image_id == 0 {
+        return None;
+    }
+    let message = match result {
+        Ok(()) if cmd.quiet == 0 => "OK".to_string(),
+        Err(e) if cmd.quiet < 2 => format!("{}:{}", kitty_error_code(e), e),
+        _ => return None,
+    };
+    let mut keys = format!("i={}", cmd.image_id);
+    if cmd.placement_id != 0 {
+        keys.push_str(&format!(",p={}", cmd.placement_id));
+    }
+    Some(format!("\x1b_G{keys};{message}\x1b\\"))
+}
+
+/// The POSIX-style error name the Kitty protocol uses for `error`.
+pub fn kitty_error_code(error: &GraphicsError) -> &'static str {
+    match error {
+        GraphicsError::ImageNotFound(_)
+        | GraphicsError::FrameNotFound { .. }
+        | GraphicsError::PlacementNotFound { .. } => "ENOENT",
+        GraphicsError::ImageTooLarge { .. } | GraphicsError::QuotaExceeded { .. } => "EFBIG",
+        GraphicsError::FileError(_) => "EBADF",
+        GraphicsError::IncompleteTransfer(_) | GraphicsError::InsufficientData { .. } => "ENODATA",
+        GraphicsError::DecodeError(_) => "EBADPNG",
+        GraphicsError::InvalidDimensions { .. }
+        | GraphicsError::Base64Decode(_)
+        | GraphicsError::UnsupportedFormat(_)
+        | GraphicsError::ParseError(_) => "EINVAL",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+
+    use super::*;
+    use crate::protocol::kitty::parse_kitty_command;
+
+    const CONTEXT: CellContext = CellContext {
+        cursor_column: 2,
+        cursor_row: 3,
+        cell_width: 10,
+        cell_height: 20,
+        screen_rows: 24,
+        screen_columns: 80,
+    };
+
+    fn b64(data: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(data)
+    }
+
+    fn run(mgr: &mut ImageManager, command: &str) -> KittyOutcome {
+        mgr.execute_kitty(parse_kitty_command(command.as_bytes()).unwrap(), &CONTEXT)
+    }
+
+    #[test]
+    fn test_transmit_replies_ok() {
+        let mut mgr = ImageManager::new();
+        let outcome = run(
+            &mut mgr,
+            &format!("a=t,f=24,s=1,v=1,i=7;{}", b64(&[1, 2, 3])),
+        );
+        assert_eq!(outcome.reply.as_deref(), Some("\x1b_Gi=7;OK\x1b\\"));
+        assert_eq!(outcome.cursor_advance, None);
+        assert_eq!(mgr.get_image(ImageId(7)).unwrap().data, vec![3, 2, 1, 255]);
+        assert_eq!(mgr.placement_count(), 0);
+
+        // Without an image ID there is nobody to reply to.
+        let outcome = run(&mut mgr, &format!("a=t,f=24,s=1,v=1;{}", b64(&[1, 2, 3])));
+        assert_eq!(outcome.reply, None);
+        assert_eq!(mgr.image_count(), 2);
+    }
+
+    #[test]
+    fn test_errors_and_quiet() {
+        let mut mgr = ImageManager::new();
+        let outcome = run(&mut mgr, "a=p,i=9");
+        assert!(outcome
+            .reply
+            .as_deref()
+            .unwrap()
+            .starts_with("\x1b_Gi=9;ENOENT:"));
+
+        let outcome = run(&mut mgr, &format!("a=t,f=32,s=2,v=2,i=1;{}", b64(&[0; 4])));
+        assert!(outcome.reply.unwrap().contains(";ENODATA:"));
+
+        let quiet_ok = format!("a=t,f=24,s=1,v=1,i=1,q=1;{}", b64(&[0; 3]));
+        assert_eq!(run(&mut mgr, &quiet_ok).reply, None);
+        assert!(run(&mut mgr, "a=p,i=9,q=1").reply.is_some());
+        assert_eq!(run(&mut mgr, "a=p,i=9,q=2").reply, None);
+    }
+
+    #[test]
+    fn test_chunked_zlib_png() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder
+            .write_all(&crate::decode::tests::sample_png())
+            .unwrap();
+        let encoded = b64(&encoder.finish().unwrap());
+        // Chunks split at a multiple of 4 characters, as the protocol requires.
+        let (first, rest) = encoded.split_at(encoded.len() / 8 * 4);
+
+        let mut mgr = ImageManager::new();
+        let outcome = run(&mut mgr, &format!("a=T,f=100,o=z,i=3,m=1;{first}"));
+        assert_eq!(outcome, KittyOutcome::default());
+        assert!(!mgr.has_image(ImageId(3)));
+        assert!(mgr.has_pending_chunks(3));
+
+        let outcome = run(&mut mgr, &format!("m=0;{rest}"));
+        assert_eq!(outcome.reply.as_deref(), Some("\x1b_Gi=3;OK\x1b\\"));
+        let image = mgr.get_image(ImageId(3)).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(mgr.placement_count(), 1);
+        assert!(!mgr.has_pending_chunks(3));
+    }
+
+    #[test]
+    fn test_file_transmission() {
+        let path = std::env::temp_dir().join(format!("crux-exec-{}", std::process::id()));
+        std::fs::write(&path, [9u8, 8, 7, 6, 5, 4, 3]).unwrap();
+        let mut mgr = ImageManager::new();
+        let cmd = format!(
+            "a=t,t=f,f=24,s=2,v=1,S=6,O=1,i=4;{}",
+            b64(path.to_str().unwrap().as_bytes())
+        );
+        assert!(run(&mut mgr, &cmd).reply.unwrap().ends_with(";OK\x1b\\"));
+        assert_eq!(
+            mgr.get_image(ImageId(4)).unwrap().data,
+            vec![6, 7, 8, 255, 3, 4, 5, 255]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_transmission_refuses_fifo() {
+        use std::ffi::CString;
+
+        let path = std::env::temp_dir().join(format!("crux-exec-fifo-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        // Opening the FIFO for reading would wait for a writer forever.
+        let mut mgr = ImageManager::new();
+        let cmd = format!(
+            "a=t,t=f,f=24,s=1,v=1,i=5;{}",
+            b64(path.to_str().unwrap().as_bytes())
+        );
+        assert!(run(&mut mgr, &cmd)
+            .reply
+            .unwrap()
+            .starts_with("\x1b_Gi=5;EBADF:"));
+        assert!(!mgr.has_image(ImageId(5)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_placement_at_cursor() {
+        let mut mgr = ImageManager::new();
+        // 25x30 pixels with a 6 pixel offset spans 4 columns and 2 rows.
+        let pixels = b64(&vec![0; 25 * 30 * 3]);
+        let outcome = run(
+            &mut mgr,
+            &format!("a=T,f=24,s=25,v=30,i=1,p=2,X=6,z=-1;{pixels}"),
+        );
+        assert_eq!(outcome.cursor_advance, Some((4, 1)));
+        assert_eq!(outcome.reply.as_deref(), Some("\x1b_Gi=1,p=2;OK\x1b\\"));
+        let placement = mgr.get_placements_in_range(0, 24)[0].clone();
+        assert_eq!((placement.column, placement.row), (2, 3));
+        assert_eq!((placement.columns, placement.rows), (4, 2));
+        assert_eq!((placement.x_offset, placement.z_index), (6, -1));
+
+        // Explicit size, cursor held; the same placement ID replaces it.
+        let outcome = run(&mut mgr, "a=p,i=1,p=2,c=8,r=3,C=1");
+        assert_eq!(outcome.cursor_advance, None);
+        assert_eq!(mgr.placement_count(), 1);
+        assert_eq!(mgr.get_placements_in_range(0, 24)[0].columns, 8);
+    }
+
+    #[test]
+    fn test_delete_targets() {
+        let mut mgr = ImageManager::new();
+        run(&mut mgr, &format!("a=t,f=24,s=1,v=1,i=1;{}", b64(&[0; 3])));
+        run(&mut mgr, "a=p,i=1,p=1,c=2,r=2");
+        run(&mut mgr, "a=p,i=1,p=2,c=1,r=1,z=5");
+        assert_eq!(mgr.placement_count(), 2);
+
+        // The cursor cell (2, 3) is covered by both.
+        run(&mut mgr, "a=d,d=z,z=5");
+        assert_eq!(mgr.placement_count(), 1);
+        run(&mut mgr, "a=d,d=p,x=4,y=5");
+        assert_eq!(mgr.placement_count(), 0, "cell (3, 4) is covered");
+        assert!(mgr.has_image(ImageId(1)), "lower-case keeps the data");
+
+        run(&mut mgr, "a=p,i=1,c=1,r=1");
+        run(&mut mgr, "a=d,d=C");
+        assert_eq!(mgr.placement_count(), 0);
+        assert!(!mgr.has_image(ImageId(1)), "upper-case frees the data");
+    }
+
+    #[test]
+    fn test_scroll_and_erase() {
+        let mut mgr = ImageManager::new();
+        run(&mut mgr, &format!("a=t,f=24,s=1,v=1,i=1;{}", b64(&[0; 3])));
+        run(&mut mgr, "a=p,i=1,p=1,r=2");
+
+        // The placement keeps its line while the screen moves down.
+        mgr.scroll_up(4);
+        let placement = mgr.get_placements_in_range(-10, 24)[0].clone();
+        assert_eq!((placement.row, mgr.top_line()), (3, 4));
+        // Still partly on screen.
+        assert_eq!(mgr.get_placements_in_range(0, 24).len(), 1);
+
+        mgr.clear_scrollback();
+        assert_eq!(mgr.placement_count(), 1);
+        mgr.clear_screen(24);
+        assert_eq!(mgr.placement_count(), 0);
+
+        run(&mut mgr, "a=p,i=1,p=1");
+        mgr.scroll_up(10);
+        mgr.clear_screen(24);
+        assert_eq!(mgr.placement_count(), 1, "scrollback survives erase");
+        mgr.clear_scrollback();
+        assert_eq!(mgr.placement_count(), 0);
+    }
+
+    #[test]
+    fn test_scrollback_limit_and_screens() {
+        let mut mgr = ImageManager::new();
+        mgr.set_scrollback_limit(5);
+        run(&mut mgr, &format!("a=t,f=24,s=1,v=1,i=1;{}", b64(&[0; 3])));
+        run(&mut mgr, "a=p,i=1,p=1,r=2");
+
+        // Rows 3 and 4 stay while the scrollback holds them.
+        mgr.scroll_up(7);
+        assert_eq!(mgr.placement_count(), 1);
+        mgr.scroll_up(3);
+        assert_eq!(mgr.placement_count(), 0);
+        assert!(mgr.has_image(ImageId(1)), "only the placement is pruned");
+
+        run(&mut mgr, "a=p,i=1,p=1");
+        mgr.switch_screen(true);
+        assert_eq!((mgr.placement_count(), mgr.top_line()), (0, 0));
+        run(&mut mgr, "a=p,i=1,p=2");
+        // The alternate screen has no scrollback.
+        mgr.scroll_up(3);
+        assert_eq!(mgr.placement_count(), 1);
+        mgr.scroll_up(1);
+        assert_eq!(mgr.placement_count(), 0);
+        run(&mut mgr, "a=p,i=1,p=2");
+
+        mgr.switch_screen(false);
+        let placements = mgr.get_placements_in_range(0, 24);
+        assert_eq!(placements.len(), 1);
+        assert_eq!((placements[0].placement_id, mgr.top_line()), (1, 10));
+        mgr.switch_screen(true);
+        assert_eq!(
+            mgr.placement_count(),
+            0,
+            "the alternate screen starts empty"
+        );
+    }
+
+    #[test]
+    fn test_unicode_placeholders() {
+        let mut mgr = ImageManager::new();
+        run(
+            &mut mgr,
+            &format!("a=t,f=24,s=20,v=40,i=5;{}", b64(&[0; 2400])),
+        );
+        let outcome = run(&mut mgr, "a=p,i=5,p=2,U=1,c=2,r=2");
+        assert_eq!(outcome.cursor_advance, None);
+        assert_eq!(mgr.placement_count(), 0);
+        mgr.scroll_up(3);
+
+        let mut runs = crate::PlaceholderRuns::new();
+        runs.push(1, 4, 5, 2, &[]);
+        runs.push(1, 5, 5, 2, &[]);
+        // Row 1, column 1.
+        runs.push(2, 5, 5, 0, &['\u{030D}', '\u{030D}']);
+        let runs = runs.finish();
+
+        let top = mgr.placeholder_placement(&runs[0]).unwrap();
+        assert_eq!((top.column, top.row, top.columns, top.rows), (4, 4, 2, 1));
+        let source = |p: &ImagePlacement| (p.source_x, p.source_y, p.source_width, p.source_height);
+        assert_eq!(source(&top), (0, 0, 20, 20));
+        let bottom = mgr.placeholder_placement(&runs[1]).unwrap();
+        assert_eq!((bottom.column, bottom.row), (5, 5));
+        assert_eq!(source(&bottom), (10, 20, 10, 20));
+
+        run(&mut mgr, "a=d,d=i,i=5");
+        assert!(mgr.placeholder_placement(&runs[0]).is_none());
+        assert!(mgr.has_image(ImageId(5)));
+    }
+
+    fn iterm2(mgr: &mut ImageManager, args: &str, file: &[u8]) -> Iterm2Outcome {
+        let input = format!("{args}:{}", b64(file));
+        let cmd = crate::protocol::iterm2::parse_iterm2_command(input.as_bytes()).unwrap();
+        mgr.execute_iterm2(cmd, &CONTEXT).unwrap()
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbaImage::new(width, height)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_iterm2_sizes() {
+        let mut mgr = ImageManager::new();
+        let mut cells = |args: &str, width, height| {
+            mgr.delete_all();
+            iterm2(&mut mgr, args, &png(width, height));
+            let placement = mgr.get_placements_in_range(0, 24)[0].clone();
+            assert_eq!((placement.column, placement.row), (2, 3));
+            (placement.columns, placement.rows)
+        };
+        // Cells are 10x20 pixels on an 80x24 screen.
+        assert_eq!(cells("inline=1", 40, 20), (4, 1));
+        assert_eq!(cells("inline=1", 2000, 50), (80, 1), "shrunk to the screen");
+        assert_eq!(cells("inline=1;width=10", 40, 20), (10, 3));
+        assert_eq!(cells("inline=1;height=100px", 40, 20), (20, 5));
+        assert_eq!(cells("inline=1;width=50%;height=2", 40, 20), (8, 2));
+        assert_eq!(
+            cells("inline=1;width=50%;height=2;preserveAspectRatio=0", 40, 20),
+            (40, 2)
+        );
+    }
+
+    #[test]
+    fn test_sixel_placement() {
+        let mut mgr = ImageManager::new();
+        // 12x25 pixels: 2 columns and 2 rows of 10x20 cells.
+        let data = b"\"1;1;12;25#1!12~";
+        assert_eq!(mgr.execute_sixel(&[0, 1], data, &CONTEXT).unwrap(), (0, 1));
+        let placement = mgr.get_placements_in_range(0, 24)[0].clone();
+        assert_eq!((placement.column, placement.row), (2, 3));
+        assert_eq!((placement.columns, placement.rows), (2, 2));
+        assert_eq!(mgr.get_image(placement.image_id).unwrap().height, 25);
+
+        assert!(mgr.execute_sixel(&[], b"", &CONTEXT).is_err());
+        assert_eq!(mgr.placement_count(), 1);
+    }
+
+    #[test]
+    fn test_iterm2_cursor_download_and_cleanup() {
+        let mut mgr = ImageManager::new();
+        let outcome = iterm2(&mut mgr, "inline=1", &png(25, 30));
+        let Iterm2Outcome::Displayed {
+            image_id,
+            cursor_advance,
+        } = outcome
+        else {
+            panic!("not displayed: {outcome:?}");
+        };
+        assert_eq!(cursor_advance, Some((3, 1)));
+        assert!(mgr.has_image(image_id));
+        let outcome = iterm2(&mut mgr, "inline=1;doNotMoveCursor=1", &png(25, 30));
+        assert!(matches!(
+            outcome,
+            Iterm2Outcome::Displayed {
+                cursor_advance: None,
+                ..
+            }
+        ));
+
+        let name = b64(b"report.pdf");
+        let outcome = iterm2(&mut mgr, &format!("name={name}"), b"%PDF");
+        assert_eq!(
+            outcome,
+            Iterm2Outcome::Download {
+                name: Some("report.pdf".into()),
+                data: b"%PDF".to_vec()
+            }
+        );
+        assert_eq!(mgr.image_count(), 2);
+
+        // Images whose placement was erased are freed by the next one.
+        mgr.clear_screen(24);
+        iterm2(&mut mgr, "inline=1", &png(1, 1));
+        assert_eq!(mgr.image_count(), 1);
+
+        let input = format!("inline=1:{}", b64(b"plain text"));
+        let cmd = crate::protocol::iterm2::parse_iterm2_command(input.as_bytes()).unwrap();
+        assert!(mgr.execute_iterm2(cmd, &CONTEXT).is_err());
+    }
+
+    #[test]
+    fn test_kitty_animation_frames() {
+        let mut mgr = ImageManager::new();
+        let red = [255, 0, 0, 255];
+        run(
+            &mut mgr,
+            &format!("a=t,f=32,s=2,v=1,i=1;{}", b64(&red.repeat(2))),
+        );
+        let frame = |mgr: &ImageManager, index| mgr.frame(ImageId(1), index).unwrap().data.clone();
+
+        // A new frame on the transparent background, data at x=1.
+        let outcome = run(
+            &mut mgr,
+            &format!("a=f,i=1,f=32,s=1,v=1,x=1,z=100;{}", b64(&[0, 255, 0, 255])),
+        );
+        assert_eq!(outcome.reply.as_deref(), Some("\x1b_Gi=1;OK\x1b\\"));
+        assert_eq!(frame(&mgr, 1), vec![0, 0, 0, 0, 0, 255, 0, 255]);
+        // A copy of the first frame, then an edit of the second.
+        run(&mut mgr, "a=f,i=1,c=1");
+        assert_eq!(frame(&mgr, 2), frame(&mgr, 0));
+        run(
+            &mut mgr,
+            &format!("a=f,i=1,r=2,f=32,s=1,v=1;{}", b64(&[0, 0, 255, 255])),
+        );
+        assert_eq!(frame(&mgr, 1), vec![255, 0, 0, 255, 0, 255, 0, 255]);
+        // The green pixel of frame 2 copied over the first pixel of frame 1.
+        run(&mut mgr, "a=c,i=1,r=2,c=1,w=1,h=1,X=1,C=1");
+        assert_eq!(frame(&mgr, 0), vec![0, 255, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(mgr.frame_count(ImageId(1)), 3);
+        assert_eq!(mgr.total_bytes(), 24);
+
+        run(&mut mgr, "a=a,i=1,s=3,v=2,r=1,z=10");
+        assert_eq!(
+            mgr.animation_state(ImageId(1)),
+            Some(AnimationState::Running)
+        );
+        let t0 = std::time::Instant::now();
+        let current = mgr.current_frame(ImageId(1), t0).unwrap();
+        assert_eq!(current.index, 0);
+        assert_eq!(
+            current.next_change,
+            Some(t0 + std::time::Duration::from_millis(10))
+        );
+        let current = mgr.current_frame(ImageId(1), t0 + std::time::Duration::from_millis(20));
+        assert_eq!(current.unwrap().index, 1);
+        run(&mut mgr, "a=a,i=1,s=1,c=3");
+        assert_eq!(mgr.current_frame(ImageId(1), t0).unwrap().index, 2);
+
+        for (command, code) in [
+            ("a=f,i=9", "ENOENT"),
+            ("a=f,i=1,r=5", "ENOENT"),
+            ("a=a,i=1,c=4", "ENOENT"),
+            ("a=a,i=1,s=7", "EINVAL"),
+        ] {
+            let reply = run(&mut mgr, command).reply.unwrap();
+            assert!(reply.contains(code), "{command}: {reply}");
+        }
+    }
+
+    #[test]
+    fn test_iterm2_animated_gif() {
+        let frames = [[255, 0, 0, 255], [0, 0, 255, 255]].map(|pixel| {
+            image::Frame::from_parts(
+                image::RgbaImage::from_pixel(10, 20, image::Rgba(pixel)),
+                0,
+                0,
+                image::Delay::from_numer_denom_ms(50, 1),
+            )
+        });
+        let mut gif = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut gif)
+            .encode_frames(frames)
+            .unwrap();
+
+        let mut mgr = ImageManager::new();
+        iterm2(&mut mgr, "inline=1", &gif);
+        let id = mgr.get_placements_in_range(0, 24)[0].image_id;
+        assert_eq!(mgr.frame_count(id), 2);
+        assert_eq!(mgr.animation_state(id), Some(AnimationState::Running));
+        let t0 = std::time::Instant::now();
+        let current = mgr.current_frame(id, t0).unwrap();
+        assert_eq!(
+            current.next_change,
+            Some(t0 + std::time::Duration::from_millis(50))
+        );
+        assert_eq!(&current.data.data[..4], &[0, 0, 255, 255]);
+    }
+}
@@ -5,7 +5,8 @@
 //!
 //! - **Protocol parsing**: Decoding escape sequences into structured commands
 //! - **Image storage**: Memory-managed store with LRU eviction and quota enforcement
-//! - **Placement tracking**: Mapping images to terminal grid positions with z-index layering
+//! - **Placement tracking**: Mapping images to absolute grid lines with z-index layering,
+//!   per screen, and to Kitty Unicode placeholders
 //!
 //! # Architecture
 //!
@@ -14,6 +15,12 @@
 //!     │
 //!     ▼
 //! protocol::kitty::parse_kitty_command()  ← parse escape sequences
+//! protocol::iterm2::parse_iterm2_command()
+//!     │
+//!     ▼
+//! ImageManager::execute_kitty()           ← load, decode, reply
+//! ImageManager::execute_iterm2()
+//! ImageManager::execute_sixel()           ← protocol::sixel::decode_sixel()
 //!     │
 //!     ▼
 //! ImageManager::store_image()             ← store with quota enforcement
@@ -21,6 +28,7 @@
 //!     │
 //!     ▼
 //! ImageManager::get_placements_in_range() ← query for rendering
+//! ImageManager::current_frame()           ← frame of an animation to draw
 //! ```
 //!
 //! # Pixel Format
@@ -33,14 +41,22 @@
 //!
 //! The [`ImageManager`] enforces a configurable memory quota (default 320 MiB).
 //! When the quota is exceeded, the least-recently-used images are evicted.
-//! Individual images are capped at 64 MiB.
+//! Individual images, and each frame of an animation, are capped at 64 MiB.
 
+pub mod animation;
+pub mod decode;
 pub mod error;
+pub mod execute;
 pub mod manager;
+pub mod placeholder;
 pub mod protocol;
+pub mod transmission;
 pub mod types;
 
 // Re-export primary types for convenience.
+pub use animation::{AnimationState, Composition, CurrentFrame};
 pub use error::GraphicsError;
+pub use execute::{CellContext, Iterm2Outcome, KittyOutcome, LoadedIterm2, LoadedKitty};
 pub use manager::ImageManager;
+pub use placeholder::{PlaceholderRun, PlaceholderRuns, PLACEHOLDER};
 pub use types::{ImageData, ImageId, ImagePlacement, PixelFormat, TransmissionMode};
@@ -2,18 +2,28 @@
 //!
 //! The [`ImageManager`] is the central store for all images transmitted via
 //! graphics protocols. It enforces a configurable memory quota (default 320MB)
-//! and evicts least-recently-used images when the quota is exceeded.
+//! and evicts least-recently-used images when the quota is exceeded. Every
+//! frame of an animated image counts towards the quota.
+//!
+//! Placements sit on absolute grid lines, so scrolling only moves the line
+//! at the top of the screen. The primary and alternate screen each have
+//! their own placements.
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
+use crate::animation::{AnimationState, CurrentFrame, Timeline, DEFAULT_GAP_MS};
 use crate::error::GraphicsError;
+use crate::placeholder::PlaceholderRun;
+use crate::protocol::kitty::KittyCommand;
 use crate::types::{ImageData, ImageId, ImagePlacement};
 
 /// Default memory quota: 320 MiB.
 const DEFAULT_QUOTA_BYTES: usize = 320 * 1024 * 1024;
 
 /// Maximum single image size: 64 MiB.
-const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+pub(crate) const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
 
 /// Maximum number of pending chunked transfers to prevent resource exhaustion.
 const MAX_PENDING_CHUNKS: usize = 32;
@@ -24,11 +34,37 @@ const MAX_CHUNK_ACCUMULATION: usize = 64 * 1024 * 1024;
 /// Internal record for a stored image.
 #[derive(Debug)]
 struct StoredImage {
-    data: ImageData,
+    /// The image itself, then the frames of its animation, if any. Shared
+    /// so renderers can hold on to the pixels without copying them.
+    frames: Vec<Frame>,
+    /// Playback position of the frames.
+    timeline: Timeline,
+    /// Size of all frames in bytes.
+    bytes: usize,
     /// Monotonically increasing access counter for LRU eviction.
     last_access: u64,
 }
 
+#[derive(Debug)]
+struct Frame {
+    data: Arc<ImageData>,
+    /// Milliseconds until the next frame; 0 skips the frame.
+    gap: u32,
+}
+
+/// Placements of the screen that is not shown.
+#[derive(Debug, Default)]
+struct HiddenScreen {
+    placements: HashMap<u32, Vec<ImagePlacement>>,
+    top_line: i64,
+}
+
+impl StoredImage {
+    fn gaps(&self) -> Vec<u32> {
+        self.frames.iter().map(|frame| frame.gap).collect()
+    }
+}
+
 /// Manages image storage, placement tracking, and memory quota enforcement.
 ///
 /// Images are stored in RAM keyed by [`ImageId`]. When the total memory
@@ -38,8 +74,19 @@ struct StoredImage {
 pub struct ImageManager {
     /// Stored images keyed by their ID.
     images: HashMap<u32, StoredImage>,
-    /// Image placements keyed by image ID, then placement ID.
+    /// Placements on the shown screen keyed by image ID.
     placements: HashMap<u32, Vec<ImagePlacement>>,
+    /// Absolute line of the top row of the shown screen.
+    top_line: i64,
+    /// Lines of the primary screen's scrollback; placements above them are
+    /// dropped. The alternate screen has none.
+    scrollback_limit: usize,
+    /// Whether the alternate screen is shown.
+    alternate_screen: bool,
+    hidden_screen: HiddenScreen,
+    /// Kitty virtual placements (`U=1`) keyed by image ID. They are shown
+    /// where Unicode placeholders for them are.
+    virtual_placements: HashMap<u32, Vec<ImagePlacement>>,
     /// Pending chunked transfers: image_id -> accumulated base64 data.
     pending_chunks: HashMap<u32, Vec<u8>>,
     /// Current total memory usage in bytes.
@@ -50,6 +97,12 @@ pub struct ImageManager {
     access_counter: u64,
     /// Next auto-assigned image ID.
     next_auto_id: u32,
+    /// First command of a Kitty transfer whose remaining chunks are still
+    /// arriving, with the payload received so far.
+    pub(crate) kitty_transfer: Option<KittyCommand>,
+    /// Images shown with the iTerm2 and Sixel protocols. Nothing can refer
+    /// to them once their placement is gone, so they are freed then.
+    pub(crate) anonymous_images: Vec<ImageId>,
 }
 
 impl Default for ImageManager {
@@ -64,11 +117,18 @@ impl ImageManager {
         Self {
             images: HashMap::new(),
             placements: HashMap::new(),
+            top_line: 0,
+            scrollback_limit: usize::MAX,
+            alternate_screen: false,
+            hidden_screen: HiddenScreen::default(),
+            virtual_placements: HashMap::new(),
             pending_chunks: HashMap::new(),
             total_bytes: 0,
             quota_bytes: DEFAULT_QUOTA_BYTES,
             access_counter: 0,
             next_auto_id: 1,
+            kitty_transfer: None,
+            anonymous_images: Vec::new(),
         }
     }
 
@@ -122,7 +182,7 @@ impl ImageManager {
 
         // Remove old image if it exists (reclaim its memory)
         if let Some(old) = self.images.remove(&id.0) {
-            self.total_bytes = self.total_bytes.saturating_sub(old.data.byte_size());
+            self.total_bytes = self.total_bytes.saturating_sub(old.bytes);
         }
 
         // Evict LRU images until we have room
@@ -142,7 +202,12 @@ impl ImageManager {
         self.images.insert(
             id.0,
             StoredImage {
-                data,
+                frames: vec![Frame {
+                    data: Arc::new(data),
+                    gap: DEFAULT_GAP_MS,
+                }],
+                timeline: Timeline::default(),
+                bytes: size,
                 last_access: self.access_counter,
             },
         );
@@ -160,7 +225,179 @@ impl ImageManager {
             .get_mut(&id.0)
             .ok_or(GraphicsError::ImageNotFound(id))?;
         stored.last_access = counter;
-        Ok(&stored.data)
+        Ok(&stored.frames[0].data)
+    }
+
+    /// Shared handle to an image's data, for rendering. Does not count as
+    /// an access for LRU eviction.
+    pub fn image_data(&self, id: ImageId) -> Option<Arc<ImageData>> {
+        self.frame(id, 0)
+    }
+
+    /// Append a frame to an image's animation, shown for `gap`
+    /// milliseconds. Returns its index; the image itself is frame 0.
+    ///
+    /// The frame must be BGRA and the size of the image. Other images are
+    /// evicted if the quota would be exceeded, but never this one.
+    pub fn add_frame(
+        &mut self,
+        id: ImageId,
+        data: ImageData,
+        gap: u32,
+    ) -> Result<usize, GraphicsError> {
+        let size = self.check_frame(id, &data)?;
+        // Accessing the image makes it the last one LRU eviction picks.
+        self.get_image(id)?;
+        while self.total_bytes + size > self.quota_bytes && self.images.len() > 1 {
+            self.evict_lru();
+        }
+        if self.total_bytes + size > self.quota_bytes {
+            return Err(GraphicsError::QuotaExceeded {
+                used: self.total_bytes + size,
+                quota: self.quota_bytes,
+            });
+        }
+
+        let stored = self.stored_mut(id)?;
+        stored.frames.push(Frame {
+            data: Arc::new(data),
+            gap,
+        });
+        stored.bytes += size;
+        stored.timeline.frames_added();
+        let index = stored.frames.len() - 1;
+        self.total_bytes += size;
+        Ok(index)
+    }
+
+    /// Replace frame `index` of an image with `data`, which must be BGRA
+    /// and the size of the image.
+    pub fn replace_frame(
+        &mut self,
+        id: ImageId,
+        index: usize,
+        data: ImageData,
+    ) -> Result<(), GraphicsError> {
+        self.check_frame(id, &data)?;
+        let frame = self.frame_mut(id, index)?;
+        frame.data = Arc::new(data);
+        Ok(())
+    }
+
+    /// Shared handle to frame `index` of an image.
+    pub fn frame(&self, id: ImageId, index: usize) -> Option<Arc<ImageData>> {
+        let stored = self.images.get(&id.0)?;
+        stored
+            .frames
+            .get(index)
+            .map(|frame| Arc::clone(&frame.data))
+    }
+
+    /// Number of frames of an image, counting the image itself; 0 if there
+    /// is no such image.
+    pub fn frame_count(&self, id: ImageId) -> usize {
+        self.images
+            .get(&id.0)
+            .map_or(0, |stored| stored.frames.len())
+    }
+
+    /// Set how many milliseconds frame `index` shows for; 0 skips it.
+    pub fn set_frame_gap(
+        &mut self,
+        id: ImageId,
+        index: usize,
+        gap: u32,
+    ) -> Result<(), GraphicsError> {
+        self.frame_mut(id, index)?.gap = gap;
+        Ok(())
+    }
+
+    /// Stop an image's animation or play it. The current frame starts over.
+    pub fn set_animation_state(
+        &mut self,
+        id: ImageId,
+        state: AnimationState,
+    ) -> Result<(), GraphicsError> {
+        self.stored_mut(id)?.timeline.set_state(state);
+        Ok(())
+    }
+
+    /// The state of an image's animation.
+    pub fn animation_state(&self, id: ImageId) -> Option<AnimationState> {
+        self.images.get(&id.0).map(|stored| stored.timeline.state)
+    }
+
+    /// Show frame `index` of an image, and play on from it if the
+    /// animation runs.
+    pub fn set_current_frame(&mut self, id: ImageId, index: usize) -> Result<(), GraphicsError> {
+        self.frame_mut(id, index)?;
+        self.stored_mut(id)?.timeline.set_current(index);
+        Ok(())
+    }
+
+    /// Play an image's frames `loops` times before stopping at the last
+    /// one; `None` loops forever.
+    pub fn set_loop_count(&mut self, id: ImageId, loops: Option<u32>) -> Result<(), GraphicsError> {
+        self.stored_mut(id)?.timeline.set_loops(loops);
+        Ok(())
+    }
+
+    /// The frame of an image to draw at `now`, and when the next one is
+    /// due. Renderers ask with a monotonic timestamp each time they draw;
+    /// playback advances with it. Does not count as an access for LRU
+    /// eviction.
+    pub fn current_frame(&mut self, id: ImageId, now: Instant) -> Option<CurrentFrame> {
+        let stored = self.images.get_mut(&id.0)?;
+        let (index, next_change) = if stored.frames.len() > 1 {
+            let gaps = stored.gaps();
+            stored.timeline.frame_at(&gaps, now)
+        } else {
+            (0, None)
+        };
+        Some(CurrentFrame {
+            data: Arc::clone(&stored.frames[index].data),
+            index,
+            next_change,
+        })
+    }
+
+    fn stored_mut(&mut self, id: ImageId) -> Result<&mut StoredImage, GraphicsError> {
+        self.images
+            .get_mut(&id.0)
+            .ok_or(GraphicsError::ImageNotFound(id))
+    }
+
+    fn frame_mut(&mut self, id: ImageId, index: usize) -> Result<&mut Frame, GraphicsError> {
+        self.stored_mut(id)?
+            .frames
+            .get_mut(index)
+            .ok_or(GraphicsError::FrameNotFound {
+                image_id: id,
+                frame: index,
+            })
+    }
+
+    /// Check that `data` can be a frame of image `id`. Returns its size.
+    fn check_frame(&self, id: ImageId, data: &ImageData) -> Result<usize, GraphicsError> {
+        let stored = self
+            .images
+            .get(&id.0)
+            .ok_or(GraphicsError::ImageNotFound(id))?;
+        let image = &stored.frames[0].data;
+        if (data.width, data.height, data.format) != (image.width, image.height, image.format) {
+            return Err(GraphicsError::InvalidDimensions {
+                width: data.width,
+                height: data.height,
+            });
+        }
+        let size = data.byte_size();
+        if size > MAX_IMAGE_BYTES {
+            return Err(GraphicsError::ImageTooLarge {
+                size,
+                max: MAX_IMAGE_BYTES,
+            });
+        }
+        Ok(size)
     }
 
     /// Check if an image exists without updating the LRU counter.
@@ -168,11 +405,18 @@ impl ImageManager {
         self.images.contains_key(&id.0)
     }
 
+    /// IDs of the stored images, in ascending order.
+    pub fn image_ids(&self) -> Vec<ImageId> {
+        let mut ids: Vec<ImageId> = self.images.keys().map(|&id| ImageId(id)).collect();
+        ids.sort_unstable_by_key(|id| id.0);
+        ids
+    }
+
     /// Delete an image and all its placements.
     pub fn delete_image(&mut self, id: ImageId) -> Result<(), GraphicsError> {
         if let Some(stored) = self.images.remove(&id.0) {
-            self.total_bytes = self.total_bytes.saturating_sub(stored.data.byte_size());
-            self.placements.remove(&id.0);
+            self.total_bytes = self.total_bytes.saturating_sub(stored.bytes);
+            self.remove_placements(id.0);
             Ok(())
         } else {
             Err(GraphicsError::ImageNotFound(id))
@@ -183,20 +427,219 @@ impl ImageManager {
     pub fn delete_all(&mut self) {
         self.images.clear();
         self.placements.clear();
+        self.hidden_screen.placements.clear();
+        self.virtual_placements.clear();
         self.pending_chunks.clear();
+        self.kitty_transfer = None;
         self.total_bytes = 0;
     }
 
-    /// Add a placement for an existing image.
+    /// Add a placement for an existing image. A placement with the same
+    /// non-zero placement ID replaces the earlier one.
     pub fn place_image(&mut self, placement: ImagePlacement) -> Result<(), GraphicsError> {
         let id = placement.image_id;
         if !self.images.contains_key(&id.0) {
             return Err(GraphicsError::ImageNotFound(id));
         }
-        self.placements.entry(id.0).or_default().push(placement);
+        let placements = self.placements.entry(id.0).or_default();
+        if placement.placement_id != 0 {
+            placements.retain(|p| p.placement_id != placement.placement_id);
+        }
+        placements.push(placement);
+        Ok(())
+    }
+
+    /// Add a Kitty virtual placement, shown wherever Unicode placeholders
+    /// refer to it. Its row and column are unused. A virtual placement
+    /// with the same non-zero placement ID replaces the earlier one.
+    pub fn place_virtual(&mut self, placement: ImagePlacement) -> Result<(), GraphicsError> {
+        let id = placement.image_id;
+        if !self.images.contains_key(&id.0) {
+            return Err(GraphicsError::ImageNotFound(id));
+        }
+        let placements = self.virtual_placements.entry(id.0).or_default();
+        if placement.placement_id != 0 {
+            placements.retain(|p| p.placement_id != placement.placement_id);
+        }
+        placements.push(placement);
         Ok(())
     }
 
+    /// Delete the virtual placements of an image: all of them, or the one
+    /// with `placement_id`.
+    pub fn delete_virtual_placements(&mut self, id: ImageId, placement_id: Option<u32>) {
+        if let Some(placements) = self.virtual_placements.get_mut(&id.0) {
+            placements.retain(|p| placement_id.is_some_and(|pid| p.placement_id != pid));
+            if placements.is_empty() {
+                self.virtual_placements.remove(&id.0);
+            }
+        }
+    }
+
+    /// The placement that draws the part of an image a run of Unicode
+    /// placeholders shows, on the run's cells.
+    ///
+    /// The virtual placement's cells are stretched over its source
+    /// rectangle. Without a placement ID the run shows the image's first
+    /// virtual placement.
+    pub fn placeholder_placement(&self, run: &PlaceholderRun) -> Option<ImagePlacement> {
+        let placement = self
+            .virtual_placements
+            .get(&run.image_id.0)?
+            .iter()
+            .find(|p| run.placement_id == 0 || p.placement_id == run.placement_id)?;
+        let (columns, rows) = (
+            u64::from(placement.columns.max(1)),
+            u64::from(placement.rows.max(1)),
+        );
+        let (first, row) = (u64::from(run.image_column), u64::from(run.image_row));
+        if first >= columns || row >= rows {
+            return None;
+        }
+        let last = (first + u64::from(run.columns)).min(columns);
+        // Pixel `cell` cells into `size` pixels spread over `cells` cells.
+        let at = |size: u32, cell: u64, cells: u64| (u64::from(size) * cell / cells) as u32;
+        let (width, height) = (placement.source_width, placement.source_height);
+        let x = at(width, first, columns);
+        let y = at(height, row, rows);
+        Some(ImagePlacement {
+            image_id: run.image_id,
+            placement_id: placement.placement_id,
+            column: run.column,
+            row: self.top_line + i64::from(run.line),
+            columns: (last - first) as u32,
+            rows: 1,
+            source_x: placement.source_x + x,
+            source_y: placement.source_y + y,
+            source_width: (at(width, last, columns) - x).max(1),
+            source_height: (at(height, row + 1, rows) - y).max(1),
+            z_index: placement.z_index,
+            x_offset: 0,
+            y_offset: 0,
+        })
+    }
+
+    /// Returns the number of placements on the shown screen.
+    pub fn placement_count(&self) -> usize {
+        self.placements.values().map(Vec::len).sum()
+    }
+
+    /// An image's placements on the shown screen.
+    pub fn placements_of(&self, id: ImageId) -> &[ImagePlacement] {
+        self.placements.get(&id.0).map_or(&[], Vec::as_slice)
+    }
+
+    /// Remove every placement `remove` returns `true` for. Returns the IDs
+    /// of the images that lost placements.
+    pub fn delete_placements_where(
+        &mut self,
+        mut remove: impl FnMut(&ImagePlacement) -> bool,
+    ) -> Vec<ImageId> {
+        let mut affected = Vec::new();
+        self.placements.retain(|&id, placements| {
+            let before = placements.len();
+            placements.retain(|p| !remove(p));
+            if placements.len() != before {
+                affected.push(ImageId(id));
+            }
+            !placements.is_empty()
+        });
+        affected
+    }
+
+    /// Delete the images in `ids` that no placement on either screen, and
+    /// no virtual placement, refers to anymore.
+    pub fn delete_unplaced_images(&mut self, ids: &[ImageId]) {
+        for &id in ids {
+            let placed = self.placements.contains_key(&id.0)
+                || self.hidden_screen.placements.contains_key(&id.0)
+                || self.virtual_placements.contains_key(&id.0);
+            if !placed {
+                let _ = self.delete_image(id);
+            }
+        }
+    }
+
+    /// Absolute line of the top row of the shown screen. A placement's
+    /// screen row is its `row` minus this; negative in the scrollback.
+    pub fn top_line(&self) -> i64 {
+        self.top_line
+    }
+
+    /// Keep placements on the last `lines` lines of the primary screen's
+    /// scrollback, as the terminal keeps its text.
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.scrollback_limit = lines;
+        if !self.alternate_screen {
+            self.prune_scrollback();
+        }
+    }
+
+    /// Whether the alternate screen is shown.
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    /// Show the alternate or the primary screen's placements. The
+    /// alternate screen starts out empty every time it is entered.
+    pub fn switch_screen(&mut self, alternate: bool) {
+        if alternate == self.alternate_screen {
+            return;
+        }
+        std::mem::swap(&mut self.placements, &mut self.hidden_screen.placements);
+        std::mem::swap(&mut self.top_line, &mut self.hidden_screen.top_line);
+        self.alternate_screen = alternate;
+        if alternate {
+            self.placements.clear();
+            self.top_line = 0;
+        }
+    }
+
+    /// Record that `lines` lines scrolled off the top of the screen.
+    /// Placements scrolled past the scrollback are deleted.
+    pub fn scroll_up(&mut self, lines: u32) {
+        self.top_line = self.top_line.saturating_add(i64::from(lines));
+        self.prune_scrollback();
+    }
+
+    /// Record that `lines` lines came back from the scrollback onto the
+    /// screen, as when the terminal grows taller.
+    pub fn scroll_down(&mut self, lines: u32) {
+        self.top_line = self.top_line.saturating_sub(i64::from(lines));
+    }
+
+    /// Delete the placements on the visible screen, rows `0..screen_rows`,
+    /// as erasing the display does.
+    pub fn clear_screen(&mut self, screen_rows: u32) {
+        let top = self.top_line;
+        self.delete_placements_where(|p| p.intersects_rows(top, top + i64::from(screen_rows)));
+    }
+
+    /// Delete the placements that are entirely in the scrollback, as
+    /// clearing the scrollback does.
+    pub fn clear_scrollback(&mut self) {
+        let top = self.top_line;
+        self.delete_placements_where(|p| p.end_row() <= top);
+    }
+
+    fn prune_scrollback(&mut self) {
+        let limit = if self.alternate_screen {
+            0
+        } else {
+            self.scrollback_limit
+        };
+        let oldest = self
+            .top_line
+            .saturating_sub(i64::try_from(limit).unwrap_or(i64::MAX));
+        self.delete_placements_where(|p| p.end_row() <= oldest);
+    }
+
+    fn remove_placements(&mut self, id: u32) {
+        self.placements.remove(&id);
+        self.hidden_screen.placements.remove(&id);
+        self.virtual_placements.remove(&id);
+    }
+
     /// Delete a specific placement.
     pub fn delete_placement(
         &mut self,
@@ -231,16 +674,18 @@ impl ImageManager {
 
     /// Get all placements for images that intersect the given row range.
     ///
-    /// Returns placements where the placement row falls within `[start_row, end_row)`.
-    pub fn get_placements_in_range(
-        &mut self,
-        start_row: i32,
-        end_row: i32,
-    ) -> Vec<&ImagePlacement> {
+    /// Returns placements covering any screen row in `[start_row,
+    /// end_row)`, so an image scrolled partly off the top is still
+    /// included. Rows in the scrollback are negative.
+    pub fn get_placements_in_range(&self, start_row: i32, end_row: i32) -> Vec<&ImagePlacement> {
+        let (start, end) = (
+            self.top_line + i64::from(start_row),
+            self.top_line + i64::from(end_row),
+        );
         let mut result = Vec::new();
         for placements in self.placements.values() {
             for placement in placements {
-                if placement.row >= start_row && placement.row < end_row {
+                if placement.intersects_rows(start, end) {
                     result.push(placement);
                 }
             }
@@ -304,12 +749,12 @@ impl ImageManager {
             .min_by_key(|(_, stored)| stored.last_access)
         {
             if let Some(stored) = self.images.remove(&lru_id) {
-                self.total_bytes = self.total_bytes.saturating_sub(stored.data.byte_size());
-                self.placements.remove(&lru_id);
+                self.total_bytes = self.total_bytes.saturating_sub(stored.bytes);
+                self.remove_placements(lru_id);
                 log::debug!(
                     "evicted image {} ({} bytes), total now {} bytes",
                     lru_id,
-                    stored.data.byte_size(),
+                    stored.bytes,
                     self.total_bytes
                 );
             }
@@ -341,6 +786,32 @@ mod tests {
         assert_eq!(data.byte_size(), 100);
     }
 
+    #[test]
+    fn test_frames_count_towards_quota() {
+        let mut mgr = ImageManager::with_quota(300);
+        mgr.store_image(ImageId(1), make_image(100)).unwrap();
+        mgr.store_image(ImageId(2), make_image(100)).unwrap();
+        assert_eq!(mgr.add_frame(ImageId(2), make_image(100), 40).unwrap(), 1);
+        assert_eq!(mgr.total_bytes(), 300);
+
+        // Room for another frame is made by evicting other images only.
+        assert_eq!(mgr.add_frame(ImageId(2), make_image(100), 40).unwrap(), 2);
+        assert!(!mgr.has_image(ImageId(1)));
+        assert!(matches!(
+            mgr.add_frame(ImageId(2), make_image(100), 40),
+            Err(GraphicsError::QuotaExceeded { .. })
+        ));
+        let wide = ImageData::new(vec![0; 8], 2, 1, PixelFormat::Bgra);
+        assert!(matches!(
+            mgr.add_frame(ImageId(2), wide, 40),
+            Err(GraphicsError::InvalidDimensions { .. })
+        ));
+        assert_eq!(mgr.frame_count(ImageId(2)), 3);
+
+        mgr.delete_image(ImageId(2)).unwrap();
+        assert_eq!(mgr.total_bytes(), 0);
+    }
+
     #[test]
     fn test_delete_image() {
         let mut mgr = ImageManager::new();
@@ -435,6 +906,11 @@ mod tests {
 
         let placements = mgr.get_placements_in_range(0, 100);
         assert_eq!(placements.len(), 1);
+        assert_eq!(mgr.placements_of(id).len(), 1);
+
+        mgr.store_image(ImageId(7), make_image(100)).unwrap();
+        assert_eq!(mgr.image_ids(), [id, ImageId(7)]);
+        assert!(mgr.placements_of(ImageId(7)).is_empty());
     }
 
     #[test]
@@ -0,0 +1,277 @@
+//! Kitty Unicode placeholders.
+//!
+//! Instead of placing an image at the cursor, a client can create a virtual
+//! placement (`a=p,U=1`) and print placeholder characters where the image
+//! goes. Each placeholder cell is `U+10EEEE` followed by combining marks
+//! for the image row and column it shows, with the image ID in the
+//! foreground color and the placement ID in the underline color. Being
+//! text, the cells survive anything that redraws text, such as tmux.
+//!
+//! Reference: <https://sw.kovidgoyal.net/kitty/graphics-protocol/#unicode-placeholders>
+
+use crate::types::ImageId;
+
+/// The placeholder character.
+pub const PLACEHOLDER: char = '\u{10EEEE}';
+
+/// The combining marks that encode numbers, in order: the n-th mark
+/// stands for n.
+const DIACRITICS: &[(char, char)] = &[
+    ('\u{0305}', '\u{0305}'),
+    ('\u{030D}', '\u{030E}'),
+    ('\u{0310}', '\u{0310}'),
+    ('\u{0312}', '\u{0312}'),
+    ('\u{033D}', '\u{033F}'),
+    ('\u{0346}', '\u{0346}'),
+    ('\u{034A}', '\u{034C}'),
+    ('\u{0350}', '\u{0352}'),
+    ('\u{0357}', '\u{0357}'),
+    ('\u{035B}', '\u{035B}'),
+    ('\u{0363}', '\u{036F}'),
+    ('\u{0483}', '\u{0487}'),
+    ('\u{0592}', '\u{0595}'),
+    ('\u{0597}', '\u{0599}'),
+    ('\u{059C}', '\u{05A1}'),
+    ('\u{05A8}', '\u{05A9}'),
+    ('\u{05AB}', '\u{05AC}'),
+    ('\u{05AF}', '\u{05AF}'),
+    ('\u{05C4}', '\u{05C4}'),
+    ('\u{0610}', '\u{0617}'),
+    ('\u{0657}', '\u{065B}'),
+    ('\u{065D}', '\u{065E}'),
+    ('\u{06D6}', '\u{06DC}'),
+    ('\u{06DF}', '\u{06E2}'),
+    ('\u{06E4}', '\u{06E4}'),
+    ('\u{06E7}', '\u{06E8}'),
+    ('\u{06EB}', '\u{06EC}'),
+    ('\u{0730}', '\u{0730}'),
+    ('\u{0732}', '\u{0733}'),
+    ('\u{0735}', '\u{0736}'),
+    ('\u{073A}', '\u{073A}'),
+    ('\u{073D}', '\u{073D}'),
+    ('\u{073F}', '\u{0741}'),
+    ('\u{0743}', '\u{0743}'),
+    ('\u{0745}', '\u{0745}'),
+    ('\u{0747}', '\u{0747}'),
+    ('\u{0749}', '\u{074A}'),
+    ('\u{07EB}', '\u{07F1}'),
+    ('\u{07F3}', '\u{07F3}'),
+    ('\u{0816}', '\u{0819}'),
+    ('\u{081B}', '\u{0823}'),
+    ('\u{0825}', '\u{0827}'),
+    ('\u{0829}', '\u{082D}'),
+    ('\u{0951}', '\u{0951}'),
+    ('\u{0953}', '\u{0954}'),
+    ('\u{0F82}', '\u{0F83}'),
+    ('\u{0F86}', '\u{0F87}'),
+    ('\u{135D}', '\u{135F}'),
+    ('\u{17DD}', '\u{17DD}'),
+    ('\u{193A}', '\u{193A}'),
+    ('\u{1A17}', '\u{1A17}'),
+    ('\u{1A75}', '\u{1A7C}'),
+    ('\u{1B6B}', '\u{1B6B}'),
+    ('\u{1B6D}', '\u{1B73}'),
+    ('\u{1CD0}', '\u{1CD2}'),
+    ('\u{1CDA}', '\u{1CDB}'),
+    ('\u{1CE0}', '\u{1CE0}'),
+    ('\u{1DC0}', '\u{1DC1}'),
+    ('\u{1DC3}', '\u{1DC9}'),
+    ('\u{1DCB}', '\u{1DCC}'),
+    ('\u{1DD1}', '\u{1DE6}'),
+    ('\u{1DFE}', '\u{1DFE}'),
+    ('\u{20D0}', '\u{20D1}'),
+    ('\u{20D4}', '\u{20D7}'),
+    ('\u{20DB}', '\u{20DC}'),
+    ('\u{20E1}', '\u{20E1}'),
+    ('\u{20E7}', '\u{20E7}'),
+    ('\u{20E9}', '\u{20E9}'),
+    ('\u{20F0}', '\u{20F0}'),
+    ('\u{2CEF}', '\u{2CF1}'),
+    ('\u{2DE0}', '\u{2DFF}'),
+    ('\u{A66F}', '\u{A66F}'),
+    ('\u{A67C}', '\u{A67D}'),
+    ('\u{A6F0}', '\u{A6F1}'),
+    ('\u{A8E0}', '\u{A8F1}'),
+    ('\u{AAB0}', '\u{AAB0}'),
+    ('\u{AAB2}', '\u{AAB3}'),
+    ('\u{AAB7}', '\u{AAB8}'),
+    ('\u{AABE}', '\u{AABF}'),
+    ('\u{AAC1}', '\u{AAC1}'),
+    ('\u{FE20}', '\u{FE26}'),
+    ('\u{10A0F}', '\u{10A0F}'),
+    ('\u{10A38}', '\u{10A38}'),
+    ('\u{1D185}', '\u{1D189}'),
+    ('\u{1D1AA}', '\u{1D1AD}'),
+    ('\u{1D242}', '\u{1D244}'),
+];
+
+/// The number a combining mark stands for.
+pub fn diacritic_value(mark: char) -> Option<u32> {
+    let mut base = 0;
+    for &(first, last) in DIACRITICS {
+        if (first..=last).contains(&mark) {
+            return Some(base + (mark as u32 - first as u32));
+        }
+        base += last as u32 - first as u32 + 1;
+    }
+    None
+}
+
+/// Consecutive placeholder cells on one line showing consecutive columns
+/// of one image row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaceholderRun {
+    pub image_id: ImageId,
+    /// Placement ID from the underline color; 0 if there is none.
+    pub placement_id: u32,
+    /// Screen row of the cells; negative in the scrollback.
+    pub line: i32,
+    /// Screen column of the first cell.
+    pub column: u32,
+    /// Image row the cells show, in cells of the virtual placement.
+    pub image_row: u32,
+    /// Image column the first cell shows.
+    pub image_column: u32,
+    /// Number of cells.
+    pub columns: u32,
+}
+
+/// Collects placeholder cells into [`PlaceholderRun`]s.
+///
+/// Marks left out take their value from the cell to the left: the same
+/// row, the next column and the same high ID byte, provided that cell is a
+/// placeholder for the same image and placement.
+#[derive(Debug, Default)]
+pub struct PlaceholderRuns {
+    runs: Vec<PlaceholderRun>,
+}
+
+impl PlaceholderRuns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the placeholder at `line`, `column` with the combining `marks`
+    /// after it. `image_id` and `placement_id` are the IDs its foreground
+    /// and underline colors encode: 24-bit colors as `0xRRGGBB`, indexed
+    /// colors as the index. Cells must be added line by line, left to
+    /// right. Cells without an image ID are ignored.
+    pub fn push(
+        &mut self,
+        line: i32,
+        column: u32,
+        image_id: u32,
+        placement_id: u32,
+        marks: &[char],
+    ) {
+        if image_id == 0 {
+            return;
+        }
+        let mut values = marks.iter().map_while(|&mark| diacritic_value(mark));
+        let (row, image_column, high) = (values.next(), values.next(), values.next());
+
+        let left = self.runs.last_mut().filter(|run| {
+            run.line == line
+                && run.column + run.columns == column
+                && run.image_id.0 & 0xFF_FFFF == image_id
+                && run.placement_id == placement_id
+        });
+        let (row, image_column, high) = match left {
+            Some(run) => (
+                row.unwrap_or(run.image_row),
+                image_column.unwrap_or(run.image_column + run.columns),
+                high.unwrap_or(run.image_id.0 >> 24),
+            ),
+            None => (
+                row.unwrap_or(0),
+                image_column.unwrap_or(0),
+                high.unwrap_or(0),
+            ),
+        };
+        let image_id = ImageId((high & 0xFF) << 24 | image_id);
+
+        if let Some(run) = self.runs.last_mut() {
+            if run.line == line
+                && run.column + run.columns == column
+                && run.image_id == image_id
+                && run.placement_id == placement_id
+                && run.image_row == row
+                && run.image_column + run.columns == image_column
+            {
+                run.columns += 1;
+                return;
+            }
+        }
+        self.runs.push(PlaceholderRun {
+            image_id,
+            placement_id,
+            line,
+            column,
+            image_row: row,
+            image_column,
+            columns: 1,
+        });
+    }
+
+    pub fn finish(self) -> Vec<PlaceholderRun> {
+        self.runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The mark for `n`.
+    fn mark(n: u32) -> char {
+        (0..0x20000)
+            .filter_map(char::from_u32)
+            .find(|&c| diacritic_value(c) == Some(n))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_diacritic_values() {
+        assert_eq!(diacritic_value('\u{0305}'), Some(0));
+        assert_eq!(diacritic_value('\u{030D}'), Some(1));
+        assert_eq!(diacritic_value('\u{1D244}'), Some(296));
+        assert_eq!(diacritic_value('a'), None);
+    }
+
+    #[test]
+    fn test_runs_inherit_marks_from_the_left() {
+        let mut runs = PlaceholderRuns::new();
+        // Row 1 spelled out, then two cells that only give the row.
+        runs.push(0, 5, 42, 0, &[mark(1), mark(0), mark(2)]);
+        runs.push(0, 6, 42, 0, &[mark(1)]);
+        runs.push(0, 7, 42, 0, &[]);
+        // A new line starts at column 0 unless told otherwise.
+        runs.push(1, 5, 42, 0, &[mark(2), mark(3)]);
+        // Another placement, and a cell without an image.
+        runs.push(1, 6, 42, 7, &[]);
+        runs.push(1, 7, 0, 0, &[]);
+
+        let runs = runs.finish();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(
+            runs[0],
+            PlaceholderRun {
+                image_id: ImageId(2 << 24 | 42),
+                placement_id: 0,
+                line: 0,
+                column: 5,
+                image_row: 1,
+                image_column: 0,
+                columns: 3,
+            }
+        );
+        assert_eq!(
+            (runs[1].image_id, runs[1].image_row, runs[1].image_column),
+            (ImageId(42), 2, 3)
+        );
+        assert_eq!(
+            (runs[2].placement_id, runs[2].image_row, runs[2].columns),
+            (7, 0, 1)
+        );
+    }
+}
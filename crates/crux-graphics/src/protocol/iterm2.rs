@@ -0,0 +1,263 @@
+//! iTerm2 inline image protocol parser.
+//!
+//! iTerm2 sends whole files, base64-encoded, in an OSC 1337 sequence:
+//!
+//! ```text
+//! OSC 1337 ; File=<key>=<value>;<key>=<value>;... : <base64-data> ST
+//! ```
+//!
+//! Where OSC = `\x1b]` and ST = `\x07` (BEL) or `\x1b\\`. With `inline=1`
+//! the file is an image shown at the cursor; otherwise it is a download.
+//!
+//! Reference: <https://iterm2.com/documentation-images.html>
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+
+use crate::error::GraphicsError;
+use crate::manager::MAX_IMAGE_BYTES;
+
+/// Requested width or height of an inline image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dimension {
+    /// The image's own size, scaled down to fit the screen (`auto`).
+    #[default]
+    Auto,
+    /// A number of cells (`N`).
+    Cells(u32),
+    /// A number of pixels (`Npx`).
+    Pixels(u32),
+    /// A percentage of the screen width or height (`N%`).
+    Percent(u32),
+}
+
+/// A parsed OSC 1337 `File=` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Iterm2Command {
+    /// File name, decoded from the base64 the protocol sends it in.
+    pub name: Option<String>,
+    /// File size the client announced, in bytes.
+    pub size: Option<usize>,
+    /// Display width.
+    pub width: Dimension,
+    /// Display height.
+    pub height: Dimension,
+    /// Keep the image's proportions when both width and height are given
+    /// (`preserveAspectRatio`, default on).
+    pub preserve_aspect_ratio: bool,
+    /// Show the file as an image (`inline=1`) rather than download it.
+    pub inline: bool,
+    /// Leave the cursor where it was after showing the image
+    /// (`doNotMoveCursor=1`).
+    pub hold_cursor: bool,
+    /// The file contents.
+    pub data: Vec<u8>,
+}
+
+impl Default for Iterm2Command {
+    fn default() -> Self {
+        Self {
+            name: None,
+            size: None,
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            preserve_aspect_ratio: true,
+            inline: false,
+            hold_cursor: false,
+            data: Vec::new(),
+        }
+    }
+}
+
+/// Parse an iTerm2 file transfer.
+///
+/// The input should be the raw bytes after `\x1b]1337;File=` and before the
+/// terminator. Format: `key=value;key=value;...:base64data`
+///
+/// Unknown keys are ignored, as iTerm2 does.
+///
+/// # Errors
+///
+/// Returns `GraphicsError::ParseError` for malformed arguments,
+/// `GraphicsError::ImageTooLarge` for files over the per-image limit and
+/// `GraphicsError::Base64Decode` for a corrupt payload.
+pub fn parse_iterm2_command(input: &[u8]) -> Result<Iterm2Command, GraphicsError> {
+    let colon = input
+        .iter()
+        .position(|&b| b == b':')
+        .ok_or_else(|| GraphicsError::ParseError("missing ':' before file data".into()))?;
+    let (args, payload) = (&input[..colon], &input[colon + 1..]);
+    let args = std::str::from_utf8(args)
+        .map_err(|e| GraphicsError::ParseError(format!("invalid UTF-8: {e}")))?;
+
+    let mut cmd = Iterm2Command::default();
+    for pair in args.split(';') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| GraphicsError::ParseError(format!("invalid key-value pair: {pair}")))?;
+        match key {
+            "name" => {
+                let name = base64::engine::general_purpose::STANDARD.decode(value)?;
+                cmd.name = Some(String::from_utf8_lossy(&name).into_owned());
+            }
+            "size" => cmd.size = Some(parse_number(key, value)?),
+            "width" => cmd.width = parse_dimension(value)?,
+            "height" => cmd.height = parse_dimension(value)?,
+            "preserveAspectRatio" => cmd.preserve_aspect_ratio = value != "0",
+            "inline" => cmd.inline = value == "1",
+            "doNotMoveCursor" => cmd.hold_cursor = value == "1",
+            _ => {}
+        }
+    }
+
+    // Check the announced and encoded sizes before decoding anything.
+    let encoded_size = payload.len() / 4 * 3;
+    for size in [cmd.size.unwrap_or(0), encoded_size] {
+        if size > MAX_IMAGE_BYTES {
+            return Err(GraphicsError::ImageTooLarge {
+                size,
+                max: MAX_IMAGE_BYTES,
+            });
+        }
+    }
+    // Some clients wrap the base64 text in lines.
+    let payload: Vec<u8> = payload
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    cmd.data = base64::engine::general_purpose::STANDARD.decode(payload)?;
+    Ok(cmd)
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, GraphicsError> {
+    value
+        .parse()
+        .map_err(|_| GraphicsError::ParseError(format!("invalid {key}: {value}")))
+}
+
+fn parse_dimension(value: &str) -> Result<Dimension, GraphicsError> {
+    if value == "auto" {
+        Ok(Dimension::Auto)
+    } else if let Some(pixels) = value.strip_suffix("px") {
+        parse_number("pixel size", pixels).map(Dimension::Pixels)
+    } else if let Some(percent) = value.strip_suffix('%') {
+        parse_number("percentage", percent).map(Dimension::Percent)
+    } else {
+        parse_number("cell count", value).map(Dimension::Cells)
+    }
+}
+
+/// Save a downloaded file into `dir`, returning where it was written.
+///
+/// Only the last component of `name` is used, so a client cannot write
+/// outside `dir`. An existing file is never overwritten: `a.txt` becomes
+/// `a (1).txt`, `a (2).txt`, and so on.
+pub fn save_download(dir: &Path, name: Option<&str>, data: &[u8]) -> io::Result<PathBuf> {
+    let name = name
+        .and_then(|name| Path::new(name).file_name())
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.starts_with('.'))
+        .unwrap_or("download");
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+
+    std::fs::create_dir_all(dir)?;
+    for n in 0u32.. {
+        let file_name = match (n, extension) {
+            (0, _) => name.to_string(),
+            (n, Some(extension)) => format!("{stem} ({n}).{extension}"),
+            (n, None) => format!("{stem} ({n})"),
+        };
+        let path = dir.join(file_name);
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                io::Write::write_all(&mut file, data)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no free download name",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn b64(data: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(data)
+    }
+
+    #[test]
+    fn test_parse_imgcat_command() {
+        let input = format!(
+            "name={};size=3;width=50%;height=10px;preserveAspectRatio=0;inline=1:{}",
+            b64(b"cat.png"),
+            b64(b"abc")
+        );
+        let cmd = parse_iterm2_command(input.as_bytes()).unwrap();
+        assert_eq!(cmd.name.as_deref(), Some("cat.png"));
+        assert_eq!(cmd.size, Some(3));
+        assert_eq!(cmd.width, Dimension::Percent(50));
+        assert_eq!(cmd.height, Dimension::Pixels(10));
+        assert!(!cmd.preserve_aspect_ratio);
+        assert!(cmd.inline);
+        assert!(!cmd.hold_cursor);
+        assert_eq!(cmd.data, b"abc");
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let cmd = parse_iterm2_command(b"width=auto;height=4;type=image/png:YWJj\nZA==").unwrap();
+        assert_eq!(cmd.width, Dimension::Auto);
+        assert_eq!(cmd.height, Dimension::Cells(4));
+        assert!(cmd.preserve_aspect_ratio);
+        assert!(!cmd.inline);
+        assert_eq!(cmd.name, None);
+        assert_eq!(cmd.data, b"abcd");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_iterm2_command(b"inline=1").is_err());
+        assert!(parse_iterm2_command(b"width=wide:YWJj").is_err());
+        assert!(parse_iterm2_command(b"inline:YWJj").is_err());
+        assert!(parse_iterm2_command(b"inline=1:not base64!").is_err());
+        assert!(matches!(
+            parse_iterm2_command(b"size=999999999999:YWJj"),
+            Err(GraphicsError::ImageTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_save_download() {
+        let dir = std::env::temp_dir().join(format!("crux-iterm2-download-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let first = save_download(&dir, Some("notes.txt"), b"one").unwrap();
+        let second = save_download(&dir, Some("../../notes.txt"), b"two").unwrap();
+        let unnamed = save_download(&dir, None, b"three").unwrap();
+        assert_eq!(first, dir.join("notes.txt"));
+        assert_eq!(second, dir.join("notes (1).txt"));
+        assert_eq!(unnamed, dir.join("download"));
+        assert_eq!(std::fs::read(&second).unwrap(), b"two");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -19,36 +19,63 @@ use crate::types::{ImageId, PixelFormat, TransmissionMode};
 /// Actions that can be performed on images.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KittyAction {
-    /// Transmit image data (possibly with immediate display).
+    /// Transmit image data without displaying it (`a=t`).
     Transmit,
-    /// Transmit and display in one step.
+    /// Transmit and display in one step (`a=T`).
     TransmitAndDisplay,
-    /// Display a previously transmitted image.
+    /// Display a previously transmitted image (`a=p`).
     Display,
     /// Delete images or placements.
     Delete,
     /// Query terminal for graphics protocol support.
     Query,
-    /// Transmit animation frame data.
+    /// Add or edit an animation frame (`a=f`).
+    ///
+    /// `r` is the 1-based frame to edit, a new frame if unset; `c` the frame
+    /// a new frame starts as, the background color `Y` (32-bit RGBA) if
+    /// unset. The data goes at `x`,`y`. `z` is the gap to the next frame in
+    /// milliseconds, negative for none. `X=1` overwrites the pixels beneath
+    /// instead of blending.
     AnimationFrame,
+    /// Control an animation (`a=a`).
+    ///
+    /// `s` sets the state: 1 stops, 2 plays and waits for more frames at
+    /// the end, 3 plays in a loop. `c` makes a 1-based frame current, `r`
+    /// and `z` set a frame's gap and `v` the loop count: 1 loops forever,
+    /// `n` loops `n - 1` times.
+    AnimationControl,
+    /// Compose one animation frame onto another (`a=c`).
+    ///
+    /// The `w`x`h` rectangle at `X`,`Y` of frame `r` is drawn at `x`,`y` of
+    /// frame `c`, both 1-based and the image itself if unset. `C=1`
+    /// overwrites instead of blending.
+    ComposeFrames,
 }
 
 /// Specifies what to delete.
+///
+/// Cell coordinates are 0-based here; the protocol sends them 1-based.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeleteTarget {
-    /// Delete all images and placements.
+    /// Delete all placements visible on screen (`d=a`).
     All,
-    /// Delete a specific image by ID (and all its placements).
+    /// Delete all placements of an image (`d=i`).
     ById(ImageId),
-    /// Delete a specific placement.
+    /// Delete a specific placement (`d=i` with `p`).
     ByPlacement {
         image_id: ImageId,
         placement_id: u32,
     },
-    /// Delete all images at the cursor position.
+    /// Delete all placements covering the cursor cell (`d=c`).
     AtCursor,
-    /// Delete all images intersecting a cell range.
+    /// Delete all placements covering a cell (`d=p`).
     InRange { column: u32, row: i32 },
+    /// Delete all placements intersecting a column (`d=x`).
+    Column(u32),
+    /// Delete all placements intersecting a row (`d=y`).
+    Row(i32),
+    /// Delete all placements with a z-index (`d=z`).
+    ZIndex(i32),
 }
 
 /// Compression format for transmitted data.
@@ -96,12 +123,27 @@ pub struct KittyCommand {
     pub source_height: u32,
     /// Z-index for layering.
     pub z_index: i32,
+    /// Pixel offset of the image within its first cell, horizontally.
+    pub cell_x_offset: u32,
+    /// Pixel offset of the image within its first cell, vertically.
+    pub cell_y_offset: u32,
+    /// `C=1`: leave the cursor where it is after displaying the image.
+    pub hold_cursor: bool,
+    /// `U=1`: create a virtual placement, shown by Unicode placeholders.
+    pub unicode_placeholder: bool,
+    /// Number of bytes to read from a file or shared memory (0 = all).
+    pub data_size: u32,
+    /// Offset to start reading a file or shared memory at.
+    pub data_offset: u32,
     /// The base64-encoded payload data.
     pub payload: Vec<u8>,
     /// Whether to suppress the OK response.
     pub quiet: u8,
     /// Delete target (only relevant when action is Delete).
     pub delete_target: Option<DeleteTarget>,
+    /// Upper-case delete specifier: also free the image data once no
+    /// placement refers to it.
+    pub delete_data: bool,
 }
 
 impl Default for KittyCommand {
@@ -123,9 +165,16 @@ impl Default for KittyCommand {
             source_width: 0,
             source_height: 0,
             z_index: 0,
+            cell_x_offset: 0,
+            cell_y_offset: 0,
+            hold_cursor: false,
+            unicode_placeholder: false,
+            data_size: 0,
+            data_offset: 0,
             payload: Vec::new(),
             quiet: 0,
             delete_target: None,
+            delete_data: false,
         }
     }
 }
@@ -170,7 +219,6 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
 
     // Parse key=value pairs
     let mut delete_specifier: Option<char> = None;
-    let mut delete_value: Option<String> = None;
 
     for pair in params_str.split(',') {
         if pair.is_empty() {
@@ -189,12 +237,19 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
             // Action
             "a" => {
                 cmd.action = match value {
-                    "t" | "T" => KittyAction::Transmit,
-                    "p" | "P" => KittyAction::TransmitAndDisplay,
-                    "d" | "D" => KittyAction::Delete,
-                    "q" | "Q" => KittyAction::Query,
-                    "f" | "F" => KittyAction::AnimationFrame,
-                    _ => KittyAction::TransmitAndDisplay,
+                    "t" => KittyAction::Transmit,
+                    "T" => KittyAction::TransmitAndDisplay,
+                    "p" => KittyAction::Display,
+                    "d" => KittyAction::Delete,
+                    "q" => KittyAction::Query,
+                    "f" => KittyAction::AnimationFrame,
+                    "a" => KittyAction::AnimationControl,
+                    "c" => KittyAction::ComposeFrames,
+                    _ => {
+                        return Err(GraphicsError::ParseError(format!(
+                            "unsupported action: {value}"
+                        )));
+                    }
                 };
             }
             // Image ID
@@ -277,6 +332,28 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
                     .parse::<i32>()
                     .map_err(|e| GraphicsError::ParseError(format!("invalid z-index: {e}")))?;
             }
+            // Pixel offsets within the first cell
+            "X" => {
+                cmd.cell_x_offset = parse_u32(value, "cell x offset")?;
+            }
+            "Y" => {
+                cmd.cell_y_offset = parse_u32(value, "cell y offset")?;
+            }
+            // Cursor movement policy
+            "C" => {
+                cmd.hold_cursor = value == "1";
+            }
+            // Virtual placement for Unicode placeholders
+            "U" => {
+                cmd.unicode_placeholder = value == "1";
+            }
+            // Size and offset of file or shared memory data
+            "S" => {
+                cmd.data_size = parse_u32(value, "data size")?;
+            }
+            "O" => {
+                cmd.data_offset = parse_u32(value, "data offset")?;
+            }
             // Quiet mode
             "q" => {
                 cmd.quiet = value
@@ -285,12 +362,7 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
             }
             // Delete specifier
             "d" => {
-                if let Some(ch) = value.chars().next() {
-                    delete_specifier = Some(ch);
-                    if value.len() > 1 {
-                        delete_value = Some(value[1..].to_string());
-                    }
-                }
+                delete_specifier = value.chars().next();
             }
             // Ignore unknown keys for forward compatibility
             _ => {
@@ -301,9 +373,14 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
 
     // Resolve delete target if action is Delete
     if cmd.action == KittyAction::Delete {
-        cmd.delete_target = Some(match delete_specifier {
-            Some('a') | Some('A') => DeleteTarget::All,
-            Some('i') | Some('I') => {
+        let specifier = delete_specifier.unwrap_or('a');
+        cmd.delete_data = specifier.is_ascii_uppercase();
+        // `x` and `y` are 1-based cell coordinates for the cell-based targets.
+        let column = cmd.source_x.saturating_sub(1);
+        let row = cmd.source_y.saturating_sub(1) as i32;
+        cmd.delete_target = Some(match specifier.to_ascii_lowercase() {
+            'a' => DeleteTarget::All,
+            'i' => {
                 if cmd.image_id > 0 {
                     if cmd.placement_id > 0 {
                         DeleteTarget::ByPlacement {
@@ -317,18 +394,16 @@ pub fn parse_kitty_command(input: &[u8]) -> Result<KittyCommand, GraphicsError>
                     DeleteTarget::All
                 }
             }
-            Some('c') | Some('C') => DeleteTarget::AtCursor,
-            Some('p') | Some('P') => {
-                let col = delete_value
-                    .as_deref()
-                    .and_then(|v| v.parse::<u32>().ok())
-                    .unwrap_or(0);
-                DeleteTarget::InRange {
-                    column: col,
-                    row: 0,
-                }
+            'c' => DeleteTarget::AtCursor,
+            'p' => DeleteTarget::InRange { column, row },
+            'x' => DeleteTarget::Column(column),
+            'y' => DeleteTarget::Row(row),
+            'z' => DeleteTarget::ZIndex(cmd.z_index),
+            other => {
+                return Err(GraphicsError::ParseError(format!(
+                    "unsupported delete target: {other}"
+                )));
             }
-            _ => DeleteTarget::All,
         });
     }
 
@@ -368,7 +443,7 @@ mod tests {
     fn test_parse_transmit_and_display() {
         let input = b"a=T,f=24,s=200,v=100,i=5;AQID";
         let cmd = parse_kitty_command(input).unwrap();
-        assert_eq!(cmd.action, KittyAction::Transmit);
+        assert_eq!(cmd.action, KittyAction::TransmitAndDisplay);
         assert_eq!(cmd.format, PixelFormat::Rgb);
         assert_eq!(cmd.width, 200);
         assert_eq!(cmd.height, 100);
@@ -378,10 +453,12 @@ mod tests {
     #[test]
     fn test_parse_display_action() {
         // Note: 'p' for display is overloaded with placement_id key.
-        // The action key is 'a', value 'p' means TransmitAndDisplay.
-        let input = b"a=p,i=3,p=1,c=10,r=5,z=-1";
+        // The action key is 'a', value 'p' means put (display).
+        let input = b"a=p,i=3,p=1,c=10,r=5,z=-1,X=4,Y=2,C=1";
         let cmd = parse_kitty_command(input).unwrap();
-        assert_eq!(cmd.action, KittyAction::TransmitAndDisplay);
+        assert_eq!(cmd.action, KittyAction::Display);
+        assert_eq!((cmd.cell_x_offset, cmd.cell_y_offset), (4, 2));
+        assert!(cmd.hold_cursor);
         assert_eq!(cmd.image_id, 3);
         assert_eq!(cmd.placement_id, 1);
         assert_eq!(cmd.display_columns, 10);
@@ -419,6 +496,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_delete_cell_targets() {
+        let cmd = parse_kitty_command(b"a=d,d=P,x=3,y=2").unwrap();
+        assert_eq!(
+            cmd.delete_target,
+            Some(DeleteTarget::InRange { column: 2, row: 1 })
+        );
+        assert!(cmd.delete_data);
+
+        let cmd = parse_kitty_command(b"a=d,d=y,y=5").unwrap();
+        assert_eq!(cmd.delete_target, Some(DeleteTarget::Row(4)));
+        assert!(!cmd.delete_data);
+
+        let cmd = parse_kitty_command(b"a=d,d=z,z=-1").unwrap();
+        assert_eq!(cmd.delete_target, Some(DeleteTarget::ZIndex(-1)));
+
+        // A multi-byte specifier must not split a character.
+        assert!(parse_kitty_command("a=d,d=éx".as_bytes()).is_err());
+    }
+
     #[test]
     fn test_parse_chunked_transfer() {
         let chunk1 = b"a=t,f=32,s=100,v=50,i=1,m=1;AAAA";
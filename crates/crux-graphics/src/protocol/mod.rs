@@ -3,9 +3,9 @@
 //! Each sub-module implements parsing for a specific terminal graphics protocol:
 //!
 //! - [`kitty`] — Kitty graphics protocol (APC-based, most capable)
-//!
-//! Future additions:
-//! - iTerm2 (OSC 1337)
-//! - Sixel (DCS-based, legacy)
+//! - [`iterm2`] — iTerm2 inline images (OSC 1337, whole files)
+//! - [`sixel`] — Sixel graphics (DCS-based, legacy)
 
+pub mod iterm2;
 pub mod kitty;
+pub mod sixel;
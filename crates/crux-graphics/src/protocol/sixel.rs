@@ -0,0 +1,344 @@
+//! Sixel graphics decoder.
+//!
+//! Sixel images arrive in a DCS sequence:
+//!
+//! ```text
+//! DCS P1 ; P2 ; P3 q <sixel-data> ST
+//! ```
+//!
+//! Where DCS = `\x1bP` and ST = `\x1b\\`. Of the parameters only P2 matters
+//! here: `1` leaves pixels that are never drawn transparent, anything else
+//! fills them with color register 0. The pixel aspect ratio (P1 and the
+//! raster attributes) is ignored; pixels are square, as in xterm and foot.
+//!
+//! The data is a stream of commands:
+//!
+//! - `?`..`~`: a column of six pixels, bit 0 on top, in the current color
+//! - `!Pn<sixel>`: the sixel repeated `Pn` times
+//! - `#Pc`: select color register `Pc`
+//! - `#Pc;Pu;Px;Py;Pz`: define register `Pc` as HLS (`Pu=1`) or RGB (`Pu=2`)
+//! - `"Pan;Pad;Ph;Pv`: raster attributes, an image size of `Ph`x`Pv`
+//! - `$`: back to the left edge; `-`: down to the next six-pixel band
+//!
+//! Reference: <https://vt100.net/docs/vt3xx-gp/chapter14.html>
+
+use crate::error::GraphicsError;
+use crate::manager::MAX_IMAGE_BYTES;
+use crate::types::{ImageData, PixelFormat};
+
+/// Number of color registers, as in xterm.
+const COLOR_REGISTERS: usize = 1024;
+
+/// Largest image decoded, in pixels.
+const MAX_PIXELS: usize = MAX_IMAGE_BYTES / 4;
+
+/// Largest width or height decoded, in pixels.
+const MAX_SIDE: usize = 16384;
+
+/// The VT340's 16 default colors, in percent RGB.
+const DEFAULT_PALETTE: [[u16; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+/// Decode the data of a Sixel DCS sequence into BGRA pixels.
+///
+/// `params` are the numeric DCS parameters before `q`; `data` is everything
+/// after it, up to the terminator.
+///
+/// # Errors
+///
+/// Returns `GraphicsError::InvalidDimensions` when nothing is drawn and no
+/// size is given, and `GraphicsError::ImageTooLarge` for images over the
+/// per-image limit.
+pub fn decode_sixel(params: &[u16], data: &[u8]) -> Result<ImageData, GraphicsError> {
+    let transparent = params.get(1) == Some(&1);
+    let mut decoder = Decoder::new();
+    let mut bytes = data.iter().copied().peekable();
+
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'?'..=b'~' => decoder.draw(byte - b'?', 1)?,
+            b'!' => {
+                let count = read_number(&mut bytes).unwrap_or(1).max(1);
+                // The sixel to repeat; anything else cancels the repeat.
+                if let Some(&sixel @ b'?'..=b'~') = bytes.peek() {
+                    bytes.next();
+                    decoder.draw(sixel - b'?', count)?;
+                }
+            }
+            b'#' => {
+                let args = read_numbers(&mut bytes);
+                decoder.select_color(&args);
+            }
+            b'"' => {
+                let args = read_numbers(&mut bytes);
+                decoder.set_raster(&args)?;
+            }
+            b'$' => decoder.x = 0,
+            b'-' => {
+                decoder.x = 0;
+                decoder.y = decoder.y.saturating_add(6);
+            }
+            _ => {}
+        }
+    }
+
+    decoder.finish(transparent)
+}
+
+/// Read a decimal number, if one comes next.
+fn read_number(bytes: &mut std::iter::Peekable<impl Iterator<Item = u8>>) -> Option<u32> {
+    let mut value: Option<u32> = None;
+    while let Some(&digit @ b'0'..=b'9') = bytes.peek() {
+        bytes.next();
+        let v = value.unwrap_or(0);
+        value = Some(v.saturating_mul(10).saturating_add(u32::from(digit - b'0')));
+    }
+    value
+}
+
+/// Read `;`-separated numbers; empty ones are 0.
+fn read_numbers(bytes: &mut std::iter::Peekable<impl Iterator<Item = u8>>) -> Vec<u32> {
+    let mut values = vec![read_number(bytes).unwrap_or(0)];
+    while bytes.peek() == Some(&b';') {
+        bytes.next();
+        values.push(read_number(bytes).unwrap_or(0));
+    }
+    values
+}
+
+/// Convert a percentage to an 8-bit channel.
+fn channel(percent: u32) -> u8 {
+    ((percent.min(100) * 255 + 50) / 100) as u8
+}
+
+/// Convert DEC HLS to RGB. DEC puts blue at 0 degrees and red at 120, a
+/// third of a turn away from the usual HSL wheel.
+fn hls_to_bgra(hue: u32, lightness: u32, saturation: u32) -> [u8; 4] {
+    let h = f64::from((hue % 360 + 240) % 360) / 60.0;
+    let l = f64::from(lightness.min(100)) / 100.0;
+    let s = f64::from(saturation.min(100)) / 100.0;
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = l - chroma / 2.0;
+    let to_u8 = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    [to_u8(b), to_u8(g), to_u8(r), 255]
+}
+
+struct Decoder {
+    palette: Vec<[u8; 4]>,
+    color: [u8; 4],
+    x: u32,
+    y: u32,
+    /// Pixel rows, each as long as the rightmost pixel drawn in it.
+    /// Pixels never drawn have alpha 0.
+    rows: Vec<Vec<[u8; 4]>>,
+    pixels: usize,
+    raster: (u32, u32),
+}
+
+impl Decoder {
+    fn new() -> Self {
+        let mut palette = vec![[0, 0, 0, 255]; COLOR_REGISTERS];
+        for (register, [r, g, b]) in palette.iter_mut().zip(DEFAULT_PALETTE) {
+            *register = [channel(b.into()), channel(g.into()), channel(r.into()), 255];
+        }
+        Self {
+            color: palette[0],
+            palette,
+            x: 0,
+            y: 0,
+            rows: Vec::new(),
+            pixels: 0,
+            raster: (0, 0),
+        }
+    }
+
+    fn select_color(&mut self, args: &[u32]) {
+        let register = args[0] as usize % COLOR_REGISTERS;
+        if let [_, space, a, b, c, ..] = *args {
+            match space {
+                1 => self.palette[register] = hls_to_bgra(a, b, c),
+                2 => self.palette[register] = [channel(c), channel(b), channel(a), 255],
+                _ => {}
+            }
+        }
+        self.color = self.palette[register];
+    }
+
+    fn set_raster(&mut self, args: &[u32]) -> Result<(), GraphicsError> {
+        if let [_, _, width, height, ..] = *args {
+            check_size(width as usize, height as usize)?;
+            self.raster = (width, height);
+        }
+        Ok(())
+    }
+
+    /// Draw `sixel` `count` times at the current position.
+    fn draw(&mut self, sixel: u8, count: u32) -> Result<(), GraphicsError> {
+        let start = self.x as usize;
+        let end = start.saturating_add(count as usize);
+        self.x = self.x.saturating_add(count);
+        if sixel == 0 {
+            return Ok(());
+        }
+        for bit in 0..6 {
+            if sixel & (1 << bit) == 0 {
+                continue;
+            }
+            let y = self.y as usize + bit;
+            if y >= self.rows.len() {
+                check_size(end, y + 1)?;
+                self.rows.resize_with(y + 1, Vec::new);
+            }
+            let row = &mut self.rows[y];
+            if row.len() < end {
+                self.pixels += end - row.len();
+                if self.pixels > MAX_PIXELS {
+                    return Err(too_large(self.pixels));
+                }
+                row.resize(end, [0; 4]);
+            }
+            row[start..end].fill(self.color);
+        }
+        Ok(())
+    }
+
+    fn finish(self, transparent: bool) -> Result<ImageData, GraphicsError> {
+        let width = self
+            .rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(self.raster.0 as usize);
+        let height = self.rows.len().max(self.raster.1 as usize);
+        if width == 0 || height == 0 {
+            return Err(GraphicsError::InvalidDimensions {
+                width: width as u32,
+                height: height as u32,
+            });
+        }
+        check_size(width, height)?;
+
+        let background = if transparent { [0; 4] } else { self.palette[0] };
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let row = self.rows.get(y).map(Vec::as_slice).unwrap_or(&[]);
+            for x in 0..width {
+                let pixel = match row.get(x) {
+                    Some(pixel) if pixel[3] != 0 => *pixel,
+                    _ => background,
+                };
+                data.extend_from_slice(&pixel);
+            }
+        }
+        Ok(ImageData::new(
+            data,
+            width as u32,
+            height as u32,
+            PixelFormat::Bgra,
+        ))
+    }
+}
+
+fn check_size(width: usize, height: usize) -> Result<(), GraphicsError> {
+    let pixels = width.saturating_mul(height);
+    if pixels > MAX_PIXELS || width > MAX_SIDE || height > MAX_SIDE {
+        return Err(too_large(pixels));
+    }
+    Ok(())
+}
+
+fn too_large(pixels: usize) -> GraphicsError {
+    GraphicsError::ImageTooLarge {
+        size: pixels.saturating_mul(4),
+        max: MAX_IMAGE_BYTES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(image: &ImageData, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * image.width + x) * 4) as usize;
+        image.data[i..i + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_decode_colors_and_bands() {
+        // Red over a full band, then a second band with one green pixel.
+        let image = decode_sixel(&[0, 1], b"#1;2;100;0;0#1~~-#2;2;0;100;0A").unwrap();
+        assert_eq!((image.width, image.height), (2, 8));
+        assert_eq!(image.format, PixelFormat::Bgra);
+        assert_eq!(pixel(&image, 1, 5), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 0, 7), [0, 255, 0, 255]);
+        assert_eq!(pixel(&image, 1, 7), [0, 0, 0, 0], "transparent background");
+    }
+
+    #[test]
+    fn test_decode_repeat_and_carriage_return() {
+        // Top pixel blue across 5 columns, then the next one at the left.
+        let image = decode_sixel(&[], b"#1!5@$#3A").unwrap();
+        assert_eq!((image.width, image.height), (5, 2));
+        assert_eq!(pixel(&image, 4, 0), [204, 51, 51, 255]);
+        assert_eq!(pixel(&image, 0, 1), [51, 204, 51, 255]);
+        // Opaque background: register 0, black.
+        assert_eq!(pixel(&image, 4, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decode_raster_and_hls() {
+        // HLS: hue 120 is red in DEC's wheel, 0 is blue.
+        let image = decode_sixel(&[0, 1], b"\"1;1;4;3#5;1;120;50;100@#6;1;0;50;100@").unwrap();
+        assert_eq!((image.width, image.height), (4, 3));
+        assert_eq!(pixel(&image, 0, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 3, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(
+            decode_sixel(&[], b"#1;2;100;0;0"),
+            Err(GraphicsError::InvalidDimensions { .. })
+        ));
+        assert!(matches!(
+            decode_sixel(&[], b"\"1;1;100000;100000"),
+            Err(GraphicsError::ImageTooLarge { .. })
+        ));
+        assert!(matches!(
+            decode_sixel(&[], b"!99999999~"),
+            Err(GraphicsError::ImageTooLarge { .. })
+        ));
+        let tall = [b"-".repeat(3000), b"@".to_vec()].concat();
+        assert!(matches!(
+            decode_sixel(&[], &tall),
+            Err(GraphicsError::ImageTooLarge { .. })
+        ));
+    }
+}
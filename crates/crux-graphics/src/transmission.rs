@@ -0,0 +1,308 @@
+//! Reading image data that is sent by reference.
+//!
+//! Besides inline base64, Kitty clients can name a file, a temporary file
+//! the terminal deletes after reading, or a POSIX shared memory object.
+//! The payload of such a command is the (base64-encoded) name.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::error::GraphicsError;
+use crate::manager::MAX_IMAGE_BYTES;
+use crate::types::TransmissionMode;
+
+/// Marker a temporary file's name must contain before it is deleted.
+const TEMP_FILE_MARKER: &str = "tty-graphics-protocol";
+
+/// Read `size` bytes (0 = to the end) starting at `offset` from the medium
+/// `name` refers to.
+pub fn read_medium(
+    mode: TransmissionMode,
+    name: &[u8],
+    size: usize,
+    offset: usize,
+) -> Result<Vec<u8>, GraphicsError> {
+    let name = std::str::from_utf8(name)
+        .map_err(|_| GraphicsError::ParseError("medium name is not UTF-8".into()))?;
+    match mode {
+        TransmissionMode::Direct => Err(GraphicsError::ParseError(
+            "direct transmission has no medium".into(),
+        )),
+        TransmissionMode::File => read_file(Path::new(name), size, offset),
+        TransmissionMode::TempFile => {
+            let path = Path::new(name);
+            let data = read_file(path, size, offset);
+            if is_deletable_temp_file(path) {
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::debug!("failed to remove {}: {}", path.display(), e);
+                }
+            } else {
+                log::warn!("not deleting {}: not a graphics temp file", path.display());
+            }
+            data
+        }
+        TransmissionMode::SharedMemory => read_shared_memory(name, size, offset),
+    }
+}
+
+/// Read from a regular file. Devices, FIFOs and directories are refused so
+/// a client cannot make the terminal block or read without bound.
+///
+/// The type is checked before opening, because opening a FIFO waits for a
+/// writer. The file is then opened non-blocking and checked again, in case
+/// it was replaced in between.
+fn read_file(path: &Path, size: usize, offset: usize) -> Result<Vec<u8>, GraphicsError> {
+    if !std::fs::metadata(path)?.is_file() {
+        return Err(not_regular_file(path));
+    }
+    let mut options = OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    options.custom_flags(libc::O_NONBLOCK);
+    let mut file = options.open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(not_regular_file(path));
+    }
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let limit = if size == 0 { MAX_IMAGE_BYTES + 1 } else { size };
+    let mut data = Vec::new();
+    file.take(limit as u64).read_to_end(&mut data)?;
+    check_size(data.len())?;
+    Ok(data)
+}
+
+fn not_regular_file(path: &Path) -> GraphicsError {
+    GraphicsError::FileError(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{} is not a regular file", path.display()),
+    ))
+}
+
+/// Temporary files are only deleted when they live in a temporary
+/// directory and carry the protocol's marker in their name. Directories are
+/// compared canonicalized, so `..` components and symlinks cannot lead out
+/// of the temporary directory.
+fn is_deletable_temp_file(path: &Path) -> bool {
+    let in_temp_dir = path
+        .parent()
+        .and_then(|dir| dir.canonicalize().ok())
+        .is_some_and(|dir| {
+            [std::env::temp_dir(), "/tmp".into(), "/dev/shm".into()]
+                .iter()
+                .filter_map(|temp| temp.canonicalize().ok())
+                .any(|temp| dir.starts_with(temp))
+        });
+    let marked = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(TEMP_FILE_MARKER));
+    in_temp_dir && marked
+}
+
+fn check_size(size: usize) -> Result<(), GraphicsError> {
+    if size > MAX_IMAGE_BYTES {
+        return Err(GraphicsError::ImageTooLarge {
+            size,
+            max: MAX_IMAGE_BYTES,
+        });
+    }
+    Ok(())
+}
+
+/// Read from a POSIX shared memory object, then unlink it as the protocol
+/// requires.
+#[cfg(unix)]
+fn read_shared_memory(name: &str, size: usize, offset: usize) -> Result<Vec<u8>, GraphicsError> {
+    use std::ffi::CString;
+
+    let c_name = CString::new(name)
+        .map_err(|_| GraphicsError::ParseError("shared memory name contains NUL".into()))?;
+    // SAFETY: `c_name` is a valid NUL-terminated string.
+    let fd = unsafe { libc::shm_open(c_name.as_ptr(), libc::O_RDONLY, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let result = read_shared_memory_fd(fd, size, offset);
+    // SAFETY: `fd` was opened above and is not used afterwards.
+    unsafe {
+        libc::close(fd);
+        libc::shm_unlink(c_name.as_ptr());
+    }
+    result
+}
+
+#[cfg(unix)]
+fn read_shared_memory_fd(
+    fd: libc::c_int,
+    size: usize,
+    offset: usize,
+) -> Result<Vec<u8>, GraphicsError> {
+    // SAFETY: `stat` is plain data, filled in by `fstat` on an open fd.
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let total = stat.st_size.max(0) as usize;
+    if offset >= total {
+        return Err(GraphicsError::InsufficientData {
+            expected: offset + size.max(1),
+            actual: total,
+        });
+    }
+    let len = if size == 0 {
+        total - offset
+    } else {
+        size.min(total - offset)
+    };
+    check_size(len)?;
+
+    // SAFETY: the mapping covers `total` bytes of a readable object and is
+    // only read within `offset..offset + len` before being unmapped.
+    unsafe {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            total,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let data = std::slice::from_raw_parts((ptr as *const u8).add(offset), len).to_vec();
+        libc::munmap(ptr, total);
+        Ok(data)
+    }
+}
+
+#[cfg(not(unix))]
+fn read_shared_memory(_name: &str, _size: usize, _offset: usize) -> Result<Vec<u8>, GraphicsError> {
+    Err(GraphicsError::UnsupportedFormat(
+        "shared memory transmission".into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("crux-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_read_file_with_offset_and_size() {
+        let path = temp_path("medium-file");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let name = path.to_str().unwrap().as_bytes();
+        assert_eq!(
+            read_medium(TransmissionMode::File, name, 0, 0).unwrap(),
+            b"0123456789"
+        );
+        assert_eq!(
+            read_medium(TransmissionMode::File, name, 3, 2).unwrap(),
+            b"234"
+        );
+        // A plain file is left alone.
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            read_medium(TransmissionMode::File, name, 0, 0),
+            Err(GraphicsError::FileError(_))
+        ));
+    }
+
+    #[test]
+    fn test_temp_file_is_deleted_only_when_marked() {
+        let marked = temp_path("tty-graphics-protocol");
+        std::fs::write(&marked, b"pixels").unwrap();
+        let data = read_medium(
+            TransmissionMode::TempFile,
+            marked.to_str().unwrap().as_bytes(),
+            0,
+            0,
+        );
+        assert_eq!(data.unwrap(), b"pixels");
+        assert!(!marked.exists());
+
+        let unmarked = temp_path("medium-unmarked");
+        std::fs::write(&unmarked, b"pixels").unwrap();
+        read_medium(
+            TransmissionMode::TempFile,
+            unmarked.to_str().unwrap().as_bytes(),
+            0,
+            0,
+        )
+        .unwrap();
+        assert!(unmarked.exists());
+        std::fs::remove_file(&unmarked).unwrap();
+    }
+
+    #[test]
+    fn test_temp_file_outside_temp_dir_is_kept() {
+        let dir = std::env::current_dir()
+            .unwrap()
+            .join(format!("crux-escape-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("x-tty-graphics-protocol-y");
+        std::fs::write(&file, b"pixels").unwrap();
+
+        // Reaches the file through the temporary directory, then back out.
+        let mut escaping = std::env::temp_dir();
+        for _ in std::env::temp_dir()
+            .canonicalize()
+            .unwrap()
+            .components()
+            .skip(1)
+        {
+            escaping.push("..");
+        }
+        let escaping = escaping.join(file.strip_prefix("/").unwrap());
+        assert!(escaping.exists());
+        assert!(!is_deletable_temp_file(&escaping));
+        read_medium(
+            TransmissionMode::TempFile,
+            escaping.to_str().unwrap().as_bytes(),
+            0,
+            0,
+        )
+        .unwrap();
+        assert!(file.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_directories_are_refused() {
+        let dir = std::env::temp_dir();
+        let result = read_medium(
+            TransmissionMode::File,
+            dir.to_str().unwrap().as_bytes(),
+            0,
+            0,
+        );
+        assert!(result.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_shared_memory() {
+        use std::ffi::CString;
+
+        let name = format!("/crux-shm-test-{}", std::process::id());
+        let c_name = CString::new(name.clone()).unwrap();
+        unsafe {
+            let fd = libc::shm_open(c_name.as_ptr(), libc::O_CREAT | libc::O_RDWR, 0o600);
+            assert!(fd >= 0);
+            assert_eq!(libc::write(fd, b"abcdef".as_ptr().cast(), 6), 6);
+            libc::close(fd);
+        }
+        let data = read_medium(TransmissionMode::SharedMemory, name.as_bytes(), 2, 1).unwrap();
+        assert_eq!(data, b"bc");
+        // The object is unlinked after reading.
+        assert!(read_medium(TransmissionMode::SharedMemory, name.as_bytes(), 0, 0).is_err());
+    }
+}
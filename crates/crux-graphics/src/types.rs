@@ -113,8 +113,10 @@ pub struct ImagePlacement {
     pub placement_id: u32,
     /// Column position in the terminal grid (0-indexed).
     pub column: u32,
-    /// Row position in the terminal grid (0-indexed, relative to scrollback).
-    pub row: i32,
+    /// Absolute grid line: the lines that had scrolled off the top of the
+    /// screen when it was placed, plus its screen row. Scrolling leaves it
+    /// alone; see [`ImageManager::top_line`](crate::ImageManager::top_line).
+    pub row: i64,
     /// Display width in columns (0 = auto from image dimensions).
     pub columns: u32,
     /// Display height in rows (0 = auto from image dimensions).
@@ -129,6 +131,10 @@ pub struct ImagePlacement {
     pub source_height: u32,
     /// Z-index for layering: negative = under text, positive = over text.
     pub z_index: i32,
+    /// Horizontal pixel offset of the image within its first cell.
+    pub x_offset: u32,
+    /// Vertical pixel offset of the image within its first cell.
+    pub y_offset: u32,
 }
 
 impl ImagePlacement {
@@ -146,8 +152,35 @@ impl ImagePlacement {
             source_width: 0,
             source_height: 0,
             z_index: 0,
+            x_offset: 0,
+            y_offset: 0,
         }
     }
+
+    /// Whether the placement covers any of the rows in `start_row..end_row`.
+    /// A placement without a size covers its own cell.
+    pub fn intersects_rows(&self, start_row: i64, end_row: i64) -> bool {
+        self.row < end_row && self.end_row() > start_row
+    }
+
+    /// The row below the placement.
+    pub fn end_row(&self) -> i64 {
+        self.row.saturating_add(i64::from(self.rows.max(1)))
+    }
+
+    /// Whether the placement covers any of the columns in
+    /// `start_column..end_column`.
+    pub fn intersects_columns(&self, start_column: u32, end_column: u32) -> bool {
+        let columns = self.columns.max(1) as u64;
+        (self.column as u64) < end_column as u64
+            && self.column as u64 + columns > start_column as u64
+    }
+
+    /// Whether the placement covers the cell at `column`, `row`.
+    pub fn covers(&self, column: u32, row: i64) -> bool {
+        self.intersects_columns(column, column.saturating_add(1))
+            && self.intersects_rows(row, row.saturating_add(1))
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,810 @@
+//! `IpcCommand` handling for the headless server.
+//!
+//! Mirrors `crux-app`'s `ipc_dispatch.rs`; responses are built with the
+//! same `crux_terminal::protocol` helpers so both front ends agree on the
+//! wire format.
+
+use std::sync::Arc;
+
+use crux_ipc::{CommandStarted, IpcCommand};
+use crux_protocol::{
+    LayoutNode, LayoutPane, OutputFormat, PaneId, RunCommandResult, Session, SessionPane, TabId,
+    WindowId,
+};
+use crux_terminal::{protocol, CommandCapture, OutputSink};
+
+use crate::layout::{Axis, Layout};
+use crate::server::{HeadlessPane, HeadlessServer};
+
+/// Nominal cell size used to convert `crux:pane/resize` pixel requests.
+const CELL_WIDTH_PX: f32 = 8.0;
+const CELL_HEIGHT_PX: f32 = 16.0;
+
+/// The headless server has a single virtual window holding a single tab.
+const HEADLESS_WINDOW: WindowId = WindowId(0);
+const HEADLESS_TAB: TabId = TabId(0);
+
+/// Maximum bytes of output kept for a single `crux:pane/run-command`.
+const RUN_COMMAND_MAX_OUTPUT: usize = 8 * 1024 * 1024;
+
+fn pane_not_found(pane_id: Option<PaneId>) -> anyhow::Error {
+    match pane_id {
+        Some(id) => anyhow::anyhow!("pane {} not found", id),
+        None => anyhow::anyhow!("no active pane"),
+    }
+}
+
+impl HeadlessServer {
+    /// Resolve a pane ID from an optional parameter, falling back to the active pane.
+    fn resolve_pane(&mut self, pane_id: Option<PaneId>) -> Option<(PaneId, &mut HeadlessPane)> {
+        let id = pane_id.or_else(|| self.active_pane_id())?;
+        let pane = self.panes.get_mut(&id)?;
+        Some((id, pane))
+    }
+
+    pub(crate) fn handle_ipc_command(&mut self, cmd: IpcCommand) {
+        match cmd {
+            IpcCommand::Handshake { params: _, reply } => {
+                let result = crux_protocol::HandshakeResult {
+                    server_name: "crux-headless".into(),
+                    server_version: env!("CARGO_PKG_VERSION").into(),
+                    protocol_version: "1.0".into(),
+                    supported_capabilities: vec!["pane".into()],
+                };
+                let _ = reply.send(Ok(result));
+            }
+
+            IpcCommand::SplitPane { params, reply } => {
+                let _ = reply.send(self.handle_split_pane(params));
+            }
+
+            IpcCommand::SendText { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((_id, pane)) => {
+                        let text = params.text.as_bytes();
+                        if params.bracketed_paste {
+                            pane.terminal.write_to_pty(b"\x1b[200~");
+                            pane.terminal.write_to_pty(text);
+                            pane.terminal.write_to_pty(b"\x1b[201~");
+                        } else {
+                            pane.terminal.write_to_pty(text);
+                        }
+                        Ok(crux_protocol::SendTextResult {
+                            bytes_written: text.len(),
+                        })
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::GetText { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((_id, pane)) => Ok(protocol::text_result(pane.terminal.read_text(
+                        params.start_line,
+                        params.end_line,
+                        params.include_escapes,
+                    ))),
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::PaneHistory { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((pane_id, pane)) => {
+                        let commands = pane
+                            .terminal
+                            .history()
+                            .query(&protocol::history_filter(&params))
+                            .into_iter()
+                            .map(|record| protocol::history_entry(record, params.include_output))
+                            .collect();
+                        Ok(crux_protocol::PaneHistoryResult { pane_id, commands })
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::DumpGrid { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((pane_id, pane)) => {
+                        let dump = pane.terminal.dump_grid(
+                            params.start_line..params.end_line,
+                            params.start_col.map(|c| c as usize)
+                                ..params.end_col.map(|c| c as usize),
+                        );
+                        protocol::grid_result(pane_id, dump)
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::GetModes { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((pane_id, pane)) => {
+                        Ok(protocol::modes_result(pane_id, pane.terminal.modes()))
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::ListImages { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((pane_id, pane)) => {
+                        Ok(protocol::images_result(pane_id, pane.terminal.graphics()))
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::GetImage { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((_id, pane)) => protocol::image_result(pane.terminal.graphics(), &params),
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::RenderImage {
+                params,
+                file,
+                reply,
+            } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((pane_id, pane)) => pane
+                        .terminal
+                        .show_image(file, params.cols, params.rows)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|image_id| {
+                            protocol::render_image_result(
+                                pane_id,
+                                pane.terminal.graphics(),
+                                image_id,
+                            )
+                        }),
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::GetSelection { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((_id, pane)) => {
+                        let text = pane.terminal.selection_to_string();
+                        Ok(crux_protocol::GetSelectionResult {
+                            has_selection: text.is_some(),
+                            text,
+                        })
+                    }
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::GetSnapshot { params, reply } => {
+                let result = match self.resolve_pane(params.pane_id) {
+                    Some((_id, pane)) => Ok(protocol::snapshot_result(
+                        &pane.terminal.content(),
+                        pane.title.clone(),
+                        pane.terminal.cwd().map(|s| s.to_string()),
+                    )),
+                    None => Err(pane_not_found(params.pane_id)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::ListPanes { reply } => {
+                let active = self.active_pane_id();
+                let panes = self
+                    .layout
+                    .panes()
+                    .into_iter()
+                    .filter_map(|id| self.panes.get(&id).map(|pane| (id, pane)))
+                    .map(|(id, pane)| {
+                        let size = pane.terminal.size();
+                        let content = pane.terminal.content();
+                        crux_protocol::PaneInfo {
+                            pane_id: id,
+                            window_id: HEADLESS_WINDOW,
+                            tab_id: HEADLESS_TAB,
+                            size: crux_protocol::PaneSize {
+                                rows: size.rows as u32,
+                                cols: size.cols as u32,
+                            },
+                            title: pane.title.clone().unwrap_or_default(),
+                            cwd: pane.terminal.cwd().map(|s| s.to_string()),
+                            is_active: active == Some(id),
+                            is_zoomed: false,
+                            cursor_x: content.cursor.point.column.0 as u32,
+                            cursor_y: content.cursor.point.line.0.max(0) as u32,
+                            tty: None,
+                            pid: pane.terminal.child_pid(),
+                        }
+                    })
+                    .collect();
+                let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes }));
+            }
+
+            IpcCommand::ResizePane { params, reply } => {
+                let result = self.handle_resize_pane(params.pane_id, params.width, params.height);
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::ActivatePane { params, reply } => {
+                if self.panes.contains_key(&params.pane_id) {
+                    self.active_pane = Some(params.pane_id);
+                    self.emit_pane_event(crux_protocol::PaneEvent::Focused {
+                        pane_id: params.pane_id,
+                    });
+                    let _ = reply.send(Ok(()));
+                } else {
+                    let _ = reply.send(Err(anyhow::anyhow!("pane {} not found", params.pane_id)));
+                }
+            }
+
+            IpcCommand::ClosePane { params, reply } => {
+                let _ = reply.send(self.handle_close_pane(params.pane_id, params.force));
+            }
+
+            IpcCommand::WindowCreate { params: _, reply } => {
+                // The headless server has exactly one virtual window.
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "window create not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::WindowList { reply } => {
+                let window_info = crux_protocol::WindowInfo {
+                    window_id: HEADLESS_WINDOW,
+                    title: "Crux (headless)".to_string(),
+                    pane_count: self.panes.len() as u32,
+                    tab_count: 1,
+                    is_focused: true,
+                };
+                let result = crux_protocol::WindowListResult {
+                    windows: vec![window_info],
+                };
+                let _ = reply.send(Ok(result));
+            }
+
+            IpcCommand::WindowFocus { params, reply } => {
+                let result = if params.window_id == HEADLESS_WINDOW {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("window {} not found", params.window_id))
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::WindowClose { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "window close not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::TabCreate { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "tab create not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::TabList { params, reply } => {
+                let result = match params.window_id {
+                    Some(id) if id != HEADLESS_WINDOW => {
+                        Err(anyhow::anyhow!("window {} not found", id))
+                    }
+                    _ => Ok(crux_protocol::TabListResult {
+                        tabs: vec![crux_protocol::TabInfo {
+                            tab_id: HEADLESS_TAB,
+                            window_id: HEADLESS_WINDOW,
+                            pane_ids: self.layout.panes(),
+                            active_pane_id: self.active_pane_id(),
+                            is_active: true,
+                        }],
+                    }),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::TabMovePane { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "tab move-pane not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::LayoutApply { params, reply } => {
+                let _ = reply.send(self.handle_layout_apply(params));
+            }
+
+            IpcCommand::LayoutGet { params, reply } => {
+                let result = match params.window_id {
+                    Some(id) if id != HEADLESS_WINDOW => {
+                        Err(anyhow::anyhow!("window {} not found", id))
+                    }
+                    _ => self
+                        .layout_tree()
+                        .map(|layout| crux_protocol::LayoutGetResult {
+                            window_id: HEADLESS_WINDOW,
+                            layout,
+                        }),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::SessionSave { params, reply } => {
+                let _ = reply.send(self.handle_session_save(params));
+            }
+
+            IpcCommand::SessionLoad { params, reply } => {
+                let _ = reply.send(self.handle_session_load(params));
+            }
+
+            cmd @ (IpcCommand::MuxList { .. }
+            | IpcCommand::MuxAttach { .. }
+            | IpcCommand::MuxResize { .. }
+            | IpcCommand::MuxKill { .. }) => {
+                cmd.reject(anyhow::anyhow!(
+                    "mux commands are served on the mux daemon's control socket"
+                ));
+            }
+
+            IpcCommand::ClipboardRead { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "clipboard not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::ClipboardWrite { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "clipboard not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::ImeGetState { reply } => {
+                let state = crux_protocol::ImeStateResult {
+                    composing: false,
+                    preedit_text: None,
+                    input_source: None,
+                };
+                let _ = reply.send(Ok(state));
+            }
+
+            IpcCommand::ImeSetInputSource { params: _, reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "IME switching not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::ConfigGetStatus { reply } => {
+                let _ = reply.send(Err(anyhow::anyhow!(
+                    "config reloading not supported in headless mode"
+                )));
+            }
+
+            IpcCommand::EventsPoll { reply } => {
+                let events = self.drain_pane_events();
+                let _ = reply.send(Ok(crux_protocol::EventsPollResult { events }));
+            }
+
+            IpcCommand::EventsSubscribe {
+                subscription,
+                reply,
+            } => {
+                let events = subscription.events().to_vec();
+                self.event_subscribers.push(subscription);
+                let _ = reply.send(Ok(crux_protocol::EventsSubscribeResult { events }));
+            }
+
+            IpcCommand::RunCommand { params, reply } => {
+                let Some(pane_id) = params.pane_id.or_else(|| self.active_pane_id()) else {
+                    let _ = reply.send(Err(pane_not_found(None)));
+                    return;
+                };
+                let in_flight = self
+                    .running_commands
+                    .get(&pane_id)
+                    .is_some_and(|token| token.strong_count() > 0);
+                if in_flight {
+                    let _ = reply.send(Err(anyhow::anyhow!(
+                        "pane {} already has a command in progress",
+                        pane_id
+                    )));
+                    return;
+                }
+                let Some(pane) = self.panes.get_mut(&pane_id) else {
+                    let _ = reply.send(Err(pane_not_found(Some(pane_id))));
+                    return;
+                };
+                if !pane.terminal.has_shell_integration() {
+                    let _ = reply.send(Err(anyhow::anyhow!(
+                        "pane {} has no OSC 133 shell integration; command boundaries cannot be detected",
+                        pane_id
+                    )));
+                    return;
+                }
+                if pane.terminal.is_command_running() {
+                    let _ = reply.send(Err(anyhow::anyhow!(
+                        "pane {} is busy running a command",
+                        pane_id
+                    )));
+                    return;
+                }
+
+                let token = Arc::new(());
+                let (done_tx, done) = tokio::sync::oneshot::channel();
+                let cwd = pane.terminal.cwd().map(|s| s.to_string());
+                // Register the capture before typing so no output is missed.
+                pane.terminal.output_tap().add(run_command_sink(
+                    pane_id,
+                    params.format,
+                    cwd,
+                    done_tx,
+                    token.clone(),
+                ));
+                pane.terminal
+                    .write_to_pty(format!("{}\r", params.command).as_bytes());
+                self.running_commands
+                    .insert(pane_id, Arc::downgrade(&token));
+                let _ = reply.send(Ok(CommandStarted { pane_id, done }));
+            }
+
+            IpcCommand::SubscribeOutput {
+                subscription,
+                reply,
+            } => {
+                let requested = subscription.pane_id();
+                let result = match self.resolve_pane(requested) {
+                    Some((pane_id, pane)) => {
+                        let subscription_id = subscription.id();
+                        pane.terminal
+                            .output_tap()
+                            .add(Box::new(move |bytes| subscription.offer(bytes)));
+                        Ok(crux_protocol::SubscribeOutputResult {
+                            subscription_id,
+                            pane_id,
+                        })
+                    }
+                    None => Err(pane_not_found(requested)),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    fn handle_split_pane(
+        &mut self,
+        params: crux_protocol::SplitPaneParams,
+    ) -> anyhow::Result<crux_protocol::SplitPaneResult> {
+        let target = match params.target_pane_id {
+            Some(id) if !self.panes.contains_key(&id) => {
+                anyhow::bail!("pane {} not found", id);
+            }
+            Some(id) => Some(id),
+            None => self.active_pane_id(),
+        };
+
+        let pane_id = self.allocate_pane_id();
+        let ratio = target
+            .map(|t| self.split_ratio(t, params.direction, params.size))
+            .unwrap_or(0.5);
+        let mut layout = self.layout.clone();
+        layout.split(target.unwrap_or(pane_id), pane_id, params.direction, ratio);
+        let rect = layout
+            .geometry(self.bounds())
+            .into_iter()
+            .find(|(id, _)| *id == pane_id)
+            .map(|(_, rect)| rect)
+            .unwrap_or_else(|| self.bounds());
+
+        let terminal = self.spawn_terminal(
+            pane_id,
+            rect,
+            params.cwd.as_deref(),
+            params.command.as_deref(),
+            params.env.as_ref(),
+        )?;
+        let size = terminal.size();
+        self.panes.insert(
+            pane_id,
+            HeadlessPane {
+                terminal,
+                title: None,
+                launch: LayoutPane {
+                    cwd: params.cwd.clone(),
+                    command: params.command.clone(),
+                    env: params.env.clone(),
+                    ..LayoutPane::default()
+                },
+            },
+        );
+        self.layout = layout;
+        self.active_pane = Some(pane_id);
+        self.emit_pane_event(crux_protocol::PaneEvent::Created { pane_id });
+        self.apply_layout();
+
+        Ok(crux_protocol::SplitPaneResult {
+            pane_id,
+            window_id: HEADLESS_WINDOW,
+            tab_id: HEADLESS_TAB,
+            size: crux_protocol::PaneSize {
+                rows: size.rows as u32,
+                cols: size.cols as u32,
+            },
+            tty: None,
+        })
+    }
+
+    fn handle_layout_apply(
+        &mut self,
+        params: crux_protocol::LayoutApplyParams,
+    ) -> anyhow::Result<crux_protocol::LayoutApplyResult> {
+        if params.new_window {
+            anyhow::bail!("new windows not supported in headless mode");
+        }
+        if let Some(id) = params.window_id.filter(|id| *id != HEADLESS_WINDOW) {
+            anyhow::bail!("window {} not found", id);
+        }
+        let tree = crux_config::layouts::resolve(params.layout, params.template.as_deref())?;
+        let layout = self.replace_layout(tree, params.force)?;
+        Ok(crux_protocol::LayoutApplyResult {
+            window_id: HEADLESS_WINDOW,
+            layout,
+        })
+    }
+
+    /// Replace every pane with a validated layout tree and return the tree
+    /// with the new pane IDs filled in. All terminals are spawned before
+    /// anything is torn down, so a failure leaves the current panes in place.
+    fn replace_layout(&mut self, mut tree: LayoutNode, force: bool) -> anyhow::Result<LayoutNode> {
+        if !force {
+            for (pane_id, pane) in &mut self.panes {
+                if pane.terminal.is_process_running() {
+                    anyhow::bail!(
+                        "pane {} has a running process, use force: true to replace it",
+                        pane_id
+                    );
+                }
+            }
+        }
+
+        let ids: Vec<PaneId> = tree
+            .panes()
+            .iter()
+            .map(|_| self.allocate_pane_id())
+            .collect();
+        let layout = Layout::from_tree(&tree, &ids).map_err(anyhow::Error::msg)?;
+        let geometry = layout.geometry(self.bounds());
+        let mut spawned = Vec::with_capacity(ids.len());
+        for (spec, (pane_id, rect)) in tree.panes().into_iter().zip(geometry) {
+            let terminal = self.spawn_terminal(
+                pane_id,
+                rect,
+                spec.cwd.as_deref(),
+                spec.command.as_deref(),
+                spec.env.as_ref(),
+            )?;
+            spawned.push((
+                pane_id,
+                HeadlessPane {
+                    terminal,
+                    title: spec.title.clone(),
+                    launch: LayoutPane {
+                        pane_id: None,
+                        ..spec.clone()
+                    },
+                },
+            ));
+        }
+
+        for pane_id in self.layout.panes() {
+            self.panes.remove(&pane_id);
+            self.running_commands.remove(&pane_id);
+            self.emit_pane_event(crux_protocol::PaneEvent::Closed { pane_id });
+        }
+        self.panes.extend(spawned);
+        self.layout = layout;
+        self.active_pane = ids.first().copied();
+        for &pane_id in &ids {
+            self.emit_pane_event(crux_protocol::PaneEvent::Created { pane_id });
+        }
+        self.apply_layout();
+
+        for (pane, id) in tree.panes_mut().into_iter().zip(&ids) {
+            pane.pane_id = Some(*id);
+        }
+        Ok(tree)
+    }
+
+    /// Write every pane's launch settings, size and scrollback to a session
+    /// file. There is no default path: a headless server has no config dir
+    /// of its own to claim.
+    fn handle_session_save(
+        &mut self,
+        params: crux_protocol::SessionSaveParams,
+    ) -> anyhow::Result<crux_protocol::SessionSaveResult> {
+        let path = params
+            .path
+            .ok_or_else(|| anyhow::anyhow!("path is required in headless mode"))?;
+        let history = params
+            .scrollback_lines
+            .unwrap_or(crux_protocol::session::DEFAULT_SCROLLBACK_LINES)
+            as usize;
+        let compress = params.compress.unwrap_or(true);
+
+        let mut layout = self.layout_tree()?;
+        let mut panes = Vec::new();
+        for spec in layout.panes_mut() {
+            let Some((pane_id, pane)) = spec
+                .pane_id
+                .and_then(|id| self.panes.get(&id).map(|pane| (id, pane)))
+            else {
+                continue;
+            };
+            let size = pane.terminal.size();
+            panes.push(SessionPane {
+                pane_id,
+                size: Some(crux_protocol::PaneSize {
+                    rows: size.rows as u32,
+                    cols: size.cols as u32,
+                }),
+                scrollback: (history > 0)
+                    .then(|| pane.terminal.scrollback_snapshot(history, compress)),
+            });
+        }
+
+        let json = serde_json::to_string_pretty(&Session::new(layout, panes))?;
+        let path = std::path::PathBuf::from(path);
+        crux_config::session::write(&path, &json, 0)?;
+        Ok(crux_protocol::SessionSaveResult {
+            path: path.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// Replace every pane with those of a session file, replaying their
+    /// saved scrollback.
+    fn handle_session_load(
+        &mut self,
+        params: crux_protocol::SessionLoadParams,
+    ) -> anyhow::Result<crux_protocol::SessionLoadResult> {
+        let path = params
+            .path
+            .ok_or_else(|| anyhow::anyhow!("path is required in headless mode"))?;
+        let session = Session::from_json(&std::fs::read_to_string(&path)?)?;
+        let saved: Vec<Option<PaneId>> = session.layout.panes().iter().map(|p| p.pane_id).collect();
+        let layout = self.replace_layout(session.layout.clone(), true)?;
+
+        for (old, new) in saved.into_iter().zip(layout.panes()) {
+            let scrollback = old
+                .and_then(|id| session.pane(id))
+                .and_then(|p| p.scrollback.as_ref());
+            let pane = new.pane_id.and_then(|id| self.panes.get(&id));
+            if let (Some(scrollback), Some(pane)) = (scrollback, pane) {
+                if let Err(e) = pane.terminal.restore_scrollback(scrollback) {
+                    log::warn!("skipping saved scrollback of pane {:?}: {}", old, e);
+                }
+            }
+        }
+        Ok(crux_protocol::SessionLoadResult {
+            pane_count: layout.panes().len() as u32,
+        })
+    }
+
+    /// The current layout with each pane's cwd, title, command and env
+    /// filled in, so that it can be passed back to `crux:layout/apply`.
+    fn layout_tree(&self) -> anyhow::Result<LayoutNode> {
+        let mut tree = self
+            .layout
+            .to_tree()
+            .ok_or_else(|| anyhow::anyhow!("no panes"))?;
+        for spec in tree.panes_mut() {
+            if let Some(pane) = spec.pane_id.and_then(|id| self.panes.get(&id)) {
+                spec.cwd = pane
+                    .terminal
+                    .cwd()
+                    .map(|s| s.to_string())
+                    .or_else(|| pane.launch.cwd.clone());
+                spec.title = pane.title.clone();
+                spec.command = pane.launch.command.clone();
+                spec.env = pane.launch.env.clone();
+            }
+        }
+        Ok(tree)
+    }
+
+    fn handle_close_pane(&mut self, pane_id: PaneId, force: bool) -> anyhow::Result<()> {
+        let pane = self
+            .panes
+            .get_mut(&pane_id)
+            .ok_or_else(|| anyhow::anyhow!("pane {} not found", pane_id))?;
+        // When force is false, check if the process is still running.
+        if !force && pane.terminal.is_process_running() {
+            anyhow::bail!(
+                "pane {} has a running process, use force: true to close",
+                pane_id
+            );
+        }
+
+        self.panes.remove(&pane_id);
+        self.running_commands.remove(&pane_id);
+        self.layout.remove(pane_id);
+        if self.active_pane == Some(pane_id) {
+            self.active_pane = None;
+        }
+        self.emit_pane_event(crux_protocol::PaneEvent::Closed { pane_id });
+        self.apply_layout();
+        Ok(())
+    }
+
+    /// Resize a pane within its enclosing split. Pixel sizes are converted
+    /// to cells with a nominal 8x16 px cell.
+    fn handle_resize_pane(
+        &mut self,
+        pane_id: PaneId,
+        width: Option<f32>,
+        height: Option<f32>,
+    ) -> anyhow::Result<()> {
+        if width.is_none() && height.is_none() {
+            return Err(anyhow::anyhow!(
+                "at least one of width or height must be specified"
+            ));
+        }
+        if !self.panes.contains_key(&pane_id) {
+            return Err(anyhow::anyhow!("pane {} not found", pane_id));
+        }
+
+        let bounds = self.bounds();
+        let to_cells = |px: f32, cell: f32| (px / cell).round().max(1.0) as usize;
+        if let Some(w) = width {
+            self.layout.resize(
+                bounds,
+                pane_id,
+                Axis::Horizontal,
+                to_cells(w, CELL_WIDTH_PX),
+            );
+        }
+        if let Some(h) = height {
+            self.layout
+                .resize(bounds, pane_id, Axis::Vertical, to_cells(h, CELL_HEIGHT_PX));
+        }
+        self.apply_layout();
+        Ok(())
+    }
+}
+
+/// Build the PTY output sink that captures one command's OSC 133 C→D output
+/// and delivers the result. Runs on the PTY reader thread.
+fn run_command_sink(
+    pane_id: PaneId,
+    format: OutputFormat,
+    cwd: Option<String>,
+    done: tokio::sync::oneshot::Sender<RunCommandResult>,
+    token: Arc<()>,
+) -> OutputSink {
+    let mut capture = CommandCapture::new(RUN_COMMAND_MAX_OUTPUT);
+    let mut done = Some(done);
+    Box::new(move |bytes| {
+        let _in_flight = &token;
+        // The IPC side gave up (timeout or disconnect); stop capturing.
+        if done.as_ref().is_none_or(|tx| tx.is_closed()) {
+            return false;
+        }
+        let Some(captured) = capture.feed(bytes) else {
+            return true;
+        };
+        if let Some(tx) = done.take() {
+            let _ = tx.send(protocol::run_command_result(
+                pane_id,
+                format,
+                cwd.clone(),
+                captured,
+                crux_ipc::output::decode_output,
+            ));
+        }
+        false
+    })
+}
@@ -0,0 +1,507 @@
+//! Virtual split layout standing in for GPUI's `DockArea`.
+//!
+//! Panes are leaves of a tree of weighted splits. The tree never renders;
+//! it only answers "how many cells does each pane get" so terminals can be
+//! resized the same way they would be on screen.
+
+use crux_protocol::layout::normalize_ratios;
+use crux_protocol::{LayoutAxis, LayoutNode, LayoutPane, PaneId, SplitDirection};
+
+/// Direction children of a split are laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    /// Children side by side, left to right.
+    Horizontal,
+    /// Children stacked, top to bottom.
+    Vertical,
+}
+
+impl Axis {
+    fn of(direction: SplitDirection) -> Self {
+        match direction {
+            SplitDirection::Left | SplitDirection::Right => Axis::Horizontal,
+            SplitDirection::Top | SplitDirection::Bottom => Axis::Vertical,
+        }
+    }
+}
+
+/// A pane's cell rectangle within the virtual window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub col: usize,
+    pub row: usize,
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl Rect {
+    fn extent(&self, axis: Axis) -> usize {
+        match axis {
+            Axis::Horizontal => self.cols,
+            Axis::Vertical => self.rows,
+        }
+    }
+
+    /// Cut this rect into consecutive pieces along `axis`, proportional to
+    /// `weights`. Boundaries are rounded so the pieces always tile exactly.
+    fn divide(&self, axis: Axis, weights: &[f32]) -> Vec<Rect> {
+        let total: f32 = weights.iter().sum();
+        let extent = self.extent(axis);
+        let mut acc = 0.0;
+        let mut start = 0;
+        weights
+            .iter()
+            .map(|w| {
+                acc += w;
+                let end = if total > 0.0 {
+                    ((acc / total) * extent as f32).round() as usize
+                } else {
+                    extent
+                }
+                .min(extent);
+                let len = end.saturating_sub(start);
+                let rect = match axis {
+                    Axis::Horizontal => Rect {
+                        col: self.col + start,
+                        cols: len,
+                        ..*self
+                    },
+                    Axis::Vertical => Rect {
+                        row: self.row + start,
+                        rows: len,
+                        ..*self
+                    },
+                };
+                start = start.max(end);
+                rect
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Pane(PaneId),
+    Split {
+        axis: Axis,
+        /// `(weight, child)` pairs; weights are relative to their sum.
+        children: Vec<(f32, Node)>,
+    },
+}
+
+impl Node {
+    /// Build the node for `tree`, taking pane IDs from `ids` in layout order.
+    fn from_tree(
+        tree: &LayoutNode,
+        ids: &mut impl Iterator<Item = PaneId>,
+    ) -> Result<Self, String> {
+        match tree {
+            LayoutNode::Pane(_) => ids.next().map(Node::Pane).ok_or("too few pane IDs".into()),
+            // There is no tab bar to switch with; one tab is just a pane.
+            LayoutNode::Tabs { panes, .. } if panes.len() == 1 => {
+                ids.next().map(Node::Pane).ok_or("too few pane IDs".into())
+            }
+            LayoutNode::Tabs { .. } => Err("tabs are not supported in headless mode".into()),
+            LayoutNode::Split {
+                axis,
+                ratios,
+                children,
+            } => {
+                let weights = normalize_ratios(ratios.as_deref(), children.len());
+                let mut built = weights
+                    .into_iter()
+                    .zip(children)
+                    .map(|(w, child)| Ok((w, Node::from_tree(child, ids)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                if built.len() == 1 {
+                    return Ok(built.pop().expect("split has one child").1);
+                }
+                let axis = match axis {
+                    LayoutAxis::Horizontal => Axis::Horizontal,
+                    LayoutAxis::Vertical => Axis::Vertical,
+                };
+                Ok(Node::Split {
+                    axis,
+                    children: built,
+                })
+            }
+        }
+    }
+
+    fn to_tree(&self) -> LayoutNode {
+        match self {
+            Node::Pane(id) => LayoutNode::Pane(LayoutPane {
+                pane_id: Some(*id),
+                ..LayoutPane::default()
+            }),
+            Node::Split { axis, children } => {
+                let weights: Vec<f32> = children.iter().map(|(w, _)| *w).collect();
+                LayoutNode::Split {
+                    axis: match axis {
+                        Axis::Horizontal => LayoutAxis::Horizontal,
+                        Axis::Vertical => LayoutAxis::Vertical,
+                    },
+                    ratios: Some(normalize_ratios(Some(&weights), weights.len())),
+                    children: children.iter().map(|(_, c)| c.to_tree()).collect(),
+                }
+            }
+        }
+    }
+
+    fn contains(&self, pane: PaneId) -> bool {
+        match self {
+            Node::Pane(id) => *id == pane,
+            Node::Split { children, .. } => children.iter().any(|(_, c)| c.contains(pane)),
+        }
+    }
+
+    fn collect_panes(&self, out: &mut Vec<PaneId>) {
+        match self {
+            Node::Pane(id) => out.push(*id),
+            Node::Split { children, .. } => {
+                for (_, child) in children {
+                    child.collect_panes(out);
+                }
+            }
+        }
+    }
+
+    fn collect_geometry(&self, rect: Rect, out: &mut Vec<(PaneId, Rect)>) {
+        match self {
+            Node::Pane(id) => out.push((*id, rect)),
+            Node::Split { axis, children } => {
+                let weights: Vec<f32> = children.iter().map(|(w, _)| *w).collect();
+                for ((_, child), piece) in children.iter().zip(rect.divide(*axis, &weights)) {
+                    child.collect_geometry(piece, out);
+                }
+            }
+        }
+    }
+
+    fn split(
+        &mut self,
+        target: PaneId,
+        new: PaneId,
+        direction: SplitDirection,
+        ratio: f32,
+    ) -> bool {
+        let axis = Axis::of(direction);
+        let before = matches!(direction, SplitDirection::Left | SplitDirection::Top);
+        match self {
+            Node::Pane(id) if *id == target => {
+                let old = (1.0 - ratio, Node::Pane(target));
+                let added = (ratio, Node::Pane(new));
+                let children = if before {
+                    vec![added, old]
+                } else {
+                    vec![old, added]
+                };
+                *self = Node::Split { axis, children };
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split {
+                axis: split_axis,
+                children,
+            } => {
+                // A same-axis split of a direct child joins this split
+                // instead of nesting a new one.
+                if *split_axis == axis {
+                    if let Some(ix) = children
+                        .iter()
+                        .position(|(_, c)| matches!(c, Node::Pane(id) if *id == target))
+                    {
+                        let weight = children[ix].0;
+                        children[ix].0 = weight * (1.0 - ratio);
+                        let at = if before { ix } else { ix + 1 };
+                        children.insert(at, (weight * ratio, Node::Pane(new)));
+                        return true;
+                    }
+                }
+                children
+                    .iter_mut()
+                    .any(|(_, c)| c.split(target, new, direction, ratio))
+            }
+        }
+    }
+
+    /// Remove a leaf below this node, collapsing a split left with one child.
+    fn remove(&mut self, pane: PaneId) -> bool {
+        let Node::Split { children, .. } = self else {
+            return false;
+        };
+        let removed = match children
+            .iter()
+            .position(|(_, c)| matches!(c, Node::Pane(id) if *id == pane))
+        {
+            Some(ix) => {
+                children.remove(ix);
+                true
+            }
+            None => children.iter_mut().any(|(_, c)| c.remove(pane)),
+        };
+        if children.len() == 1 {
+            let (_, only) = children.pop().expect("split has one child");
+            *self = only;
+        }
+        removed
+    }
+
+    fn resize(&mut self, rect: Rect, pane: PaneId, axis: Axis, cells: usize) -> bool {
+        let Node::Split {
+            axis: split_axis,
+            children,
+        } = self
+        else {
+            return false;
+        };
+        let weights: Vec<f32> = children.iter().map(|(w, _)| *w).collect();
+        let pieces = rect.divide(*split_axis, &weights);
+        let Some(ix) = children.iter().position(|(_, c)| c.contains(pane)) else {
+            return false;
+        };
+        // The innermost split along `axis` decides the pane's size.
+        if children[ix].1.resize(pieces[ix], pane, axis, cells) {
+            return true;
+        }
+        if *split_axis != axis || children.len() < 2 {
+            return false;
+        }
+
+        let extent = rect.extent(axis);
+        let others = children.len() - 1;
+        let cells = cells.clamp(1, extent.saturating_sub(others).max(1)) as f32;
+        let rest = extent as f32 - cells;
+        let others_total: f32 = pieces
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != ix)
+            .map(|(_, p)| p.extent(axis) as f32)
+            .sum();
+        for (i, (weight, _)) in children.iter_mut().enumerate() {
+            *weight = if i == ix {
+                cells
+            } else if others_total > 0.0 {
+                rest * pieces[i].extent(axis) as f32 / others_total
+            } else {
+                rest / others as f32
+            };
+        }
+        true
+    }
+}
+
+/// Split tree of the panes in the headless window.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    root: Option<Node>,
+}
+
+impl Layout {
+    /// Layout holding a single pane.
+    pub fn new(pane: PaneId) -> Self {
+        Self {
+            root: Some(Node::Pane(pane)),
+        }
+    }
+
+    /// Layout for a declarative tree, giving its panes `ids` in the order of
+    /// [`LayoutNode::panes`]. Tab groups of more than one pane are rejected.
+    pub fn from_tree(tree: &LayoutNode, ids: &[PaneId]) -> Result<Self, String> {
+        let mut ids = ids.iter().copied();
+        let root = Node::from_tree(tree, &mut ids)?;
+        if ids.next().is_some() {
+            return Err("too many pane IDs".into());
+        }
+        Ok(Self { root: Some(root) })
+    }
+
+    /// The layout in the `crux:layout/get` schema, with only pane IDs set.
+    pub fn to_tree(&self) -> Option<LayoutNode> {
+        self.root.as_ref().map(Node::to_tree)
+    }
+
+    /// Pane IDs in layout order (left to right, top to bottom).
+    pub fn panes(&self) -> Vec<PaneId> {
+        let mut panes = Vec::new();
+        if let Some(root) = &self.root {
+            root.collect_panes(&mut panes);
+        }
+        panes
+    }
+
+    pub fn contains(&self, pane: PaneId) -> bool {
+        self.root.as_ref().is_some_and(|r| r.contains(pane))
+    }
+
+    /// Place `new` next to `target`, taking `ratio` (0..1) of its space.
+    ///
+    /// An empty layout simply becomes `new`. Returns `false` if `target`
+    /// is not in the layout.
+    pub fn split(
+        &mut self,
+        target: PaneId,
+        new: PaneId,
+        direction: SplitDirection,
+        ratio: f32,
+    ) -> bool {
+        let ratio = ratio.clamp(0.05, 0.95);
+        match &mut self.root {
+            None => {
+                self.root = Some(Node::Pane(new));
+                true
+            }
+            Some(root) => root.split(target, new, direction, ratio),
+        }
+    }
+
+    /// Remove a pane; splits left with a single child collapse into it.
+    pub fn remove(&mut self, pane: PaneId) -> bool {
+        match &mut self.root {
+            Some(Node::Pane(id)) if *id == pane => {
+                self.root = None;
+                true
+            }
+            Some(root) => root.remove(pane),
+            None => false,
+        }
+    }
+
+    /// Set `pane`'s extent along `axis` to `cells`, within a window of
+    /// `bounds`, by reweighting the closest enclosing split on that axis.
+    /// Returns `false` if no such split exists.
+    pub fn resize(&mut self, bounds: Rect, pane: PaneId, axis: Axis, cells: usize) -> bool {
+        self.root
+            .as_mut()
+            .is_some_and(|root| root.resize(bounds, pane, axis, cells))
+    }
+
+    /// Cell rectangle of every pane within `bounds`.
+    pub fn geometry(&self, bounds: Rect) -> Vec<(PaneId, Rect)> {
+        let mut out = Vec::new();
+        if let Some(root) = &self.root {
+            root.collect_geometry(bounds, &mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Rect = Rect {
+        col: 0,
+        row: 0,
+        cols: 80,
+        rows: 24,
+    };
+
+    fn rect(col: usize, row: usize, cols: usize, rows: usize) -> Rect {
+        Rect {
+            col,
+            row,
+            cols,
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_split_right_and_bottom() {
+        let mut layout = Layout::new(PaneId(0));
+        assert!(layout.split(PaneId(0), PaneId(1), SplitDirection::Right, 0.5));
+        assert!(layout.split(PaneId(1), PaneId(2), SplitDirection::Bottom, 0.25));
+        assert_eq!(
+            layout.geometry(WINDOW),
+            vec![
+                (PaneId(0), rect(0, 0, 40, 24)),
+                (PaneId(1), rect(40, 0, 40, 18)),
+                (PaneId(2), rect(40, 18, 40, 6)),
+            ]
+        );
+        assert!(!layout.split(PaneId(9), PaneId(3), SplitDirection::Left, 0.5));
+    }
+
+    #[test]
+    fn test_same_axis_split_joins_parent() {
+        let mut layout = Layout::new(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Right, 0.5);
+        layout.split(PaneId(0), PaneId(2), SplitDirection::Left, 0.5);
+        assert_eq!(layout.panes(), vec![PaneId(2), PaneId(0), PaneId(1)]);
+        let widths: Vec<usize> = layout
+            .geometry(WINDOW)
+            .iter()
+            .map(|(_, r)| r.cols)
+            .collect();
+        assert_eq!(widths, vec![20, 20, 40]);
+    }
+
+    #[test]
+    fn test_remove_collapses_splits() {
+        let mut layout = Layout::new(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Right, 0.5);
+        layout.split(PaneId(1), PaneId(2), SplitDirection::Bottom, 0.5);
+        assert!(layout.remove(PaneId(1)));
+        assert_eq!(
+            layout.geometry(WINDOW),
+            vec![
+                (PaneId(0), rect(0, 0, 40, 24)),
+                (PaneId(2), rect(40, 0, 40, 24)),
+            ]
+        );
+        assert!(layout.remove(PaneId(0)));
+        assert_eq!(layout.geometry(WINDOW), vec![(PaneId(2), WINDOW)]);
+        assert!(layout.remove(PaneId(2)));
+        assert!(layout.panes().is_empty());
+        assert!(!layout.remove(PaneId(2)));
+    }
+
+    #[test]
+    fn test_resize_uses_matching_axis() {
+        let mut layout = Layout::new(PaneId(0));
+        layout.split(PaneId(0), PaneId(1), SplitDirection::Right, 0.5);
+        layout.split(PaneId(1), PaneId(2), SplitDirection::Bottom, 0.5);
+
+        assert!(layout.resize(WINDOW, PaneId(2), Axis::Horizontal, 60));
+        assert!(layout.resize(WINDOW, PaneId(2), Axis::Vertical, 20));
+        assert_eq!(
+            layout.geometry(WINDOW),
+            vec![
+                (PaneId(0), rect(0, 0, 20, 24)),
+                (PaneId(1), rect(20, 0, 60, 4)),
+                (PaneId(2), rect(20, 4, 60, 20)),
+            ]
+        );
+
+        // A lone pane has no split to resize.
+        let mut single = Layout::new(PaneId(0));
+        assert!(!single.resize(WINDOW, PaneId(0), Axis::Horizontal, 10));
+    }
+
+    #[test]
+    fn test_from_tree_round_trip() {
+        let tree = LayoutNode::preset("main-left").unwrap();
+        let ids = [PaneId(4), PaneId(5), PaneId(6)];
+        let layout = Layout::from_tree(&tree, &ids).unwrap();
+        assert_eq!(
+            layout.geometry(WINDOW),
+            vec![
+                (PaneId(4), rect(0, 0, 48, 24)),
+                (PaneId(5), rect(48, 0, 32, 12)),
+                (PaneId(6), rect(48, 12, 32, 12)),
+            ]
+        );
+
+        let back = layout.to_tree().unwrap();
+        let pane_ids: Vec<_> = back.panes().iter().map(|p| p.pane_id).collect();
+        assert_eq!(pane_ids, ids.map(Some));
+        assert_eq!(Layout::from_tree(&back, &ids).unwrap().panes(), ids);
+
+        let tabs = LayoutNode::Tabs {
+            active: 0,
+            panes: vec![LayoutPane::default(), LayoutPane::default()],
+        };
+        assert!(Layout::from_tree(&tabs, &ids[..2]).is_err());
+        assert!(Layout::from_tree(&tree, &ids[..2]).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Headless Crux: terminal panes served over IPC without GPUI.
+//!
+//! [`HeadlessServer`] owns a registry of [`crux_terminal::CruxTerminal`]
+//! panes arranged in a virtual [`layout::Layout`] and answers the same
+//! `crux:*` JSON-RPC methods as the GUI on the same Unix socket. It runs
+//! anywhere a PTY is available (CI, containers, SSH sessions), so the full
+//! protocol can be exercised end to end without a window server or GPU.
+//!
+//! With `--mux` the binary instead runs a [`MuxDaemon`]: a long-lived host
+//! of named headless sessions that GUI windows and terminals attach to and
+//! detach from without killing the processes inside.
+
+mod dispatch;
+pub mod layout;
+pub mod mux;
+pub mod server;
+
+pub use mux::{MuxConfig, MuxDaemon};
+pub use server::{HeadlessConfig, HeadlessServer};
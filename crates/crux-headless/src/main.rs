@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crux_headless::{HeadlessConfig, HeadlessServer, MuxConfig, MuxDaemon};
+
+#[derive(Parser)]
+#[command(
+    name = "crux-headless",
+    about = "Run Crux terminal panes without a window, controlled over IPC"
+)]
+struct Args {
+    /// Run the mux daemon hosting detached sessions instead of a single
+    /// set of panes
+    #[arg(long)]
+    mux: bool,
+
+    /// Socket path to listen on (default: $CRUX_SOCKET or the per-process
+    /// path; with --mux, $CRUX_MUX_SOCKET or the per-user control socket)
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Width of the virtual window in cells
+    #[arg(long, default_value = "80")]
+    cols: usize,
+
+    /// Height of the virtual window in cells
+    #[arg(long, default_value = "24")]
+    rows: usize,
+
+    /// Shell to run in each pane (default: $SHELL)
+    #[arg(long)]
+    shell: Option<String>,
+
+    /// Arguments passed to the shell (default: -l)
+    #[arg(long, allow_hyphen_values = true, num_args = 1..)]
+    shell_args: Option<Vec<String>>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .target(env_logger::Target::Stderr)
+        .init();
+
+    let args = Args::parse();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    if args.mux {
+        let config = MuxConfig {
+            socket_path: args.socket.unwrap_or_else(crux_ipc::mux_socket_path),
+            cols: args.cols.max(1),
+            rows: args.rows.max(1),
+            shell: args.shell,
+            shell_args: args.shell_args,
+            ..MuxConfig::new()
+        };
+        return runtime.block_on(async move {
+            let cancel = cancel_on_signal();
+            log::info!(
+                "crux mux daemon serving on {}",
+                config.socket_path.display()
+            );
+            MuxDaemon::new(config).serve(cancel).await
+        });
+    }
+
+    let socket_path = args.socket.unwrap_or_else(crux_ipc::socket_path);
+    let config = HeadlessConfig {
+        cols: args.cols.max(1),
+        rows: args.rows.max(1),
+        shell: args.shell,
+        shell_args: args.shell_args,
+        ..HeadlessConfig::new(socket_path.clone())
+    };
+    runtime.block_on(async move {
+        let server = HeadlessServer::new(config)?;
+        let cancel = cancel_on_signal();
+        log::info!("crux-headless serving on {}", socket_path.display());
+        server.serve(cancel).await
+    })
+}
+
+/// A token cancelled on Ctrl-C or SIGTERM.
+fn cancel_on_signal() -> crux_ipc::CancellationToken {
+    let cancel = crux_ipc::CancellationToken::new();
+    let on_signal = cancel.clone();
+    tokio::spawn(async move {
+        let mut term =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+        let sigterm = async {
+            match term.as_mut() {
+                Some(term) => {
+                    term.recv().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm => {}
+        }
+        on_signal.cancel();
+    });
+    cancel
+}
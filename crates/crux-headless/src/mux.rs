@@ -0,0 +1,346 @@
+//! Mux daemon: detached sessions that outlive the window they were shown in.
+//!
+//! The daemon hosts any number of named sessions, each a [`HeadlessServer`]
+//! with its own socket in [`crux_ipc::mux_dir`]. Clients attach by calling
+//! `crux:mux/attach` on the daemon's control socket and then talk to the
+//! returned session socket with the regular `crux:*` methods (typically
+//! `crux:pane/subscribe-output` plus `crux:pane/send-text`). Closing or
+//! crashing a client leaves the session running; it ends when its last
+//! process exits or on `crux:mux/kill`.
+//!
+//! All sessions are driven from one event loop, so the daemon can inspect
+//! and resize them between requests without any locking.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+
+use crux_ipc::{CancellationToken, IpcCommand};
+use crux_protocol::{MuxAttachParams, MuxListResult, MuxSessionInfo};
+
+use crate::server::{HeadlessConfig, HeadlessServer};
+
+/// How often session terminals are drained while no request is pending.
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often sessions are checked for exited processes.
+const REAP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Longest accepted session name.
+const MAX_SESSION_NAME: usize = 64;
+
+/// Settings for a [`MuxDaemon`].
+#[derive(Debug, Clone)]
+pub struct MuxConfig {
+    /// Control socket serving `crux:mux/*`.
+    pub socket_path: PathBuf,
+    /// Directory the session sockets are created in.
+    pub session_dir: PathBuf,
+    /// Size of new sessions that are created without one.
+    pub cols: usize,
+    pub rows: usize,
+    /// Shell to spawn; `None` uses the user's default shell.
+    pub shell: Option<String>,
+    /// Arguments for `shell`; `None` starts a login shell.
+    pub shell_args: Option<Vec<String>>,
+}
+
+impl MuxConfig {
+    /// Defaults for the per-user daemon.
+    pub fn new() -> Self {
+        let size = crux_terminal::TerminalSize::default();
+        Self {
+            socket_path: crux_ipc::mux_socket_path(),
+            session_dir: crux_ipc::mux_dir(),
+            cols: size.cols,
+            rows: size.rows,
+            shell: None,
+            shell_args: None,
+        }
+    }
+}
+
+impl Default for MuxConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct MuxSession {
+    server: HeadlessServer,
+    socket_path: PathBuf,
+    /// Stops the session's IPC server.
+    cancel: CancellationToken,
+    created_at_ms: u64,
+}
+
+impl MuxSession {
+    fn info(&self, name: &str) -> MuxSessionInfo {
+        let (cols, rows) = self.server.window_size();
+        MuxSessionInfo {
+            name: name.to_string(),
+            socket_path: self.socket_path.to_string_lossy().into_owned(),
+            pane_count: self.server.pane_count() as u32,
+            created_at_ms: self.created_at_ms,
+            rows: rows as u32,
+            cols: cols as u32,
+        }
+    }
+}
+
+/// Long-running host of detached sessions.
+pub struct MuxDaemon {
+    config: MuxConfig,
+    sessions: BTreeMap<String, MuxSession>,
+}
+
+impl MuxDaemon {
+    pub fn new(config: MuxConfig) -> Self {
+        Self {
+            config,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    /// Serve the control socket and all sessions until `cancel` fires.
+    /// Every session is killed on the way out.
+    pub async fn serve(mut self, cancel: CancellationToken) -> anyhow::Result<()> {
+        let (control_tx, mut control_rx) = mpsc::channel(64);
+        let control = crux_ipc::server::start_server(
+            self.config.socket_path.clone(),
+            control_tx,
+            cancel.clone(),
+        )
+        .await?;
+        // Commands from every session socket, tagged with the session name.
+        let (session_tx, mut session_rx) = mpsc::channel::<(String, IpcCommand)>(64);
+
+        let mut ticker = tokio::time::interval(TICK_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut reaper = tokio::time::interval(REAP_INTERVAL);
+        reaper.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                Some(cmd) = control_rx.recv() => {
+                    self.handle_control(cmd, &session_tx, &cancel).await;
+                }
+                Some((name, cmd)) = session_rx.recv() => match self.sessions.get_mut(&name) {
+                    Some(session) => {
+                        // Catch up on terminal output first so replies see it.
+                        session.server.tick();
+                        session.server.handle_ipc_command(cmd);
+                    }
+                    None => cmd.reject(anyhow::anyhow!("session {:?} has ended", name)),
+                },
+                _ = ticker.tick() => {
+                    for session in self.sessions.values_mut() {
+                        session.server.tick();
+                    }
+                }
+                _ = reaper.tick() => self.reap(),
+            }
+        }
+
+        for name in self.sessions.keys().cloned().collect::<Vec<_>>() {
+            self.remove(&name);
+        }
+        let _ = control.await;
+        Ok(())
+    }
+
+    async fn handle_control(
+        &mut self,
+        cmd: IpcCommand,
+        session_tx: &mpsc::Sender<(String, IpcCommand)>,
+        cancel: &CancellationToken,
+    ) {
+        match cmd {
+            IpcCommand::Handshake { params: _, reply } => {
+                let result = crux_protocol::HandshakeResult {
+                    server_name: "crux-mux".into(),
+                    server_version: env!("CARGO_PKG_VERSION").into(),
+                    protocol_version: "1.0".into(),
+                    supported_capabilities: vec!["mux".into()],
+                };
+                let _ = reply.send(Ok(result));
+            }
+
+            IpcCommand::MuxList { reply } => {
+                let sessions = self
+                    .sessions
+                    .iter()
+                    .map(|(name, session)| session.info(name))
+                    .collect();
+                let _ = reply.send(Ok(MuxListResult { sessions }));
+            }
+
+            IpcCommand::MuxAttach { params, reply } => {
+                let _ = reply.send(self.attach(params, session_tx, cancel).await);
+            }
+
+            IpcCommand::MuxResize { params, reply } => {
+                let result = match self.sessions.get_mut(&params.name) {
+                    Some(session) => {
+                        session
+                            .server
+                            .resize(params.cols as usize, params.rows as usize);
+                        Ok(())
+                    }
+                    None => Err(session_not_found(&params.name)),
+                };
+                let _ = reply.send(result);
+            }
+
+            IpcCommand::MuxKill { params, reply } => {
+                let result = if self.remove(&params.name) {
+                    log::info!("killed session {:?}", params.name);
+                    Ok(())
+                } else {
+                    Err(session_not_found(&params.name))
+                };
+                let _ = reply.send(result);
+            }
+
+            cmd => cmd.reject(anyhow::anyhow!(
+                "this is the mux control socket; attach to a session and use its socket"
+            )),
+        }
+    }
+
+    /// Look up a session, creating it when asked to, and size it for the
+    /// attaching client.
+    async fn attach(
+        &mut self,
+        params: MuxAttachParams,
+        session_tx: &mpsc::Sender<(String, IpcCommand)>,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<MuxSessionInfo> {
+        let name = match params.name.clone() {
+            Some(name) => {
+                validate_session_name(&name)?;
+                name
+            }
+            None if params.create => self.free_name(),
+            None => anyhow::bail!("name is required unless create is set"),
+        };
+
+        if !self.sessions.contains_key(&name) {
+            if !params.create {
+                return Err(session_not_found(&name));
+            }
+            let session = self
+                .spawn_session(&name, &params, session_tx, cancel)
+                .await?;
+            log::info!("created session {:?}", name);
+            self.sessions.insert(name.clone(), session);
+        }
+
+        let session = self
+            .sessions
+            .get_mut(&name)
+            .ok_or_else(|| session_not_found(&name))?;
+        if let (Some(rows), Some(cols)) = (params.rows, params.cols) {
+            session.server.resize(cols as usize, rows as usize);
+        }
+        Ok(session.info(&name))
+    }
+
+    async fn spawn_session(
+        &self,
+        name: &str,
+        params: &MuxAttachParams,
+        session_tx: &mpsc::Sender<(String, IpcCommand)>,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<MuxSession> {
+        let socket_path = self.config.session_dir.join(format!("{name}.sock"));
+        let server = HeadlessServer::new(HeadlessConfig {
+            cols: params.cols.map_or(self.config.cols, |c| c as usize).max(1),
+            rows: params.rows.map_or(self.config.rows, |r| r as usize).max(1),
+            shell: self.config.shell.clone(),
+            shell_args: self.config.shell_args.clone(),
+            cwd: params.cwd.clone(),
+            command: params.command.clone(),
+            ..HeadlessConfig::new(socket_path.clone())
+        })?;
+
+        let session_cancel = cancel.child_token();
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(64);
+        crux_ipc::server::start_server(socket_path.clone(), cmd_tx, session_cancel.clone()).await?;
+        let tx = session_tx.clone();
+        let tag = name.to_string();
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if tx.send((tag.clone(), cmd)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(MuxSession {
+            server,
+            socket_path,
+            cancel: session_cancel,
+            created_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        })
+    }
+
+    /// Drop sessions whose processes have all exited.
+    fn reap(&mut self) {
+        let ended: Vec<String> = self
+            .sessions
+            .iter_mut()
+            .filter_map(|(name, session)| {
+                (!session.server.has_running_process()).then(|| name.clone())
+            })
+            .collect();
+        for name in ended {
+            log::info!("session {:?} ended", name);
+            self.remove(&name);
+        }
+    }
+
+    /// Stop a session's socket and drop its panes, hanging up their
+    /// processes. Returns whether the session existed.
+    fn remove(&mut self, name: &str) -> bool {
+        match self.sessions.remove(name) {
+            Some(session) => {
+                session.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The lowest unused numeric session name.
+    fn free_name(&self) -> String {
+        (0u64..)
+            .map(|n| n.to_string())
+            .find(|name| !self.sessions.contains_key(name))
+            .expect("session names exhausted")
+    }
+}
+
+fn session_not_found(name: &str) -> anyhow::Error {
+    anyhow::anyhow!("session {:?} not found", name)
+}
+
+/// Session names become socket file names: keep them short and plain.
+pub fn validate_session_name(name: &str) -> anyhow::Result<()> {
+    let plain = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if name.is_empty() || name.len() > MAX_SESSION_NAME || !plain || name.starts_with('.') {
+        anyhow::bail!(
+            "invalid session name {:?}: use up to {} letters, digits, '-', '_' or '.'",
+            name,
+            MAX_SESSION_NAME
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,330 @@
+//! Pane registry and event loop of the headless server.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crux_ipc::CancellationToken;
+use crux_protocol::{LayoutPane, PaneEvent, PaneId, PaneSize, SplitDirection, SplitSize};
+use crux_terminal::{CruxTerminal, TerminalEvent, TerminalSize};
+
+use crate::layout::{Layout, Rect};
+
+/// How often terminal events are drained while no IPC command is pending.
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Maximum buffered events for `crux:events/poll`, as in the GUI.
+const MAX_PANE_EVENTS: usize = 10_000;
+
+/// Settings for a [`HeadlessServer`].
+#[derive(Debug, Clone)]
+pub struct HeadlessConfig {
+    /// Unix socket the IPC server listens on. Also exported to every pane
+    /// as `CRUX_SOCKET`.
+    pub socket_path: PathBuf,
+    /// Size of the virtual window in cells, shared by all panes.
+    pub cols: usize,
+    pub rows: usize,
+    /// Shell to spawn; `None` uses the user's default shell.
+    pub shell: Option<String>,
+    /// Arguments for `shell`; `None` starts a login shell.
+    pub shell_args: Option<Vec<String>>,
+    /// Working directory of the first pane.
+    pub cwd: Option<String>,
+    /// Command the first pane runs instead of the shell.
+    pub command: Option<Vec<String>>,
+}
+
+impl HeadlessConfig {
+    pub fn new(socket_path: PathBuf) -> Self {
+        let size = TerminalSize::default();
+        Self {
+            socket_path,
+            cols: size.cols,
+            rows: size.rows,
+            shell: None,
+            shell_args: None,
+            cwd: None,
+            command: None,
+        }
+    }
+}
+
+pub(crate) struct HeadlessPane {
+    pub(crate) terminal: CruxTerminal,
+    /// Last title set via OSC 0/2.
+    pub(crate) title: Option<String>,
+    /// The cwd, command and env the pane was started with, for
+    /// `crux:session/save`.
+    pub(crate) launch: LayoutPane,
+}
+
+/// Terminal panes served over IPC without a window.
+///
+/// Owns the same state the GUI keeps for IPC (pane registry, active pane,
+/// event buffers, in-flight commands), with a virtual [`Layout`] in place
+/// of the dock.
+pub struct HeadlessServer {
+    pub(crate) config: HeadlessConfig,
+    pub(crate) panes: HashMap<PaneId, HeadlessPane>,
+    pub(crate) layout: Layout,
+    pub(crate) active_pane: Option<PaneId>,
+    next_pane_id: u64,
+    /// Buffer of pane lifecycle events drained by `crux:events/poll`.
+    pane_events: VecDeque<PaneEvent>,
+    /// Per-connection `crux:events/subscribe` queues fed by `emit_pane_event`.
+    pub(crate) event_subscribers: Vec<crux_ipc::EventSubscription>,
+    /// In-flight `crux:pane/run-command` per pane. The PTY-side capture holds
+    /// the strong reference, so the entry goes stale once it finishes.
+    pub(crate) running_commands: HashMap<PaneId, std::sync::Weak<()>>,
+}
+
+impl HeadlessServer {
+    /// Create the server and spawn its first pane.
+    pub fn new(config: HeadlessConfig) -> anyhow::Result<Self> {
+        let mut server = Self {
+            config,
+            panes: HashMap::new(),
+            layout: Layout::default(),
+            active_pane: None,
+            next_pane_id: 0,
+            pane_events: VecDeque::new(),
+            event_subscribers: Vec::new(),
+            running_commands: HashMap::new(),
+        };
+        let pane_id = server.allocate_pane_id();
+        let bounds = server.bounds();
+        let launch = LayoutPane {
+            cwd: server.config.cwd.clone(),
+            command: server.config.command.clone(),
+            ..LayoutPane::default()
+        };
+        let terminal = server.spawn_terminal(
+            pane_id,
+            bounds,
+            launch.cwd.as_deref(),
+            launch.command.as_deref(),
+            None,
+        )?;
+        server.panes.insert(
+            pane_id,
+            HeadlessPane {
+                terminal,
+                title: None,
+                launch,
+            },
+        );
+        server.layout = Layout::new(pane_id);
+        server.active_pane = Some(pane_id);
+        Ok(server)
+    }
+
+    /// Serve IPC requests on `config.socket_path` until `cancel` fires.
+    pub async fn serve(mut self, cancel: CancellationToken) -> anyhow::Result<()> {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(64);
+        let server =
+            crux_ipc::server::start_server(self.config.socket_path.clone(), cmd_tx, cancel.clone())
+                .await?;
+
+        let mut ticker = tokio::time::interval(TICK_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                Some(cmd) = cmd_rx.recv() => {
+                    // Catch up on terminal output first so replies see it.
+                    self.tick();
+                    self.handle_ipc_command(cmd);
+                }
+                _ = ticker.tick() => self.tick(),
+            }
+        }
+        let _ = server.await;
+        Ok(())
+    }
+
+    /// Process pending terminal events: answer terminal queries and track
+    /// titles, as `CruxTerminalView` does on every frame.
+    pub fn tick(&mut self) {
+        let mut titles = Vec::new();
+        for (pane_id, pane) in &mut self.panes {
+            for event in pane.terminal.drain_events() {
+                match event {
+                    TerminalEvent::PtyWrite(text) => pane.terminal.write_to_pty(text.as_bytes()),
+                    TerminalEvent::Title(title) => {
+                        if pane.title.as_deref() != Some(title.as_str()) {
+                            pane.title = Some(title.clone());
+                            titles.push((*pane_id, title));
+                        }
+                    }
+                    TerminalEvent::ProcessExit(code) => {
+                        log::info!("pane {} process exited with code {}", pane_id, code);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (pane_id, title) in titles {
+            self.emit_pane_event(PaneEvent::TitleChanged { pane_id, title });
+        }
+    }
+
+    /// Resize the virtual window and every pane in it.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.config.cols = cols.max(1);
+        self.config.rows = rows.max(1);
+        self.apply_layout();
+    }
+
+    /// Size of the virtual window in cells as `(cols, rows)`.
+    pub fn window_size(&self) -> (usize, usize) {
+        (self.config.cols, self.config.rows)
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+
+    /// Whether any pane's child process is still alive.
+    pub fn has_running_process(&mut self) -> bool {
+        self.panes
+            .values_mut()
+            .any(|pane| pane.terminal.is_process_running())
+    }
+
+    pub(crate) fn allocate_pane_id(&mut self) -> PaneId {
+        let id = PaneId(self.next_pane_id);
+        self.next_pane_id += 1;
+        id
+    }
+
+    /// The whole virtual window.
+    pub(crate) fn bounds(&self) -> Rect {
+        Rect {
+            col: 0,
+            row: 0,
+            cols: self.config.cols,
+            rows: self.config.rows,
+        }
+    }
+
+    /// Start a shell for `pane_id` sized to `rect`.
+    pub(crate) fn spawn_terminal(
+        &self,
+        pane_id: PaneId,
+        rect: Rect,
+        cwd: Option<&str>,
+        command: Option<&[String]>,
+        env: Option<&HashMap<String, String>>,
+    ) -> anyhow::Result<CruxTerminal> {
+        let mut child_env = env.cloned().unwrap_or_default();
+        child_env.insert("CRUX_PANE".to_string(), pane_id.0.to_string());
+        child_env.insert("TERM_PROGRAM".to_string(), "Crux".to_string());
+        child_env.insert(
+            "CRUX_SOCKET".to_string(),
+            self.config.socket_path.to_string_lossy().into_owned(),
+        );
+        CruxTerminal::new(
+            self.config.shell.clone(),
+            self.config.shell_args.as_deref(),
+            terminal_size(rect),
+            cwd,
+            command,
+            Some(&child_env),
+        )
+    }
+
+    /// Fraction of `target`'s space a new split pane should take.
+    pub(crate) fn split_ratio(
+        &self,
+        target: PaneId,
+        direction: SplitDirection,
+        size: Option<SplitSize>,
+    ) -> f32 {
+        match size {
+            None => 0.5,
+            Some(SplitSize::Percent(p)) => f32::from(p) / 100.0,
+            Some(SplitSize::Cells(cells)) => {
+                let extent = self
+                    .layout
+                    .geometry(self.bounds())
+                    .into_iter()
+                    .find(|(id, _)| *id == target)
+                    .map(|(_, r)| match direction {
+                        SplitDirection::Left | SplitDirection::Right => r.cols,
+                        SplitDirection::Top | SplitDirection::Bottom => r.rows,
+                    })
+                    .unwrap_or(0);
+                if extent == 0 {
+                    0.5
+                } else {
+                    cells as f32 / extent as f32
+                }
+            }
+        }
+    }
+
+    /// Resize every terminal to its layout rectangle, emitting
+    /// `PaneEvent::Resized` for the ones that changed.
+    pub(crate) fn apply_layout(&mut self) {
+        let mut resized = Vec::new();
+        for (pane_id, rect) in self.layout.geometry(self.bounds()) {
+            let Some(pane) = self.panes.get_mut(&pane_id) else {
+                continue;
+            };
+            let size = terminal_size(rect);
+            let current = pane.terminal.size();
+            if (current.rows, current.cols) != (size.rows, size.cols) {
+                pane.terminal.resize(size);
+                resized.push((pane_id, size));
+            }
+        }
+        for (pane_id, size) in resized {
+            self.emit_pane_event(PaneEvent::Resized {
+                pane_id,
+                size: PaneSize {
+                    rows: size.rows as u32,
+                    cols: size.cols as u32,
+                },
+            });
+        }
+    }
+
+    /// The pane IPC requests without a `pane_id` target.
+    pub(crate) fn active_pane_id(&self) -> Option<PaneId> {
+        self.active_pane
+            .filter(|id| self.panes.contains_key(id))
+            .or_else(|| self.layout.panes().first().copied())
+    }
+
+    /// Push a pane lifecycle event to subscribers and into the poll buffer.
+    pub(crate) fn emit_pane_event(&mut self, event: PaneEvent) {
+        // Subscriptions whose connection has closed are dropped here.
+        self.event_subscribers.retain(|sub| sub.offer(&event));
+
+        if self.pane_events.len() >= MAX_PANE_EVENTS {
+            log::warn!(
+                "pane event buffer full ({}), dropping oldest event",
+                MAX_PANE_EVENTS
+            );
+            self.pane_events.pop_front();
+        }
+        self.pane_events.push_back(event);
+    }
+
+    /// Drain all buffered pane events for consumption.
+    pub(crate) fn drain_pane_events(&mut self) -> Vec<PaneEvent> {
+        self.pane_events.drain(..).collect()
+    }
+}
+
+/// Terminal size for a layout rectangle. Panes are never smaller than one cell.
+fn terminal_size(rect: Rect) -> TerminalSize {
+    TerminalSize {
+        rows: rect.rows.max(1),
+        cols: rect.cols.max(1),
+        ..TerminalSize::default()
+    }
+}
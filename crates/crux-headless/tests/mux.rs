@@ -0,0 +1,128 @@
+//! End-to-end tests of the mux daemon's control socket and its sessions.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use crux_headless::{MuxConfig, MuxDaemon};
+use crux_ipc::{CancellationToken, IpcClient, IpcTransport};
+use crux_protocol::{method, MuxListResult, MuxSessionInfo};
+
+fn connect(socket: PathBuf) -> IpcClient {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match IpcClient::connect_to(socket.clone()) {
+            Ok(client) => return client,
+            Err(e) if Instant::now() >= deadline => panic!("daemon never came up: {e}"),
+            Err(_) => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+fn wait_for_text(client: &IpcClient, needle: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let result = client.call(method::PANE_GET_TEXT, json!({})).unwrap();
+        let lines: Vec<String> = serde_json::from_value(result["lines"].clone()).unwrap();
+        if lines.iter().any(|l| l.contains(needle)) {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "{needle:?} never appeared: {lines:?}"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_mux_sessions_survive_detach() {
+    let dir = std::env::temp_dir().join(format!("crux-mux-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = MuxConfig {
+        socket_path: dir.join("control.sock"),
+        session_dir: dir.clone(),
+        shell: Some("/bin/sh".into()),
+        shell_args: Some(Vec::new()),
+        ..MuxConfig::new()
+    };
+    let control_socket = config.socket_path.clone();
+    let cancel = CancellationToken::new();
+    let daemon_cancel = cancel.clone();
+    let daemon = std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime")
+            .block_on(MuxDaemon::new(config).serve(daemon_cancel))
+            .expect("serve");
+    });
+
+    let control = connect(control_socket);
+    let attach = |params: serde_json::Value| -> anyhow::Result<MuxSessionInfo> {
+        Ok(serde_json::from_value(
+            control.call(method::MUX_ATTACH, params)?,
+        )?)
+    };
+    let list = || -> MuxListResult {
+        serde_json::from_value(control.call(method::MUX_LIST, json!({})).unwrap()).unwrap()
+    };
+
+    assert!(attach(json!({ "name": "work" })).is_err());
+    assert!(attach(json!({ "name": "../work", "create": true })).is_err());
+    let info = attach(json!({ "name": "work", "create": true, "rows": 30, "cols": 100 })).unwrap();
+    assert_eq!((info.name.as_str(), info.pane_count), ("work", 1));
+    assert_eq!((info.rows, info.cols), (30, 100));
+
+    // Start something in the session, then detach by dropping the client.
+    {
+        let session = connect(PathBuf::from(&info.socket_path));
+        session
+            .call(
+                method::PANE_SEND_TEXT,
+                json!({ "text": "echo mux-$((6*7)); echo $CRUX_SOCKET\n" }),
+            )
+            .unwrap();
+        wait_for_text(&session, "mux-42");
+        // Non-mux methods are refused on the control socket.
+        assert!(control.call(method::PANE_LIST, json!({})).is_err());
+    }
+
+    // Reattaching finds the same session with its screen intact.
+    let again = attach(json!({ "name": "work", "rows": 20, "cols": 60 })).unwrap();
+    assert_eq!(again.socket_path, info.socket_path);
+    assert_eq!((again.rows, again.cols), (20, 60));
+    let session = connect(PathBuf::from(&again.socket_path));
+    wait_for_text(&session, "mux-42");
+    wait_for_text(&session, &info.socket_path);
+
+    let auto = attach(json!({ "create": true })).unwrap();
+    assert_eq!(auto.name, "0");
+    let names: Vec<String> = list().sessions.into_iter().map(|s| s.name).collect();
+    assert_eq!(names, ["0", "work"]);
+
+    control
+        .call(method::MUX_KILL, json!({ "name": "work" }))
+        .unwrap();
+    assert!(control
+        .call(method::MUX_KILL, json!({ "name": "work" }))
+        .is_err());
+    assert_eq!(list().sessions.len(), 1);
+
+    // A session whose shell exits goes away on its own.
+    let auto_session = connect(PathBuf::from(&auto.socket_path));
+    auto_session
+        .call(method::PANE_SEND_TEXT, json!({ "text": "exit\n" }))
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !list().sessions.is_empty() {
+        assert!(Instant::now() < deadline, "exited session was not reaped");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    cancel.cancel();
+    daemon.join().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
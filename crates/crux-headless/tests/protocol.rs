@@ -0,0 +1,418 @@
+//! End-to-end protocol tests against a headless server on a private socket.
+//!
+//! Each test starts a [`HeadlessServer`] running `/bin/sh` and drives it with
+//! the same synchronous [`IpcClient`] the CLI uses.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use crux_headless::{HeadlessConfig, HeadlessServer};
+use crux_ipc::{CancellationToken, IpcClient, IpcTransport};
+use crux_protocol::{method, PaneEvent, PaneId};
+
+struct Harness {
+    client: IpcClient,
+    cancel: CancellationToken,
+    server: Option<JoinHandle<()>>,
+}
+
+impl Harness {
+    fn start() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let socket = std::env::temp_dir().join(format!(
+            "crux-headless-{}-{}.sock",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let config = HeadlessConfig {
+            shell: Some("/bin/sh".into()),
+            shell_args: Some(Vec::new()),
+            ..HeadlessConfig::new(socket.clone())
+        };
+        let cancel = CancellationToken::new();
+        let server_cancel = cancel.clone();
+        let server = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("runtime");
+            runtime.block_on(async move {
+                let server = HeadlessServer::new(config).expect("spawn first pane");
+                server.serve(server_cancel).await.expect("serve");
+            });
+        });
+        Self {
+            client: connect(socket),
+            cancel,
+            server: Some(server),
+        }
+    }
+
+    fn call(&self, method: &str, params: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        self.client.call(method, params)
+    }
+
+    /// Poll `crux:pane/get-text` until `needle` shows up on screen.
+    fn wait_for_text(&self, pane_id: u64, needle: &str) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let result = self
+                .call(method::PANE_GET_TEXT, json!({ "pane_id": pane_id }))
+                .unwrap();
+            let lines: Vec<String> = serde_json::from_value(result["lines"].clone()).unwrap();
+            if lines.iter().any(|l| l.contains(needle)) {
+                return lines;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "{needle:?} never appeared in pane {pane_id}: {lines:?}"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn list(&self) -> crux_protocol::ListPanesResult {
+        serde_json::from_value(self.call(method::PANE_LIST, json!({})).unwrap()).unwrap()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+fn connect(socket: PathBuf) -> IpcClient {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match IpcClient::connect_to(socket.clone()) {
+            Ok(client) => return client,
+            Err(e) if Instant::now() >= deadline => panic!("server never came up: {e}"),
+            Err(_) => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+#[test]
+fn test_send_text_and_read_back() {
+    let harness = Harness::start();
+    let handshake = harness
+        .call(
+            method::HANDSHAKE,
+            json!({
+                "client_name": "test",
+                "client_version": "0",
+                "protocol_version": "1.0",
+                "capabilities": [],
+            }),
+        )
+        .unwrap();
+    assert_eq!(handshake["server_name"], "crux-headless");
+
+    let panes = harness.list().panes;
+    assert_eq!(panes.len(), 1);
+    assert_eq!((panes[0].size.cols, panes[0].size.rows), (80, 24));
+    assert!(panes[0].is_active);
+
+    // The joined output does not appear in the echoed command line.
+    harness
+        .call(
+            method::PANE_SEND_TEXT,
+            json!({ "pane_id": 0, "text": "echo head''less\n" }),
+        )
+        .unwrap();
+    harness.wait_for_text(0, "headless");
+
+    let err = harness
+        .call(method::PANE_GET_TEXT, json!({ "pane_id": 42 }))
+        .unwrap_err();
+    assert!(err.to_string().contains("pane 42 not found"), "{err}");
+}
+
+#[test]
+fn test_split_resize_and_close() {
+    let harness = Harness::start();
+
+    let split: crux_protocol::SplitPaneResult = serde_json::from_value(
+        harness
+            .call(method::PANE_SPLIT, json!({ "direction": "right" }))
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(split.pane_id, PaneId(1));
+    assert_eq!((split.size.cols, split.size.rows), (40, 24));
+
+    let panes = harness.list().panes;
+    let sizes: Vec<_> = panes
+        .iter()
+        .map(|p| (p.pane_id.0, p.size.cols, p.is_active))
+        .collect();
+    assert_eq!(sizes, vec![(0, 40, false), (1, 40, true)]);
+
+    harness
+        .call(
+            method::PANE_SEND_TEXT,
+            json!({ "pane_id": 1, "text": "echo right''side\n" }),
+        )
+        .unwrap();
+    harness.wait_for_text(1, "rightside");
+
+    // 30 cells at the nominal 8 px cell width.
+    harness
+        .call(method::PANE_RESIZE, json!({ "pane_id": 0, "width": 240.0 }))
+        .unwrap();
+    let cols: Vec<_> = harness.list().panes.iter().map(|p| p.size.cols).collect();
+    assert_eq!(cols, vec![30, 50]);
+
+    harness
+        .call(method::PANE_CLOSE, json!({ "pane_id": 1, "force": true }))
+        .unwrap();
+    let panes = harness.list().panes;
+    assert_eq!(panes.len(), 1);
+    assert_eq!(panes[0].size.cols, 80);
+
+    let events: crux_protocol::EventsPollResult =
+        serde_json::from_value(harness.call(method::EVENTS_POLL, json!({})).unwrap()).unwrap();
+    let kinds: Vec<String> = events
+        .events
+        .iter()
+        .map(|e| match e {
+            PaneEvent::Created { pane_id } => format!("created {}", pane_id.0),
+            PaneEvent::Closed { pane_id } => format!("closed {}", pane_id.0),
+            PaneEvent::Focused { pane_id } => format!("focused {}", pane_id.0),
+            PaneEvent::Resized { pane_id, size } => format!("resized {} {}", pane_id.0, size.cols),
+            PaneEvent::TitleChanged { pane_id, .. } => format!("title {}", pane_id.0),
+            PaneEvent::ConfigReloaded { .. } | PaneEvent::ConfigError { .. } => {
+                "config".to_string()
+            }
+        })
+        .filter(|k| !k.starts_with("title"))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "created 1",
+            "resized 0 40",
+            "resized 0 30",
+            "resized 1 50",
+            "closed 1",
+            "resized 0 80",
+        ]
+    );
+}
+
+#[test]
+fn test_single_window_and_tab() {
+    let harness = Harness::start();
+    harness
+        .call(method::PANE_SPLIT, json!({ "direction": "bottom" }))
+        .unwrap();
+
+    let windows: crux_protocol::WindowListResult =
+        serde_json::from_value(harness.call(method::WINDOW_LIST, json!({})).unwrap()).unwrap();
+    assert_eq!(windows.windows.len(), 1);
+    assert_eq!(windows.windows[0].tab_count, 1);
+    assert_eq!(windows.windows[0].pane_count, 2);
+
+    let tabs: crux_protocol::TabListResult =
+        serde_json::from_value(harness.call(method::TAB_LIST, json!({})).unwrap()).unwrap();
+    assert_eq!(tabs.tabs.len(), 1);
+    assert_eq!(tabs.tabs[0].pane_ids, vec![PaneId(0), PaneId(1)]);
+    assert_eq!(tabs.tabs[0].active_pane_id, Some(PaneId(1)));
+
+    harness
+        .call(method::WINDOW_FOCUS, json!({ "window_id": 0 }))
+        .unwrap();
+    let err = harness
+        .call(method::WINDOW_FOCUS, json!({ "window_id": 3 }))
+        .unwrap_err();
+    assert!(err.to_string().contains("window 3 not found"), "{err}");
+    let err = harness.call(method::WINDOW_CREATE, json!({})).unwrap_err();
+    assert!(err.to_string().contains("not supported"), "{err}");
+}
+
+#[test]
+fn test_layout_apply_and_get() {
+    let harness = Harness::start();
+
+    // The first pane's shell is still running.
+    let err = harness
+        .call(method::LAYOUT_APPLY, json!({ "template": "two-columns" }))
+        .unwrap_err();
+    assert!(err.to_string().contains("running process"), "{err}");
+
+    let layout = json!({
+        "type": "split",
+        "axis": "vertical",
+        "ratios": [3, 1],
+        "children": [
+            { "type": "pane", "title": "top" },
+            { "type": "pane", "command": ["/bin/sh", "-c", "echo bottom-pane; exec sh"] }
+        ]
+    });
+    let applied: crux_protocol::LayoutApplyResult = serde_json::from_value(
+        harness
+            .call(
+                method::LAYOUT_APPLY,
+                json!({ "layout": layout, "force": true }),
+            )
+            .unwrap(),
+    )
+    .unwrap();
+    let ids: Vec<_> = applied
+        .layout
+        .panes()
+        .iter()
+        .map(|p| p.pane_id.unwrap())
+        .collect();
+    assert_eq!(ids, vec![PaneId(1), PaneId(2)]);
+    harness.wait_for_text(2, "bottom-pane");
+
+    let list = harness.list();
+    let mut listed: Vec<_> = list
+        .panes
+        .iter()
+        .map(|p| (p.pane_id, p.size.rows))
+        .collect();
+    listed.sort_by_key(|(id, _)| id.0);
+    assert_eq!(listed, vec![(PaneId(1), 18), (PaneId(2), 6)]);
+
+    let got: crux_protocol::LayoutGetResult =
+        serde_json::from_value(harness.call(method::LAYOUT_GET, json!({})).unwrap()).unwrap();
+    match &got.layout {
+        crux_protocol::LayoutNode::Split { axis, ratios, .. } => {
+            assert_eq!(*axis, crux_protocol::LayoutAxis::Vertical);
+            assert_eq!(ratios.as_deref(), Some(&[0.75, 0.25][..]));
+        }
+        other => panic!("unexpected layout {other:?}"),
+    }
+    assert_eq!(got.layout.panes()[0].title.as_deref(), Some("top"));
+    // The tree can be applied again as it is.
+    assert_eq!(
+        got.layout.panes()[1].command.as_deref(),
+        Some(&["/bin/sh", "-c", "echo bottom-pane; exec sh"].map(String::from)[..])
+    );
+
+    let err = harness
+        .call(
+            method::LAYOUT_APPLY,
+            json!({ "template": "no-such-layout" }),
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown layout"), "{err}");
+}
+
+#[test]
+fn test_session_save_and_load() {
+    let harness = Harness::start();
+    let dir = std::env::temp_dir().join(format!("crux-session-{}", std::process::id()));
+    let path = dir.join("session.json");
+
+    let layout = json!({
+        "type": "split",
+        "axis": "horizontal",
+        "children": [
+            { "type": "pane", "cwd": "/tmp", "title": "left" },
+            { "type": "pane", "env": { "CRUX_TEST_VAR": "kept" } }
+        ]
+    });
+    harness
+        .call(
+            method::LAYOUT_APPLY,
+            json!({ "layout": layout, "force": true }),
+        )
+        .unwrap();
+    harness
+        .call(
+            method::PANE_SEND_TEXT,
+            json!({ "pane_id": 1, "text": "echo saved-$((6*7))\n" }),
+        )
+        .unwrap();
+    harness.wait_for_text(1, "saved-42");
+
+    harness
+        .call(method::SESSION_SAVE, json!({ "path": path }))
+        .unwrap();
+    let session =
+        crux_protocol::Session::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let panes = session.layout.panes();
+    assert_eq!(panes[0].cwd.as_deref(), Some("/tmp"));
+    assert_eq!(panes[0].title.as_deref(), Some("left"));
+    assert_eq!(
+        panes[1].env.as_ref().unwrap()["CRUX_TEST_VAR"],
+        "kept".to_string()
+    );
+    let saved = session.pane(PaneId(1)).unwrap();
+    assert_eq!(
+        saved.scrollback.as_ref().unwrap().encoding,
+        crux_protocol::ScrollbackEncoding::GzipBase64
+    );
+
+    let loaded: crux_protocol::SessionLoadResult = serde_json::from_value(
+        harness
+            .call(method::SESSION_LOAD, json!({ "path": path }))
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(loaded.pane_count, 2);
+    // Panes come back under new IDs with the old output above the prompt.
+    harness.wait_for_text(3, "saved-42");
+    harness
+        .call(
+            method::PANE_SEND_TEXT,
+            json!({ "pane_id": 4, "text": "echo env-$CRUX_TEST_VAR\n" }),
+        )
+        .unwrap();
+    harness.wait_for_text(4, "env-kept");
+
+    std::fs::write(&path, r#"{"version": 99, "layout": {"type": "pane"}}"#).unwrap();
+    let err = harness
+        .call(method::SESSION_LOAD, json!({ "path": path }))
+        .unwrap_err();
+    assert!(err.to_string().contains("newer than"), "{err}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_render_list_and_get_image() {
+    const PNG_1X1: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+    let harness = Harness::start();
+    let rendered = harness
+        .call(
+            method::RENDER_IMAGE,
+            json!({ "pane_id": 0, "data": PNG_1X1, "cols": 6, "rows": 3 }),
+        )
+        .unwrap();
+    let image_id = rendered["image_id"].clone();
+    assert_eq!(rendered["placement"]["cols"], 6);
+    assert_eq!(rendered["placement"]["rows"], 3);
+
+    let listed = harness
+        .call(method::PANE_LIST_IMAGES, json!({ "pane_id": 0 }))
+        .unwrap();
+    assert_eq!(listed["images"][0]["image_id"], image_id);
+    assert_eq!(listed["images"][0]["width"], 1);
+    assert_eq!(listed["images"][0]["placements"][0], rendered["placement"]);
+
+    let image = harness
+        .call(method::PANE_GET_IMAGE, json!({ "image_id": image_id }))
+        .unwrap();
+    assert_eq!(image["mime_type"], "image/png");
+    assert!(image["data"].as_str().is_some_and(|data| !data.is_empty()));
+
+    let err = harness
+        .call(method::PANE_GET_IMAGE, json!({ "image_id": 999 }))
+        .unwrap_err();
+    assert!(err.to_string().contains("image 999 not found"), "{err}");
+    let err = harness
+        .call(method::RENDER_IMAGE, json!({ "data": "bm90IGFuIGltYWdl" }))
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown image file"), "{err}");
+}
@@ -1,6 +1,7 @@
 //! Synchronous IPC client for connecting to a running Crux instance.
 
-use std::io::{Read, Write};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
 use std::sync::Mutex;
@@ -13,17 +14,117 @@ use crux_protocol::{decode_frame, encode_frame, JsonRpcId, JsonRpcRequest, JsonR
 /// Enables testing MCP tools without a running Crux instance.
 pub trait IpcTransport: Send + Sync {
     fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value>;
+
+    /// Like [`call`](Self::call), for requests that may legitimately take
+    /// longer than the default read timeout (e.g. `crux:pane/run-command`).
+    fn call_with_timeout(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value> {
+        let _ = timeout;
+        self.call(method, params)
+    }
 }
 
+/// Default read timeout for request/response calls.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum pending buffer size (16MB, matching MAX_FRAME_SIZE in protocol).
+const MAX_PENDING_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum number of server notifications held while waiting for a response.
+const MAX_QUEUED_NOTIFICATIONS: usize = 1024;
+
 /// Synchronous IPC client that connects to the running Crux terminal via Unix socket.
 ///
 /// Thread-safe via internal mutexes so it can be shared across async tasks
 /// (e.g., wrapped in `Arc<IpcClient>` for the MCP server).
+///
+/// After subscribing (e.g. `crux:events/subscribe`) the server may push
+/// notifications at any time. Notifications that arrive while a call is
+/// waiting for its response are queued and handed out by
+/// [`next_notification`](Self::next_notification).
 pub struct IpcClient {
-    stream: Mutex<UnixStream>,
+    stream: Mutex<ClientStream>,
     next_id: Mutex<u64>,
 }
 
+/// Socket plus the read-side state that must survive between calls.
+struct ClientStream {
+    socket: UnixStream,
+    /// Bytes read past the end of the last decoded frame.
+    pending: Vec<u8>,
+    notifications: VecDeque<JsonRpcRequest>,
+}
+
+/// A decoded server-to-client frame.
+enum Incoming {
+    Response(JsonRpcResponse),
+    Notification(JsonRpcRequest),
+}
+
+impl ClientStream {
+    /// Block until one complete frame has been read and decoded.
+    fn read_frame(&mut self) -> Result<Incoming> {
+        let mut buf = vec![0u8; 65536];
+        loop {
+            if let Some((consumed, payload)) = decode_frame(&self.pending)
+                .map_err(|e| anyhow::anyhow!("frame decode error: {e}"))?
+            {
+                let value: serde_json::Value = serde_json::from_slice(&payload)?;
+                self.pending.drain(..consumed);
+                // Requests without an id are server notifications.
+                if value.get("method").is_some() && value.get("id").is_none() {
+                    return Ok(Incoming::Notification(serde_json::from_value(value)?));
+                }
+                return Ok(Incoming::Response(serde_json::from_value(value)?));
+            }
+
+            let n = self.socket.read(&mut buf)?;
+            if n == 0 {
+                bail!("server closed connection");
+            }
+            self.pending.extend_from_slice(&buf[..n]);
+
+            // Check buffer size limit to prevent unbounded growth.
+            if self.pending.len() > MAX_PENDING_SIZE {
+                bail!("response too large ({} bytes)", self.pending.len());
+            }
+        }
+    }
+
+    fn queue_notification(&mut self, notification: JsonRpcRequest) {
+        if self.notifications.len() >= MAX_QUEUED_NOTIFICATIONS {
+            log::warn!("IPC notification queue full, dropping oldest notification");
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(notification);
+    }
+
+    /// Read frames until the response to request `id` arrives, queueing any
+    /// notifications seen on the way.
+    fn read_response(&mut self, id: u64) -> Result<serde_json::Value> {
+        loop {
+            match self.read_frame()? {
+                Incoming::Notification(notification) => self.queue_notification(notification),
+                Incoming::Response(response) => {
+                    // Frame-level errors carry a null id; anything else must match.
+                    if response.id != JsonRpcId::Number(id) && response.id != JsonRpcId::Null {
+                        log::debug!("ignoring response with unexpected id {}", response.id);
+                        continue;
+                    }
+                    if let Some(err) = response.error {
+                        bail!("server error {}: {}", err.code, err.message);
+                    }
+                    return Ok(response.result.unwrap_or(serde_json::Value::Null));
+                }
+            }
+        }
+    }
+}
+
 impl IpcClient {
     /// Connect to a running Crux instance.
     ///
@@ -37,9 +138,13 @@ impl IpcClient {
     pub fn connect_to(path: PathBuf) -> Result<Self> {
         let stream = UnixStream::connect(&path)
             .with_context(|| format!("failed to connect to {}", path.display()))?;
-        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        stream.set_read_timeout(Some(CALL_TIMEOUT))?;
         Ok(Self {
-            stream: Mutex::new(stream),
+            stream: Mutex::new(ClientStream {
+                socket: stream,
+                pending: Vec::new(),
+                notifications: VecDeque::new(),
+            }),
             next_id: Mutex::new(1),
         })
     }
@@ -69,8 +174,13 @@ impl IpcClient {
         ))
     }
 
-    /// Send a JSON-RPC request and wait for the response.
-    fn call_inner(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+    /// Send a JSON-RPC request and wait up to `timeout` for the response.
+    fn call_inner(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value> {
         let mut stream = self
             .stream
             .lock()
@@ -87,47 +197,63 @@ impl IpcClient {
         let frame =
             encode_frame(&req_bytes).map_err(|e| anyhow::anyhow!("frame encode error: {e}"))?;
 
-        stream.write_all(&frame)?;
-        stream.flush()?;
-
-        let mut buf = vec![0u8; 65536];
-        let mut pending = Vec::new();
+        stream.socket.write_all(&frame)?;
+        stream.socket.flush()?;
 
-        // Maximum pending buffer size (16MB, matching MAX_FRAME_SIZE in protocol).
-        const MAX_PENDING_SIZE: usize = 16 * 1024 * 1024;
-
-        loop {
-            let n = stream.read(&mut buf)?;
-            if n == 0 {
-                bail!("server closed connection");
-            }
-            pending.extend_from_slice(&buf[..n]);
+        if timeout == CALL_TIMEOUT {
+            return stream.read_response(id);
+        }
+        stream.socket.set_read_timeout(Some(timeout))?;
+        let result = stream.read_response(id);
+        stream.socket.set_read_timeout(Some(CALL_TIMEOUT))?;
+        result
+    }
 
-            // Check buffer size limit to prevent unbounded growth.
-            if pending.len() > MAX_PENDING_SIZE {
-                bail!("response too large ({} bytes)", pending.len());
-            }
+    /// Wait up to `timeout` for the next server notification.
+    ///
+    /// Returns queued notifications first. Returns `Ok(None)` if nothing
+    /// arrived in time. Stray responses (which cannot occur while no call is
+    /// in flight) are discarded.
+    pub fn next_notification(&self, timeout: Duration) -> Result<Option<JsonRpcRequest>> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|_| anyhow::anyhow!("IPC client mutex poisoned"))?;
+        if let Some(notification) = stream.notifications.pop_front() {
+            return Ok(Some(notification));
+        }
 
-            if let Some((consumed, payload)) =
-                decode_frame(&pending).map_err(|e| anyhow::anyhow!("frame decode error: {e}"))?
-            {
-                let response: JsonRpcResponse = serde_json::from_slice(&payload)?;
-                if let Some(err) = response.error {
-                    bail!("server error {}: {}", err.code, err.message);
-                }
-                // Note: any remaining bytes in pending are discarded after drain.
-                // This is safe because the client is strictly synchronous
-                // (one request, one response per call).
-                pending.drain(..consumed);
-                return Ok(response.result.unwrap_or(serde_json::Value::Null));
+        // A zero duration would disable the timeout entirely.
+        stream
+            .socket
+            .set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+        let result = loop {
+            match stream.read_frame() {
+                Ok(Incoming::Notification(notification)) => break Ok(Some(notification)),
+                Ok(Incoming::Response(_)) => continue,
+                Err(e) => match e.downcast_ref::<std::io::Error>().map(|io| io.kind()) {
+                    Some(ErrorKind::WouldBlock | ErrorKind::TimedOut) => break Ok(None),
+                    _ => break Err(e),
+                },
             }
-        }
+        };
+        stream.socket.set_read_timeout(Some(CALL_TIMEOUT))?;
+        result
     }
 }
 
 impl IpcTransport for IpcClient {
     fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
-        self.call_inner(method, params)
+        self.call_inner(method, params, CALL_TIMEOUT)
+    }
+
+    fn call_with_timeout(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value> {
+        self.call_inner(method, params, timeout.max(Duration::from_millis(1)))
     }
 }
 
@@ -185,8 +311,8 @@ mod tests {
         // variable in the test environment.
         unsafe { std::env::remove_var("CRUX_SOCKET") };
         let result = find_socket();
-        if result.is_err() {
-            let err_msg = result.unwrap_err().to_string();
+        if let Err(err) = result {
+            let err_msg = err.to_string();
             assert!(
                 err_msg.contains("no running Crux instance found"),
                 "expected 'no running Crux instance' error, got: {err_msg}",
@@ -206,4 +332,54 @@ mod tests {
         assert!(json.contains("\"method\":\"test_method\""));
         assert!(json.contains("\"id\":1"));
     }
+
+    #[test]
+    fn call_queues_notifications_received_before_response() {
+        use std::os::unix::net::UnixListener;
+
+        let path =
+            std::env::temp_dir().join(format!("crux-client-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = conn.read(&mut buf).unwrap();
+
+            // Push a notification and the response in a single write.
+            let notification = JsonRpcRequest::notification(
+                "crux:events/notify",
+                Some(serde_json::json!({"dropped": 0})),
+            );
+            let response =
+                JsonRpcResponse::success(JsonRpcId::Number(1), serde_json::json!({"ok": true}));
+            let mut out = encode_frame(&serde_json::to_vec(&notification).unwrap()).unwrap();
+            out.extend(encode_frame(&serde_json::to_vec(&response).unwrap()).unwrap());
+            conn.write_all(&out).unwrap();
+            // Keep the connection open until the client is done reading.
+            let _ = done_rx.recv();
+        });
+
+        let client = IpcClient::connect_to(path.clone()).unwrap();
+        let result = client
+            .call("crux:pane/list", serde_json::json!({}))
+            .unwrap();
+        assert_eq!(result["ok"], true);
+
+        let notification = client
+            .next_notification(Duration::from_millis(100))
+            .unwrap()
+            .expect("notification should have been queued");
+        assert_eq!(notification.method, "crux:events/notify");
+        assert!(client
+            .next_notification(Duration::from_millis(10))
+            .unwrap()
+            .is_none());
+
+        done_tx.send(()).unwrap();
+        server.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -7,13 +7,23 @@ use tokio::sync::oneshot;
 
 use crux_protocol::{
     ActivatePaneParams, ClipboardReadParams, ClipboardReadResult, ClipboardWriteParams,
-    ClosePaneParams, EventsPollResult, GetSelectionParams, GetSelectionResult, GetSnapshotParams,
-    GetSnapshotResult, GetTextParams, GetTextResult, HandshakeParams, HandshakeResult,
-    ImeSetInputSourceParams, ImeStateResult, ListPanesResult, ResizePaneParams, SendTextParams,
-    SendTextResult, SessionLoadParams, SessionLoadResult, SessionSaveParams, SessionSaveResult,
-    SplitPaneParams, SplitPaneResult, WindowCreateParams, WindowCreateResult, WindowListResult,
+    ClosePaneParams, ConfigStatusResult, DumpGridParams, DumpGridResult, EventsPollResult,
+    EventsSubscribeResult, GetImageParams, GetImageResult, GetModesParams, GetModesResult,
+    GetSelectionParams, GetSelectionResult, GetSnapshotParams, GetSnapshotResult, GetTextParams,
+    GetTextResult, HandshakeParams, HandshakeResult, ImeSetInputSourceParams, ImeStateResult,
+    LayoutApplyParams, LayoutApplyResult, LayoutGetParams, LayoutGetResult, ListImagesParams,
+    ListImagesResult, ListPanesResult, MuxAttachParams, MuxKillParams, MuxListResult,
+    MuxResizeParams, MuxSessionInfo, PaneHistoryParams, PaneHistoryResult, PaneId,
+    RenderImageParams, RenderImageResult, ResizePaneParams, RunCommandParams, RunCommandResult,
+    SendTextParams, SendTextResult, SessionLoadParams, SessionLoadResult, SessionSaveParams,
+    SessionSaveResult, SplitPaneParams, SplitPaneResult, SubscribeOutputResult, TabCreateParams,
+    TabCreateResult, TabListParams, TabListResult, TabMovePaneParams, WindowCloseParams,
+    WindowCreateParams, WindowCreateResult, WindowFocusParams, WindowListResult,
 };
 
+use crate::output::OutputSubscription;
+use crate::subscription::EventSubscription;
+
 /// Commands sent from the IPC server to the GPUI main thread.
 pub enum IpcCommand {
     Handshake {
@@ -40,6 +50,33 @@ pub enum IpcCommand {
         params: GetSnapshotParams,
         reply: oneshot::Sender<anyhow::Result<GetSnapshotResult>>,
     },
+    PaneHistory {
+        params: PaneHistoryParams,
+        reply: oneshot::Sender<anyhow::Result<PaneHistoryResult>>,
+    },
+    DumpGrid {
+        params: DumpGridParams,
+        reply: oneshot::Sender<anyhow::Result<DumpGridResult>>,
+    },
+    GetModes {
+        params: GetModesParams,
+        reply: oneshot::Sender<anyhow::Result<GetModesResult>>,
+    },
+    ListImages {
+        params: ListImagesParams,
+        reply: oneshot::Sender<anyhow::Result<ListImagesResult>>,
+    },
+    GetImage {
+        params: GetImageParams,
+        reply: oneshot::Sender<anyhow::Result<GetImageResult>>,
+    },
+    /// Place an image in a pane without writing to its PTY. `file` is the
+    /// image file, already read by the connection handler.
+    RenderImage {
+        params: RenderImageParams,
+        file: Vec<u8>,
+        reply: oneshot::Sender<anyhow::Result<RenderImageResult>>,
+    },
     ListPanes {
         reply: oneshot::Sender<anyhow::Result<ListPanesResult>>,
     },
@@ -62,6 +99,36 @@ pub enum IpcCommand {
     WindowList {
         reply: oneshot::Sender<anyhow::Result<WindowListResult>>,
     },
+    WindowClose {
+        params: WindowCloseParams,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
+    WindowFocus {
+        params: WindowFocusParams,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
+    TabCreate {
+        params: TabCreateParams,
+        reply: oneshot::Sender<anyhow::Result<TabCreateResult>>,
+    },
+    TabList {
+        params: TabListParams,
+        reply: oneshot::Sender<anyhow::Result<TabListResult>>,
+    },
+    TabMovePane {
+        params: TabMovePaneParams,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
+    /// Replace a window's panes with a declarative layout. Either all panes
+    /// of the layout are created or the window is left untouched.
+    LayoutApply {
+        params: LayoutApplyParams,
+        reply: oneshot::Sender<anyhow::Result<LayoutApplyResult>>,
+    },
+    LayoutGet {
+        params: LayoutGetParams,
+        reply: oneshot::Sender<anyhow::Result<LayoutGetResult>>,
+    },
     SessionSave {
         params: SessionSaveParams,
         reply: oneshot::Sender<anyhow::Result<SessionSaveResult>>,
@@ -70,6 +137,23 @@ pub enum IpcCommand {
         params: SessionLoadParams,
         reply: oneshot::Sender<anyhow::Result<SessionLoadResult>>,
     },
+    /// Sessions of the mux daemon. Only the daemon's control socket
+    /// serves the `Mux*` commands.
+    MuxList {
+        reply: oneshot::Sender<anyhow::Result<MuxListResult>>,
+    },
+    MuxAttach {
+        params: MuxAttachParams,
+        reply: oneshot::Sender<anyhow::Result<MuxSessionInfo>>,
+    },
+    MuxResize {
+        params: MuxResizeParams,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
+    MuxKill {
+        params: MuxKillParams,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
     ClipboardRead {
         params: ClipboardReadParams,
         reply: oneshot::Sender<anyhow::Result<ClipboardReadResult>>,
@@ -85,7 +169,119 @@ pub enum IpcCommand {
         params: ImeSetInputSourceParams,
         reply: oneshot::Sender<anyhow::Result<()>>,
     },
+    ConfigGetStatus {
+        reply: oneshot::Sender<anyhow::Result<ConfigStatusResult>>,
+    },
     EventsPoll {
         reply: oneshot::Sender<anyhow::Result<EventsPollResult>>,
     },
+    /// Register a connection's event queue; the GPUI side keeps the
+    /// subscription and feeds it every emitted [`crux_protocol::PaneEvent`].
+    EventsSubscribe {
+        subscription: EventSubscription,
+        reply: oneshot::Sender<anyhow::Result<EventsSubscribeResult>>,
+    },
+    /// Type a command at the pane's prompt and capture its output.
+    ///
+    /// The reply is sent as soon as the command has been submitted; the
+    /// result follows on [`CommandStarted::done`] when OSC 133 D arrives.
+    RunCommand {
+        params: RunCommandParams,
+        reply: oneshot::Sender<anyhow::Result<CommandStarted>>,
+    },
+    /// Attach `subscription` to the requested pane's PTY output.
+    SubscribeOutput {
+        subscription: OutputSubscription,
+        reply: oneshot::Sender<anyhow::Result<SubscribeOutputResult>>,
+    },
+}
+
+impl IpcCommand {
+    /// Answer the command with `error` without handling it, for front ends
+    /// that do not serve it.
+    pub fn reject(self, error: anyhow::Error) {
+        macro_rules! fail {
+            ($reply:expr) => {{
+                let _ = $reply.send(Err(error));
+            }};
+        }
+        match self {
+            IpcCommand::Handshake { reply, .. } => fail!(reply),
+            IpcCommand::SplitPane { reply, .. } => fail!(reply),
+            IpcCommand::SendText { reply, .. } => fail!(reply),
+            IpcCommand::GetText { reply, .. } => fail!(reply),
+            IpcCommand::GetSelection { reply, .. } => fail!(reply),
+            IpcCommand::GetSnapshot { reply, .. } => fail!(reply),
+            IpcCommand::PaneHistory { reply, .. } => fail!(reply),
+            IpcCommand::DumpGrid { reply, .. } => fail!(reply),
+            IpcCommand::GetModes { reply, .. } => fail!(reply),
+            IpcCommand::ListImages { reply, .. } => fail!(reply),
+            IpcCommand::GetImage { reply, .. } => fail!(reply),
+            IpcCommand::RenderImage { reply, .. } => fail!(reply),
+            IpcCommand::ListPanes { reply } => fail!(reply),
+            IpcCommand::ResizePane { reply, .. } => fail!(reply),
+            IpcCommand::ActivatePane { reply, .. } => fail!(reply),
+            IpcCommand::ClosePane { reply, .. } => fail!(reply),
+            IpcCommand::WindowCreate { reply, .. } => fail!(reply),
+            IpcCommand::WindowList { reply } => fail!(reply),
+            IpcCommand::WindowClose { reply, .. } => fail!(reply),
+            IpcCommand::WindowFocus { reply, .. } => fail!(reply),
+            IpcCommand::TabCreate { reply, .. } => fail!(reply),
+            IpcCommand::TabList { reply, .. } => fail!(reply),
+            IpcCommand::TabMovePane { reply, .. } => fail!(reply),
+            IpcCommand::LayoutApply { reply, .. } => fail!(reply),
+            IpcCommand::LayoutGet { reply, .. } => fail!(reply),
+            IpcCommand::SessionSave { reply, .. } => fail!(reply),
+            IpcCommand::SessionLoad { reply, .. } => fail!(reply),
+            IpcCommand::MuxList { reply } => fail!(reply),
+            IpcCommand::MuxAttach { reply, .. } => fail!(reply),
+            IpcCommand::MuxResize { reply, .. } => fail!(reply),
+            IpcCommand::MuxKill { reply, .. } => fail!(reply),
+            IpcCommand::ClipboardRead { reply, .. } => fail!(reply),
+            IpcCommand::ClipboardWrite { reply, .. } => fail!(reply),
+            IpcCommand::ImeGetState { reply } => fail!(reply),
+            IpcCommand::ImeSetInputSource { reply, .. } => fail!(reply),
+            IpcCommand::ConfigGetStatus { reply } => fail!(reply),
+            IpcCommand::EventsPoll { reply } => fail!(reply),
+            IpcCommand::EventsSubscribe { reply, .. } => fail!(reply),
+            IpcCommand::RunCommand { reply, .. } => fail!(reply),
+            IpcCommand::SubscribeOutput { reply, .. } => fail!(reply),
+        }
+    }
+
+    /// The pane this command explicitly targets, if any.
+    ///
+    /// Front ends with several windows use this to route the command to the
+    /// window owning the pane; `None` means "the active pane" or a command
+    /// that is not about a single pane.
+    pub fn target_pane(&self) -> Option<PaneId> {
+        match self {
+            IpcCommand::SplitPane { params, .. } => params.target_pane_id,
+            IpcCommand::SendText { params, .. } => params.pane_id,
+            IpcCommand::GetText { params, .. } => params.pane_id,
+            IpcCommand::GetSelection { params, .. } => params.pane_id,
+            IpcCommand::GetSnapshot { params, .. } => params.pane_id,
+            IpcCommand::PaneHistory { params, .. } => params.pane_id,
+            IpcCommand::DumpGrid { params, .. } => params.pane_id,
+            IpcCommand::GetModes { params, .. } => params.pane_id,
+            IpcCommand::ListImages { params, .. } => params.pane_id,
+            IpcCommand::GetImage { params, .. } => params.pane_id,
+            IpcCommand::RenderImage { params, .. } => params.pane_id,
+            IpcCommand::ResizePane { params, .. } => Some(params.pane_id),
+            IpcCommand::ActivatePane { params, .. } => Some(params.pane_id),
+            IpcCommand::ClosePane { params, .. } => Some(params.pane_id),
+            IpcCommand::RunCommand { params, .. } => params.pane_id,
+            IpcCommand::SubscribeOutput { subscription, .. } => subscription.pane_id(),
+            IpcCommand::TabMovePane { params, .. } => Some(params.pane_id),
+            _ => None,
+        }
+    }
+}
+
+/// A command submitted by [`IpcCommand::RunCommand`].
+pub struct CommandStarted {
+    /// Pane the command was typed into.
+    pub pane_id: PaneId,
+    /// Completed result; dropped without a value if the pane goes away.
+    pub done: oneshot::Receiver<RunCommandResult>,
 }
@@ -103,7 +103,7 @@ mod tests {
         assert!(json["error"].is_object());
         assert_eq!(json["error"]["code"], -1001);
         assert_eq!(json["error"]["message"], "Pane not found");
-        assert!(json["result"].is_null() || !json.get("result").is_some());
+        assert!(json["result"].is_null() || json.get("result").is_none());
     }
 
     #[test]
@@ -117,7 +117,7 @@ mod tests {
         let json = serde_json::to_value(&resp).unwrap();
 
         assert_eq!(json["result"], result_data);
-        assert!(json["error"].is_null() || !json.get("error").is_some());
+        assert!(json["error"].is_null() || json.get("error").is_none());
     }
 
     #[test]
@@ -214,6 +214,47 @@ mod tests {
         assert_eq!(info.size.rows, 24);
         assert!(info.is_active);
     }
+
+    #[test]
+    fn test_command_target_pane() {
+        use crate::command::IpcCommand;
+        use tokio::sync::oneshot;
+
+        let (reply, _rx) = oneshot::channel();
+        let cmd = IpcCommand::TabMovePane {
+            params: TabMovePaneParams {
+                pane_id: PaneId(3),
+                tab_id: TabId(9),
+            },
+            reply,
+        };
+        assert_eq!(cmd.target_pane(), Some(PaneId(3)));
+
+        let (reply, _rx) = oneshot::channel();
+        let cmd = IpcCommand::GetText {
+            params: serde_json::from_value(json!({})).unwrap(),
+            reply,
+        };
+        assert_eq!(cmd.target_pane(), None);
+
+        let (reply, _rx) = oneshot::channel();
+        assert_eq!(IpcCommand::WindowList { reply }.target_pane(), None);
+    }
+
+    #[test]
+    fn test_command_reject() {
+        use crate::command::IpcCommand;
+        use tokio::sync::oneshot;
+
+        let (reply, mut rx) = oneshot::channel();
+        IpcCommand::MuxAttach {
+            params: MuxAttachParams::default(),
+            reply,
+        }
+        .reject(anyhow::anyhow!("not a mux daemon"));
+        let err = rx.try_recv().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "not a mux daemon");
+    }
 }
 
 #[cfg(test)]
@@ -2,17 +2,159 @@
 //!
 //! Reads length-prefixed JSON-RPC frames from a [`tokio::net::UnixStream`],
 //! dispatches commands via an [`mpsc`] channel, and writes back responses.
+//! Connections that subscribe to events or pane output additionally receive
+//! `crux:events/notify` and `crux:pane/output` notifications interleaved
+//! with responses.
 
+use std::io::Read;
+
+use anyhow::Context;
+use base64::Engine;
 use serde::Serialize;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
 use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
 
 use crux_protocol::{
-    decode_frame, encode_frame, error_code, method, JsonRpcId, JsonRpcRequest, JsonRpcResponse,
+    decode_frame, encode_frame, error_code, method, CancelCommandParams, CancelCommandResult,
+    EventsNotifyParams, EventsSubscribeParams, JsonRpcId, JsonRpcRequest, JsonRpcResponse,
+    OutputNotifyParams, PaneId, RenderImageParams, RunCommandParams, SendTextParams,
+    SubscribeOutputParams, SubscribeOutputResult, UnsubscribeOutputParams, UnsubscribeOutputResult,
 };
 
-use crate::command::IpcCommand;
+use crate::command::{CommandStarted, IpcCommand};
+use crate::output::{self, OutputDecoder, OutputStream};
+use crate::subscription::{self, EventStream};
+
+/// Idle connections without any subscription are closed after this long.
+const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Default `crux:pane/run-command` timeout when the client gives none.
+const RUN_COMMAND_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for the shell to report completion after sending ^C.
+const RUN_COMMAND_INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+
+/// Largest image file `crux:render/image` reads: 64 MiB, the most a single
+/// image may take in the graphics store.
+const MAX_RENDER_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Per-connection state that outlives a single request.
+#[derive(Default)]
+pub(crate) struct Connection {
+    events: Option<EventStream>,
+    outputs: OutputStreams,
+    commands: RunningCommands,
+}
+
+impl Connection {
+    fn is_subscribed(&self) -> bool {
+        self.events.is_some() || !self.outputs.streams.is_empty()
+    }
+}
+
+/// `crux:pane/run-command` requests of one connection that are waiting for
+/// their command to finish. Each waits in a task of its own, so the
+/// connection keeps serving other requests, and sends its response back
+/// here.
+struct RunningCommands {
+    responses_tx: mpsc::Sender<JsonRpcResponse>,
+    responses: mpsc::Receiver<JsonRpcResponse>,
+    /// The pane of each command and the sender that cancels it, which is
+    /// closed once the command finished.
+    cancels: Vec<(PaneId, oneshot::Sender<()>)>,
+}
+
+impl Default for RunningCommands {
+    fn default() -> Self {
+        let (responses_tx, responses) = mpsc::channel(16);
+        Self {
+            responses_tx,
+            responses,
+            cancels: Vec::new(),
+        }
+    }
+}
+
+impl RunningCommands {
+    fn is_running(&self) -> bool {
+        self.cancels.iter().any(|(_, cancel)| !cancel.is_closed())
+    }
+
+    /// Wait for the response of the next command to finish.
+    async fn next(&mut self) -> JsonRpcResponse {
+        match self.responses.recv().await {
+            Some(response) => response,
+            // Never happens: `responses_tx` is held here.
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Cancel the command running in `pane_id`, or every command if
+    /// `None`. Returns whether there was one.
+    fn cancel(&mut self, pane_id: Option<PaneId>) -> bool {
+        let mut cancelled = false;
+        for (pane, cancel) in std::mem::take(&mut self.cancels) {
+            if pane_id.is_none_or(|id| id == pane) {
+                cancelled |= cancel.send(()).is_ok();
+            } else if !cancel.is_closed() {
+                self.cancels.push((pane, cancel));
+            }
+        }
+        cancelled
+    }
+}
+
+/// Wait for the next subscribed event. Pends forever when unsubscribed.
+async fn next_event(events: &mut Option<EventStream>) -> EventsNotifyParams {
+    if let Some(stream) = events.as_mut() {
+        if let Some(params) = stream.recv().await {
+            return params;
+        }
+        // Producer side went away (app shutting down); stop listening.
+        *events = None;
+    }
+    std::future::pending().await
+}
+
+/// Output subscriptions of one connection.
+#[derive(Default)]
+struct OutputStreams {
+    streams: Vec<OutputStream>,
+    /// Round-robin start index so a chatty pane cannot starve the others.
+    next: usize,
+}
+
+impl OutputStreams {
+    /// Wait for the next output notification across all subscriptions.
+    /// Pends forever when there are none.
+    async fn next(&mut self) -> OutputNotifyParams {
+        loop {
+            let (index, chunk) = std::future::poll_fn(|cx| {
+                let len = self.streams.len();
+                for offset in 0..len {
+                    let index = (self.next + offset) % len;
+                    if let std::task::Poll::Ready(chunk) = self.streams[index].poll_chunk(cx) {
+                        self.next = index + 1;
+                        return std::task::Poll::Ready((index, chunk));
+                    }
+                }
+                std::task::Poll::Pending
+            })
+            .await;
+
+            let closed = chunk.is_none();
+            let params = self.streams[index].notify(chunk);
+            if closed {
+                self.streams.remove(index);
+            }
+            if let Some(params) = params {
+                return params;
+            }
+        }
+    }
+}
 
 /// Handle a single client connection.
 pub async fn handle_client(
@@ -21,15 +163,41 @@ pub async fn handle_client(
 ) -> anyhow::Result<()> {
     let mut buf = vec![0u8; 8192];
     let mut pending = Vec::new();
+    let mut conn = Connection::default();
+    let idle = tokio::time::sleep(CLIENT_IDLE_TIMEOUT);
+    tokio::pin!(idle);
 
     // Maximum pending buffer size (16MB, matching MAX_FRAME_SIZE in protocol).
     const MAX_PENDING_SIZE: usize = 16 * 1024 * 1024;
 
     loop {
-        let n = stream.read(&mut buf).await?;
+        let busy = conn.is_subscribed() || conn.commands.is_running();
+        let n = tokio::select! {
+            read = stream.read(&mut buf) => read?,
+            params = next_event(&mut conn.events) => {
+                write_notification(&mut stream, method::EVENTS_NOTIFY, &params).await?;
+                continue;
+            }
+            params = conn.outputs.next() => {
+                write_notification(&mut stream, method::PANE_OUTPUT_NOTIFY, &params).await?;
+                continue;
+            }
+            response = conn.commands.next() => {
+                let resp_bytes = serde_json::to_vec(&response)?;
+                if let Ok(frame) = encode_frame(&resp_bytes) {
+                    stream.write_all(&frame).await?;
+                }
+                idle.as_mut().reset(Instant::now() + CLIENT_IDLE_TIMEOUT);
+                continue;
+            }
+            _ = &mut idle, if !busy => {
+                anyhow::bail!("idle for {} seconds", CLIENT_IDLE_TIMEOUT.as_secs());
+            }
+        };
         if n == 0 {
             break; // client disconnected
         }
+        idle.as_mut().reset(Instant::now() + CLIENT_IDLE_TIMEOUT);
 
         // Check buffer size limit before extending to prevent unbounded growth.
         if pending.len() + n > MAX_PENDING_SIZE {
@@ -106,7 +274,9 @@ pub async fn handle_client(
                         for item in arr {
                             match serde_json::from_value::<JsonRpcRequest>(item) {
                                 Ok(request) => {
-                                    if let Some(resp) = dispatch_request(request, &cmd_tx).await {
+                                    if let Some(resp) =
+                                        dispatch_request(request, &cmd_tx, &mut conn).await
+                                    {
                                         responses.push(resp);
                                     }
                                     // Notifications (None returned) are not added.
@@ -150,7 +320,7 @@ pub async fn handle_client(
                     };
 
                     // Fix 5: Only send response for non-notification requests.
-                    if let Some(response) = dispatch_request(request, &cmd_tx).await {
+                    if let Some(response) = dispatch_request(request, &cmd_tx, &mut conn).await {
                         let resp_bytes = serde_json::to_vec(&response)?;
                         if let Ok(frame) = encode_frame(&resp_bytes) {
                             stream.write_all(&frame).await?;
@@ -166,12 +336,27 @@ pub async fn handle_client(
     Ok(())
 }
 
+/// Write a server-initiated notification frame.
+async fn write_notification<P: Serialize>(
+    stream: &mut UnixStream,
+    method: &str,
+    params: &P,
+) -> anyhow::Result<()> {
+    let notification = JsonRpcRequest::notification(method, Some(serde_json::to_value(params)?));
+    let bytes = serde_json::to_vec(&notification)?;
+    if let Ok(frame) = encode_frame(&bytes) {
+        stream.write_all(&frame).await?;
+    }
+    Ok(())
+}
+
 /// Route a JSON-RPC request to the appropriate handler.
 ///
 /// Returns `None` for notifications (requests without an id) per JSON-RPC 2.0 spec.
 async fn dispatch_request(
     req: JsonRpcRequest,
     cmd_tx: &mpsc::Sender<IpcCommand>,
+    conn: &mut Connection,
 ) -> Option<JsonRpcResponse> {
     // Fix 4: Validate jsonrpc version.
     if req.jsonrpc != "2.0" {
@@ -223,6 +408,36 @@ async fn dispatch_request(
             })
             .await
         }
+        method::PANE_HISTORY => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::PaneHistory { params, reply }
+            })
+            .await
+        }
+        method::PANE_DUMP_GRID => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::DumpGrid { params, reply }
+            })
+            .await
+        }
+        method::PANE_GET_MODES => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::GetModes { params, reply }
+            })
+            .await
+        }
+        method::PANE_LIST_IMAGES => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::ListImages { params, reply }
+            })
+            .await
+        }
+        method::PANE_GET_IMAGE => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::GetImage { params, reply }
+            })
+            .await
+        }
         method::PANE_LIST => {
             send_command(id.clone(), cmd_tx, |reply| IpcCommand::ListPanes { reply }).await
         }
@@ -253,6 +468,52 @@ async fn dispatch_request(
         method::WINDOW_LIST => {
             send_command(id.clone(), cmd_tx, |reply| IpcCommand::WindowList { reply }).await
         }
+        method::WINDOW_CLOSE => {
+            dispatch_with_params_unit(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::WindowClose { params, reply }
+            })
+            .await
+        }
+        method::WINDOW_FOCUS => {
+            dispatch_with_params_unit(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::WindowFocus { params, reply }
+            })
+            .await
+        }
+        method::TAB_CREATE => {
+            // All parameters are optional.
+            let params = req.params.or_else(|| Some(serde_json::json!({})));
+            dispatch_with_params(id.clone(), params, cmd_tx, |params, reply| {
+                IpcCommand::TabCreate { params, reply }
+            })
+            .await
+        }
+        method::TAB_LIST => {
+            let params = req.params.or_else(|| Some(serde_json::json!({})));
+            dispatch_with_params(id.clone(), params, cmd_tx, |params, reply| {
+                IpcCommand::TabList { params, reply }
+            })
+            .await
+        }
+        method::TAB_MOVE_PANE => {
+            dispatch_with_params_unit(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::TabMovePane { params, reply }
+            })
+            .await
+        }
+        method::LAYOUT_APPLY => {
+            dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::LayoutApply { params, reply }
+            })
+            .await
+        }
+        method::LAYOUT_GET => {
+            let params = req.params.or_else(|| Some(serde_json::json!({})));
+            dispatch_with_params(id.clone(), params, cmd_tx, |params, reply| {
+                IpcCommand::LayoutGet { params, reply }
+            })
+            .await
+        }
         method::SESSION_SAVE => {
             dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
                 IpcCommand::SessionSave { params, reply }
@@ -265,6 +526,29 @@ async fn dispatch_request(
             })
             .await
         }
+        method::RENDER_IMAGE => render_image(id.clone(), req.params, cmd_tx).await,
+        method::MUX_LIST => {
+            send_command(id.clone(), cmd_tx, |reply| IpcCommand::MuxList { reply }).await
+        }
+        method::MUX_ATTACH => {
+            let params = req.params.or_else(|| Some(serde_json::json!({})));
+            dispatch_with_params(id.clone(), params, cmd_tx, |params, reply| {
+                IpcCommand::MuxAttach { params, reply }
+            })
+            .await
+        }
+        method::MUX_RESIZE => {
+            dispatch_with_params_unit(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::MuxResize { params, reply }
+            })
+            .await
+        }
+        method::MUX_KILL => {
+            dispatch_with_params_unit(id.clone(), req.params, cmd_tx, |params, reply| {
+                IpcCommand::MuxKill { params, reply }
+            })
+            .await
+        }
         method::CLIPBOARD_READ => {
             dispatch_with_params(id.clone(), req.params, cmd_tx, |params, reply| {
                 IpcCommand::ClipboardRead { params, reply }
@@ -289,9 +573,29 @@ async fn dispatch_request(
             })
             .await
         }
+        method::CONFIG_GET_STATUS => {
+            send_command(id.clone(), cmd_tx, |reply| IpcCommand::ConfigGetStatus {
+                reply,
+            })
+            .await
+        }
         method::EVENTS_POLL => {
             send_command(id.clone(), cmd_tx, |reply| IpcCommand::EventsPoll { reply }).await
         }
+        method::EVENTS_SUBSCRIBE => subscribe_events(id.clone(), req.params, cmd_tx, conn).await,
+        method::PANE_RUN_COMMAND => {
+            match run_command(id.clone(), req.params, cmd_tx, conn, !is_notification).await {
+                Some(response) => response,
+                // Answered on its own once the command finishes, even
+                // when it came in a batch.
+                None => return None,
+            }
+        }
+        method::PANE_CANCEL_COMMAND => cancel_command(id.clone(), req.params, conn),
+        method::PANE_SUBSCRIBE_OUTPUT => {
+            subscribe_output(id.clone(), req.params, cmd_tx, conn).await
+        }
+        method::PANE_UNSUBSCRIBE_OUTPUT => unsubscribe_output(id.clone(), req.params, conn),
         _ => JsonRpcResponse::error(
             id,
             error_code::METHOD_NOT_FOUND,
@@ -328,6 +632,292 @@ where
     send_command(id, cmd_tx, |reply| make_cmd(params, reply)).await
 }
 
+/// Register this connection for `crux:events/notify` pushes.
+///
+/// The queue is only attached to the connection once the GPUI side has
+/// accepted the subscription, so a failed subscribe leaves any previous
+/// subscription in place.
+async fn subscribe_events(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    cmd_tx: &mpsc::Sender<IpcCommand>,
+    conn: &mut Connection,
+) -> JsonRpcResponse {
+    let params: EventsSubscribeParams = match parse_params(id.clone(), params) {
+        Ok(p) => p,
+        Err(resp) => return *resp,
+    };
+    let (subscription, events) = subscription::event_channel(params.events);
+    let response = send_command(id, cmd_tx, |reply| IpcCommand::EventsSubscribe {
+        subscription,
+        reply,
+    })
+    .await;
+    if response.error.is_none() {
+        conn.events = Some(events);
+    }
+    response
+}
+
+/// Submit a command and wait for its OSC 133 completion marker.
+///
+/// The GPUI thread only submits the command; waiting happens in a task of
+/// its own, so a slow command blocks neither the GPUI thread nor other
+/// requests on this connection. Returns the response if the command could
+/// not be started; otherwise the task sends it to `conn` when the command
+/// finishes, unless `reply` is false.
+async fn run_command(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    cmd_tx: &mpsc::Sender<IpcCommand>,
+    conn: &mut Connection,
+    reply: bool,
+) -> Option<JsonRpcResponse> {
+    let params: RunCommandParams = match parse_params(id.clone(), params) {
+        Ok(p) => p,
+        Err(resp) => return Some(*resp),
+    };
+    if params.command.trim().is_empty() || params.command.contains(['\n', '\r']) {
+        return Some(JsonRpcResponse::error(
+            id,
+            error_code::INVALID_PARAMS,
+            "command must be a single non-empty line",
+        ));
+    }
+    let timeout = params
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(RUN_COMMAND_DEFAULT_TIMEOUT);
+    let interrupt = params.interrupt_on_timeout;
+
+    let (tx, rx) = oneshot::channel();
+    if cmd_tx
+        .send(IpcCommand::RunCommand { params, reply: tx })
+        .await
+        .is_err()
+    {
+        return Some(JsonRpcResponse::error(
+            id,
+            error_code::INTERNAL_ERROR,
+            "server shutting down",
+        ));
+    }
+    let started = match rx.await {
+        Ok(Ok(started)) => started,
+        Ok(Err(e)) => {
+            return Some(JsonRpcResponse::error(
+                id,
+                error_code::INTERNAL_ERROR,
+                e.to_string(),
+            ))
+        }
+        Err(_) => {
+            return Some(JsonRpcResponse::error(
+                id,
+                error_code::INTERNAL_ERROR,
+                "handler dropped",
+            ))
+        }
+    };
+
+    let (cancel_tx, cancel) = oneshot::channel();
+    let cancels = &mut conn.commands.cancels;
+    cancels.retain(|(_, cancel)| !cancel.is_closed());
+    cancels.push((started.pane_id, cancel_tx));
+    let responses = conn.commands.responses_tx.clone();
+    let cmd_tx = cmd_tx.clone();
+    tokio::spawn(async move {
+        let response = tokio::select! {
+            response = wait_for_command(id, started, timeout, interrupt, &cmd_tx, cancel) => response,
+            // The client went away; nobody is waiting for the result.
+            _ = responses.closed() => return,
+        };
+        if reply {
+            let _ = responses.send(response).await;
+        }
+    });
+    None
+}
+
+/// Why a running command is interrupted.
+enum Interrupt {
+    Timeout,
+    Cancel,
+}
+
+/// Wait for `started` to finish. On timeout the command is interrupted
+/// with ^C (unless `interrupt` is false) and the partial result returned
+/// with `timed_out` set; when `cancel` fires, likewise with `cancelled`.
+async fn wait_for_command(
+    id: JsonRpcId,
+    started: CommandStarted,
+    timeout: Duration,
+    interrupt: bool,
+    cmd_tx: &mpsc::Sender<IpcCommand>,
+    mut cancel: oneshot::Receiver<()>,
+) -> JsonRpcResponse {
+    let mut done = started.done;
+    let reason = tokio::select! {
+        result = &mut done => return command_response(id, started.pane_id, result.ok()),
+        _ = tokio::time::sleep(timeout) => Interrupt::Timeout,
+        Ok(()) = &mut cancel => Interrupt::Cancel,
+    };
+    if matches!(reason, Interrupt::Timeout) && !interrupt {
+        return JsonRpcResponse::error(
+            id,
+            error_code::COMMAND_TIMEOUT,
+            format!("command timed out after {} ms", timeout.as_millis()),
+        );
+    }
+
+    let (tx, _rx) = oneshot::channel();
+    let _ = cmd_tx
+        .send(IpcCommand::SendText {
+            params: SendTextParams {
+                pane_id: Some(started.pane_id),
+                text: "\x03".to_string(),
+                bracketed_paste: false,
+            },
+            reply: tx,
+        })
+        .await;
+    let result = match tokio::time::timeout(RUN_COMMAND_INTERRUPT_GRACE, &mut done).await {
+        Ok(result) => result.ok().map(|mut result| {
+            match reason {
+                Interrupt::Timeout => result.timed_out = true,
+                Interrupt::Cancel => result.cancelled = true,
+            }
+            result
+        }),
+        Err(_) => {
+            return match reason {
+                Interrupt::Timeout => JsonRpcResponse::error(
+                    id,
+                    error_code::COMMAND_TIMEOUT,
+                    format!(
+                        "command timed out after {} ms and did not stop after ^C",
+                        timeout.as_millis()
+                    ),
+                ),
+                Interrupt::Cancel => JsonRpcResponse::error(
+                    id,
+                    error_code::COMMAND_CANCELLED,
+                    "command was cancelled and did not stop after ^C",
+                ),
+            };
+        }
+    };
+    command_response(id, started.pane_id, result)
+}
+
+/// The response to a finished command; `None` if its pane went away first.
+fn command_response(
+    id: JsonRpcId,
+    pane_id: PaneId,
+    result: Option<crux_protocol::RunCommandResult>,
+) -> JsonRpcResponse {
+    let Some(result) = result else {
+        return JsonRpcResponse::error(
+            id,
+            error_code::INTERNAL_ERROR,
+            format!("pane {} closed before the command finished", pane_id),
+        );
+    };
+    match serde_json::to_value(result) {
+        Ok(v) => JsonRpcResponse::success(id, v),
+        Err(e) => JsonRpcResponse::error(id, error_code::INTERNAL_ERROR, e.to_string()),
+    }
+}
+
+/// Interrupt commands this connection started with
+/// `crux:pane/run-command`. Their own requests are answered with the
+/// output so far once they stop.
+fn cancel_command(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    conn: &mut Connection,
+) -> JsonRpcResponse {
+    let params: CancelCommandParams =
+        match parse_params(id.clone(), params.or_else(|| Some(serde_json::json!({})))) {
+            Ok(p) => p,
+            Err(resp) => return *resp,
+        };
+    let result = CancelCommandResult {
+        cancelled: conn.commands.cancel(params.pane_id),
+    };
+    match serde_json::to_value(result) {
+        Ok(v) => JsonRpcResponse::success(id, v),
+        Err(e) => JsonRpcResponse::error(id, error_code::INTERNAL_ERROR, e.to_string()),
+    }
+}
+
+/// Start streaming a pane's PTY output to this connection.
+///
+/// The filter is compiled here so a bad pattern is rejected before the GPUI
+/// side is involved. As with events, the stream is only attached once the
+/// app has registered the subscription with the pane.
+async fn subscribe_output(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    cmd_tx: &mpsc::Sender<IpcCommand>,
+    conn: &mut Connection,
+) -> JsonRpcResponse {
+    let params: SubscribeOutputParams = match parse_params(id.clone(), params) {
+        Ok(p) => p,
+        Err(resp) => return *resp,
+    };
+    let decoder = match OutputDecoder::new(params.format, params.filter.as_deref()) {
+        Ok(d) => d,
+        Err(e) => {
+            return JsonRpcResponse::error(
+                id,
+                error_code::INVALID_PARAMS,
+                format!("invalid filter: {e}"),
+            )
+        }
+    };
+    let (subscription, mut stream) = output::output_channel(params.pane_id, decoder);
+    let response = send_command(id, cmd_tx, |reply| IpcCommand::SubscribeOutput {
+        subscription,
+        reply,
+    })
+    .await;
+    let accepted = response
+        .result
+        .clone()
+        .and_then(|v| serde_json::from_value::<SubscribeOutputResult>(v).ok());
+    if let Some(result) = accepted {
+        stream.set_pane_id(result.pane_id);
+        conn.outputs.streams.push(stream);
+    }
+    response
+}
+
+/// Stop an output subscription on this connection.
+///
+/// Dropping the stream closes its queue; the PTY side notices on its next
+/// read and unregisters itself.
+fn unsubscribe_output(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    conn: &mut Connection,
+) -> JsonRpcResponse {
+    let params: UnsubscribeOutputParams = match parse_params(id.clone(), params) {
+        Ok(p) => p,
+        Err(resp) => return *resp,
+    };
+    let streams = &mut conn.outputs.streams;
+    let before = streams.len();
+    streams.retain(|s| s.id() != params.subscription_id);
+    let result = UnsubscribeOutputResult {
+        unsubscribed: streams.len() != before,
+    };
+    match serde_json::to_value(result) {
+        Ok(v) => JsonRpcResponse::success(id, v),
+        Err(e) => JsonRpcResponse::error(id, error_code::INTERNAL_ERROR, e.to_string()),
+    }
+}
+
 /// Parse params, send a command that returns `()` (mapped to `{"success": true}`).
 async fn dispatch_with_params_unit<P>(
     id: JsonRpcId,
@@ -345,6 +935,65 @@ where
     send_command_unit(id, cmd_tx, |reply| make_cmd(params, reply)).await
 }
 
+/// Load the file of a `crux:render/image` request on this connection's
+/// task, so that neither the GUI nor the headless loop blocks on reading
+/// it, then hand it to the pane.
+async fn render_image(
+    id: JsonRpcId,
+    params: Option<serde_json::Value>,
+    cmd_tx: &mpsc::Sender<IpcCommand>,
+) -> JsonRpcResponse {
+    let params: RenderImageParams = match parse_params(id.clone(), params) {
+        Ok(p) => p,
+        Err(resp) => return *resp,
+    };
+    let load = {
+        let params = params.clone();
+        tokio::task::spawn_blocking(move || render_image_file(&params))
+    };
+    let file = match load.await {
+        Ok(Ok(file)) => file,
+        Ok(Err(e)) => {
+            return JsonRpcResponse::error(id, error_code::INVALID_PARAMS, format!("{e:#}"))
+        }
+        Err(e) => return JsonRpcResponse::error(id, error_code::INTERNAL_ERROR, e.to_string()),
+    };
+    send_command(id, cmd_tx, |reply| IpcCommand::RenderImage {
+        params,
+        file,
+        reply,
+    })
+    .await
+}
+
+/// The file `crux:render/image` shows, given inline or by path. Only
+/// regular files are read, and no more than [`MAX_RENDER_IMAGE_BYTES`] of
+/// them, so a device or FIFO cannot block or exhaust memory.
+fn render_image_file(params: &RenderImageParams) -> anyhow::Result<Vec<u8>> {
+    let data = match (&params.data, &params.path) {
+        (Some(data), None) => base64::engine::general_purpose::STANDARD.decode(data)?,
+        (None, Some(path)) => {
+            let file = std::fs::File::open(path).with_context(|| format!("cannot read {path}"))?;
+            if !file.metadata()?.is_file() {
+                anyhow::bail!("{path} is not a regular file");
+            }
+            let mut data = Vec::new();
+            file.take(MAX_RENDER_IMAGE_BYTES as u64 + 1)
+                .read_to_end(&mut data)
+                .with_context(|| format!("cannot read {path}"))?;
+            data
+        }
+        _ => anyhow::bail!("exactly one of data and path is required"),
+    };
+    if data.len() > MAX_RENDER_IMAGE_BYTES {
+        anyhow::bail!(
+            "image file is too large: over {} bytes",
+            MAX_RENDER_IMAGE_BYTES
+        );
+    }
+    Ok(data)
+}
+
 /// Extract and deserialise `params` from a JSON-RPC request value.
 fn parse_params<P: serde::de::DeserializeOwned>(
     id: JsonRpcId,
@@ -399,11 +1048,30 @@ async fn send_command_unit(
 
 #[cfg(test)]
 mod tests {
-    use crux_protocol::{error_code, method, HandshakeResult, JsonRpcId, JsonRpcRequest};
+    use crux_protocol::{
+        error_code, method, EventsSubscribeResult, HandshakeResult, JsonRpcId, JsonRpcRequest,
+        PaneEvent, PaneEventType, PaneId, RunCommandResult, SubscribeOutputResult,
+    };
     use serde_json::json;
     use tokio::sync::mpsc;
+    use tokio::sync::oneshot;
 
-    use super::dispatch_request;
+    use super::{dispatch_request, Connection};
+    use crate::command::{CommandStarted, IpcCommand};
+
+    fn run_command_result(pane_id: PaneId, output: &str) -> RunCommandResult {
+        RunCommandResult {
+            pane_id,
+            output: output.to_string(),
+            exit_code: Some(0),
+            started_at_ms: 1,
+            finished_at_ms: 2,
+            cwd: None,
+            timed_out: false,
+            cancelled: false,
+            truncated: false,
+        }
+    }
 
     #[tokio::test]
     async fn test_dispatch_unknown_method_returns_error() {
@@ -416,7 +1084,9 @@ mod tests {
             id: Some(JsonRpcId::Number(1)),
         };
 
-        let response = dispatch_request(request, &cmd_tx).await.unwrap();
+        let response = dispatch_request(request, &cmd_tx, &mut Connection::default())
+            .await
+            .unwrap();
 
         let err = response
             .error
@@ -441,7 +1111,9 @@ mod tests {
             id: Some(JsonRpcId::Number(2)),
         };
 
-        let response = dispatch_request(request, &cmd_tx).await.unwrap();
+        let response = dispatch_request(request, &cmd_tx, &mut Connection::default())
+            .await
+            .unwrap();
 
         let err = response
             .error
@@ -468,14 +1140,12 @@ mod tests {
 
         // Spawn a consumer to reply to the command so dispatch doesn't hang.
         tokio::spawn(async move {
-            if let Some(cmd) = cmd_rx.recv().await {
-                if let crate::command::IpcCommand::ListPanes { reply } = cmd {
-                    let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes: vec![] }));
-                }
+            if let Some(crate::command::IpcCommand::ListPanes { reply }) = cmd_rx.recv().await {
+                let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes: vec![] }));
             }
         });
 
-        let response = dispatch_request(request, &cmd_tx).await;
+        let response = dispatch_request(request, &cmd_tx, &mut Connection::default()).await;
 
         // Notifications should return None (no response)
         assert!(response.is_none());
@@ -498,19 +1168,19 @@ mod tests {
         };
 
         // Spawn a task to handle the command
-        let response_handle = tokio::spawn(async move { dispatch_request(request, &cmd_tx).await });
+        let response_handle = tokio::spawn(async move {
+            dispatch_request(request, &cmd_tx, &mut Connection::default()).await
+        });
 
         // Receive the command and reply
-        if let Some(cmd) = cmd_rx.recv().await {
-            if let crate::command::IpcCommand::Handshake { reply, .. } = cmd {
-                let result = HandshakeResult {
-                    server_name: "test".to_string(),
-                    server_version: "1.0".to_string(),
-                    protocol_version: "1.0".to_string(),
-                    supported_capabilities: vec![],
-                };
-                let _ = reply.send(Ok(result));
-            }
+        if let Some(crate::command::IpcCommand::Handshake { reply, .. }) = cmd_rx.recv().await {
+            let result = HandshakeResult {
+                server_name: "test".to_string(),
+                server_version: "1.0".to_string(),
+                protocol_version: "1.0".to_string(),
+                supported_capabilities: vec![],
+            };
+            let _ = reply.send(Ok(result));
         }
 
         let response = response_handle.await.unwrap().unwrap();
@@ -532,7 +1202,9 @@ mod tests {
             id: Some(JsonRpcId::Number(4)),
         };
 
-        let response = dispatch_request(request, &cmd_tx).await.unwrap();
+        let response = dispatch_request(request, &cmd_tx, &mut Connection::default())
+            .await
+            .unwrap();
 
         let err = response
             .error
@@ -544,4 +1216,423 @@ mod tests {
         // No command should be sent
         assert!(cmd_rx.try_recv().is_err());
     }
+
+    #[tokio::test]
+    async fn test_dispatch_events_subscribe_attaches_stream() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::EVENTS_SUBSCRIBE.to_string(),
+            params: Some(json!({ "events": ["pane_closed"] })),
+            id: Some(JsonRpcId::Number(5)),
+        };
+
+        let response_handle = tokio::spawn(async move {
+            let mut conn = Connection::default();
+            let response = dispatch_request(request, &cmd_tx, &mut conn).await;
+            (response, conn)
+        });
+
+        let Some(crate::command::IpcCommand::EventsSubscribe {
+            subscription,
+            reply,
+        }) = cmd_rx.recv().await
+        else {
+            panic!("expected EventsSubscribe command");
+        };
+        assert_eq!(subscription.events(), &[PaneEventType::PaneClosed]);
+        let _ = reply.send(Ok(EventsSubscribeResult {
+            events: subscription.events().to_vec(),
+        }));
+
+        let (response, mut conn) = response_handle.await.unwrap();
+        let result = response.unwrap().result.expect("should have result");
+        assert_eq!(result["events"], json!(["pane_closed"]));
+
+        // Filtered-out events are skipped; matching ones reach the connection.
+        subscription.offer(&PaneEvent::Created { pane_id: PaneId(1) });
+        subscription.offer(&PaneEvent::Closed { pane_id: PaneId(1) });
+        let params = super::next_event(&mut conn.events).await;
+        assert!(matches!(params.event, PaneEvent::Closed { .. }));
+        assert_eq!(params.dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_events_subscribe_failure_keeps_connection_unsubscribed() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::EVENTS_SUBSCRIBE.to_string(),
+            params: Some(json!({})),
+            id: Some(JsonRpcId::Number(6)),
+        };
+
+        tokio::spawn(async move {
+            if let Some(crate::command::IpcCommand::EventsSubscribe { reply, .. }) =
+                cmd_rx.recv().await
+            {
+                let _ = reply.send(Err(anyhow::anyhow!("not accepting subscribers")));
+            }
+        });
+
+        let mut conn = Connection::default();
+        let response = dispatch_request(request, &cmd_tx, &mut conn).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_code::INTERNAL_ERROR);
+        assert!(conn.events.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_subscribe_output_streams_and_unsubscribes() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_SUBSCRIBE_OUTPUT.to_string(),
+            params: Some(json!({ "format": "stripped" })),
+            id: Some(JsonRpcId::Number(7)),
+        };
+
+        let response_handle = tokio::spawn(async move {
+            let mut conn = Connection::default();
+            let response = dispatch_request(request, &cmd_tx, &mut conn).await;
+            (response, conn)
+        });
+
+        let Some(crate::command::IpcCommand::SubscribeOutput {
+            subscription,
+            reply,
+        }) = cmd_rx.recv().await
+        else {
+            panic!("expected SubscribeOutput command");
+        };
+        assert_eq!(subscription.pane_id(), None);
+        let subscription_id = subscription.id();
+        let _ = reply.send(Ok(SubscribeOutputResult {
+            subscription_id,
+            pane_id: PaneId(3),
+        }));
+
+        let (response, mut conn) = response_handle.await.unwrap();
+        let result = response.unwrap().result.expect("should have result");
+        assert_eq!(result["pane_id"], 3);
+
+        assert!(subscription.offer(b"\x1b[32mok\x1b[0m\n"));
+        let params = conn.outputs.next().await;
+        assert_eq!(params.subscription_id, subscription_id);
+        assert_eq!(params.pane_id, PaneId(3));
+        assert_eq!(params.seq, 0);
+        assert_eq!(params.data, "ok\n");
+
+        let (cmd_tx, _cmd_rx) = mpsc::channel(1);
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_UNSUBSCRIBE_OUTPUT.to_string(),
+            params: Some(json!({ "subscription_id": subscription_id })),
+            id: Some(JsonRpcId::Number(8)),
+        };
+        let response = dispatch_request(request, &cmd_tx, &mut conn).await.unwrap();
+        assert_eq!(response.result.unwrap()["unsubscribed"], true);
+        assert!(!conn.is_subscribed());
+        // The PTY side learns about it on the next read.
+        assert!(!subscription.offer(b"late"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_subscribe_output_rejects_bad_filter() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_SUBSCRIBE_OUTPUT.to_string(),
+            params: Some(json!({ "filter": "(" })),
+            id: Some(JsonRpcId::Number(9)),
+        };
+
+        let mut conn = Connection::default();
+        let response = dispatch_request(request, &cmd_tx, &mut conn).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_code::INVALID_PARAMS);
+        assert!(cmd_rx.try_recv().is_err(), "no command should be sent");
+        assert!(!conn.is_subscribed());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_run_command_waits_for_completion() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            if let Some(IpcCommand::RunCommand { params, reply }) = cmd_rx.recv().await {
+                assert_eq!(params.command, "echo hi");
+                let (done_tx, done) = oneshot::channel();
+                let _ = reply.send(Ok(CommandStarted {
+                    pane_id: PaneId(2),
+                    done,
+                }));
+                let _ = done_tx.send(run_command_result(PaneId(2), "hi\n"));
+            }
+        });
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({ "command": "echo hi" })),
+            id: Some(JsonRpcId::Number(10)),
+        };
+        let mut conn = Connection::default();
+        assert!(dispatch_request(request, &cmd_tx, &mut conn)
+            .await
+            .is_none());
+        let response = conn.commands.next().await;
+        assert_eq!(response.id, JsonRpcId::Number(10));
+        let result = response.result.expect("should have result");
+        assert_eq!(result["output"], "hi\n");
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["timed_out"], false);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_run_command_interrupts_on_timeout() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            let Some(IpcCommand::RunCommand { reply, .. }) = cmd_rx.recv().await else {
+                return;
+            };
+            let (done_tx, done) = oneshot::channel();
+            let _ = reply.send(Ok(CommandStarted {
+                pane_id: PaneId(4),
+                done,
+            }));
+            // The shell finishes only once ^C arrives.
+            if let Some(IpcCommand::SendText { params, reply }) = cmd_rx.recv().await {
+                assert_eq!(params.pane_id, Some(PaneId(4)));
+                assert_eq!(params.text, "\x03");
+                let _ = reply.send(Ok(crux_protocol::SendTextResult { bytes_written: 1 }));
+                let mut result = run_command_result(PaneId(4), "partial");
+                result.exit_code = Some(130);
+                let _ = done_tx.send(result);
+            }
+        });
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({ "command": "sleep 100", "timeout_ms": 20 })),
+            id: Some(JsonRpcId::Number(11)),
+        };
+        let mut conn = Connection::default();
+        assert!(dispatch_request(request, &cmd_tx, &mut conn)
+            .await
+            .is_none());
+        let response = conn.commands.next().await;
+        let result = response.result.expect("should have result");
+        assert_eq!(result["output"], "partial");
+        assert_eq!(result["exit_code"], 130);
+        assert_eq!(result["timed_out"], true);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_run_command_timeout_without_interrupt() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            if let Some(IpcCommand::RunCommand { reply, .. }) = cmd_rx.recv().await {
+                let (done_tx, done) = oneshot::channel();
+                let _ = reply.send(Ok(CommandStarted {
+                    pane_id: PaneId(1),
+                    done,
+                }));
+                // Keep the command "running" past the timeout.
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                drop(done_tx);
+            }
+        });
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({
+                "command": "sleep 100",
+                "timeout_ms": 20,
+                "interrupt_on_timeout": false,
+            })),
+            id: Some(JsonRpcId::Number(12)),
+        };
+        let mut conn = Connection::default();
+        assert!(dispatch_request(request, &cmd_tx, &mut conn)
+            .await
+            .is_none());
+        let response = conn.commands.next().await;
+        assert_eq!(response.error.unwrap().code, error_code::COMMAND_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_run_command_leaves_connection_free() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+        let (finish_tx, finish) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            if let Some(IpcCommand::RunCommand { reply, .. }) = cmd_rx.recv().await {
+                let (done_tx, done) = oneshot::channel();
+                let _ = reply.send(Ok(CommandStarted {
+                    pane_id: PaneId(3),
+                    done,
+                }));
+                if let Some(IpcCommand::ListPanes { reply }) = cmd_rx.recv().await {
+                    let _ = reply.send(Ok(crux_protocol::ListPanesResult { panes: vec![] }));
+                }
+                let _ = finish.await;
+                let _ = done_tx.send(run_command_result(PaneId(3), "done"));
+            }
+        });
+
+        let mut conn = Connection::default();
+        let run = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({ "command": "sleep 1" })),
+            id: Some(JsonRpcId::Number(20)),
+        };
+        assert!(dispatch_request(run, &cmd_tx, &mut conn).await.is_none());
+        assert!(conn.commands.is_running());
+
+        // Other requests are answered while the command runs.
+        let list = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_LIST.to_string(),
+            params: None,
+            id: Some(JsonRpcId::Number(21)),
+        };
+        let response = dispatch_request(list, &cmd_tx, &mut conn).await.unwrap();
+        assert!(response.result.is_some());
+
+        finish_tx.send(()).unwrap();
+        let response = conn.commands.next().await;
+        assert_eq!(response.id, JsonRpcId::Number(20));
+        assert_eq!(response.result.unwrap()["output"], "done");
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_cancel_command_interrupts_it() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            let Some(IpcCommand::RunCommand { reply, .. }) = cmd_rx.recv().await else {
+                return;
+            };
+            let (done_tx, done) = oneshot::channel();
+            let _ = reply.send(Ok(CommandStarted {
+                pane_id: PaneId(5),
+                done,
+            }));
+            if let Some(IpcCommand::SendText { params, reply }) = cmd_rx.recv().await {
+                assert_eq!(params.pane_id, Some(PaneId(5)));
+                assert_eq!(params.text, "\x03");
+                let _ = reply.send(Ok(crux_protocol::SendTextResult { bytes_written: 1 }));
+                let mut result = run_command_result(PaneId(5), "partial");
+                result.exit_code = Some(130);
+                let _ = done_tx.send(result);
+            }
+        });
+
+        let cancel = |pane_id: u64, id: u64| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_CANCEL_COMMAND.to_string(),
+            params: Some(json!({ "pane_id": pane_id })),
+            id: Some(JsonRpcId::Number(id)),
+        };
+        let mut conn = Connection::default();
+        let response = dispatch_request(cancel(5, 30), &cmd_tx, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(response.result.unwrap()["cancelled"], false);
+
+        let run = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({ "command": "sleep 100" })),
+            id: Some(JsonRpcId::Number(31)),
+        };
+        assert!(dispatch_request(run, &cmd_tx, &mut conn).await.is_none());
+        let response = dispatch_request(cancel(6, 32), &cmd_tx, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(response.result.unwrap()["cancelled"], false);
+        let response = dispatch_request(cancel(5, 33), &cmd_tx, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(response.result.unwrap()["cancelled"], true);
+
+        let response = conn.commands.next().await;
+        assert_eq!(response.id, JsonRpcId::Number(31));
+        let result = response.result.expect("should have result");
+        assert_eq!(result["output"], "partial");
+        assert_eq!(result["cancelled"], true);
+        assert_eq!(result["timed_out"], false);
+        assert!(!conn.commands.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_run_command_rejects_multiline() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::PANE_RUN_COMMAND.to_string(),
+            params: Some(json!({ "command": "echo a\necho b" })),
+            id: Some(JsonRpcId::Number(13)),
+        };
+        let response = dispatch_request(request, &cmd_tx, &mut Connection::default())
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_code::INVALID_PARAMS);
+        assert!(cmd_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_render_image_reads_regular_files_only() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+        let render = |params: serde_json::Value| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method::RENDER_IMAGE.to_string(),
+            params: Some(params),
+            id: Some(JsonRpcId::Number(14)),
+        };
+
+        for path in ["/dev/zero", "/"] {
+            let response = dispatch_request(
+                render(json!({ "path": path })),
+                &cmd_tx,
+                &mut Connection::default(),
+            )
+            .await
+            .unwrap();
+            let err = response.error.unwrap();
+            assert_eq!(err.code, error_code::INVALID_PARAMS);
+            assert!(
+                err.message.contains("not a regular file"),
+                "{}",
+                err.message
+            );
+        }
+        assert!(cmd_rx.try_recv().is_err());
+
+        let path = std::env::temp_dir().join(format!("crux-render-{}", std::process::id()));
+        std::fs::write(&path, b"image bytes").unwrap();
+        tokio::spawn(async move {
+            if let Some(IpcCommand::RenderImage { file, reply, .. }) = cmd_rx.recv().await {
+                assert_eq!(file, b"image bytes");
+                let _ = reply.send(Err(anyhow::anyhow!("not an image")));
+            }
+        });
+        let response = dispatch_request(
+            render(json!({ "path": path })),
+            &cmd_tx,
+            &mut Connection::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.error.unwrap().message, "not an image");
+        std::fs::remove_file(&path).unwrap();
+    }
 }
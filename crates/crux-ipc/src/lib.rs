@@ -18,12 +18,16 @@ pub mod command;
 #[cfg(test)]
 mod command_tests;
 pub mod handler;
+pub mod output;
 pub mod server;
 pub mod socket;
+pub mod subscription;
 
 pub use client::{IpcClient, IpcTransport};
-pub use command::IpcCommand;
-pub use socket::{discover_socket, socket_path};
+pub use command::{CommandStarted, IpcCommand};
+pub use output::OutputSubscription;
+pub use socket::{discover_socket, mux_dir, mux_socket_path, socket_path};
+pub use subscription::EventSubscription;
 pub use tokio_util::sync::CancellationToken;
 
 use std::path::PathBuf;
@@ -0,0 +1,502 @@
+//! Streaming of raw PTY output to `crux:pane/subscribe-output` clients.
+//!
+//! The PTY reader thread pushes chunks into an [`OutputSubscription`] without
+//! blocking: when the subscriber's queue is full the chunk is discarded and
+//! its size is carried forward on the next chunk that fits. The connection
+//! handler owns the matching [`OutputStream`], which turns chunks into
+//! ordered `crux:pane/output` notifications (UTF-8 reassembly, optional
+//! escape stripping and server-side line filtering).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use regex::Regex;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crux_protocol::{OutputFormat, OutputNotifyParams, PaneId};
+
+/// Maximum number of undelivered PTY reads buffered per subscription.
+pub const OUTPUT_QUEUE_CAPACITY: usize = 1024;
+
+/// Lines longer than this are evaluated against the filter and flushed
+/// without waiting for a newline.
+const MAX_FILTER_LINE: usize = 64 * 1024;
+
+/// Subscription ids are unique across all connections.
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A PTY read plus the number of bytes lost immediately before it.
+pub(crate) struct OutputChunk {
+    bytes: Vec<u8>,
+    dropped: u64,
+}
+
+/// Producer half of an output subscription, driven by the PTY reader thread.
+pub struct OutputSubscription {
+    id: u64,
+    pane_id: Option<PaneId>,
+    tx: mpsc::Sender<OutputChunk>,
+    dropped: AtomicU64,
+}
+
+/// Consumer half of an output subscription, owned by the connection handler.
+pub(crate) struct OutputStream {
+    id: u64,
+    pane_id: PaneId,
+    rx: mpsc::Receiver<OutputChunk>,
+    decoder: OutputDecoder,
+    seq: u64,
+}
+
+/// Create a linked subscription/stream pair for the requested pane.
+pub(crate) fn output_channel(
+    pane_id: Option<PaneId>,
+    decoder: OutputDecoder,
+) -> (OutputSubscription, OutputStream) {
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = mpsc::channel(OUTPUT_QUEUE_CAPACITY);
+    (
+        OutputSubscription {
+            id,
+            pane_id,
+            tx,
+            dropped: AtomicU64::new(0),
+        },
+        OutputStream {
+            id,
+            // Replaced with the resolved pane once the app accepts.
+            pane_id: pane_id.unwrap_or(PaneId(0)),
+            rx,
+            decoder,
+            seq: 0,
+        },
+    )
+}
+
+impl OutputSubscription {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Pane requested by the client (`None` = active pane).
+    pub fn pane_id(&self) -> Option<PaneId> {
+        self.pane_id
+    }
+
+    /// Queue a chunk of PTY output for delivery.
+    ///
+    /// Never blocks. Returns `false` once the subscriber has gone away
+    /// (disconnected or unsubscribed) so the caller can discard it.
+    pub fn offer(&self, bytes: &[u8]) -> bool {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        let chunk = OutputChunk {
+            bytes: bytes.to_vec(),
+            dropped,
+        };
+        match self.tx.try_send(chunk) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped
+                    .fetch_add(dropped + bytes.len() as u64, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+impl OutputStream {
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn set_pane_id(&mut self, pane_id: PaneId) {
+        self.pane_id = pane_id;
+    }
+
+    /// Poll for the next chunk. `Ready(None)` means the pane is gone.
+    pub(crate) fn poll_chunk(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<OutputChunk>> {
+        self.rx.poll_recv(cx)
+    }
+
+    /// Turn a received chunk (or end of stream) into a notification.
+    ///
+    /// Returns `None` when the filter swallowed everything and there is no
+    /// gap or closure to report.
+    pub(crate) fn notify(&mut self, chunk: Option<OutputChunk>) -> Option<OutputNotifyParams> {
+        let (data, dropped, closed) = match chunk {
+            Some(chunk) => {
+                if chunk.dropped > 0 {
+                    // Whatever escape or partial line we were inside is lost.
+                    self.decoder.reset();
+                }
+                (self.decoder.push(&chunk.bytes), chunk.dropped, false)
+            }
+            None => (self.decoder.finish(), 0, true),
+        };
+        if data.is_empty() && dropped == 0 && !closed {
+            return None;
+        }
+        let params = OutputNotifyParams {
+            subscription_id: self.id,
+            pane_id: self.pane_id,
+            seq: self.seq,
+            data,
+            dropped,
+            closed,
+        };
+        self.seq += 1;
+        Some(params)
+    }
+}
+
+/// Convert a complete byte capture into text in the requested format.
+pub fn decode_output(bytes: &[u8], format: OutputFormat) -> String {
+    let mut decoder = OutputDecoder {
+        format,
+        utf8_tail: Vec::new(),
+        stripper: AnsiStripper::default(),
+        filter: None,
+    };
+    let mut text = decoder.push(bytes);
+    text.push_str(&decoder.finish());
+    text
+}
+
+/// Converts PTY bytes into notification text for one subscription.
+pub(crate) struct OutputDecoder {
+    format: OutputFormat,
+    /// Incomplete UTF-8 sequence left over from the previous chunk.
+    utf8_tail: Vec<u8>,
+    stripper: AnsiStripper,
+    filter: Option<LineFilter>,
+}
+
+struct LineFilter {
+    regex: Regex,
+    /// Pending line in the subscriber's format.
+    line: String,
+    /// Pending line with escapes removed, used for matching.
+    plain: String,
+}
+
+impl OutputDecoder {
+    /// Build a decoder, compiling `filter` if given.
+    pub(crate) fn new(format: OutputFormat, filter: Option<&str>) -> Result<Self, regex::Error> {
+        let filter = filter
+            .map(|pattern| {
+                Regex::new(pattern).map(|regex| LineFilter {
+                    regex,
+                    line: String::new(),
+                    plain: String::new(),
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            format,
+            utf8_tail: Vec::new(),
+            stripper: AnsiStripper::default(),
+            filter,
+        })
+    }
+
+    /// Process a chunk and return the text to forward.
+    fn push(&mut self, bytes: &[u8]) -> String {
+        let text = decode_utf8(&mut self.utf8_tail, bytes);
+        let keep_escapes = self.format == OutputFormat::Raw;
+        let mut out = String::new();
+
+        for ch in text.chars() {
+            let visible = self.stripper.feed(ch);
+            let Some(filter) = self.filter.as_mut() else {
+                if keep_escapes || visible {
+                    out.push(ch);
+                }
+                continue;
+            };
+            if keep_escapes || visible {
+                filter.line.push(ch);
+            }
+            if !visible {
+                continue;
+            }
+            if ch == '\n' {
+                filter.flush_into(&mut out);
+            } else {
+                filter.plain.push(ch);
+                if filter.plain.len() >= MAX_FILTER_LINE {
+                    filter.flush_into(&mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// Flush state at end of stream: a trailing unterminated line is still
+    /// checked against the filter.
+    fn finish(&mut self) -> String {
+        let had_tail = !self.utf8_tail.is_empty();
+        self.utf8_tail.clear();
+        let mut out = String::new();
+        match self.filter.as_mut() {
+            Some(filter) => filter.flush_into(&mut out),
+            None if had_tail => out.push(char::REPLACEMENT_CHARACTER),
+            None => {}
+        }
+        out
+    }
+
+    /// Forget partial state after a gap in the byte stream.
+    fn reset(&mut self) {
+        self.utf8_tail.clear();
+        self.stripper = AnsiStripper::default();
+        if let Some(filter) = self.filter.as_mut() {
+            filter.line.clear();
+            filter.plain.clear();
+        }
+    }
+}
+
+impl LineFilter {
+    fn flush_into(&mut self, out: &mut String) {
+        if self.regex.is_match(self.plain.trim_end_matches('\r')) {
+            out.push_str(&self.line);
+        }
+        self.line.clear();
+        self.plain.clear();
+    }
+}
+
+/// Incremental escape-sequence recogniser.
+///
+/// Tracks state across chunk boundaries so a sequence split between two PTY
+/// reads is still removed.
+#[derive(Default)]
+struct AnsiStripper {
+    state: StripState,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum StripState {
+    #[default]
+    Ground,
+    /// After ESC.
+    Escape,
+    /// After ESC plus an intermediate (charset selection etc.).
+    EscapeIntermediate,
+    /// Inside `ESC [ ... final`.
+    Csi,
+    /// Inside `ESC ] ...`, terminated by BEL or ST.
+    Osc,
+    /// Inside DCS / SOS / PM / APC, terminated by ST.
+    String,
+    /// ESC seen inside an OSC or string sequence.
+    StringEscape,
+}
+
+impl AnsiStripper {
+    /// Advance by one character. Returns `true` if it is visible text.
+    fn feed(&mut self, ch: char) -> bool {
+        use StripState::*;
+        match self.state {
+            Ground => {
+                if ch == '\x1b' {
+                    self.state = Escape;
+                    return false;
+                }
+                return true;
+            }
+            Escape => {
+                self.state = match ch {
+                    '[' => Csi,
+                    ']' => Osc,
+                    'P' | 'X' | '^' | '_' => String,
+                    ' ' | '#' | '%' | '(' | ')' | '*' | '+' => EscapeIntermediate,
+                    _ => Ground,
+                };
+            }
+            EscapeIntermediate => self.state = Ground,
+            Csi => {
+                if ('\x40'..='\x7e').contains(&ch) {
+                    self.state = Ground;
+                }
+            }
+            Osc => match ch {
+                '\x07' => self.state = Ground,
+                '\x1b' => self.state = StringEscape,
+                _ => {}
+            },
+            String => {
+                if ch == '\x1b' {
+                    self.state = StringEscape;
+                }
+            }
+            StringEscape => self.state = Ground,
+        }
+        false
+    }
+}
+
+/// Decode as much of `tail` followed by `bytes` as possible, leaving an
+/// incomplete trailing sequence in `tail` for the next call. Invalid bytes
+/// become U+FFFD.
+pub fn decode_utf8(tail: &mut Vec<u8>, bytes: &[u8]) -> String {
+    let mut buf = std::mem::take(tail);
+    buf.extend_from_slice(bytes);
+
+    let mut out = String::with_capacity(buf.len());
+    let mut rest = &buf[..];
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                out.push_str(valid);
+                break;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                // `valid_up_to` guarantees this prefix is valid UTF-8.
+                out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                match e.error_len() {
+                    Some(len) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[len..];
+                    }
+                    None => {
+                        *tail = after.to_vec();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder(format: OutputFormat, filter: Option<&str>) -> OutputDecoder {
+        OutputDecoder::new(format, filter).unwrap()
+    }
+
+    #[test]
+    fn raw_passes_bytes_through() {
+        let mut d = decoder(OutputFormat::Raw, None);
+        assert_eq!(d.push(b"\x1b[31mred\x1b[0m\r\n"), "\x1b[31mred\x1b[0m\r\n");
+    }
+
+    #[test]
+    fn utf8_split_across_chunks_is_reassembled() {
+        let mut d = decoder(OutputFormat::Raw, None);
+        let bytes = "한글".as_bytes();
+        assert_eq!(d.push(&bytes[..2]), "");
+        assert_eq!(d.push(&bytes[2..]), "한글");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let mut d = decoder(OutputFormat::Raw, None);
+        assert_eq!(d.push(b"a\xffb"), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn stripped_removes_sequences_split_across_chunks() {
+        let mut d = decoder(OutputFormat::Stripped, None);
+        assert_eq!(d.push(b"\x1b[3"), "");
+        assert_eq!(d.push(b"1mred\x1b]0;ti"), "red");
+        assert_eq!(d.push(b"tle\x07 \x1b(Bok\x1bP1$r\x1b\\!"), " ok!");
+    }
+
+    #[test]
+    fn filter_forwards_matching_lines_only() {
+        let mut d = decoder(OutputFormat::Stripped, Some("^error"));
+        assert_eq!(d.push(b"ok 1\r\nerr"), "");
+        assert_eq!(
+            d.push(b"or: \x1b[1mboom\x1b[0m\r\nok 2\n"),
+            "error: boom\r\n"
+        );
+    }
+
+    #[test]
+    fn filter_in_raw_mode_keeps_escapes_of_matching_lines() {
+        let mut d = decoder(OutputFormat::Raw, Some("boom"));
+        assert_eq!(
+            d.push(b"\x1b[31mboom\x1b[0m\nquiet\n"),
+            "\x1b[31mboom\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn finish_flushes_unterminated_matching_line() {
+        let mut d = decoder(OutputFormat::Stripped, Some("done"));
+        assert_eq!(d.push(b"all done"), "");
+        assert_eq!(d.finish(), "all done");
+    }
+
+    #[test]
+    fn decode_output_handles_whole_captures() {
+        let bytes = b"\x1b[1mbold\x1b[0m \xe2\x9c";
+        assert_eq!(
+            decode_output(bytes, OutputFormat::Stripped),
+            "bold \u{fffd}"
+        );
+        assert_eq!(decode_output(b"\x1b[1mok", OutputFormat::Raw), "\x1b[1mok");
+    }
+
+    #[test]
+    fn invalid_filter_is_rejected() {
+        assert!(OutputDecoder::new(OutputFormat::Raw, Some("(")).is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_numbers_notifications_and_reports_gaps() {
+        let (sub, mut stream) = output_channel(None, decoder(OutputFormat::Raw, None));
+        stream.set_pane_id(PaneId(5));
+
+        for _ in 0..OUTPUT_QUEUE_CAPACITY {
+            assert!(sub.offer(b"x"));
+        }
+        // Queue is full: these two reads are lost.
+        assert!(sub.offer(b"lost"));
+        assert!(sub.offer(b"gone"));
+
+        let mut seq = 0;
+        for _ in 0..OUTPUT_QUEUE_CAPACITY {
+            let chunk = stream.rx.recv().await;
+            let params = stream.notify(chunk).unwrap();
+            assert_eq!(params.seq, seq);
+            assert_eq!(params.pane_id, PaneId(5));
+            assert_eq!(params.dropped, 0);
+            seq += 1;
+        }
+
+        // The next chunk that fits carries the size of the gap before it.
+        assert!(sub.offer(b"after"));
+        let chunk = stream.rx.recv().await;
+        let params = stream.notify(chunk).unwrap();
+        assert_eq!(params.seq, seq);
+        assert_eq!(params.data, "after");
+        assert_eq!(params.dropped, 8);
+    }
+
+    #[tokio::test]
+    async fn stream_reports_closure_when_producer_drops() {
+        let (sub, mut stream) = output_channel(Some(PaneId(1)), decoder(OutputFormat::Raw, None));
+        drop(sub);
+        let chunk = stream.rx.recv().await;
+        let params = stream.notify(chunk).unwrap();
+        assert!(params.closed);
+        assert_eq!(params.seq, 0);
+    }
+
+    #[test]
+    fn offer_reports_unsubscribed_stream() {
+        let (sub, stream) = output_channel(None, decoder(OutputFormat::Raw, None));
+        drop(stream);
+        assert!(!sub.offer(b"late"));
+    }
+}
@@ -93,20 +93,15 @@ pub async fn start_server(
 
                             let tx = cmd_tx.clone();
                             tokio::spawn(async move {
-                                // Wrap client handler with timeout.
-                                use tokio::time::{timeout, Duration};
-                                const CLIENT_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
-
-                                match timeout(CLIENT_TIMEOUT, handle_client(stream, tx)).await {
-                                    Ok(Ok(())) => {
+                                // handle_client enforces its own idle timeout; subscribed
+                                // connections stay open until the client disconnects.
+                                match handle_client(stream, tx).await {
+                                    Ok(()) => {
                                         log::debug!("client disconnected gracefully");
                                     }
-                                    Ok(Err(e)) => {
+                                    Err(e) => {
                                         log::debug!("client disconnected: {e}");
                                     }
-                                    Err(_) => {
-                                        log::warn!("client timed out after {} seconds", CLIENT_TIMEOUT.as_secs());
-                                    }
                                 }
                                 drop(permit); // Release on disconnect.
                             });
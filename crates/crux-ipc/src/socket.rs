@@ -14,25 +14,49 @@ pub fn socket_path() -> PathBuf {
 
     // Ensure the parent directory exists with restricted permissions.
     if env.crux_socket.is_none() {
-        let dir = resolve_runtime_dir(env.xdg_runtime_dir.as_deref());
-        if let Err(e) = std::fs::create_dir_all(&dir) {
-            log::warn!("failed to create socket directory {}: {}", dir.display(), e);
-        } else {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Err(e) =
-                    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
-                {
-                    log::warn!("failed to set socket dir permissions: {e}");
-                }
-            }
-        }
+        ensure_private_dir(&resolve_runtime_dir(env.xdg_runtime_dir.as_deref()));
     }
 
     path
 }
 
+/// Control socket of the mux daemon.
+///
+/// Priority:
+/// 1. `$CRUX_MUX_SOCKET` environment variable
+/// 2. `<runtime dir>/mux/control.sock`
+pub fn mux_socket_path() -> PathBuf {
+    if let Ok(path) = std::env::var("CRUX_MUX_SOCKET") {
+        return PathBuf::from(path);
+    }
+    mux_dir().join("control.sock")
+}
+
+/// Directory holding the mux daemon's sockets, created owner-only.
+pub fn mux_dir() -> PathBuf {
+    let xdg = std::env::var("XDG_RUNTIME_DIR").ok();
+    let runtime = resolve_runtime_dir(xdg.as_deref());
+    ensure_private_dir(&runtime);
+    let dir = runtime.join("mux");
+    ensure_private_dir(&dir);
+    dir
+}
+
+/// Create `dir` if needed and restrict it to the owner.
+fn ensure_private_dir(dir: &std::path::Path) {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        log::warn!("failed to create socket directory {}: {}", dir.display(), e);
+    } else {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Err(e) = std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)) {
+                log::warn!("failed to set socket dir permissions: {e}");
+            }
+        }
+    }
+}
+
 /// Discover an existing Crux server socket.
 ///
 /// Used by CLI clients to find a running server:
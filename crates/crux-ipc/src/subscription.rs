@@ -0,0 +1,139 @@
+//! Per-connection event subscriptions.
+//!
+//! A connection that calls `crux:events/subscribe` gets its own bounded
+//! queue. The GPUI thread hands events to the [`EventSubscription`] half
+//! without ever blocking; the connection handler drains the other half and
+//! pushes `crux:events/notify` frames. When a slow client lets its queue fill
+//! up, further events are dropped and counted so the next notification can
+//! report the gap.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crux_protocol::{EventsNotifyParams, PaneEvent, PaneEventType};
+
+/// Maximum number of undelivered events buffered per connection.
+pub const EVENT_QUEUE_CAPACITY: usize = 256;
+
+/// Producer half of a subscription, owned by the GPUI main thread.
+pub struct EventSubscription {
+    filter: Vec<PaneEventType>,
+    tx: mpsc::Sender<PaneEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Consumer half of a subscription, owned by the connection handler.
+pub(crate) struct EventStream {
+    rx: mpsc::Receiver<PaneEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Create a linked subscription/stream pair for the given event filter.
+///
+/// An empty `filter` matches every event type.
+pub(crate) fn event_channel(filter: Vec<PaneEventType>) -> (EventSubscription, EventStream) {
+    let (tx, rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+    (
+        EventSubscription {
+            filter,
+            tx,
+            dropped: dropped.clone(),
+        },
+        EventStream { rx, dropped },
+    )
+}
+
+impl EventSubscription {
+    /// Event types this subscription receives (empty = all).
+    pub fn events(&self) -> &[PaneEventType] {
+        &self.filter
+    }
+
+    /// Whether `event` passes this subscription's filter.
+    pub fn matches(&self, event: &PaneEvent) -> bool {
+        self.filter.is_empty() || self.filter.contains(&event.event_type())
+    }
+
+    /// Queue `event` for delivery if it matches the filter.
+    ///
+    /// Never blocks. Returns `false` once the connection has gone away so the
+    /// caller can discard the subscription.
+    pub fn offer(&self, event: &PaneEvent) -> bool {
+        if !self.matches(event) {
+            return !self.tx.is_closed();
+        }
+        match self.tx.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+impl EventStream {
+    /// Wait for the next event, attaching the count of events dropped since
+    /// the previous one. Returns `None` if the producer half was dropped.
+    pub(crate) async fn recv(&mut self) -> Option<EventsNotifyParams> {
+        let event = self.rx.recv().await?;
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        Some(EventsNotifyParams { event, dropped })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crux_protocol::PaneId;
+
+    #[tokio::test]
+    async fn empty_filter_matches_everything() {
+        let (sub, mut stream) = event_channel(vec![]);
+        assert!(sub.offer(&PaneEvent::Created { pane_id: PaneId(1) }));
+        assert!(sub.offer(&PaneEvent::Closed { pane_id: PaneId(1) }));
+
+        let first = stream.recv().await.unwrap();
+        assert!(matches!(first.event, PaneEvent::Created { .. }));
+        let second = stream.recv().await.unwrap();
+        assert!(matches!(second.event, PaneEvent::Closed { .. }));
+    }
+
+    #[tokio::test]
+    async fn filter_skips_unrequested_types() {
+        let (sub, mut stream) = event_channel(vec![PaneEventType::PaneClosed]);
+        assert!(sub.offer(&PaneEvent::Created { pane_id: PaneId(1) }));
+        assert!(sub.offer(&PaneEvent::Closed { pane_id: PaneId(1) }));
+
+        let only = stream.recv().await.unwrap();
+        assert!(matches!(only.event, PaneEvent::Closed { .. }));
+        assert!(stream.rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn full_queue_counts_dropped_events() {
+        let (sub, mut stream) = event_channel(vec![]);
+        for i in 0..EVENT_QUEUE_CAPACITY as u64 + 3 {
+            assert!(sub.offer(&PaneEvent::Focused { pane_id: PaneId(i) }));
+        }
+
+        let first = stream.recv().await.unwrap();
+        assert_eq!(first.dropped, 3);
+        let second = stream.recv().await.unwrap();
+        assert_eq!(second.dropped, 0);
+    }
+
+    #[test]
+    fn offer_reports_closed_connection() {
+        let (sub, stream) = event_channel(vec![PaneEventType::PaneClosed]);
+        drop(stream);
+        // Both matching and filtered-out events notice the closed queue.
+        assert!(!sub.offer(&PaneEvent::Closed { pane_id: PaneId(1) }));
+        assert!(!sub.offer(&PaneEvent::Created { pane_id: PaneId(1) }));
+    }
+}
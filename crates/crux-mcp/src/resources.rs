@@ -27,6 +27,20 @@ pub fn resource_templates() -> Vec<ResourceTemplate> {
             },
             None,
         ),
+        Annotated::new(
+            RawResourceTemplate {
+                uri_template: "crux://pane/{pane_id}/history".into(),
+                name: "Pane Command History".into(),
+                title: Some("Pane Command History".into()),
+                description: Some(
+                    "Completed commands as JSON (command, cwd, exit code, timing), newest first"
+                        .into(),
+                ),
+                mime_type: Some("application/json".into()),
+                icons: None,
+            },
+            None,
+        ),
     ]
 }
 
@@ -105,6 +119,28 @@ pub fn read_resource_data(
                 meta: None,
             })
         }
+        "history" => {
+            let result = ipc
+                .call(
+                    crux_protocol::method::PANE_HISTORY,
+                    serde_json::json!({ "pane_id": pane_id }),
+                )
+                .map_err(|e| McpError::internal_error(format!("IPC error: {e}"), None))?;
+
+            let commands = result
+                .get("commands")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!([]));
+            let json =
+                serde_json::to_string_pretty(&commands).unwrap_or_else(|_| commands.to_string());
+
+            Ok(ResourceContents::TextResourceContents {
+                uri: format!("crux://pane/{pane_id}/history"),
+                mime_type: Some("application/json".into()),
+                text: json,
+                meta: None,
+            })
+        }
         other => Err(McpError::resource_not_found(
             format!("unknown resource type: {other}"),
             None,
@@ -119,7 +155,7 @@ mod tests {
     #[test]
     fn test_resource_templates_count() {
         let templates = resource_templates();
-        assert_eq!(templates.len(), 2, "should have 2 resource templates");
+        assert_eq!(templates.len(), 3, "should have 3 resource templates");
     }
 
     #[test]
@@ -158,6 +194,25 @@ mod tests {
         assert_eq!(result, Some((123, "state")));
     }
 
+    #[test]
+    fn test_resource_templates_history() {
+        let templates = resource_templates();
+        let history = templates
+            .iter()
+            .find(|t| t.raw.uri_template == "crux://pane/{pane_id}/history");
+        assert!(history.is_some(), "history template should exist");
+        assert_eq!(
+            history.unwrap().raw.mime_type,
+            Some("application/json".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_resource_uri_history() {
+        let result = parse_resource_uri("crux://pane/7/history");
+        assert_eq!(result, Some((7, "history")));
+    }
+
     #[test]
     fn test_parse_resource_uri_zero_id() {
         let result = parse_resource_uri("crux://pane/0/scrollback");
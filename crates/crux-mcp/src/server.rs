@@ -26,7 +26,10 @@ impl CruxMcpServer {
         let tool_router = crate::tools::pane::router()
             + crate::tools::command::router()
             + crate::tools::state::router()
-            + crate::tools::content::router();
+            + crate::tools::content::router()
+            + crate::tools::grid::router()
+            + crate::tools::image::router()
+            + crate::tools::layout::router();
 
         // Rate limiter: 20 requests per second with burst of 40
         // SAFETY: 20 is non-zero
@@ -48,6 +51,26 @@ impl CruxMcpServer {
         &self,
         method: &str,
         params: serde_json::Value,
+    ) -> Result<serde_json::Value, McpError> {
+        self.ipc_call_inner(method, params, None).await
+    }
+
+    /// Like [`ipc_call`](Self::ipc_call) for requests that may outlast the
+    /// default IPC read timeout.
+    pub(crate) async fn ipc_call_with_timeout(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: std::time::Duration,
+    ) -> Result<serde_json::Value, McpError> {
+        self.ipc_call_inner(method, params, Some(timeout)).await
+    }
+
+    async fn ipc_call_inner(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Option<std::time::Duration>,
     ) -> Result<serde_json::Value, McpError> {
         // Check rate limiter before making the IPC call
         if self.rate_limiter.check().is_err() {
@@ -61,39 +84,42 @@ impl CruxMcpServer {
         let method = method.to_string();
         let method_for_error = method.clone();
 
-        tokio::task::spawn_blocking(move || ipc.call(&method, params))
-            .await
-            .map_err(|e| McpError::internal_error(format!("task join error: {e}"), None))?
-            .map_err(|e| {
-                let err_str = e.to_string();
-
-                // Parse IPC error messages intelligently
-                if err_str.contains("server error") {
-                    // Extract error code and message from "server error {code}: {message}"
-                    if err_str.contains("-1001") || err_str.to_lowercase().contains("not found") {
-                        return McpError::invalid_params(
-                            "Pane not found. Use crux_list_panes to see available pane IDs.",
-                            None,
-                        );
-                    }
-                }
-
-                if err_str.contains("server closed connection") {
-                    return McpError::internal_error(
-                        "Crux terminal disconnected. Is Crux still running?",
+        tokio::task::spawn_blocking(move || match timeout {
+            Some(timeout) => ipc.call_with_timeout(&method, params, timeout),
+            None => ipc.call(&method, params),
+        })
+        .await
+        .map_err(|e| McpError::internal_error(format!("task join error: {e}"), None))?
+        .map_err(|e| {
+            let err_str = e.to_string();
+
+            // Parse IPC error messages intelligently
+            if err_str.contains("server error") {
+                // Extract error code and message from "server error {code}: {message}"
+                if err_str.contains("-1001") || err_str.to_lowercase().contains("not found") {
+                    return McpError::invalid_params(
+                        "Pane not found. Use crux_list_panes to see available pane IDs.",
                         None,
                     );
                 }
+            }
 
-                // Default error with context
-                McpError::internal_error(
-                    format!(
-                        "IPC call to '{}' failed: {}. Check Crux terminal logs.",
-                        method_for_error, e
-                    ),
+            if err_str.contains("server closed connection") {
+                return McpError::internal_error(
+                    "Crux terminal disconnected. Is Crux still running?",
                     None,
-                )
-            })
+                );
+            }
+
+            // Default error with context
+            McpError::internal_error(
+                format!(
+                    "IPC call to '{}' failed: {}. Check Crux terminal logs.",
+                    method_for_error, e
+                ),
+                None,
+            )
+        })
     }
 }
 
@@ -152,7 +178,7 @@ impl ServerHandler for CruxMcpServer {
             }
         };
 
-        // Generate 2 resources per pane: scrollback and state
+        // Generate 3 resources per pane: scrollback, state and command history
         let mut resources = Vec::new();
         for pane in panes {
             if let Some(pane_id) = pane.get("pane_id").and_then(|v| v.as_u64()) {
@@ -185,6 +211,21 @@ impl ServerHandler for CruxMcpServer {
                     },
                     None,
                 ));
+
+                // Command history resource
+                resources.push(Annotated::new(
+                    RawResource {
+                        uri: format!("crux://pane/{pane_id}/history"),
+                        name: format!("Pane {pane_id} Command History"),
+                        title: Some(format!("Pane {pane_id} Command History")),
+                        description: Some("Completed commands as JSON".into()),
+                        mime_type: Some("application/json".into()),
+                        icons: None,
+                        meta: None,
+                        size: None,
+                    },
+                    None,
+                ));
             }
         }
 
@@ -3,16 +3,26 @@ use rmcp::model::*;
 use rmcp::{schemars, tool, tool_router, ErrorData as McpError};
 
 use crate::server::CruxMcpServer;
-use crate::tools::extract_lines;
 
 use super::common::validate_command;
 
+/// Default command timeout in milliseconds.
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+/// Extra time the IPC call may take beyond the command timeout (interrupt
+/// grace period plus transport overhead).
+const IPC_TIMEOUT_MARGIN: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct ExecuteCommandParams {
     /// Pane ID (uses active pane if omitted)
     pub pane_id: Option<u64>,
-    /// Shell command to execute
+    /// Shell command to execute (single line)
     pub command: String,
+    /// Timeout in milliseconds (default: 30000). The command is interrupted
+    /// with Ctrl-C when it expires.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
 }
 
 pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMcpServer> {
@@ -22,7 +32,9 @@ pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMc
 #[tool_router(router = execute_command_tools)]
 impl CruxMcpServer {
     /// Execute a command in a terminal pane and capture output.
-    #[tool(description = "Execute a shell command in a terminal pane and return the output")]
+    #[tool(
+        description = "Execute a shell command in a terminal pane and return its complete output, exit code and timing. Requires shell integration (OSC 133)"
+    )]
     async fn crux_execute_command(
         &self,
         Parameters(params): Parameters<ExecuteCommandParams>,
@@ -32,57 +44,47 @@ impl CruxMcpServer {
             return Err(McpError::invalid_params(reason, None));
         }
 
-        // Capture output before sending command to detect new output
-        let before = self
-            .ipc_call(
-                crux_protocol::method::PANE_GET_TEXT,
-                serde_json::json!({ "pane_id": params.pane_id }),
-            )
-            .await?;
-        let before_len = before
-            .get("lines")
-            .and_then(|v| v.as_array())
-            .map(|a| a.len())
-            .unwrap_or(0);
-
-        let send_params = serde_json::json!({
+        let timeout_ms = params.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+        let run_params = serde_json::json!({
             "pane_id": params.pane_id,
-            "text": format!("{}\n", params.command),
-            "bracketed_paste": false,
+            "command": params.command,
+            "format": "stripped",
+            "timeout_ms": timeout_ms,
         });
-        self.ipc_call(crux_protocol::method::PANE_SEND_TEXT, send_params)
+        let result = self
+            .ipc_call_with_timeout(
+                crux_protocol::method::PANE_RUN_COMMAND,
+                run_params,
+                std::time::Duration::from_millis(timeout_ms) + IPC_TIMEOUT_MARGIN,
+            )
             .await?;
 
-        // Poll for new output with timeout
-        let timeout = std::time::Duration::from_millis(500);
-        let poll_interval = std::time::Duration::from_millis(50);
-        let start = std::time::Instant::now();
-
-        let result = loop {
-            tokio::time::sleep(poll_interval).await;
-
-            let after = self
-                .ipc_call(
-                    crux_protocol::method::PANE_GET_TEXT,
-                    serde_json::json!({ "pane_id": params.pane_id }),
-                )
-                .await?;
-            let after_len = after
-                .get("lines")
-                .and_then(|v| v.as_array())
-                .map(|a| a.len())
-                .unwrap_or(0);
-
-            if after_len > before_len || start.elapsed() >= timeout {
-                break after;
-            }
-        };
-
-        let output = extract_lines(&result);
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        let summary = summarize_run(&result);
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&summary).unwrap_or_else(|_| summary.to_string()),
+        )]))
     }
 }
 
+/// Reduce a `crux:pane/run-command` result to the fields agents care about.
+fn summarize_run(result: &serde_json::Value) -> serde_json::Value {
+    let started = result.get("started_at_ms").and_then(|v| v.as_u64());
+    let finished = result.get("finished_at_ms").and_then(|v| v.as_u64());
+    let output = result
+        .get("output")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .replace("\r\n", "\n");
+    serde_json::json!({
+        "output": output,
+        "exit_code": result.get("exit_code").cloned().unwrap_or(serde_json::Value::Null),
+        "duration_ms": finished.zip(started).map(|(f, s)| f.saturating_sub(s)),
+        "cwd": result.get("cwd").cloned().unwrap_or(serde_json::Value::Null),
+        "timed_out": result.get("timed_out").and_then(|v| v.as_bool()).unwrap_or(false),
+        "truncated": result.get("truncated").and_then(|v| v.as_bool()).unwrap_or(false),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,10 +94,35 @@ mod tests {
         let params = ExecuteCommandParams {
             pane_id: Some(42),
             command: "echo hello".into(),
+            timeout_ms: Some(5000),
         };
         let json = serde_json::to_string(&params).unwrap();
         let parsed: ExecuteCommandParams = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.pane_id, Some(42));
         assert_eq!(parsed.command, "echo hello");
+        assert_eq!(parsed.timeout_ms, Some(5000));
+
+        let parsed: ExecuteCommandParams = serde_json::from_str(r#"{"command":"ls"}"#).unwrap();
+        assert!(parsed.timeout_ms.is_none());
+    }
+
+    #[test]
+    fn test_summarize_run() {
+        let result = serde_json::json!({
+            "pane_id": 1,
+            "output": "a\r\nb\r\n",
+            "exit_code": 1,
+            "started_at_ms": 1000,
+            "finished_at_ms": 1250,
+            "cwd": "/tmp",
+            "timed_out": false,
+            "truncated": false,
+        });
+        let summary = summarize_run(&result);
+        assert_eq!(summary["output"], "a\nb\n");
+        assert_eq!(summary["exit_code"], 1);
+        assert_eq!(summary["duration_ms"], 250);
+        assert_eq!(summary["cwd"], "/tmp");
+        assert_eq!(summary["timed_out"], false);
     }
 }
@@ -0,0 +1,110 @@
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::*;
+use rmcp::{schemars, tool, tool_router, ErrorData as McpError};
+
+use crate::server::CruxMcpServer;
+
+/// Default number of commands returned.
+const DEFAULT_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandStatus {
+    Any,
+    Success,
+    Failure,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CommandHistoryParams {
+    /// Pane ID (uses active pane if omitted)
+    pub pane_id: Option<u64>,
+    /// Filter by exit status: any, success, failure (default: any)
+    pub status: Option<CommandStatus>,
+    /// Only commands that finished at or after this Unix time in milliseconds
+    pub since_ms: Option<u64>,
+    /// Maximum number of commands, newest first (default: 20)
+    pub limit: Option<u32>,
+    /// Include each command's output (last 200 lines)
+    pub include_output: Option<bool>,
+}
+
+pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMcpServer> {
+    CruxMcpServer::history_tools()
+}
+
+#[tool_router(router = history_tools)]
+impl CruxMcpServer {
+    /// List recently completed commands in a terminal pane.
+    #[tool(
+        description = "List commands completed in a terminal pane, newest first, with exit code, cwd, duration and optionally their output. Use status=failure, limit=1, include_output=true for the last failing command. Requires shell integration (OSC 133)"
+    )]
+    async fn crux_get_command_history(
+        &self,
+        Parameters(params): Parameters<CommandHistoryParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_HISTORY,
+                history_request(&params),
+            )
+            .await?;
+
+        let commands = result
+            .get("commands")
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!([]));
+        if commands.as_array().is_some_and(|c| c.is_empty()) {
+            return Ok(CallToolResult::success(vec![Content::text(
+                "no matching commands recorded",
+            )]));
+        }
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&commands).unwrap_or_else(|_| commands.to_string()),
+        )]))
+    }
+}
+
+fn history_request(params: &CommandHistoryParams) -> serde_json::Value {
+    serde_json::json!({
+        "pane_id": params.pane_id,
+        "status": params.status.unwrap_or(CommandStatus::Any),
+        "since_ms": params.since_ms,
+        "limit": params.limit.unwrap_or(DEFAULT_LIMIT),
+        "include_output": params.include_output.unwrap_or(false),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_history_params_serde() {
+        let parsed: CommandHistoryParams =
+            serde_json::from_str(r#"{"status":"failure","limit":1,"include_output":true}"#)
+                .unwrap();
+        assert!(matches!(parsed.status, Some(CommandStatus::Failure)));
+        assert_eq!(parsed.limit, Some(1));
+        assert_eq!(parsed.include_output, Some(true));
+    }
+
+    #[test]
+    fn test_history_request_defaults() {
+        let params = CommandHistoryParams {
+            pane_id: None,
+            status: None,
+            since_ms: None,
+            limit: None,
+            include_output: None,
+        };
+        let request = history_request(&params);
+        assert_eq!(request["status"], "any");
+        assert_eq!(request["limit"], DEFAULT_LIMIT);
+        assert_eq!(request["include_output"], false);
+
+        // The request must deserialize as the protocol type.
+        let parsed: crux_protocol::PaneHistoryParams = serde_json::from_value(request).unwrap();
+        assert_eq!(parsed.status, crux_protocol::HistoryStatus::Any);
+    }
+}
@@ -1,6 +1,7 @@
 mod common;
 mod execute_command;
 mod get_output;
+mod history;
 mod send_keys;
 mod send_text;
 mod wait_for_output;
@@ -13,6 +14,7 @@ pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMc
         + send_text::router()
         + get_output::router()
         + wait_for_output::router()
+        + history::router()
 }
 
 #[cfg(test)]
@@ -50,8 +50,8 @@ impl CruxMcpServer {
         if let Some(start) = params.offset {
             p["start_line"] = serde_json::json!(start);
         }
-        if let Some((s, l)) = params.offset.zip(params.limit) {
-            p["end_line"] = serde_json::json!(s + l);
+        if let Some(limit) = params.limit {
+            p["end_line"] = serde_json::json!(params.offset.unwrap_or(0).saturating_add(limit));
         }
         let result = self
             .ipc_call(crux_protocol::method::PANE_GET_TEXT, p)
@@ -80,9 +80,10 @@ impl CruxMcpServer {
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
-    /// Save the current terminal session layout to a file.
+    /// Save the current terminal session (layout, pane launch settings and
+    /// scrollback) to a file.
     #[tool(
-        description = "Save the current terminal session layout to a JSON file. Uses ~/.config/crux/session.json by default."
+        description = "Save the current terminal session to a JSON file: pane layout, each pane's working directory, command, environment and title, and compressed scrollback. Uses ~/.config/crux/session.json by default."
     )]
     async fn crux_save_session(
         &self,
@@ -102,7 +103,7 @@ impl CruxMcpServer {
 
     /// Load a terminal session layout from a file, restoring panes and splits.
     #[tool(
-        description = "Load a terminal session layout from a JSON file, restoring panes and splits. Uses ~/.config/crux/session.json by default."
+        description = "Load a terminal session from a JSON file, replacing the current panes. Each pane is relaunched with its saved working directory, command and environment and its scrollback is replayed. Uses ~/.config/crux/session.json by default."
     )]
     async fn crux_load_session(
         &self,
@@ -0,0 +1,143 @@
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::*;
+use rmcp::{schemars, tool, tool_router, ErrorData as McpError};
+
+use crate::server::CruxMcpServer;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DumpGridParams {
+    /// Pane ID (uses active pane if omitted)
+    pub pane_id: Option<u64>,
+    /// First line to dump (0 = top of screen, negative for scrollback; default: 0)
+    pub start_line: Option<i32>,
+    /// Line to stop before (default: bottom of screen)
+    pub end_line: Option<i32>,
+    /// First column to dump, 0-based (default: 0)
+    pub start_col: Option<u32>,
+    /// Column to stop before (default: right edge)
+    pub end_col: Option<u32>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct InspectCellParams {
+    /// Pane ID (uses active pane if omitted)
+    pub pane_id: Option<u64>,
+    /// Line of the cell (0 = top of screen, negative for scrollback)
+    pub line: i32,
+    /// Column of the cell, 0-based
+    pub col: u32,
+}
+
+pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMcpServer> {
+    CruxMcpServer::grid_tools()
+}
+
+#[tool_router(router = grid_tools)]
+impl CruxMcpServer {
+    /// Dump a region of the terminal grid as structured cell data.
+    #[tool(
+        description = "Dump a region of a terminal pane's grid as JSON: per cell the text, width, resolved #rrggbb colors, bold/dim/italic/underline/strikethrough/inverse flags and hyperlink, plus cursor, scroll region and display offset. Keep regions small; every cell is listed"
+    )]
+    async fn crux_dump_grid(
+        &self,
+        Parameters(params): Parameters<DumpGridParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_DUMP_GRID,
+                serde_json::to_value(&params).unwrap_or_default(),
+            )
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    /// Inspect a single cell of the terminal grid.
+    #[tool(
+        description = "Inspect one cell of a terminal pane: its text, colors, attributes and hyperlink. Useful for checking how a TUI rendered a specific character"
+    )]
+    async fn crux_inspect_cell(
+        &self,
+        Parameters(params): Parameters<InspectCellParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_DUMP_GRID,
+                inspect_request(&params),
+            )
+            .await?;
+
+        let cell = single_cell(&result).ok_or_else(|| {
+            McpError::invalid_params(
+                format!("no cell at line {} column {}", params.line, params.col),
+                None,
+            )
+        })?;
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&cell).unwrap_or_else(|_| cell.to_string()),
+        )]))
+    }
+}
+
+fn inspect_request(params: &InspectCellParams) -> serde_json::Value {
+    serde_json::json!({
+        "pane_id": params.pane_id,
+        "start_line": params.line,
+        "end_line": params.line.saturating_add(1),
+        "start_col": params.col,
+        "end_col": params.col.saturating_add(1),
+    })
+}
+
+/// The only cell of a 1x1 dump, tagged with its line.
+fn single_cell(result: &serde_json::Value) -> Option<serde_json::Value> {
+    let row = result.get("rows")?.as_array()?.first()?;
+    let mut cell = row.get("cells")?.as_array()?.first()?.clone();
+    cell["line"] = row.get("line")?.clone();
+    Some(cell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_grid_params_match_protocol() {
+        let params: DumpGridParams =
+            serde_json::from_str(r#"{"start_line":-5,"end_line":0,"end_col":10}"#).unwrap();
+        let parsed: crux_protocol::DumpGridParams =
+            serde_json::from_value(serde_json::to_value(&params).unwrap()).unwrap();
+        assert_eq!(parsed.start_line, Some(-5));
+        assert_eq!(parsed.end_line, Some(0));
+        assert_eq!(parsed.start_col, None);
+        assert_eq!(parsed.end_col, Some(10));
+    }
+
+    #[test]
+    fn test_inspect_request_is_one_cell() {
+        let params = InspectCellParams {
+            pane_id: Some(3),
+            line: -2,
+            col: 7,
+        };
+        let parsed: crux_protocol::DumpGridParams =
+            serde_json::from_value(inspect_request(&params)).unwrap();
+        assert_eq!(parsed.pane_id.map(|p| p.0), Some(3));
+        assert_eq!((parsed.start_line, parsed.end_line), (Some(-2), Some(-1)));
+        assert_eq!((parsed.start_col, parsed.end_col), (Some(7), Some(8)));
+    }
+
+    #[test]
+    fn test_single_cell() {
+        let result = serde_json::json!({
+            "rows": [{"line": 4, "cells": [{"col": 2, "text": "x"}]}]
+        });
+        let cell = single_cell(&result).unwrap();
+        assert_eq!(cell["line"], 4);
+        assert_eq!(cell["text"], "x");
+        assert!(single_cell(&serde_json::json!({"rows": []})).is_none());
+        assert!(single_cell(&serde_json::json!({"rows": [{"line": 0, "cells": []}]})).is_none());
+    }
+}
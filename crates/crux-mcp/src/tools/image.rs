@@ -0,0 +1,107 @@
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::*;
+use rmcp::{schemars, tool, tool_router, ErrorData as McpError};
+
+use super::PaneIdParam;
+use crate::server::CruxMcpServer;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct GetImageParams {
+    /// Pane ID (uses active pane if omitted)
+    pub pane_id: Option<u64>,
+    /// Image ID from crux_list_images
+    pub image_id: u32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RenderImageParams {
+    /// Pane ID (uses active pane if omitted)
+    pub pane_id: Option<u64>,
+    /// Path of a PNG, JPEG or GIF file
+    pub path: Option<String>,
+    /// Base64-encoded PNG, JPEG or GIF file, instead of a path
+    pub data: Option<String>,
+    /// Width in cells (default: the image's own size, fitted to the pane)
+    pub cols: Option<u32>,
+    /// Height in cells (default: keeps the aspect ratio)
+    pub rows: Option<u32>,
+}
+
+pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMcpServer> {
+    CruxMcpServer::image_tools()
+}
+
+#[tool_router(router = image_tools)]
+impl CruxMcpServer {
+    /// List the inline images programs have shown in a terminal pane.
+    #[tool(
+        description = "List the inline images (Kitty graphics, iTerm2 imgcat, Sixel) in a terminal pane: image ID, size in pixels, frame count and where each is placed (line, column, size in cells). Use crux_get_image to see one"
+    )]
+    async fn crux_list_images(
+        &self,
+        Parameters(params): Parameters<PaneIdParam>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_LIST_IMAGES,
+                serde_json::json!({ "pane_id": params.pane_id }),
+            )
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    /// Get an inline image from a terminal pane.
+    #[tool(
+        description = "Get an inline image shown in a terminal pane as a PNG image, e.g. to inspect a chart or plot a program printed. Animated images return the frame currently shown"
+    )]
+    async fn crux_get_image(
+        &self,
+        Parameters(params): Parameters<GetImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_GET_IMAGE,
+                serde_json::to_value(&params).unwrap_or_default(),
+            )
+            .await?;
+
+        let image: crux_protocol::GetImageResult = serde_json::from_value(result)
+            .map_err(|e| McpError::internal_error(format!("unexpected image format: {e}"), None))?;
+        Ok(CallToolResult::success(vec![
+            Content::image(image.data, image.mime_type),
+            Content::text(format!(
+                "image {}: {}x{} pixels",
+                image.image_id, image.width, image.height
+            )),
+        ]))
+    }
+
+    /// Show an image in a terminal pane.
+    #[tool(
+        description = "Show a PNG, JPEG or GIF image inline in a terminal pane at the cursor, without sending anything to the running program. Give either a file path or base64 data"
+    )]
+    async fn crux_render_image(
+        &self,
+        Parameters(params): Parameters<RenderImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        if params.path.is_some() == params.data.is_some() {
+            return Err(McpError::invalid_params(
+                "give exactly one of path and data",
+                None,
+            ));
+        }
+        let result = self
+            .ipc_call(
+                crux_protocol::method::RENDER_IMAGE,
+                serde_json::to_value(&params).unwrap_or_default(),
+            )
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+}
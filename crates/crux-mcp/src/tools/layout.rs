@@ -0,0 +1,97 @@
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::*;
+use rmcp::{schemars, tool, tool_router, ErrorData as McpError};
+
+use crate::server::CruxMcpServer;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct LoadWorkspaceParams {
+    /// Template name: a built-in preset (single, two-columns, two-rows,
+    /// three-columns, main-left, grid) or a file in the config directory's
+    /// layouts/ folder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Inline layout tree instead of a template. Nodes are
+    /// {"type":"split","axis":"horizontal"|"vertical","ratios":[..],"children":[..]},
+    /// {"type":"tabs","active":0,"panes":[..]} or
+    /// {"type":"pane","cwd":..,"command":[..],"env":{..},"title":..}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<serde_json::Value>,
+    /// Build the layout in a new window instead of the focused one
+    #[serde(default)]
+    pub new_window: bool,
+    /// Replace panes even if they are running a process
+    #[serde(default)]
+    pub force: bool,
+}
+
+pub(crate) fn router() -> rmcp::handler::server::router::tool::ToolRouter<CruxMcpServer> {
+    CruxMcpServer::layout_tools()
+}
+
+#[tool_router(router = layout_tools)]
+impl CruxMcpServer {
+    /// Replace the window's panes with a template or inline layout.
+    #[tool(
+        description = "Set up a workspace in one step: replace the focused window's panes (or open a new window) with a named layout template or an inline tree of splits, tabs and panes with per-pane cwd, command, env and title. Returns the built tree with the new pane IDs"
+    )]
+    async fn crux_load_workspace(
+        &self,
+        Parameters(params): Parameters<LoadWorkspaceParams>,
+    ) -> Result<CallToolResult, McpError> {
+        if params.template.is_some() == params.layout.is_some() {
+            return Err(McpError::invalid_params(
+                "exactly one of template and layout is required",
+                None,
+            ));
+        }
+        let result = self
+            .ipc_call(
+                crux_protocol::method::LAYOUT_APPLY,
+                serde_json::to_value(&params).unwrap_or_default(),
+            )
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    /// Describe the current layout.
+    #[tool(
+        description = "Get the focused window's layout as a tree of splits, tabs and panes in the same schema crux_load_workspace accepts, with pane IDs, cwd and titles"
+    )]
+    async fn crux_get_layout(&self) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(crux_protocol::method::LAYOUT_GET, serde_json::json!({}))
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_workspace_params_match_protocol() {
+        let params: LoadWorkspaceParams = serde_json::from_str(
+            r#"{"layout":{"type":"split","axis":"vertical","children":[{"type":"pane"},{"type":"pane","command":["htop"]}]},"force":true}"#,
+        )
+        .unwrap();
+        let parsed: crux_protocol::LayoutApplyParams =
+            serde_json::from_value(serde_json::to_value(&params).unwrap()).unwrap();
+        assert!(parsed.force);
+        assert!(!parsed.new_window);
+        assert_eq!(parsed.layout.unwrap().panes().len(), 2);
+
+        let params: LoadWorkspaceParams = serde_json::from_str(r#"{"template":"grid"}"#).unwrap();
+        let parsed: crux_protocol::LayoutApplyParams =
+            serde_json::from_value(serde_json::to_value(&params).unwrap()).unwrap();
+        assert_eq!(parsed.template.as_deref(), Some("grid"));
+        assert!(parsed.layout.is_none());
+    }
+}
@@ -1,5 +1,8 @@
 pub mod command;
 pub mod content;
+pub mod grid;
+pub mod image;
+pub mod layout;
 pub mod pane;
 pub mod state;
 
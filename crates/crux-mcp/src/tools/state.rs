@@ -45,6 +45,26 @@ impl CruxMcpServer {
         )]))
     }
 
+    /// Get the input-related modes an application has set in a terminal pane.
+    #[tool(
+        description = "Get the terminal modes the application in a pane has set: application cursor keys, bracketed paste, mouse reporting, alternate screen and other DEC modes, cursor style, character sets and Kitty keyboard flags. Check before sending keys to a TUI"
+    )]
+    async fn crux_get_terminal_modes(
+        &self,
+        Parameters(params): Parameters<PaneIdParam>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = self
+            .ipc_call(
+                crux_protocol::method::PANE_GET_MODES,
+                serde_json::json!({ "pane_id": params.pane_id }),
+            )
+            .await?;
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
     /// Get the currently selected text in a terminal pane.
     #[tool(description = "Get the currently selected text in a terminal pane")]
     async fn crux_get_selection(
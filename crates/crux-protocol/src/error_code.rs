@@ -11,3 +11,5 @@ pub const INTERNAL_ERROR: i32 = -32603;
 pub const PANE_NOT_FOUND: i32 = -1001;
 pub const WINDOW_NOT_FOUND: i32 = -1002;
 pub const HANDSHAKE_REQUIRED: i32 = -1003;
+pub const COMMAND_TIMEOUT: i32 = -1004;
+pub const COMMAND_CANCELLED: i32 = -1005;
@@ -0,0 +1,315 @@
+//! Declarative pane layouts for `crux:layout/apply` and `crux:layout/get`.
+//!
+//! A layout is a tree of splits whose leaves are single panes or groups of
+//! tabbed panes. The same schema describes what to build and what exists,
+//! so the output of `crux:layout/get` can be fed back to
+//! `crux:layout/apply`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::PaneId;
+
+/// Deepest split nesting a layout may use.
+pub const MAX_LAYOUT_DEPTH: usize = 16;
+
+/// Most panes a single layout may create.
+pub const MAX_LAYOUT_PANES: usize = 64;
+
+/// Names accepted by [`LayoutNode::preset`].
+pub const LAYOUT_PRESETS: &[&str] = &[
+    "single",
+    "two-columns",
+    "two-rows",
+    "three-columns",
+    "main-left",
+    "grid",
+];
+
+/// Direction the children of a split are laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutAxis {
+    /// Side by side, left to right.
+    Horizontal,
+    /// Stacked, top to bottom.
+    Vertical,
+}
+
+/// One terminal pane of a layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LayoutPane {
+    /// Set in layouts returned by the server; ignored by `crux:layout/apply`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pane_id: Option<PaneId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Program and arguments to run instead of the default shell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// Fixed tab title; the terminal's own title is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A node of a layout tree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayoutNode {
+    /// Children sharing the space along `axis`.
+    Split {
+        axis: LayoutAxis,
+        /// Relative size of each child. Equal sizes when omitted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ratios: Option<Vec<f32>>,
+        children: Vec<LayoutNode>,
+    },
+    /// Panes shown as tabs in the same space.
+    Tabs {
+        /// Index of the visible tab.
+        #[serde(default)]
+        active: usize,
+        panes: Vec<LayoutPane>,
+    },
+    /// A single pane.
+    Pane(LayoutPane),
+}
+
+impl LayoutNode {
+    /// A single-pane layout running the default shell.
+    pub fn pane() -> Self {
+        LayoutNode::Pane(LayoutPane::default())
+    }
+
+    /// Equal-sized split of `children` along `axis`.
+    pub fn split(axis: LayoutAxis, children: Vec<LayoutNode>) -> Self {
+        LayoutNode::Split {
+            axis,
+            ratios: None,
+            children,
+        }
+    }
+
+    /// Built-in layout by name; see [`LAYOUT_PRESETS`].
+    pub fn preset(name: &str) -> Option<Self> {
+        use LayoutAxis::{Horizontal, Vertical};
+        let layout = match name {
+            "single" => Self::pane(),
+            "two-columns" => Self::split(Horizontal, vec![Self::pane(), Self::pane()]),
+            "two-rows" => Self::split(Vertical, vec![Self::pane(), Self::pane()]),
+            "three-columns" => {
+                Self::split(Horizontal, vec![Self::pane(), Self::pane(), Self::pane()])
+            }
+            "main-left" => LayoutNode::Split {
+                axis: Horizontal,
+                ratios: Some(vec![0.6, 0.4]),
+                children: vec![
+                    Self::pane(),
+                    Self::split(Vertical, vec![Self::pane(), Self::pane()]),
+                ],
+            },
+            "grid" => Self::split(
+                Vertical,
+                vec![
+                    Self::split(Horizontal, vec![Self::pane(), Self::pane()]),
+                    Self::split(Horizontal, vec![Self::pane(), Self::pane()]),
+                ],
+            ),
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// Panes of the layout in order (left to right, top to bottom, tabs in
+    /// tab order).
+    pub fn panes(&self) -> Vec<&LayoutPane> {
+        let mut out = Vec::new();
+        self.collect_panes(&mut out);
+        out
+    }
+
+    /// Mutable counterpart of [`LayoutNode::panes`], in the same order.
+    pub fn panes_mut(&mut self) -> Vec<&mut LayoutPane> {
+        let mut out = Vec::new();
+        self.collect_panes_mut(&mut out);
+        out
+    }
+
+    fn collect_panes_mut<'a>(&'a mut self, out: &mut Vec<&'a mut LayoutPane>) {
+        match self {
+            LayoutNode::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes_mut(out);
+                }
+            }
+            LayoutNode::Tabs { panes, .. } => out.extend(panes),
+            LayoutNode::Pane(pane) => out.push(pane),
+        }
+    }
+
+    fn collect_panes<'a>(&'a self, out: &mut Vec<&'a LayoutPane>) {
+        match self {
+            LayoutNode::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes(out);
+                }
+            }
+            LayoutNode::Tabs { panes, .. } => out.extend(panes),
+            LayoutNode::Pane(pane) => out.push(pane),
+        }
+    }
+
+    /// Check that the layout can be built: no empty splits or tab groups,
+    /// one positive ratio per split child, an existing active tab, and
+    /// within [`MAX_LAYOUT_DEPTH`] and [`MAX_LAYOUT_PANES`].
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_at(0)?;
+        let count = self.panes().len();
+        if count > MAX_LAYOUT_PANES {
+            return Err(format!(
+                "layout has {count} panes, at most {MAX_LAYOUT_PANES} are allowed"
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_at(&self, depth: usize) -> Result<(), String> {
+        if depth > MAX_LAYOUT_DEPTH {
+            return Err(format!(
+                "layout is nested deeper than {MAX_LAYOUT_DEPTH} levels"
+            ));
+        }
+        match self {
+            LayoutNode::Split {
+                ratios, children, ..
+            } => {
+                if children.is_empty() {
+                    return Err("split has no children".into());
+                }
+                if let Some(ratios) = ratios {
+                    if ratios.len() != children.len() {
+                        return Err(format!(
+                            "split has {} children but {} ratios",
+                            children.len(),
+                            ratios.len()
+                        ));
+                    }
+                    if let Some(r) = ratios.iter().find(|r| !r.is_finite() || **r <= 0.0) {
+                        return Err(format!("split ratio must be positive, got {r}"));
+                    }
+                }
+                children
+                    .iter()
+                    .try_for_each(|child| child.validate_at(depth + 1))
+            }
+            LayoutNode::Tabs { active, panes } => {
+                if panes.is_empty() {
+                    return Err("tabs node has no panes".into());
+                }
+                if *active >= panes.len() {
+                    return Err(format!(
+                        "active tab {active} out of range for {} panes",
+                        panes.len()
+                    ));
+                }
+                Ok(())
+            }
+            LayoutNode::Pane(_) => Ok(()),
+        }
+    }
+}
+
+/// Fractions of a split's space for each of `count` children, summing to 1.
+///
+/// `ratios` is taken as relative weights; `None` (or a mismatched length)
+/// gives every child the same share.
+pub fn normalize_ratios(ratios: Option<&[f32]>, count: usize) -> Vec<f32> {
+    match ratios {
+        Some(ratios) if ratios.len() == count && ratios.iter().all(|r| *r > 0.0) => {
+            let total: f32 = ratios.iter().sum();
+            ratios.iter().map(|r| r / total).collect()
+        }
+        _ => vec![1.0 / count.max(1) as f32; count],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn layout_node_serde_uses_type_tag() {
+        let json = json!({
+            "type": "split",
+            "axis": "horizontal",
+            "ratios": [2.0, 1.0],
+            "children": [
+                { "type": "pane", "cwd": "/src", "command": ["nvim"], "title": "editor" },
+                { "type": "tabs", "active": 1, "panes": [{}, { "cwd": "/tmp" }] }
+            ]
+        });
+        let layout: LayoutNode = serde_json::from_value(json.clone()).unwrap();
+        let panes = layout.panes();
+        assert_eq!(panes.len(), 3);
+        assert_eq!(panes[0].title.as_deref(), Some("editor"));
+        assert_eq!(panes[2].cwd.as_deref(), Some("/tmp"));
+        assert!(layout.validate().is_ok());
+        assert_eq!(serde_json::to_value(&layout).unwrap(), json);
+    }
+
+    #[test]
+    fn validate_rejects_malformed_layouts() {
+        let bad_ratios = LayoutNode::Split {
+            axis: LayoutAxis::Vertical,
+            ratios: Some(vec![1.0]),
+            children: vec![LayoutNode::pane(), LayoutNode::pane()],
+        };
+        assert!(bad_ratios
+            .validate()
+            .unwrap_err()
+            .contains("2 children but 1 ratios"));
+
+        let negative = LayoutNode::Split {
+            axis: LayoutAxis::Vertical,
+            ratios: Some(vec![1.0, -1.0]),
+            children: vec![LayoutNode::pane(), LayoutNode::pane()],
+        };
+        assert!(negative.validate().is_err());
+
+        let empty_split = LayoutNode::split(LayoutAxis::Horizontal, vec![]);
+        assert!(empty_split.validate().is_err());
+
+        let bad_tab = LayoutNode::Tabs {
+            active: 2,
+            panes: vec![LayoutPane::default()],
+        };
+        assert!(bad_tab.validate().unwrap_err().contains("out of range"));
+
+        let too_many = LayoutNode::split(
+            LayoutAxis::Horizontal,
+            vec![LayoutNode::pane(); MAX_LAYOUT_PANES + 1],
+        );
+        assert!(too_many.validate().is_err());
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        for name in LAYOUT_PRESETS {
+            let layout = LayoutNode::preset(name).unwrap();
+            assert!(layout.validate().is_ok(), "{name}");
+        }
+        assert_eq!(LayoutNode::preset("grid").unwrap().panes().len(), 4);
+        assert!(LayoutNode::preset("nope").is_none());
+    }
+
+    #[test]
+    fn normalize_ratios_falls_back_to_equal() {
+        assert_eq!(normalize_ratios(Some(&[3.0, 1.0]), 2), vec![0.75, 0.25]);
+        assert_eq!(normalize_ratios(None, 4), vec![0.25; 4]);
+        assert_eq!(normalize_ratios(Some(&[1.0]), 2), vec![0.5, 0.5]);
+    }
+}
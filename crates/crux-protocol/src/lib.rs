@@ -5,8 +5,10 @@
 
 pub mod error_code;
 pub mod framing;
+pub mod layout;
 pub mod method;
 mod rpc;
+pub mod session;
 mod types;
 
 // Re-export everything at crate root to preserve the existing public API.
@@ -19,14 +21,33 @@ pub use types::{
 
 // rpc
 pub use rpc::{
-    ActivatePaneParams, ClipboardContentType, ClipboardReadParams, ClipboardReadResult,
-    ClipboardWriteParams, ClosePaneParams, EventsPollResult, EventsSubscribeParams,
+    ActivatePaneParams, CancelCommandParams, CancelCommandResult, Charset, CharsetState,
+    ClipboardContentType, ClipboardReadParams, ClipboardReadResult, ClipboardWriteParams,
+    ClosePaneParams, CommandHistoryEntry, ConfigStatusResult, CursorModes, CursorStyle,
+    DumpGridParams, DumpGridResult, EventsNotifyParams, EventsPollResult, EventsSubscribeParams,
+    EventsSubscribeResult, GetImageParams, GetImageResult, GetModesParams, GetModesResult,
     GetSelectionParams, GetSelectionResult, GetSnapshotParams, GetSnapshotResult, GetTextParams,
-    GetTextResult, HandshakeParams, HandshakeResult, ImeSetInputSourceParams, ImeStateResult,
-    JsonRpcError, JsonRpcRequest, JsonRpcResponse, ListPanesResult, ResizePaneParams,
-    SendTextParams, SendTextResult, SessionLoadParams, SessionLoadResult, SessionSaveParams,
-    SessionSaveResult, SplitPaneParams, SplitPaneResult, WindowCreateParams, WindowCreateResult,
-    WindowInfo, WindowListResult,
+    GetTextResult, GridCell, GridCursor, GridRow, HandshakeParams, HandshakeResult, HistoryStatus,
+    ImageInfo, ImagePlacementInfo, ImeSetInputSourceParams, ImeStateResult, JsonRpcError,
+    JsonRpcRequest, JsonRpcResponse, KeyboardModes, LayoutApplyParams, LayoutApplyResult,
+    LayoutGetParams, LayoutGetResult, ListImagesParams, ListImagesResult, ListPanesResult,
+    MuxAttachParams, MuxKillParams, MuxListResult, MuxResizeParams, MuxSessionInfo, OutputFormat,
+    OutputNotifyParams, PaneHistoryParams, PaneHistoryResult, PaneModes, RenderImageParams,
+    RenderImageResult, ResizePaneParams, RunCommandParams, RunCommandResult, SendTextParams,
+    SendTextResult, SessionLoadParams, SessionLoadResult, SessionSaveParams, SessionSaveResult,
+    SplitPaneParams, SplitPaneResult, SubscribeOutputParams, SubscribeOutputResult,
+    TabCreateParams, TabCreateResult, TabInfo, TabListParams, TabListResult, TabMovePaneParams,
+    UnderlineStyle, UnsubscribeOutputParams, UnsubscribeOutputResult, WindowCloseParams,
+    WindowCreateParams, WindowCreateResult, WindowFocusParams, WindowInfo, WindowListResult,
+};
+
+// layout
+pub use layout::{LayoutAxis, LayoutNode, LayoutPane};
+
+// session
+pub use session::{
+    Scrollback, ScrollbackEncoding, Session, SessionError, SessionPane, SessionWindow,
+    SESSION_VERSION,
 };
 
 // framing
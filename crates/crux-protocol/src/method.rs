@@ -10,13 +10,40 @@ pub const PANE_ACTIVATE: &str = "crux:pane/activate";
 pub const PANE_CLOSE: &str = "crux:pane/close";
 pub const PANE_GET_SNAPSHOT: &str = "crux:pane/get-snapshot";
 pub const PANE_GET_SELECTION: &str = "crux:pane/get-selection";
+pub const PANE_DUMP_GRID: &str = "crux:pane/dump-grid";
+pub const PANE_GET_MODES: &str = "crux:pane/get-modes";
+pub const PANE_LIST_IMAGES: &str = "crux:pane/list-images";
+pub const PANE_GET_IMAGE: &str = "crux:pane/get-image";
+pub const PANE_RUN_COMMAND: &str = "crux:pane/run-command";
+pub const PANE_CANCEL_COMMAND: &str = "crux:pane/cancel-command";
+pub const PANE_HISTORY: &str = "crux:pane/history";
+pub const PANE_SUBSCRIBE_OUTPUT: &str = "crux:pane/subscribe-output";
+pub const PANE_UNSUBSCRIBE_OUTPUT: &str = "crux:pane/unsubscribe-output";
+/// Server-to-client notification carrying streamed PTY output.
+pub const PANE_OUTPUT_NOTIFY: &str = "crux:pane/output";
 pub const WINDOW_CREATE: &str = "crux:window/create";
 pub const WINDOW_LIST: &str = "crux:window/list";
+pub const WINDOW_CLOSE: &str = "crux:window/close";
+pub const WINDOW_FOCUS: &str = "crux:window/focus";
+pub const TAB_CREATE: &str = "crux:tab/create";
+pub const TAB_LIST: &str = "crux:tab/list";
+pub const TAB_MOVE_PANE: &str = "crux:tab/move-pane";
+pub const LAYOUT_APPLY: &str = "crux:layout/apply";
+pub const LAYOUT_GET: &str = "crux:layout/get";
 pub const SESSION_SAVE: &str = "crux:session/save";
 pub const SESSION_LOAD: &str = "crux:session/load";
+pub const RENDER_IMAGE: &str = "crux:render/image";
+/// Mux daemon control socket: detached sessions.
+pub const MUX_LIST: &str = "crux:mux/list";
+pub const MUX_ATTACH: &str = "crux:mux/attach";
+pub const MUX_RESIZE: &str = "crux:mux/resize";
+pub const MUX_KILL: &str = "crux:mux/kill";
 pub const CLIPBOARD_READ: &str = "crux:clipboard/read";
 pub const CLIPBOARD_WRITE: &str = "crux:clipboard/write";
 pub const IME_GET_STATE: &str = "crux:ime/get-state";
 pub const IME_SET_INPUT_SOURCE: &str = "crux:ime/set-input-source";
+pub const CONFIG_GET_STATUS: &str = "crux:config/get-status";
 pub const EVENTS_SUBSCRIBE: &str = "crux:events/subscribe";
 pub const EVENTS_POLL: &str = "crux:events/poll";
+/// Server-to-client notification carrying a subscribed event.
+pub const EVENTS_NOTIFY: &str = "crux:events/notify";
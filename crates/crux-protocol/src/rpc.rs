@@ -4,6 +4,7 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::layout::LayoutNode;
 use crate::types::{
     JsonRpcId, PaneEvent, PaneEventType, PaneId, PaneInfo, PaneSize, SplitDirection, SplitSize,
     TabId, WindowId,
@@ -145,8 +146,12 @@ pub struct SendTextResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetTextParams {
     pub pane_id: Option<PaneId>,
+    /// First line to read: 0 is the top of the screen, negative lines are
+    /// scrollback. Defaults to 0.
     pub start_line: Option<i32>,
+    /// Line to stop before (exclusive). Defaults to the bottom of the screen.
     pub end_line: Option<i32>,
+    /// Rebuild SGR escape sequences from cell colors and attributes.
     #[serde(default)]
     pub include_escapes: bool,
 }
@@ -154,12 +159,317 @@ pub struct GetTextParams {
 /// Result of `crux:pane/get-text`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetTextResult {
+    /// Logical lines; soft-wrapped rows are joined into one line.
     pub lines: Vec<String>,
+    /// Line the read started at, after clamping to the available scrollback.
     pub first_line: i32,
     pub cursor_row: u32,
     pub cursor_col: u32,
 }
 
+/// Parameters for `crux:pane/dump-grid`.
+///
+/// Lines use the same addressing as `crux:pane/get-text`; columns are
+/// 0-based. Ranges are half-open and default to the visible screen.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DumpGridParams {
+    pub pane_id: Option<PaneId>,
+    #[serde(default)]
+    pub start_line: Option<i32>,
+    #[serde(default)]
+    pub end_line: Option<i32>,
+    #[serde(default)]
+    pub start_col: Option<u32>,
+    #[serde(default)]
+    pub end_col: Option<u32>,
+}
+
+/// Underline variant of a cell (SGR 4, 4:2 .. 4:5, 21).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnderlineStyle {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// Cursor shape as set by DECSCUSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorStyle {
+    Block,
+    Underline,
+    Beam,
+    HollowBlock,
+    Hidden,
+}
+
+/// One cell in a `crux:pane/dump-grid` result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridCell {
+    pub col: u32,
+    /// Character plus combining marks; empty for the second half of a wide
+    /// character.
+    pub text: String,
+    /// Display width: 2 for wide characters, 0 for their spacer cell.
+    pub width: u8,
+    /// Foreground as `#rrggbb`, resolved against the pane's palette.
+    pub fg: String,
+    /// Background as `#rrggbb`, resolved against the pane's palette.
+    pub bg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline_color: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub dim: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline: Option<UnderlineStyle>,
+    #[serde(default)]
+    pub strikethrough: bool,
+    /// Foreground and background are swapped when drawn.
+    #[serde(default)]
+    pub inverse: bool,
+    #[serde(default)]
+    pub hidden: bool,
+    /// OSC 8 hyperlink target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hyperlink: Option<String>,
+}
+
+/// One row in a `crux:pane/dump-grid` result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridRow {
+    /// Grid line (0 = top of screen, negative = scrollback).
+    pub line: i32,
+    pub cells: Vec<GridCell>,
+}
+
+/// Cursor state in a `crux:pane/dump-grid` result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridCursor {
+    pub line: i32,
+    pub col: u32,
+    pub style: CursorStyle,
+    pub visible: bool,
+}
+
+/// Result of `crux:pane/dump-grid`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpGridResult {
+    pub pane_id: PaneId,
+    pub rows: Vec<GridRow>,
+    pub cursor: GridCursor,
+    /// Scrolling region (DECSTBM) as screen lines, `top` inclusive and
+    /// `bottom` exclusive.
+    pub scroll_top: u32,
+    pub scroll_bottom: u32,
+    /// Lines the viewport is scrolled back into history.
+    pub display_offset: u32,
+    pub screen_lines: u32,
+    pub columns: u32,
+}
+
+/// Parameters for `crux:pane/get-modes`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GetModesParams {
+    pub pane_id: Option<PaneId>,
+}
+
+/// DEC private and ANSI modes that change how input is encoded or how the
+/// screen behaves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaneModes {
+    /// DECCKM (`?1`): cursor keys send `ESC O` instead of `CSI`.
+    pub application_cursor_keys: bool,
+    /// DECKPAM/DECKPNM: keypad sends application sequences.
+    pub application_keypad: bool,
+    /// DECOM (`?6`): cursor addressing is relative to the scroll region.
+    pub origin: bool,
+    /// DECAWM (`?7`): text wraps at the right margin.
+    pub auto_wrap: bool,
+    /// IRM (4): typed characters are inserted rather than overwriting.
+    pub insert: bool,
+    /// LNM (20): line feed also returns the carriage.
+    pub line_feed_new_line: bool,
+    /// `?1049` / `?47`: the alternate screen is active.
+    pub alternate_screen: bool,
+    /// `?1007`: wheel scrolls send arrow keys on the alternate screen.
+    pub alternate_scroll: bool,
+    /// `?2004`: pasted text is wrapped in `CSI 200~` / `CSI 201~`.
+    pub bracketed_paste: bool,
+    /// `?1004`: focus changes are reported.
+    pub focus_events: bool,
+    /// `?1000`: button presses are reported.
+    pub mouse_click: bool,
+    /// `?1002`: motion with a button held is reported.
+    pub mouse_drag: bool,
+    /// `?1003`: all motion is reported.
+    pub mouse_motion: bool,
+    /// `?1005`: mouse reports use UTF-8 coordinates.
+    pub mouse_utf8: bool,
+    /// `?1006`: mouse reports use the SGR format.
+    pub mouse_sgr: bool,
+    /// `?1042`: the bell sets the urgency hint.
+    pub urgency_hints: bool,
+}
+
+/// Cursor state in `crux:pane/get-modes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorModes {
+    pub style: CursorStyle,
+    pub blinking: bool,
+    /// DECTCEM (`?25`).
+    pub visible: bool,
+}
+
+/// Character set designated to a G0-G3 slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Charset {
+    Ascii,
+    /// DEC Special Graphics (line drawing), `ESC ( 0`.
+    DecSpecialGraphics,
+}
+
+/// Character set state in `crux:pane/get-modes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharsetState {
+    /// Slot invoked into GL (0 = G0 after SI, 1 = G1 after SO).
+    pub active: u8,
+    pub g0: Charset,
+    pub g1: Charset,
+    pub g2: Charset,
+    pub g3: Charset,
+}
+
+/// Kitty keyboard protocol state in `crux:pane/get-modes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyboardModes {
+    /// Active progressive enhancement flags as a bitmask (0 = legacy
+    /// encoding).
+    pub kitty_flags: u8,
+    pub disambiguate_escape_codes: bool,
+    pub report_event_types: bool,
+    pub report_alternate_keys: bool,
+    pub report_all_keys_as_escapes: bool,
+    pub report_associated_text: bool,
+}
+
+impl KeyboardModes {
+    pub fn from_kitty_flags(flags: u8) -> Self {
+        Self {
+            kitty_flags: flags,
+            disambiguate_escape_codes: flags & 1 != 0,
+            report_event_types: flags & 2 != 0,
+            report_alternate_keys: flags & 4 != 0,
+            report_all_keys_as_escapes: flags & 8 != 0,
+            report_associated_text: flags & 16 != 0,
+        }
+    }
+}
+
+/// Result of `crux:pane/get-modes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetModesResult {
+    pub pane_id: PaneId,
+    pub modes: PaneModes,
+    pub cursor: CursorModes,
+    pub charset: CharsetState,
+    pub keyboard: KeyboardModes,
+}
+
+/// Parameters for `crux:pane/list-images`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListImagesParams {
+    pub pane_id: Option<PaneId>,
+}
+
+/// Where an image is shown, in `crux:pane/list-images`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImagePlacementInfo {
+    pub placement_id: u32,
+    /// Screen line of the top edge; negative in the scrollback.
+    pub line: i32,
+    pub col: u32,
+    pub rows: u32,
+    pub cols: u32,
+    pub z_index: i32,
+}
+
+/// An image stored in a pane, in `crux:pane/list-images`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub image_id: u32,
+    /// Size in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Number of animation frames; 1 for a still image.
+    pub frames: u32,
+    /// Placements on the shown screen. Empty for an image that was only
+    /// transmitted or is placed on the other screen.
+    pub placements: Vec<ImagePlacementInfo>,
+}
+
+/// Result of `crux:pane/list-images`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListImagesResult {
+    pub pane_id: PaneId,
+    pub images: Vec<ImageInfo>,
+}
+
+/// Parameters for `crux:pane/get-image`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetImageParams {
+    pub pane_id: Option<PaneId>,
+    pub image_id: u32,
+}
+
+/// Result of `crux:pane/get-image`: the frame the image shows, as a PNG
+/// file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetImageResult {
+    pub image_id: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Always `image/png`.
+    pub mime_type: String,
+    /// Base64-encoded file contents.
+    pub data: String,
+}
+
+/// Parameters for `crux:render/image`. The image is a PNG, JPEG or GIF
+/// file given either inline or by path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RenderImageParams {
+    pub pane_id: Option<PaneId>,
+    /// Base64-encoded file contents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Path of the file, read by the terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Size in cells; the image's own size, fitted to the pane, when
+    /// omitted. With only one given, the other keeps the aspect ratio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u32>,
+}
+
+/// Result of `crux:render/image`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderImageResult {
+    pub pane_id: PaneId,
+    pub image_id: u32,
+    /// Where the image was placed.
+    pub placement: ImagePlacementInfo,
+}
+
 /// Parameters for `crux:pane/get-selection`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetSelectionParams {
@@ -236,6 +546,10 @@ pub struct WindowCreateParams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowCreateResult {
     pub window_id: WindowId,
+    /// Tab holding the window's initial pane.
+    pub tab_id: TabId,
+    /// The shell pane every new window starts with.
+    pub pane_id: PaneId,
 }
 
 /// Info about a window, returned from `crux:window/list`.
@@ -244,6 +558,8 @@ pub struct WindowInfo {
     pub window_id: WindowId,
     pub title: String,
     pub pane_count: u32,
+    #[serde(default)]
+    pub tab_count: u32,
     pub is_focused: bool,
 }
 
@@ -253,11 +569,143 @@ pub struct WindowListResult {
     pub windows: Vec<WindowInfo>,
 }
 
+/// Parameters for `crux:window/close`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowCloseParams {
+    pub window_id: WindowId,
+    /// Close even if panes in the window still run a process.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Parameters for `crux:window/focus`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowFocusParams {
+    pub window_id: WindowId,
+}
+
+/// Parameters for `crux:tab/create`.
+///
+/// Opens a new terminal pane as a tab. It joins `tab_id`'s tab bar when
+/// given, otherwise the focused tab bar of `window_id` (or of the focused
+/// window).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TabCreateParams {
+    #[serde(default)]
+    pub window_id: Option<WindowId>,
+    #[serde(default)]
+    pub tab_id: Option<TabId>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+}
+
+/// Result of `crux:tab/create`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabCreateResult {
+    pub window_id: WindowId,
+    pub tab_id: TabId,
+    pub pane_id: PaneId,
+}
+
+/// Parameters for `crux:tab/list`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TabListParams {
+    /// Only list tabs of this window. All windows if omitted.
+    #[serde(default)]
+    pub window_id: Option<WindowId>,
+}
+
+/// A tab bar and the panes shown as its tabs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabInfo {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+    /// Panes in tab order.
+    pub pane_ids: Vec<PaneId>,
+    /// The pane whose tab is selected.
+    pub active_pane_id: Option<PaneId>,
+    /// Whether this tab bar contains the window's focused pane.
+    pub is_active: bool,
+}
+
+/// Result of `crux:tab/list`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabListResult {
+    pub tabs: Vec<TabInfo>,
+}
+
+/// Parameters for `crux:tab/move-pane`.
+///
+/// Moves a pane, with its running process, into another tab bar, possibly
+/// in another window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabMovePaneParams {
+    pub pane_id: PaneId,
+    pub tab_id: TabId,
+}
+
+/// Parameters for `crux:layout/apply`.
+///
+/// Exactly one of `layout` and `template` must be given. The layout replaces
+/// every pane of the target window, or fills a new window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutApplyParams {
+    #[serde(default)]
+    pub layout: Option<LayoutNode>,
+    /// Name of a built-in preset or a template in the config directory's
+    /// `layouts/` folder.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Window to build the layout in. Defaults to the focused window.
+    #[serde(default)]
+    pub window_id: Option<WindowId>,
+    /// Open a new window for the layout instead.
+    #[serde(default)]
+    pub new_window: bool,
+    /// Replace panes even if they have a running process.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Result of `crux:layout/apply`: the built layout, with pane IDs filled in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutApplyResult {
+    pub window_id: WindowId,
+    pub layout: LayoutNode,
+}
+
+/// Parameters for `crux:layout/get`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutGetParams {
+    /// Window to describe. Defaults to the focused window.
+    #[serde(default)]
+    pub window_id: Option<WindowId>,
+}
+
+/// Result of `crux:layout/get`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutGetResult {
+    pub window_id: WindowId,
+    pub layout: LayoutNode,
+}
+
 /// Parameters for `crux:session/save`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionSaveParams {
     /// File path to save the session to. Uses default if omitted.
     pub path: Option<String>,
+    /// Lines of history to save per pane, on top of the screen. `0` saves
+    /// no scrollback at all. Defaults to
+    /// [`crate::session::DEFAULT_SCROLLBACK_LINES`].
+    #[serde(default)]
+    pub scrollback_lines: Option<u32>,
+    /// Store scrollback gzip-compressed. Defaults to `true`.
+    #[serde(default)]
+    pub compress: Option<bool>,
 }
 
 /// Result of `crux:session/save`.
@@ -279,6 +727,62 @@ pub struct SessionLoadResult {
     pub pane_count: u32,
 }
 
+/// A session hosted by the mux daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuxSessionInfo {
+    pub name: String,
+    /// Socket serving the session's panes with the regular `crux:*` methods.
+    pub socket_path: String,
+    pub pane_count: u32,
+    /// Unix time in milliseconds.
+    pub created_at_ms: u64,
+    /// Size of the session's virtual window in cells.
+    pub rows: u32,
+    pub cols: u32,
+}
+
+/// Result of `crux:mux/list`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuxListResult {
+    pub sessions: Vec<MuxSessionInfo>,
+}
+
+/// Parameters for `crux:mux/attach`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MuxAttachParams {
+    /// Session to attach to. With `create`, a missing name picks a free one.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Start the session if it does not exist.
+    #[serde(default)]
+    pub create: bool,
+    /// Working directory of a newly created session's shell.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Command of a newly created session instead of the shell.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Resize the session to the attaching client's size.
+    #[serde(default)]
+    pub rows: Option<u32>,
+    #[serde(default)]
+    pub cols: Option<u32>,
+}
+
+/// Parameters for `crux:mux/resize`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuxResizeParams {
+    pub name: String,
+    pub rows: u32,
+    pub cols: u32,
+}
+
+/// Parameters for `crux:mux/kill`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuxKillParams {
+    pub name: String,
+}
+
 /// Parameters for `crux:handshake`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeParams {
@@ -348,19 +852,249 @@ pub struct ImeSetInputSourceParams {
     pub input_source: String,
 }
 
+/// Result of `crux:config/get-status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigStatusResult {
+    /// The config file being watched for changes, if any.
+    pub path: Option<String>,
+    /// Whether changes to `path` are picked up automatically.
+    pub watching: bool,
+    /// Successful reloads since launch.
+    pub reloads: u64,
+    /// Keys changed by the latest successful reload.
+    #[serde(default)]
+    pub last_changed: Vec<String>,
+    /// Why the latest reload failed; `None` once a reload succeeds. The
+    /// previous configuration stays in effect while this is set.
+    pub error: Option<String>,
+}
+
 /// Parameters for `crux:events/subscribe`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventsSubscribeParams {
-    /// Event types to subscribe to.
+    /// Event types to subscribe to. An empty list subscribes to every type.
+    #[serde(default)]
     pub events: Vec<PaneEventType>,
 }
 
+/// Result of `crux:events/subscribe`.
+///
+/// Subscribing again on the same connection replaces the previous filter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsSubscribeResult {
+    /// Event types the connection will now receive (empty = all).
+    pub events: Vec<PaneEventType>,
+}
+
+/// Params of the `crux:events/notify` notification pushed to subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsNotifyParams {
+    pub event: PaneEvent,
+    /// Events discarded since the previous notification because the
+    /// connection's queue was full.
+    #[serde(default)]
+    pub dropped: u64,
+}
+
+/// Parameters for `crux:pane/run-command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunCommandParams {
+    /// Pane to run in. Defaults to the active pane.
+    pub pane_id: Option<PaneId>,
+    /// Single-line shell command, typed at the prompt followed by Enter.
+    pub command: String,
+    /// Encoding of `output`. Defaults to escape-stripped text.
+    #[serde(default = "default_run_command_format")]
+    pub format: OutputFormat,
+    /// How long to wait for the command to finish. Defaults to 30 seconds.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Send ^C when the timeout expires and return whatever the command
+    /// printed before it stopped.
+    #[serde(default = "default_true")]
+    pub interrupt_on_timeout: bool,
+}
+
+fn default_run_command_format() -> OutputFormat {
+    OutputFormat::Stripped
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Result of `crux:pane/run-command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunCommandResult {
+    pub pane_id: PaneId,
+    /// Everything the command printed between OSC 133 C and D.
+    pub output: String,
+    /// Exit status reported by the shell (`133;D;N`).
+    pub exit_code: Option<i32>,
+    /// Unix time in milliseconds when output started (133;C).
+    pub started_at_ms: u64,
+    /// Unix time in milliseconds when the command completed (133;D).
+    pub finished_at_ms: u64,
+    /// Working directory when the command was submitted (OSC 7).
+    pub cwd: Option<String>,
+    /// The timeout expired and the command was interrupted with ^C.
+    #[serde(default)]
+    pub timed_out: bool,
+    /// `crux:pane/cancel-command` interrupted the command with ^C.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Output exceeded the capture limit and was cut short.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Parameters for `crux:pane/cancel-command`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CancelCommandParams {
+    /// Pane whose command to cancel. Unset cancels every command this
+    /// connection is running.
+    #[serde(default)]
+    pub pane_id: Option<PaneId>,
+}
+
+/// Result of `crux:pane/cancel-command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelCommandResult {
+    /// `false` if this connection had no such command running.
+    pub cancelled: bool,
+}
+
+/// Exit status selector for `crux:pane/history`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryStatus {
+    #[default]
+    Any,
+    /// Exit code 0.
+    Success,
+    /// Non-zero exit code.
+    Failure,
+}
+
+/// Parameters for `crux:pane/history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneHistoryParams {
+    pub pane_id: Option<PaneId>,
+    #[serde(default)]
+    pub status: HistoryStatus,
+    /// Only commands that finished at or after this Unix time (milliseconds).
+    #[serde(default)]
+    pub since_ms: Option<u64>,
+    /// Maximum number of commands to return, newest first.
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// Include each command's captured output text.
+    #[serde(default)]
+    pub include_output: bool,
+}
+
+/// One completed command in `crux:pane/history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandHistoryEntry {
+    /// Per-pane identifier, increasing with each command.
+    pub id: u64,
+    pub command: String,
+    /// Working directory when the command started (OSC 7).
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    pub duration_ms: u64,
+    /// Output line range when the command finished (0 = top of screen,
+    /// negative = scrollback). Shifts as later output scrolls.
+    pub output_start_line: i32,
+    pub output_end_line: i32,
+    /// Output text (last 200 lines), when `include_output` was set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// The output was longer than what was kept.
+    #[serde(default)]
+    pub output_truncated: bool,
+}
+
+/// Result of `crux:pane/history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneHistoryResult {
+    pub pane_id: PaneId,
+    /// Matching commands, newest first.
+    pub commands: Vec<CommandHistoryEntry>,
+}
+
 /// Result of `crux:events/poll` — returns buffered events since last poll.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventsPollResult {
     pub events: Vec<PaneEvent>,
 }
 
+/// Encoding of streamed PTY output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Bytes exactly as the program wrote them, escape sequences included.
+    #[default]
+    Raw,
+    /// Escape sequences (CSI, OSC, DCS, ...) removed.
+    Stripped,
+}
+
+/// Parameters for `crux:pane/subscribe-output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeOutputParams {
+    /// Pane to observe. Defaults to the active pane.
+    pub pane_id: Option<PaneId>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Only forward complete lines whose escape-stripped text matches this
+    /// regex. Output becomes line-buffered when set.
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+/// Result of `crux:pane/subscribe-output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeOutputResult {
+    /// Identifies this subscription in `crux:pane/output` notifications.
+    pub subscription_id: u64,
+    pub pane_id: PaneId,
+}
+
+/// Parameters for `crux:pane/unsubscribe-output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsubscribeOutputParams {
+    pub subscription_id: u64,
+}
+
+/// Result of `crux:pane/unsubscribe-output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsubscribeOutputResult {
+    /// `false` if the subscription did not exist on this connection.
+    pub unsubscribed: bool,
+}
+
+/// Params of the `crux:pane/output` notification pushed to output subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputNotifyParams {
+    pub subscription_id: u64,
+    pub pane_id: PaneId,
+    /// Per-subscription sequence number, starting at 0 with no gaps.
+    pub seq: u64,
+    /// Output text. Invalid UTF-8 is replaced with U+FFFD; multi-byte
+    /// characters split across reads are reassembled first.
+    pub data: String,
+    /// PTY bytes discarded since the previous notification because the
+    /// subscriber fell behind.
+    #[serde(default)]
+    pub dropped: u64,
+    /// The pane is gone; this is the last notification for the subscription.
+    #[serde(default)]
+    pub closed: bool,
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -458,4 +1192,281 @@ mod tests {
         let params: ClipboardReadParams = serde_json::from_str("{}").unwrap();
         assert_eq!(params.content_type, ClipboardContentType::Auto);
     }
+
+    #[test]
+    fn events_subscribe_params_default_empty() {
+        let params: EventsSubscribeParams = serde_json::from_str("{}").unwrap();
+        assert!(params.events.is_empty());
+
+        let params: EventsSubscribeParams =
+            serde_json::from_str(r#"{"events":["pane_created","title_changed"]}"#).unwrap();
+        assert_eq!(
+            params.events,
+            vec![PaneEventType::PaneCreated, PaneEventType::TitleChanged]
+        );
+    }
+
+    #[test]
+    fn config_status_result_serde() {
+        let json = r#"{"path":"/tmp/config.toml","watching":true,"reloads":2,"error":"bad"}"#;
+        let status: ConfigStatusResult = serde_json::from_str(json).unwrap();
+        assert_eq!(status.reloads, 2);
+        assert!(status.last_changed.is_empty());
+        assert_eq!(status.error.as_deref(), Some("bad"));
+
+        let event = PaneEvent::ConfigReloaded {
+            changed: vec!["font.size".into()],
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["ConfigReloaded"]["changed"][0], "font.size");
+        assert_eq!(
+            serde_json::to_value(PaneEventType::ConfigReloaded).unwrap(),
+            "config_reloaded"
+        );
+    }
+
+    #[test]
+    fn events_notify_params_serde() {
+        let params = EventsNotifyParams {
+            event: PaneEvent::Focused { pane_id: PaneId(3) },
+            dropped: 2,
+        };
+        let json = serde_json::to_value(&params).unwrap();
+        assert_eq!(json["dropped"], 2);
+        assert_eq!(json["event"]["Focused"]["pane_id"], 3);
+
+        let parsed: EventsNotifyParams = serde_json::from_value(json).unwrap();
+        assert!(matches!(parsed.event, PaneEvent::Focused { pane_id } if pane_id == PaneId(3)));
+    }
+
+    #[test]
+    fn subscribe_output_params_defaults() {
+        let params: SubscribeOutputParams = serde_json::from_str("{}").unwrap();
+        assert!(params.pane_id.is_none());
+        assert_eq!(params.format, OutputFormat::Raw);
+        assert!(params.filter.is_none());
+
+        let params: SubscribeOutputParams =
+            serde_json::from_str(r#"{"pane_id":2,"format":"stripped","filter":"^error"}"#).unwrap();
+        assert_eq!(params.pane_id, Some(PaneId(2)));
+        assert_eq!(params.format, OutputFormat::Stripped);
+        assert_eq!(params.filter.as_deref(), Some("^error"));
+    }
+
+    #[test]
+    fn output_notify_params_optional_fields() {
+        let parsed: OutputNotifyParams =
+            serde_json::from_str(r#"{"subscription_id":1,"pane_id":4,"seq":7,"data":"ok\n"}"#)
+                .unwrap();
+        assert_eq!(parsed.seq, 7);
+        assert_eq!(parsed.data, "ok\n");
+        assert_eq!(parsed.dropped, 0);
+        assert!(!parsed.closed);
+    }
+
+    #[test]
+    fn run_command_params_defaults() {
+        let params: RunCommandParams = serde_json::from_str(r#"{"command":"ls"}"#).unwrap();
+        assert!(params.pane_id.is_none());
+        assert_eq!(params.command, "ls");
+        assert_eq!(params.format, OutputFormat::Stripped);
+        assert!(params.timeout_ms.is_none());
+        assert!(params.interrupt_on_timeout);
+    }
+
+    #[test]
+    fn pane_history_params_defaults() {
+        let params: PaneHistoryParams = serde_json::from_str("{}").unwrap();
+        assert!(params.pane_id.is_none());
+        assert_eq!(params.status, HistoryStatus::Any);
+        assert!(params.since_ms.is_none());
+        assert!(params.limit.is_none());
+        assert!(!params.include_output);
+
+        let params: PaneHistoryParams =
+            serde_json::from_str(r#"{"status":"failure","limit":1,"include_output":true}"#)
+                .unwrap();
+        assert_eq!(params.status, HistoryStatus::Failure);
+        assert_eq!(params.limit, Some(1));
+        assert!(params.include_output);
+    }
+
+    #[test]
+    fn command_history_entry_omits_missing_output() {
+        let entry = CommandHistoryEntry {
+            id: 3,
+            command: "cargo test".into(),
+            cwd: Some("/src".into()),
+            exit_code: Some(101),
+            started_at_ms: 1_000,
+            finished_at_ms: 4_000,
+            duration_ms: 3_000,
+            output_start_line: -20,
+            output_end_line: 5,
+            output: None,
+            output_truncated: false,
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert!(json.get("output").is_none());
+        assert_eq!(json["exit_code"], 101);
+        let parsed: CommandHistoryEntry = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.command, "cargo test");
+        assert!(parsed.output.is_none());
+    }
+
+    #[test]
+    fn dump_grid_params_default_to_screen() {
+        let params: DumpGridParams = serde_json::from_str("{}").unwrap();
+        assert!(params.pane_id.is_none());
+        assert!(params.start_line.is_none() && params.end_line.is_none());
+        assert!(params.start_col.is_none() && params.end_col.is_none());
+    }
+
+    #[test]
+    fn grid_cell_serde() {
+        let cell = GridCell {
+            col: 4,
+            text: "x".into(),
+            width: 1,
+            fg: "#f38ba8".into(),
+            bg: "#1e1e2e".into(),
+            underline_color: None,
+            bold: true,
+            dim: false,
+            italic: false,
+            underline: Some(UnderlineStyle::Curly),
+            strikethrough: false,
+            inverse: false,
+            hidden: false,
+            hyperlink: None,
+        };
+        let json = serde_json::to_value(&cell).unwrap();
+        assert_eq!(json["underline"], "curly");
+        assert_eq!(json["bold"], true);
+        assert!(json.get("hyperlink").is_none());
+        let parsed: GridCell = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, cell);
+
+        let cursor = GridCursor {
+            line: 3,
+            col: 0,
+            style: CursorStyle::HollowBlock,
+            visible: true,
+        };
+        assert_eq!(
+            serde_json::to_value(cursor).unwrap()["style"],
+            "hollow_block"
+        );
+    }
+
+    #[test]
+    fn keyboard_modes_from_kitty_flags() {
+        let keyboard = KeyboardModes::from_kitty_flags(0b101);
+        assert!(keyboard.disambiguate_escape_codes);
+        assert!(!keyboard.report_event_types);
+        assert!(keyboard.report_alternate_keys);
+        assert_eq!(KeyboardModes::from_kitty_flags(0), KeyboardModes::default());
+    }
+
+    #[test]
+    fn get_modes_result_serde() {
+        let result = GetModesResult {
+            pane_id: PaneId(2),
+            modes: PaneModes {
+                bracketed_paste: true,
+                ..PaneModes::default()
+            },
+            cursor: CursorModes {
+                style: CursorStyle::Beam,
+                blinking: true,
+                visible: true,
+            },
+            charset: CharsetState {
+                active: 1,
+                g0: Charset::Ascii,
+                g1: Charset::DecSpecialGraphics,
+                g2: Charset::Ascii,
+                g3: Charset::Ascii,
+            },
+            keyboard: KeyboardModes::from_kitty_flags(1),
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["modes"]["bracketed_paste"], true);
+        assert_eq!(json["cursor"]["style"], "beam");
+        assert_eq!(json["charset"]["g1"], "dec_special_graphics");
+        assert_eq!(json["keyboard"]["kitty_flags"], 1);
+        let parsed: GetModesResult = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.modes, result.modes);
+        assert_eq!(parsed.charset, result.charset);
+    }
+
+    #[test]
+    fn render_image_params_serde() {
+        let params: RenderImageParams =
+            serde_json::from_value(serde_json::json!({ "path": "/tmp/chart.png", "cols": 40 }))
+                .unwrap();
+        assert_eq!(params.path.as_deref(), Some("/tmp/chart.png"));
+        assert_eq!(
+            (params.data, params.cols, params.rows),
+            (None, Some(40), None)
+        );
+        let json = serde_json::to_value(RenderImageParams::default()).unwrap();
+        assert_eq!(json, serde_json::json!({ "pane_id": null }));
+    }
+
+    #[test]
+    fn run_command_result_serde() {
+        let result = RunCommandResult {
+            pane_id: PaneId(1),
+            output: "hi\n".into(),
+            exit_code: Some(0),
+            started_at_ms: 10,
+            finished_at_ms: 25,
+            cwd: Some("/tmp".into()),
+            timed_out: false,
+            cancelled: false,
+            truncated: false,
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["exit_code"], 0);
+        assert_eq!(json["cwd"], "/tmp");
+        let parsed: RunCommandResult = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.output, "hi\n");
+        assert_eq!(parsed.finished_at_ms - parsed.started_at_ms, 15);
+    }
+
+    #[test]
+    fn tab_params_default_to_focused_window() {
+        let params: TabCreateParams = serde_json::from_str("{}").unwrap();
+        assert!(params.window_id.is_none());
+        assert!(params.tab_id.is_none());
+        let params: TabListParams = serde_json::from_str(r#"{"window_id":2}"#).unwrap();
+        assert_eq!(params.window_id, Some(WindowId(2)));
+        let params: WindowCloseParams = serde_json::from_str(r#"{"window_id":1}"#).unwrap();
+        assert!(!params.force);
+    }
+
+    #[test]
+    fn tab_list_result_serde() {
+        let result = TabListResult {
+            tabs: vec![TabInfo {
+                tab_id: TabId(7),
+                window_id: WindowId(1),
+                pane_ids: vec![PaneId(3), PaneId(4)],
+                active_pane_id: Some(PaneId(4)),
+                is_active: true,
+            }],
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["tabs"][0]["pane_ids"], serde_json::json!([3, 4]));
+        let parsed: TabListResult = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.tabs[0].active_pane_id, Some(PaneId(4)));
+
+        // Window listings from older servers have no tab count.
+        let info: WindowInfo = serde_json::from_str(
+            r#"{"window_id":0,"title":"Crux","pane_count":1,"is_focused":true}"#,
+        )
+        .unwrap();
+        assert_eq!(info.tab_count, 0);
+    }
 }
@@ -0,0 +1,377 @@
+//! On-disk session format for `crux:session/save` and `crux:session/load`.
+//!
+//! A session is a [`LayoutNode`] tree describing how to relaunch every pane
+//! (cwd, command, env, title) plus per-pane state that only makes sense when
+//! restoring: the size at save time and a scrollback snapshot. The GUI's
+//! autosave also records every other open window in [`Session::windows`].
+//!
+//! Files carry a `version`. Older formats are migrated on load; files from
+//! a newer Crux are rejected with [`SessionError::UnsupportedVersion`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::{LayoutAxis, LayoutNode, LayoutPane, MAX_LAYOUT_DEPTH};
+use crate::types::{PaneId, PaneSize};
+
+/// Version written by this build.
+///
+/// - 1: raw gpui-component `DockAreaState` dump (no `version` key of ours).
+/// - 2: [`Session`].
+pub const SESSION_VERSION: u32 = 2;
+
+/// Scrollback lines saved per pane when `crux:session/save` does not say.
+pub const DEFAULT_SCROLLBACK_LINES: u32 = 2_000;
+
+/// Errors from reading a session file.
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    /// The file is not JSON or does not match any known session format.
+    #[error("invalid session file: {0}")]
+    Invalid(String),
+    /// The file was written by a newer Crux.
+    #[error("session format version {0} is newer than the supported version {SESSION_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+/// A saved window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    /// Pane tree. Pane IDs are those at save time and key [`Session::panes`].
+    pub layout: LayoutNode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<SessionPane>,
+    /// Further windows, in the order they were opened.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<SessionWindow>,
+}
+
+/// A saved window after the first one of a [`Session`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionWindow {
+    pub layout: LayoutNode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<SessionPane>,
+}
+
+/// Restore-only state of one saved pane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionPane {
+    pub pane_id: PaneId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<PaneSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback: Option<Scrollback>,
+}
+
+/// How [`Scrollback::data`] is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollbackEncoding {
+    /// Lines joined with `\n`, SGR escapes included.
+    Plain,
+    /// [`ScrollbackEncoding::Plain`] text, gzip-compressed, then base64.
+    GzipBase64,
+}
+
+/// Saved scrollback and screen contents of a pane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scrollback {
+    pub encoding: ScrollbackEncoding,
+    /// Number of lines in the decoded text.
+    pub lines: u32,
+    pub data: String,
+}
+
+impl Session {
+    pub fn new(layout: LayoutNode, panes: Vec<SessionPane>) -> Self {
+        Self {
+            version: SESSION_VERSION,
+            layout,
+            panes,
+            windows: Vec::new(),
+        }
+    }
+
+    /// Combine one single-window session per window into one session.
+    /// Returns `None` for an empty list.
+    pub fn from_windows(windows: Vec<Session>) -> Option<Self> {
+        let mut windows = windows.into_iter();
+        let mut session = windows.next()?;
+        session.windows = windows
+            .map(|w| SessionWindow {
+                layout: w.layout,
+                panes: w.panes,
+            })
+            .collect();
+        Some(session)
+    }
+
+    /// Split into one single-window session per saved window, first window
+    /// first.
+    pub fn into_windows(mut self) -> Vec<Session> {
+        let rest = std::mem::take(&mut self.windows);
+        std::iter::once(self)
+            .chain(rest.into_iter().map(|w| Session::new(w.layout, w.panes)))
+            .collect()
+    }
+
+    /// Parse a session file of any supported version.
+    pub fn from_json(json: &str) -> Result<Self, SessionError> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| SessionError::Invalid(e.to_string()))?;
+        let session = if value.get("layout").is_some() {
+            let version = value
+                .get("version")
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| SessionError::Invalid("missing version".into()))?;
+            let version = u32::try_from(version).unwrap_or(u32::MAX);
+            if version > SESSION_VERSION {
+                return Err(SessionError::UnsupportedVersion(version));
+            }
+            let mut session: Session =
+                serde_json::from_value(value).map_err(|e| SessionError::Invalid(e.to_string()))?;
+            session.version = SESSION_VERSION;
+            session
+        } else if let Some(center) = value.get("center") {
+            Self::from_dock_state(center)?
+        } else {
+            return Err(SessionError::Invalid(
+                "neither a Crux session nor a dock layout".into(),
+            ));
+        };
+        session.layout.validate().map_err(SessionError::Invalid)?;
+        for window in &session.windows {
+            window.layout.validate().map_err(SessionError::Invalid)?;
+        }
+        Ok(session)
+    }
+
+    /// Restore-only state saved for `pane_id`.
+    pub fn pane(&self, pane_id: PaneId) -> Option<&SessionPane> {
+        self.panes.iter().find(|p| p.pane_id == pane_id)
+    }
+
+    /// Migrate a version 1 file: the `center` panel tree of a `DockAreaState`.
+    /// Only the split structure, cwd and title survive.
+    fn from_dock_state(center: &serde_json::Value) -> Result<Self, SessionError> {
+        let layout = dock_panel_to_layout(center, 0)?
+            .ok_or_else(|| SessionError::Invalid("dock layout has no terminal panes".into()))?;
+        Ok(Self::new(layout, Vec::new()))
+    }
+}
+
+/// Convert one `PanelState` of a version 1 file. Returns `None` for panels
+/// that hold no terminal (empty tab groups, unknown panels).
+fn dock_panel_to_layout(
+    panel: &serde_json::Value,
+    depth: usize,
+) -> Result<Option<LayoutNode>, SessionError> {
+    if depth > MAX_LAYOUT_DEPTH {
+        return Err(SessionError::Invalid(format!(
+            "dock layout is nested deeper than {MAX_LAYOUT_DEPTH} levels"
+        )));
+    }
+    let info = panel
+        .get("info")
+        .ok_or_else(|| SessionError::Invalid("dock panel without info".into()))?;
+    let children = panel
+        .get("children")
+        .and_then(|c| c.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    if let Some(stack) = info.get("stack") {
+        let sizes: Vec<f32> = stack
+            .get("sizes")
+            .and_then(|s| s.as_array())
+            .map(|s| s.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect())
+            .unwrap_or_default();
+        let axis = if stack.get("axis").and_then(|a| a.as_u64()) == Some(0) {
+            LayoutAxis::Horizontal
+        } else {
+            LayoutAxis::Vertical
+        };
+        let mut nodes = Vec::new();
+        let mut ratios = Vec::new();
+        for (i, child) in children.iter().enumerate() {
+            if let Some(node) = dock_panel_to_layout(child, depth + 1)? {
+                nodes.push(node);
+                ratios.push(sizes.get(i).copied().filter(|s| *s > 0.0).unwrap_or(1.0));
+            }
+        }
+        return Ok(match nodes.len() {
+            0 => None,
+            1 => nodes.pop(),
+            _ => Some(LayoutNode::Split {
+                axis,
+                ratios: Some(ratios),
+                children: nodes,
+            }),
+        });
+    }
+
+    if let Some(tabs) = info.get("tabs") {
+        let mut panes: Vec<LayoutPane> = children.iter().filter_map(dock_terminal_pane).collect();
+        let active = tabs
+            .get("active_index")
+            .and_then(|a| a.as_u64())
+            .unwrap_or(0) as usize;
+        return Ok(match panes.len() {
+            0 => None,
+            1 => panes.pop().map(LayoutNode::Pane),
+            n => Some(LayoutNode::Tabs {
+                active: active.min(n - 1),
+                panes,
+            }),
+        });
+    }
+
+    Ok(dock_terminal_pane(panel).map(LayoutNode::Pane))
+}
+
+/// Pane description from a version 1 terminal panel's `info.panel` JSON.
+fn dock_terminal_pane(panel: &serde_json::Value) -> Option<LayoutPane> {
+    if panel.get("panel_name").and_then(|n| n.as_str()) != Some("CruxTerminalPanel") {
+        return None;
+    }
+    let info = panel.get("info")?.get("panel")?;
+    let string = |key: &str| info.get(key).and_then(|v| v.as_str()).map(String::from);
+    Some(LayoutPane {
+        pane_id: info.get("pane_id").and_then(|v| v.as_u64()).map(PaneId),
+        cwd: string("cwd"),
+        title: string("title"),
+        ..LayoutPane::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn session_round_trips() {
+        let mut layout = LayoutNode::preset("two-rows").unwrap();
+        for (i, pane) in layout.panes_mut().into_iter().enumerate() {
+            pane.pane_id = Some(PaneId(i as u64 + 3));
+            pane.cwd = Some("/tmp".into());
+        }
+        let session = Session::new(
+            layout,
+            vec![SessionPane {
+                pane_id: PaneId(4),
+                size: Some(PaneSize { rows: 12, cols: 80 }),
+                scrollback: Some(Scrollback {
+                    encoding: ScrollbackEncoding::Plain,
+                    lines: 1,
+                    data: "$ ls".into(),
+                }),
+            }],
+        );
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded = Session::from_json(&json).unwrap();
+        assert_eq!(loaded, session);
+        assert!(loaded.pane(PaneId(4)).unwrap().scrollback.is_some());
+        assert!(loaded.pane(PaneId(3)).is_none());
+    }
+
+    #[test]
+    fn multi_window_session_round_trips() {
+        let first = Session::new(LayoutNode::preset("two-rows").unwrap(), Vec::new());
+        let second = Session::new(
+            LayoutNode::Pane(LayoutPane {
+                cwd: Some("/srv".into()),
+                ..LayoutPane::default()
+            }),
+            vec![SessionPane {
+                pane_id: PaneId(7),
+                size: None,
+                scrollback: None,
+            }],
+        );
+        let session = Session::from_windows(vec![first.clone(), second.clone()]).unwrap();
+        assert_eq!(session.windows.len(), 1);
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded = Session::from_json(&json).unwrap();
+        assert_eq!(loaded.into_windows(), vec![first, second]);
+        assert!(Session::from_windows(Vec::new()).is_none());
+
+        let bad = json!({
+            "version": 2,
+            "layout": { "type": "pane" },
+            "windows": [{ "layout": { "type": "tabs", "panes": [] } }]
+        });
+        assert!(matches!(
+            Session::from_json(&bad.to_string()),
+            Err(SessionError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn migrates_dock_area_state() {
+        let panel = |id: u64, cwd: &str| {
+            json!({
+                "panel_name": "CruxTerminalPanel",
+                "children": [],
+                "info": { "panel": { "pane_id": id, "cwd": cwd, "title": null } }
+            })
+        };
+        let v1 = json!({
+            "version": null,
+            "center": {
+                "panel_name": "StackPanel",
+                "children": [
+                    { "panel_name": "TabPanel", "children": [panel(0, "/a")], "info": { "tabs": { "active_index": 0 } } },
+                    { "panel_name": "TabPanel", "children": [panel(1, "/b"), panel(2, "/c")], "info": { "tabs": { "active_index": 1 } } }
+                ],
+                "info": { "stack": { "sizes": [300.0, 100.0], "axis": 0 } }
+            }
+        });
+        let session = Session::from_json(&v1.to_string()).unwrap();
+        assert_eq!(session.version, SESSION_VERSION);
+        match &session.layout {
+            LayoutNode::Split {
+                axis,
+                ratios,
+                children,
+            } => {
+                assert_eq!(*axis, LayoutAxis::Horizontal);
+                assert_eq!(ratios.as_deref(), Some(&[300.0, 100.0][..]));
+                assert!(matches!(children[1], LayoutNode::Tabs { active: 1, .. }));
+            }
+            other => panic!("unexpected layout {other:?}"),
+        }
+        let cwds: Vec<_> = session
+            .layout
+            .panes()
+            .iter()
+            .map(|p| p.cwd.clone().unwrap())
+            .collect();
+        assert_eq!(cwds, ["/a", "/b", "/c"]);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(
+            Session::from_json("not json"),
+            Err(SessionError::Invalid(_))
+        ));
+        assert!(matches!(
+            Session::from_json(r#"{"panes": []}"#),
+            Err(SessionError::Invalid(_))
+        ));
+        let future = json!({ "version": 99, "layout": { "type": "pane" } });
+        assert!(matches!(
+            Session::from_json(&future.to_string()),
+            Err(SessionError::UnsupportedVersion(99))
+        ));
+        let empty = json!({ "version": 2, "layout": { "type": "tabs", "panes": [] } });
+        assert!(matches!(
+            Session::from_json(&empty.to_string()),
+            Err(SessionError::Invalid(_))
+        ));
+        let empty_dock = json!({ "center": { "panel_name": "StackPanel", "children": [], "info": { "stack": { "sizes": [], "axis": 1 } } } });
+        assert!(Session::from_json(&empty_dock.to_string()).is_err());
+    }
+}
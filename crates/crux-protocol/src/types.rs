@@ -65,11 +65,48 @@ impl fmt::Display for TabId {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PaneEvent {
-    Created { pane_id: PaneId },
-    Closed { pane_id: PaneId },
-    Focused { pane_id: PaneId },
-    Resized { pane_id: PaneId, size: PaneSize },
-    TitleChanged { pane_id: PaneId, title: String },
+    Created {
+        pane_id: PaneId,
+    },
+    Closed {
+        pane_id: PaneId,
+    },
+    Focused {
+        pane_id: PaneId,
+    },
+    Resized {
+        pane_id: PaneId,
+        size: PaneSize,
+    },
+    TitleChanged {
+        pane_id: PaneId,
+        title: String,
+    },
+    /// The config file was reloaded; `changed` lists the dotted keys that
+    /// differ from the previous configuration.
+    ConfigReloaded {
+        changed: Vec<String>,
+    },
+    /// The config file changed but could not be loaded; the previous
+    /// configuration stays in effect.
+    ConfigError {
+        message: String,
+    },
+}
+
+impl PaneEvent {
+    /// The subscription type this event is delivered under.
+    pub fn event_type(&self) -> PaneEventType {
+        match self {
+            PaneEvent::Created { .. } => PaneEventType::PaneCreated,
+            PaneEvent::Closed { .. } => PaneEventType::PaneClosed,
+            PaneEvent::Focused { .. } => PaneEventType::PaneFocused,
+            PaneEvent::Resized { .. } => PaneEventType::PaneResized,
+            PaneEvent::TitleChanged { .. } => PaneEventType::TitleChanged,
+            PaneEvent::ConfigReloaded { .. } => PaneEventType::ConfigReloaded,
+            PaneEvent::ConfigError { .. } => PaneEventType::ConfigError,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -123,7 +160,7 @@ pub struct PaneSize {
 // ---------------------------------------------------------------------------
 
 /// Event types available for subscription.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum PaneEventType {
     PaneCreated,
@@ -132,6 +169,8 @@ pub enum PaneEventType {
     PaneResized,
     TitleChanged,
     ClipboardSet,
+    ConfigReloaded,
+    ConfigError,
 }
 
 // ---------------------------------------------------------------------------
@@ -204,6 +243,38 @@ mod tests {
         assert!(matches!(parsed, PaneEventType::TitleChanged));
     }
 
+    #[test]
+    fn pane_event_maps_to_event_type() {
+        let id = PaneId(1);
+        assert_eq!(
+            PaneEvent::Created { pane_id: id }.event_type(),
+            PaneEventType::PaneCreated
+        );
+        assert_eq!(
+            PaneEvent::Resized {
+                pane_id: id,
+                size: PaneSize { rows: 24, cols: 80 },
+            }
+            .event_type(),
+            PaneEventType::PaneResized
+        );
+        assert_eq!(
+            PaneEvent::TitleChanged {
+                pane_id: id,
+                title: "vim".into(),
+            }
+            .event_type(),
+            PaneEventType::TitleChanged
+        );
+        assert_eq!(
+            PaneEvent::ConfigError {
+                message: "bad".into()
+            }
+            .event_type(),
+            PaneEventType::ConfigError
+        );
+    }
+
     #[test]
     fn osc52_policy_default() {
         let policy = Osc52Policy::default();
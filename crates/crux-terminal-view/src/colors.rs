@@ -4,100 +4,63 @@ use crux_config::ColorConfig;
 use crux_terminal::{Color, NamedColor};
 use gpui::Hsla;
 
-/// Catppuccin Mocha palette (default theme).
-/// These constants serve as fallback defaults.
-const BLACK: u32 = 0x1e1e2e;
-const RED: u32 = 0xf38ba8;
-const GREEN: u32 = 0xa6e3a1;
-const YELLOW: u32 = 0xf9e2af;
-const BLUE: u32 = 0x89b4fa;
-const MAGENTA: u32 = 0xcba6f7;
-const CYAN: u32 = 0x94e2d5;
-const WHITE: u32 = 0xcdd6f4;
-const BRIGHT_BLACK: u32 = 0x585b70;
-const BRIGHT_RED: u32 = 0xeba0ac;
-const BRIGHT_GREEN: u32 = 0x94e2d5;
-const BRIGHT_YELLOW: u32 = 0xf5e0dc;
-const BRIGHT_BLUE: u32 = 0x74c7ec;
-const BRIGHT_MAGENTA: u32 = 0xf5c2e7;
-const BRIGHT_CYAN: u32 = 0x89dceb;
-const BRIGHT_WHITE: u32 = 0xffffff;
-const FOREGROUND: u32 = 0xcdd6f4;
-const BACKGROUND: u32 = 0x1e1e2e;
-const CURSOR: u32 = 0xf5e0dc;
-
-/// Convert an alacritty `Color` to a GPUI `Hsla`.
-pub(crate) fn color_to_hsla(color: Color) -> Hsla {
-    match color {
-        Color::Named(named) => named_color_to_hsla(named),
-        Color::Spec(rgb) => rgb_to_hsla(rgb.r, rgb.g, rgb.b),
-        Color::Indexed(idx) => indexed_color_to_hsla(idx),
-    }
+/// Terminal colors resolved against a [`ColorConfig`].
+pub(crate) struct Palette {
+    indexed: [u32; 256],
+    dim: [u32; 8],
+    foreground: u32,
+    dim_foreground: u32,
+    background: u32,
+    cursor: u32,
 }
 
-fn named_color_to_hsla(color: NamedColor) -> Hsla {
-    let rgb = match color {
-        NamedColor::Black => BLACK,
-        NamedColor::Red => RED,
-        NamedColor::Green => GREEN,
-        NamedColor::Yellow => YELLOW,
-        NamedColor::Blue => BLUE,
-        NamedColor::Magenta => MAGENTA,
-        NamedColor::Cyan => CYAN,
-        NamedColor::White => WHITE,
-        NamedColor::BrightBlack => BRIGHT_BLACK,
-        NamedColor::BrightRed => BRIGHT_RED,
-        NamedColor::BrightGreen => BRIGHT_GREEN,
-        NamedColor::BrightYellow => BRIGHT_YELLOW,
-        NamedColor::BrightBlue => BRIGHT_BLUE,
-        NamedColor::BrightMagenta => BRIGHT_MAGENTA,
-        NamedColor::BrightCyan => BRIGHT_CYAN,
-        NamedColor::BrightWhite => BRIGHT_WHITE,
-        NamedColor::Foreground | NamedColor::BrightForeground | NamedColor::DimForeground => {
-            FOREGROUND
+impl Palette {
+    pub(crate) fn new(config: &ColorConfig) -> Self {
+        Self {
+            indexed: config.palette(),
+            dim: config.dim_colors(),
+            foreground: config.foreground,
+            dim_foreground: config.dim_foreground(),
+            background: config.background,
+            cursor: config.cursor,
         }
-        NamedColor::Background => BACKGROUND,
-        NamedColor::Cursor => CURSOR,
-        _ => FOREGROUND,
-    };
-    hex_to_hsla(rgb)
-}
+    }
 
-/// Convert a 256-color index to GPUI Hsla.
-fn indexed_color_to_hsla(idx: u8) -> Hsla {
-    match idx {
-        // Standard 16 colors map to named colors.
-        0 => hex_to_hsla(BLACK),
-        1 => hex_to_hsla(RED),
-        2 => hex_to_hsla(GREEN),
-        3 => hex_to_hsla(YELLOW),
-        4 => hex_to_hsla(BLUE),
-        5 => hex_to_hsla(MAGENTA),
-        6 => hex_to_hsla(CYAN),
-        7 => hex_to_hsla(WHITE),
-        8 => hex_to_hsla(BRIGHT_BLACK),
-        9 => hex_to_hsla(BRIGHT_RED),
-        10 => hex_to_hsla(BRIGHT_GREEN),
-        11 => hex_to_hsla(BRIGHT_YELLOW),
-        12 => hex_to_hsla(BRIGHT_BLUE),
-        13 => hex_to_hsla(BRIGHT_MAGENTA),
-        14 => hex_to_hsla(BRIGHT_CYAN),
-        15 => hex_to_hsla(BRIGHT_WHITE),
-        // 216 color cube (indices 16..=231).
-        16..=231 => {
-            let idx = idx - 16;
-            let r_idx = idx / 36;
-            let g_idx = (idx % 36) / 6;
-            let b_idx = idx % 6;
-            let r = if r_idx == 0 { 0 } else { 55 + 40 * r_idx };
-            let g = if g_idx == 0 { 0 } else { 55 + 40 * g_idx };
-            let b = if b_idx == 0 { 0 } else { 55 + 40 * b_idx };
-            rgb_to_hsla(r, g, b)
-        }
-        // Grayscale ramp (indices 232..=255).
-        232..=255 => {
-            let v = 8 + 10 * (idx - 232);
-            rgb_to_hsla(v, v, v)
+    /// Convert an alacritty `Color` to a GPUI `Hsla`.
+    pub(crate) fn color_to_hsla(&self, color: Color) -> Hsla {
+        hex_to_hsla(self.color_to_rgb(color))
+    }
+
+    /// The faint (SGR 2) variant of `color`, if the palette has one.
+    pub(crate) fn dim_color_to_hsla(&self, color: Color) -> Option<Hsla> {
+        let rgb = match color {
+            Color::Named(NamedColor::Foreground) => self.dim_foreground,
+            Color::Named(named) if (named as usize) < 8 => self.dim[named as usize],
+            Color::Indexed(idx) if idx < 8 => self.dim[idx as usize],
+            _ => return None,
+        };
+        Some(hex_to_hsla(rgb))
+    }
+
+    fn color_to_rgb(&self, color: Color) -> u32 {
+        match color {
+            Color::Spec(rgb) => (rgb.r as u32) << 16 | (rgb.g as u32) << 8 | rgb.b as u32,
+            Color::Indexed(idx) => self.indexed[idx as usize],
+            Color::Named(named) => match named {
+                NamedColor::Background => self.background,
+                NamedColor::Cursor => self.cursor,
+                NamedColor::DimForeground => self.dim_foreground,
+                NamedColor::DimBlack
+                | NamedColor::DimRed
+                | NamedColor::DimGreen
+                | NamedColor::DimYellow
+                | NamedColor::DimBlue
+                | NamedColor::DimMagenta
+                | NamedColor::DimCyan
+                | NamedColor::DimWhite => self.dim[named as usize - NamedColor::DimBlack as usize],
+                named if (named as usize) < 16 => self.indexed[named as usize],
+                _ => self.foreground,
+            },
         }
     }
 }
@@ -139,9 +102,34 @@ pub(crate) fn cursor_hsla(config: &ColorConfig) -> Hsla {
     hex_to_hsla(config.cursor)
 }
 
+/// Selection highlight: the configured color, else the foreground at `alpha`.
+pub(crate) fn selection_hsla(config: &ColorConfig, alpha: f32) -> Hsla {
+    match config.selection_background {
+        Some(color) => hex_to_hsla(color),
+        None => Hsla {
+            a: alpha,
+            ..hex_to_hsla(config.foreground)
+        },
+    }
+}
+
+/// Selected text color, if one is configured.
+pub(crate) fn selection_foreground_hsla(config: &ColorConfig) -> Option<Hsla> {
+    config.selection_foreground.map(hex_to_hsla)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crux_terminal::palette;
+
+    fn named_color_to_hsla(color: NamedColor) -> Hsla {
+        hex_to_hsla(palette::named_color_to_rgb(color))
+    }
+
+    fn indexed_color_to_hsla(idx: u8) -> Hsla {
+        hex_to_hsla(palette::indexed_color_to_rgb(idx))
+    }
 
     // Helper to extract approximate RGB from Hsla for assertions.
     fn hsla_to_rgb_u8(color: Hsla) -> (u8, u8, u8) {
@@ -242,7 +230,7 @@ mod tests {
             g: 64,
             b: 32,
         });
-        let hsla = color_to_hsla(color);
+        let hsla = Palette::new(&ColorConfig::default()).color_to_hsla(color);
         let (r, g, b) = hsla_to_rgb_u8(hsla);
         // Allow +/-1 for floating point conversion
         assert!((r as i16 - 128).abs() <= 1, "r={}", r);
@@ -280,4 +268,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_default_palette_matches_builtin() {
+        let colors = Palette::new(&ColorConfig::default());
+        for idx in 0..=255u8 {
+            assert_eq!(
+                colors.color_to_rgb(Color::Indexed(idx)),
+                palette::indexed_color_to_rgb(idx),
+                "index {idx}"
+            );
+        }
+        for named in [
+            NamedColor::Red,
+            NamedColor::BrightCyan,
+            NamedColor::Foreground,
+            NamedColor::Background,
+            NamedColor::Cursor,
+        ] {
+            assert_eq!(
+                colors.color_to_rgb(Color::Named(named)),
+                palette::named_color_to_rgb(named)
+            );
+        }
+    }
+
+    #[test]
+    fn test_palette_follows_config() {
+        let mut config = ColorConfig {
+            foreground: 0x999999,
+            dim: Some([0x010101; 8]),
+            selection_background: Some(0x123456),
+            ..ColorConfig::default()
+        };
+        config.normal[1] = 0xff0000;
+        config.indexed.push(crux_config::IndexedColor {
+            index: 100,
+            color: 0x00ff00,
+        });
+        let colors = Palette::new(&config);
+        assert_eq!(colors.color_to_rgb(Color::Named(NamedColor::Red)), 0xff0000);
+        assert_eq!(colors.color_to_rgb(Color::Indexed(1)), 0xff0000);
+        assert_eq!(colors.color_to_rgb(Color::Indexed(100)), 0x00ff00);
+        assert_eq!(
+            colors.color_to_rgb(Color::Named(NamedColor::DimRed)),
+            0x010101
+        );
+        assert_eq!(
+            colors.color_to_rgb(Color::Named(NamedColor::DimForeground)),
+            0x666666
+        );
+        assert!(colors
+            .dim_color_to_hsla(Color::Named(NamedColor::Green))
+            .is_some());
+        assert!(colors.dim_color_to_hsla(Color::Indexed(100)).is_none());
+        assert_eq!(
+            hsla_to_rgb_u8(selection_hsla(&config, 0.3)),
+            (0x12, 0x34, 0x56)
+        );
+    }
 }
@@ -8,6 +8,7 @@ use gpui::*;
 use crux_terminal::{CellFlags, CursorShape, Line, Point, TerminalContent};
 
 use crate::colors;
+use crate::images::VisibleImage;
 
 /// Semi-transparent selection highlight color.
 const SELECTION_ALPHA: f32 = 0.3;
@@ -35,11 +36,13 @@ pub struct RenderConfig {
     pub cursor_visible: bool,
     pub marked_text: Option<String>,
     pub color_config: crux_config::ColorConfig,
+    /// Inline images on screen, in z-index order.
+    pub images: Vec<VisibleImage>,
 }
 
 /// Render the terminal content as a canvas element.
 ///
-/// Returns a sized canvas that paints terminal cells, backgrounds, selection, and cursor.
+/// Returns a sized canvas that paints terminal cells, backgrounds, images, selection, and cursor.
 pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
     let RenderConfig {
         content,
@@ -52,16 +55,16 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
         cursor_visible,
         marked_text,
         color_config,
+        images,
     } = config;
     let fg_color = colors::foreground_hsla(&color_config);
     let bg_color = colors::background_hsla(&color_config);
     let cursor_color = colors::cursor_hsla(&color_config);
+    let palette = colors::Palette::new(&color_config);
 
-    // Selection highlight: use the foreground color with reduced alpha.
-    let selection_color = Hsla {
-        a: SELECTION_ALPHA,
-        ..fg_color
-    };
+    // Selection highlight: the theme's color, else the foreground with reduced alpha.
+    let selection_color = colors::selection_hsla(&color_config, SELECTION_ALPHA);
+    let selection_fg = colors::selection_foreground_hsla(&color_config);
 
     canvas(
         // Prepaint: shape text lines and collect background/selection quads.
@@ -86,7 +89,12 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                     let cell_idx = row * content.cols + col;
                     let (ch, cell_fg, cell_bg, cell_flags) = if cell_idx < content.cells.len() {
                         let cell = &content.cells[cell_idx];
-                        let ch = if cell.c == '\0' { ' ' } else { cell.c };
+                        // Placeholder cells are drawn by the image they stand for.
+                        let ch = if cell.c == '\0' || cell.c == crux_graphics::PLACEHOLDER {
+                            ' '
+                        } else {
+                            cell.c
+                        };
                         (ch, cell.fg, cell.bg, cell.flags)
                     } else {
                         (
@@ -97,8 +105,8 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                         )
                     };
 
-                    let mut cell_fg_hsla = colors::color_to_hsla(cell_fg);
-                    let mut cell_bg_hsla = colors::color_to_hsla(cell_bg);
+                    let mut cell_fg_hsla = palette.color_to_hsla(cell_fg);
+                    let mut cell_bg_hsla = palette.color_to_hsla(cell_bg);
 
                     // Handle INVERSE, HIDDEN, and DIM cell flags.
                     if cell_flags.contains(CellFlags::INVERSE) {
@@ -108,7 +116,16 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                         cell_fg_hsla = cell_bg_hsla;
                     }
                     if cell_flags.contains(CellFlags::DIM) {
-                        cell_fg_hsla.l *= 0.66;
+                        // Swapped or hidden colors no longer come from `cell_fg`.
+                        let dim = if cell_flags.intersects(CellFlags::INVERSE | CellFlags::HIDDEN) {
+                            None
+                        } else {
+                            palette.dim_color_to_hsla(cell_fg)
+                        };
+                        match dim {
+                            Some(dim) => cell_fg_hsla = dim,
+                            None => cell_fg_hsla.l *= 0.66,
+                        }
                     }
 
                     // Merge horizontally adjacent cells with same non-default background.
@@ -163,6 +180,9 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                             Point::new(Line(row as i32), crux_terminal::Column(col))
                         };
                         if sel.contains(cell_point) {
+                            if let Some(selection_fg) = selection_fg {
+                                cell_fg_hsla = selection_fg;
+                            }
                             selection_quads.push(fill(
                                 Bounds::new(
                                     point(
@@ -417,7 +437,7 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                 composition,
             }
         },
-        // Paint: draw backgrounds, selection, text lines, and cursor.
+        // Paint: draw backgrounds, images, selection, text lines, and cursor.
         move |bounds: Bounds<Pixels>,
               state: TerminalPrepaintState,
               window: &mut Window,
@@ -432,12 +452,17 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                 window.paint_quad(quad);
             }
 
-            // 3. Paint selection highlight.
+            // 3. Paint images below the text (negative z-index).
+            let (below, above): (Vec<_>, Vec<_>) =
+                images.iter().partition(|image| image.z_index < 0);
+            paint_images(&below, bounds, cell_width, cell_height, window);
+
+            // 4. Paint selection highlight.
             for quad in state.selection_quads {
                 window.paint_quad(quad);
             }
 
-            // 4. Paint text lines.
+            // 5. Paint text lines.
             for (row, shaped_line) in state.shaped_lines.iter().enumerate() {
                 let line_origin = point(origin.x, origin.y + cell_height * row as f32);
                 if let Err(e) = shaped_line.paint(line_origin, cell_height, window, cx) {
@@ -445,12 +470,15 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                 }
             }
 
-            // 5. Paint cursor.
+            // 6. Paint images over the text.
+            paint_images(&above, bounds, cell_width, cell_height, window);
+
+            // 7. Paint cursor.
             if let Some(cursor_quad) = state.cursor_quad {
                 window.paint_quad(cursor_quad);
             }
 
-            // 6. Paint IME composition (preedit) overlay.
+            // 8. Paint IME composition (preedit) overlay.
             if let Some((shaped, comp_origin, bg_quad)) = state.composition {
                 log::debug!(
                     "[IME] painting composition overlay at ({}, {}), width={}",
@@ -464,7 +492,7 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
                 }
             }
 
-            // 7. Paint bell flash overlay.
+            // 9. Paint bell flash overlay.
             if state.bell_flash {
                 let flash_color = Hsla {
                     h: 0.0,
@@ -478,3 +506,40 @@ pub fn render_terminal_canvas(config: RenderConfig) -> impl IntoElement {
     )
     .size_full()
 }
+
+/// Paint images at their cells, clipped to the terminal so images scrolled
+/// partly out of view are cut off.
+fn paint_images(
+    images: &[&VisibleImage],
+    bounds: Bounds<Pixels>,
+    cell_width: Pixels,
+    cell_height: Pixels,
+    window: &mut Window,
+) {
+    if images.is_empty() {
+        return;
+    }
+    window.with_content_mask(Some(ContentMask { bounds }), |window| {
+        for image in images {
+            let image_bounds = Bounds::new(
+                point(
+                    bounds.origin.x + cell_width * image.column as f32 + px(image.x_offset as f32),
+                    bounds.origin.y + cell_height * image.row as f32 + px(image.y_offset as f32),
+                ),
+                size(
+                    cell_width * image.columns as f32,
+                    cell_height * image.rows as f32,
+                ),
+            );
+            if let Err(e) = window.paint_image(
+                image_bounds,
+                Corners::default(),
+                image.image.clone(),
+                0,
+                false,
+            ) {
+                log::warn!("failed to paint image: {}", e);
+            }
+        }
+    });
+}
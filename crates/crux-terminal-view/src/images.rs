@@ -0,0 +1,172 @@
+//! Textures for the inline images placed in a terminal.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crux_graphics::{ImageData, ImagePlacement, PixelFormat, PlaceholderRun};
+use crux_terminal::CruxTerminal;
+use gpui::{RenderImage, Window};
+use image::{Frame, RgbaImage};
+
+/// A placed image on the visible screen.
+pub(crate) struct VisibleImage {
+    pub image: Arc<RenderImage>,
+    pub column: u32,
+    /// Screen row of the top edge; negative when it starts above the view.
+    pub row: i32,
+    pub columns: u32,
+    pub rows: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub z_index: i32,
+}
+
+/// Source pixels an image is cropped to: x, y, width, height.
+type SourceRect = [u32; 4];
+
+/// Textures of the images drawn in the last frame, so each is converted
+/// and uploaded once rather than every frame.
+#[derive(Default)]
+pub(crate) struct ImageCache {
+    textures: HashMap<(u32, SourceRect), Texture>,
+    /// When an animated image drawn in the last frame shows its next frame.
+    next_frame: Option<Instant>,
+}
+
+struct Texture {
+    /// The image data it was made from; a retransmitted image or the next
+    /// frame of an animation gets new data.
+    source: Arc<ImageData>,
+    image: Arc<RenderImage>,
+}
+
+impl ImageCache {
+    /// Collect the placements visible in `screen_rows` rows scrolled back by
+    /// `display_offset` lines, and the images the visible `placeholders`
+    /// stand for. Textures no longer visible are released.
+    pub fn visible(
+        &mut self,
+        terminal: &CruxTerminal,
+        screen_rows: usize,
+        display_offset: usize,
+        placeholders: &[PlaceholderRun],
+        window: &mut Window,
+    ) -> Vec<VisibleImage> {
+        let top = -(display_offset as i32);
+        let bottom = top + screen_rows as i32;
+        let now = Instant::now();
+        self.next_frame = None;
+        let (top_line, placed): (i64, Vec<(ImagePlacement, Arc<ImageData>)>) =
+            terminal.graphics().with_images(|images| {
+                let placements: Vec<ImagePlacement> = images
+                    .get_placements_in_range(top, bottom)
+                    .into_iter()
+                    .cloned()
+                    .chain(
+                        placeholders
+                            .iter()
+                            .filter_map(|run| images.placeholder_placement(run)),
+                    )
+                    .collect();
+                let placed = placements
+                    .into_iter()
+                    .filter_map(|p| {
+                        let frame = images.current_frame(p.image_id, now)?;
+                        if let Some(next) = frame.next_change {
+                            self.next_frame =
+                                Some(self.next_frame.map_or(next, |due| due.min(next)));
+                        }
+                        Some((p, frame.data))
+                    })
+                    .collect();
+                (images.top_line(), placed)
+            });
+        if placed.is_empty() && self.textures.is_empty() {
+            return Vec::new();
+        }
+
+        let mut textures = HashMap::with_capacity(placed.len());
+        let mut visible = Vec::with_capacity(placed.len());
+        for (placement, source) in placed {
+            let Some(rect) = source_rect(&placement, &source) else {
+                continue;
+            };
+            let key = (placement.image_id.0, rect);
+            let image = match textures.get(&key) {
+                Some(Texture { image, .. }) => Arc::clone(image),
+                None => {
+                    let texture = match self.textures.remove(&key) {
+                        Some(texture) if Arc::ptr_eq(&texture.source, &source) => texture,
+                        stale => {
+                            if let Some(stale) = stale {
+                                let _ = window.drop_image(stale.image);
+                            }
+                            let Some(image) = render_image(&source, rect) else {
+                                continue;
+                            };
+                            Texture {
+                                source,
+                                image: Arc::new(image),
+                            }
+                        }
+                    };
+                    let image = Arc::clone(&texture.image);
+                    textures.insert(key, texture);
+                    image
+                }
+            };
+            visible.push(VisibleImage {
+                image,
+                column: placement.column,
+                row: (placement.row - top_line + display_offset as i64) as i32,
+                columns: placement.columns.max(1),
+                rows: placement.rows.max(1),
+                x_offset: placement.x_offset,
+                y_offset: placement.y_offset,
+                z_index: placement.z_index,
+            });
+        }
+
+        for (_, texture) in std::mem::replace(&mut self.textures, textures) {
+            let _ = window.drop_image(texture.image);
+        }
+        visible
+    }
+
+    /// Whether an animated image on screen is due to show its next frame.
+    pub fn frame_due(&self) -> bool {
+        self.next_frame.is_some_and(|due| Instant::now() >= due)
+    }
+}
+
+/// The part of the image a placement shows, clamped to the image.
+fn source_rect(placement: &ImagePlacement, image: &ImageData) -> Option<SourceRect> {
+    let x = placement.source_x.min(image.width);
+    let y = placement.source_y.min(image.height);
+    let width = match placement.source_width {
+        0 => image.width - x,
+        w => w.min(image.width - x),
+    };
+    let height = match placement.source_height {
+        0 => image.height - y,
+        h => h.min(image.height - y),
+    };
+    (width > 0 && height > 0).then_some([x, y, width, height])
+}
+
+/// Crop `image` to `rect` as a single-frame texture. GPUI takes BGRA pixels
+/// in an RGBA buffer, which is how images are stored.
+fn render_image(image: &ImageData, [x, y, width, height]: SourceRect) -> Option<RenderImage> {
+    if image.format != PixelFormat::Bgra {
+        return None;
+    }
+    let stride = image.width as usize * 4;
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in y..y + height {
+        let start = row as usize * stride + x as usize * 4;
+        pixels.extend_from_slice(image.data.get(start..start + width as usize * 4)?);
+    }
+    let buffer = RgbaImage::from_raw(width, height, pixels)?;
+    Some(RenderImage::new(vec![Frame::new(buffer)]))
+}
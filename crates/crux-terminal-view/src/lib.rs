@@ -4,6 +4,7 @@ mod clipboard_handler;
 mod colors;
 mod element;
 pub mod ime_switch;
+mod images;
 mod input;
 mod keyboard;
 #[allow(dead_code)] // TODO: Wire into keyboard.rs when Kitty keyboard response encoding is needed
@@ -12,5 +13,8 @@ mod mouse;
 pub mod url_detector;
 mod view;
 
-pub use crux_terminal::ensure_terminfo_installed;
+pub use crux_terminal::{
+    ensure_terminfo_installed, protocol, CommandCapture, CommandRecord, CruxTerminal, HistoryFilter, OutputSink, RemoteFeed,
+    TerminalSize, TmuxAction, TmuxController, TmuxLayout, TmuxLayoutCell, TmuxNotification, TmuxPaneId, TmuxWindowId,
+};
 pub use view::CruxTerminalView;
@@ -8,11 +8,13 @@ use unicode_normalization::UnicodeNormalization;
 
 use crux_config::{ColorConfig, FontConfig};
 use crux_terminal::{
-    Column, CruxTerminal, DamageState, Dimensions, Line, Point, Scroll, Selection, SelectionType,
-    Side, TermMode, TerminalContent, TerminalEvent, TerminalSize,
+    Column, CommandHistory, CruxTerminal, DamageState, Dimensions, GridDump, GridText, Line, OutputTap, Point, Scroll, Selection,
+    SelectionType, Side, TermMode, TerminalContent, TerminalEvent, TerminalModes, TerminalSize, TmuxController,
+    TmuxNotification,
 };
 
 use crate::element::render_terminal_canvas;
+use crate::images::ImageCache;
 use crate::input::OptionAsAlt;
 use crate::mouse;
 
@@ -85,6 +87,11 @@ pub struct CruxTerminalView {
     vim_ime_switch: bool,
     /// The input source that was active before switching to ASCII.
     saved_input_source: Option<String>,
+    /// Textures of the inline images drawn in the last frame.
+    images: ImageCache,
+    /// Largest file a program may save to ~/Downloads, or `None` when
+    /// downloads are off.
+    max_download_bytes: Option<usize>,
 }
 
 /// Alias for GPUI's 2D point to avoid confusion with alacritty's grid Point.
@@ -101,6 +108,13 @@ impl CruxTerminalView {
         self.terminal.cwd()
     }
 
+    /// Start driving the tmux control-mode session running in this terminal.
+    /// The tmux client takes the terminal's current size.
+    pub fn tmux_controller(&self) -> TmuxController {
+        let size = self.terminal.size();
+        TmuxController::new(self.terminal.writer(), self.terminal.tmux_router().clone(), size.cols, size.rows)
+    }
+
     /// Returns whether IME is currently composing (has active preedit text).
     pub fn is_composing(&self) -> bool {
         self.marked_text.is_some()
@@ -112,9 +126,7 @@ impl CruxTerminalView {
     }
 
     /// Update font configuration and recalculate cell metrics (font size, line height).
-    /// TODO: Call this when config hot-reload is wired into the app.
-    #[allow(dead_code)]
-    pub(crate) fn update_font_config(&mut self, config: FontConfig) {
+    pub fn update_font_config(&mut self, config: FontConfig) {
         self.font_config = config;
         self.font_size = px(self.font_config.size);
         self.font = font(&self.font_config.family);
@@ -122,6 +134,22 @@ impl CruxTerminalView {
         self.cell_measured = false;
     }
 
+    /// Update the color scheme used from the next frame on.
+    pub fn update_color_config(&mut self, config: ColorConfig) {
+        self.color_config = config;
+    }
+
+    /// Allow or refuse saving files programs send, per `terminal.allow_downloads`
+    /// and `terminal.max_download_bytes`.
+    pub fn set_downloads(&mut self, config: &crux_config::TerminalConfig) {
+        self.max_download_bytes = config.allow_downloads.then_some(config.max_download_bytes);
+    }
+
+    /// Change the scrollback limit of the running terminal.
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.terminal.set_scrollback_lines(lines);
+    }
+
     pub fn new(cx: &mut Context<Self>) -> Self {
         use crux_config::TerminalConfig;
         Self::new_with_options(
@@ -149,20 +177,12 @@ impl CruxTerminalView {
         terminal_config: crux_config::TerminalConfig,
         cx: &mut Context<Self>,
     ) -> Self {
-        let focus_handle = cx.focus_handle();
-
-        let terminal_font = font(&font_config.family);
-        let font_size = px(font_config.size);
-
-        // Default cell metrics; will be recalculated on first layout.
-        let cell_width = px(8.4);
-        let cell_height = px(17.0);
-
         let size = TerminalSize {
             rows: 24,
             cols: 80,
-            cell_width: f32::from(cell_width),
-            cell_height: f32::from(cell_height),
+            // Default cell metrics; recalculated on first layout.
+            cell_width: 8.4,
+            cell_height: 17.0,
             scrollback_lines: terminal_config.scrollback_lines,
         };
 
@@ -203,14 +223,42 @@ impl CruxTerminalView {
             }
         };
 
-        // Periodic refresh at ~60fps to pick up PTY output and handle cursor blink.
+        let mut view = Self::from_terminal(terminal, font_config, color_config, cx);
+        view.set_downloads(&terminal_config);
+        view
+    }
+
+    /// Create a view around an existing terminal, such as a remote tmux pane.
+    pub fn from_terminal(
+        terminal: CruxTerminal,
+        font_config: FontConfig,
+        color_config: ColorConfig,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let focus_handle = cx.focus_handle();
+
+        let terminal_font = font(&font_config.family);
+        let font_size = px(font_config.size);
+
+        // Default cell metrics; will be recalculated on first layout.
+        let cell_width = px(8.4);
+        let cell_height = px(17.0);
+
+        // Periodic refresh at ~60fps to pick up PTY output, handle cursor blink
+        // and play animated images.
         cx.spawn(async |this: WeakEntity<Self>, cx: &mut AsyncApp| loop {
             cx.background_executor()
                 .timer(Duration::from_millis(16))
                 .await;
             let ok = this.update(cx, |this: &mut Self, cx: &mut Context<Self>| {
                 // Only notify if there's actual work: dirty content, active bell, or cursor blinking.
-                if this.dirty || this.is_bell_active() || this.should_notify_for_blink() {
+                // A tmux control-mode session also needs its notifications handled while hidden.
+                if this.dirty
+                    || this.is_bell_active()
+                    || this.should_notify_for_blink()
+                    || this.images.frame_due()
+                    || this.terminal.tmux_router().is_active()
+                {
                     cx.notify();
                 }
             });
@@ -247,6 +295,8 @@ impl CruxTerminalView {
             marked_text_timestamp: None,
             vim_ime_switch: false,
             saved_input_source: None,
+            images: ImageCache::default(),
+            max_download_bytes: None,
         }
     }
 
@@ -587,7 +637,45 @@ impl CruxTerminalView {
                     }
                 }
                 TerminalEvent::Graphics { .. } => {
-                    // Graphics events are handled by the image manager (future).
+                    // Kitty, iTerm2 and Sixel images are executed by the
+                    // terminal's reader thread and drawn from its placements.
+                }
+                TerminalEvent::FileDownload { name, data } => {
+                    // iTerm2 saves files sent with inline=0 to ~/Downloads.
+                    let Some(max) = self.max_download_bytes else {
+                        log::warn!(
+                            "ignoring file {:?} sent by the terminal: terminal.allow_downloads is off",
+                            name
+                        );
+                        continue;
+                    };
+                    if data.len() > max {
+                        log::warn!(
+                            "ignoring file {:?} sent by the terminal: {} bytes is over terminal.max_download_bytes ({})",
+                            name,
+                            data.len(),
+                            max
+                        );
+                        continue;
+                    }
+                    let Some(home) = std::env::var_os("HOME") else {
+                        log::warn!("no HOME to save downloaded file in");
+                        continue;
+                    };
+                    let dir = std::path::Path::new(&home).join("Downloads");
+                    let saved = crux_graphics::protocol::iterm2::save_download(
+                        &dir,
+                        name.as_deref(),
+                        &data,
+                    );
+                    match saved {
+                        Ok(path) => log::info!("saved downloaded file to {}", path.display()),
+                        Err(e) => log::warn!("failed to save downloaded file: {}", e),
+                    }
+                }
+                TerminalEvent::Tmux(notification) => {
+                    // Handled by the app, which owns the tmux session.
+                    cx.emit(notification);
                 }
             }
         }
@@ -659,6 +747,45 @@ impl CruxTerminalView {
         crux_terminal::extract_text_lines(&content)
     }
 
+    /// Read grid lines `start_line..end_line` (negative lines are scrollback),
+    /// optionally with SGR escapes.
+    pub fn read_text(
+        &self,
+        start_line: Option<i32>,
+        end_line: Option<i32>,
+        escapes: bool,
+    ) -> GridText {
+        self.terminal.read_text(start_line, end_line, escapes)
+    }
+
+    /// Dump grid rows `lines` restricted to columns `cols`, cell by cell.
+    pub fn dump_grid(&self, lines: Range<Option<i32>>, cols: Range<Option<usize>>) -> GridDump {
+        self.terminal.dump_grid(lines, cols)
+    }
+
+    /// Terminal modes, cursor style, charsets and keyboard flags set by the
+    /// running application.
+    pub fn modes(&self) -> TerminalModes {
+        self.terminal.modes()
+    }
+
+    /// Inline images shown in the terminal.
+    pub fn graphics(&self) -> &crux_terminal::Graphics {
+        self.terminal.graphics()
+    }
+
+    /// Show an image file at the cursor without writing to the PTY.
+    pub fn show_image(
+        &mut self,
+        data: Vec<u8>,
+        cols: Option<u32>,
+        rows: Option<u32>,
+    ) -> Result<crux_graphics::ImageId, crux_graphics::GraphicsError> {
+        let image_id = self.terminal.show_image(data, cols, rows)?;
+        self.dirty = true;
+        Ok(image_id)
+    }
+
     /// Get terminal grid content as text lines from an existing content snapshot.
     ///
     /// This avoids redundant FairMutex acquisition when the caller already has
@@ -682,6 +809,36 @@ impl CruxTerminalView {
         self.terminal.content()
     }
 
+    /// Whether the shell emits OSC 133 prompt marks.
+    pub fn has_shell_integration(&self) -> bool {
+        self.terminal.has_shell_integration()
+    }
+
+    /// Whether the shell reports a command in progress (OSC 133 C without D).
+    pub fn is_command_running(&self) -> bool {
+        self.terminal.is_command_running()
+    }
+
+    /// Commands completed in this terminal (OSC 133 shell integration).
+    pub fn history(&self) -> &CommandHistory {
+        self.terminal.history()
+    }
+
+    /// Screen and scrollback packed for a session file.
+    pub fn scrollback_snapshot(&self, history_lines: usize, compress: bool) -> crux_protocol::Scrollback {
+        self.terminal.scrollback_snapshot(history_lines, compress)
+    }
+
+    /// Replay scrollback saved by [`Self::scrollback_snapshot`].
+    pub fn restore_scrollback(&self, saved: &crux_protocol::Scrollback) -> anyhow::Result<()> {
+        self.terminal.restore_scrollback(saved)
+    }
+
+    /// Raw PTY output observers for this terminal.
+    pub fn output_tap(&self) -> &OutputTap {
+        self.terminal.output_tap()
+    }
+
     /// Scroll to the previous prompt (OSC 133 semantic zone).
     pub fn scroll_to_prev_prompt(&mut self) {
         let content = self.terminal.content();
@@ -734,6 +891,8 @@ impl CruxTerminalView {
     }
 }
 
+impl EventEmitter<TmuxNotification> for CruxTerminalView {}
+
 impl Focusable for CruxTerminalView {
     fn focus_handle(&self, _cx: &App) -> FocusHandle {
         self.focus_handle.clone()
@@ -1112,6 +1271,16 @@ impl Render for CruxTerminalView {
         // Clear dirty flag now that we are rendering this frame.
         self.dirty = false;
 
+        let images = self
+            .images
+            .visible(
+                &self.terminal,
+                content.rows,
+                content.display_offset,
+                &content.image_placeholders,
+                window,
+            );
+
         // Capture cell dimensions for the resize canvas.
         let cell_width = self.cell_width;
         let cell_height = self.cell_height;
@@ -1199,6 +1368,7 @@ impl Render for CruxTerminalView {
                 cursor_visible,
                 marked_text,
                 color_config: self.color_config.clone(),
+                images,
             }))
     }
 }
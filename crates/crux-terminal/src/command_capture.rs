@@ -0,0 +1,238 @@
+//! Byte-exact capture of one command's output using OSC 133 markers.
+//!
+//! Feed raw PTY output (typically from an [`OutputTap`](crate::OutputTap)
+//! sink) into a [`CommandCapture`]. Everything between the next `133;C`
+//! (output start) and the following `133;D` (command complete) is collected
+//! as-is, independent of how far the screen scrolls in between. Unlike the
+//! per-read scanners in [`osc_scanner`](crate::osc_scanner), markers split
+//! across PTY reads are handled.
+
+use std::time::SystemTime;
+
+use crate::osc_scanner::{find_string_terminator, parse_osc133_payload, Osc133Mark};
+
+/// OSC 133 introducer: `ESC ] 1 3 3 ;`.
+const OSC133_PREFIX: &[u8] = b"\x1b]133;";
+
+/// Longest unterminated marker we hold back waiting for more bytes.
+/// Real markers are a handful of bytes; anything longer is not ours.
+const MAX_PENDING_MARKER: usize = 128;
+
+/// Output of a completed command.
+#[derive(Debug, Clone)]
+pub struct CapturedCommand {
+    /// Raw bytes between `133;C` and `133;D`, escape sequences included.
+    pub output: Vec<u8>,
+    /// Exit status from `133;D;N`, if the shell reported one.
+    pub exit_code: Option<i32>,
+    /// When `133;C` was seen.
+    pub started_at: SystemTime,
+    /// When `133;D` was seen.
+    pub finished_at: SystemTime,
+    /// Output exceeded the capture limit; only the first bytes were kept.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Waiting for `133;C`.
+    Waiting,
+    /// Collecting output until `133;D`.
+    Capturing,
+    /// `133;D` seen; further input is ignored.
+    Finished,
+}
+
+/// Incremental OSC 133 C→D output collector.
+#[derive(Debug)]
+pub struct CommandCapture {
+    phase: Phase,
+    /// Possible start of a marker left over from the previous chunk.
+    carry: Vec<u8>,
+    output: Vec<u8>,
+    limit: usize,
+    truncated: bool,
+    started_at: Option<SystemTime>,
+}
+
+impl CommandCapture {
+    /// Create a capture that keeps at most `limit` bytes of output.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            phase: Phase::Waiting,
+            carry: Vec::new(),
+            output: Vec::new(),
+            limit,
+            truncated: false,
+            started_at: None,
+        }
+    }
+
+    /// Whether `133;C` has been seen, i.e. the command is running.
+    pub fn is_started(&self) -> bool {
+        self.phase != Phase::Waiting
+    }
+
+    /// Feed a chunk of PTY output. Returns the captured command once its
+    /// `133;D` marker arrives; subsequent calls return `None`.
+    pub fn feed(&mut self, bytes: &[u8]) -> Option<CapturedCommand> {
+        if self.phase == Phase::Finished {
+            return None;
+        }
+
+        let mut data = std::mem::take(&mut self.carry);
+        data.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        while let Some(start) = find_subslice(&data[pos..], OSC133_PREFIX).map(|i| pos + i) {
+            let payload_start = start + OSC133_PREFIX.len();
+            let Some((end, next)) = find_string_terminator(&data, payload_start) else {
+                if data.len() - start <= MAX_PENDING_MARKER {
+                    self.collect(&data[pos..start]);
+                    self.carry = data[start..].to_vec();
+                    return None;
+                }
+                // Too long to be a marker; treat as ordinary output.
+                break;
+            };
+
+            self.collect(&data[pos..start]);
+            pos = next;
+            match (self.phase, parse_osc133_payload(&data[payload_start..end])) {
+                (Phase::Waiting, Some(Osc133Mark::OutputStart)) => {
+                    self.phase = Phase::Capturing;
+                    self.started_at = Some(SystemTime::now());
+                }
+                (Phase::Capturing, Some(Osc133Mark::CommandEnd(exit_code))) => {
+                    self.phase = Phase::Finished;
+                    let finished_at = SystemTime::now();
+                    return Some(CapturedCommand {
+                        output: std::mem::take(&mut self.output),
+                        exit_code,
+                        started_at: self.started_at.unwrap_or(finished_at),
+                        finished_at,
+                        truncated: self.truncated,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // Hold back a trailing partial prefix (e.g. a lone ESC) for the next chunk.
+        let tail = partial_prefix_len(&data[pos..]);
+        let keep_from = data.len() - tail;
+        self.collect(&data[pos..keep_from]);
+        self.carry = data[keep_from..].to_vec();
+        None
+    }
+
+    fn collect(&mut self, bytes: &[u8]) {
+        if self.phase != Phase::Capturing || bytes.is_empty() {
+            return;
+        }
+        let room = self.limit.saturating_sub(self.output.len());
+        if bytes.len() > room {
+            self.truncated = true;
+        }
+        self.output
+            .extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Length of the longest suffix of `data` that is a proper prefix of
+/// [`OSC133_PREFIX`].
+fn partial_prefix_len(data: &[u8]) -> usize {
+    (1..OSC133_PREFIX.len())
+        .rev()
+        .find(|&n| data.len() >= n && data.ends_with(&OSC133_PREFIX[..n]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(capture: &mut CommandCapture, chunks: &[&[u8]]) -> Option<CapturedCommand> {
+        let mut result = None;
+        for chunk in chunks {
+            if let Some(done) = capture.feed(chunk) {
+                result = Some(done);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_captures_between_c_and_d() {
+        let mut capture = CommandCapture::new(1024);
+        let done = capture
+            .feed(b"ls\r\n\x1b]133;C\x07a.txt\r\nb.txt\r\n\x1b]133;D;0\x07\x1b]133;A\x07$ ")
+            .unwrap();
+        assert_eq!(done.output, b"a.txt\r\nb.txt\r\n");
+        assert_eq!(done.exit_code, Some(0));
+        assert!(!done.truncated);
+        assert!(done.finished_at >= done.started_at);
+    }
+
+    #[test]
+    fn test_markers_split_across_chunks() {
+        let mut capture = CommandCapture::new(1024);
+        let done = feed_all(
+            &mut capture,
+            &[
+                b"echo hi\r\n\x1b",
+                b"]13",
+                b"3;C\x1b",
+                b"\\hi\r\n\x1b]133;D",
+                b";2\x1b\\",
+            ],
+        )
+        .unwrap();
+        assert_eq!(done.output, b"hi\r\n");
+        assert_eq!(done.exit_code, Some(2));
+    }
+
+    #[test]
+    fn test_other_escapes_are_kept() {
+        let mut capture = CommandCapture::new(1024);
+        let done = capture
+            .feed(b"\x1b]133;C\x07\x1b[31mred\x1b[0m\x1b]0;title\x07\x1b]133;D\x07")
+            .unwrap();
+        assert_eq!(done.output, b"\x1b[31mred\x1b[0m\x1b]0;title\x07");
+        assert_eq!(done.exit_code, None);
+    }
+
+    #[test]
+    fn test_nothing_before_output_start() {
+        let mut capture = CommandCapture::new(1024);
+        assert!(capture.feed(b"\x1b]133;D;1\x07stale").is_none());
+        assert!(!capture.is_started());
+        assert!(capture.feed(b"\x1b]133;C\x07").is_none());
+        assert!(capture.is_started());
+        let done = capture.feed(b"fresh\x1b]133;D;0\x07").unwrap();
+        assert_eq!(done.output, b"fresh");
+    }
+
+    #[test]
+    fn test_output_is_truncated_at_limit() {
+        let mut capture = CommandCapture::new(4);
+        let done = feed_all(
+            &mut capture,
+            &[b"\x1b]133;C\x07abc", b"defg\x1b]133;D;0\x07"],
+        )
+        .unwrap();
+        assert_eq!(done.output, b"abcd");
+        assert!(done.truncated);
+    }
+
+    #[test]
+    fn test_finished_capture_ignores_further_input() {
+        let mut capture = CommandCapture::new(1024);
+        assert!(capture.feed(b"\x1b]133;C\x07x\x1b]133;D;0\x07").is_some());
+        assert!(capture.feed(b"\x1b]133;C\x07y\x1b]133;D;0\x07").is_none());
+    }
+}
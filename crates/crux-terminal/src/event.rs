@@ -3,6 +3,8 @@ use std::sync::mpsc;
 use alacritty_terminal::event::{Event as AlacEvent, EventListener};
 use alacritty_terminal::vte::ansi::CursorShape;
 
+use crate::tmux::TmuxNotification;
+
 /// Semantic zone types from OSC 133 (FinalTerm) shell integration.
 ///
 /// Shells that support prompt marking emit OSC 133 sequences to delimit
@@ -79,8 +81,25 @@ pub enum TerminalEvent {
         old_shape: CursorShape,
         new_shape: CursorShape,
     },
+    /// A file sent with the iTerm2 protocol for download rather than display
+    /// (`OSC 1337 ; File=inline=0`). Where to save it is up to the UI.
+    FileDownload {
+        /// File name the client gave, if any. Not sanitized.
+        name: Option<String>,
+        data: Vec<u8>,
+    },
+    /// tmux control-mode notification (`tmux -CC`). Pane output is routed
+    /// to remote terminals directly and never appears here.
+    Tmux(TmuxNotification),
 }
 
+/// Primary device attributes as alacritty_terminal reports them (VT102).
+const ALACRITTY_DA1: &str = "\x1b[?6c";
+
+/// Primary device attributes we report instead: a VT220 with Sixel
+/// graphics (4), which tools like `lsix` check for before drawing.
+const CRUX_DA1: &str = "\x1b[?62;4c";
+
 /// Bridges alacritty_terminal events into our channel-based system.
 pub struct CruxEventListener {
     sender: mpsc::Sender<TerminalEvent>,
@@ -98,6 +117,9 @@ impl EventListener for CruxEventListener {
             AlacEvent::Wakeup => Some(TerminalEvent::Wakeup),
             AlacEvent::Title(title) => Some(TerminalEvent::Title(title)),
             AlacEvent::Bell => Some(TerminalEvent::Bell),
+            AlacEvent::PtyWrite(text) if text == ALACRITTY_DA1 => {
+                Some(TerminalEvent::PtyWrite(CRUX_DA1.to_string()))
+            }
             AlacEvent::PtyWrite(text) => Some(TerminalEvent::PtyWrite(text)),
             AlacEvent::ChildExit(code) => Some(TerminalEvent::ProcessExit(code)),
             AlacEvent::ColorRequest(_idx, format_fn) => {
@@ -142,6 +164,22 @@ mod tests {
         assert!(matches!(event, TerminalEvent::Wakeup));
     }
 
+    #[test]
+    fn test_primary_device_attributes_report_sixel() {
+        let (tx, rx) = mpsc::channel();
+        let listener = CruxEventListener::new(tx);
+        listener.send_event(AlacEvent::PtyWrite("\x1b[?6c".into()));
+        listener.send_event(AlacEvent::PtyWrite("\x1b[0n".into()));
+        let replies: Vec<_> = rx
+            .try_iter()
+            .map(|event| match event {
+                TerminalEvent::PtyWrite(text) => text,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect();
+        assert_eq!(replies, ["\x1b[?62;4c", "\x1b[0n"]);
+    }
+
     #[test]
     fn test_title_event_mapping() {
         let (tx, rx) = mpsc::channel();
@@ -0,0 +1,623 @@
+//! Inline images.
+//!
+//! Kitty graphics commands, iTerm2 file transfers and Sixel images run on
+//! the reader thread, between the bytes around them, so they see the
+//! cursor where the client left it and can move it past the image before
+//! any later output is parsed. Their images are read and decoded before
+//! the terminal is locked.
+//!
+//! The same thread keeps placements on the text they cover: lines
+//! scrolling into the scrollback take placements with them, erasing the
+//! display deletes the placements on it, and switching to the alternate
+//! screen switches to its own placements.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::term::{Term, TermMode};
+use alacritty_terminal::vte::ansi::Processor;
+use alacritty_terminal::vte::{Params, Parser, Perform};
+use crux_graphics::protocol::iterm2::{parse_iterm2_command, Dimension, Iterm2Command};
+use crux_graphics::protocol::kitty::parse_kitty_command;
+use crux_graphics::protocol::sixel::decode_sixel;
+use crux_graphics::{
+    CellContext, GraphicsError, ImageData, ImageId, ImageManager, Iterm2Outcome, LoadedIterm2,
+    LoadedKitty,
+};
+
+use crate::event::{CruxEventListener, TerminalEvent};
+use crate::graphics_scanner::KittyGraphicsScanner;
+
+/// Images and placements of one terminal, shared between the reader
+/// thread, which executes graphics commands, and the renderer.
+#[derive(Debug, Clone)]
+pub struct Graphics {
+    inner: Arc<Mutex<GraphicsState>>,
+}
+
+#[derive(Debug)]
+struct GraphicsState {
+    images: ImageManager,
+    cell_width: u32,
+    cell_height: u32,
+    /// The terminal's scrollback limit, restored after counting scrolls.
+    scrollback_lines: usize,
+}
+
+impl Graphics {
+    pub(crate) fn new(cell_width: f32, cell_height: f32, scrollback_lines: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(GraphicsState {
+                images: {
+                    let mut images = ImageManager::new();
+                    images.set_scrollback_limit(scrollback_lines);
+                    images
+                },
+                cell_width: cell_width.round() as u32,
+                cell_height: cell_height.round() as u32,
+                scrollback_lines,
+            })),
+        }
+    }
+
+    /// Run `f` with the image store, e.g. to collect the placements to
+    /// draw and the frames animated images show. Placement rows are
+    /// absolute lines; [`ImageManager::top_line`] is the top of the
+    /// screen.
+    pub fn with_images<R>(&self, f: impl FnOnce(&mut ImageManager) -> R) -> R {
+        f(&mut self.lock().images)
+    }
+
+    /// Show a PNG, JPEG or GIF file at the cursor of `term`, `columns` by
+    /// `rows` cells, without moving the cursor. A missing size is worked
+    /// out as for an iTerm2 inline image. The image is decoded before
+    /// `term` is locked.
+    pub(crate) fn show_image(
+        &self,
+        term: &FairMutex<Term<CruxEventListener>>,
+        data: Vec<u8>,
+        columns: Option<u32>,
+        rows: Option<u32>,
+    ) -> Result<ImageId, GraphicsError> {
+        let cmd = Iterm2Command {
+            width: columns.map_or(Dimension::Auto, Dimension::Cells),
+            height: rows.map_or(Dimension::Auto, Dimension::Cells),
+            inline: true,
+            hold_cursor: true,
+            data,
+            ..Iterm2Command::default()
+        };
+        let loaded = LoadedIterm2::load(cmd);
+        let term = term.lock();
+        let mut state = self.lock();
+        let context = state.context(&term);
+        match state.images.execute_loaded_iterm2(loaded, &context)? {
+            Iterm2Outcome::Displayed { image_id, .. } => Ok(image_id),
+            Iterm2Outcome::Download { .. } => Err(GraphicsError::ParseError(
+                "image was not displayed inline".into(),
+            )),
+        }
+    }
+
+    pub(crate) fn set_cell_size(&self, cell_width: f32, cell_height: f32) {
+        let mut state = self.lock();
+        state.cell_width = cell_width.round() as u32;
+        state.cell_height = cell_height.round() as u32;
+    }
+
+    pub(crate) fn set_scrollback_lines(&self, lines: usize) {
+        let mut state = self.lock();
+        state.scrollback_lines = lines;
+        state.images.set_scrollback_limit(lines);
+    }
+
+    /// Follow the lines a resize moved between the screen and the
+    /// scrollback.
+    pub(crate) fn track_resize(&self, history_before: usize, history_after: usize) {
+        let mut state = self.lock();
+        let lines = |n: usize| n.min(u32::MAX as usize) as u32;
+        if history_after > history_before {
+            state
+                .images
+                .scroll_up(lines(history_after - history_before));
+        } else {
+            state
+                .images
+                .scroll_down(lines(history_before - history_after));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, GraphicsState> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A point in the PTY output where the reader has to stop parsing and act.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GraphicsCut {
+    /// A complete Kitty graphics command, cut after its terminator.
+    Kitty(Vec<u8>),
+    /// The arguments and data of an iTerm2 `OSC 1337 ; File=` transfer,
+    /// cut after its terminator.
+    Iterm2(Vec<u8>),
+    /// The parameters and data of a Sixel DCS sequence, cut after its
+    /// terminator.
+    Sixel { params: Vec<u16>, data: Vec<u8> },
+    /// An erase of the display, cut before its introducer so placements
+    /// are deleted where they were before it ran.
+    Erase(Erase),
+    /// A switch between the primary and alternate screen of the given
+    /// length, cut before its introducer. It is parsed on its own.
+    ScreenSwitch(usize),
+}
+
+/// A [`GraphicsCut`] with its image read and decoded, ready to be applied
+/// to the terminal.
+pub(crate) enum LoadedCut {
+    /// `None` for a chunk of a transfer that is not complete yet, or a
+    /// command that did not parse.
+    Kitty(Option<LoadedKitty>),
+    Iterm2(Result<LoadedIterm2, GraphicsError>),
+    Sixel(Result<ImageData, GraphicsError>),
+    Erase(Erase),
+    ScreenSwitch(usize),
+}
+
+/// Display erases that delete placements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Erase {
+    /// ED 2: the visible screen.
+    Screen,
+    /// ED 3: the scrollback.
+    Scrollback,
+    /// RIS: everything, including the image data.
+    Reset,
+}
+
+/// Reader-thread side of [`Graphics`].
+pub(crate) struct GraphicsTracker {
+    graphics: Graphics,
+    scanner: KittyGraphicsScanner,
+    parser: Parser,
+    performer: CutPerformer,
+    /// A string cut whose terminating ESC ended the previous chunk.
+    pending: Option<GraphicsCut>,
+}
+
+impl GraphicsTracker {
+    pub(crate) fn new(graphics: Graphics) -> Self {
+        Self {
+            graphics,
+            scanner: KittyGraphicsScanner::new(),
+            parser: Parser::new(),
+            performer: CutPerformer::default(),
+            pending: None,
+        }
+    }
+
+    /// Find the points in `chunk` where graphics state changes, in order.
+    pub(crate) fn scan(&mut self, chunk: &[u8]) -> Vec<(usize, GraphicsCut)> {
+        let mut cuts: Vec<_> = self
+            .scanner
+            .scan(chunk)
+            .into_iter()
+            .map(|(end, payload)| (end, GraphicsCut::Kitty(payload)))
+            .collect();
+
+        let mut i = 0;
+        if let Some(cut) = self.pending.take() {
+            i = self.finish_st(chunk, 0);
+            cuts.push((i, cut));
+        }
+        while i < chunk.len() {
+            i += self
+                .parser
+                .advance_until_terminated(&mut self.performer, &chunk[i..]);
+            let Some(found) = self.performer.found.take() else {
+                continue;
+            };
+            // Control sequences cannot contain ESC, so the last one starts
+            // the sequence. It may have started in an earlier chunk.
+            let start = chunk[..i]
+                .iter()
+                .rposition(|&b| b == 0x1b || b == 0x9b)
+                .unwrap_or(0);
+            let cut = match found {
+                Found::Erase(erase) => {
+                    cuts.push((start, GraphicsCut::Erase(erase)));
+                    continue;
+                }
+                Found::ScreenSwitch => {
+                    cuts.push((start, GraphicsCut::ScreenSwitch(i - start)));
+                    continue;
+                }
+                Found::Iterm2(payload) => GraphicsCut::Iterm2(payload),
+                Found::Sixel { params, data } => GraphicsCut::Sixel { params, data },
+            };
+            // OSC and DCS strings are cut at their end. The parser stops on
+            // the ESC of an `ESC \` terminator; cutting there would leave
+            // the terminal's parser in the middle of it.
+            if chunk[i - 1] == 0x1b {
+                if i == chunk.len() {
+                    self.pending = Some(cut);
+                    break;
+                }
+                i = self.finish_st(chunk, i);
+            }
+            cuts.push((i, cut));
+        }
+        cuts.sort_by_key(|&(offset, _)| offset);
+        cuts
+    }
+
+    /// Skip the `\` of an `ESC \` terminator at `chunk[i]`, if it is
+    /// there. Returns the offset after it.
+    fn finish_st(&mut self, chunk: &[u8], i: usize) -> usize {
+        if chunk.get(i) != Some(&b'\\') {
+            return i;
+        }
+        self.parser.advance(&mut self.performer, b"\\");
+        i + 1
+    }
+
+    /// Parse `bytes` into `term`, counting the lines that scrolled off the
+    /// top of the screen.
+    pub(crate) fn advance(
+        &self,
+        parser: &mut Processor,
+        term: &mut Term<CruxEventListener>,
+        bytes: &[u8],
+    ) {
+        let scrollback_lines = {
+            let state = self.graphics.lock();
+            if state.images.placement_count() == 0 {
+                drop(state);
+                parser.advance(term, bytes);
+                return;
+            }
+            if term.mode().contains(TermMode::ALT_SCREEN) {
+                0
+            } else {
+                state.scrollback_lines
+            }
+        };
+
+        // Once the scrollback is full it stops growing, so lift the limit
+        // while parsing: every scrolled line then shows up in the history
+        // size. Restoring it drops the same oldest lines the terminal
+        // would have dropped. The alternate screen, which has no
+        // scrollback, gets one just as long.
+        let before = term.grid().history_size();
+        let most_scrolled = bytes.len().saturating_mul(term.screen_lines());
+        term.grid_mut()
+            .update_history(scrollback_lines.saturating_add(most_scrolled));
+        parser.advance(term, bytes);
+        let scrolled = term.grid().history_size().saturating_sub(before);
+        term.grid_mut().update_history(scrollback_lines);
+
+        if scrolled > 0 {
+            let lines = scrolled.min(u32::MAX as usize) as u32;
+            self.graphics.lock().images.scroll_up(lines);
+        }
+    }
+
+    /// Read and decode the image of `cut`, which does not need the
+    /// terminal. Kitty transfers sent in chunks are put together here.
+    pub(crate) fn load(&self, cut: GraphicsCut) -> LoadedCut {
+        match cut {
+            GraphicsCut::Kitty(payload) => {
+                let cmd = match parse_kitty_command(&payload) {
+                    Ok(cmd) => cmd,
+                    Err(e) => {
+                        log::debug!("ignoring Kitty graphics command: {}", e);
+                        return LoadedCut::Kitty(None);
+                    }
+                };
+                let cmd = self.graphics.lock().images.collect_kitty_chunks(cmd);
+                LoadedCut::Kitty(cmd.map(LoadedKitty::load))
+            }
+            GraphicsCut::Iterm2(payload) => {
+                LoadedCut::Iterm2(parse_iterm2_command(&payload).map(LoadedIterm2::load))
+            }
+            GraphicsCut::Sixel { params, data } => LoadedCut::Sixel(decode_sixel(&params, &data)),
+            GraphicsCut::Erase(erase) => LoadedCut::Erase(erase),
+            GraphicsCut::ScreenSwitch(len) => LoadedCut::ScreenSwitch(len),
+        }
+    }
+
+    /// Act on `cut`. `rest` is the output after the cut; returns how many
+    /// bytes of it were parsed.
+    pub(crate) fn apply(
+        &self,
+        cut: LoadedCut,
+        parser: &mut Processor,
+        term: &mut Term<CruxEventListener>,
+        event_tx: &mpsc::Sender<TerminalEvent>,
+        rest: &[u8],
+    ) -> usize {
+        match cut {
+            LoadedCut::Kitty(None) => 0,
+            LoadedCut::Kitty(Some(loaded)) => {
+                self.execute_kitty(loaded, parser, term, event_tx);
+                0
+            }
+            LoadedCut::Iterm2(loaded) => {
+                self.execute_iterm2(loaded, parser, term, event_tx);
+                0
+            }
+            LoadedCut::Sixel(image) => {
+                let advance = image.and_then(|image| {
+                    let mut state = self.graphics.lock();
+                    let context = state.context(term);
+                    state.images.place_sixel(image, &context)
+                });
+                match advance {
+                    Ok(advance) => self.move_cursor(parser, term, advance),
+                    Err(e) => log::debug!("ignoring Sixel image: {}", e),
+                }
+                0
+            }
+            LoadedCut::Erase(erase) => {
+                let mut state = self.graphics.lock();
+                match erase {
+                    Erase::Screen => state.images.clear_screen(term.screen_lines() as u32),
+                    Erase::Scrollback => state.images.clear_scrollback(),
+                    Erase::Reset => state.images.delete_all(),
+                }
+                0
+            }
+            LoadedCut::ScreenSwitch(len) => {
+                let len = len.min(rest.len());
+                parser.advance(term, &rest[..len]);
+                let alternate = term.mode().contains(TermMode::ALT_SCREEN);
+                self.graphics.lock().images.switch_screen(alternate);
+                len
+            }
+        }
+    }
+
+    fn execute_kitty(
+        &self,
+        loaded: LoadedKitty,
+        parser: &mut Processor,
+        term: &mut Term<CruxEventListener>,
+        event_tx: &mpsc::Sender<TerminalEvent>,
+    ) {
+        let outcome = {
+            let mut state = self.graphics.lock();
+            let context = state.context(term);
+            state.images.execute_loaded_kitty(loaded, &context)
+        };
+
+        if let Some(reply) = outcome.reply {
+            let _ = event_tx.send(TerminalEvent::PtyWrite(reply));
+        }
+        if let Some(advance) = outcome.cursor_advance {
+            self.move_cursor(parser, term, advance);
+        }
+    }
+
+    fn execute_iterm2(
+        &self,
+        loaded: Result<LoadedIterm2, GraphicsError>,
+        parser: &mut Processor,
+        term: &mut Term<CruxEventListener>,
+        event_tx: &mpsc::Sender<TerminalEvent>,
+    ) {
+        let outcome = loaded.and_then(|loaded| {
+            let mut state = self.graphics.lock();
+            let context = state.context(term);
+            state.images.execute_loaded_iterm2(loaded, &context)
+        });
+        match outcome {
+            Ok(Iterm2Outcome::Displayed {
+                cursor_advance: Some(advance),
+                ..
+            }) => self.move_cursor(parser, term, advance),
+            Ok(Iterm2Outcome::Displayed {
+                cursor_advance: None,
+                ..
+            }) => {}
+            Ok(Iterm2Outcome::Download { name, data }) => {
+                let _ = event_tx.send(TerminalEvent::FileDownload { name, data });
+            }
+            Err(e) => log::debug!("ignoring iTerm2 file transfer: {}", e),
+        }
+    }
+
+    /// Move the cursor past an image: `rows` lines down, then `columns`
+    /// right.
+    fn move_cursor(
+        &self,
+        parser: &mut Processor,
+        term: &mut Term<CruxEventListener>,
+        (columns, rows): (u32, u32),
+    ) {
+        // IND moves down a line, scrolling at the bottom, without the
+        // carriage return LF adds in newline mode.
+        let mut moves = "\x1bD".repeat(rows as usize);
+        if columns > 0 {
+            moves.push_str(&format!("\x1b[{columns}C"));
+        }
+        self.advance(parser, term, moves.as_bytes());
+    }
+}
+
+impl GraphicsState {
+    /// Where a command runs: the cursor and the cell and screen size.
+    fn context(&self, term: &Term<CruxEventListener>) -> CellContext {
+        let cursor = term.grid().cursor.point;
+        CellContext {
+            cursor_column: cursor.column.0 as u32,
+            cursor_row: cursor.line.0,
+            cell_width: self.cell_width,
+            cell_height: self.cell_height,
+            screen_rows: term.screen_lines() as u32,
+            screen_columns: term.columns() as u32,
+        }
+    }
+}
+
+enum Found {
+    Erase(Erase),
+    ScreenSwitch,
+    Iterm2(Vec<u8>),
+    Sixel { params: Vec<u16>, data: Vec<u8> },
+}
+
+/// Most Sixel data collected for one image: 64 MiB.
+const MAX_SIXEL_BYTES: usize = 64 * 1024 * 1024;
+
+/// Stops the cut parser after each sequence that affects placements.
+#[derive(Default)]
+struct CutPerformer {
+    found: Option<Found>,
+    /// The Sixel image being received: its parameters and data so far.
+    /// `None` once it grew too large.
+    sixel: Option<Option<(Vec<u16>, Vec<u8>)>>,
+}
+
+impl Perform for CutPerformer {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let mut values = params.iter().map(|p| p.first().copied().unwrap_or(0));
+        self.found = match (intermediates, action) {
+            ([], 'J') => match values.next().unwrap_or(0) {
+                2 => Some(Found::Erase(Erase::Screen)),
+                3 => Some(Found::Erase(Erase::Scrollback)),
+                _ => None,
+            },
+            ([b'?'], 'h' | 'l') => values
+                .any(|mode| matches!(mode, 47 | 1047 | 1049))
+                .then_some(Found::ScreenSwitch),
+            _ => None,
+        };
+    }
+
+    fn hook(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if !ignore && intermediates.is_empty() && action == 'q' {
+            let params = params
+                .iter()
+                .map(|p| p.first().copied().unwrap_or(0))
+                .collect();
+            self.sixel = Some(Some((params, Vec::new())));
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        if let Some(sixel) = &mut self.sixel {
+            if let Some((_, data)) = sixel {
+                if data.len() < MAX_SIXEL_BYTES {
+                    data.push(byte);
+                } else {
+                    log::debug!("dropping Sixel image over {} bytes", MAX_SIXEL_BYTES);
+                    *sixel = None;
+                }
+            }
+        }
+    }
+
+    fn unhook(&mut self) {
+        if let Some(Some((params, data))) = self.sixel.take() {
+            self.found = Some(Found::Sixel { params, data });
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        // The parser splits the arguments on ';' too; put them back together.
+        if let [b"1337", first, rest @ ..] = params {
+            if let Some(first) = first.strip_prefix(b"File=") {
+                let mut payload = first.to_vec();
+                for param in rest {
+                    payload.push(b';');
+                    payload.extend_from_slice(param);
+                }
+                self.found = Some(Found::Iterm2(payload));
+            }
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], ignore: bool, byte: u8) {
+        if !ignore && intermediates.is_empty() && byte == b'c' {
+            self.found = Some(Found::Erase(Erase::Reset));
+        }
+    }
+
+    fn terminated(&self) -> bool {
+        self.found.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(chunk: &[u8]) -> Vec<(usize, GraphicsCut)> {
+        GraphicsTracker::new(Graphics::new(10.0, 20.0, 100)).scan(chunk)
+    }
+
+    #[test]
+    fn test_scan_finds_cuts_in_order() {
+        let chunk = b"ab\x1b[2Jcd\x1b_Ga=d\x1b\\\x1b[?1049h\x1b[3J\x1bc";
+        assert_eq!(
+            scan(chunk),
+            vec![
+                (2, GraphicsCut::Erase(Erase::Screen)),
+                (16, GraphicsCut::Kitty(b"a=d".to_vec())),
+                (16, GraphicsCut::ScreenSwitch(8)),
+                (24, GraphicsCut::Erase(Erase::Scrollback)),
+                (28, GraphicsCut::Erase(Erase::Reset)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_joins_split_iterm2_transfer() {
+        let mut tracker = GraphicsTracker::new(Graphics::new(10.0, 20.0, 100));
+        assert_eq!(tracker.scan(b"x\x1b]1337;File=inline=1;wid"), vec![]);
+        assert_eq!(
+            tracker.scan(b"th=2:QUJD\x07\x1b]1337;SetMark\x07"),
+            vec![(10, GraphicsCut::Iterm2(b"inline=1;width=2:QUJD".to_vec()))]
+        );
+    }
+
+    #[test]
+    fn test_scan_collects_sixel_data() {
+        let mut tracker = GraphicsTracker::new(Graphics::new(10.0, 20.0, 100));
+        assert_eq!(tracker.scan(b"\x1bP0;1q\"1;1;2;6#1"), vec![]);
+        assert_eq!(
+            tracker.scan(b"~~\x1b\\\x1bP1000p"),
+            vec![(
+                4,
+                GraphicsCut::Sixel {
+                    params: vec![0, 1],
+                    data: b"\"1;1;2;6#1~~".to_vec(),
+                }
+            )]
+        );
+
+        // The cut waits for the backslash of a terminator split in two.
+        assert_eq!(tracker.scan(b"\x1bPq~\x1b"), vec![]);
+        assert_eq!(
+            tracker.scan(b"\\x"),
+            vec![(
+                1,
+                GraphicsCut::Sixel {
+                    params: vec![0],
+                    data: b"~".to_vec(),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_scan_ignores_other_sequences() {
+        assert_eq!(scan(b"\x1b[J\x1b[1J\x1b[?25h\x1b[?2J\x1b[2K"), vec![]);
+    }
+}
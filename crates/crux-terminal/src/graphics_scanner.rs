@@ -75,6 +75,21 @@ impl KittyGraphicsScanner {
     /// This method is designed to be called on every PTY read. It handles
     /// sequences that span multiple reads by maintaining internal state.
     pub fn feed(&mut self, buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
+        for (_, payload) in self.scan(buf) {
+            log::debug!("Kitty graphics APC: {} bytes payload", payload.len());
+            let _ = event_tx.send(TerminalEvent::Graphics {
+                protocol: GraphicsProtocol::Kitty,
+                payload,
+            });
+        }
+    }
+
+    /// Like [`feed`](Self::feed), but returns the payloads instead of
+    /// sending them, each with the offset in `buf` just past its
+    /// terminator. The reader thread uses the offsets to execute commands
+    /// in order with the surrounding output.
+    pub fn scan(&mut self, buf: &[u8]) -> Vec<(usize, Vec<u8>)> {
+        let mut complete = Vec::new();
         let mut i = 0;
         while i < buf.len() {
             match self.state {
@@ -135,9 +150,11 @@ impl KittyGraphicsScanner {
                 KittyScanState::PayloadEscSeen => {
                     if buf[i] == b'\\' {
                         // ST found — sequence complete
-                        self.emit_graphics_event(event_tx);
                         self.state = KittyScanState::Ground;
                         i += 1;
+                        if !self.accumulator.is_empty() {
+                            complete.push((i, std::mem::take(&mut self.accumulator)));
+                        }
                     } else if buf[i] == b'_' {
                         // Nested ESC _ inside payload — shouldn't happen in
                         // valid Kitty protocol, but handle gracefully: treat
@@ -162,19 +179,7 @@ impl KittyGraphicsScanner {
                 }
             }
         }
-    }
-
-    /// Emit a `TerminalEvent::Graphics` with the accumulated payload.
-    fn emit_graphics_event(&mut self, event_tx: &mpsc::Sender<TerminalEvent>) {
-        if self.accumulator.is_empty() {
-            return;
-        }
-        let payload = std::mem::take(&mut self.accumulator);
-        log::debug!("Kitty graphics APC: {} bytes payload", payload.len());
-        let _ = event_tx.send(TerminalEvent::Graphics {
-            protocol: GraphicsProtocol::Kitty,
-            payload,
-        });
+        complete
     }
 
     /// Reset the scanner to the ground state, discarding any partial sequence.
@@ -456,6 +461,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scan_reports_end_offsets() {
+        let mut scanner = KittyGraphicsScanner::new();
+        let buf = b"ab\x1b_Ga=t;AA\x1b\\cd\x1b_Ga=d";
+        assert_eq!(scanner.scan(buf), vec![(13, b"a=t;AA".to_vec())]);
+        assert_eq!(scanner.scan(b"\x1b\\ef"), vec![(2, b"a=d".to_vec())]);
+    }
+
     #[test]
     fn test_stateful_non_graphics_apc_ignored() {
         let (tx, rx) = mpsc::channel();
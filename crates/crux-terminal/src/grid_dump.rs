@@ -0,0 +1,207 @@
+//! Structured per-cell dump of a grid region.
+//!
+//! Colors are reported as stored in the cell (named, indexed or RGB);
+//! resolving them against a palette is up to the caller.
+
+use std::ops::Range;
+
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line, Point};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::{Term, TermMode};
+use alacritty_terminal::vte::ansi::{Color, CursorShape};
+
+use crate::tracked_state::TrackedModes;
+
+/// One grid cell.
+#[derive(Debug, Clone)]
+pub struct CellInfo {
+    pub column: usize,
+    /// The character plus any zero-width combining characters. Empty for
+    /// the spacer half of a wide character.
+    pub text: String,
+    /// Display width in columns: 2 for wide characters, 0 for spacers.
+    pub width: u8,
+    pub fg: Color,
+    pub bg: Color,
+    pub underline_color: Option<Color>,
+    pub flags: Flags,
+    /// OSC 8 hyperlink URI.
+    pub hyperlink: Option<String>,
+}
+
+/// One grid row.
+#[derive(Debug, Clone)]
+pub struct RowInfo {
+    /// Grid line (0 = top of screen, negative = scrollback).
+    pub line: i32,
+    pub cells: Vec<CellInfo>,
+}
+
+/// A region of the grid plus the screen state needed to interpret it.
+#[derive(Debug, Clone)]
+pub struct GridDump {
+    pub rows: Vec<RowInfo>,
+    pub cursor: Point,
+    pub cursor_shape: CursorShape,
+    pub cursor_visible: bool,
+    /// Scrolling region as a half-open range of screen lines.
+    pub scroll_region: Range<usize>,
+    /// Lines the viewport is scrolled back into history.
+    pub display_offset: usize,
+    pub screen_lines: usize,
+    pub columns: usize,
+}
+
+/// Dump rows `lines` (end exclusive) restricted to columns `cols`.
+///
+/// Both ranges default to the visible screen and are clamped to the grid.
+pub(crate) fn dump_grid<T>(
+    term: &Term<T>,
+    tracked: &TrackedModes,
+    lines: Range<Option<i32>>,
+    cols: Range<Option<usize>>,
+) -> GridDump {
+    let grid = term.grid();
+    let top = -(grid.history_size() as i32);
+    let bottom = grid.screen_lines() as i32;
+    let start = lines.start.unwrap_or(0).clamp(top, bottom);
+    let end = lines.end.unwrap_or(bottom).clamp(start, bottom);
+    let col_end = cols.end.unwrap_or(grid.columns()).min(grid.columns());
+    let col_start = cols.start.unwrap_or(0).min(col_end);
+
+    let rows = (start..end)
+        .map(|line| {
+            let row = &grid[Line(line)];
+            RowInfo {
+                line,
+                cells: (col_start..col_end)
+                    .map(|col| cell_info(&row[Column(col)], col))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    GridDump {
+        rows,
+        cursor: grid.cursor.point,
+        cursor_shape: term.cursor_style().shape,
+        cursor_visible: term.mode().contains(TermMode::SHOW_CURSOR),
+        scroll_region: tracked.scroll_region(grid.screen_lines()),
+        display_offset: grid.display_offset(),
+        screen_lines: grid.screen_lines(),
+        columns: grid.columns(),
+    }
+}
+
+fn cell_info(cell: &Cell, column: usize) -> CellInfo {
+    let spacer = cell
+        .flags
+        .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER);
+    let mut text = String::new();
+    if !spacer {
+        text.push(cell.c);
+        text.extend(cell.zerowidth().into_iter().flatten());
+    }
+    let width = if spacer {
+        0
+    } else if cell.flags.contains(Flags::WIDE_CHAR) {
+        2
+    } else {
+        1
+    };
+    CellInfo {
+        column,
+        text,
+        width,
+        fg: cell.fg,
+        bg: cell.bg,
+        underline_color: cell.underline_color(),
+        flags: cell.flags,
+        hyperlink: cell.hyperlink().map(|link| link.uri().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config;
+    use alacritty_terminal::vte::ansi::{NamedColor, Processor, Rgb};
+
+    use crate::terminal::TerminalSize;
+
+    fn term_with(bytes: &[u8]) -> Term<VoidListener> {
+        let size = TerminalSize {
+            rows: 4,
+            cols: 10,
+            ..TerminalSize::default()
+        };
+        let mut term = Term::new(Config::default(), &size, VoidListener);
+        let mut parser: Processor = Processor::new();
+        parser.advance(&mut term, bytes);
+        term
+    }
+
+    fn dump(term: &Term<VoidListener>, line: i32) -> GridDump {
+        dump_grid(
+            term,
+            &TrackedModes::default(),
+            Some(line)..Some(line + 1),
+            None..None,
+        )
+    }
+
+    #[test]
+    fn test_cell_colors_and_flags() {
+        let term = term_with(b"\x1b[1;4;31;48;2;10;20;30mA\x1b[0mb");
+        let grid = dump(&term, 0);
+        let a = &grid.rows[0].cells[0];
+        assert_eq!(a.text, "A");
+        assert_eq!(a.fg, Color::Named(NamedColor::Red));
+        assert_eq!(
+            a.bg,
+            Color::Spec(Rgb {
+                r: 10,
+                g: 20,
+                b: 30
+            })
+        );
+        assert!(a.flags.contains(Flags::BOLD | Flags::UNDERLINE));
+        let b = &grid.rows[0].cells[1];
+        assert_eq!(b.fg, Color::Named(NamedColor::Foreground));
+        assert!(!b.flags.contains(Flags::BOLD));
+    }
+
+    #[test]
+    fn test_wide_char_and_hyperlink() {
+        let term = term_with("\x1b]8;;https://example.com\x07가\x1b]8;;\x07x".as_bytes());
+        let cells = &dump(&term, 0).rows[0].cells;
+        assert_eq!(cells[0].text, "가");
+        assert_eq!(cells[0].width, 2);
+        assert_eq!(cells[0].hyperlink.as_deref(), Some("https://example.com"));
+        assert_eq!(cells[1].width, 0);
+        assert_eq!(cells[1].text, "");
+        assert_eq!(cells[2].text, "x");
+        assert!(cells[2].hyperlink.is_none());
+    }
+
+    #[test]
+    fn test_region_is_clamped() {
+        let term = term_with(b"abc");
+        let grid = dump_grid(
+            &term,
+            &TrackedModes::default(),
+            Some(-50)..Some(50),
+            Some(1)..Some(3),
+        );
+        assert_eq!(grid.rows.len(), 4);
+        assert_eq!(grid.rows[0].line, 0);
+        let texts: Vec<_> = grid.rows[0].cells.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["b", "c"]);
+        assert_eq!(grid.rows[0].cells[0].column, 1);
+        assert_eq!(grid.cursor, Point::new(Line(0), Column(3)));
+        assert!(grid.cursor_visible);
+        assert_eq!(grid.scroll_region, 0..4);
+    }
+}
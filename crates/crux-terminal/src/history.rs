@@ -0,0 +1,458 @@
+//! Per-pane command history built from OSC 133 shell integration.
+//!
+//! The PTY reader thread feeds every prompt mark to a [`HistoryRecorder`]
+//! right after the VTE parser has consumed the bytes preceding it, so the
+//! cursor position (and therefore the command text and output range) is
+//! exact even when a whole command completes within one UI frame.
+//! Completed commands land in a shared [`CommandHistory`].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line, Point};
+use alacritty_terminal::term::Term;
+
+use crate::osc_scanner::{Osc133Mark, ShellMark};
+
+/// Maximum number of commands kept per pane.
+const MAX_HISTORY_ENTRIES: usize = 1_000;
+
+/// Maximum number of trailing output lines kept per command.
+const MAX_OUTPUT_LINES: i32 = 200;
+
+/// A completed command.
+#[derive(Debug, Clone)]
+pub struct CommandRecord {
+    /// Monotonic per-pane identifier.
+    pub id: u64,
+    /// Command line as it appeared between `133;B` and `133;C`.
+    pub command: String,
+    /// Working directory (OSC 7) when the command started.
+    pub cwd: Option<String>,
+    /// Exit status from `133;D;N`, if the shell reported one.
+    pub exit_code: Option<i32>,
+    /// When `133;C` was seen.
+    pub started_at: SystemTime,
+    /// When `133;D` was seen.
+    pub finished_at: SystemTime,
+    /// First grid line of the output when the command finished
+    /// (0 = top of screen, negative = scrollback).
+    pub output_start_line: i32,
+    /// Last grid line of the output when the command finished.
+    pub output_end_line: i32,
+    /// Output text, limited to the last [`MAX_OUTPUT_LINES`] lines.
+    pub output: String,
+    /// Output was longer than what `output` holds.
+    pub output_truncated: bool,
+}
+
+impl CommandRecord {
+    /// Whether the command reported a non-zero exit status.
+    pub fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+/// Which commands [`CommandHistory::query`] returns by exit status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExitStatusFilter {
+    #[default]
+    Any,
+    /// Exit code 0.
+    Success,
+    /// Non-zero exit code.
+    Failure,
+}
+
+/// Selection criteria for [`CommandHistory::query`].
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub status: ExitStatusFilter,
+    /// Only commands that finished at or after this time.
+    pub since: Option<SystemTime>,
+    /// Maximum number of records to return.
+    pub limit: Option<usize>,
+}
+
+/// Shared, bounded list of completed commands for one terminal.
+#[derive(Debug, Clone, Default)]
+pub struct CommandHistory {
+    inner: Arc<Mutex<VecDeque<CommandRecord>>>,
+}
+
+impl CommandHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of recorded commands.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Most recent command, if any.
+    pub fn last(&self) -> Option<CommandRecord> {
+        self.lock().back().cloned()
+    }
+
+    /// Matching commands, newest first.
+    pub fn query(&self, filter: &HistoryFilter) -> Vec<CommandRecord> {
+        let records = self.lock();
+        records
+            .iter()
+            .rev()
+            .filter(|r| match filter.status {
+                ExitStatusFilter::Any => true,
+                ExitStatusFilter::Success => r.exit_code == Some(0),
+                ExitStatusFilter::Failure => r.failed(),
+            })
+            .filter(|r| filter.since.is_none_or(|since| r.finished_at >= since))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn push(&self, record: CommandRecord) {
+        let mut records = self.lock();
+        if records.len() == MAX_HISTORY_ENTRIES {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<CommandRecord>> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A command between `133;C` and `133;D`.
+#[derive(Debug)]
+struct RunningCommand {
+    command: String,
+    cwd: Option<String>,
+    started_at: SystemTime,
+    /// Output start as (absolute row, column); see [`absolute_row`].
+    output_start: (i64, usize),
+    /// Text of the row above the output (normally the command line), used
+    /// to relocate the output once absolute rows drift.
+    anchor: Option<String>,
+}
+
+/// Reader-thread state machine turning shell marks into [`CommandRecord`]s.
+#[derive(Debug)]
+pub(crate) struct HistoryRecorder {
+    history: CommandHistory,
+    cwd: Option<String>,
+    /// Where the user started typing (`133;B`), as (absolute row, column).
+    input_start: Option<(i64, usize)>,
+    running: Option<RunningCommand>,
+    next_id: u64,
+}
+
+impl HistoryRecorder {
+    pub(crate) fn new(history: CommandHistory) -> Self {
+        Self {
+            history,
+            cwd: None,
+            input_start: None,
+            running: None,
+            next_id: 1,
+        }
+    }
+
+    /// Observe a mark. `term` must reflect all output up to and including it.
+    pub(crate) fn observe<T: EventListener>(&mut self, mark: &ShellMark, term: &Term<T>) {
+        let cursor = term.grid().cursor.point;
+        match mark {
+            ShellMark::Cwd(path) => self.cwd = Some(path.clone()),
+            ShellMark::Prompt(Osc133Mark::PromptStart) => {
+                // A new prompt without `133;D` abandons whatever was running.
+                self.input_start = None;
+                self.running = None;
+            }
+            ShellMark::Prompt(Osc133Mark::CommandStart) => {
+                self.input_start = Some((absolute_row(term, cursor.line), cursor.column.0));
+            }
+            ShellMark::Prompt(Osc133Mark::OutputStart) => {
+                let command = self
+                    .input_start
+                    .take()
+                    .map(|(row, col)| {
+                        let start = Point::new(grid_line(term, row), Column(col));
+                        text_before(term, start, cursor).trim().to_string()
+                    })
+                    .unwrap_or_default();
+                let anchor =
+                    (cursor.line > term.topmost_line()).then(|| row_text(term, cursor.line - 1));
+                self.running = Some(RunningCommand {
+                    command,
+                    cwd: self.cwd.clone(),
+                    started_at: SystemTime::now(),
+                    output_start: (absolute_row(term, cursor.line), cursor.column.0),
+                    anchor,
+                });
+            }
+            ShellMark::Prompt(Osc133Mark::CommandEnd(exit_code)) => {
+                let Some(running) = self.running.take() else {
+                    return;
+                };
+                let (row, col) = running.output_start;
+                let mut start = Point::new(grid_line(term, row), Column(col));
+                let mut output_truncated = false;
+                if let Some(anchor) = &running.anchor {
+                    match find_anchor(term, anchor, start.line, cursor.line) {
+                        Some(line) => start.line = line,
+                        // Scrolled out or redrawn; the beginning is unknown.
+                        None => output_truncated = true,
+                    }
+                }
+                if start.line < term.topmost_line() {
+                    start = Point::new(term.topmost_line(), Column(0));
+                    output_truncated = true;
+                }
+                if cursor.line.0 - start.line.0 >= MAX_OUTPUT_LINES {
+                    start = Point::new(cursor.line - (MAX_OUTPUT_LINES - 1), Column(0));
+                    output_truncated = true;
+                }
+                let id = self.next_id;
+                self.next_id += 1;
+                self.history.push(CommandRecord {
+                    id,
+                    command: running.command,
+                    cwd: running.cwd,
+                    exit_code: *exit_code,
+                    started_at: running.started_at,
+                    finished_at: SystemTime::now(),
+                    output_start_line: start.line.0,
+                    output_end_line: cursor.line.0,
+                    output: text_before(term, start, cursor),
+                    output_truncated,
+                });
+            }
+        }
+    }
+}
+
+/// Row index counted from the top of scrollback. Unlike [`Line`], it does
+/// not change as new output scrolls the screen — until scrollback is full,
+/// after which every scrolled line shifts it by one.
+fn absolute_row<T>(term: &Term<T>, line: Line) -> i64 {
+    i64::from(line.0) + term.grid().history_size() as i64
+}
+
+fn grid_line<T>(term: &Term<T>, row: i64) -> Line {
+    let line = row - term.grid().history_size() as i64;
+    Line(line.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32)
+}
+
+/// Locate the output start line by finding `anchor` on the row above it.
+///
+/// `expected` is where the output starts if no rows were evicted from
+/// scrollback; evictions only ever move the real start further up, so
+/// search upward from there for the nearest matching row.
+fn find_anchor<T>(term: &Term<T>, anchor: &str, expected: Line, cursor: Line) -> Option<Line> {
+    let expected = expected.min(cursor);
+    let mut line = expected;
+    while line > term.topmost_line() {
+        if row_text(term, line - 1) == anchor {
+            return Some(line);
+        }
+        line -= 1;
+    }
+    None
+}
+
+fn row_text<T>(term: &Term<T>, line: Line) -> String {
+    term.bounds_to_string(
+        Point::new(line, Column(0)),
+        Point::new(line, term.last_column()),
+    )
+}
+
+/// Text from `start` up to (not including) `end`.
+fn text_before<T>(term: &Term<T>, start: Point, end: Point) -> String {
+    let start = start.max(Point::new(term.topmost_line(), Column(0)));
+    let end = if end.column.0 > 0 {
+        Point::new(end.line, end.column - 1)
+    } else if end.line > start.line {
+        Point::new(end.line - 1, term.last_column())
+    } else {
+        return String::new();
+    };
+    if end < start {
+        return String::new();
+    }
+    term.bounds_to_string(start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    use crate::osc_scanner::scan_shell_marks;
+    use crate::terminal::TerminalSize;
+
+    struct Harness {
+        term: Term<VoidListener>,
+        parser: Processor,
+        recorder: HistoryRecorder,
+        history: CommandHistory,
+    }
+
+    impl Harness {
+        fn new(rows: usize) -> Self {
+            let size = TerminalSize {
+                rows,
+                cols: 40,
+                ..TerminalSize::default()
+            };
+            let config = Config {
+                scrolling_history: 100,
+                ..Config::default()
+            };
+            let history = CommandHistory::new();
+            Self {
+                term: Term::new(config, &size, VoidListener),
+                parser: Processor::new(),
+                recorder: HistoryRecorder::new(history.clone()),
+                history,
+            }
+        }
+
+        /// Same split-and-observe sequence as the PTY read loop.
+        fn feed(&mut self, bytes: &[u8]) {
+            let mut start = 0;
+            for (end, mark) in scan_shell_marks(bytes) {
+                self.parser.advance(&mut self.term, &bytes[start..end]);
+                start = end;
+                self.recorder.observe(&mark, &self.term);
+            }
+            self.parser.advance(&mut self.term, &bytes[start..]);
+        }
+
+        fn run(&mut self, command: &str, output: &str, exit_code: i32) {
+            self.feed(
+                format!(
+                    "\x1b]133;A\x07$ \x1b]133;B\x07{command}\r\n\x1b]133;C\x07{output}\x1b]133;D;{exit_code}\x07"
+                )
+                .as_bytes(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_records_command_output_and_exit_code() {
+        let mut h = Harness::new(10);
+        h.feed(b"\x1b]7;file:///home/user/project\x07");
+        h.run("make test", "error: boom\r\n", 2);
+
+        let record = h.history.last().unwrap();
+        assert_eq!(record.id, 1);
+        assert_eq!(record.command, "make test");
+        assert_eq!(record.cwd.as_deref(), Some("/home/user/project"));
+        assert_eq!(record.exit_code, Some(2));
+        assert!(record.failed());
+        assert_eq!(record.output, "error: boom");
+        assert_eq!(record.output_start_line, 1);
+        assert_eq!(record.output_end_line, 2);
+        assert!(!record.output_truncated);
+    }
+
+    #[test]
+    fn test_command_without_output() {
+        let mut h = Harness::new(10);
+        h.run("true", "", 0);
+        let record = h.history.last().unwrap();
+        assert_eq!(record.command, "true");
+        assert_eq!(record.output, "");
+    }
+
+    #[test]
+    fn test_output_range_survives_scrolling() {
+        let mut h = Harness::new(5);
+        let output: String = (0..8).map(|i| format!("line {i}\r\n")).collect();
+        h.run("seq", &output, 0);
+
+        let record = h.history.last().unwrap();
+        assert_eq!(record.command, "seq");
+        assert!(record.output.starts_with("line 0\n"));
+        assert!(record.output.ends_with("line 7"));
+        assert_eq!(record.output_end_line - record.output_start_line, 8);
+    }
+
+    #[test]
+    fn test_output_located_with_full_scrollback() {
+        let mut h = Harness::new(5);
+        let filler: String = (0..150).map(|i| format!("filler {i}\r\n")).collect();
+        h.feed(filler.as_bytes());
+        h.run("ls", "a\r\nb\r\nc\r\n", 0);
+
+        let record = h.history.last().unwrap();
+        assert_eq!(record.output, "a\nb\nc");
+        assert!(!record.output_truncated);
+    }
+
+    #[test]
+    fn test_long_output_keeps_tail() {
+        let mut h = Harness::new(5);
+        let output: String = (0..300).map(|i| format!("{i}\r\n")).collect();
+        h.run("big", &output, 0);
+
+        let record = h.history.last().unwrap();
+        assert!(record.output_truncated);
+        assert!(record.output.ends_with("299"));
+        assert!(!record.output.contains("\n0\n"));
+    }
+
+    #[test]
+    fn test_end_without_start_is_ignored() {
+        let mut h = Harness::new(10);
+        h.feed(b"\x1b]133;A\x07$ \x1b]133;D;130\x07");
+        assert!(h.history.is_empty());
+    }
+
+    #[test]
+    fn test_query_filters_by_status_and_limit() {
+        let mut h = Harness::new(10);
+        h.run("ok", "", 0);
+        h.run("bad", "", 1);
+        h.run("worse", "", 127);
+
+        let failed = h.history.query(&HistoryFilter {
+            status: ExitStatusFilter::Failure,
+            ..HistoryFilter::default()
+        });
+        let names: Vec<_> = failed.iter().map(|r| r.command.as_str()).collect();
+        assert_eq!(names, ["worse", "bad"]);
+
+        let ok = h.history.query(&HistoryFilter {
+            status: ExitStatusFilter::Success,
+            ..HistoryFilter::default()
+        });
+        assert_eq!(ok.len(), 1);
+
+        let latest = h.history.query(&HistoryFilter {
+            limit: Some(1),
+            ..HistoryFilter::default()
+        });
+        assert_eq!(latest[0].command, "worse");
+
+        let future = h.history.query(&HistoryFilter {
+            since: Some(SystemTime::now() + std::time::Duration::from_secs(60)),
+            ..HistoryFilter::default()
+        });
+        assert!(future.is_empty());
+    }
+}
@@ -4,20 +4,46 @@
 //! a self-contained terminal emulator entity that can be driven from
 //! any UI framework (GPUI in our case).
 
+pub mod command_capture;
 pub mod event;
+pub mod graphics;
 pub mod graphics_scanner;
-pub(crate) mod osc_scanner;
+pub mod grid_dump;
+pub mod history;
+pub mod modes;
+pub mod osc_scanner;
+pub mod output_tap;
+pub mod palette;
+pub mod protocol;
 pub mod pty;
+pub mod remote;
+pub mod scrollback;
 pub mod terminal;
+pub mod text;
+pub mod tmux;
+pub mod tracked_state;
 pub mod traits;
 
 // Re-export primary types at crate root for convenience.
+pub use command_capture::{CapturedCommand, CommandCapture};
 pub use event::{CruxEventListener, SemanticZone, SemanticZoneType, TerminalEvent};
-pub use pty::ensure_terminfo_installed;
+pub use graphics::Graphics;
+pub use grid_dump::{CellInfo, GridDump, RowInfo};
+pub use history::{CommandHistory, CommandRecord, ExitStatusFilter, HistoryFilter};
+pub use modes::TerminalModes;
+pub use output_tap::{OutputSink, OutputSinkId, OutputTap};
+pub use pty::{ensure_terminfo_installed, PtyWriter};
+pub use remote::RemoteFeed;
 pub use terminal::{
     extract_text_lines, CruxTerminal, CursorState, DamageState, IndexedCell, LineDamage,
     TerminalContent, TerminalSize,
 };
+pub use text::GridText;
+pub use tmux::{
+    TmuxAction, TmuxController, TmuxLayout, TmuxLayoutCell, TmuxNotification, TmuxPaneId,
+    TmuxRouter, TmuxWindowId,
+};
+pub use tracked_state::{TrackedModes, TrackedState};
 pub use traits::Terminal;
 
 // Re-export commonly needed alacritty types so downstream crates
@@ -27,4 +53,6 @@ pub use alacritty_terminal::index::{Column, Direction, Line, Point, Side};
 pub use alacritty_terminal::selection::{Selection, SelectionRange, SelectionType};
 pub use alacritty_terminal::term::cell::Flags as CellFlags;
 pub use alacritty_terminal::term::TermMode;
-pub use alacritty_terminal::vte::ansi::{Color, CursorShape, NamedColor};
+pub use alacritty_terminal::vte::ansi::{
+    CharsetIndex, Color, CursorShape, CursorStyle, NamedColor, StandardCharset,
+};
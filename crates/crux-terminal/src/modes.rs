@@ -0,0 +1,118 @@
+//! Snapshot of the input-affecting terminal state an application has set:
+//! DEC private and ANSI modes, cursor style, character sets and Kitty
+//! keyboard protocol flags.
+
+use alacritty_terminal::term::{Term, TermMode};
+use alacritty_terminal::vte::ansi::{CharsetIndex, CursorStyle, StandardCharset};
+
+use crate::tracked_state::TrackedModes;
+
+/// Bit offset of the Kitty keyboard flags inside [`TermMode`].
+const KITTY_FLAGS_SHIFT: u32 = TermMode::DISAMBIGUATE_ESC_CODES.bits().trailing_zeros();
+
+/// Modes read by [`CruxTerminal::modes`](crate::CruxTerminal::modes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalModes {
+    pub mode: TermMode,
+    /// Cursor style requested via DECSCUSR, or the default.
+    pub cursor_style: CursorStyle,
+    /// Character set slot currently invoked into GL.
+    pub active_charset: CharsetIndex,
+    /// Character sets designated to G0-G3.
+    pub charsets: [StandardCharset; 4],
+}
+
+impl TerminalModes {
+    /// Kitty keyboard progressive enhancement flags as the application
+    /// pushed them (`CSI > flags u`); 0 when the protocol is not in use.
+    pub fn kitty_keyboard_flags(&self) -> u8 {
+        ((self.mode & TermMode::KITTY_KEYBOARD_PROTOCOL).bits() >> KITTY_FLAGS_SHIFT) as u8
+    }
+}
+
+pub(crate) fn read_modes<T>(term: &Term<T>, tracked: &TrackedModes) -> TerminalModes {
+    let charsets = &term.grid().cursor.charsets;
+    TerminalModes {
+        mode: *term.mode(),
+        cursor_style: term.cursor_style(),
+        active_charset: tracked.active_charset,
+        charsets: [
+            CharsetIndex::G0,
+            CharsetIndex::G1,
+            CharsetIndex::G2,
+            CharsetIndex::G3,
+        ]
+        .map(|index| charsets[index]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config;
+    use alacritty_terminal::vte::ansi::{CursorShape, Processor};
+
+    use crate::terminal::TerminalSize;
+
+    fn modes_after(config: Config, bytes: &[u8]) -> TerminalModes {
+        let size = TerminalSize {
+            rows: 4,
+            cols: 10,
+            ..TerminalSize::default()
+        };
+        let mut term = Term::new(config, &size, VoidListener);
+        let mut parser: Processor = Processor::new();
+        parser.advance(&mut term, bytes);
+        read_modes(&term, &TrackedModes::default())
+    }
+
+    #[test]
+    fn test_private_modes() {
+        let modes = modes_after(
+            Config::default(),
+            b"\x1b[?1h\x1b[?2004h\x1b[?1049h\x1b[?1006h",
+        );
+        assert!(modes.mode.contains(
+            TermMode::APP_CURSOR
+                | TermMode::BRACKETED_PASTE
+                | TermMode::ALT_SCREEN
+                | TermMode::SGR_MOUSE
+        ));
+        let modes = modes_after(Config::default(), b"\x1b[?2004h\x1b[?2004l");
+        assert!(!modes.mode.contains(TermMode::BRACKETED_PASTE));
+    }
+
+    #[test]
+    fn test_cursor_style_and_charsets() {
+        let modes = modes_after(Config::default(), b"\x1b[5 q\x1b)0");
+        assert_eq!(modes.cursor_style.shape, CursorShape::Beam);
+        assert!(modes.cursor_style.blinking);
+        assert_eq!(modes.charsets[0], StandardCharset::Ascii);
+        assert_eq!(
+            modes.charsets[1],
+            StandardCharset::SpecialCharacterAndLineDrawing
+        );
+    }
+
+    #[test]
+    fn test_kitty_keyboard_flags() {
+        let config = Config {
+            kitty_keyboard: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            modes_after(config.clone(), b"\x1b[>5u").kitty_keyboard_flags(),
+            5
+        );
+        assert_eq!(
+            modes_after(config, b"\x1b[>31u\x1b[<u").kitty_keyboard_flags(),
+            0
+        );
+        // Without protocol support the request is ignored.
+        assert_eq!(
+            modes_after(Config::default(), b"\x1b[>1u").kitty_keyboard_flags(),
+            0
+        );
+    }
+}
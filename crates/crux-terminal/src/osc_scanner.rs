@@ -34,7 +34,7 @@ pub(crate) fn find_string_terminator(data: &[u8], start: usize) -> Option<(usize
 /// OSC 7 format: `file://hostname/path` or `file:///path`.
 /// Returns `None` if the URI is not a valid `file://` URL.
 /// Percent-encoded characters (e.g. `%20`) are decoded.
-pub(crate) fn parse_osc7_uri(uri: &str) -> Option<String> {
+pub fn parse_osc7_uri(uri: &str) -> Option<String> {
     let rest = uri.strip_prefix("file://")?;
 
     // Skip the hostname — the path starts at the next '/'.
@@ -69,7 +69,82 @@ pub(crate) fn parse_osc7_uri(uri: &str) -> Option<String> {
 /// within a single buffer. Sequences split across reads are missed, which
 /// is acceptable since OSC 7 payloads are short (~80 bytes) and the PTY
 /// read buffer is 4KB.
-pub(crate) fn scan_osc7(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
+pub fn scan_osc7(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
+    for (_, mark) in scan_shell_marks(buf) {
+        if let ShellMark::Cwd(_) = mark {
+            let _ = event_tx.send(mark.into_event());
+        }
+    }
+}
+
+/// A single OSC 133 boundary marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Osc133Mark {
+    /// `133;A`
+    PromptStart,
+    /// `133;B`
+    CommandStart,
+    /// `133;C`
+    OutputStart,
+    /// `133;D` or `133;D;N`
+    CommandEnd(Option<i32>),
+}
+
+impl Osc133Mark {
+    fn into_event(self) -> TerminalEvent {
+        let (mark, exit_code) = match self {
+            Osc133Mark::PromptStart => (SemanticZoneType::Prompt, None),
+            Osc133Mark::CommandStart => (SemanticZoneType::Input, None),
+            Osc133Mark::OutputStart => (SemanticZoneType::Output, None),
+            Osc133Mark::CommandEnd(exit_code) => (SemanticZoneType::Output, exit_code),
+        };
+        TerminalEvent::PromptMark { mark, exit_code }
+    }
+}
+
+/// Parse the payload following `ESC ] 133 ;` up to (not including) ST.
+pub(crate) fn parse_osc133_payload(payload: &[u8]) -> Option<Osc133Mark> {
+    match payload.first()? {
+        b'A' => Some(Osc133Mark::PromptStart),
+        b'B' => Some(Osc133Mark::CommandStart),
+        b'C' => Some(Osc133Mark::OutputStart),
+        b'D' => {
+            // Parse optional exit code: "D" or "D;N"
+            let exit_code = payload
+                .strip_prefix(b"D;")
+                .and_then(|s| std::str::from_utf8(s).ok())
+                .and_then(|s| s.parse::<i32>().ok());
+            Some(Osc133Mark::CommandEnd(exit_code))
+        }
+        _ => None,
+    }
+}
+
+/// A shell integration sequence found in PTY output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ShellMark {
+    /// OSC 7 working directory report.
+    Cwd(String),
+    /// OSC 133 prompt/command boundary.
+    Prompt(Osc133Mark),
+}
+
+impl ShellMark {
+    pub(crate) fn into_event(self) -> TerminalEvent {
+        match self {
+            ShellMark::Cwd(path) => TerminalEvent::CwdChanged(path),
+            ShellMark::Prompt(mark) => mark.into_event(),
+        }
+    }
+}
+
+/// Find every complete OSC 7 and OSC 133 sequence in `buf`, in order.
+///
+/// Each mark is paired with the offset just past its terminator, so the
+/// caller can feed the VTE parser up to that point and observe the
+/// terminal state exactly where the mark occurred.
+pub(crate) fn scan_shell_marks(buf: &[u8]) -> Vec<(usize, ShellMark)> {
+    let mut marks = Vec::new();
     // OSC introducer: ESC ] (0x1b 0x5d)
     let mut i = 0;
     while i + 4 < buf.len() {
@@ -79,29 +154,39 @@ pub(crate) fn scan_osc7(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
             continue;
         }
 
-        // Check for "7;" after ESC ]
-        if buf[i + 2] != b'7' || buf[i + 3] != b';' {
+        let payload_start = if buf[i + 2..].starts_with(b"7;") {
+            i + 4
+        } else if buf[i + 2..].starts_with(b"133;") && i + 6 < buf.len() {
+            i + 6
+        } else {
             i += 2;
             continue;
-        }
+        };
 
         // Find the string terminator: BEL (0x07) or ESC \ (0x1b 0x5c).
-        let payload_start = i + 4;
-
-        if let Some((end, next_i)) = find_string_terminator(buf, payload_start) {
-            if let Ok(uri) = std::str::from_utf8(&buf[payload_start..end]) {
-                if let Some(path) = parse_osc7_uri(uri) {
-                    log::debug!("OSC 7 CWD: {}", path);
-                    let _ = event_tx.send(TerminalEvent::CwdChanged(path));
-                }
-            }
-            // Skip past the terminator.
-            i = next_i;
-        } else {
+        let Some((end, next_i)) = find_string_terminator(buf, payload_start) else {
             // Incomplete sequence — skip the ESC ] and continue.
             i += 2;
+            continue;
+        };
+
+        let payload = &buf[payload_start..end];
+        let mark = if payload_start == i + 4 {
+            std::str::from_utf8(payload)
+                .ok()
+                .and_then(parse_osc7_uri)
+                .map(ShellMark::Cwd)
+        } else {
+            parse_osc133_payload(payload).map(ShellMark::Prompt)
+        };
+        if let Some(mark) = mark {
+            log::debug!("shell mark: {:?}", mark);
+            marks.push((next_i, mark));
         }
+        // Skip past the terminator.
+        i = next_i;
     }
+    marks
 }
 
 /// Scan a byte buffer for OSC 133 (FinalTerm) prompt-marking sequences.
@@ -116,72 +201,10 @@ pub(crate) fn scan_osc7(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
 ///
 /// Like `scan_osc7`, this is stateless per call — sequences split across
 /// reads are missed, which is acceptable given the short payload size.
-pub(crate) fn scan_osc133(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
-    // OSC introducer: ESC ] (0x1b 0x5d)
-    // Minimum sequence: ESC ] 1 3 3 ; A BEL = 7 bytes
-    let mut i = 0;
-    while i + 6 < buf.len() {
-        // Look for ESC ]
-        if buf[i] != 0x1b || buf[i + 1] != 0x5d {
-            i += 1;
-            continue;
-        }
-
-        // Check for "133;" after ESC ]
-        if i + 5 >= buf.len()
-            || buf[i + 2] != b'1'
-            || buf[i + 3] != b'3'
-            || buf[i + 4] != b'3'
-            || buf[i + 5] != b';'
-        {
-            i += 2;
-            continue;
-        }
-
-        // Read the payload after "133;" (at least the marker letter).
-        let payload_start = i + 6;
-        if payload_start >= buf.len() {
-            break;
-        }
-
-        // Find the string terminator: BEL (0x07) or ESC \ (0x1b 0x5c).
-        if let Some((end, next_i)) = find_string_terminator(buf, payload_start) {
-            if let Ok(payload) = std::str::from_utf8(&buf[payload_start..end]) {
-                let event = match payload.as_bytes().first() {
-                    Some(b'A') => Some(TerminalEvent::PromptMark {
-                        mark: SemanticZoneType::Prompt,
-                        exit_code: None,
-                    }),
-                    Some(b'B') => Some(TerminalEvent::PromptMark {
-                        mark: SemanticZoneType::Input,
-                        exit_code: None,
-                    }),
-                    Some(b'C') => Some(TerminalEvent::PromptMark {
-                        mark: SemanticZoneType::Output,
-                        exit_code: None,
-                    }),
-                    Some(b'D') => {
-                        // Parse optional exit code: "D" or "D;N"
-                        let exit_code = payload
-                            .strip_prefix("D;")
-                            .and_then(|s| s.parse::<i32>().ok());
-                        Some(TerminalEvent::PromptMark {
-                            mark: SemanticZoneType::Output,
-                            exit_code,
-                        })
-                    }
-                    _ => None,
-                };
-                if let Some(event) = event {
-                    log::debug!("OSC 133: {}", payload);
-                    let _ = event_tx.send(event);
-                }
-            }
-            // Skip past the terminator.
-            i = next_i;
-        } else {
-            // Incomplete sequence — skip the ESC ] and continue.
-            i += 2;
+pub fn scan_osc133(buf: &[u8], event_tx: &mpsc::Sender<TerminalEvent>) {
+    for (_, mark) in scan_shell_marks(buf) {
+        if let ShellMark::Prompt(_) = mark {
+            let _ = event_tx.send(mark.into_event());
         }
     }
 }
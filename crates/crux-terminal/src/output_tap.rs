@@ -0,0 +1,147 @@
+//! Observers for raw PTY output.
+//!
+//! The PTY reader thread hands every chunk it reads to the terminal's
+//! [`OutputTap`] before parsing it. Sinks run on the reader thread, so they
+//! must never block: copy the bytes into a bounded queue (or drop them) and
+//! return immediately.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Callback receiving raw PTY bytes. Returning `false` unregisters it.
+pub type OutputSink = Box<dyn FnMut(&[u8]) -> bool + Send>;
+
+/// Identifier returned by [`OutputTap::add`].
+pub type OutputSinkId = u64;
+
+/// Shared, cloneable registry of PTY output sinks.
+#[derive(Clone, Default)]
+pub struct OutputTap {
+    inner: Arc<TapInner>,
+}
+
+#[derive(Default)]
+struct TapInner {
+    sinks: Mutex<Vec<(OutputSinkId, OutputSink)>>,
+    /// Mirror of `sinks.len()` so the reader can skip locking when idle.
+    active: AtomicUsize,
+    next_id: AtomicU64,
+}
+
+impl OutputTap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a sink. It stays registered until it returns `false` or is
+    /// removed with [`OutputTap::remove`].
+    pub fn add(&self, sink: OutputSink) -> OutputSinkId {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut sinks = self.lock();
+        sinks.push((id, sink));
+        self.inner.active.store(sinks.len(), Ordering::Release);
+        id
+    }
+
+    /// Unregister a sink. Returns `false` if it was already gone.
+    pub fn remove(&self, id: OutputSinkId) -> bool {
+        let mut sinks = self.lock();
+        let before = sinks.len();
+        sinks.retain(|(sink_id, _)| *sink_id != id);
+        self.inner.active.store(sinks.len(), Ordering::Release);
+        sinks.len() != before
+    }
+
+    /// Number of registered sinks.
+    pub fn len(&self) -> usize {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deliver a chunk to every sink, dropping the ones that decline it.
+    pub(crate) fn dispatch(&self, bytes: &[u8]) {
+        if self.is_empty() {
+            return;
+        }
+        let mut sinks = self.lock();
+        sinks.retain_mut(|(_, sink)| sink(bytes));
+        self.inner.active.store(sinks.len(), Ordering::Release);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(OutputSinkId, OutputSink)>> {
+        // A panicking sink must not take PTY output down with it.
+        self.inner
+            .sinks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for OutputTap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputTap")
+            .field("sinks", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collector() -> (Arc<Mutex<Vec<u8>>>, OutputSink) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink_seen = seen.clone();
+        let sink: OutputSink = Box::new(move |bytes| {
+            sink_seen.lock().unwrap().extend_from_slice(bytes);
+            true
+        });
+        (seen, sink)
+    }
+
+    #[test]
+    fn test_dispatch_reaches_every_sink() {
+        let tap = OutputTap::new();
+        let (a, sink_a) = collector();
+        let (b, sink_b) = collector();
+        tap.add(sink_a);
+        tap.add(sink_b);
+
+        tap.dispatch(b"hello");
+        tap.dispatch(b" world");
+
+        assert_eq!(&*a.lock().unwrap(), b"hello world");
+        assert_eq!(&*b.lock().unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_sink_returning_false_is_removed() {
+        let tap = OutputTap::new();
+        let mut calls = 0;
+        tap.add(Box::new(move |_| {
+            calls += 1;
+            calls < 2
+        }));
+        assert_eq!(tap.len(), 1);
+
+        tap.dispatch(b"a");
+        assert_eq!(tap.len(), 1);
+        tap.dispatch(b"b");
+        assert!(tap.is_empty());
+    }
+
+    #[test]
+    fn test_remove_by_id() {
+        let tap = OutputTap::new();
+        let (seen, sink) = collector();
+        let id = tap.add(sink);
+
+        assert!(tap.remove(id));
+        assert!(!tap.remove(id));
+        tap.dispatch(b"ignored");
+        assert!(seen.lock().unwrap().is_empty());
+    }
+}
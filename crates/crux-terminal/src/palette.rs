@@ -0,0 +1,114 @@
+//! Default color palette and color resolution to RGB.
+//!
+//! Shared by the GPUI renderer and IPC introspection, so both agree on what
+//! a named or indexed color looks like.
+
+use alacritty_terminal::vte::ansi::{Color, NamedColor};
+
+/// Catppuccin Mocha palette (default theme).
+/// These constants serve as fallback defaults.
+pub const BLACK: u32 = 0x1e1e2e;
+pub const RED: u32 = 0xf38ba8;
+pub const GREEN: u32 = 0xa6e3a1;
+pub const YELLOW: u32 = 0xf9e2af;
+pub const BLUE: u32 = 0x89b4fa;
+pub const MAGENTA: u32 = 0xcba6f7;
+pub const CYAN: u32 = 0x94e2d5;
+pub const WHITE: u32 = 0xcdd6f4;
+pub const BRIGHT_BLACK: u32 = 0x585b70;
+pub const BRIGHT_RED: u32 = 0xeba0ac;
+pub const BRIGHT_GREEN: u32 = 0x94e2d5;
+pub const BRIGHT_YELLOW: u32 = 0xf5e0dc;
+pub const BRIGHT_BLUE: u32 = 0x74c7ec;
+pub const BRIGHT_MAGENTA: u32 = 0xf5c2e7;
+pub const BRIGHT_CYAN: u32 = 0x89dceb;
+pub const BRIGHT_WHITE: u32 = 0xffffff;
+pub const FOREGROUND: u32 = 0xcdd6f4;
+pub const BACKGROUND: u32 = 0x1e1e2e;
+pub const CURSOR: u32 = 0xf5e0dc;
+
+/// Resolve an alacritty `Color` to `0xRRGGBB` using the render palette.
+pub fn color_to_rgb(color: Color) -> u32 {
+    match color {
+        Color::Named(named) => named_color_to_rgb(named),
+        Color::Spec(rgb) => (rgb.r as u32) << 16 | (rgb.g as u32) << 8 | rgb.b as u32,
+        Color::Indexed(idx) => indexed_color_to_rgb(idx),
+    }
+}
+
+pub fn named_color_to_rgb(color: NamedColor) -> u32 {
+    match color {
+        NamedColor::Black => BLACK,
+        NamedColor::Red => RED,
+        NamedColor::Green => GREEN,
+        NamedColor::Yellow => YELLOW,
+        NamedColor::Blue => BLUE,
+        NamedColor::Magenta => MAGENTA,
+        NamedColor::Cyan => CYAN,
+        NamedColor::White => WHITE,
+        NamedColor::BrightBlack => BRIGHT_BLACK,
+        NamedColor::BrightRed => BRIGHT_RED,
+        NamedColor::BrightGreen => BRIGHT_GREEN,
+        NamedColor::BrightYellow => BRIGHT_YELLOW,
+        NamedColor::BrightBlue => BRIGHT_BLUE,
+        NamedColor::BrightMagenta => BRIGHT_MAGENTA,
+        NamedColor::BrightCyan => BRIGHT_CYAN,
+        NamedColor::BrightWhite => BRIGHT_WHITE,
+        NamedColor::Foreground | NamedColor::BrightForeground | NamedColor::DimForeground => {
+            FOREGROUND
+        }
+        NamedColor::Background => BACKGROUND,
+        NamedColor::Cursor => CURSOR,
+        _ => FOREGROUND,
+    }
+}
+
+/// Convert a 256-color index to `0xRRGGBB`.
+pub fn indexed_color_to_rgb(idx: u8) -> u32 {
+    match idx {
+        // Standard 16 colors map to named colors.
+        0 => BLACK,
+        1 => RED,
+        2 => GREEN,
+        3 => YELLOW,
+        4 => BLUE,
+        5 => MAGENTA,
+        6 => CYAN,
+        7 => WHITE,
+        8 => BRIGHT_BLACK,
+        9 => BRIGHT_RED,
+        10 => BRIGHT_GREEN,
+        11 => BRIGHT_YELLOW,
+        12 => BRIGHT_BLUE,
+        13 => BRIGHT_MAGENTA,
+        14 => BRIGHT_CYAN,
+        15 => BRIGHT_WHITE,
+        // 216 color cube (indices 16..=231).
+        16..=231 => {
+            let idx = idx - 16;
+            let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i as u32 };
+            level(idx / 36) << 16 | level((idx % 36) / 6) << 8 | level(idx % 6)
+        }
+        // Grayscale ramp (indices 232..=255).
+        232..=255 => {
+            let v = 8 + 10 * (idx - 232) as u32;
+            v << 16 | v << 8 | v
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::vte::ansi::Rgb;
+
+    #[test]
+    fn test_color_to_rgb() {
+        assert_eq!(color_to_rgb(Color::Named(NamedColor::Red)), RED);
+        assert_eq!(color_to_rgb(Color::Indexed(1)), RED);
+        assert_eq!(color_to_rgb(Color::Indexed(67)), 0x5f87af);
+        assert_eq!(color_to_rgb(Color::Indexed(255)), 0xeeeeee);
+        let spec = Color::Spec(Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(color_to_rgb(spec), 0x010203);
+    }
+}
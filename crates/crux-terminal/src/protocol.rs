@@ -0,0 +1,509 @@
+//! Conversions from terminal state to `crux-protocol` IPC results.
+//!
+//! Every IPC front end (the GPUI app and the headless server) answers pane
+//! queries from the same [`CruxTerminal`](crate::CruxTerminal) state, so the
+//! mapping lives here rather than in each dispatcher.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use alacritty_terminal::term::cell::Flags;
+use alacritty_terminal::term::TermMode;
+use alacritty_terminal::vte::ansi::{CursorShape, StandardCharset};
+use anyhow::Context;
+use base64::Engine;
+use crux_graphics::{ImageId, ImagePlacement};
+use crux_protocol::{
+    Charset, CharsetState, CommandHistoryEntry, CursorModes, CursorStyle, DumpGridResult,
+    GetImageParams, GetImageResult, GetModesResult, GetSnapshotResult, GetTextResult, GridCell,
+    GridCursor, GridRow, HistoryStatus, ImageInfo, ImagePlacementInfo, KeyboardModes,
+    ListImagesResult, OutputFormat, PaneHistoryParams, PaneId, PaneModes, RenderImageResult,
+    RunCommandResult, UnderlineStyle, MAX_FRAME_SIZE,
+};
+
+use crate::command_capture::CapturedCommand;
+use crate::graphics::Graphics;
+use crate::grid_dump::{CellInfo, GridDump};
+use crate::history::{CommandRecord, ExitStatusFilter, HistoryFilter};
+use crate::modes::TerminalModes;
+use crate::palette::color_to_rgb;
+use crate::terminal::{extract_text_lines, TerminalContent};
+use crate::text::GridText;
+
+/// Result of `crux:pane/get-text`.
+pub fn text_result(text: GridText) -> GetTextResult {
+    GetTextResult {
+        lines: text.lines,
+        first_line: text.first_line,
+        cursor_row: text.cursor.line.0.max(0) as u32,
+        cursor_col: text.cursor.column.0 as u32,
+    }
+}
+
+/// Result of `crux:pane/get-snapshot`.
+pub fn snapshot_result(
+    content: &TerminalContent,
+    title: Option<String>,
+    cwd: Option<String>,
+) -> GetSnapshotResult {
+    GetSnapshotResult {
+        lines: extract_text_lines(content),
+        rows: content.rows as u32,
+        cols: content.cols as u32,
+        cursor_row: content.cursor.point.line.0,
+        cursor_col: content.cursor.point.column.0 as u32,
+        cursor_shape: format!("{:?}", content.cursor.shape),
+        display_offset: content.display_offset as u32,
+        has_selection: content.selection.is_some(),
+        title,
+        cwd,
+    }
+}
+
+/// Result of `crux:pane/dump-grid`, with colors resolved against the
+/// default palette. Fails if the region is too large to send.
+pub fn grid_result(pane_id: PaneId, dump: GridDump) -> anyhow::Result<DumpGridResult> {
+    let rows = dump
+        .rows
+        .into_iter()
+        .map(|row| GridRow {
+            line: row.line,
+            cells: row.cells.into_iter().map(grid_cell).collect(),
+        })
+        .collect();
+    let result = DumpGridResult {
+        pane_id,
+        rows,
+        cursor: GridCursor {
+            line: dump.cursor.line.0,
+            col: dump.cursor.column.0 as u32,
+            style: cursor_style(dump.cursor_shape),
+            visible: dump.cursor_visible,
+        },
+        scroll_top: dump.scroll_region.start as u32,
+        scroll_bottom: dump.scroll_region.end as u32,
+        display_offset: dump.display_offset as u32,
+        screen_lines: dump.screen_lines as u32,
+        columns: dump.columns as u32,
+    };
+    // Leave room in the response frame for the JSON-RPC envelope.
+    let size = serde_json::to_vec(&result)?.len();
+    if size > MAX_FRAME_SIZE - 1024 {
+        anyhow::bail!(
+            "grid region is too large to send: {} bytes; request fewer lines or columns",
+            size
+        );
+    }
+    Ok(result)
+}
+
+fn grid_cell(cell: CellInfo) -> GridCell {
+    let hex = |color| format!("#{:06x}", color_to_rgb(color));
+    let flags = cell.flags;
+    let underline = if flags.contains(Flags::DOUBLE_UNDERLINE) {
+        Some(UnderlineStyle::Double)
+    } else if flags.contains(Flags::UNDERCURL) {
+        Some(UnderlineStyle::Curly)
+    } else if flags.contains(Flags::DOTTED_UNDERLINE) {
+        Some(UnderlineStyle::Dotted)
+    } else if flags.contains(Flags::DASHED_UNDERLINE) {
+        Some(UnderlineStyle::Dashed)
+    } else if flags.contains(Flags::UNDERLINE) {
+        Some(UnderlineStyle::Single)
+    } else {
+        None
+    };
+    GridCell {
+        col: cell.column as u32,
+        text: cell.text,
+        width: cell.width,
+        fg: hex(cell.fg),
+        bg: hex(cell.bg),
+        underline_color: cell.underline_color.map(hex),
+        bold: flags.contains(Flags::BOLD),
+        dim: flags.contains(Flags::DIM),
+        italic: flags.contains(Flags::ITALIC),
+        underline,
+        strikethrough: flags.contains(Flags::STRIKEOUT),
+        inverse: flags.contains(Flags::INVERSE),
+        hidden: flags.contains(Flags::HIDDEN),
+        hyperlink: cell.hyperlink,
+    }
+}
+
+/// Result of `crux:pane/get-modes`.
+pub fn modes_result(pane_id: PaneId, modes: TerminalModes) -> GetModesResult {
+    let mode = modes.mode;
+    let charset = |index: usize| match modes.charsets[index] {
+        StandardCharset::Ascii => Charset::Ascii,
+        StandardCharset::SpecialCharacterAndLineDrawing => Charset::DecSpecialGraphics,
+    };
+    GetModesResult {
+        pane_id,
+        modes: PaneModes {
+            application_cursor_keys: mode.contains(TermMode::APP_CURSOR),
+            application_keypad: mode.contains(TermMode::APP_KEYPAD),
+            origin: mode.contains(TermMode::ORIGIN),
+            auto_wrap: mode.contains(TermMode::LINE_WRAP),
+            insert: mode.contains(TermMode::INSERT),
+            line_feed_new_line: mode.contains(TermMode::LINE_FEED_NEW_LINE),
+            alternate_screen: mode.contains(TermMode::ALT_SCREEN),
+            alternate_scroll: mode.contains(TermMode::ALTERNATE_SCROLL),
+            bracketed_paste: mode.contains(TermMode::BRACKETED_PASTE),
+            focus_events: mode.contains(TermMode::FOCUS_IN_OUT),
+            mouse_click: mode.contains(TermMode::MOUSE_REPORT_CLICK),
+            mouse_drag: mode.contains(TermMode::MOUSE_DRAG),
+            mouse_motion: mode.contains(TermMode::MOUSE_MOTION),
+            mouse_utf8: mode.contains(TermMode::UTF8_MOUSE),
+            mouse_sgr: mode.contains(TermMode::SGR_MOUSE),
+            urgency_hints: mode.contains(TermMode::URGENCY_HINTS),
+        },
+        cursor: CursorModes {
+            style: cursor_style(modes.cursor_style.shape),
+            blinking: modes.cursor_style.blinking,
+            visible: mode.contains(TermMode::SHOW_CURSOR),
+        },
+        charset: CharsetState {
+            active: modes.active_charset as u8,
+            g0: charset(0),
+            g1: charset(1),
+            g2: charset(2),
+            g3: charset(3),
+        },
+        keyboard: KeyboardModes::from_kitty_flags(modes.kitty_keyboard_flags()),
+    }
+}
+
+fn cursor_style(shape: CursorShape) -> CursorStyle {
+    match shape {
+        CursorShape::Block => CursorStyle::Block,
+        CursorShape::Underline => CursorStyle::Underline,
+        CursorShape::Beam => CursorStyle::Beam,
+        CursorShape::HollowBlock => CursorStyle::HollowBlock,
+        CursorShape::Hidden => CursorStyle::Hidden,
+    }
+}
+
+/// Result of `crux:pane/list-images`.
+pub fn images_result(pane_id: PaneId, graphics: &Graphics) -> ListImagesResult {
+    graphics.with_images(|images| {
+        let top_line = images.top_line();
+        let images = images
+            .image_ids()
+            .into_iter()
+            .filter_map(|id| {
+                let data = images.image_data(id)?;
+                Some(ImageInfo {
+                    image_id: id.0,
+                    width: data.width,
+                    height: data.height,
+                    frames: images.frame_count(id) as u32,
+                    placements: images
+                        .placements_of(id)
+                        .iter()
+                        .map(|placement| placement_info(placement, top_line))
+                        .collect(),
+                })
+            })
+            .collect();
+        ListImagesResult { pane_id, images }
+    })
+}
+
+/// Result of `crux:pane/get-image`: the frame the image shows now.
+pub fn image_result(
+    graphics: &Graphics,
+    params: &GetImageParams,
+) -> anyhow::Result<GetImageResult> {
+    let frame = graphics
+        .with_images(|images| images.current_frame(ImageId(params.image_id), Instant::now()))
+        .with_context(|| format!("image {} not found", params.image_id))?;
+    let png = crux_graphics::decode::encode_png(&frame.data)?;
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    // Leave room in the response frame for the other fields.
+    if data.len() > MAX_FRAME_SIZE - 1024 {
+        anyhow::bail!(
+            "image {} is too large to send: {} bytes of base64 PNG",
+            params.image_id,
+            data.len()
+        );
+    }
+    Ok(GetImageResult {
+        image_id: params.image_id,
+        width: frame.data.width,
+        height: frame.data.height,
+        mime_type: "image/png".into(),
+        data,
+    })
+}
+
+/// Result of `crux:render/image` once `image_id` was shown.
+pub fn render_image_result(
+    pane_id: PaneId,
+    graphics: &Graphics,
+    image_id: ImageId,
+) -> anyhow::Result<RenderImageResult> {
+    let placement = graphics
+        .with_images(|images| {
+            let top_line = images.top_line();
+            images
+                .placements_of(image_id)
+                .first()
+                .map(|placement| placement_info(placement, top_line))
+        })
+        .context("the image was removed before it could be reported")?;
+    Ok(RenderImageResult {
+        pane_id,
+        image_id: image_id.0,
+        placement,
+    })
+}
+
+fn placement_info(placement: &ImagePlacement, top_line: i64) -> ImagePlacementInfo {
+    let line = placement.row - top_line;
+    ImagePlacementInfo {
+        placement_id: placement.placement_id,
+        line: line.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32,
+        col: placement.column,
+        rows: placement.rows,
+        cols: placement.columns,
+        z_index: placement.z_index,
+    }
+}
+
+/// History query for `crux:pane/history` parameters.
+pub fn history_filter(params: &PaneHistoryParams) -> HistoryFilter {
+    HistoryFilter {
+        status: match params.status {
+            HistoryStatus::Any => ExitStatusFilter::Any,
+            HistoryStatus::Success => ExitStatusFilter::Success,
+            HistoryStatus::Failure => ExitStatusFilter::Failure,
+        },
+        since: params
+            .since_ms
+            .map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+        limit: params.limit.map(|n| n as usize),
+    }
+}
+
+/// One `crux:pane/history` entry.
+pub fn history_entry(record: CommandRecord, include_output: bool) -> CommandHistoryEntry {
+    let duration = record
+        .finished_at
+        .duration_since(record.started_at)
+        .unwrap_or_default();
+    CommandHistoryEntry {
+        id: record.id,
+        command: record.command,
+        cwd: record.cwd,
+        exit_code: record.exit_code,
+        started_at_ms: unix_ms(record.started_at),
+        finished_at_ms: unix_ms(record.finished_at),
+        duration_ms: duration.as_millis() as u64,
+        output_start_line: record.output_start_line,
+        output_end_line: record.output_end_line,
+        output: include_output.then_some(record.output),
+        output_truncated: record.output_truncated,
+    }
+}
+
+/// Result of `crux:pane/run-command`. `decode` turns the captured bytes
+/// into text in the requested `format`.
+pub fn run_command_result(
+    pane_id: PaneId,
+    format: OutputFormat,
+    cwd: Option<String>,
+    captured: CapturedCommand,
+    decode: impl FnOnce(&[u8], OutputFormat) -> String,
+) -> RunCommandResult {
+    RunCommandResult {
+        pane_id,
+        output: decode(&captured.output, format),
+        exit_code: captured.exit_code,
+        started_at_ms: unix_ms(captured.started_at),
+        finished_at_ms: unix_ms(captured.finished_at),
+        cwd,
+        timed_out: false,
+        cancelled: false,
+        truncated: captured.truncated,
+    }
+}
+
+fn unix_ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::index::{Column, Line, Point};
+    use alacritty_terminal::vte::ansi::{Color, CursorStyle as TermCursorStyle, NamedColor};
+
+    use crate::grid_dump::RowInfo;
+
+    #[test]
+    fn test_grid_result_resolves_colors_and_flags() {
+        let dump = GridDump {
+            rows: vec![RowInfo {
+                line: -1,
+                cells: vec![CellInfo {
+                    column: 3,
+                    text: "x".into(),
+                    width: 1,
+                    fg: Color::Named(NamedColor::Red),
+                    bg: Color::Indexed(16),
+                    underline_color: None,
+                    flags: Flags::BOLD | Flags::UNDERCURL | Flags::INVERSE,
+                    hyperlink: None,
+                }],
+            }],
+            cursor: Point::new(Line(2), Column(5)),
+            cursor_shape: CursorShape::Beam,
+            cursor_visible: true,
+            scroll_region: 1..20,
+            display_offset: 0,
+            screen_lines: 24,
+            columns: 80,
+        };
+        let result = grid_result(PaneId(7), dump).unwrap();
+        let cell = &result.rows[0].cells[0];
+        assert_eq!(result.rows[0].line, -1);
+        assert_eq!(cell.col, 3);
+        assert_eq!(cell.fg, "#f38ba8");
+        assert_eq!(cell.bg, "#000000");
+        assert!(cell.bold && cell.inverse && !cell.italic);
+        assert_eq!(cell.underline, Some(UnderlineStyle::Curly));
+        assert_eq!(result.cursor.style, CursorStyle::Beam);
+        assert_eq!((result.scroll_top, result.scroll_bottom), (1, 20));
+    }
+
+    #[test]
+    fn test_grid_result_refuses_oversized_dump() {
+        let cell = CellInfo {
+            column: 0,
+            text: "x".into(),
+            width: 1,
+            fg: Color::Named(NamedColor::Foreground),
+            bg: Color::Named(NamedColor::Background),
+            underline_color: None,
+            flags: Flags::empty(),
+            hyperlink: Some(format!("https://example.com/{}", "a".repeat(10_000))),
+        };
+        let dump = GridDump {
+            rows: vec![RowInfo {
+                line: 0,
+                cells: vec![cell; 2_000],
+            }],
+            cursor: Point::new(Line(0), Column(0)),
+            cursor_shape: CursorShape::Block,
+            cursor_visible: true,
+            scroll_region: 0..24,
+            display_offset: 0,
+            screen_lines: 24,
+            columns: 2_000,
+        };
+        let error = grid_result(PaneId(1), dump).unwrap_err();
+        assert!(error.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_modes_result() {
+        let modes = TerminalModes {
+            mode: TermMode::APP_CURSOR
+                | TermMode::BRACKETED_PASTE
+                | TermMode::SHOW_CURSOR
+                | TermMode::DISAMBIGUATE_ESC_CODES
+                | TermMode::REPORT_ALTERNATE_KEYS,
+            cursor_style: TermCursorStyle {
+                shape: CursorShape::Underline,
+                blinking: false,
+            },
+            active_charset: alacritty_terminal::vte::ansi::CharsetIndex::G1,
+            charsets: [
+                StandardCharset::Ascii,
+                StandardCharset::SpecialCharacterAndLineDrawing,
+                StandardCharset::Ascii,
+                StandardCharset::Ascii,
+            ],
+        };
+        let result = modes_result(PaneId(1), modes);
+        assert!(result.modes.application_cursor_keys && result.modes.bracketed_paste);
+        assert!(!result.modes.alternate_screen);
+        assert!(result.cursor.visible);
+        assert_eq!(result.cursor.style, CursorStyle::Underline);
+        assert_eq!(result.charset.active, 1);
+        assert_eq!(result.charset.g1, Charset::DecSpecialGraphics);
+        assert_eq!(result.keyboard.kitty_flags, 5);
+    }
+
+    #[test]
+    fn test_history_filter_and_entry() {
+        let params = PaneHistoryParams {
+            pane_id: None,
+            status: HistoryStatus::Failure,
+            since_ms: Some(1_000),
+            limit: Some(3),
+            include_output: false,
+        };
+        let filter = history_filter(&params);
+        assert_eq!(filter.status, ExitStatusFilter::Failure);
+        assert_eq!(filter.since, Some(UNIX_EPOCH + Duration::from_secs(1)));
+        assert_eq!(filter.limit, Some(3));
+
+        let record = CommandRecord {
+            id: 4,
+            command: "make".into(),
+            cwd: None,
+            exit_code: Some(2),
+            started_at: UNIX_EPOCH + Duration::from_millis(1_500),
+            finished_at: UNIX_EPOCH + Duration::from_millis(2_000),
+            output_start_line: 10,
+            output_end_line: 12,
+            output: "error".into(),
+            output_truncated: false,
+        };
+        let entry = history_entry(record, false);
+        assert_eq!(entry.duration_ms, 500);
+        assert_eq!(entry.started_at_ms, 1_500);
+        assert!(entry.output.is_none());
+    }
+
+    #[test]
+    fn test_image_results() {
+        const PNG_1X1: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+        let (terminal, _feed) = crate::CruxTerminal::new_remote(
+            crate::TerminalSize::default(),
+            Box::new(std::io::sink()),
+        );
+        let file = base64::engine::general_purpose::STANDARD
+            .decode(PNG_1X1)
+            .unwrap();
+        let id = terminal.show_image(file, Some(4), Some(2)).unwrap();
+        let rendered = render_image_result(PaneId(1), terminal.graphics(), id).unwrap();
+        assert_eq!((rendered.placement.line, rendered.placement.col), (0, 0));
+        assert_eq!((rendered.placement.cols, rendered.placement.rows), (4, 2));
+
+        let listed = images_result(PaneId(1), terminal.graphics());
+        assert_eq!(listed.images.len(), 1);
+        let image = &listed.images[0];
+        assert_eq!((image.image_id, image.width, image.height), (id.0, 1, 1));
+        assert_eq!(image.frames, 1);
+        assert_eq!(image.placements, [rendered.placement]);
+
+        let get = |image_id| {
+            image_result(
+                terminal.graphics(),
+                &GetImageParams {
+                    pane_id: None,
+                    image_id,
+                },
+            )
+        };
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(get(id.0).unwrap().data)
+            .unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert!(get(id.0 + 1).is_err());
+    }
+}
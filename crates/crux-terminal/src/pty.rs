@@ -1,6 +1,6 @@
-use std::io::Read;
+use std::io::{Read, Write};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 
 use alacritty_terminal::sync::FairMutex;
@@ -9,7 +9,12 @@ use alacritty_terminal::vte::ansi::{self, Processor};
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 
 use crate::event::{CruxEventListener, TerminalEvent};
-use crate::osc_scanner::{scan_osc133, scan_osc7};
+use crate::graphics::{Graphics, GraphicsCut, GraphicsTracker, LoadedCut};
+use crate::history::{CommandHistory, HistoryRecorder};
+use crate::osc_scanner::{scan_shell_marks, ShellMark};
+use crate::output_tap::OutputTap;
+use crate::tmux::{TmuxControlScanner, TmuxNotification, TmuxRouter};
+use crate::tracked_state::{StateTracker, TrackedState};
 use crate::TerminalSize;
 
 /// Typed error for PTY spawn failures.
@@ -23,6 +28,33 @@ pub enum PtyError {
     GetWriter(#[source] anyhow::Error),
 }
 
+/// Cloneable handle to a terminal's input side.
+///
+/// Writes from every clone are serialized, so a whole `write_all` reaches
+/// the PTY (or remote sink) without interleaving.
+#[derive(Clone)]
+pub struct PtyWriter {
+    inner: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl PtyWriter {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Write and flush `data`.
+    pub fn write_all(&self, data: &[u8]) -> std::io::Result<()> {
+        let mut writer = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        writer.write_all(data)?;
+        writer.flush()
+    }
+}
+
 /// Check if a terminfo entry is available on the system.
 ///
 /// Searches the standard terminfo directories in order:
@@ -174,13 +206,29 @@ pub fn spawn_pty(
 /// alacritty_terminal parser, then signals wakeup.
 ///
 /// The `event_tx` channel is used to emit events that alacritty_terminal
-/// does not handle natively (e.g. OSC 7 CWD changes).
+/// does not handle natively (e.g. OSC 7 CWD changes). Every chunk read is
+/// also handed unmodified to `output_tap` before it reaches the parser.
+///
+/// tmux control-mode traffic is taken out of the stream before parsing:
+/// pane output goes to the remote terminals registered with `tmux`, other
+/// notifications are sent as [`TerminalEvent::Tmux`].
+///
+/// Kitty graphics commands, iTerm2 file transfers and Sixel images are
+/// executed against `graphics` at their position in the stream; Kitty
+/// replies are sent as [`TerminalEvent::PtyWrite`] and downloads as
+/// [`TerminalEvent::FileDownload`].
 ///
 /// The thread exits when the PTY reader returns EOF or an error.
+#[allow(clippy::too_many_arguments)]
 pub fn start_pty_read_loop(
     term: Arc<FairMutex<Term<CruxEventListener>>>,
     mut reader: Box<dyn Read + Send>,
     event_tx: mpsc::Sender<TerminalEvent>,
+    output_tap: OutputTap,
+    history: CommandHistory,
+    tracked: TrackedState,
+    tmux: TmuxRouter,
+    graphics: Graphics,
     wakeup: impl Fn() + Send + 'static,
 ) -> JoinHandle<()> {
     std::thread::Builder::new()
@@ -188,6 +236,11 @@ pub fn start_pty_read_loop(
         .spawn(move || {
             let mut buf = [0u8; 0x1000]; // 4KB read buffer
             let mut parser: Processor = ansi::Processor::new();
+            let mut recorder = HistoryRecorder::new(history);
+            let mut tracker = StateTracker::new(tracked);
+            let mut graphics = GraphicsTracker::new(graphics);
+            let mut tmux_scanner = TmuxControlScanner::new();
+            let mut notifications = Vec::new();
             let mut pending_bytes: usize = 0;
             let mut last_wakeup = std::time::Instant::now();
 
@@ -200,16 +253,72 @@ pub fn start_pty_read_loop(
                 match reader.read(&mut buf) {
                     Ok(0) => break, // EOF
                     Ok(n) => {
-                        // Scan for OSC sequences before feeding to the VTE parser.
+                        // Raw bytes go to observers first, exactly as read.
+                        output_tap.dispatch(&buf[..n]);
+
+                        let chunk = tmux_scanner.feed(&buf[..n], &mut notifications);
+                        for notification in notifications.drain(..) {
+                            match notification {
+                                TmuxNotification::Output { pane, data } => {
+                                    tmux.route(pane, data);
+                                    continue;
+                                }
+                                TmuxNotification::Enter => tmux.set_active(true),
+                                TmuxNotification::Exit { .. } => tmux.set_active(false),
+                                _ => {}
+                            }
+                            let _ = event_tx.send(TerminalEvent::Tmux(notification));
+                        }
+
+                        // Scan for OSC sequences before feeding the VTE parser.
                         // alacritty_terminal does not handle OSC 7 or OSC 133,
                         // so we intercept them here. The VTE parser will log
                         // them as "unhandled osc_dispatch" but otherwise ignore them.
-                        scan_osc7(&buf[..n], &event_tx);
-                        scan_osc133(&buf[..n], &event_tx);
-
+                        // The parser is advanced up to each mark so the history
+                        // recorder sees the cursor exactly where the mark was;
+                        // graphics commands and erases are cut the same way.
+                        let chunk: &[u8] = &chunk;
                         {
-                            let mut term = term.lock();
-                            parser.advance(&mut *term, &buf[..n]);
+                            let mut cuts: Vec<(usize, Cut)> = scan_shell_marks(chunk)
+                                .into_iter()
+                                .map(|(end, mark)| (end, Cut::Mark(mark)))
+                                .collect();
+                            cuts.extend(
+                                graphics
+                                    .scan(chunk)
+                                    .into_iter()
+                                    .map(|(offset, cut)| (offset, Cut::Graphics(cut))),
+                            );
+                            cuts.sort_by_key(|&(offset, _)| offset);
+
+                            let mut start = 0;
+                            for (offset, cut) in cuts {
+                                // Images are read and decoded before the
+                                // terminal is locked, so that a slow file or
+                                // a large image does not hold up the screen.
+                                let cut = cut.load(&graphics);
+                                let mut term = term.lock();
+                                let offset = offset.max(start);
+                                graphics.advance(&mut parser, &mut term, &chunk[start..offset]);
+                                start = offset;
+                                match cut {
+                                    Cut::Mark(mark) => {
+                                        recorder.observe(&mark, &term);
+                                        let _ = event_tx.send(mark.into_event());
+                                    }
+                                    Cut::Graphics(cut) => {
+                                        start += graphics.apply(
+                                            cut,
+                                            &mut parser,
+                                            &mut term,
+                                            &event_tx,
+                                            &chunk[start..],
+                                        );
+                                    }
+                                }
+                            }
+                            graphics.advance(&mut parser, &mut term.lock(), &chunk[start..]);
+                            tracker.advance(chunk);
                         }
                         pending_bytes += n;
 
@@ -239,6 +348,21 @@ pub fn start_pty_read_loop(
         .expect("failed to spawn PTY reader thread: system resource exhaustion or invalid thread configuration")
 }
 
+/// A point where the reader stops parsing a chunk to act on it.
+enum Cut<G = GraphicsCut> {
+    Mark(ShellMark),
+    Graphics(G),
+}
+
+impl Cut {
+    fn load(self, graphics: &GraphicsTracker) -> Cut<LoadedCut> {
+        match self {
+            Cut::Mark(mark) => Cut::Mark(mark),
+            Cut::Graphics(cut) => Cut::Graphics(graphics.load(cut)),
+        }
+    }
+}
+
 /// Ensure the xterm-crux terminfo is installed on the system.
 ///
 /// This function:
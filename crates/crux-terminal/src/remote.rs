@@ -0,0 +1,64 @@
+//! Byte source for terminals that have no local PTY.
+//!
+//! A remote terminal runs the usual reader thread, but reads from a channel
+//! filled through its [`RemoteFeed`] instead of a PTY master. tmux control
+//! mode uses this for panes that live on the tmux server.
+
+use std::io::Read;
+use std::sync::mpsc;
+
+/// Sending half of a remote terminal's output stream.
+///
+/// Dropping the last clone ends the stream: the terminal's reader thread
+/// exits and the terminal reports its "process" as no longer running.
+#[derive(Clone)]
+pub struct RemoteFeed {
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl RemoteFeed {
+    /// Queue `data` as terminal output. Returns `false` once the terminal
+    /// is gone.
+    pub fn feed(&self, data: Vec<u8>) -> bool {
+        self.tx.send(data).is_ok()
+    }
+}
+
+/// Blocking [`Read`] over the chunks sent through a [`RemoteFeed`].
+pub(crate) struct ChannelReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // Every feed was dropped: end of stream.
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A connected feed and reader.
+pub(crate) fn channel() -> (RemoteFeed, ChannelReader) {
+    let (tx, rx) = mpsc::channel();
+    (
+        RemoteFeed { tx },
+        ChannelReader {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        },
+    )
+}
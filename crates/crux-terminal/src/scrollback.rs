@@ -0,0 +1,109 @@
+//! Scrollback snapshots stored in session files.
+
+use std::io::{Read, Write};
+
+use base64::Engine;
+use crux_protocol::{Scrollback, ScrollbackEncoding};
+
+/// Largest decoded snapshot accepted on restore, so a corrupt or hostile
+/// session file cannot balloon into gigabytes of grid.
+const MAX_DECODED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Pack `lines` (SGR escapes included) into a [`Scrollback`].
+pub fn encode(lines: &[String], compress: bool) -> Scrollback {
+    let text = lines.join("\n");
+    let (encoding, data) = if compress {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        // Writing to a Vec cannot fail.
+        let gz = encoder
+            .write_all(text.as_bytes())
+            .and_then(|_| encoder.finish())
+            .expect("gzip into memory");
+        (
+            ScrollbackEncoding::GzipBase64,
+            base64::engine::general_purpose::STANDARD.encode(gz),
+        )
+    } else {
+        (ScrollbackEncoding::Plain, text)
+    };
+    Scrollback {
+        encoding,
+        lines: lines.len() as u32,
+        data,
+    }
+}
+
+/// Unpack a [`Scrollback`] into its lines.
+pub fn decode(scrollback: &Scrollback) -> anyhow::Result<Vec<String>> {
+    let text = match scrollback.encoding {
+        ScrollbackEncoding::Plain => scrollback.data.clone(),
+        ScrollbackEncoding::GzipBase64 => {
+            let gz = base64::engine::general_purpose::STANDARD
+                .decode(scrollback.data.as_bytes())
+                .map_err(|e| anyhow::anyhow!("scrollback is not valid base64: {}", e))?;
+            let mut text = String::new();
+            flate2::read::GzDecoder::new(gz.as_slice())
+                .take(MAX_DECODED_BYTES + 1)
+                .read_to_string(&mut text)
+                .map_err(|e| anyhow::anyhow!("scrollback is not valid gzip text: {}", e))?;
+            text
+        }
+    };
+    if text.len() as u64 > MAX_DECODED_BYTES {
+        anyhow::bail!("scrollback larger than {} bytes", MAX_DECODED_BYTES);
+    }
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(text.split('\n').map(str::to_string).collect())
+}
+
+/// Bytes that redraw `lines` when fed to the terminal parser, leaving the
+/// cursor at the start of a fresh line with default attributes.
+pub(crate) fn replay_bytes(lines: &[String]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for line in lines {
+        out.extend_from_slice(line.as_bytes());
+        out.extend_from_slice(b"\x1b[0m\r\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let lines = vec![
+            "$ ls".to_string(),
+            "\x1b[1;34msrc\x1b[0m  Cargo.toml".to_string(),
+            String::new(),
+        ];
+        for compress in [false, true] {
+            let scrollback = encode(&lines, compress);
+            assert_eq!(scrollback.lines, 3);
+            assert_eq!(decode(&scrollback).unwrap(), lines);
+        }
+        assert_eq!(
+            encode(&lines, true).encoding,
+            ScrollbackEncoding::GzipBase64
+        );
+        assert!(decode(&encode(&[], true)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        let bad = Scrollback {
+            encoding: ScrollbackEncoding::GzipBase64,
+            lines: 1,
+            data: "not base64!".into(),
+        };
+        assert!(decode(&bad).is_err());
+        let not_gzip = Scrollback {
+            data: base64::engine::general_purpose::STANDARD.encode(b"plain"),
+            ..bad
+        };
+        assert!(decode(&not_gzip).is_err());
+    }
+}
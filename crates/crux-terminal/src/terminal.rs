@@ -1,4 +1,5 @@
 use std::io::Write;
+use std::ops::Range;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread::JoinHandle;
@@ -9,10 +10,22 @@ use alacritty_terminal::selection::SelectionRange;
 use alacritty_terminal::sync::FairMutex;
 use alacritty_terminal::term::cell::Flags;
 use alacritty_terminal::term::{Config, Term, TermDamage, TermMode};
-use alacritty_terminal::vte::ansi::{Color, CursorShape};
+use alacritty_terminal::vte::ansi::{Color, CursorShape, Processor};
+use crux_graphics::{GraphicsError, ImageId, PlaceholderRun, PlaceholderRuns, PLACEHOLDER};
+use crux_protocol::Scrollback;
 
 use crate::event::{CruxEventListener, SemanticZone, SemanticZoneType, TerminalEvent};
-use crate::pty;
+use crate::graphics::Graphics;
+use crate::grid_dump::{self, GridDump};
+use crate::history::CommandHistory;
+use crate::modes::{self, TerminalModes};
+use crate::output_tap::OutputTap;
+use crate::pty::{self, PtyWriter};
+use crate::remote::{self, RemoteFeed};
+use crate::scrollback;
+use crate::text::{self, GridText};
+use crate::tmux::TmuxRouter;
+use crate::tracked_state::TrackedState;
 use crate::traits::Terminal;
 
 /// Default scrollback history size in lines.
@@ -92,6 +105,8 @@ pub struct TerminalContent {
     pub rows: usize,
     /// Damage information from alacritty_terminal's damage tracking.
     pub damage: DamageState,
+    /// Cells showing Kitty Unicode-placeholder images.
+    pub image_placeholders: Vec<PlaceholderRun>,
 }
 
 /// A single cell with its grid position.
@@ -113,12 +128,26 @@ pub struct CursorState {
 
 /// The core terminal entity. Owns the alacritty_terminal state, PTY
 /// handles, and I/O threads.
+///
+/// Remote terminals (see [`CruxTerminal::new_remote`]) have no PTY or child
+/// process: output arrives through a [`RemoteFeed`] and input goes to the
+/// sink they were created with.
 pub struct CruxTerminal {
     term: Arc<FairMutex<Term<CruxEventListener>>>,
-    pty_writer: Box<dyn Write + Send>,
-    master_pty: Box<dyn portable_pty::MasterPty + Send>,
-    child: Box<dyn portable_pty::Child + Send + Sync>,
+    pty_writer: PtyWriter,
+    master_pty: Option<Box<dyn portable_pty::MasterPty + Send>>,
+    child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
     reader_thread: Option<JoinHandle<()>>,
+    /// Observers of raw PTY output, fed by the reader thread.
+    output_tap: OutputTap,
+    /// Remote panes of a tmux control-mode session running in this terminal.
+    tmux: TmuxRouter,
+    /// Completed commands, recorded by the reader thread.
+    history: CommandHistory,
+    /// Settings tracked alongside the VTE parser (scroll region, ...).
+    tracked: TrackedState,
+    /// Inline images, updated by the reader thread.
+    graphics: Graphics,
     event_rx: mpsc::Receiver<TerminalEvent>,
     size: TerminalSize,
     /// Current working directory reported by the shell via OSC 7.
@@ -158,6 +187,39 @@ impl CruxTerminal {
         let default_shell_args = vec!["-l".to_string()];
         let shell_args = shell_args.unwrap_or(&default_shell_args);
 
+        // Spawn the PTY process.
+        let (master_pty, child) = pty::spawn_pty(&shell, shell_args, &size, cwd, command, env)?;
+
+        // Get reader and writer handles from the master PTY.
+        let reader = master_pty.try_clone_reader()?;
+        let writer = master_pty.take_writer()?;
+
+        Ok(Self::start(
+            size,
+            reader,
+            writer,
+            Some(master_pty),
+            Some(child),
+        ))
+    }
+
+    /// Create a terminal with no PTY. Output is whatever is sent through the
+    /// returned [`RemoteFeed`]; input written to the terminal goes to `input`.
+    pub fn new_remote(size: TerminalSize, input: Box<dyn Write + Send>) -> (Self, RemoteFeed) {
+        let (feed, reader) = remote::channel();
+        let terminal = Self::start(size, Box::new(reader), input, None, None);
+        (terminal, feed)
+    }
+
+    /// Build the terminal state around an output source and start the
+    /// reader thread.
+    fn start(
+        size: TerminalSize,
+        reader: Box<dyn std::io::Read + Send>,
+        writer: Box<dyn Write + Send>,
+        master_pty: Option<Box<dyn portable_pty::MasterPty + Send>>,
+        child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
+    ) -> Self {
         // Event channel for terminal → UI communication.
         let (event_tx, event_rx) = mpsc::channel();
 
@@ -171,29 +233,42 @@ impl CruxTerminal {
         let term = Term::new(config, &size, event_listener);
         let term = Arc::new(FairMutex::new(term));
 
-        // Spawn the PTY process.
-        let (master_pty, child) = pty::spawn_pty(&shell, shell_args, &size, cwd, command, env)?;
-
-        // Get reader and writer handles from the master PTY.
-        let reader = master_pty.try_clone_reader()?;
-        let writer = master_pty.take_writer()?;
-
         // Start background PTY reader thread.
         // The event_tx clone is used for OSC 7 (CWD) events that
         // alacritty_terminal does not handle natively.
         let term_clone = term.clone();
-        let reader_thread = pty::start_pty_read_loop(term_clone, reader, event_tx, || {
-            // The wakeup callback is intentionally minimal.
-            // In the GPUI integration layer, this will be replaced
-            // with a cx.notify() call via the event channel.
-        });
+        let output_tap = OutputTap::new();
+        let history = CommandHistory::new();
+        let tracked = TrackedState::new();
+        let tmux = TmuxRouter::new();
+        let graphics = Graphics::new(size.cell_width, size.cell_height, size.scrollback_lines);
+        let reader_thread = pty::start_pty_read_loop(
+            term_clone,
+            reader,
+            event_tx,
+            output_tap.clone(),
+            history.clone(),
+            tracked.clone(),
+            tmux.clone(),
+            graphics.clone(),
+            || {
+                // The wakeup callback is intentionally minimal.
+                // In the GPUI integration layer, this will be replaced
+                // with a cx.notify() call via the event channel.
+            },
+        );
 
-        Ok(Self {
+        Self {
             term,
-            pty_writer: writer,
+            pty_writer: PtyWriter::new(writer),
             master_pty,
             child,
             reader_thread: Some(reader_thread),
+            output_tap,
+            tmux,
+            history,
+            tracked,
+            graphics,
             event_rx,
             size,
             cwd: None,
@@ -202,39 +277,90 @@ impl CruxTerminal {
             current_zone_start_line: 0,
             current_zone_start_col: 0,
             last_cursor_shape: CursorShape::Block,
-        })
+        }
+    }
+
+    /// Registry of raw PTY output observers for this terminal.
+    ///
+    /// The returned handle is shared with the reader thread; sinks added to
+    /// it see every byte read from the PTY from that point on.
+    pub fn output_tap(&self) -> &OutputTap {
+        &self.output_tap
     }
 
     /// Write keyboard input or other data to the PTY.
     pub fn write_to_pty(&mut self, data: &[u8]) {
         if let Err(e) = self.pty_writer.write_all(data) {
             log::warn!("failed to write to PTY: {}", e);
-            return;
-        }
-        if let Err(e) = self.pty_writer.flush() {
-            log::warn!("failed to flush PTY: {}", e);
         }
     }
 
+    /// Shareable handle for writing to this terminal's PTY from elsewhere.
+    pub fn writer(&self) -> PtyWriter {
+        self.pty_writer.clone()
+    }
+
+    /// Routes pane output of a tmux control-mode session running in this
+    /// terminal. [`TmuxRouter::is_active`] tells whether one is running.
+    pub fn tmux_router(&self) -> &TmuxRouter {
+        &self.tmux
+    }
+
+    /// Inline images shown in this terminal.
+    pub fn graphics(&self) -> &Graphics {
+        &self.graphics
+    }
+
+    /// Show a PNG, JPEG or GIF file at the cursor without writing to the
+    /// PTY; the cursor stays where it is. `cols` and `rows` default to the
+    /// image's own size, fitted to the screen.
+    pub fn show_image(
+        &self,
+        data: Vec<u8>,
+        cols: Option<u32>,
+        rows: Option<u32>,
+    ) -> Result<ImageId, GraphicsError> {
+        self.graphics.show_image(&self.term, data, cols, rows)
+    }
+
     /// Resize the terminal grid and PTY.
     pub fn resize(&mut self, size: TerminalSize) {
         self.size = size;
+        self.graphics
+            .set_cell_size(size.cell_width, size.cell_height);
 
         // Resize PTY first so the child process gets SIGWINCH before grid changes.
-        if let Err(e) = self.master_pty.resize(portable_pty::PtySize {
-            rows: u16::try_from(size.rows).unwrap_or(u16::MAX),
-            cols: u16::try_from(size.cols).unwrap_or(u16::MAX),
-            pixel_width: u16::try_from((size.cols as f32 * size.cell_width) as usize)
-                .unwrap_or(u16::MAX),
-            pixel_height: u16::try_from((size.rows as f32 * size.cell_height) as usize)
-                .unwrap_or(u16::MAX),
-        }) {
-            log::warn!("failed to resize PTY: {}", e);
+        if let Some(master_pty) = &self.master_pty {
+            if let Err(e) = master_pty.resize(portable_pty::PtySize {
+                rows: u16::try_from(size.rows).unwrap_or(u16::MAX),
+                cols: u16::try_from(size.cols).unwrap_or(u16::MAX),
+                pixel_width: u16::try_from((size.cols as f32 * size.cell_width) as usize)
+                    .unwrap_or(u16::MAX),
+                pixel_height: u16::try_from((size.rows as f32 * size.cell_height) as usize)
+                    .unwrap_or(u16::MAX),
+            }) {
+                log::warn!("failed to resize PTY: {}", e);
+            }
         }
 
         // Then resize the alacritty terminal grid.
-        // Note: scrollback_lines is fixed at terminal creation time and cannot be changed.
-        self.term.lock().resize(size);
+        // Note: scrollback_lines is not applied here; see `set_scrollback_lines`.
+        let mut term = self.term.lock();
+        let history = term.grid().history_size();
+        term.resize(size);
+        self.graphics
+            .track_resize(history, term.grid().history_size());
+        self.tracked.on_resize();
+    }
+
+    /// Change the scrollback limit. Shrinking it drops the oldest history.
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.size.scrollback_lines = lines;
+        self.graphics.set_scrollback_lines(lines);
+        self.term.lock().set_options(Config {
+            scrolling_history: lines,
+            ..Config::default()
+        });
     }
 
     /// Access the terminal state under a lock.
@@ -287,11 +413,21 @@ impl CruxTerminal {
 
         // Scope the immutable borrow from renderable_content() so we can
         // call reset_damage() afterward.
-        let (cells, cursor, mode, display_offset, selection) = {
+        let (cells, cursor, mode, display_offset, selection, placeholders) = {
             let content = term.renderable_content();
 
             let mut cells = Vec::with_capacity(cols * rows);
+            let mut placeholders = PlaceholderRuns::new();
             for Indexed { point, cell } in content.display_iter {
+                if cell.c == PLACEHOLDER {
+                    placeholders.push(
+                        point.line.0,
+                        point.column.0 as u32,
+                        color_id(cell.fg),
+                        cell.underline_color().map_or(0, color_id),
+                        cell.zerowidth().unwrap_or(&[]),
+                    );
+                }
                 cells.push(IndexedCell {
                     point,
                     c: cell.c,
@@ -312,6 +448,7 @@ impl CruxTerminal {
                 content.mode,
                 content.display_offset,
                 content.selection,
+                placeholders.finish(),
             )
         };
 
@@ -327,9 +464,62 @@ impl CruxTerminal {
             cols,
             rows,
             damage,
+            image_placeholders: placeholders,
         }
     }
 
+    /// Read grid lines `start_line..end_line` (0 = top of screen, negative =
+    /// scrollback), optionally with SGR escapes rebuilt from cell attributes.
+    ///
+    /// Unlike [`content`](Self::content), this does not touch damage state,
+    /// so it is safe to call outside the render loop.
+    pub fn read_text(
+        &self,
+        start_line: Option<i32>,
+        end_line: Option<i32>,
+        escapes: bool,
+    ) -> GridText {
+        let term = self.term.lock();
+        text::read_text(&term, start_line, end_line, escapes)
+    }
+
+    /// The screen and up to `history_lines` of scrollback above it, with SGR
+    /// escapes, packed for a session file. Trailing blank lines are dropped.
+    pub fn scrollback_snapshot(&self, history_lines: usize, compress: bool) -> Scrollback {
+        let start = -(history_lines.min(i32::MAX as usize) as i32);
+        let mut lines = self.read_text(Some(start), None, true).lines;
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+        scrollback::encode(&lines, compress)
+    }
+
+    /// Draw saved scrollback into the grid as if it had been output before
+    /// anything else. The child process sees none of it.
+    pub fn restore_scrollback(&self, saved: &Scrollback) -> anyhow::Result<()> {
+        let lines = scrollback::decode(saved)?;
+        let bytes = scrollback::replay_bytes(&lines);
+        let mut term = self.term.lock();
+        let mut parser: Processor = Processor::new();
+        parser.advance(&mut *term, &bytes);
+        Ok(())
+    }
+
+    /// Modes, cursor style, character sets and keyboard protocol flags the
+    /// running application has set.
+    pub fn modes(&self) -> TerminalModes {
+        let term = self.term.lock();
+        modes::read_modes(&term, &self.tracked.get())
+    }
+
+    /// Dump grid cells in `lines` (end exclusive; negative lines are
+    /// scrollback) and `cols`, together with cursor and scroll state.
+    /// Open range ends default to the visible screen.
+    pub fn dump_grid(&self, lines: Range<Option<i32>>, cols: Range<Option<usize>>) -> GridDump {
+        let term = self.term.lock();
+        grid_dump::dump_grid(&term, &self.tracked.get(), lines, cols)
+    }
+
     /// Drain pending events from the terminal.
     ///
     /// Also processes `CwdChanged` and `PromptMark` events internally
@@ -422,6 +612,11 @@ impl CruxTerminal {
         &self.semantic_zones
     }
 
+    /// Commands completed in this terminal, from OSC 133 shell integration.
+    pub fn history(&self) -> &CommandHistory {
+        &self.history
+    }
+
     /// Get the line number of the most recent prompt start.
     ///
     /// Scans completed zones in reverse for the last `Prompt` zone.
@@ -438,6 +633,17 @@ impl CruxTerminal {
             .map(|z| z.start_line)
     }
 
+    /// Whether the shell has emitted any OSC 133 prompt marks.
+    pub fn has_shell_integration(&self) -> bool {
+        self.current_zone_type.is_some() || !self.semantic_zones.is_empty()
+    }
+
+    /// Whether a command is running, i.e. `133;C` was seen without a
+    /// closing `133;D`.
+    pub fn is_command_running(&self) -> bool {
+        self.current_zone_type == Some(SemanticZoneType::Output)
+    }
+
     /// Get the current terminal size.
     pub fn size(&self) -> TerminalSize {
         self.size
@@ -453,9 +659,16 @@ impl CruxTerminal {
         self.term.lock().selection_to_string()
     }
 
-    /// Check if the child process is still running.
+    /// Check if the child process is still running. A remote terminal
+    /// counts as running until its feed is dropped.
     pub fn is_process_running(&mut self) -> bool {
-        match self.child.try_wait() {
+        let Some(child) = self.child.as_mut() else {
+            return self
+                .reader_thread
+                .as_ref()
+                .is_some_and(|thread| !thread.is_finished());
+        };
+        match child.try_wait() {
             Ok(Some(_)) => false,
             Ok(None) => true,
             Err(_) => false,
@@ -464,7 +677,7 @@ impl CruxTerminal {
 
     /// Get the child process PID.
     pub fn child_pid(&self) -> Option<u32> {
-        self.child.process_id()
+        self.child.as_ref().and_then(|child| child.process_id())
     }
 }
 
@@ -558,7 +771,13 @@ impl Drop for CruxTerminal {
         // 1. Send SIGHUP to let the shell clean up (save history, etc.)
         // 2. Wait briefly for graceful exit
         // 3. Force SIGKILL only if the child refuses to exit
-        if let Some(pid) = self.child.process_id() {
+        //
+        // A remote terminal has no child; its reader thread exits on its own
+        // once the feed is dropped, so it is not joined here.
+        let Some(child) = self.child.as_mut() else {
+            return;
+        };
+        if let Some(pid) = child.process_id() {
             // Safe cast: PIDs on macOS/Linux are always within i32 range.
             let pid_i32 = i32::try_from(pid).expect("PID exceeds i32::MAX");
             unsafe {
@@ -567,7 +786,7 @@ impl Drop for CruxTerminal {
 
             // Give the child up to 500ms to exit gracefully.
             for _ in 0..10 {
-                if let Ok(Some(_)) = self.child.try_wait() {
+                if let Ok(Some(_)) = child.try_wait() {
                     if let Some(thread) = self.reader_thread.take() {
                         let _ = thread.join();
                     }
@@ -578,12 +797,12 @@ impl Drop for CruxTerminal {
         }
 
         // Force kill if still running.
-        if let Err(e) = self.child.kill() {
+        if let Err(e) = child.kill() {
             log::debug!("failed to kill child process: {}", e);
         }
 
         // Reap to prevent zombies.
-        if let Err(e) = self.child.wait() {
+        if let Err(e) = child.wait() {
             log::debug!("failed to wait for child process: {}", e);
         }
 
@@ -596,12 +815,24 @@ impl Drop for CruxTerminal {
     }
 }
 
+/// The image or placement ID a placeholder cell's color encodes.
+fn color_id(color: Color) -> u32 {
+    match color {
+        Color::Spec(rgb) => u32::from(rgb.r) << 16 | u32::from(rgb.g) << 8 | u32::from(rgb.b),
+        Color::Indexed(index) => u32::from(index),
+        Color::Named(_) => 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use alacritty_terminal::index::{Column, Line, Point};
     use alacritty_terminal::term::TermMode;
     use alacritty_terminal::vte::ansi::{Color, CursorShape};
+    use std::sync::Mutex;
+
+    use crate::tmux::TmuxNotification;
 
     #[test]
     fn test_terminal_size_default() {
@@ -638,6 +869,7 @@ mod tests {
             cols: 80,
             rows: 24,
             damage: DamageState::None,
+            image_placeholders: Vec::new(),
         };
 
         let lines = extract_text_lines(&content);
@@ -673,6 +905,7 @@ mod tests {
             cols: 80,
             rows: 3,
             damage: DamageState::None,
+            image_placeholders: Vec::new(),
         };
 
         let lines = extract_text_lines(&content);
@@ -708,6 +941,7 @@ mod tests {
             cols: 80,
             rows: 1,
             damage: DamageState::None,
+            image_placeholders: Vec::new(),
         };
 
         let lines = extract_text_lines(&content);
@@ -766,6 +1000,7 @@ mod tests {
             cols: 80,
             rows: 4,
             damage: DamageState::None,
+            image_placeholders: Vec::new(),
         };
 
         let lines = extract_text_lines(&content);
@@ -803,10 +1038,268 @@ mod tests {
             cols: 80,
             rows: 1,
             damage: DamageState::None,
+            image_placeholders: Vec::new(),
         };
 
         let lines = extract_text_lines(&content);
         assert_eq!(lines.len(), 1);
         assert_eq!(lines[0], "");
     }
+
+    #[test]
+    fn test_set_scrollback_lines_trims_history() {
+        let (mut terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(std::io::sink()));
+        feed.feed(
+            (0..100)
+                .map(|i| format!("line {i}\r\n"))
+                .collect::<String>()
+                .into_bytes(),
+        );
+
+        let history =
+            |terminal: &CruxTerminal| terminal.with_term(|term| term.grid().history_size());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while history(&terminal) < 77 {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        terminal.set_scrollback_lines(10);
+        assert_eq!(terminal.size().scrollback_lines, 10);
+        assert_eq!(history(&terminal), 10);
+    }
+
+    #[test]
+    fn test_kitty_image_places_replies_and_scrolls() {
+        let (mut terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(std::io::sink()));
+        // A 2x2 RGBA image shown over 3x2 cells after "ab".
+        feed.feed(b"ab\x1b_Ga=T,f=32,s=2,v=2,i=7,c=3,r=2;AAAA/wAAAP8AAAD/AAAA/w==\x1b\\X".to_vec());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut replies = Vec::new();
+        while replies.is_empty() {
+            replies.extend(
+                terminal
+                    .drain_events()
+                    .into_iter()
+                    .filter_map(|event| match event {
+                        TerminalEvent::PtyWrite(text) => Some(text),
+                        _ => None,
+                    }),
+            );
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(replies, ["\x1b_Gi=7;OK\x1b\\"]);
+
+        // The cursor moved past the image before "X" was printed.
+        let placements = |terminal: &CruxTerminal| {
+            terminal.graphics().with_images(|images| {
+                images
+                    .get_placements_in_range(i32::MIN, i32::MAX)
+                    .into_iter()
+                    .map(|p| (p.column, p.row - images.top_line(), p.columns, p.rows))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(placements(&terminal), [(2, 0, 3, 2)]);
+        assert_eq!(
+            terminal.with_term(|term| term.grid().cursor.point),
+            Point::new(Line(1), Column(6))
+        );
+
+        // Scrolling a line off the top takes the placement with it.
+        feed.feed(b"\x1b[24;1H\n".to_vec());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while placements(&terminal) != [(2, -1, 3, 2)] {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // Erasing the screen deletes it, since it is still partly visible.
+        feed.feed(b"\x1b[2J".to_vec());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !placements(&terminal).is_empty() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_images_per_screen_and_placeholders() {
+        let (terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(std::io::sink()));
+        let wait_for = |done: &dyn Fn() -> bool| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+            while !done() {
+                assert!(std::time::Instant::now() < deadline);
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        };
+        let placed = |terminal: &CruxTerminal| {
+            terminal
+                .graphics()
+                .with_images(|images| images.placement_count())
+        };
+        // A 1x1 image placed on the primary screen.
+        feed.feed(b"\x1b_Ga=T,f=32,s=1,v=1,i=1,q=2;AAAA/w==\x1b\\".to_vec());
+        wait_for(&|| placed(&terminal) == 1);
+
+        // The alternate screen starts without it, and gives it back on exit.
+        feed.feed(b"\x1b[?1049h".to_vec());
+        wait_for(&|| placed(&terminal) == 0);
+        feed.feed(b"\x1b[?1049l".to_vec());
+        wait_for(&|| placed(&terminal) == 1);
+
+        // A virtual placement drawn by placeholder cells: image 2 in red,
+        // rows and columns in diacritics.
+        feed.feed(b"\x1b_Ga=T,f=32,s=1,v=1,i=2,U=1,c=2,r=1,q=2;AAAA/w==\x1b\\".to_vec());
+        feed.feed("\x1b[3;1H\x1b[38;2;0;0;2m\u{10EEEE}\u{305}\u{305}\u{10EEEE}".into());
+        wait_for(&|| !terminal.content().image_placeholders.is_empty());
+        let runs = terminal.content().image_placeholders;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(
+            (
+                runs[0].image_id,
+                runs[0].line,
+                runs[0].column,
+                runs[0].columns
+            ),
+            (ImageId(2), 2, 0, 2)
+        );
+        let placement = terminal
+            .graphics()
+            .with_images(|images| images.placeholder_placement(&runs[0]))
+            .unwrap();
+        assert_eq!((placement.row, placement.columns), (2, 2));
+    }
+
+    #[test]
+    fn test_iterm2_image_and_download() {
+        const PNG_1X1: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+        let (mut terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(std::io::sink()));
+        feed.feed(format!("\x1b]1337;File=inline=1;width=3;height=2:{PNG_1X1}\x07X").into_bytes());
+        // "bm90ZXM=" is "notes".
+        feed.feed(b"\x1b]1337;File=name=bm90ZXM=;size=2:aGk=\x1b\\".to_vec());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut downloads = Vec::new();
+        while downloads.is_empty() {
+            downloads.extend(
+                terminal
+                    .drain_events()
+                    .into_iter()
+                    .filter_map(|event| match event {
+                        TerminalEvent::FileDownload { name, data } => Some((name, data)),
+                        _ => None,
+                    }),
+            );
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(downloads, [(Some("notes".to_string()), b"hi".to_vec())]);
+
+        let placements = terminal.graphics().with_images(|images| {
+            images
+                .get_placements_in_range(0, 24)
+                .into_iter()
+                .map(|p| (p.column, p.row, p.columns, p.rows))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(placements, [(0, 0, 3, 2)]);
+        assert_eq!(
+            terminal.with_term(|term| term.grid().cursor.point),
+            Point::new(Line(1), Column(4))
+        );
+    }
+
+    #[test]
+    fn test_sixel_image_moves_cursor_to_last_row() {
+        let (terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(std::io::sink()));
+        // 16x40 pixels cover 2x3 cells of 8x16.
+        feed.feed(b"ab\x1bP0;1q\"1;1;16;40#1!16~\x1b\\X".to_vec());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while terminal.read_text(Some(2), Some(3), false).lines != ["  X"] {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let placements = terminal.graphics().with_images(|images| {
+            images
+                .get_placements_in_range(0, 24)
+                .into_iter()
+                .map(|p| (p.column, p.row, p.columns, p.rows))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(placements, [(2, 0, 2, 3)]);
+    }
+
+    #[test]
+    fn test_remote_terminal_strips_tmux_control_mode() {
+        #[derive(Clone, Default)]
+        struct Sink(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let sink = Sink::default();
+        let (mut terminal, feed) =
+            CruxTerminal::new_remote(TerminalSize::default(), Box::new(sink.clone()));
+        feed.feed(b"hello\x1bP1000p%window-add @1\r\n%exit\r\n\x1b\\world".to_vec());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.len() < 3 {
+            events.extend(
+                terminal
+                    .drain_events()
+                    .into_iter()
+                    .filter_map(|event| match event {
+                        TerminalEvent::Tmux(notification) => Some(notification),
+                        _ => None,
+                    }),
+            );
+            assert!(
+                std::time::Instant::now() < deadline,
+                "events were {events:?}"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(
+            events,
+            [
+                TmuxNotification::Enter,
+                TmuxNotification::WindowAdd(crate::TmuxWindowId(1)),
+                TmuxNotification::Exit { reason: None },
+            ]
+        );
+        assert_eq!(
+            terminal.read_text(Some(0), Some(1), false).lines,
+            ["helloworld"]
+        );
+        assert!(!terminal.tmux_router().is_active());
+        assert!(terminal.is_process_running());
+
+        terminal.write_to_pty(b"typed");
+        assert_eq!(*sink.0.lock().unwrap(), b"typed");
+
+        drop(feed);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while terminal.is_process_running() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
 }
@@ -0,0 +1,355 @@
+//! Text extraction from the terminal grid, including scrollback.
+//!
+//! Lines are addressed like alacritty's grid: 0 is the top of the screen,
+//! negative lines reach into scrollback history. Rows joined by soft wraps
+//! come back as one logical line. With escapes enabled, SGR sequences are
+//! rebuilt from each cell's colors and flags so the output renders the same
+//! when written back to a terminal.
+
+use std::fmt::Write as _;
+
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line, Point};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::Term;
+use alacritty_terminal::vte::ansi::{Color, NamedColor};
+
+/// Tab stop interval assumed when collapsing tab padding.
+const TAB_WIDTH: usize = 8;
+
+/// Flags that map onto SGR attributes.
+const SGR_FLAGS: Flags = Flags::BOLD
+    .union(Flags::DIM)
+    .union(Flags::ITALIC)
+    .union(Flags::ALL_UNDERLINES)
+    .union(Flags::INVERSE)
+    .union(Flags::HIDDEN)
+    .union(Flags::STRIKEOUT);
+
+/// Lines read by [`CruxTerminal::read_text`](crate::CruxTerminal::read_text).
+#[derive(Debug, Clone)]
+pub struct GridText {
+    /// Logical lines; soft-wrapped rows are joined.
+    pub lines: Vec<String>,
+    /// Grid line of the first row read (after clamping to the buffer).
+    pub first_line: i32,
+    /// Cursor position on the screen.
+    pub cursor: Point,
+}
+
+/// Read grid rows `start_line..end_line` (end exclusive).
+///
+/// `start_line` defaults to the top of the screen and `end_line` to its
+/// bottom; both are clamped to the available scrollback and screen.
+pub(crate) fn read_text<T>(
+    term: &Term<T>,
+    start_line: Option<i32>,
+    end_line: Option<i32>,
+    escapes: bool,
+) -> GridText {
+    let grid = term.grid();
+    let top = -(grid.history_size() as i32);
+    let bottom = grid.screen_lines() as i32;
+    let start = start_line.unwrap_or(0).clamp(top, bottom);
+    let end = end_line.unwrap_or(bottom).clamp(start, bottom);
+    let cols = grid.columns();
+
+    let mut lines = Vec::new();
+    let mut writer = LineWriter::new(escapes);
+    for line in start..end {
+        let row = &grid[Line(line)];
+        let wrapped = line + 1 < end && row[Column(cols - 1)].flags.contains(Flags::WRAPLINE);
+        let len = if wrapped {
+            cols
+        } else {
+            (0..cols)
+                .rev()
+                .find(|&col| is_visible(&row[Column(col)], escapes))
+                .map_or(0, |col| col + 1)
+        };
+        for col in 0..len {
+            writer.push(&row[Column(col)], col);
+        }
+        if !wrapped {
+            lines.push(writer.finish());
+        }
+    }
+
+    GridText {
+        lines,
+        first_line: start,
+        cursor: grid.cursor.point,
+    }
+}
+
+/// Whether a cell must be kept when trimming the end of a line.
+fn is_visible(cell: &Cell, escapes: bool) -> bool {
+    if cell.c != ' ' && cell.c != '\t' {
+        return true;
+    }
+    escapes
+        && (cell.bg != Color::Named(NamedColor::Background)
+            || cell
+                .flags
+                .intersects(Flags::INVERSE | Flags::ALL_UNDERLINES | Flags::STRIKEOUT))
+}
+
+/// SGR-relevant attributes of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Style {
+    fg: Color,
+    bg: Color,
+    underline: Option<Color>,
+    flags: Flags,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fg: Color::Named(NamedColor::Foreground),
+            bg: Color::Named(NamedColor::Background),
+            underline: None,
+            flags: Flags::empty(),
+        }
+    }
+}
+
+impl Style {
+    fn of(cell: &Cell) -> Self {
+        Self {
+            fg: cell.fg,
+            bg: cell.bg,
+            underline: cell.underline_color(),
+            flags: cell.flags & SGR_FLAGS,
+        }
+    }
+
+    /// Write a complete SGR sequence: reset, then every attribute.
+    fn write_sgr(&self, out: &mut String) {
+        out.push_str("\x1b[0");
+        let flags = [
+            (Flags::BOLD, "1"),
+            (Flags::DIM, "2"),
+            (Flags::ITALIC, "3"),
+            (Flags::UNDERLINE, "4"),
+            (Flags::DOUBLE_UNDERLINE, "4:2"),
+            (Flags::UNDERCURL, "4:3"),
+            (Flags::DOTTED_UNDERLINE, "4:4"),
+            (Flags::DASHED_UNDERLINE, "4:5"),
+            (Flags::INVERSE, "7"),
+            (Flags::HIDDEN, "8"),
+            (Flags::STRIKEOUT, "9"),
+        ];
+        for (flag, code) in flags {
+            if self.flags.contains(flag) {
+                out.push(';');
+                out.push_str(code);
+            }
+        }
+        write_color(out, self.fg, ColorSlot::Foreground);
+        write_color(out, self.bg, ColorSlot::Background);
+        if let Some(color) = self.underline {
+            write_color(out, color, ColorSlot::Underline);
+        }
+        out.push('m');
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ColorSlot {
+    Foreground,
+    Background,
+    Underline,
+}
+
+fn write_color(out: &mut String, color: Color, slot: ColorSlot) {
+    let extended = match slot {
+        ColorSlot::Foreground => 38,
+        ColorSlot::Background => 48,
+        ColorSlot::Underline => 58,
+    };
+    let index = match color {
+        Color::Named(named) => match named as usize {
+            n @ 0..=15 => n,
+            n if (NamedColor::DimBlack as usize..=NamedColor::DimWhite as usize).contains(&n) => {
+                n - NamedColor::DimBlack as usize
+            }
+            // Default foreground/background: covered by the leading reset.
+            _ => return,
+        },
+        Color::Indexed(index) => index as usize,
+        Color::Spec(rgb) => {
+            let _ = write!(out, ";{extended};2;{};{};{}", rgb.r, rgb.g, rgb.b);
+            return;
+        }
+    };
+    let base = match slot {
+        ColorSlot::Foreground => 30,
+        ColorSlot::Background => 40,
+        ColorSlot::Underline => {
+            let _ = write!(out, ";58;5;{index}");
+            return;
+        }
+    };
+    let _ = match (color, index) {
+        (Color::Named(_), 0..=7) => write!(out, ";{}", base + index),
+        (Color::Named(_), 8..=15) => write!(out, ";{}", base + 60 + index - 8),
+        _ => write!(out, ";{extended};5;{index}"),
+    };
+}
+
+/// Accumulates one logical line, tracking the active SGR style.
+struct LineWriter {
+    text: String,
+    escapes: bool,
+    style: Style,
+    /// Skip blank padding cells up to this column (after a tab).
+    tab_until: Option<usize>,
+}
+
+impl LineWriter {
+    fn new(escapes: bool) -> Self {
+        Self {
+            text: String::new(),
+            escapes,
+            style: Style::default(),
+            tab_until: None,
+        }
+    }
+
+    fn push(&mut self, cell: &Cell, col: usize) {
+        if let Some(until) = self.tab_until {
+            if col < until && cell.c == ' ' {
+                return;
+            }
+            self.tab_until = None;
+        }
+        if cell
+            .flags
+            .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER)
+        {
+            return;
+        }
+        if self.escapes {
+            let style = Style::of(cell);
+            if style != self.style {
+                style.write_sgr(&mut self.text);
+                self.style = style;
+            }
+        }
+        self.text.push(cell.c);
+        if cell.c == '\t' {
+            self.tab_until = Some((col / TAB_WIDTH + 1) * TAB_WIDTH);
+        }
+        for &c in cell.zerowidth().into_iter().flatten() {
+            self.text.push(c);
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        if self.style != Style::default() {
+            self.text.push_str("\x1b[0m");
+        }
+        self.style = Style::default();
+        self.tab_until = None;
+        std::mem::take(&mut self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    use crate::terminal::TerminalSize;
+
+    fn term_with(rows: usize, cols: usize, bytes: &[u8]) -> Term<VoidListener> {
+        let size = TerminalSize {
+            rows,
+            cols,
+            ..TerminalSize::default()
+        };
+        let config = Config {
+            scrolling_history: 100,
+            ..Config::default()
+        };
+        let mut term = Term::new(config, &size, VoidListener);
+        let mut parser: Processor = Processor::new();
+        parser.advance(&mut term, bytes);
+        term
+    }
+
+    #[test]
+    fn test_defaults_to_visible_screen() {
+        let term = term_with(3, 20, b"one\r\ntwo   \r\nthree");
+        let text = read_text(&term, None, None, false);
+        assert_eq!(text.lines, ["one", "two", "three"]);
+        assert_eq!(text.first_line, 0);
+        assert_eq!(text.cursor.line, Line(2));
+    }
+
+    #[test]
+    fn test_negative_lines_read_scrollback() {
+        let output: String = (0..10).map(|i| format!("l{i}\r\n")).collect();
+        let term = term_with(3, 20, output.as_bytes());
+        // l8 and l9 are on screen above the empty cursor line.
+        let text = read_text(&term, Some(-3), Some(1), false);
+        assert_eq!(text.lines, ["l5", "l6", "l7", "l8"]);
+        assert_eq!(text.first_line, -3);
+    }
+
+    #[test]
+    fn test_range_is_clamped_to_buffer() {
+        let term = term_with(3, 20, b"a\r\nb\r\n");
+        let text = read_text(&term, Some(-1000), Some(1000), false);
+        assert_eq!(text.first_line, 0);
+        assert_eq!(text.lines, ["a", "b", ""]);
+        assert!(read_text(&term, Some(2), Some(1), false).lines.is_empty());
+    }
+
+    #[test]
+    fn test_wrapped_rows_are_joined() {
+        let term = term_with(4, 5, b"abcdefgh\r\nxy");
+        let text = read_text(&term, None, None, false);
+        assert_eq!(text.lines, ["abcdefgh", "xy", ""]);
+    }
+
+    #[test]
+    fn test_wide_chars_and_tabs() {
+        let term = term_with(2, 20, "가나\tx".as_bytes());
+        let text = read_text(&term, Some(0), Some(1), false);
+        assert_eq!(text.lines, ["가나\tx"]);
+    }
+
+    #[test]
+    fn test_escapes_rebuild_sgr() {
+        let term = term_with(
+            2,
+            40,
+            b"\x1b[1;31mred\x1b[0m plain \x1b[38;5;200;48;2;1;2;3mx\x1b[0m",
+        );
+        let text = read_text(&term, Some(0), Some(1), true);
+        assert_eq!(
+            text.lines,
+            ["\x1b[0;1;31mred\x1b[0m plain \x1b[0;38;5;200;48;2;1;2;3mx\x1b[0m"]
+        );
+    }
+
+    #[test]
+    fn test_escapes_keep_trailing_background() {
+        let term = term_with(2, 6, b"a\x1b[44m  \x1b[0m");
+        let text = read_text(&term, Some(0), Some(1), true);
+        assert_eq!(text.lines, ["a\x1b[0;44m  \x1b[0m"]);
+        let plain = read_text(&term, Some(0), Some(1), false);
+        assert_eq!(plain.lines, ["a"]);
+    }
+
+    #[test]
+    fn test_bright_and_dim_named_colors() {
+        let term = term_with(2, 20, b"\x1b[2;92mok\x1b[0m");
+        let text = read_text(&term, Some(0), Some(1), true);
+        assert_eq!(text.lines, ["\x1b[0;2;92mok\x1b[0m"]);
+    }
+}
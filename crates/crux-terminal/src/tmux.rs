@@ -0,0 +1,1286 @@
+//! tmux control mode (`tmux -CC`).
+//!
+//! In control mode tmux stops drawing and speaks a line protocol over the
+//! terminal instead: it announces itself with `DCS 1000 p`, then sends
+//! `%`-notifications and `%begin`/`%end`-framed command replies until a
+//! closing `ST`. [`TmuxControlScanner`] takes that protocol out of the PTY
+//! stream so it never reaches the VTE parser.
+//!
+//! [`TmuxController`] is the client side of the conversation. It asks tmux
+//! for its windows, turns notifications into [`TmuxAction`]s for the UI and
+//! creates a remote [`CruxTerminal`] per tmux pane. Pane output is routed
+//! to those terminals from the PTY reader thread through a [`TmuxRouter`];
+//! keystrokes typed into them go back to tmux as `send-keys` commands.
+//!
+//! # Wire format
+//!
+//! ```text
+//! ESC P 1000 p
+//! %begin 1700000000 12 0
+//! @1 b25f,80x24,0,0,3 zsh
+//! %end 1700000000 12 0
+//! %output %3 ls\015\012
+//! %layout-change @1 5f10,80x24,0,0{40x24,0,0,3,39x24,41,0,4} ...
+//! %exit
+//! ESC \
+//! ```
+
+use std::borrow::Cow;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crux_protocol::{LayoutAxis, LayoutNode, LayoutPane};
+
+use crate::pty::PtyWriter;
+use crate::remote::RemoteFeed;
+use crate::terminal::{CruxTerminal, TerminalSize};
+
+/// Sequence tmux sends when it enters control mode.
+const CONTROL_MODE_START: &[u8] = b"\x1bP1000p";
+
+/// Sequence that ends control mode (ST) at the start of a line.
+const CONTROL_MODE_END: &[u8] = b"\x1b\\";
+
+/// Longest protocol line kept; longer lines are dropped.
+const MAX_LINE_LEN: usize = 1024 * 1024;
+
+/// Output kept per pane while it waits for its initial contents.
+const MAX_BACKLOG: usize = 1024 * 1024;
+
+/// Deepest layout nesting accepted from tmux.
+const MAX_LAYOUT_DEPTH: usize = 64;
+
+/// Keys per `send-keys` command.
+const SEND_KEYS_CHUNK: usize = 256;
+
+/// `list-windows` format: window ID, layout and name (which may have spaces).
+const LIST_WINDOWS_FORMAT: &str = "#{window_id} #{window_layout} #{window_name}";
+
+/// A tmux pane (`%N`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TmuxPaneId(pub u32);
+
+/// A tmux window (`@N`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TmuxWindowId(pub u32);
+
+impl fmt::Display for TmuxPaneId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for TmuxWindowId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+impl TmuxPaneId {
+    fn parse(s: &str) -> Option<Self> {
+        s.strip_prefix('%')?.parse().ok().map(Self)
+    }
+}
+
+impl TmuxWindowId {
+    fn parse(s: &str) -> Option<Self> {
+        s.strip_prefix('@')?.parse().ok().map(Self)
+    }
+}
+
+/// A node of a tmux window layout, sizes in cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxLayout {
+    pub cols: usize,
+    pub rows: usize,
+    pub x: usize,
+    pub y: usize,
+    pub cell: TmuxLayoutCell,
+}
+
+/// Content of a [`TmuxLayout`] node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmuxLayoutCell {
+    Pane(TmuxPaneId),
+    /// Children side by side (`{...}`).
+    LeftRight(Vec<TmuxLayout>),
+    /// Children stacked (`[...]`).
+    TopBottom(Vec<TmuxLayout>),
+}
+
+impl TmuxLayout {
+    /// Parse a layout string such as `b25f,80x24,0,0{40x24,0,0,1,39x24,41,0,2}`.
+    /// The leading checksum is optional.
+    pub fn parse(s: &str) -> Option<Self> {
+        let bytes = s.as_bytes();
+        let body = match bytes.get(4) {
+            Some(b',') if bytes[..4].iter().all(u8::is_ascii_hexdigit) => &bytes[5..],
+            _ => bytes,
+        };
+        let mut parser = LayoutParser { s: body, pos: 0 };
+        let layout = parser.node(0)?;
+        (parser.pos == body.len()).then_some(layout)
+    }
+
+    /// Panes in depth-first order (the order of [`LayoutNode::panes`] for
+    /// [`Self::to_layout`]).
+    pub fn panes(&self) -> Vec<&TmuxLayout> {
+        let mut panes = Vec::new();
+        self.collect_panes(&mut panes);
+        panes
+    }
+
+    fn collect_panes<'a>(&'a self, out: &mut Vec<&'a TmuxLayout>) {
+        match &self.cell {
+            TmuxLayoutCell::Pane(_) => out.push(self),
+            TmuxLayoutCell::LeftRight(children) | TmuxLayoutCell::TopBottom(children) => {
+                for child in children {
+                    child.collect_panes(out);
+                }
+            }
+        }
+    }
+
+    /// IDs of the panes in depth-first order.
+    pub fn pane_ids(&self) -> Vec<TmuxPaneId> {
+        self.panes()
+            .into_iter()
+            .filter_map(|node| match node.cell {
+                TmuxLayoutCell::Pane(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The same split structure as a Crux layout, with ratios taken from
+    /// the tmux cell sizes.
+    pub fn to_layout(&self) -> LayoutNode {
+        let (axis, children) = match &self.cell {
+            TmuxLayoutCell::Pane(_) => return LayoutNode::Pane(LayoutPane::default()),
+            TmuxLayoutCell::LeftRight(children) => (LayoutAxis::Horizontal, children),
+            TmuxLayoutCell::TopBottom(children) => (LayoutAxis::Vertical, children),
+        };
+        if let [only] = children.as_slice() {
+            return only.to_layout();
+        }
+        let ratios = children
+            .iter()
+            .map(|child| match axis {
+                LayoutAxis::Horizontal => child.cols.max(1) as f32,
+                LayoutAxis::Vertical => child.rows.max(1) as f32,
+            })
+            .collect();
+        LayoutNode::Split {
+            axis,
+            ratios: Some(ratios),
+            children: children.iter().map(TmuxLayout::to_layout).collect(),
+        }
+    }
+}
+
+struct LayoutParser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl LayoutParser<'_> {
+    fn node(&mut self, depth: usize) -> Option<TmuxLayout> {
+        if depth > MAX_LAYOUT_DEPTH {
+            return None;
+        }
+        let cols = self.number()?;
+        self.expect(b'x')?;
+        let rows = self.number()?;
+        self.expect(b',')?;
+        let x = self.number()?;
+        self.expect(b',')?;
+        let y = self.number()?;
+        let cell = match self.s.get(self.pos)? {
+            b',' => {
+                self.pos += 1;
+                TmuxLayoutCell::Pane(TmuxPaneId(u32::try_from(self.number()?).ok()?))
+            }
+            b'{' => TmuxLayoutCell::LeftRight(self.children(b'}', depth)?),
+            b'[' => TmuxLayoutCell::TopBottom(self.children(b']', depth)?),
+            _ => return None,
+        };
+        Some(TmuxLayout {
+            cols,
+            rows,
+            x,
+            y,
+            cell,
+        })
+    }
+
+    fn children(&mut self, close: u8, depth: usize) -> Option<Vec<TmuxLayout>> {
+        self.pos += 1;
+        let mut children = Vec::new();
+        loop {
+            children.push(self.node(depth + 1)?);
+            match self.s.get(self.pos)? {
+                b',' => self.pos += 1,
+                c if *c == close => {
+                    self.pos += 1;
+                    return Some(children);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.s.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.s.get(self.pos) == Some(&byte)).then(|| self.pos += 1)
+    }
+}
+
+/// A message from tmux in control mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TmuxNotification {
+    /// `DCS 1000 p`: control mode started.
+    Enter,
+    /// Output of one command, framed by `%begin` and `%end` (or `%error`).
+    Reply {
+        number: u64,
+        success: bool,
+        lines: Vec<String>,
+    },
+    /// `%output` / `%extended-output`: bytes written by a pane.
+    Output { pane: TmuxPaneId, data: Vec<u8> },
+    /// `%window-add`: a window was created in the attached session.
+    WindowAdd(TmuxWindowId),
+    /// `%window-close`: a window of the attached session closed.
+    WindowClose(TmuxWindowId),
+    /// `%window-renamed`.
+    WindowRenamed { window: TmuxWindowId, name: String },
+    /// `%layout-change`: panes of a window were added, removed or resized.
+    LayoutChange {
+        window: TmuxWindowId,
+        layout: TmuxLayout,
+    },
+    /// `%session-changed`: the client is now attached to another session.
+    SessionChanged { name: String },
+    /// `%exit` or the closing `ST`: control mode ended.
+    Exit { reason: Option<String> },
+    /// Any other notification, verbatim.
+    Other(String),
+}
+
+impl TmuxNotification {
+    /// Parse a notification line (outside a `%begin` block), without the
+    /// line terminator.
+    pub fn parse(line: &[u8]) -> Self {
+        let text = || String::from_utf8_lossy(line).into_owned();
+        let (name, rest) = match line.iter().position(|&b| b == b' ') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (line, &line[line.len()..]),
+        };
+
+        match name {
+            b"%output" => {
+                let Some(i) = rest.iter().position(|&b| b == b' ') else {
+                    return Self::Other(text());
+                };
+                match TmuxPaneId::parse(&String::from_utf8_lossy(&rest[..i])) {
+                    Some(pane) => Self::Output {
+                        pane,
+                        data: unescape(&rest[i + 1..]),
+                    },
+                    None => Self::Other(text()),
+                }
+            }
+            b"%extended-output" => {
+                // %extended-output %PANE AGE ... : DATA
+                let pane_end = rest.iter().position(|&b| b == b' ');
+                let data_start = rest.windows(3).position(|w| w == b" : ");
+                let pane = pane_end
+                    .and_then(|end| TmuxPaneId::parse(&String::from_utf8_lossy(&rest[..end])));
+                match (pane, data_start) {
+                    (Some(pane), Some(start)) => Self::Output {
+                        pane,
+                        data: unescape(&rest[start + 3..]),
+                    },
+                    _ => Self::Other(text()),
+                }
+            }
+            b"%exit" => Self::Exit {
+                reason: (!rest.is_empty()).then(|| String::from_utf8_lossy(rest).into_owned()),
+            },
+            _ => {
+                let rest = String::from_utf8_lossy(rest);
+                let mut args = rest.splitn(2, ' ');
+                let first = args.next().unwrap_or_default();
+                let second = args.next().unwrap_or_default();
+                let window = TmuxWindowId::parse(first);
+                match (name, window) {
+                    (b"%window-add", Some(window)) => Self::WindowAdd(window),
+                    (b"%window-close", Some(window)) => Self::WindowClose(window),
+                    (b"%window-renamed", Some(window)) => Self::WindowRenamed {
+                        window,
+                        name: second.to_string(),
+                    },
+                    (b"%layout-change", Some(window)) => {
+                        // Newer tmux appends the visible layout and flags.
+                        let layout = second.split(' ').next().and_then(TmuxLayout::parse);
+                        match layout {
+                            Some(layout) => Self::LayoutChange { window, layout },
+                            None => Self::Other(text()),
+                        }
+                    }
+                    (b"%session-changed", _) => Self::SessionChanged {
+                        name: second.to_string(),
+                    },
+                    _ => Self::Other(text()),
+                }
+            }
+        }
+    }
+}
+
+/// Undo tmux's output escaping: `\ooo` octal escapes for control bytes and
+/// backslashes.
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'\\' {
+            if let Some(digits) = data.get(i + 1..i + 4) {
+                if digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+                    let value = digits
+                        .iter()
+                        .fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+                    out.push(value as u8);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Reply being collected between `%begin` and `%end`.
+#[derive(Debug)]
+struct ReplyBlock {
+    number: u64,
+    lines: Vec<String>,
+}
+
+/// Stateful scanner that separates tmux control-mode traffic from normal
+/// terminal output.
+///
+/// Like the Kitty graphics scanner it keeps state across PTY reads: the
+/// start sequence, protocol lines and reply blocks can all be split
+/// between reads.
+#[derive(Debug, Default)]
+pub struct TmuxControlScanner {
+    /// Inside control mode.
+    active: bool,
+    /// Bytes of [`CONTROL_MODE_START`] matched at the end of the last read.
+    matched: usize,
+    /// Current protocol line.
+    line: Vec<u8>,
+    /// Current line is over [`MAX_LINE_LEN`] and is being skipped.
+    overflow: bool,
+    block: Option<ReplyBlock>,
+    /// `%exit` was seen, so the closing ST needs no second `Exit`.
+    exited: bool,
+}
+
+impl TmuxControlScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the stream is currently in control mode.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Scan one read. Returns the bytes that belong to the terminal itself;
+    /// control-mode messages are appended to `notifications`.
+    pub fn feed<'a>(
+        &mut self,
+        buf: &'a [u8],
+        notifications: &mut Vec<TmuxNotification>,
+    ) -> Cow<'a, [u8]> {
+        // Fast path: nothing that could start control mode.
+        if !self.active && self.matched == 0 && !buf.contains(&0x1b) {
+            return Cow::Borrowed(buf);
+        }
+
+        let mut passthrough = Vec::new();
+        let mut i = 0;
+        while i < buf.len() {
+            let byte = buf[i];
+            if self.active {
+                i += 1;
+                self.control_byte(byte, notifications);
+                continue;
+            }
+            if byte == CONTROL_MODE_START[self.matched] {
+                i += 1;
+                self.matched += 1;
+                if self.matched == CONTROL_MODE_START.len() {
+                    self.matched = 0;
+                    self.active = true;
+                    self.exited = false;
+                    notifications.push(TmuxNotification::Enter);
+                }
+            } else if self.matched > 0 {
+                // Not control mode after all: release the held bytes and
+                // look at this byte again from the start.
+                passthrough.extend_from_slice(&CONTROL_MODE_START[..self.matched]);
+                self.matched = 0;
+            } else {
+                passthrough.push(byte);
+                i += 1;
+            }
+        }
+        Cow::Owned(passthrough)
+    }
+
+    fn control_byte(&mut self, byte: u8, notifications: &mut Vec<TmuxNotification>) {
+        if byte == b'\n' {
+            let mut line = std::mem::take(&mut self.line);
+            if std::mem::take(&mut self.overflow) {
+                log::warn!("tmux: dropped a control-mode line over {MAX_LINE_LEN} bytes");
+                return;
+            }
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            self.handle_line(&line, notifications);
+            return;
+        }
+        if self.overflow {
+            return;
+        }
+        if self.line.len() >= MAX_LINE_LEN {
+            self.line.clear();
+            self.overflow = true;
+            return;
+        }
+        self.line.push(byte);
+        if self.line == CONTROL_MODE_END {
+            self.line.clear();
+            self.active = false;
+            self.block = None;
+            if !self.exited {
+                notifications.push(TmuxNotification::Exit { reason: None });
+            }
+        }
+    }
+
+    fn handle_line(&mut self, line: &[u8], notifications: &mut Vec<TmuxNotification>) {
+        if let Some(block) = &mut self.block {
+            let end = match line {
+                l if l.starts_with(b"%end ") => Some(true),
+                l if l.starts_with(b"%error ") => Some(false),
+                _ => None,
+            };
+            // Only the guard with our command number closes the block; a
+            // reply line may itself start with "%end".
+            if let Some(success) = end.filter(|_| guard_number(line) == Some(block.number)) {
+                let block = self.block.take().expect("reply block");
+                notifications.push(TmuxNotification::Reply {
+                    number: block.number,
+                    success,
+                    lines: block.lines,
+                });
+            } else {
+                block.lines.push(String::from_utf8_lossy(line).into_owned());
+            }
+            return;
+        }
+
+        if line.starts_with(b"%begin ") {
+            match guard_number(line) {
+                Some(number) => {
+                    self.block = Some(ReplyBlock {
+                        number,
+                        lines: Vec::new(),
+                    })
+                }
+                None => log::warn!("tmux: malformed %begin: {}", String::from_utf8_lossy(line)),
+            }
+            return;
+        }
+        if line.is_empty() {
+            return;
+        }
+        let notification = TmuxNotification::parse(line);
+        if matches!(notification, TmuxNotification::Exit { .. }) {
+            self.exited = true;
+        }
+        notifications.push(notification);
+    }
+}
+
+/// Command number of a `%begin TIME NUMBER FLAGS` style guard line.
+fn guard_number(line: &[u8]) -> Option<u64> {
+    let line = std::str::from_utf8(line).ok()?;
+    line.split(' ').nth(2)?.parse().ok()
+}
+
+/// Pane output waiting for, or delivered to, a remote terminal.
+struct RoutedPane {
+    feed: RemoteFeed,
+    /// Output held back until the pane's initial contents are in.
+    backlog: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct RouterInner {
+    /// A control-mode session is running.
+    active: AtomicBool,
+    panes: Mutex<HashMap<TmuxPaneId, RoutedPane>>,
+}
+
+/// Shared table from tmux panes to the remote terminals showing them.
+///
+/// The PTY reader thread of the terminal running tmux routes `%output`
+/// through it; the [`TmuxController`] adds and removes panes.
+#[derive(Clone, Default)]
+pub struct TmuxRouter {
+    inner: Arc<RouterInner>,
+}
+
+impl TmuxRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether tmux is in control mode in this terminal.
+    pub fn is_active(&self) -> bool {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_active(&self, active: bool) {
+        self.inner.active.store(active, Ordering::Release);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<TmuxPaneId, RoutedPane>> {
+        self.inner
+            .panes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Start routing `pane` to `feed`. Output is held back until
+    /// [`Self::release`], so the initial contents can be drawn first.
+    /// Replaces any previous feed of the pane.
+    pub fn register(&self, pane: TmuxPaneId, feed: RemoteFeed) {
+        self.lock().insert(
+            pane,
+            RoutedPane {
+                feed,
+                backlog: Some(Vec::new()),
+            },
+        );
+    }
+
+    pub fn unregister(&self, pane: TmuxPaneId) {
+        self.lock().remove(&pane);
+    }
+
+    /// Drop every pane, ending their terminals' output.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Whether `pane` is routed anywhere.
+    pub fn contains(&self, pane: TmuxPaneId) -> bool {
+        self.lock().contains_key(&pane)
+    }
+
+    /// Deliver `data` to `pane` ahead of any held-back output.
+    pub fn prime(&self, pane: TmuxPaneId, data: Vec<u8>) {
+        if let Some(routed) = self.lock().get(&pane) {
+            routed.feed.feed(data);
+        }
+    }
+
+    /// Flush the held-back output of `pane` and deliver from now on.
+    pub fn release(&self, pane: TmuxPaneId) {
+        if let Some(routed) = self.lock().get_mut(&pane) {
+            if let Some(backlog) = routed.backlog.take() {
+                if !backlog.is_empty() {
+                    routed.feed.feed(backlog);
+                }
+            }
+        }
+    }
+
+    /// Deliver `%output` of `pane`. Output of unknown panes is dropped.
+    pub(crate) fn route(&self, pane: TmuxPaneId, data: Vec<u8>) {
+        let mut panes = self.lock();
+        let Some(routed) = panes.get_mut(&pane) else {
+            return;
+        };
+        match &mut routed.backlog {
+            Some(backlog) => {
+                if backlog.len() + data.len() <= MAX_BACKLOG {
+                    backlog.extend_from_slice(&data);
+                }
+            }
+            None => {
+                if !routed.feed.feed(data) {
+                    // The terminal was closed.
+                    panes.remove(&pane);
+                }
+            }
+        }
+    }
+}
+
+/// What a command reply is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Ignore,
+    ListWindows,
+    CapturePane(TmuxPaneId),
+    CursorPosition(TmuxPaneId),
+}
+
+/// Sends commands to tmux and remembers what each reply is for.
+///
+/// tmux answers commands in order, so replies are matched to a FIFO queue.
+/// Every command, including `send-keys` from remote panes, goes through
+/// here so the queue stays in step.
+#[derive(Clone)]
+struct TmuxCommands {
+    gateway: PtyWriter,
+    pending: Arc<Mutex<VecDeque<Pending>>>,
+}
+
+impl TmuxCommands {
+    fn send(&self, command: &str, pending: Pending) {
+        let mut queue = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match self.gateway.write_all(format!("{command}\n").as_bytes()) {
+            Ok(()) => queue.push_back(pending),
+            Err(e) => log::warn!("tmux: failed to send {:?}: {}", command, e),
+        }
+    }
+
+    fn pop(&self) -> Option<Pending> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop_front()
+    }
+}
+
+/// Input side of a remote tmux pane: bytes become `send-keys` commands.
+struct TmuxPaneInput {
+    pane: TmuxPaneId,
+    commands: TmuxCommands,
+}
+
+impl Write for TmuxPaneInput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for command in send_keys(self.pane, buf) {
+            self.commands.send(&command, Pending::Ignore);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `send-keys` commands typing `data` into `pane`, as hex key codes so any
+/// byte survives tmux's command parser.
+fn send_keys(pane: TmuxPaneId, data: &[u8]) -> Vec<String> {
+    data.chunks(SEND_KEYS_CHUNK)
+        .map(|chunk| {
+            let keys: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            format!("send-keys -t {pane} -H {}", keys.join(" "))
+        })
+        .collect()
+}
+
+/// A window of the attached tmux session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxWindow {
+    pub name: String,
+    pub layout: TmuxLayout,
+}
+
+/// What the UI should do in response to tmux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmuxAction {
+    /// Show a new window. Create its panes with [`TmuxController::open_pane`].
+    OpenWindow {
+        window: TmuxWindowId,
+        name: String,
+        layout: TmuxLayout,
+    },
+    /// Panes were added to or removed from a shown window: rebuild it from
+    /// `layout`.
+    Relayout {
+        window: TmuxWindowId,
+        layout: TmuxLayout,
+    },
+    RenameWindow {
+        window: TmuxWindowId,
+        name: String,
+    },
+    CloseWindow(TmuxWindowId),
+    /// Control mode ended; every window has been closed.
+    Exit,
+}
+
+/// Client side of a tmux control-mode session.
+pub struct TmuxController {
+    commands: TmuxCommands,
+    router: TmuxRouter,
+    windows: BTreeMap<TmuxWindowId, TmuxWindow>,
+}
+
+impl TmuxController {
+    /// Start talking to tmux, which just entered control mode in the
+    /// terminal behind `gateway` and `router`. `cols` x `rows` becomes the
+    /// size of the tmux client.
+    pub fn new(gateway: PtyWriter, router: TmuxRouter, cols: usize, rows: usize) -> Self {
+        let commands = TmuxCommands {
+            gateway,
+            // The command that started tmux is answered first.
+            pending: Arc::new(Mutex::new(VecDeque::from([Pending::Ignore]))),
+        };
+        commands.send(&format!("refresh-client -C {cols}x{rows}"), Pending::Ignore);
+        let controller = Self {
+            commands,
+            router,
+            windows: BTreeMap::new(),
+        };
+        controller.list_windows();
+        controller
+    }
+
+    fn list_windows(&self) {
+        self.commands.send(
+            &format!("list-windows -F \"{LIST_WINDOWS_FORMAT}\""),
+            Pending::ListWindows,
+        );
+    }
+
+    /// Windows known so far.
+    pub fn windows(&self) -> &BTreeMap<TmuxWindowId, TmuxWindow> {
+        &self.windows
+    }
+
+    /// Create the terminal showing `pane`. Its current screen is fetched
+    /// from tmux and drawn before any new output.
+    pub fn open_pane(&self, pane: TmuxPaneId, size: TerminalSize) -> CruxTerminal {
+        let input = TmuxPaneInput {
+            pane,
+            commands: self.commands.clone(),
+        };
+        let (terminal, feed) = CruxTerminal::new_remote(size, Box::new(input));
+        self.router.register(pane, feed);
+        self.commands.send(
+            &format!("capture-pane -p -e -t {pane}"),
+            Pending::CapturePane(pane),
+        );
+        self.commands.send(
+            &format!("display-message -p -t {pane} \"#{{cursor_x}} #{{cursor_y}}\""),
+            Pending::CursorPosition(pane),
+        );
+        terminal
+    }
+
+    /// Close `pane` in tmux.
+    pub fn kill_pane(&self, pane: TmuxPaneId) {
+        self.commands
+            .send(&format!("kill-pane -t {pane}"), Pending::Ignore);
+    }
+
+    /// Detach the tmux client, ending control mode.
+    pub fn detach(&self) {
+        self.commands.send("detach-client", Pending::Ignore);
+    }
+
+    /// Process a notification from the terminal running tmux.
+    pub fn handle(&mut self, notification: TmuxNotification) -> Vec<TmuxAction> {
+        match notification {
+            TmuxNotification::Reply { success, lines, .. } => match self.commands.pop() {
+                Some(pending) => self.handle_reply(pending, success, lines),
+                None => {
+                    log::debug!("tmux: reply to a command we did not send");
+                    Vec::new()
+                }
+            },
+            TmuxNotification::WindowAdd(_) => {
+                self.list_windows();
+                Vec::new()
+            }
+            TmuxNotification::WindowClose(window) => {
+                self.close_window(window).into_iter().collect()
+            }
+            TmuxNotification::WindowRenamed { window, name } => match self.windows.get_mut(&window)
+            {
+                Some(known) => {
+                    known.name = name.clone();
+                    vec![TmuxAction::RenameWindow { window, name }]
+                }
+                None => Vec::new(),
+            },
+            TmuxNotification::LayoutChange { window, layout } => {
+                self.update_layout(window, layout).into_iter().collect()
+            }
+            TmuxNotification::SessionChanged { .. } => {
+                // Another session: replace every window with its windows.
+                self.router.clear();
+                let actions = std::mem::take(&mut self.windows)
+                    .into_keys()
+                    .map(TmuxAction::CloseWindow)
+                    .collect();
+                self.list_windows();
+                actions
+            }
+            TmuxNotification::Exit { reason } => {
+                if let Some(reason) = reason {
+                    log::info!("tmux control mode ended: {}", reason);
+                }
+                self.router.clear();
+                let mut actions: Vec<TmuxAction> = std::mem::take(&mut self.windows)
+                    .into_keys()
+                    .map(TmuxAction::CloseWindow)
+                    .collect();
+                actions.push(TmuxAction::Exit);
+                actions
+            }
+            TmuxNotification::Enter
+            | TmuxNotification::Output { .. }
+            | TmuxNotification::Other(_) => Vec::new(),
+        }
+    }
+
+    fn handle_reply(
+        &mut self,
+        pending: Pending,
+        success: bool,
+        lines: Vec<String>,
+    ) -> Vec<TmuxAction> {
+        match pending {
+            Pending::Ignore => {
+                if !success {
+                    log::debug!("tmux: command failed: {}", lines.join(" "));
+                }
+                Vec::new()
+            }
+            Pending::ListWindows if success => self.sync_windows(&lines),
+            Pending::ListWindows => {
+                log::warn!("tmux: list-windows failed: {}", lines.join(" "));
+                Vec::new()
+            }
+            Pending::CapturePane(pane) => {
+                if success {
+                    let mut screen = b"\x1b[0m\x1b[H\x1b[2J".to_vec();
+                    screen.extend_from_slice(lines.join("\r\n").as_bytes());
+                    self.router.prime(pane, screen);
+                }
+                Vec::new()
+            }
+            Pending::CursorPosition(pane) => {
+                let position = lines.first().and_then(|line| {
+                    let (x, y) = line.split_once(' ')?;
+                    Some((x.parse::<usize>().ok()?, y.parse::<usize>().ok()?))
+                });
+                if let (true, Some((x, y))) = (success, position) {
+                    self.router.prime(
+                        pane,
+                        format!("\x1b[0m\x1b[{};{}H", y + 1, x + 1).into_bytes(),
+                    );
+                }
+                self.router.release(pane);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Reconcile the known windows with a `list-windows` reply.
+    fn sync_windows(&mut self, lines: &[String]) -> Vec<TmuxAction> {
+        let mut listed = BTreeMap::new();
+        for line in lines {
+            let mut fields = line.splitn(3, ' ');
+            let window = fields.next().and_then(TmuxWindowId::parse);
+            let layout = fields.next().and_then(TmuxLayout::parse);
+            let name = fields.next().unwrap_or_default().to_string();
+            match (window, layout) {
+                (Some(window), Some(layout)) => {
+                    listed.insert(window, TmuxWindow { name, layout });
+                }
+                _ => log::warn!("tmux: unexpected list-windows line: {}", line),
+            }
+        }
+
+        let mut actions: Vec<TmuxAction> = self
+            .windows
+            .keys()
+            .filter(|window| !listed.contains_key(window))
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|window| self.close_window(window))
+            .collect();
+        for (window, info) in listed {
+            match self.windows.entry(window) {
+                Entry::Vacant(entry) => {
+                    actions.push(TmuxAction::OpenWindow {
+                        window,
+                        name: info.name.clone(),
+                        layout: info.layout.clone(),
+                    });
+                    entry.insert(info);
+                }
+                Entry::Occupied(_) => actions.extend(self.update_layout(window, info.layout)),
+            }
+        }
+        actions
+    }
+
+    /// Record a window's new layout. Only a change in its set of panes needs
+    /// the UI to rebuild the window; tmux redraws resized panes itself.
+    fn update_layout(&mut self, window: TmuxWindowId, layout: TmuxLayout) -> Option<TmuxAction> {
+        let known = self.windows.get_mut(&window)?;
+        let old = known.layout.pane_ids();
+        let new = layout.pane_ids();
+        known.layout = layout.clone();
+        if old == new {
+            return None;
+        }
+        for pane in old.iter().filter(|pane| !new.contains(pane)) {
+            self.router.unregister(*pane);
+        }
+        Some(TmuxAction::Relayout { window, layout })
+    }
+
+    fn close_window(&mut self, window: TmuxWindowId) -> Option<TmuxAction> {
+        let closed = self.windows.remove(&window)?;
+        for pane in closed.layout.pane_ids() {
+            self.router.unregister(pane);
+        }
+        Some(TmuxAction::CloseWindow(window))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(scanner: &mut TmuxControlScanner, input: &[u8]) -> (Vec<u8>, Vec<TmuxNotification>) {
+        let mut notifications = Vec::new();
+        let passthrough = scanner.feed(input, &mut notifications).into_owned();
+        (passthrough, notifications)
+    }
+
+    /// A gateway writer that records what was sent to tmux.
+    #[derive(Clone, Default)]
+    struct Sent(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Sent {
+        fn take_lines(&self) -> Vec<String> {
+            let bytes = std::mem::take(&mut *self.0.lock().unwrap());
+            String::from_utf8(bytes)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    fn reply(lines: &[&str]) -> TmuxNotification {
+        TmuxNotification::Reply {
+            number: 1,
+            success: true,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_scanner_passes_plain_output_through() {
+        let mut scanner = TmuxControlScanner::new();
+        let (out, notes) = scan(&mut scanner, b"hello \x1b[1mworld\x1bPq#0");
+        assert_eq!(out, b"hello \x1b[1mworld\x1bPq#0");
+        assert!(notes.is_empty());
+        assert!(!scanner.is_active());
+    }
+
+    #[test]
+    fn test_scanner_control_mode_session() {
+        let mut scanner = TmuxControlScanner::new();
+        // The start sequence is split across reads.
+        let (out, notes) = scan(&mut scanner, b"$ tmux -CC\r\n\x1bP10");
+        assert_eq!(out, b"$ tmux -CC\r\n");
+        assert!(notes.is_empty());
+
+        let (out, notes) = scan(
+            &mut scanner,
+            b"00p%begin 1 5 0\n%end not ours\n%end 1 5 0\n%output %3 a\\033[Kb\\134\n%window-",
+        );
+        assert!(out.is_empty());
+        assert_eq!(
+            notes,
+            vec![
+                TmuxNotification::Enter,
+                TmuxNotification::Reply {
+                    number: 5,
+                    success: true,
+                    lines: vec!["%end not ours".into()],
+                },
+                TmuxNotification::Output {
+                    pane: TmuxPaneId(3),
+                    data: b"a\x1b[Kb\\".to_vec(),
+                },
+            ]
+        );
+        assert!(scanner.is_active());
+
+        let (out, notes) = scan(
+            &mut scanner,
+            b"add @2\r\n%begin 2 6 1\n%error 2 6 1\n%exit\n\x1b\\$ ",
+        );
+        assert_eq!(out, b"$ ");
+        assert_eq!(
+            notes,
+            vec![
+                TmuxNotification::WindowAdd(TmuxWindowId(2)),
+                TmuxNotification::Reply {
+                    number: 6,
+                    success: false,
+                    lines: vec![],
+                },
+                TmuxNotification::Exit { reason: None },
+            ]
+        );
+        assert!(!scanner.is_active());
+
+        // A bare ST also ends control mode.
+        let (_, notes) = scan(&mut scanner, b"\x1bP1000p\x1b\\");
+        assert_eq!(
+            notes,
+            vec![
+                TmuxNotification::Enter,
+                TmuxNotification::Exit { reason: None }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_notifications() {
+        assert_eq!(
+            TmuxNotification::parse(b"%extended-output %12 450 : x\\015"),
+            TmuxNotification::Output {
+                pane: TmuxPaneId(12),
+                data: b"x\r".to_vec(),
+            }
+        );
+        assert_eq!(
+            TmuxNotification::parse(b"%window-renamed @4 my editor"),
+            TmuxNotification::WindowRenamed {
+                window: TmuxWindowId(4),
+                name: "my editor".into(),
+            }
+        );
+        assert_eq!(
+            TmuxNotification::parse(b"%exit detached"),
+            TmuxNotification::Exit {
+                reason: Some("detached".into())
+            }
+        );
+        let change =
+            TmuxNotification::parse(b"%layout-change @1 6a3e,80x24,0,0,1 6a3e,80x24,0,0,1 *");
+        assert!(matches!(
+            change,
+            TmuxNotification::LayoutChange {
+                window: TmuxWindowId(1),
+                ..
+            }
+        ));
+        assert!(matches!(
+            TmuxNotification::parse(b"%sessions-changed"),
+            TmuxNotification::Other(_)
+        ));
+        assert!(matches!(
+            TmuxNotification::parse(b"%output garbage"),
+            TmuxNotification::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let layout = TmuxLayout::parse(
+            "b25f,160x40,0,0{80x40,0,0,1,79x40,81,0[79x20,81,0,2,79x19,81,21,3]}",
+        )
+        .unwrap();
+        assert_eq!((layout.cols, layout.rows), (160, 40));
+        assert_eq!(
+            layout.pane_ids(),
+            [TmuxPaneId(1), TmuxPaneId(2), TmuxPaneId(3)]
+        );
+        let sizes: Vec<_> = layout.panes().iter().map(|p| (p.cols, p.rows)).collect();
+        assert_eq!(sizes, [(80, 40), (79, 20), (79, 19)]);
+
+        match layout.to_layout() {
+            LayoutNode::Split {
+                axis,
+                ratios,
+                children,
+            } => {
+                assert_eq!(axis, LayoutAxis::Horizontal);
+                assert_eq!(ratios.unwrap(), [80.0, 79.0]);
+                assert!(matches!(
+                    &children[1],
+                    LayoutNode::Split {
+                        axis: LayoutAxis::Vertical,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("unexpected layout {other:?}"),
+        }
+
+        assert_eq!(
+            TmuxLayout::parse("80x24,0,0,7").unwrap().cell,
+            TmuxLayoutCell::Pane(TmuxPaneId(7))
+        );
+        assert!(TmuxLayout::parse("80x24,0,0{").is_none());
+        assert!(TmuxLayout::parse("80x24,0,0,1trailing").is_none());
+        assert!(TmuxLayout::parse("").is_none());
+    }
+
+    #[test]
+    fn test_send_keys_uses_hex() {
+        assert_eq!(
+            send_keys(TmuxPaneId(2), b"ls\r"),
+            ["send-keys -t %2 -H 6c 73 0d"]
+        );
+        assert_eq!(send_keys(TmuxPaneId(2), &[b'x'; 300]).len(), 2);
+    }
+
+    #[test]
+    fn test_controller_tracks_windows() {
+        let sent = Sent::default();
+        let router = TmuxRouter::new();
+        let mut controller = TmuxController::new(
+            PtyWriter::new(Box::new(sent.clone())),
+            router.clone(),
+            120,
+            40,
+        );
+        assert_eq!(
+            sent.take_lines(),
+            [
+                "refresh-client -C 120x40",
+                "list-windows -F \"#{window_id} #{window_layout} #{window_name}\"",
+            ]
+        );
+
+        // The reply to the command that started tmux comes first.
+        assert!(controller.handle(reply(&[])).is_empty());
+        assert!(controller.handle(reply(&[])).is_empty());
+        let actions = controller.handle(reply(&[
+            "@1 b25f,80x24,0,0,1 zsh",
+            "@3 0000,80x24,0,0{40x24,0,0,4,39x24,41,0,5} two words",
+        ]));
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(
+            &actions[1],
+            TmuxAction::OpenWindow { window: TmuxWindowId(3), name, .. } if name == "two words"
+        ));
+
+        // Opening a pane asks tmux for its screen; output waits for it.
+        let mut terminal = controller.open_pane(TmuxPaneId(4), TerminalSize::default());
+        assert_eq!(
+            sent.take_lines(),
+            [
+                "capture-pane -p -e -t %4",
+                "display-message -p -t %4 \"#{cursor_x} #{cursor_y}\"",
+            ]
+        );
+        router.route(TmuxPaneId(4), b" later".to_vec());
+        controller.handle(reply(&["$ earlier"]));
+        controller.handle(reply(&["9 0"]));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let text = terminal.read_text(None, None, false).lines.join("\n");
+            if text.contains("$ earlier later") {
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "screen was {text:?}");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // Typing into the remote pane sends keys to tmux.
+        terminal.write_to_pty(b"q");
+        assert_eq!(sent.take_lines(), ["send-keys -t %4 -H 71"]);
+        assert!(controller.handle(reply(&[])).is_empty());
+
+        // Only a change in the pane set needs a rebuild.
+        let resized = TmuxLayout::parse("80x24,0,0{30x24,0,0,4,49x24,31,0,5}").unwrap();
+        assert!(controller
+            .handle(TmuxNotification::LayoutChange {
+                window: TmuxWindowId(3),
+                layout: resized,
+            })
+            .is_empty());
+        let merged = TmuxLayout::parse("80x24,0,0,5").unwrap();
+        assert!(matches!(
+            controller
+                .handle(TmuxNotification::LayoutChange {
+                    window: TmuxWindowId(3),
+                    layout: merged,
+                })
+                .as_slice(),
+            [TmuxAction::Relayout {
+                window: TmuxWindowId(3),
+                ..
+            }]
+        ));
+        assert!(!router.contains(TmuxPaneId(4)));
+
+        assert_eq!(
+            controller.handle(TmuxNotification::WindowClose(TmuxWindowId(1))),
+            [TmuxAction::CloseWindow(TmuxWindowId(1))]
+        );
+        assert_eq!(
+            controller.handle(TmuxNotification::Exit { reason: None }),
+            [TmuxAction::CloseWindow(TmuxWindowId(3)), TmuxAction::Exit]
+        );
+        assert!(controller.windows().is_empty());
+    }
+}
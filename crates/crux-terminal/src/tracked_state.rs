@@ -0,0 +1,192 @@
+//! Terminal state that `alacritty_terminal` keeps private.
+//!
+//! A second, minimal VTE parser runs over the PTY output on the reader
+//! thread and records the few settings IPC introspection needs but `Term`
+//! does not expose: the DECSTBM scrolling region and the active character
+//! set (SI/SO).
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use alacritty_terminal::vte::ansi::CharsetIndex;
+use alacritty_terminal::vte::{Params, Parser, Perform};
+
+/// Snapshot of tracked settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackedModes {
+    /// DECSTBM margins as sent by the application: 1-based top and
+    /// optional bottom line. `None` means the full screen.
+    pub scroll_margins: Option<(usize, Option<usize>)>,
+    /// Character set slot invoked into GL: G0 after SI, G1 after SO.
+    pub active_charset: CharsetIndex,
+}
+
+impl TrackedModes {
+    /// Scrolling region as a half-open range of screen lines, resolved
+    /// the same way `alacritty_terminal` applies DECSTBM.
+    pub fn scroll_region(&self, screen_lines: usize) -> std::ops::Range<usize> {
+        match self.scroll_margins {
+            Some((top, bottom)) => {
+                let top = top.max(1) - 1;
+                let bottom = bottom.unwrap_or(screen_lines).min(screen_lines);
+                if top < bottom {
+                    top..bottom
+                } else {
+                    0..screen_lines
+                }
+            }
+            None => 0..screen_lines,
+        }
+    }
+}
+
+/// Shared handle to [`TrackedModes`], updated by the reader thread.
+#[derive(Debug, Clone, Default)]
+pub struct TrackedState {
+    inner: Arc<Mutex<TrackedModes>>,
+}
+
+impl TrackedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current tracked settings.
+    pub fn get(&self) -> TrackedModes {
+        *self.lock()
+    }
+
+    /// Forget state that a resize resets in `alacritty_terminal`.
+    pub(crate) fn on_resize(&self) {
+        self.lock().scroll_margins = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TrackedModes> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Reader-thread parser feeding a [`TrackedState`].
+pub(crate) struct StateTracker {
+    parser: Parser,
+    performer: TrackerPerformer,
+}
+
+impl StateTracker {
+    pub(crate) fn new(state: TrackedState) -> Self {
+        Self {
+            parser: Parser::new(),
+            performer: TrackerPerformer { state },
+        }
+    }
+
+    pub(crate) fn advance(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.performer, bytes);
+    }
+}
+
+struct TrackerPerformer {
+    state: TrackedState,
+}
+
+impl Perform for TrackerPerformer {
+    fn execute(&mut self, byte: u8) {
+        let index = match byte {
+            0x0E => CharsetIndex::G1, // SO
+            0x0F => CharsetIndex::G0, // SI
+            _ => return,
+        };
+        self.state.lock().active_charset = index;
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore || !intermediates.is_empty() || action != 'r' {
+            return;
+        }
+        // DECSTBM: CSI Pt ; Pb r. Zero or missing values mean the defaults.
+        let mut iter = params
+            .iter()
+            .map(|p| p.first().copied().unwrap_or(0) as usize);
+        let top = iter.next().filter(|&t| t != 0).unwrap_or(1);
+        let bottom = iter.next().filter(|&b| b != 0);
+        if bottom.is_some_and(|b| top >= b) {
+            // alacritty_terminal ignores invalid regions.
+            return;
+        }
+        self.state.lock().scroll_margins = match (top, bottom) {
+            (1, None) => None,
+            margins => Some(margins),
+        };
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        // RIS (ESC c) resets everything.
+        if intermediates.is_empty() && byte == b'c' {
+            *self.state.lock() = TrackedModes::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(bytes: &[u8]) -> TrackedModes {
+        let state = TrackedState::new();
+        StateTracker::new(state.clone()).advance(bytes);
+        state.get()
+    }
+
+    #[test]
+    fn test_full_screen_by_default() {
+        let modes = track(b"hello");
+        assert_eq!(modes.scroll_margins, None);
+        assert_eq!(modes.scroll_region(24), 0..24);
+    }
+
+    #[test]
+    fn test_decstbm_sets_region() {
+        let modes = track(b"\x1b[2;20r");
+        assert_eq!(modes.scroll_margins, Some((2, Some(20))));
+        assert_eq!(modes.scroll_region(24), 1..20);
+    }
+
+    #[test]
+    fn test_decstbm_split_across_reads() {
+        let state = TrackedState::new();
+        let mut tracker = StateTracker::new(state.clone());
+        tracker.advance(b"\x1b[5;");
+        tracker.advance(b"10r");
+        assert_eq!(state.get().scroll_region(24), 4..10);
+    }
+
+    #[test]
+    fn test_reset_and_invalid_regions() {
+        assert_eq!(track(b"\x1b[2;20r\x1b[r").scroll_margins, None);
+        assert_eq!(track(b"\x1b[2;20r\x1bc").scroll_margins, None);
+        // Invalid region leaves the previous one in place.
+        assert_eq!(
+            track(b"\x1b[2;20r\x1b[9;3r").scroll_margins,
+            Some((2, Some(20)))
+        );
+        // Private-mode variants are not DECSTBM.
+        assert_eq!(track(b"\x1b[?1r").scroll_margins, None);
+    }
+
+    #[test]
+    fn test_shift_in_and_out() {
+        assert_eq!(track(b"a").active_charset, CharsetIndex::G0);
+        assert_eq!(track(b"\x1b)0\x0eqqq").active_charset, CharsetIndex::G1);
+        assert_eq!(track(b"\x0eq\x0f").active_charset, CharsetIndex::G0);
+        assert_eq!(track(b"\x0e\x1bc").active_charset, CharsetIndex::G0);
+    }
+
+    #[test]
+    fn test_resize_resets_region() {
+        let state = TrackedState::new();
+        StateTracker::new(state.clone()).advance(b"\x1b[3;8r");
+        state.on_resize();
+        assert_eq!(state.get().scroll_margins, None);
+    }
+}
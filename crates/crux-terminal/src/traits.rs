@@ -192,6 +192,7 @@ pub mod mock {
                 cols: self.mock_size.cols,
                 rows: self.mock_size.rows,
                 damage: DamageState::default(),
+                image_placeholders: Vec::new(),
             }
         }
 
@@ -233,8 +234,7 @@ pub mod mock {
         {
             // Create a temporary term for testing
             use alacritty_terminal::term::test::TermSize;
-            let term_size =
-                TermSize::new(self.mock_size.cols as usize, self.mock_size.rows as usize);
+            let term_size = TermSize::new(self.mock_size.cols, self.mock_size.rows);
             let term = Term::new(Config::default(), &term_size, {
                 let (tx, _rx) = std::sync::mpsc::channel();
                 CruxEventListener::new(tx)
@@ -248,8 +248,7 @@ pub mod mock {
         {
             // Create a temporary term for testing
             use alacritty_terminal::term::test::TermSize;
-            let term_size =
-                TermSize::new(self.mock_size.cols as usize, self.mock_size.rows as usize);
+            let term_size = TermSize::new(self.mock_size.cols, self.mock_size.rows);
             let mut term = Term::new(Config::default(), &term_size, {
                 let (tx, _rx) = std::sync::mpsc::channel();
                 CruxEventListener::new(tx)
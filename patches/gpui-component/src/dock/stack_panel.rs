@@ -400,6 +400,11 @@ impl StackPanel {
         });
     }
 
+    /// Returns the child panels, in layout order.
+    pub fn panels(&self) -> &[Arc<dyn PanelView>] {
+        &self.panels
+    }
+
     /// Resize the panel at the given index to the specified size in pixels.
     pub fn resize_panel_at(&mut self, ix: usize, size: Pixels, window: &mut Window, cx: &mut Context<Self>) {
         self.state.update(cx, |state, cx| {
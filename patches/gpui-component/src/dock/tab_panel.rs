@@ -215,6 +215,11 @@ impl TabPanel {
         self.active_ix
     }
 
+    /// Returns the panels shown as tabs, in tab order.
+    pub fn panels(&self) -> &[Arc<dyn PanelView>] {
+        &self.panels
+    }
+
     /// Set the active tab index, cycling the tab bar and focusing the panel.
     pub fn set_active_ix(&mut self, ix: usize, window: &mut Window, cx: &mut Context<Self>) {
         if ix == self.active_ix {